@@ -37,7 +37,7 @@ fn mode_bench(c: &mut Criterion, name: &str, values: ArrayRef) {
     let mut accumulator = prepare_mode_accumulator();
     c.bench_function(name, |b| {
         b.iter(|| {
-            accumulator.update_batch(&[values.clone()]).unwrap();
+            accumulator.update_batch(std::slice::from_ref(&values)).unwrap();
             black_box(accumulator.evaluate().unwrap());
         });
     });
@@ -55,6 +55,10 @@ fn mode_benchmark(c: &mut Criterion) {
     // Case: 70% nulls
     let values = Arc::new(create_primitive_array::<Int32Type>(8192, 0.7)) as ArrayRef;
     mode_bench(c, "mode benchmark 70% nulls", values);
+
+    // Case: only nulls
+    let values = Arc::new(create_primitive_array::<Int32Type>(8192, 1.0)) as ArrayRef;
+    mode_bench(c, "mode benchmark all nulls", values);
 }
 
 criterion_group!(benches, mode_benchmark);
@@ -0,0 +1,54 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use datafusion::logical_expr::{ColumnarValue, ScalarUDFImpl};
+use datafusion::scalar::ScalarValue;
+use datafusion_functions_extra::fingerprint::FingerprintCombineFunction;
+use datafusion_functions_extra::fold_assign::FoldAssignFunction;
+
+fn scalar_bench(c: &mut Criterion, name: &str, function: &dyn ScalarUDFImpl, args: Vec<ScalarValue>) {
+    let args: Vec<_> = args.into_iter().map(ColumnarValue::Scalar).collect();
+    c.bench_function(name, |b| {
+        b.iter(|| black_box(function.invoke(&args).unwrap()));
+    });
+}
+
+/// Invocations on literals, as done while constant folding large generated queries
+fn scalar_literals_benchmark(c: &mut Criterion) {
+    scalar_bench(
+        c,
+        "fold_assign literals",
+        &FoldAssignFunction::new(),
+        vec![
+            ScalarValue::Utf8(Some("customer-42".to_string())),
+            ScalarValue::Int64(Some(10)),
+            ScalarValue::Int64(Some(7)),
+        ],
+    );
+
+    let fingerprint = ScalarValue::FixedSizeBinary(16, Some(vec![7; 16]));
+    scalar_bench(
+        c,
+        "fingerprint_combine literals",
+        &FingerprintCombineFunction::new(),
+        vec![fingerprint.clone(), fingerprint],
+    );
+}
+
+criterion_group!(benches, scalar_literals_benchmark);
+criterion_main!(benches);
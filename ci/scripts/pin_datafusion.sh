@@ -0,0 +1,57 @@
+#!/usr/bin/env bash
+#
+# Licensed to the Apache Software Foundation (ASF) under one
+# or more contributor license agreements.  See the NOTICE file
+# distributed with this work for additional information
+# regarding copyright ownership.  The ASF licenses this file
+# to you under the Apache License, Version 2.0 (the
+# "License"); you may not use this file except in compliance
+# with the License.  You may obtain a copy of the License at
+#
+#   http://www.apache.org/licenses/LICENSE-2.0
+#
+# Unless required by applicable law or agreed to in writing,
+# software distributed under the License is distributed on an
+# "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+# KIND, either express or implied.  See the License for the
+# specific language governing permissions and limitations
+# under the License.
+
+# Pins `datafusion` and every `datafusion-*` crate it depends on to the release $1, e.g. 42.0.0.
+#
+# `cargo update -p datafusion --precise` alone leaves the sub-crates at the latest patch release.
+# A sub-crate can only be downgraded once the crates requiring it are, so the crates are pinned
+# in passes until none is left behind.
+
+set -eo pipefail
+version="$1"
+if [ -z "$version" ]; then
+    echo "usage: $0 <datafusion version>" >&2
+    exit 1
+fi
+
+# The DataFusion crates in Cargo.lock that are not at $version
+unpinned() {
+    awk -v version="$version" '
+        /^name = / { name = $3; gsub(/"/, "", name) }
+        /^version = / && name ~ /^datafusion(-|$)/ && name != "datafusion-functions-extra" {
+            gsub(/"/, "", $3)
+            if ($3 != version) print name
+        }
+    ' Cargo.lock
+}
+
+[ -f Cargo.lock ] || cargo generate-lockfile --quiet
+for _ in 1 2 3 4 5 6 7 8; do
+    crates=$(unpinned)
+    if [ -z "$crates" ]; then
+        echo "Pinned the DataFusion crates to $version"
+        exit 0
+    fi
+    for crate in $crates; do
+        cargo update --quiet -p "$crate" --precise "$version" 2>/dev/null || true
+    done
+done
+
+echo "Could not pin to $version:" $(unpinned) >&2
+exit 1
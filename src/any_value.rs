@@ -0,0 +1,129 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::any::Any;
+use std::fmt::Debug;
+
+use arrow::datatypes::DataType;
+use datafusion::arrow;
+use datafusion::common::{exec_err, plan_err, Result, ScalarValue};
+use datafusion::functions_aggregate::first_last::first_value_udaf;
+use datafusion::logical_expr::expr::AggregateFunction;
+use datafusion::logical_expr::function::{AccumulatorArgs, AggregateFunctionSimplification};
+use datafusion::logical_expr::simplify::SimplifyInfo;
+use datafusion::logical_expr::{Accumulator, AggregateUDFImpl, Expr, Signature, Volatility};
+use datafusion::sql::sqlparser::ast::NullTreatment;
+
+make_udaf_expr_and_func!(
+    AnyValueFunction,
+    any_value,
+    "Returns any non-null value, or the first non-null value of the ordering if `deterministic` is true.",
+    any_value_udaf
+);
+
+/// The `AnyValueFunction` returns an arbitrary non-null value of a group, for columns known
+/// to be constant within the group or whose value doesn't matter.
+///
+/// - `any_value(x)` returns the first non-null value the aggregation sees, which can change
+///   with the partitioning of the input.
+/// - `any_value(x, true ORDER BY y)` returns the first non-null value ordered by `y`, so the
+///   result is reproducible as long as `y` has no ties. Passing `true` without an `ORDER BY`
+///   is a planning error.
+/// - The result is NULL if every value is NULL.
+pub struct AnyValueFunction {
+    signature: Signature,
+}
+
+impl Debug for AnyValueFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AnyValueFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for AnyValueFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AnyValueFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+        }
+    }
+}
+
+/// Rewrites `any_value` into `first_value(x) IGNORE NULLS`, keeping the ordering of the
+/// aggregate, after checking that a deterministic `any_value` is ordered
+fn simplify_to_first_value(mut aggr_func: AggregateFunction, _info: &dyn SimplifyInfo) -> Result<Expr> {
+    if aggr_func.args.len() == 2 {
+        let deterministic = match aggr_func.args.remove(1) {
+            Expr::Literal(ScalarValue::Boolean(deterministic)) => deterministic.unwrap_or(false),
+            arg => return plan_err!("any_value expects a literal boolean deterministic flag, got {arg}"),
+        };
+        let ordered = aggr_func.order_by.as_ref().is_some_and(|order_by| !order_by.is_empty());
+        if deterministic && !ordered {
+            return plan_err!("any_value(x, true) requires an ORDER BY to pick the first value of");
+        }
+    }
+    Ok(Expr::AggregateFunction(AggregateFunction::new_udf(
+        first_value_udaf(),
+        aggr_func.args,
+        aggr_func.distinct,
+        aggr_func.filter,
+        aggr_func.order_by,
+        Some(NullTreatment::IgnoreNulls),
+    )))
+}
+
+impl AggregateUDFImpl for AnyValueFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "any_value"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        match arg_types {
+            [value] => Ok(vec![value.clone()]),
+            [value, DataType::Boolean | DataType::Null] => Ok(vec![value.clone(), DataType::Boolean]),
+            [_, flag] => plan_err!("any_value expects a boolean deterministic flag, got {flag}"),
+            _ => plan_err!("any_value expects a value and an optional deterministic flag"),
+        }
+    }
+
+    fn return_type(&self, arg_types: &[DataType]) -> Result<DataType> {
+        Ok(arg_types[0].clone())
+    }
+
+    fn accumulator(&self, _acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        exec_err!("should not reach here")
+    }
+
+    fn simplify(&self) -> Option<AggregateFunctionSimplification> {
+        Some(Box::new(simplify_to_first_value))
+    }
+}
@@ -0,0 +1,318 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::any::Any;
+use std::fmt::Debug;
+
+use arrow::array::{Array, ArrayRef};
+use datafusion::arrow;
+use datafusion::arrow::datatypes::{DataType, Field};
+use datafusion::common::cast::as_binary_array;
+use datafusion::common::{plan_err, Result, ScalarValue};
+use datafusion::logical_expr::function::{AccumulatorArgs, StateFieldsArgs};
+use datafusion::logical_expr::{Accumulator, AggregateUDFImpl, Signature, Volatility};
+
+use crate::common::args::literal_arg;
+use crate::fingerprint::{fingerprint_value_type, hash_rows};
+use crate::sketches::hyperloglog::{HyperLogLog, DEFAULT_PRECISION, PRECISION_RANGE};
+
+make_udaf_expr_and_func!(
+    ApproxCountDistinctHllFunction,
+    approx_count_distinct_hll,
+    "Estimates the number of distinct values with a HyperLogLog sketch of the given precision.",
+    approx_count_distinct_hll_udaf
+);
+
+make_udaf_expr_and_func!(
+    ApproxCountDistinctHllSketchFunction,
+    approx_count_distinct_hll_sketch,
+    "Returns the serialized HyperLogLog sketch estimating the number of distinct values.",
+    approx_count_distinct_hll_sketch_udaf
+);
+
+/// What [`HllAccumulator`] returns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HllOutput {
+    /// `approx_count_distinct_hll`: the estimated number of distinct values
+    Count,
+    /// `approx_count_distinct_hll_sketch`: the serialized sketch
+    Sketch,
+}
+
+impl HllOutput {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Count => "approx_count_distinct_hll",
+            Self::Sketch => "approx_count_distinct_hll_sketch",
+        }
+    }
+
+    fn return_type(&self) -> DataType {
+        match self {
+            Self::Count => DataType::UInt64,
+            Self::Sketch => DataType::Binary,
+        }
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        match arg_types {
+            [value] => Ok(vec![fingerprint_value_type(value)]),
+            [value, _] => Ok(vec![fingerprint_value_type(value), DataType::Int64]),
+            _ => plan_err!("{} expects a value and an optional precision", self.name()),
+        }
+    }
+
+    fn accumulator(&self, acc_args: &AccumulatorArgs) -> Result<HllAccumulator> {
+        let Some(expr) = acc_args.exprs.get(1) else {
+            return Ok(HllAccumulator::new(*self, DEFAULT_PRECISION));
+        };
+        let precision = literal_arg(expr, self.name(), "precision")?;
+        let valid = match precision {
+            ScalarValue::Int64(Some(precision)) => u8::try_from(precision)
+                .ok()
+                .filter(|precision| PRECISION_RANGE.contains(precision)),
+            _ => None,
+        };
+        let Some(precision) = valid else {
+            return plan_err!(
+                "{} expects a precision between {} and {}, got {precision}",
+                self.name(),
+                PRECISION_RANGE.start(),
+                PRECISION_RANGE.end()
+            );
+        };
+        Ok(HllAccumulator::new(*self, precision))
+    }
+}
+
+/// The `ApproxCountDistinctHllFunction` estimates the number of distinct non-null values with a
+/// HyperLogLog++ sketch.
+///
+/// - `approx_count_distinct_hll(x, precision)` keeps `2^precision` registers, for a standard
+///   error of about `1.04 / sqrt(2^precision)`: from 26% at precision 4 to 0.2% at precision
+///   18, taking 16 bytes to 256 KiB per group. The default precision is 14, for about 0.8%.
+/// - Small cardinalities are estimated almost exactly, from a sparse sketch taking a few bytes
+///   per distinct value.
+/// - The result is 0 if there are no non-null values.
+pub struct ApproxCountDistinctHllFunction {
+    signature: Signature,
+}
+
+impl Debug for ApproxCountDistinctHllFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ApproxCountDistinctHllFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for ApproxCountDistinctHllFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ApproxCountDistinctHllFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+        }
+    }
+}
+
+impl AggregateUDFImpl for ApproxCountDistinctHllFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        HllOutput::Count.name()
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        HllOutput::Count.coerce_types(arg_types)
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(HllOutput::Count.return_type())
+    }
+
+    fn is_nullable(&self) -> bool {
+        false
+    }
+
+    fn state_fields(&self, _args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(vec![Field::new("sketch", DataType::Binary, true)])
+    }
+
+    fn accumulator(&self, acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        Ok(Box::new(HllOutput::Count.accumulator(&acc_args)?))
+    }
+
+    fn default_value(&self, _data_type: &DataType) -> Result<ScalarValue> {
+        Ok(ScalarValue::UInt64(Some(0)))
+    }
+}
+
+/// The `ApproxCountDistinctHllSketchFunction` returns the sketch `approx_count_distinct_hll`
+/// estimates its result from, serialized in the versioned format of [`crate::sketches::format`].
+///
+/// - Sketches can be stored, e.g. per day, and merged later with [`HyperLogLog::merge`] to
+///   estimate the distinct values of their union, as long as they have the same precision.
+/// - Values are hashed with Spark's Murmur3, so sketches written by different releases merge.
+pub struct ApproxCountDistinctHllSketchFunction {
+    signature: Signature,
+}
+
+impl Debug for ApproxCountDistinctHllSketchFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ApproxCountDistinctHllSketchFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for ApproxCountDistinctHllSketchFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ApproxCountDistinctHllSketchFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+        }
+    }
+}
+
+impl AggregateUDFImpl for ApproxCountDistinctHllSketchFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        HllOutput::Sketch.name()
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        HllOutput::Sketch.coerce_types(arg_types)
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(HllOutput::Sketch.return_type())
+    }
+
+    fn state_fields(&self, _args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(vec![Field::new("sketch", DataType::Binary, true)])
+    }
+
+    fn accumulator(&self, acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        Ok(Box::new(HllOutput::Sketch.accumulator(&acc_args)?))
+    }
+}
+
+/// Accumulator for [`ApproxCountDistinctHllFunction`] and
+/// [`ApproxCountDistinctHllSketchFunction`], adding the 64-bit hashes of the values to a
+/// [`HyperLogLog`] sketch.
+#[derive(Debug)]
+pub struct HllAccumulator {
+    sketch: HyperLogLog,
+    output: HllOutput,
+}
+
+impl HllAccumulator {
+    pub fn new(output: HllOutput, precision: u8) -> Self {
+        Self {
+            sketch: HyperLogLog::new(precision),
+            output,
+        }
+    }
+}
+
+impl Accumulator for HllAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        let values = &values[0];
+        for (row, hash) in hash_rows(std::slice::from_ref(values), 2)?.into_iter().enumerate() {
+            if values.is_valid(row) {
+                self.sketch.add_hash(hash as u64);
+            }
+        }
+        Ok(())
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        for bytes in as_binary_array(&states[0])?.iter().flatten() {
+            self.sketch.merge(&HyperLogLog::from_bytes(bytes)?)?;
+        }
+        Ok(())
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        Ok(vec![ScalarValue::Binary(Some(self.sketch.to_bytes()))])
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        Ok(match self.output {
+            HllOutput::Count => ScalarValue::UInt64(Some(self.sketch.count())),
+            HllOutput::Sketch => ScalarValue::Binary(Some(self.sketch.to_bytes())),
+        })
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self) + self.sketch.size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow::array::Int64Array;
+
+    use super::*;
+
+    #[test]
+    fn test_sketch_merges_into_count() -> Result<()> {
+        let mut sketches = vec![];
+        for values in [0..30_000, 20_000..50_000] {
+            let mut acc = HllAccumulator::new(HllOutput::Sketch, 12);
+            acc.update_batch(&[Arc::new(Int64Array::from_iter(values.map(Some).chain([None]))) as ArrayRef])?;
+            sketches.push(acc.evaluate()?);
+        }
+        let mut acc = HllAccumulator::new(HllOutput::Count, 12);
+        acc.merge_batch(&[ScalarValue::iter_to_array(sketches)?])?;
+        let ScalarValue::UInt64(Some(count)) = acc.evaluate()? else {
+            unreachable!()
+        };
+        // 1.04 / sqrt(4096) is a standard error of 1.6%
+        assert!((count as f64 - 50_000.0).abs() < 50_000.0 * 0.05, "got {count}");
+
+        let mut other_precision = HllAccumulator::new(HllOutput::Count, 13);
+        let sketch = acc.state()?[0].to_array()?;
+        assert!(other_precision.merge_batch(&[sketch]).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,489 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::any::Any;
+use std::fmt::Debug;
+use std::ops::RangeInclusive;
+use std::sync::Arc;
+
+use arrow::array::{Array, ArrayRef, Float64Array};
+use datafusion::arrow;
+use datafusion::arrow::datatypes::{DataType, Field};
+use datafusion::common::cast::{as_binary_array, as_float64_array};
+use datafusion::common::{plan_err, Result, ScalarValue};
+use datafusion::logical_expr::function::{AccumulatorArgs, StateFieldsArgs};
+use datafusion::logical_expr::{Accumulator, AggregateUDFImpl, Signature, Volatility};
+use datafusion::physical_expr::PhysicalExpr;
+
+use crate::common::args::{literal_arg, literal_f64_arg};
+use crate::common::nulls::all_null_or_filtered;
+use crate::compat::{arg_type, single_row_list};
+use crate::sketches::tdigest::{TDigest, DEFAULT_COMPRESSION};
+
+make_udaf_expr_and_func!(
+    ApproxPercentileFunction,
+    approx_percentile,
+    x q,
+    "Estimates the q-th percentile of the values, or of the values of serialized t-digests, with a t-digest.",
+    approx_percentile_udaf
+);
+
+make_udaf_expr_and_func!(
+    ApproxQuantilesFunction,
+    approx_quantiles,
+    x qs,
+    "Estimates the quantiles in the list qs of the values, or of the values of serialized t-digests, with a single t-digest.",
+    approx_quantiles_udaf
+);
+
+make_udaf_expr_and_func!(
+    TDigestAggFunction,
+    tdigest_agg,
+    "Returns a serialized t-digest of the values, or the merge of serialized t-digests.",
+    tdigest_agg_udaf
+);
+
+/// Smallest and largest compression of the digests
+const COMPRESSION_RANGE: RangeInclusive<f64> = 10.0..=10_000.0;
+
+/// What [`TDigestAccumulator`] returns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TDigestOutput {
+    /// `approx_percentile`: the estimated percentile
+    Percentile,
+    /// `approx_quantiles`: the list of estimated quantiles
+    Quantiles,
+    /// `tdigest_agg`: the serialized digest
+    Digest,
+}
+
+impl TDigestOutput {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Percentile => "approx_percentile",
+            Self::Quantiles => "approx_quantiles",
+            Self::Digest => "tdigest_agg",
+        }
+    }
+
+    fn return_type(&self) -> DataType {
+        match self {
+            Self::Percentile => DataType::Float64,
+            Self::Quantiles => DataType::new_list(DataType::Float64, true),
+            Self::Digest => DataType::Binary,
+        }
+    }
+
+    /// Index of the compression argument
+    fn compression_index(&self) -> usize {
+        match self {
+            Self::Percentile | Self::Quantiles => 2,
+            Self::Digest => 1,
+        }
+    }
+
+    /// Serialized digests are merged, other values are added to a digest as DOUBLE
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        let max_args = self.compression_index() + 1;
+        let Some(value_type) = arg_types
+            .first()
+            .filter(|_| (max_args - 1..=max_args).contains(&arg_types.len()))
+        else {
+            return match self {
+                Self::Percentile => plan_err!("approx_percentile expects a value, q and optional compression"),
+                Self::Quantiles => plan_err!("approx_quantiles expects a value, a list of q and optional compression"),
+                Self::Digest => plan_err!("tdigest_agg expects a value and optional compression"),
+            };
+        };
+        let value_type = match value_type {
+            DataType::Binary | DataType::LargeBinary | DataType::BinaryView => DataType::Binary,
+            value_type if value_type.is_numeric() || value_type == &DataType::Null => DataType::Float64,
+            value_type => return plan_err!("{} expects numbers or t-digests, got {value_type}", self.name()),
+        };
+        let qs_type = match (self, arg_types.get(1)) {
+            (Self::Quantiles, Some(DataType::List(_) | DataType::LargeList(_) | DataType::FixedSizeList(_, _))) => {
+                self.return_type()
+            }
+            (Self::Quantiles, Some(qs_type)) => {
+                return plan_err!("approx_quantiles expects a list of q, got {qs_type}")
+            }
+            _ => DataType::Float64,
+        };
+        Ok(std::iter::once(value_type)
+            .chain(arg_types[1..].iter().enumerate().map(|(i, _)| match i {
+                0 => qs_type.clone(),
+                _ => DataType::Float64,
+            }))
+            .collect())
+    }
+
+    fn accumulator(&self, acc_args: &AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        let compression = match acc_args.exprs.get(self.compression_index()) {
+            Some(expr) => literal_f64_arg(expr, self.name(), "compression")?,
+            None => DEFAULT_COMPRESSION,
+        };
+        if !COMPRESSION_RANGE.contains(&compression) {
+            return plan_err!(
+                "{} expects a compression between {} and {}, got {compression}",
+                self.name(),
+                COMPRESSION_RANGE.start(),
+                COMPRESSION_RANGE.end()
+            );
+        }
+        let qs = match self {
+            Self::Percentile => vec![literal_f64_arg(&acc_args.exprs[1], self.name(), "q")?],
+            Self::Quantiles => literal_qs_arg(&acc_args.exprs[1])?,
+            Self::Digest => vec![],
+        };
+        if let Some(q) = qs.iter().find(|q| !(0.0..=1.0).contains(*q)) {
+            return plan_err!("{} expects a q between 0 and 1, got {q}", self.name());
+        }
+        let merges_digests = arg_type(acc_args, 0)? == DataType::Binary;
+        Ok(Box::new(TDigestAccumulator::new(
+            compression,
+            *self,
+            qs,
+            merges_digests,
+        )))
+    }
+}
+
+/// The non-empty constant list of quantiles of `approx_quantiles`, without NULLs
+fn literal_qs_arg(expr: &Arc<dyn PhysicalExpr>) -> Result<Vec<f64>> {
+    let ScalarValue::List(qs) = literal_arg(expr, "approx_quantiles", "qs")? else {
+        return plan_err!("approx_quantiles expects a list of q");
+    };
+    if qs.is_null(0) || qs.value_length(0) == 0 {
+        return plan_err!("approx_quantiles expects a non-empty list of q");
+    }
+    let qs = qs.value(0);
+    let qs = as_float64_array(&qs)?;
+    if qs.null_count() > 0 {
+        return plan_err!("approx_quantiles expects non-null q");
+    }
+    Ok(qs.values().to_vec())
+}
+
+/// The `ApproxPercentileFunction` estimates a percentile of the non-null values with a
+/// [`TDigest`], in memory bounded by the compression rather than the number of values.
+///
+/// - `approx_percentile(x, q, compression)` estimates the `q`-th percentile, with `q` between 0
+///   and 1. The result interpolates between values, as with `percentile_cont`.
+/// - The compression, between 10 and 10000 and 100 by default, bounds the digest to about twice
+///   as many centroids. The error is smallest near the tails, well under 1% of the rank at
+///   the default compression.
+/// - `x` can also be a column of digests of [`TDigestAggFunction`], e.g. stored per day, which
+///   are merged to estimate the percentile of all their values.
+/// - The result is NULL if there are no non-null values.
+pub struct ApproxPercentileFunction {
+    signature: Signature,
+}
+
+impl Debug for ApproxPercentileFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ApproxPercentileFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for ApproxPercentileFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ApproxPercentileFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+        }
+    }
+}
+
+impl AggregateUDFImpl for ApproxPercentileFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        TDigestOutput::Percentile.name()
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        TDigestOutput::Percentile.coerce_types(arg_types)
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(TDigestOutput::Percentile.return_type())
+    }
+
+    fn state_fields(&self, _args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(vec![Field::new("digest", DataType::Binary, true)])
+    }
+
+    fn accumulator(&self, acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        TDigestOutput::Percentile.accumulator(&acc_args)
+    }
+}
+
+/// The `ApproxQuantilesFunction` estimates several percentiles of the non-null values from a
+/// single [`TDigest`], as [`ApproxPercentileFunction`] does for one.
+///
+/// - `approx_quantiles(x, [q1, q2, ...], compression)` returns the list of the estimated
+///   quantiles, in the order of the constant list of `q`.
+/// - `x` can also be a column of digests of [`TDigestAggFunction`].
+/// - The result is NULL if there are no non-null values.
+pub struct ApproxQuantilesFunction {
+    signature: Signature,
+}
+
+impl Debug for ApproxQuantilesFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ApproxQuantilesFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for ApproxQuantilesFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ApproxQuantilesFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+        }
+    }
+}
+
+impl AggregateUDFImpl for ApproxQuantilesFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        TDigestOutput::Quantiles.name()
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        TDigestOutput::Quantiles.coerce_types(arg_types)
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(TDigestOutput::Quantiles.return_type())
+    }
+
+    fn state_fields(&self, _args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(vec![Field::new("digest", DataType::Binary, true)])
+    }
+
+    fn accumulator(&self, acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        TDigestOutput::Quantiles.accumulator(&acc_args)
+    }
+}
+
+/// The `TDigestAggFunction` returns the [`TDigest`] `approx_percentile` estimates its result
+/// from, serialized in the versioned format of [`crate::sketches::format`].
+///
+/// - Digests can be stored and merged later by `approx_percentile` or `tdigest_agg` itself,
+///   e.g. to roll daily digests up into monthly ones.
+/// - `tdigest_agg(x, compression)` takes the compression of `approx_percentile`.
+pub struct TDigestAggFunction {
+    signature: Signature,
+}
+
+impl Debug for TDigestAggFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TDigestAggFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for TDigestAggFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TDigestAggFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+        }
+    }
+}
+
+impl AggregateUDFImpl for TDigestAggFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        TDigestOutput::Digest.name()
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        TDigestOutput::Digest.coerce_types(arg_types)
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(TDigestOutput::Digest.return_type())
+    }
+
+    fn is_nullable(&self) -> bool {
+        false
+    }
+
+    fn state_fields(&self, _args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(vec![Field::new("digest", DataType::Binary, true)])
+    }
+
+    fn accumulator(&self, acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        TDigestOutput::Digest.accumulator(&acc_args)
+    }
+
+    fn default_value(&self, _data_type: &DataType) -> Result<ScalarValue> {
+        Ok(ScalarValue::Binary(Some(TDigest::default().to_bytes())))
+    }
+}
+
+/// Accumulator for [`ApproxPercentileFunction`], [`ApproxQuantilesFunction`] and
+/// [`TDigestAggFunction`], adding the values,
+/// or merging the digests, into a [`TDigest`].
+#[derive(Debug)]
+pub struct TDigestAccumulator {
+    digest: TDigest,
+    output: TDigestOutput,
+    /// The quantiles to estimate, a single one for [`TDigestOutput::Percentile`]
+    qs: Vec<f64>,
+    merges_digests: bool,
+}
+
+impl TDigestAccumulator {
+    pub fn new(compression: f64, output: TDigestOutput, qs: Vec<f64>, merges_digests: bool) -> Self {
+        Self {
+            digest: TDigest::new(compression),
+            output,
+            qs,
+            merges_digests,
+        }
+    }
+
+    fn merge_digests(&mut self, digests: &ArrayRef) -> Result<()> {
+        for bytes in as_binary_array(digests)?.iter().flatten() {
+            self.digest.merge(&TDigest::from_bytes(bytes)?);
+        }
+        Ok(())
+    }
+}
+
+impl Accumulator for TDigestAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        if all_null_or_filtered(&values[0], None) {
+            return Ok(());
+        }
+        if self.merges_digests {
+            return self.merge_digests(&values[0]);
+        }
+        for value in as_float64_array(&values[0])?.iter().flatten() {
+            self.digest.add(value);
+        }
+        Ok(())
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        self.merge_digests(&states[0])
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        Ok(vec![ScalarValue::Binary(Some(self.digest.to_bytes()))])
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        match self.output {
+            TDigestOutput::Percentile => Ok(ScalarValue::Float64(self.digest.quantile(self.qs[0]))),
+            TDigestOutput::Quantiles if self.digest.is_empty() => ScalarValue::try_from(self.output.return_type()),
+            TDigestOutput::Quantiles => {
+                let estimates: Float64Array = self.qs.iter().map(|q| self.digest.quantile(*q)).collect();
+                Ok(ScalarValue::List(Arc::new(single_row_list(Arc::new(estimates)))))
+            }
+            TDigestOutput::Digest => Ok(ScalarValue::Binary(Some(self.digest.to_bytes()))),
+        }
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self) + self.digest.size() + self.qs.capacity() * std::mem::size_of::<f64>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow::array::{BinaryArray, Float64Array};
+
+    use super::*;
+
+    fn digest(values: impl Iterator<Item = f64>) -> Result<Option<Vec<u8>>> {
+        let mut acc = TDigestAccumulator::new(DEFAULT_COMPRESSION, TDigestOutput::Digest, vec![], false);
+        acc.update_batch(&[Arc::new(Float64Array::from_iter_values(values))])?;
+        match acc.evaluate()? {
+            ScalarValue::Binary(digest) => Ok(digest),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_percentile_of_merged_digests() -> Result<()> {
+        // Two halves of 0..10000, stored separately
+        let digests: ArrayRef = Arc::new(BinaryArray::from_iter(vec![
+            digest((0..5000).map(f64::from))?,
+            None,
+            digest((5000..10_000).map(f64::from))?,
+        ]));
+        for (q, exact) in [(0.5, 4999.5), (0.99, 9899.01), (0.0, 0.0), (1.0, 9999.0)] {
+            let mut acc = TDigestAccumulator::new(DEFAULT_COMPRESSION, TDigestOutput::Percentile, vec![q], true);
+            acc.update_batch(&[Arc::clone(&digests)])?;
+            let ScalarValue::Float64(Some(estimate)) = acc.evaluate()? else {
+                panic!("expected a non-null percentile");
+            };
+            // Within 0.5% of the rank
+            assert!(
+                (estimate - exact).abs() <= 50.0,
+                "q {q}: exact {exact}, estimated {estimate}"
+            );
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,202 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::any::Any;
+use std::fmt::Debug;
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use arrow::array::{Array, ArrayRef, GenericListArray, OffsetSizeTrait};
+use arrow::buffer::OffsetBuffer;
+use arrow::compute::concat;
+use arrow::datatypes::{DataType, Field, FieldRef};
+use datafusion::arrow;
+use datafusion::common::cast::as_generic_list_array;
+use datafusion::common::{Result, ScalarValue};
+use datafusion::logical_expr::function::{AccumulatorArgs, StateFieldsArgs};
+use datafusion::logical_expr::{Accumulator, AggregateUDFImpl, Signature, Volatility};
+
+use crate::common::output_width::OutputWidth;
+
+make_udaf_expr_and_func!(
+    ArrayAggExtFunction,
+    array_agg_ext,
+    x,
+    "Collects the values into a list.",
+    array_agg_ext_udaf
+);
+
+/// The `ArrayAggExtFunction` collects the values of a group, including NULLs, into a list,
+/// like `array_agg`.
+///
+/// - Returns a `List`, or a `LargeList` if configured with [`OutputWidth::Large`].
+/// - The result is NULL if there are no rows.
+pub struct ArrayAggExtFunction {
+    signature: Signature,
+    output_width: OutputWidth,
+}
+
+impl Debug for ArrayAggExtFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ArrayAggExtFunction")
+            .field("signature", &self.signature)
+            .field("output_width", &self.output_width)
+            .finish()
+    }
+}
+
+impl Default for ArrayAggExtFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ArrayAggExtFunction {
+    pub fn new() -> Self {
+        Self::new_with_output_width(OutputWidth::default())
+    }
+
+    pub fn new_with_output_width(output_width: OutputWidth) -> Self {
+        Self {
+            signature: Signature::any(1, Volatility::Immutable),
+            output_width,
+        }
+    }
+}
+
+impl AggregateUDFImpl for ArrayAggExtFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "array_agg_ext"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, arg_types: &[DataType]) -> Result<DataType> {
+        Ok(self.output_width.list_type(item_field(&arg_types[0])))
+    }
+
+    fn state_fields(&self, args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(vec![Field::new(
+            "values",
+            self.output_width.list_type(item_field(&args.input_types[0])),
+            true,
+        )])
+    }
+
+    fn accumulator(&self, acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        let item_type = crate::compat::arg_type(&acc_args, 0)?;
+        Ok(match self.output_width {
+            OutputWidth::Regular => Box::new(ArrayAggExtAccumulator::<i32>::new(item_type)),
+            OutputWidth::Large => Box::new(ArrayAggExtAccumulator::<i64>::new(item_type)),
+        })
+    }
+}
+
+fn item_field(item_type: &DataType) -> FieldRef {
+    Arc::new(Field::new("item", item_type.clone(), true))
+}
+
+/// Accumulator collecting the values of a group into a list with offsets `O`.
+#[derive(Debug)]
+pub struct ArrayAggExtAccumulator<O: OffsetSizeTrait> {
+    batches: Vec<ArrayRef>,
+    item_type: DataType,
+    phantom: PhantomData<O>,
+}
+
+impl<O: OffsetSizeTrait> ArrayAggExtAccumulator<O> {
+    pub fn new(item_type: DataType) -> Self {
+        Self {
+            batches: vec![],
+            item_type,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<O: OffsetSizeTrait> Accumulator for ArrayAggExtAccumulator<O> {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        if !values[0].is_empty() {
+            self.batches.push(Arc::clone(&values[0]));
+        }
+        Ok(())
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        let lists = as_generic_list_array::<O>(&states[0])?;
+        self.batches
+            .extend(lists.iter().flatten().filter(|values| !values.is_empty()));
+        Ok(())
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        Ok(vec![self.evaluate()?])
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        let width = OutputWidth::of::<O>();
+        let field = item_field(&self.item_type);
+        if self.batches.is_empty() {
+            return ScalarValue::try_from(width.list_type(field));
+        }
+        let len = self.batches.iter().map(|values| values.len()).sum();
+        width.check_len(len, "array_agg_ext")?;
+        let batches: Vec<&dyn Array> = self.batches.iter().map(|values| values.as_ref()).collect();
+        let values = concat(&batches)?;
+        let list = GenericListArray::<O>::try_new(field, OffsetBuffer::from_lengths([len]), values, None)?;
+        ScalarValue::try_from_array(&list, 0)
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self)
+            + self.batches.capacity() * std::mem::size_of::<ArrayRef>()
+            + self
+                .batches
+                .iter()
+                .map(|values| values.get_array_memory_size())
+                .sum::<usize>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::array::{Int64Array, LargeListArray};
+    use arrow::datatypes::Int64Type;
+
+    use super::*;
+
+    #[test]
+    fn test_array_agg_ext_merge() -> Result<()> {
+        let mut partial = ArrayAggExtAccumulator::<i64>::new(DataType::Int64);
+        partial.update_batch(&[Arc::new(Int64Array::from(vec![Some(1), None]))])?;
+        let state = partial.state()?[0].to_array()?;
+
+        let mut merged = ArrayAggExtAccumulator::<i64>::new(DataType::Int64);
+        merged.update_batch(&[Arc::new(Int64Array::from(vec![3]))])?;
+        merged.merge_batch(&[state])?;
+
+        let expected = LargeListArray::from_iter_primitive::<Int64Type, _, _>(vec![Some(vec![Some(3), Some(1), None])]);
+        assert_eq!(merged.evaluate()?, ScalarValue::LargeList(Arc::new(expected)));
+        Ok(())
+    }
+}
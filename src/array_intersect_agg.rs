@@ -0,0 +1,249 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::any::Any;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use arrow::array::{Array, ArrayRef};
+use arrow::datatypes::{DataType, Field};
+use arrow::row::{RowConverter, SortField};
+use datafusion::arrow;
+use datafusion::common::cast::as_list_array;
+use datafusion::common::{plan_err, Result, ScalarValue};
+use datafusion::logical_expr::function::{AccumulatorArgs, StateFieldsArgs};
+use datafusion::logical_expr::{Accumulator, AggregateUDFImpl, Signature, Volatility};
+
+use crate::compat::single_row_list;
+
+make_udaf_expr_and_func!(
+    ArrayIntersectAggFunction,
+    array_intersect_agg,
+    list,
+    "Intersects the lists into a list of the distinct non-null elements present in all of them.",
+    array_intersect_agg_udaf
+);
+
+/// The `ArrayIntersectAggFunction` intersects the lists of a group into a list of the distinct
+/// non-null elements present in every one of them, e.g. the tags shared by all the posts of a
+/// user with `array_intersect_agg(tags)`.
+///
+/// - Every element of the intersection is counted by the number of lists it was found in, and
+///   the elements missing from a list are dropped, so only the intersection is kept in memory.
+/// - The elements are listed in the order they were first aggregated in, which depends on the
+///   partitioning of the input. `List`, `LargeList` and `FixedSizeList` inputs are supported.
+/// - NULL lists and elements are ignored. The result is NULL if there are no non-null lists,
+///   and an empty list if they have no elements in common.
+pub struct ArrayIntersectAggFunction {
+    signature: Signature,
+}
+
+impl Debug for ArrayIntersectAggFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ArrayIntersectAggFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for ArrayIntersectAggFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ArrayIntersectAggFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+        }
+    }
+}
+
+impl AggregateUDFImpl for ArrayIntersectAggFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "array_intersect_agg"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        let element_type = match arg_types {
+            [DataType::Null] => DataType::Int64,
+            [DataType::List(field) | DataType::LargeList(field) | DataType::FixedSizeList(field, _)] => {
+                match field.data_type() {
+                    DataType::Dictionary(_, value_type) => value_type.as_ref().clone(),
+                    DataType::Null => DataType::Int64,
+                    element_type => element_type.clone(),
+                }
+            }
+            [arg_type] => return plan_err!("array_intersect_agg expects a list, got {arg_type}"),
+            _ => return plan_err!("array_intersect_agg expects 1 argument, got {}", arg_types.len()),
+        };
+        if !RowConverter::supports_fields(&[SortField::new(element_type.clone())]) {
+            return plan_err!("array_intersect_agg doesn't support elements of type {element_type}");
+        }
+        Ok(vec![DataType::new_list(element_type, true)])
+    }
+
+    fn return_type(&self, arg_types: &[DataType]) -> Result<DataType> {
+        Ok(arg_types[0].clone())
+    }
+
+    fn state_fields(&self, args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(vec![Field::new("intersection", args.input_types[0].clone(), true)])
+    }
+
+    fn accumulator(&self, acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        let DataType::List(field) = crate::compat::arg_type(&acc_args, 0)? else {
+            return plan_err!("array_intersect_agg expects a list");
+        };
+        Ok(Box::new(ArrayIntersectAggAccumulator::try_new(field.data_type())?))
+    }
+}
+
+/// Accumulator for [`ArrayIntersectAggFunction`].
+///
+/// The state is the intersection itself, so merging it intersects it as one more list.
+#[derive(Debug)]
+pub struct ArrayIntersectAggAccumulator {
+    element_type: DataType,
+    converter: RowConverter,
+    /// The number of lists each element of the intersection was found in, by the element in
+    /// the row format
+    counts: HashMap<Box<[u8]>, usize>,
+    /// The elements of the intersection in the order they were first aggregated in
+    elements: Vec<Box<[u8]>>,
+    /// The number of non-null lists intersected
+    lists: usize,
+}
+
+impl ArrayIntersectAggAccumulator {
+    pub fn try_new(element_type: &DataType) -> Result<Self> {
+        Ok(Self {
+            element_type: element_type.clone(),
+            converter: RowConverter::new(vec![SortField::new(element_type.clone())])?,
+            counts: HashMap::new(),
+            elements: vec![],
+            lists: 0,
+        })
+    }
+
+    /// Intersects the non-null lists of `lists` with the intersection
+    fn intersect(&mut self, lists: &ArrayRef) -> Result<()> {
+        for list in as_list_array(lists)?.iter().flatten() {
+            if self.lists > 0 && self.counts.is_empty() {
+                // Nothing left to intersect
+                self.lists += 1;
+                continue;
+            }
+            let rows = self.converter.convert_columns(&[Arc::clone(&list)])?;
+            for (index, row) in rows.iter().enumerate() {
+                if list.is_null(index) {
+                    continue;
+                }
+                match self.counts.get_mut(row.as_ref()) {
+                    // Counted once per list, as the count no longer matches after
+                    Some(count) if *count == self.lists => *count += 1,
+                    Some(_) => {}
+                    None if self.lists == 0 => {
+                        self.counts.insert(row.as_ref().into(), 1);
+                        self.elements.push(row.as_ref().into());
+                    }
+                    None => {}
+                }
+            }
+            self.lists += 1;
+            let lists = self.lists;
+            self.counts.retain(|_, count| *count == lists);
+            if self.elements.len() > self.counts.len() {
+                self.elements.retain(|row| self.counts.contains_key(row));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Accumulator for ArrayIntersectAggAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        self.intersect(&values[0])
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        self.intersect(&states[0])
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        Ok(vec![self.evaluate()?])
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        if self.lists == 0 {
+            return ScalarValue::try_from(DataType::new_list(self.element_type.clone(), true));
+        }
+        let parser = self.converter.parser();
+        let mut columns = self
+            .converter
+            .convert_rows(self.elements.iter().map(|row| parser.parse(row)))?;
+        Ok(ScalarValue::List(Arc::new(single_row_list(columns.remove(0)))))
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self)
+            + self.converter.size()
+            + self.counts.capacity() * std::mem::size_of::<(Box<[u8]>, usize)>()
+            + self.elements.capacity() * std::mem::size_of::<Box<[u8]>>()
+            + 2 * self.elements.iter().map(|row| row.len()).sum::<usize>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{Int32Array, ListArray};
+    use arrow::datatypes::Int32Type;
+
+    #[test]
+    fn test_intersect_with_duplicates() -> Result<()> {
+        let mut acc = ArrayIntersectAggAccumulator::try_new(&DataType::Int32)?;
+        let lists: ArrayRef = Arc::new(ListArray::from_iter_primitive::<Int32Type, _, _>(vec![
+            Some(vec![Some(1), Some(2), Some(3), None]),
+            None,
+            // 1 twice doesn't make up for the missing 2
+            Some(vec![Some(3), Some(1), Some(1)]),
+        ]));
+        acc.update_batch(&[lists])?;
+        let lists: ArrayRef = Arc::new(ListArray::from_iter_primitive::<Int32Type, _, _>(vec![Some(vec![
+            Some(3),
+            Some(1),
+            Some(2),
+        ])]));
+        acc.update_batch(&[lists])?;
+        let ScalarValue::List(list) = acc.evaluate()? else {
+            panic!("expected a list");
+        };
+        assert_eq!(list.value(0).as_ref(), &Int32Array::from(vec![1, 3]));
+        Ok(())
+    }
+}
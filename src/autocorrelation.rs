@@ -0,0 +1,219 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+use std::any::Any;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use arrow::array::{Array, ArrayRef};
+use arrow::datatypes::{DataType, Field};
+use datafusion::arrow;
+use datafusion::common::cast::{as_float64_array, as_struct_array};
+use datafusion::common::{plan_err, Result, ScalarValue};
+use datafusion::logical_expr::function::{AccumulatorArgs, StateFieldsArgs};
+use datafusion::logical_expr::utils::AggregateOrderSensitivity;
+use datafusion::logical_expr::{Accumulator, AggregateUDFImpl, Signature, Volatility};
+
+use crate::common::args::literal_arg;
+use crate::struct_agg::{ordered_state_fields, struct_fields, StructAggAccumulator};
+
+make_udaf_expr_and_func!(
+    AutocorrelationFunction,
+    autocorrelation,
+    x lag,
+    "Computes the autocorrelation of the values at a lag, in the order of the ORDER BY.",
+    autocorrelation_udaf
+);
+
+/// The `AutocorrelationFunction` computes the sample autocorrelation of a series at a lag,
+/// `autocorrelation(x, lag ORDER BY ts)`, as R's `acf` and statsmodels' `acf` do:
+///
+/// `sum((x[t] - mean) * (x[t + lag] - mean)) / sum((x[t] - mean)^2)`
+///
+/// - The series is ordered by the required `ORDER BY`, and `lag` is a literal non-negative
+///   number of rows. Rows with a NULL `x` are ignored, so they don't count in the lag.
+/// - The rows are buffered with their ordering values until the result is emitted, as those of
+///   different partitions interleave.
+/// - The result is NULL if there are no more rows than `lag`, or if all values are equal.
+pub struct AutocorrelationFunction {
+    signature: Signature,
+}
+
+impl Debug for AutocorrelationFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AutocorrelationFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for AutocorrelationFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AutocorrelationFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+        }
+    }
+}
+
+impl AggregateUDFImpl for AutocorrelationFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "autocorrelation"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        let [x_type, lag_type] = arg_types else {
+            return plan_err!(
+                "autocorrelation expects a value and a lag, got {} arguments",
+                arg_types.len()
+            );
+        };
+        if !(x_type.is_numeric() || x_type.is_null()) {
+            return plan_err!("autocorrelation expects a numeric value, got {x_type}");
+        }
+        if !(lag_type.is_integer() || lag_type.is_null()) {
+            return plan_err!("autocorrelation expects an integer lag, got {lag_type}");
+        }
+        Ok(vec![DataType::Float64, DataType::Int64])
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Float64)
+    }
+
+    fn state_fields(&self, args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(ordered_state_fields(&[DataType::Float64], &args))
+    }
+
+    fn accumulator(&self, acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        if acc_args.ordering_req.is_empty() {
+            return plan_err!("autocorrelation requires an ORDER BY to order the series by");
+        }
+        let lag = match literal_arg(&acc_args.exprs[1], self.name(), "lag")? {
+            ScalarValue::Int64(Some(lag)) if lag >= 0 => lag as usize,
+            lag => return plan_err!("autocorrelation expects a non-negative lag, got {lag}"),
+        };
+        Ok(Box::new(AutocorrelationAccumulator {
+            values: StructAggAccumulator::try_new_ordered(struct_fields("c", &[DataType::Float64]), &acc_args)?,
+            args: acc_args.exprs.len(),
+            lag,
+        }))
+    }
+
+    /// The accumulator sorts the values itself, so a sorted input only spares it work
+    fn order_sensitivity(&self) -> AggregateOrderSensitivity {
+        AggregateOrderSensitivity::Beneficial
+    }
+
+    fn with_beneficial_ordering(
+        self: Arc<Self>,
+        _beneficial_ordering: bool,
+    ) -> Result<Option<Arc<dyn AggregateUDFImpl>>> {
+        Ok(Some(self))
+    }
+}
+
+/// The sample autocorrelation of `values` at `lag`, NULL without more values than `lag` or
+/// without variance
+fn sample_autocorrelation(values: &[f64], lag: usize) -> Option<f64> {
+    if values.len() <= lag {
+        return None;
+    }
+    let mean = values.iter().sum::<f64>() / values.len() as f64;
+    let variance = values.iter().map(|x| (x - mean).powi(2)).sum::<f64>();
+    if variance == 0.0 {
+        return None;
+    }
+    let covariance = values
+        .iter()
+        .zip(&values[lag..])
+        .map(|(x, lagged)| (x - mean) * (lagged - mean))
+        .sum::<f64>();
+    Some(covariance / variance)
+}
+
+/// Accumulator for [`AutocorrelationFunction`], collecting the values with their ordering
+/// values as [`StructAggAccumulator`]
+#[derive(Debug)]
+pub struct AutocorrelationAccumulator {
+    values: StructAggAccumulator,
+    /// The number of arguments, followed by the ordering values in the batches
+    args: usize,
+    lag: usize,
+}
+
+impl Accumulator for AutocorrelationAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        let mut columns = vec![Arc::clone(&values[0])];
+        columns.extend(values[self.args..].iter().cloned());
+        self.values.update_batch(&columns)
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        self.values.merge_batch(states)
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        self.values.state()
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        let ScalarValue::List(rows) = self.values.evaluate()? else {
+            unreachable!("struct_agg returns a list");
+        };
+        if rows.is_null(0) {
+            return Ok(ScalarValue::Float64(None));
+        }
+        let rows = rows.value(0);
+        let values: Vec<f64> = as_float64_array(as_struct_array(&rows)?.column(0))?
+            .iter()
+            .flatten()
+            .collect();
+        Ok(ScalarValue::Float64(sample_autocorrelation(&values, self.lag)))
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self) - std::mem::size_of_val(&self.values) + self.values.size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_autocorrelation() {
+        let alternating = [1.0, -1.0, 1.0, -1.0];
+        assert_eq!(sample_autocorrelation(&alternating, 0), Some(1.0));
+        assert_eq!(sample_autocorrelation(&alternating, 1), Some(-0.75));
+        assert_eq!(sample_autocorrelation(&alternating, 2), Some(0.5));
+        assert_eq!(sample_autocorrelation(&alternating, 4), None);
+        assert_eq!(sample_autocorrelation(&[2.0, 2.0], 1), None);
+    }
+}
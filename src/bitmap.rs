@@ -0,0 +1,567 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+use std::any::Any;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use arrow::array::{ArrayRef, BinaryArray, BooleanArray, Int64Array};
+use datafusion::arrow;
+use datafusion::arrow::datatypes::{DataType, Field};
+use datafusion::common::cast::{as_binary_array, as_int64_array};
+use datafusion::common::{exec_err, plan_err, Result, ScalarValue};
+use datafusion::logical_expr::function::{AccumulatorArgs, StateFieldsArgs};
+use datafusion::logical_expr::{Accumulator, AggregateUDFImpl, ColumnarValue, ScalarUDFImpl, Signature, Volatility};
+
+use crate::common::args::scalar_args;
+use crate::sketches::roaring::RoaringBitmap;
+
+make_udaf_expr_and_func!(
+    BitmapAggFunction,
+    bitmap_agg,
+    "Returns a serialized Roaring bitmap of the integer values, for set operations with other bitmaps.",
+    bitmap_agg_udaf
+);
+
+make_udf_expr_and_func!(
+    RbAndFunction,
+    rb_and,
+    a b,
+    "Returns a Roaring bitmap of the values of both bitmaps.",
+    rb_and_udf
+);
+
+make_udf_expr_and_func!(
+    RbOrFunction,
+    rb_or,
+    a b,
+    "Returns a Roaring bitmap of the values of either bitmap.",
+    rb_or_udf
+);
+
+make_udf_expr_and_func!(
+    RbCardinalityFunction,
+    rb_cardinality,
+    bitmap,
+    "Returns the number of values of a Roaring bitmap.",
+    rb_cardinality_udf
+);
+
+make_udf_expr_and_func!(
+    RbContainsFunction,
+    rb_contains,
+    bitmap value,
+    "Returns whether a Roaring bitmap contains a value.",
+    rb_contains_udf
+);
+
+/// Coerces integers to Int64, the values of the bitmaps being checked against the `u32` range
+/// when they are added
+fn coerce_value_type(fn_name: &str, value_type: &DataType) -> Result<DataType> {
+    match value_type {
+        _ if value_type.is_integer() || value_type.is_null() => Ok(DataType::Int64),
+        _ => plan_err!("{fn_name} expects integer values, got {value_type}"),
+    }
+}
+
+/// The `BitmapAggFunction` collects the distinct non-null values into a Roaring bitmap, serialized
+/// in the versioned format of [`crate::sketches::format`], like ClickHouse's `groupBitmapState`
+/// or Doris' `bitmap_union`.
+///
+/// - Values must be between 0 and 2^32 - 1, as user or segment ids usually are.
+/// - Unlike [`crate::theta::ThetaSketchAggFunction`], bitmaps are exact: [`RbAndFunction`] and
+///   [`RbOrFunction`] combine them, [`RbCardinalityFunction`] counts their values and
+///   [`RbContainsFunction`] looks a value up.
+pub struct BitmapAggFunction {
+    signature: Signature,
+}
+
+impl Debug for BitmapAggFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BitmapAggFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for BitmapAggFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BitmapAggFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+        }
+    }
+}
+
+impl AggregateUDFImpl for BitmapAggFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "bitmap_agg"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        match arg_types {
+            [value] => Ok(vec![coerce_value_type(self.name(), value)?]),
+            _ => plan_err!("bitmap_agg expects a single argument"),
+        }
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Binary)
+    }
+
+    fn is_nullable(&self) -> bool {
+        false
+    }
+
+    fn state_fields(&self, _args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(vec![Field::new("bitmap", DataType::Binary, true)])
+    }
+
+    fn accumulator(&self, _acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        Ok(Box::<BitmapAggAccumulator>::default())
+    }
+
+    fn default_value(&self, _data_type: &DataType) -> Result<ScalarValue> {
+        Ok(ScalarValue::Binary(Some(RoaringBitmap::new().to_bytes())))
+    }
+}
+
+/// Accumulator for [`BitmapAggFunction`]
+#[derive(Debug, Default)]
+pub struct BitmapAggAccumulator {
+    bitmap: RoaringBitmap,
+}
+
+impl Accumulator for BitmapAggAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        for value in as_int64_array(&values[0])?.iter().flatten() {
+            let Ok(value) = u32::try_from(value) else {
+                return exec_err!("bitmap_agg expects values between 0 and {}, got {value}", u32::MAX);
+            };
+            self.bitmap.insert(value);
+        }
+        Ok(())
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        for bytes in as_binary_array(&states[0])?.iter().flatten() {
+            self.bitmap.merge(&RoaringBitmap::from_bytes(bytes)?);
+        }
+        Ok(())
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        Ok(vec![self.evaluate()?])
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        Ok(ScalarValue::Binary(Some(self.bitmap.to_bytes())))
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self) + self.bitmap.size()
+    }
+}
+
+/// Coerces a bitmap argument to Binary
+fn coerce_bitmap_type(fn_name: &str, arg_type: &DataType) -> Result<DataType> {
+    match arg_type {
+        DataType::Binary | DataType::LargeBinary | DataType::BinaryView | DataType::Null => Ok(DataType::Binary),
+        _ => plan_err!("{fn_name} expects Roaring bitmaps, got {arg_type}"),
+    }
+}
+
+/// Invokes the set operation `op` on the bitmaps of `args`, NULL if either bitmap is NULL
+fn invoke_set_operation(
+    args: &[ColumnarValue],
+    fn_name: &str,
+    op: impl Fn(&RoaringBitmap, &RoaringBitmap) -> RoaringBitmap,
+) -> Result<ColumnarValue> {
+    let combine = |a: Option<&[u8]>, b: Option<&[u8]>| -> Result<Option<Vec<u8>>> {
+        match (a, b) {
+            (Some(a), Some(b)) => Ok(Some(
+                op(&RoaringBitmap::from_bytes(a)?, &RoaringBitmap::from_bytes(b)?).to_bytes(),
+            )),
+            _ => Ok(None),
+        }
+    };
+    if let Some([a, b]) = scalar_args(args) {
+        let (ScalarValue::Binary(a), ScalarValue::Binary(b)) = (a, b) else {
+            return exec_err!("{fn_name} expects Roaring bitmaps, got {a:?} and {b:?}");
+        };
+        let combined = combine(a.as_deref(), b.as_deref())?;
+        return Ok(ColumnarValue::Scalar(ScalarValue::Binary(combined)));
+    }
+
+    let arrays = ColumnarValue::values_to_arrays(args)?;
+    let (a, b) = (as_binary_array(&arrays[0])?, as_binary_array(&arrays[1])?);
+    let combined = a
+        .iter()
+        .zip(b.iter())
+        .map(|(a, b)| combine(a, b))
+        .collect::<Result<BinaryArray>>()?;
+    Ok(ColumnarValue::Array(Arc::new(combined)))
+}
+
+/// The `RbAndFunction` intersects two bitmaps of [`BitmapAggFunction`], e.g. to find the users
+/// in both of two segments.
+///
+/// - The result is NULL if either bitmap is NULL.
+pub struct RbAndFunction {
+    signature: Signature,
+}
+
+impl Debug for RbAndFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RbAndFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for RbAndFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RbAndFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+        }
+    }
+}
+
+impl ScalarUDFImpl for RbAndFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "rb_and"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        match arg_types {
+            [a, b] => Ok(vec![
+                coerce_bitmap_type(self.name(), a)?,
+                coerce_bitmap_type(self.name(), b)?,
+            ]),
+            _ => plan_err!("rb_and expects 2 Roaring bitmaps, got {} arguments", arg_types.len()),
+        }
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Binary)
+    }
+
+    fn invoke(&self, args: &[ColumnarValue]) -> Result<ColumnarValue> {
+        invoke_set_operation(args, self.name(), RoaringBitmap::intersect)
+    }
+}
+
+/// The `RbOrFunction` unites two bitmaps of [`BitmapAggFunction`], e.g. to find the users in
+/// either of two segments.
+///
+/// - The result is NULL if either bitmap is NULL.
+pub struct RbOrFunction {
+    signature: Signature,
+}
+
+impl Debug for RbOrFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RbOrFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for RbOrFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RbOrFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+        }
+    }
+}
+
+impl ScalarUDFImpl for RbOrFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "rb_or"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        match arg_types {
+            [a, b] => Ok(vec![
+                coerce_bitmap_type(self.name(), a)?,
+                coerce_bitmap_type(self.name(), b)?,
+            ]),
+            _ => plan_err!("rb_or expects 2 Roaring bitmaps, got {} arguments", arg_types.len()),
+        }
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Binary)
+    }
+
+    fn invoke(&self, args: &[ColumnarValue]) -> Result<ColumnarValue> {
+        invoke_set_operation(args, self.name(), RoaringBitmap::union)
+    }
+}
+
+/// The `RbCardinalityFunction` counts the values of a bitmap of [`BitmapAggFunction`],
+/// [`RbAndFunction`] or [`RbOrFunction`].
+///
+/// - The result is NULL if the bitmap is NULL.
+pub struct RbCardinalityFunction {
+    signature: Signature,
+}
+
+impl Debug for RbCardinalityFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RbCardinalityFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for RbCardinalityFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RbCardinalityFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+        }
+    }
+}
+
+impl ScalarUDFImpl for RbCardinalityFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "rb_cardinality"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        match arg_types {
+            [bitmap] => Ok(vec![coerce_bitmap_type(self.name(), bitmap)?]),
+            _ => plan_err!(
+                "rb_cardinality expects a Roaring bitmap, got {} arguments",
+                arg_types.len()
+            ),
+        }
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Int64)
+    }
+
+    fn invoke(&self, args: &[ColumnarValue]) -> Result<ColumnarValue> {
+        let cardinality = |bitmap: &[u8]| Ok(RoaringBitmap::from_bytes(bitmap)?.len() as i64);
+        if let Some([bitmap]) = scalar_args(args) {
+            let ScalarValue::Binary(bitmap) = bitmap else {
+                return exec_err!("rb_cardinality expects a Roaring bitmap, got {bitmap:?}");
+            };
+            let cardinality = bitmap.as_deref().map(cardinality).transpose()?;
+            return Ok(ColumnarValue::Scalar(ScalarValue::Int64(cardinality)));
+        }
+
+        let arrays = ColumnarValue::values_to_arrays(args)?;
+        let cardinalities = as_binary_array(&arrays[0])?
+            .iter()
+            .map(|bitmap| bitmap.map(cardinality).transpose())
+            .collect::<Result<Int64Array>>()?;
+        Ok(ColumnarValue::Array(Arc::new(cardinalities)))
+    }
+}
+
+/// The `RbContainsFunction` returns whether a bitmap of [`BitmapAggFunction`], [`RbAndFunction`]
+/// or [`RbOrFunction`] contains an integer value.
+///
+/// - Values outside of the range of bitmaps, 0 to 2^32 - 1, are never contained.
+/// - The result is NULL if the bitmap or the value is NULL.
+pub struct RbContainsFunction {
+    signature: Signature,
+}
+
+impl Debug for RbContainsFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RbContainsFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for RbContainsFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RbContainsFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+        }
+    }
+}
+
+impl ScalarUDFImpl for RbContainsFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "rb_contains"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        match arg_types {
+            [bitmap, value] => Ok(vec![
+                coerce_bitmap_type(self.name(), bitmap)?,
+                coerce_value_type(self.name(), value)?,
+            ]),
+            _ => plan_err!("rb_contains expects a Roaring bitmap and a value"),
+        }
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Boolean)
+    }
+
+    fn invoke(&self, args: &[ColumnarValue]) -> Result<ColumnarValue> {
+        let is_scalar = args.iter().all(|arg| matches!(arg, ColumnarValue::Scalar(_)));
+        let arrays = ColumnarValue::values_to_arrays(args)?;
+        let (bitmaps, values) = (as_binary_array(&arrays[0])?, as_int64_array(&arrays[1])?);
+        // consecutive rows often share the bitmap, e.g. a scalar one, so the last one is reused
+        let mut decoded: Option<(&[u8], RoaringBitmap)> = None;
+        let contained = bitmaps
+            .iter()
+            .zip(values.iter())
+            .map(|(bitmap, value)| {
+                let (Some(bitmap), Some(value)) = (bitmap, value) else {
+                    return Ok(None);
+                };
+                if decoded.as_ref().map_or(true, |(bytes, _)| *bytes != bitmap) {
+                    decoded = Some((bitmap, RoaringBitmap::from_bytes(bitmap)?));
+                }
+                let (_, bitmap) = decoded.as_ref().unwrap();
+                Ok(Some(u32::try_from(value).is_ok_and(|value| bitmap.contains(value))))
+            })
+            .collect::<Result<BooleanArray>>()?;
+        let contained: ArrayRef = Arc::new(contained);
+        if is_scalar {
+            return Ok(ColumnarValue::Scalar(ScalarValue::try_from_array(&contained, 0)?));
+        }
+        Ok(ColumnarValue::Array(contained))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::array::Array;
+
+    use super::*;
+
+    fn bitmap(values: Vec<Option<i64>>) -> Result<Option<Vec<u8>>> {
+        let mut acc = BitmapAggAccumulator::default();
+        acc.update_batch(&[Arc::new(Int64Array::from(values)) as ArrayRef])?;
+        match acc.evaluate()? {
+            ScalarValue::Binary(bitmap) => Ok(bitmap),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_bitmap_functions_on_arrays() -> Result<()> {
+        let a: ArrayRef = Arc::new(BinaryArray::from_iter(vec![
+            bitmap(vec![Some(1), Some(2), None, Some(3)])?,
+            None,
+        ]));
+        let b: ArrayRef = Arc::new(BinaryArray::from_iter(vec![
+            bitmap(vec![Some(3), Some(4)])?,
+            bitmap(vec![])?,
+        ]));
+        let args = [ColumnarValue::Array(a), ColumnarValue::Array(b)];
+
+        let cardinality = |bitmaps: ColumnarValue| -> Result<Vec<Option<i64>>> {
+            let ColumnarValue::Array(cardinalities) = RbCardinalityFunction::new().invoke(&[bitmaps])? else {
+                unreachable!()
+            };
+            Ok(as_int64_array(&cardinalities)?.iter().collect())
+        };
+        assert_eq!(cardinality(RbOrFunction::new().invoke(&args)?)?, vec![Some(4), None]);
+        let intersection = RbAndFunction::new().invoke(&args)?;
+        assert_eq!(cardinality(intersection.clone())?, vec![Some(1), None]);
+
+        let values = ColumnarValue::Array(Arc::new(Int64Array::from(vec![3, -3])));
+        let ColumnarValue::Array(contained) = RbContainsFunction::new().invoke(&[intersection, values])? else {
+            unreachable!()
+        };
+        assert_eq!(
+            contained.as_any().downcast_ref::<BooleanArray>().unwrap(),
+            &BooleanArray::from(vec![Some(true), None])
+        );
+
+        assert!(bitmap(vec![Some(-1)]).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,299 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+use std::any::Any;
+use std::fmt::Debug;
+
+use arrow::array::ArrayRef;
+use arrow::datatypes::{DataType, Field};
+use datafusion::arrow;
+use datafusion::common::cast::{as_binary_array, as_fixed_size_binary_array};
+use datafusion::common::{exec_err, plan_err, Result, ScalarValue};
+use datafusion::logical_expr::function::{AccumulatorArgs, StateFieldsArgs};
+use datafusion::logical_expr::{Accumulator, AggregateUDFImpl, Signature, Volatility};
+
+use crate::compat::arg_type;
+
+make_udaf_expr_and_func!(
+    BitAndAggFunction,
+    bit_and_agg,
+    value,
+    "Computes the bitwise AND of the binary values, byte by byte.",
+    bit_and_agg_udaf
+);
+
+make_udaf_expr_and_func!(
+    BitOrAggFunction,
+    bit_or_agg,
+    value,
+    "Computes the bitwise OR of the binary values, byte by byte.",
+    bit_or_agg_udaf
+);
+
+make_udaf_expr_and_func!(
+    BitXorAggFunction,
+    bit_xor_agg,
+    value,
+    "Computes the bitwise XOR of the binary values, byte by byte.",
+    bit_xor_agg_udaf
+);
+
+/// The bitwise operation combining the values, of [`BitAndAggFunction`], [`BitOrAggFunction`] or
+/// [`BitXorAggFunction`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BitwiseOp {
+    And,
+    Or,
+    Xor,
+}
+
+impl BitwiseOp {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::And => "bit_and_agg",
+            Self::Or => "bit_or_agg",
+            Self::Xor => "bit_xor_agg",
+        }
+    }
+
+    fn apply(&self, acc: &mut [u8], value: &[u8]) {
+        let bytes = acc.iter_mut().zip(value);
+        match self {
+            Self::And => bytes.for_each(|(a, b)| *a &= b),
+            Self::Or => bytes.for_each(|(a, b)| *a |= b),
+            Self::Xor => bytes.for_each(|(a, b)| *a ^= b),
+        }
+    }
+
+    /// Keeps fixed size binaries, whose size is part of the result type, and coerces the other
+    /// binaries to Binary
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        match arg_types {
+            [DataType::FixedSizeBinary(size)] => Ok(vec![DataType::FixedSizeBinary(*size)]),
+            [DataType::Binary | DataType::LargeBinary | DataType::BinaryView | DataType::Null] => {
+                Ok(vec![DataType::Binary])
+            }
+            [arg_type] => plan_err!("{} expects binary values, got {arg_type}", self.name()),
+            _ => plan_err!("{} expects 1 argument, got {}", self.name(), arg_types.len()),
+        }
+    }
+
+    fn state_fields(&self, args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(vec![Field::new("value", args.return_type.clone(), true)])
+    }
+
+    fn accumulator(&self, acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        Ok(Box::new(BitwiseAccumulator::new(*self, arg_type(&acc_args, 0)?)))
+    }
+}
+
+/// The `BitAndAggFunction` computes the bitwise AND of the binary values of a group,
+/// `bit_and_agg(value)`, e.g. the flags set in every mask.
+///
+/// - Values are combined byte by byte and must all have the same length. `FixedSizeBinary`
+///   values return a `FixedSizeBinary`, other binaries a `Binary`.
+/// - NULL values are ignored, and the result is NULL if there are none.
+pub struct BitAndAggFunction {
+    signature: Signature,
+}
+
+/// The `BitOrAggFunction` computes the bitwise OR of the binary values of a group,
+/// `bit_or_agg(value)`, e.g. the flags set in any mask.
+///
+/// - Values are combined byte by byte and must all have the same length. `FixedSizeBinary`
+///   values return a `FixedSizeBinary`, other binaries a `Binary`.
+/// - NULL values are ignored, and the result is NULL if there are none.
+pub struct BitOrAggFunction {
+    signature: Signature,
+}
+
+/// The `BitXorAggFunction` computes the bitwise XOR of the binary values of a group,
+/// `bit_xor_agg(value)`, e.g. the flags set in an odd number of masks.
+///
+/// - Values are combined byte by byte and must all have the same length. `FixedSizeBinary`
+///   values return a `FixedSizeBinary`, other binaries a `Binary`.
+/// - NULL values are ignored, and the result is NULL if there are none.
+pub struct BitXorAggFunction {
+    signature: Signature,
+}
+
+macro_rules! bitwise_function {
+    ($UDAF:ident, $OP:expr) => {
+        impl Debug for $UDAF {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.debug_struct(stringify!($UDAF))
+                    .field("signature", &self.signature)
+                    .finish()
+            }
+        }
+
+        impl Default for $UDAF {
+            fn default() -> Self {
+                Self::new()
+            }
+        }
+
+        impl $UDAF {
+            pub fn new() -> Self {
+                Self {
+                    signature: Signature::user_defined(Volatility::Immutable),
+                }
+            }
+        }
+
+        impl AggregateUDFImpl for $UDAF {
+            fn as_any(&self) -> &dyn Any {
+                self
+            }
+
+            fn name(&self) -> &str {
+                $OP.name()
+            }
+
+            fn signature(&self) -> &Signature {
+                &self.signature
+            }
+
+            fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+                $OP.coerce_types(arg_types)
+            }
+
+            fn return_type(&self, arg_types: &[DataType]) -> Result<DataType> {
+                Ok(arg_types[0].clone())
+            }
+
+            fn state_fields(&self, args: StateFieldsArgs) -> Result<Vec<Field>> {
+                $OP.state_fields(args)
+            }
+
+            fn accumulator(&self, acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+                $OP.accumulator(acc_args)
+            }
+        }
+    };
+}
+
+bitwise_function!(BitAndAggFunction, BitwiseOp::And);
+bitwise_function!(BitOrAggFunction, BitwiseOp::Or);
+bitwise_function!(BitXorAggFunction, BitwiseOp::Xor);
+
+/// Accumulator for the bitwise aggregates, combining the values into the bytes of the first one
+#[derive(Debug)]
+pub struct BitwiseAccumulator {
+    op: BitwiseOp,
+    /// `Binary` or `FixedSizeBinary`
+    data_type: DataType,
+    bytes: Option<Vec<u8>>,
+}
+
+impl BitwiseAccumulator {
+    pub fn new(op: BitwiseOp, data_type: DataType) -> Self {
+        Self {
+            op,
+            data_type,
+            bytes: None,
+        }
+    }
+
+    fn add(&mut self, value: &[u8]) -> Result<()> {
+        match &mut self.bytes {
+            None => self.bytes = Some(value.to_vec()),
+            Some(bytes) if bytes.len() != value.len() => {
+                return exec_err!(
+                    "{} expects values of the same length, got {} and {} bytes",
+                    self.op.name(),
+                    bytes.len(),
+                    value.len()
+                );
+            }
+            Some(bytes) => self.op.apply(bytes, value),
+        }
+        Ok(())
+    }
+}
+
+impl Accumulator for BitwiseAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        match self.data_type {
+            DataType::FixedSizeBinary(_) => as_fixed_size_binary_array(&values[0])?
+                .iter()
+                .flatten()
+                .try_for_each(|value| self.add(value)),
+            _ => as_binary_array(&values[0])?
+                .iter()
+                .flatten()
+                .try_for_each(|value| self.add(value)),
+        }
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        self.update_batch(states)
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        Ok(vec![self.evaluate()?])
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        match self.data_type {
+            DataType::FixedSizeBinary(size) => Ok(ScalarValue::FixedSizeBinary(size, self.bytes.clone())),
+            _ => Ok(ScalarValue::Binary(self.bytes.clone())),
+        }
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self) + self.bytes.as_ref().map_or(0, Vec::capacity)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow::array::{BinaryArray, FixedSizeBinaryArray};
+
+    use super::*;
+
+    #[test]
+    fn test_bitwise_accumulators() -> Result<()> {
+        let values: ArrayRef = Arc::new(BinaryArray::from(vec![
+            Some(&[0b1100, 0xff][..]),
+            None,
+            Some(&[0b1010, 0x0f][..]),
+        ]));
+        for (op, expected) in [
+            (BitwiseOp::And, [0b1000, 0x0f]),
+            (BitwiseOp::Or, [0b1110, 0xff]),
+            (BitwiseOp::Xor, [0b0110, 0xf0]),
+        ] {
+            let mut acc = BitwiseAccumulator::new(op, DataType::Binary);
+            acc.update_batch(&[Arc::clone(&values)])?;
+            assert_eq!(acc.evaluate()?, ScalarValue::Binary(Some(expected.to_vec())));
+        }
+
+        let fixed: ArrayRef = Arc::new(FixedSizeBinaryArray::try_from_iter([[1, 2], [4, 2]].into_iter())?);
+        let mut acc = BitwiseAccumulator::new(BitwiseOp::Or, DataType::FixedSizeBinary(2));
+        acc.update_batch(&[fixed])?;
+        let mut merged = BitwiseAccumulator::new(BitwiseOp::Or, DataType::FixedSizeBinary(2));
+        merged.merge_batch(&[acc.state()?[0].to_array()?])?;
+        assert_eq!(merged.evaluate()?, ScalarValue::FixedSizeBinary(2, Some(vec![5, 2])));
+
+        let mut acc = BitwiseAccumulator::new(BitwiseOp::And, DataType::Binary);
+        let mismatched: ArrayRef = Arc::new(BinaryArray::from(vec![&[1][..], &[1, 2][..]]));
+        assert!(acc.update_batch(&[mismatched]).is_err());
+        Ok(())
+    }
+}
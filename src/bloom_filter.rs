@@ -0,0 +1,254 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::any::Any;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use arrow::array::{Array, ArrayRef, BooleanArray};
+use datafusion::arrow;
+use datafusion::arrow::datatypes::{DataType, Field};
+use datafusion::common::cast::as_binary_array;
+use datafusion::common::{plan_err, Result, ScalarValue};
+use datafusion::logical_expr::function::{AccumulatorArgs, StateFieldsArgs};
+use datafusion::logical_expr::{Accumulator, AggregateUDFImpl, ColumnarValue, ScalarUDFImpl, Signature, Volatility};
+
+use crate::common::args::{literal_arg, literal_f64_arg};
+use crate::common::sketch_lookup::{coerce_lookup_types, lookup_values};
+use crate::fingerprint::{hash_lookup_values, lookup_value_type};
+use crate::sketches::bloom::{BloomFilter, DEFAULT_EXPECTED_ITEMS, DEFAULT_FPP};
+
+make_udaf_expr_and_func!(
+    BloomFilterAggFunction,
+    bloom_filter_agg,
+    "Returns a serialized Bloom filter of the values, for membership checks with bloom_contains.",
+    bloom_filter_agg_udaf
+);
+
+make_udf_expr_and_func!(
+    BloomContainsFunction,
+    bloom_contains,
+    filter value,
+    "Returns whether a value may have been added to a Bloom filter, with false positives but no false negatives.",
+    bloom_contains_udf
+);
+
+/// The `BloomFilterAggFunction` builds a Bloom filter of the non-null values, serialized in the
+/// versioned format of [`crate::sketches::format`], to check values against it later with
+/// [`BloomContainsFunction`], e.g. to skip rows of a join that can't match.
+///
+/// - `bloom_filter_agg(x, expected_items, fpp)` sizes the filter so that values not added are
+///   reported as contained with probability `fpp` once `expected_items` distinct values are
+///   added. The defaults of 10000 and 0.01 take 12 KiB. The probability grows beyond
+///   `expected_items` values.
+/// - Filters only merge with filters of the same size.
+/// - Values are hashed with Spark's Murmur3 after widening integers to BIGINT, floats to DOUBLE
+///   and strings to VARCHAR, so filters written by different releases can be queried.
+pub struct BloomFilterAggFunction {
+    signature: Signature,
+}
+
+impl Debug for BloomFilterAggFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BloomFilterAggFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for BloomFilterAggFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BloomFilterAggFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+        }
+    }
+}
+
+impl AggregateUDFImpl for BloomFilterAggFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "bloom_filter_agg"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        match arg_types {
+            [value] => Ok(vec![lookup_value_type(value)]),
+            [value, _] => Ok(vec![lookup_value_type(value), DataType::Int64]),
+            [value, _, _] => Ok(vec![lookup_value_type(value), DataType::Int64, DataType::Float64]),
+            _ => plan_err!("bloom_filter_agg expects a value and optional expected_items and fpp"),
+        }
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Binary)
+    }
+
+    fn is_nullable(&self) -> bool {
+        false
+    }
+
+    fn state_fields(&self, _args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(vec![Field::new("filter", DataType::Binary, true)])
+    }
+
+    fn accumulator(&self, acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        let expected_items = match acc_args.exprs.get(1) {
+            Some(expr) => match literal_arg(expr, self.name(), "expected_items")? {
+                ScalarValue::Int64(Some(expected_items)) if expected_items > 0 => expected_items as u64,
+                expected_items => {
+                    return plan_err!("bloom_filter_agg expects a positive expected_items, got {expected_items}")
+                }
+            },
+            None => DEFAULT_EXPECTED_ITEMS,
+        };
+        let fpp = match acc_args.exprs.get(2) {
+            Some(expr) => literal_f64_arg(expr, self.name(), "fpp")?,
+            None => DEFAULT_FPP,
+        };
+        if !(fpp > 0.0 && fpp < 1.0) {
+            return plan_err!("bloom_filter_agg expects an fpp between 0 and 1 exclusive, got {fpp}");
+        }
+        Ok(Box::new(BloomFilterAccumulator::new(expected_items, fpp)))
+    }
+
+    fn default_value(&self, _data_type: &DataType) -> Result<ScalarValue> {
+        Ok(ScalarValue::Binary(Some(BloomFilter::default().to_bytes())))
+    }
+}
+
+/// Accumulator for [`BloomFilterAggFunction`], adding the 64-bit hashes of the values to a
+/// [`BloomFilter`].
+#[derive(Debug)]
+pub struct BloomFilterAccumulator {
+    filter: BloomFilter,
+}
+
+impl BloomFilterAccumulator {
+    pub fn new(expected_items: u64, fpp: f64) -> Self {
+        Self {
+            filter: BloomFilter::with_fpp(expected_items, fpp),
+        }
+    }
+}
+
+impl Accumulator for BloomFilterAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        let values = &values[0];
+        for (row, hash) in hash_lookup_values(values)?.into_iter().enumerate() {
+            if values.is_valid(row) {
+                self.filter.add_hash(hash);
+            }
+        }
+        Ok(())
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        for bytes in as_binary_array(&states[0])?.iter().flatten() {
+            self.filter.merge(&BloomFilter::from_bytes(bytes)?)?;
+        }
+        Ok(())
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        Ok(vec![self.evaluate()?])
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        Ok(ScalarValue::Binary(Some(self.filter.to_bytes())))
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self) + self.filter.size()
+    }
+}
+
+/// The `BloomContainsFunction` checks whether a value was added to a filter of
+/// [`BloomFilterAggFunction`].
+///
+/// - The result is always true for values that were added, and true with the false positive
+///   probability of the filter for other values.
+/// - The result is NULL if the filter or the value is NULL.
+pub struct BloomContainsFunction {
+    signature: Signature,
+}
+
+impl Debug for BloomContainsFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BloomContainsFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for BloomContainsFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BloomContainsFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+        }
+    }
+}
+
+impl ScalarUDFImpl for BloomContainsFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "bloom_contains"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        coerce_lookup_types(self.name(), "Bloom filter", arg_types)
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Boolean)
+    }
+
+    fn invoke(&self, args: &[ColumnarValue]) -> Result<ColumnarValue> {
+        lookup_values(
+            self.name(),
+            args,
+            BloomFilter::from_bytes,
+            BloomFilter::contains_hash,
+            |contained| Arc::new(BooleanArray::from(contained)),
+        )
+    }
+}
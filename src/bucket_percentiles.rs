@@ -0,0 +1,254 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::any::Any;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use arrow::array::{Array, ArrayRef, BinaryArray, Float64Array, MapArray, StructArray};
+use arrow::buffer::{NullBuffer, OffsetBuffer};
+use arrow::compute::{and, filter, is_not_null, sort_to_indices, take};
+use arrow::datatypes::{DataType, Field, Fields};
+use datafusion::arrow;
+use datafusion::common::cast::{as_binary_array, as_float64_array, as_list_array};
+use datafusion::common::{plan_err, Result, ScalarValue};
+use datafusion::logical_expr::function::{AccumulatorArgs, StateFieldsArgs};
+use datafusion::logical_expr::{Accumulator, AggregateUDFImpl, Signature, Volatility};
+
+use crate::common::collections::ArrowBytesPayloadMap;
+use crate::common::nulls::all_null_or_filtered;
+use crate::compat::{single_row_list, OutputType};
+use crate::sketches::tdigest::TDigest;
+
+make_udaf_expr_and_func!(
+    BucketPercentilesFunction,
+    bucket_percentiles,
+    value bucket_key,
+    "Calculates the approximate p50, p95 and p99 of the values for each bucket key.",
+    bucket_percentiles_udaf
+);
+
+/// The percentiles computed for each bucket, and the names of their fields
+const PERCENTILES: [(&str, f64); 3] = [("p50", 0.5), ("p95", 0.95), ("p99", 0.99)];
+
+fn percentiles_fields() -> Fields {
+    PERCENTILES
+        .iter()
+        .map(|(name, _)| Field::new(*name, DataType::Float64, true))
+        .collect()
+}
+
+fn map_entries_field() -> Arc<Field> {
+    let entries = Fields::from(vec![
+        Field::new("key", DataType::Utf8, false),
+        Field::new("value", DataType::Struct(percentiles_fields()), true),
+    ]);
+    Arc::new(Field::new("entries", DataType::Struct(entries), false))
+}
+
+/// The `BucketPercentilesFunction` calculates percentiles of `value` separately for each
+/// distinct `bucket_key` within a group, returning a `Map<Utf8, Struct<p50, p95, p99>>`.
+///
+/// - This replaces a two-level aggregation (percentiles grouped by bucket, then collected into
+///   a map) with a single aggregate.
+/// - The percentiles are estimated with a [`TDigest`] per bucket.
+/// - Rows where `value` or `bucket_key` is NULL are ignored, keys are converted to strings and
+///   the map is sorted by key.
+pub struct BucketPercentilesFunction {
+    signature: Signature,
+}
+
+impl Debug for BucketPercentilesFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BucketPercentilesFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for BucketPercentilesFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BucketPercentilesFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+        }
+    }
+}
+
+impl AggregateUDFImpl for BucketPercentilesFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "bucket_percentiles"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        let [value, key] = arg_types else {
+            return plan_err!(
+                "bucket_percentiles expects 2 arguments (value, bucket_key), got {}",
+                arg_types.len()
+            );
+        };
+        if !value.is_numeric() && !value.is_null() {
+            return plan_err!("bucket_percentiles expects a numeric value, got {:?}", value);
+        }
+        let key_value_type = match key {
+            DataType::Dictionary(_, value_type) => value_type.as_ref(),
+            key => key,
+        };
+        match key_value_type {
+            DataType::Null | DataType::Utf8 | DataType::LargeUtf8 | DataType::Utf8View => {}
+            key if key.is_integer() => {}
+            key => {
+                return plan_err!(
+                    "bucket_percentiles expects a string or integer bucket key, got {:?}",
+                    key
+                )
+            }
+        }
+        Ok(vec![DataType::Float64, DataType::Utf8])
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Map(map_entries_field(), false))
+    }
+
+    fn state_fields(&self, _args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(vec![
+            Field::new_list("keys", Field::new("item", DataType::Utf8, true), true),
+            Field::new_list("digests", Field::new("item", DataType::Binary, true), true),
+        ])
+    }
+
+    fn accumulator(&self, _acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        Ok(Box::new(BucketPercentilesAccumulator::new()))
+    }
+}
+
+/// Accumulator for [`BucketPercentilesFunction`], keeping a [`TDigest`] per bucket key.
+///
+/// The digests are exchanged between partial and final aggregation in their serialized form.
+#[derive(Debug)]
+pub struct BucketPercentilesAccumulator {
+    buckets: ArrowBytesPayloadMap<i32, TDigest>,
+}
+
+impl Default for BucketPercentilesAccumulator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BucketPercentilesAccumulator {
+    pub fn new() -> Self {
+        Self {
+            buckets: ArrowBytesPayloadMap::new(OutputType::Utf8),
+        }
+    }
+}
+
+impl Accumulator for BucketPercentilesAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        if all_null_or_filtered(&values[0], None) || all_null_or_filtered(&values[1], None) {
+            return Ok(());
+        }
+        let (mut values, mut keys) = (Arc::clone(&values[0]), Arc::clone(&values[1]));
+        if values.null_count() > 0 || keys.null_count() > 0 {
+            let valid = and(&is_not_null(&values)?, &is_not_null(&keys)?)?;
+            values = filter(&values, &valid)?;
+            keys = filter(&keys, &valid)?;
+        }
+
+        let values = as_float64_array(&values)?;
+        self.buckets
+            .update(&keys, TDigest::default, |row, digest| digest.add(values.value(row)));
+        Ok(())
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        let keys = as_list_array(&states[0])?;
+        let digests = as_list_array(&states[1])?;
+        for (keys, digests) in keys.iter().zip(digests.iter()) {
+            let (Some(keys), Some(digests)) = (keys, digests) else {
+                continue;
+            };
+            let digests = as_binary_array(&digests)?
+                .iter()
+                .map(|bytes| TDigest::from_bytes(bytes.unwrap_or_default()))
+                .collect::<Result<Vec<_>>>()?;
+            self.buckets
+                .update(&keys, TDigest::default, |row, digest| digest.merge(&digests[row]));
+        }
+        Ok(())
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        let (keys, mut digests) = self.buckets.take(OutputType::Utf8).into_parts();
+        let digests: BinaryArray = digests.iter_mut().map(|digest| Some(digest.to_bytes())).collect();
+        Ok(vec![
+            ScalarValue::List(Arc::new(single_row_list(keys))),
+            ScalarValue::List(Arc::new(single_row_list(Arc::new(digests)))),
+        ])
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        let (keys, mut digests) = self.buckets.take(OutputType::Utf8).into_parts();
+
+        let order = sort_to_indices(&keys, None, None)?;
+        let keys = take(&keys, &order, None)?;
+        let percentiles = PERCENTILES
+            .iter()
+            .map(|(_, q)| {
+                let values: Float64Array = order
+                    .values()
+                    .iter()
+                    .map(|&i| digests[i as usize].quantile(*q))
+                    .collect();
+                Arc::new(values) as ArrayRef
+            })
+            .collect();
+        let values = StructArray::try_new(percentiles_fields(), percentiles, None)?;
+
+        let entries_field = map_entries_field();
+        let DataType::Struct(entries_fields) = entries_field.data_type() else {
+            unreachable!("map entries are a struct");
+        };
+        let len = keys.len();
+        let entries = StructArray::try_new(entries_fields.clone(), vec![keys, Arc::new(values)], None)?;
+        // no rows gives a NULL map, as other aggregates return NULL for empty input
+        let nulls = (len == 0).then(|| NullBuffer::new_null(1));
+        let map = MapArray::try_new(entries_field, OffsetBuffer::from_lengths([len]), entries, nulls, false)?;
+        Ok(ScalarValue::Map(Arc::new(map)))
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self)
+            + self.buckets.size()
+            + self.buckets.payloads().iter().map(TDigest::size).sum::<usize>()
+    }
+}
@@ -0,0 +1,300 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::any::Any;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use datafusion::arrow::array::{BooleanArray, Int64Array};
+use datafusion::arrow::datatypes::DataType;
+use datafusion::common::cast::{as_date32_array, as_string_array};
+use datafusion::common::{exec_err, plan_err, Result, ScalarValue};
+use datafusion::logical_expr::{ColumnarValue, ScalarUDFImpl, Signature, Volatility};
+
+use crate::common::args::scalar_args;
+use crate::common::calendar::{CalendarTable, Calendars, END_DAY, FIRST_DAY};
+
+make_udf_expr_and_func!(
+    HolidayFunction,
+    holiday,
+    date calendar,
+    "Returns whether a date is a holiday of a calendar.",
+    holiday_udf
+);
+
+make_udf_expr_and_func!(
+    BusinessDaysBetweenFunction,
+    business_days_between,
+    start end calendar,
+    "Returns the number of business days of a calendar from a start date, included, to an end date, excluded.",
+    business_days_between_udf
+);
+
+/// Coerces the date arguments to `Date32` and the calendar to `Utf8`
+fn coerce_calendar_args(fn_name: &str, arg_types: &[DataType], dates: usize) -> Result<Vec<DataType>> {
+    if arg_types.len() != dates + 1 {
+        return plan_err!("{fn_name} expects {dates} date(s) and a calendar");
+    }
+    let (date_types, calendar_type) = arg_types.split_at(dates);
+    for date_type in date_types {
+        if !matches!(
+            date_type,
+            DataType::Date32 | DataType::Date64 | DataType::Timestamp(_, _) | DataType::Null
+        ) {
+            return plan_err!("{fn_name} expects dates, got {date_type}");
+        }
+    }
+    match &calendar_type[0] {
+        DataType::Utf8 | DataType::LargeUtf8 | DataType::Utf8View | DataType::Null => {}
+        calendar_type => return plan_err!("{fn_name} expects the name of a calendar, got {calendar_type}"),
+    }
+    Ok([vec![DataType::Date32; dates], vec![DataType::Utf8]].concat())
+}
+
+/// Looks up the calendars of consecutive rows, which usually have the same one
+struct CalendarLookup<'a> {
+    fn_name: &'static str,
+    calendars: &'a Calendars,
+    last: Option<(&'a str, Arc<CalendarTable>)>,
+}
+
+impl<'a> CalendarLookup<'a> {
+    fn new(fn_name: &'static str, calendars: &'a Calendars) -> Self {
+        Self {
+            fn_name,
+            calendars,
+            last: None,
+        }
+    }
+
+    fn get(&mut self, name: &'a str) -> Result<&CalendarTable> {
+        if !matches!(&self.last, Some((last, _)) if *last == name) {
+            let Some(calendar) = self.calendars.get(name) else {
+                return exec_err!(
+                    "{} can't find calendar '{name}', expected one of {}",
+                    self.fn_name,
+                    self.calendars.names().join(", ")
+                );
+            };
+            self.last = Some((name, calendar));
+        }
+        Ok(self.last.as_ref().map(|(_, calendar)| calendar.as_ref()).unwrap())
+    }
+}
+
+fn unsupported_date<T>(fn_name: &str, day: i32) -> Result<T> {
+    exec_err!(
+        "{fn_name} supports dates from 1900-01-01 to 2199-12-31, got {}",
+        ScalarValue::Date32(Some(day))
+    )
+}
+
+/// The `HolidayFunction` returns whether a date is a holiday of a calendar,
+/// `holiday(date, calendar)`.
+///
+/// - The calendars are those of [`Calendars`]: `'us'`, `'eu'`, `'weekends'` and the custom
+///   calendars the function was created with, see [`Self::new_with_calendars`]. Holidays falling
+///   on a weekend are only holidays on the weekday they are observed on, if any.
+/// - Weekends are not holidays, so `business_days_between(d, d + 1, calendar)` is 1 when `d` is
+///   neither a weekend nor a holiday.
+/// - Timestamps are truncated to their date. Dates from 1900 to 2199 are supported.
+/// - The result is NULL if the date or the calendar is NULL.
+pub struct HolidayFunction {
+    signature: Signature,
+    calendars: Calendars,
+}
+
+impl Debug for HolidayFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HolidayFunction")
+            .field("signature", &self.signature)
+            .field("calendars", &self.calendars.names())
+            .finish()
+    }
+}
+
+impl Default for HolidayFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HolidayFunction {
+    pub fn new() -> Self {
+        Self::new_with_calendars(Calendars::new())
+    }
+
+    /// Creates the function with the custom calendars of `calendars`
+    pub fn new_with_calendars(calendars: Calendars) -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+            calendars,
+        }
+    }
+}
+
+impl ScalarUDFImpl for HolidayFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "holiday"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        coerce_calendar_args(self.name(), arg_types, 1)
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Boolean)
+    }
+
+    fn invoke(&self, args: &[ColumnarValue]) -> Result<ColumnarValue> {
+        let mut lookup = CalendarLookup::new("holiday", &self.calendars);
+        let mut is_holiday = |day: i32, name| match lookup.get(name)?.is_holiday(day) {
+            Some(is_holiday) => Ok(is_holiday),
+            None => unsupported_date("holiday", day),
+        };
+        if let Some([date, calendar]) = scalar_args(args) {
+            let holiday = match (date, calendar) {
+                (ScalarValue::Date32(Some(day)), ScalarValue::Utf8(Some(name))) => Some(is_holiday(*day, name)?),
+                _ => None,
+            };
+            return Ok(ColumnarValue::Scalar(ScalarValue::Boolean(holiday)));
+        }
+        let arrays = ColumnarValue::values_to_arrays(args)?;
+        let (days, names) = (as_date32_array(&arrays[0])?, as_string_array(&arrays[1])?);
+        let holidays = days
+            .iter()
+            .zip(names.iter())
+            .map(|row| match row {
+                (Some(day), Some(name)) => is_holiday(day, name).map(Some),
+                _ => Ok(None),
+            })
+            .collect::<Result<BooleanArray>>()?;
+        Ok(ColumnarValue::Array(Arc::new(holidays)))
+    }
+}
+
+/// The `BusinessDaysBetweenFunction` counts the business days of a calendar between two dates,
+/// `business_days_between(start, end, calendar)`.
+///
+/// - Business days are the days that are neither Saturdays, Sundays nor holidays of the
+///   calendar, see [`HolidayFunction`] for the calendars.
+/// - The start date is counted and the end date isn't, as with NumPy's `busday_count`, so the
+///   counts of consecutive periods add up. The result is negative if the end date is before the
+///   start date.
+/// - Timestamps are truncated to their date. Dates from 1900 to 2199 are supported.
+/// - The result is NULL if a date or the calendar is NULL.
+pub struct BusinessDaysBetweenFunction {
+    signature: Signature,
+    calendars: Calendars,
+}
+
+impl Debug for BusinessDaysBetweenFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BusinessDaysBetweenFunction")
+            .field("signature", &self.signature)
+            .field("calendars", &self.calendars.names())
+            .finish()
+    }
+}
+
+impl Default for BusinessDaysBetweenFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BusinessDaysBetweenFunction {
+    pub fn new() -> Self {
+        Self::new_with_calendars(Calendars::new())
+    }
+
+    /// Creates the function with the custom calendars of `calendars`
+    pub fn new_with_calendars(calendars: Calendars) -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+            calendars,
+        }
+    }
+}
+
+impl ScalarUDFImpl for BusinessDaysBetweenFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "business_days_between"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        coerce_calendar_args(self.name(), arg_types, 2)
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Int64)
+    }
+
+    fn invoke(&self, args: &[ColumnarValue]) -> Result<ColumnarValue> {
+        let mut lookup = CalendarLookup::new("business_days_between", &self.calendars);
+        let mut business_days = |start: i32, end: i32, name| {
+            if let Some(business_days) = lookup.get(name)?.business_days_between(start, end) {
+                return Ok(business_days);
+            }
+            // The end date can be the day after the last supported one
+            let day = if (FIRST_DAY..=END_DAY).contains(&start) {
+                end
+            } else {
+                start
+            };
+            unsupported_date("business_days_between", day)
+        };
+        if let Some([start, end, calendar]) = scalar_args(args) {
+            let count = match (start, end, calendar) {
+                (ScalarValue::Date32(Some(start)), ScalarValue::Date32(Some(end)), ScalarValue::Utf8(Some(name))) => {
+                    Some(business_days(*start, *end, name)?)
+                }
+                _ => None,
+            };
+            return Ok(ColumnarValue::Scalar(ScalarValue::Int64(count)));
+        }
+        let arrays = ColumnarValue::values_to_arrays(args)?;
+        let (starts, ends) = (as_date32_array(&arrays[0])?, as_date32_array(&arrays[1])?);
+        let names = as_string_array(&arrays[2])?;
+        let counts = starts
+            .iter()
+            .zip(ends.iter())
+            .zip(names.iter())
+            .map(|row| match row {
+                ((Some(start), Some(end)), Some(name)) => business_days(start, end, name).map(Some),
+                _ => Ok(None),
+            })
+            .collect::<Result<Int64Array>>()?;
+        Ok(ColumnarValue::Array(Arc::new(counts)))
+    }
+}
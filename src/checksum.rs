@@ -0,0 +1,350 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::any::Any;
+use std::fmt::Debug;
+
+use arrow::array::ArrayRef;
+use datafusion::arrow;
+use datafusion::arrow::datatypes::{DataType, Field};
+use datafusion::common::cast::{as_fixed_size_binary_array, as_int64_array};
+use datafusion::common::{plan_err, Result, ScalarValue};
+use datafusion::logical_expr::function::{AccumulatorArgs, StateFieldsArgs};
+use datafusion::logical_expr::{Accumulator, AggregateUDFImpl, Signature, Volatility};
+
+use crate::fingerprint::{fingerprint_scalar, fingerprint_type, fingerprint_value, fingerprint_value_type, hash_rows};
+
+make_udaf_expr_and_func!(
+    ChecksumAggFunction,
+    checksum_agg,
+    "Calculates an order-independent 64-bit checksum of the rows.",
+    checksum_agg_udaf
+);
+
+make_udaf_expr_and_func!(
+    HashAggFunction,
+    hash_agg,
+    "Calculates an order-independent 128-bit hash of the rows.",
+    hash_agg_udaf
+);
+
+/// Width of the order-independent hash of the rows computed by [`ChecksumAccumulator`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChecksumWidth {
+    /// `checksum_agg`: a signed 64-bit integer
+    Bits64,
+    /// `hash_agg`: a 16-byte fixed size binary, in the format of `column_fingerprint`
+    Bits128,
+}
+
+impl ChecksumWidth {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Bits64 => "checksum_agg",
+            Self::Bits128 => "hash_agg",
+        }
+    }
+
+    /// Number of 32-bit hashes of a row making up its hash
+    fn words(&self) -> usize {
+        match self {
+            Self::Bits64 => 2,
+            Self::Bits128 => 4,
+        }
+    }
+
+    fn return_type(&self) -> DataType {
+        match self {
+            Self::Bits64 => DataType::Int64,
+            Self::Bits128 => fingerprint_type(),
+        }
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        if arg_types.is_empty() {
+            return plan_err!("{} expects at least 1 argument", self.name());
+        }
+        Ok(arg_types.iter().map(fingerprint_value_type).collect())
+    }
+}
+
+/// The `ChecksumAggFunction` calculates a 64-bit checksum of the multiset of rows made of its
+/// arguments, to cheaply compare the contents of tables across systems.
+///
+/// - `checksum_agg(a, b, ...)` sums the hashes of every row modulo 2^64, so it doesn't depend on
+///   the order of the rows or how they are partitioned, and checksums of disjoint parts of a
+///   table add up.
+/// - Rows are hashed as `column_fingerprint` hashes values, so `checksum_agg(x)` is the lower 64
+///   bits of `column_fingerprint(x)`, see [`HashAggFunction`] for the 128-bit hash.
+/// - NULL values are hashed to a constant that depends on their column, so they change the
+///   checksum. The checksum of no rows is 0.
+pub struct ChecksumAggFunction {
+    signature: Signature,
+}
+
+impl Debug for ChecksumAggFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ChecksumAggFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for ChecksumAggFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ChecksumAggFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+        }
+    }
+}
+
+impl AggregateUDFImpl for ChecksumAggFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        ChecksumWidth::Bits64.name()
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        ChecksumWidth::Bits64.coerce_types(arg_types)
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(ChecksumWidth::Bits64.return_type())
+    }
+
+    fn is_nullable(&self) -> bool {
+        false
+    }
+
+    fn state_fields(&self, _args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(vec![Field::new("checksum", ChecksumWidth::Bits64.return_type(), false)])
+    }
+
+    fn accumulator(&self, _acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        Ok(Box::new(ChecksumAccumulator::new(ChecksumWidth::Bits64)))
+    }
+
+    fn default_value(&self, _data_type: &DataType) -> Result<ScalarValue> {
+        Ok(ScalarValue::Int64(Some(0)))
+    }
+}
+
+/// The `HashAggFunction` calculates a 128-bit hash of the multiset of rows made of its
+/// arguments, for comparisons where the 64-bit [`ChecksumAggFunction`] collides too often.
+///
+/// - `hash_agg(x)` equals `column_fingerprint(x)` and `hash_agg(a, b, ...)` extends it to rows of
+///   several columns, so results combine with `fingerprint_combine`.
+/// - It is not a cryptographic hash: it detects accidental differences, not deliberate ones.
+pub struct HashAggFunction {
+    signature: Signature,
+}
+
+impl Debug for HashAggFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HashAggFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for HashAggFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HashAggFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+        }
+    }
+}
+
+impl AggregateUDFImpl for HashAggFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        ChecksumWidth::Bits128.name()
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        ChecksumWidth::Bits128.coerce_types(arg_types)
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(ChecksumWidth::Bits128.return_type())
+    }
+
+    fn state_fields(&self, _args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(vec![Field::new("hash", ChecksumWidth::Bits128.return_type(), true)])
+    }
+
+    fn accumulator(&self, _acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        Ok(Box::new(ChecksumAccumulator::new(ChecksumWidth::Bits128)))
+    }
+}
+
+/// Accumulator for [`ChecksumAggFunction`] and [`HashAggFunction`], summing the hashes of the
+/// rows.
+#[derive(Debug)]
+pub struct ChecksumAccumulator {
+    checksum: u128,
+    width: ChecksumWidth,
+}
+
+impl ChecksumAccumulator {
+    pub fn new(width: ChecksumWidth) -> Self {
+        Self { checksum: 0, width }
+    }
+}
+
+impl Accumulator for ChecksumAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        for hash in hash_rows(values, self.width.words())? {
+            self.checksum = self.checksum.wrapping_add(hash);
+        }
+        Ok(())
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        match self.width {
+            ChecksumWidth::Bits64 => {
+                for checksum in as_int64_array(&states[0])?.iter().flatten() {
+                    self.checksum = self.checksum.wrapping_add(checksum as u64 as u128);
+                }
+            }
+            ChecksumWidth::Bits128 => {
+                for hash in as_fixed_size_binary_array(&states[0])?.iter().flatten() {
+                    self.checksum = self.checksum.wrapping_add(fingerprint_value(hash)?);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        Ok(vec![self.evaluate()?])
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        Ok(match self.width {
+            ChecksumWidth::Bits64 => ScalarValue::Int64(Some(self.checksum as u64 as i64)),
+            ChecksumWidth::Bits128 => fingerprint_scalar(self.checksum),
+        })
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{Int32Array, StringArray};
+    use std::sync::Arc;
+
+    use crate::fingerprint::ColumnFingerprintAccumulator;
+
+    /// A batch of `(int, string)` rows
+    type Batch<'a> = (Vec<Option<i32>>, Vec<Option<&'a str>>);
+
+    fn checksum(width: ChecksumWidth, batches: &[Batch]) -> Result<ScalarValue> {
+        let mut acc = ChecksumAccumulator::new(width);
+        for (ints, strings) in batches {
+            let mut partial = ChecksumAccumulator::new(width);
+            partial.update_batch(&[
+                Arc::new(Int32Array::from(ints.clone())),
+                Arc::new(StringArray::from(strings.clone())),
+            ])?;
+            acc.merge_batch(&[partial.state()?[0].to_array()?])?;
+        }
+        acc.evaluate()
+    }
+
+    #[test]
+    fn test_checksum_is_order_independent_and_tells_null_columns_apart() -> Result<()> {
+        for width in [ChecksumWidth::Bits64, ChecksumWidth::Bits128] {
+            let expected = checksum(
+                width,
+                &[(vec![Some(1), None, Some(2)], vec![None, Some("a"), Some("b")])],
+            )?;
+            assert_eq!(
+                checksum(
+                    width,
+                    &[
+                        (vec![Some(2)], vec![Some("b")]),
+                        (vec![None, Some(1)], vec![Some("a"), None])
+                    ]
+                )?,
+                expected
+            );
+            // The same values with the NULL in the other column
+            assert_ne!(
+                checksum(
+                    width,
+                    &[(vec![None, Some(1), Some(2)], vec![Some("a"), None, Some("b")])]
+                )?,
+                checksum(width, &[(vec![Some(1), Some(1), Some(2)], vec![None, None, Some("b")])])?
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_single_column_matches_column_fingerprint() -> Result<()> {
+        let values: ArrayRef = Arc::new(StringArray::from(vec![Some("a"), None, Some("b")]));
+        let mut fingerprint = ColumnFingerprintAccumulator::new();
+        fingerprint.update_batch(&[Arc::clone(&values)])?;
+        let ScalarValue::FixedSizeBinary(_, Some(bytes)) = fingerprint.evaluate()? else {
+            panic!("expected a fingerprint");
+        };
+        let fingerprint = fingerprint_value(&bytes)?;
+
+        let mut hash = ChecksumAccumulator::new(ChecksumWidth::Bits128);
+        hash.update_batch(&[Arc::clone(&values)])?;
+        assert_eq!(hash.evaluate()?, fingerprint_scalar(fingerprint));
+
+        let mut checksum = ChecksumAccumulator::new(ChecksumWidth::Bits64);
+        checksum.update_batch(&[values])?;
+        assert_eq!(
+            checksum.evaluate()?,
+            ScalarValue::Int64(Some(fingerprint as u64 as i64))
+        );
+        Ok(())
+    }
+}
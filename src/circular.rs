@@ -0,0 +1,294 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::any::Any;
+use std::f64::consts::TAU;
+use std::fmt::Debug;
+
+use arrow::array::{Array, ArrayRef};
+use datafusion::arrow;
+use datafusion::arrow::datatypes::{DataType, Field, Float64Type, UInt64Type};
+use datafusion::common::cast::{as_float64_array, as_primitive_array};
+use datafusion::common::{Result, ScalarValue};
+use datafusion::logical_expr::function::{AccumulatorArgs, StateFieldsArgs};
+use datafusion::logical_expr::{Accumulator, AggregateUDFImpl, Signature, Volatility};
+
+use crate::common::nulls::all_null_or_filtered;
+
+make_udaf_expr_and_func!(
+    CircularMeanFunction,
+    circular_mean,
+    x,
+    "Calculates the mean direction of angles in radians, in [0, 2π).",
+    circular_mean_udaf
+);
+
+make_udaf_expr_and_func!(
+    CircularStddevFunction,
+    circular_stddev,
+    x,
+    "Calculates the circular standard deviation of angles in radians.",
+    circular_stddev_udaf
+);
+
+/// Which statistic a [`CircularAccumulator`] evaluates to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircularStat {
+    Mean,
+    Stddev,
+}
+
+/// The `CircularMeanFunction` calculates the direction of the mean of the unit vectors of
+/// angles given in radians, e.g. `circular_mean(radians(wind_direction))`.
+///
+/// - NULL values are ignored.
+/// - The result is in `[0, 2π)`, and NULL if there are no values or the vectors cancel out.
+pub struct CircularMeanFunction {
+    signature: Signature,
+}
+
+impl Debug for CircularMeanFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CircularMeanFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for CircularMeanFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CircularMeanFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::coercible(vec![DataType::Float64], Volatility::Immutable),
+        }
+    }
+}
+
+impl AggregateUDFImpl for CircularMeanFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "circular_mean"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Float64)
+    }
+
+    fn state_fields(&self, _args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(CircularAccumulator::state_fields())
+    }
+
+    fn accumulator(&self, _acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        Ok(Box::new(CircularAccumulator::new(CircularStat::Mean)))
+    }
+}
+
+/// The `CircularStddevFunction` calculates `sqrt(-2 ln R)` of angles given in radians, where `R`
+/// is the length of the mean of their unit vectors.
+///
+/// - NULL values are ignored.
+/// - The result is 0 for identical angles, grows without bound as the angles spread around the
+///   circle, and is NULL if there are no values.
+pub struct CircularStddevFunction {
+    signature: Signature,
+}
+
+impl Debug for CircularStddevFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CircularStddevFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for CircularStddevFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CircularStddevFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::coercible(vec![DataType::Float64], Volatility::Immutable),
+        }
+    }
+}
+
+impl AggregateUDFImpl for CircularStddevFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "circular_stddev"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Float64)
+    }
+
+    fn state_fields(&self, _args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(CircularAccumulator::state_fields())
+    }
+
+    fn accumulator(&self, _acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        Ok(Box::new(CircularAccumulator::new(CircularStat::Stddev)))
+    }
+}
+
+/// Accumulator summing the unit vectors of angles, shared by [`CircularMeanFunction`] and
+/// [`CircularStddevFunction`].
+#[derive(Debug)]
+pub struct CircularAccumulator {
+    sum_sin: f64,
+    sum_cos: f64,
+    count: u64,
+    stat: CircularStat,
+}
+
+impl CircularAccumulator {
+    pub fn new(stat: CircularStat) -> Self {
+        Self {
+            sum_sin: 0.0,
+            sum_cos: 0.0,
+            count: 0,
+            stat,
+        }
+    }
+
+    pub fn state_fields() -> Vec<Field> {
+        vec![
+            Field::new("sum_sin", DataType::Float64, true),
+            Field::new("sum_cos", DataType::Float64, true),
+            Field::new("count", DataType::UInt64, true),
+        ]
+    }
+}
+
+impl Accumulator for CircularAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        if all_null_or_filtered(&values[0], None) {
+            return Ok(());
+        }
+        for angle in as_float64_array(&values[0])?.iter().flatten() {
+            let (sin, cos) = angle.sin_cos();
+            self.sum_sin += sin;
+            self.sum_cos += cos;
+            self.count += 1;
+        }
+        Ok(())
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        let sum_sin = as_primitive_array::<Float64Type>(&states[0])?;
+        let sum_cos = as_primitive_array::<Float64Type>(&states[1])?;
+        let count = as_primitive_array::<UInt64Type>(&states[2])?;
+        for i in 0..count.len() {
+            if count.is_valid(i) {
+                self.sum_sin += sum_sin.value(i);
+                self.sum_cos += sum_cos.value(i);
+                self.count += count.value(i);
+            }
+        }
+        Ok(())
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        Ok(vec![
+            ScalarValue::from(self.sum_sin),
+            ScalarValue::from(self.sum_cos),
+            ScalarValue::from(self.count),
+        ])
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        if self.count == 0 {
+            return Ok(ScalarValue::Float64(None));
+        }
+        let result = match self.stat {
+            CircularStat::Mean => {
+                (self.sum_sin != 0.0 || self.sum_cos != 0.0).then(|| self.sum_sin.atan2(self.sum_cos).rem_euclid(TAU))
+            }
+            CircularStat::Stddev => {
+                let mean_length = self.sum_sin.hypot(self.sum_cos) / self.count as f64;
+                Some((-2.0 * mean_length.min(1.0).ln()).sqrt())
+            }
+        };
+        Ok(ScalarValue::Float64(result))
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::Float64Array;
+    use std::f64::consts::PI;
+    use std::sync::Arc;
+
+    fn evaluate(stat: CircularStat, degrees: &[f64]) -> Result<Option<f64>> {
+        let mut acc = CircularAccumulator::new(stat);
+        acc.update_batch(&[Arc::new(Float64Array::from_iter_values(
+            degrees.iter().map(|d| d.to_radians()),
+        ))])?;
+        let ScalarValue::Float64(result) = acc.evaluate()? else {
+            unreachable!()
+        };
+        Ok(result)
+    }
+
+    #[test]
+    fn test_circular_mean_wraps_around() -> Result<()> {
+        // The arithmetic mean of 350° and 10° is 180°, the circular mean is 0°
+        let mean = evaluate(CircularStat::Mean, &[350.0, 10.0])?.unwrap();
+        assert!(mean.min(TAU - mean) < 1e-12, "got {mean}");
+        let mean = evaluate(CircularStat::Mean, &[260.0, 280.0])?.unwrap();
+        assert!((mean - 1.5 * PI).abs() < 1e-12, "got {mean}");
+        assert_eq!(evaluate(CircularStat::Mean, &[])?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_circular_stddev() -> Result<()> {
+        assert_eq!(evaluate(CircularStat::Stddev, &[30.0, 30.0, 390.0])?, Some(0.0));
+        // R = cos(10°) for angles 10° either side of the mean
+        let stddev = evaluate(CircularStat::Stddev, &[350.0, 10.0])?.unwrap();
+        let expected = (-2.0 * 10f64.to_radians().cos().ln()).sqrt();
+        assert!((stddev - expected).abs() < 1e-12, "got {stddev}");
+        Ok(())
+    }
+}
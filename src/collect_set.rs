@@ -0,0 +1,543 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::any::Any;
+use std::collections::HashSet;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use arrow::array::{Array, ArrayRef};
+use arrow::compute::kernels::boolean::is_not_null;
+use arrow::compute::{filter, sort, SortOptions};
+use arrow::datatypes::{DataType, Field, FieldRef};
+use arrow::row::{OwnedRow, RowConverter, SortField};
+use datafusion::arrow;
+use datafusion::common::cast::as_list_array;
+use datafusion::common::{plan_err, Result, ScalarValue};
+use datafusion::logical_expr::expr::{AggregateFunction, Cast, TryCast};
+use datafusion::logical_expr::function::{AccumulatorArgs, AggregateFunctionSimplification, StateFieldsArgs};
+use datafusion::logical_expr::simplify::SimplifyInfo;
+use datafusion::logical_expr::{lit, Accumulator, AggregateUDFImpl, Expr, Signature, Volatility};
+
+use crate::common::args::literal_arg;
+use crate::common::output_width::{list_values, OutputWidth};
+use crate::compat::{ArrowBytesViewSet, OutputType};
+
+make_udaf_expr_and_func!(
+    ArrayUnionAggFunction,
+    array_union_agg,
+    list,
+    "Unions the lists into a list of their distinct non-null elements.",
+    array_union_agg_udaf
+);
+
+make_udaf_expr_and_func!(
+    CollectSetFunction,
+    collect_set,
+    "Collects the distinct non-null values into a list.",
+    collect_set_udaf
+);
+
+/// The `CollectSetFunction` collects the distinct non-null values of a group into a list, like
+/// Spark's `collect_set`.
+///
+/// - `collect_set(x, limit, 'asc')` returns the values sorted, `'desc'` in descending order,
+///   and so does `collect_set(x ORDER BY x [DESC])`, which is rewritten to it. Otherwise they are
+///   in the order they were first aggregated in, which depends on the partitioning of the input.
+/// - `collect_set(x, limit)` keeps at most `limit` values: the first ones, or the smallest ones
+///   in the given order. Every group then holds at most `limit` values and a batch. A NULL
+///   `limit` keeps all the values.
+/// - Strings and binaries are coerced to `Utf8View` and `BinaryView` and deduplicated with the
+///   byte sets of [`crate::common::collections`], other values in the row format.
+/// - Returns a `List`, or a `LargeList` if configured with [`OutputWidth::Large`].
+/// - The result is NULL if there are no non-null values.
+pub struct CollectSetFunction {
+    signature: Signature,
+    output_width: OutputWidth,
+}
+
+impl Debug for CollectSetFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CollectSetFunction")
+            .field("signature", &self.signature)
+            .field("output_width", &self.output_width)
+            .finish()
+    }
+}
+
+impl Default for CollectSetFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CollectSetFunction {
+    pub fn new() -> Self {
+        Self::new_with_output_width(OutputWidth::default())
+    }
+
+    pub fn new_with_output_width(output_width: OutputWidth) -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+            output_width,
+        }
+    }
+}
+
+impl AggregateUDFImpl for CollectSetFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "collect_set"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        let (value_type, options) = match arg_types {
+            [value_type, options @ ..] if options.len() <= 2 => (value_type, options),
+            _ => {
+                return plan_err!(
+                    "collect_set expects 1 to 3 arguments (x[, limit[, order]]), got {}",
+                    arg_types.len()
+                )
+            }
+        };
+        let mut coerced = vec![coerce_value_type(self.name(), value_type)?];
+        if let Some(limit_type) = options.first() {
+            if !limit_type.is_integer() && !limit_type.is_null() {
+                return plan_err!("collect_set expects an integer limit, got {limit_type}");
+            }
+            coerced.push(DataType::Int64);
+        }
+        if let Some(order_type) = options.get(1) {
+            if !matches!(order_type, DataType::Utf8 | DataType::LargeUtf8 | DataType::Utf8View) {
+                return plan_err!("collect_set expects a string order, got {order_type}");
+            }
+            coerced.push(DataType::Utf8);
+        }
+        Ok(coerced)
+    }
+
+    fn return_type(&self, arg_types: &[DataType]) -> Result<DataType> {
+        Ok(self.output_width.list_type(item_field(&arg_types[0])))
+    }
+
+    fn state_fields(&self, args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(vec![Field::new(
+            "values",
+            self.output_width.list_type(item_field(&args.input_types[0])),
+            true,
+        )])
+    }
+
+    fn accumulator(&self, acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        let limit = match acc_args.exprs.get(1) {
+            None => None,
+            Some(expr) => match literal_arg(expr, self.name(), "limit")? {
+                ScalarValue::Int64(None) => None,
+                ScalarValue::Int64(Some(limit)) if limit > 0 => Some(limit as usize),
+                limit => return plan_err!("collect_set expects a positive limit, got {limit}"),
+            },
+        };
+        let order = match acc_args.exprs.get(2) {
+            None => None,
+            Some(expr) => match literal_arg(expr, self.name(), "order")? {
+                ScalarValue::Utf8(Some(order)) if order.eq_ignore_ascii_case("asc") => Some(false),
+                ScalarValue::Utf8(Some(order)) if order.eq_ignore_ascii_case("desc") => Some(true),
+                order => return plan_err!("collect_set expects an order of 'asc' or 'desc', got {order}"),
+            },
+        };
+        let order = order.map(|descending| SortOptions {
+            descending,
+            nulls_first: false,
+        });
+        let value_type = crate::compat::arg_type(&acc_args, 0)?;
+        Ok(Box::new(CollectSetAccumulator::try_new(
+            value_type,
+            limit,
+            order,
+            self.output_width,
+        )?))
+    }
+
+    fn simplify(&self) -> Option<AggregateFunctionSimplification> {
+        Some(Box::new(rewrite_order_by))
+    }
+}
+
+/// Rewrites `collect_set(x[, limit] ORDER BY x [DESC])` into `collect_set(x, limit, order)`.
+///
+/// The values are sorted by themselves, so only the argument can be ordered by. This is checked
+/// in the first pass of the optimizer, where the argument is still the ORDER BY expression cast
+/// by its coercion, rather than a common subexpression.
+fn rewrite_order_by(mut aggr_func: AggregateFunction, _info: &dyn SimplifyInfo) -> Result<Expr> {
+    let descending = match aggr_func.order_by.as_deref() {
+        None | Some([]) => return Ok(Expr::AggregateFunction(aggr_func)),
+        Some([sort]) if uncast(&sort.expr) == uncast(&aggr_func.args[0]) => !sort.asc,
+        Some(_) => return plan_err!("collect_set can only be ordered by its argument"),
+    };
+    if aggr_func.args.len() > 2 {
+        return plan_err!("collect_set expects either an order or an ORDER BY");
+    }
+    if aggr_func.args.len() == 1 {
+        aggr_func.args.push(lit(ScalarValue::Int64(None)));
+    }
+    aggr_func.args.push(lit(if descending { "desc" } else { "asc" }));
+    aggr_func.order_by = None;
+    Ok(Expr::AggregateFunction(aggr_func))
+}
+
+/// Coerces the values of `collect_set` and `array_union_agg`: strings and binaries to the view
+/// types of the byte sets, other values must be convertible to the row format
+fn coerce_value_type(name: &str, value_type: &DataType) -> Result<DataType> {
+    let value_type = match value_type {
+        DataType::Dictionary(_, value_type) => value_type.as_ref(),
+        value_type => value_type,
+    };
+    Ok(match value_type {
+        DataType::Null => DataType::Int64,
+        DataType::Utf8 | DataType::LargeUtf8 | DataType::Utf8View => DataType::Utf8View,
+        DataType::Binary | DataType::LargeBinary | DataType::BinaryView => DataType::BinaryView,
+        value_type if RowConverter::supports_fields(&[SortField::new(value_type.clone())]) => value_type.clone(),
+        value_type => return plan_err!("{name} doesn't support values of type {value_type}"),
+    })
+}
+
+fn item_field(item_type: &DataType) -> FieldRef {
+    Arc::new(Field::new("item", item_type.clone(), true))
+}
+
+/// The expression cast by `expr`, or `expr` itself
+fn uncast(expr: &Expr) -> &Expr {
+    match expr {
+        Expr::Cast(Cast { expr, .. }) | Expr::TryCast(TryCast { expr, .. }) => uncast(expr),
+        expr => expr,
+    }
+}
+
+/// The distinct values of a [`CollectSetAccumulator`], in the order they were first inserted
+trait DistinctValues: Debug + Send + Sync {
+    fn insert(&mut self, values: &ArrayRef) -> Result<()>;
+
+    /// The number of distinct non-null values
+    fn len(&self) -> usize;
+
+    /// Takes the distinct non-null values, leaving the set empty
+    fn take(&mut self) -> Result<ArrayRef>;
+
+    fn size(&self) -> usize;
+}
+
+impl DistinctValues for ArrowBytesViewSet {
+    fn insert(&mut self, values: &ArrayRef) -> Result<()> {
+        ArrowBytesViewSet::insert(self, values);
+        Ok(())
+    }
+
+    fn len(&self) -> usize {
+        self.non_null_len()
+    }
+
+    fn take(&mut self) -> Result<ArrayRef> {
+        let values = ArrowBytesViewSet::take(self).into_state();
+        if values.null_count() == 0 {
+            return Ok(values);
+        }
+        Ok(filter(&values, &is_not_null(&values)?)?)
+    }
+
+    fn size(&self) -> usize {
+        ArrowBytesViewSet::size(self)
+    }
+}
+
+/// Distinct values in the row format, for the types without a byte set
+#[derive(Debug)]
+struct RowSet {
+    converter: RowConverter,
+    seen: HashSet<OwnedRow>,
+    /// The distinct rows in the order they were first inserted
+    rows: Vec<OwnedRow>,
+}
+
+impl RowSet {
+    fn try_new(value_type: DataType) -> Result<Self> {
+        Ok(Self {
+            converter: RowConverter::new(vec![SortField::new(value_type)])?,
+            seen: HashSet::new(),
+            rows: vec![],
+        })
+    }
+}
+
+impl DistinctValues for RowSet {
+    fn insert(&mut self, values: &ArrayRef) -> Result<()> {
+        let rows = self.converter.convert_columns(&[Arc::clone(values)])?;
+        for (index, row) in rows.iter().enumerate() {
+            if values.is_null(index) {
+                continue;
+            }
+            let row = row.owned();
+            if !self.seen.contains(&row) {
+                self.seen.insert(row.clone());
+                self.rows.push(row);
+            }
+        }
+        Ok(())
+    }
+
+    fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    fn take(&mut self) -> Result<ArrayRef> {
+        self.seen.clear();
+        let rows = std::mem::take(&mut self.rows);
+        let mut columns = self.converter.convert_rows(rows.iter().map(|row| row.row()))?;
+        Ok(columns.remove(0))
+    }
+
+    fn size(&self) -> usize {
+        self.converter.size()
+            + self.seen.capacity() * std::mem::size_of::<OwnedRow>()
+            + self.rows.capacity() * std::mem::size_of::<OwnedRow>()
+            + 2 * self.rows.iter().map(|row| row.as_ref().len()).sum::<usize>()
+    }
+}
+
+/// Accumulator for [`CollectSetFunction`]
+#[derive(Debug)]
+pub struct CollectSetAccumulator {
+    values: Box<dyn DistinctValues>,
+    value_type: DataType,
+    limit: Option<usize>,
+    /// The order of the values, if sorted
+    order: Option<SortOptions>,
+    output_width: OutputWidth,
+}
+
+impl CollectSetAccumulator {
+    fn try_new(
+        value_type: DataType,
+        limit: Option<usize>,
+        order: Option<SortOptions>,
+        output_width: OutputWidth,
+    ) -> Result<Self> {
+        let values: Box<dyn DistinctValues> = match value_type {
+            DataType::Utf8View => Box::new(ArrowBytesViewSet::new(OutputType::Utf8View)),
+            DataType::BinaryView => Box::new(ArrowBytesViewSet::new(OutputType::BinaryView)),
+            _ => Box::new(RowSet::try_new(value_type.clone())?),
+        };
+        Ok(Self {
+            values,
+            value_type,
+            limit,
+            order,
+            output_width,
+        })
+    }
+
+    /// Takes the values, sorted if ordered and cut to the limit
+    fn take(&mut self) -> Result<ArrayRef> {
+        let values = self.values.take()?;
+        let values = match self.order {
+            Some(options) => sort(&values, Some(options))?,
+            None => values,
+        };
+        Ok(match self.limit {
+            Some(limit) if values.len() > limit => values.slice(0, limit),
+            _ => values,
+        })
+    }
+
+    fn insert(&mut self, values: &ArrayRef) -> Result<()> {
+        self.values.insert(values)?;
+        if self.limit.is_some_and(|limit| self.values.len() > limit) {
+            let values = self.take()?;
+            self.values.insert(&values)?;
+        }
+        Ok(())
+    }
+}
+
+impl Accumulator for CollectSetAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        self.insert(&values[0])
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        for values in list_values(&states[0])? {
+            self.insert(&values)?;
+        }
+        Ok(())
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        Ok(vec![self.evaluate()?])
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        let values = self.take()?;
+        // Keep the values, as evaluate is called once per frame in windows
+        self.values.insert(&values)?;
+        if values.is_empty() {
+            return self.output_width.null_list(&self.value_type);
+        }
+        self.output_width.list_scalar(values, "collect_set")
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self) + self.values.size()
+    }
+}
+
+/// The `ArrayUnionAggFunction` unions the lists of a group into a list of their distinct non-null
+/// elements, e.g. merging the tags of every post of a user with `array_union_agg(tags)`.
+///
+/// - The elements are deduplicated as with `collect_set`, and listed in the order they were
+///   first aggregated in, which depends on the partitioning of the input.
+/// - `List`, `LargeList` and `FixedSizeList` inputs are supported. String and binary elements
+///   are returned as `Utf8View` and `BinaryView`.
+/// - Returns a `List`, or a `LargeList` if configured with [`OutputWidth::Large`].
+/// - NULL lists and elements are ignored, and the result is NULL if there are no non-null
+///   elements.
+pub struct ArrayUnionAggFunction {
+    signature: Signature,
+    output_width: OutputWidth,
+}
+
+impl Debug for ArrayUnionAggFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ArrayUnionAggFunction")
+            .field("signature", &self.signature)
+            .field("output_width", &self.output_width)
+            .finish()
+    }
+}
+
+impl Default for ArrayUnionAggFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ArrayUnionAggFunction {
+    pub fn new() -> Self {
+        Self::new_with_output_width(OutputWidth::default())
+    }
+
+    pub fn new_with_output_width(output_width: OutputWidth) -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+            output_width,
+        }
+    }
+}
+
+impl AggregateUDFImpl for ArrayUnionAggFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "array_union_agg"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        let element_type = match arg_types {
+            [DataType::Null] => DataType::Null,
+            [DataType::List(field) | DataType::LargeList(field) | DataType::FixedSizeList(field, _)] => {
+                field.data_type().clone()
+            }
+            [arg_type] => return plan_err!("array_union_agg expects a list, got {arg_type}"),
+            _ => return plan_err!("array_union_agg expects 1 argument, got {}", arg_types.len()),
+        };
+        Ok(vec![DataType::new_list(
+            coerce_value_type(self.name(), &element_type)?,
+            true,
+        )])
+    }
+
+    fn return_type(&self, arg_types: &[DataType]) -> Result<DataType> {
+        let DataType::List(field) = &arg_types[0] else {
+            return plan_err!("array_union_agg expects a list, got {}", arg_types[0]);
+        };
+        Ok(self.output_width.list_type(Arc::clone(field)))
+    }
+
+    fn state_fields(&self, args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(vec![Field::new("values", self.return_type(args.input_types)?, true)])
+    }
+
+    fn accumulator(&self, acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        let DataType::List(field) = crate::compat::arg_type(&acc_args, 0)? else {
+            return plan_err!("array_union_agg expects a list");
+        };
+        Ok(Box::new(ArrayUnionAggAccumulator(CollectSetAccumulator::try_new(
+            field.data_type().clone(),
+            None,
+            None,
+            self.output_width,
+        )?)))
+    }
+}
+
+/// Accumulator for [`ArrayUnionAggFunction`], a [`CollectSetAccumulator`] of the elements of
+/// the lists
+#[derive(Debug)]
+pub struct ArrayUnionAggAccumulator(CollectSetAccumulator);
+
+impl Accumulator for ArrayUnionAggAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        let lists = as_list_array(&values[0])?;
+        if lists.null_count() == 0 {
+            // Insert the elements of every list at once
+            let offsets = lists.value_offsets();
+            let (start, end) = (offsets[0] as usize, offsets[lists.len()] as usize);
+            return self.0.insert(&lists.values().slice(start, end - start));
+        }
+        for list in lists.iter().flatten() {
+            self.0.insert(&list)?;
+        }
+        Ok(())
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        self.0.merge_batch(states)
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        self.0.state()
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        self.0.evaluate()
+    }
+
+    fn size(&self) -> usize {
+        self.0.size()
+    }
+}
@@ -0,0 +1,82 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Helpers for constant arguments: aggregate arguments that must be constant, such as a fraction
+//! or a size, and scalar function arguments that are all literals.
+
+use std::sync::Arc;
+
+use arrow::datatypes::Schema;
+use arrow::record_batch::RecordBatch;
+use datafusion::arrow;
+use datafusion::common::{not_impl_err, plan_err, Result, ScalarValue};
+use datafusion::logical_expr::ColumnarValue;
+use datafusion::physical_expr::PhysicalExpr;
+
+/// Evaluates `expr`, which must be constant, as done by `get_scalar_value` in
+/// `datafusion/functions-aggregate/src/approx_percentile_cont.rs`
+pub fn literal_arg(expr: &Arc<dyn PhysicalExpr>, fn_name: &str, arg_name: &str) -> Result<ScalarValue> {
+    let batch = RecordBatch::new_empty(Arc::new(Schema::empty()));
+    match expr.evaluate(&batch) {
+        Ok(ColumnarValue::Scalar(value)) => Ok(value),
+        _ => not_impl_err!("{fn_name} expects a literal {arg_name}, got: {expr}"),
+    }
+}
+
+/// Evaluates the constant floating point argument `expr`, failing if it is NULL
+pub fn literal_f64_arg(expr: &Arc<dyn PhysicalExpr>, fn_name: &str, arg_name: &str) -> Result<f64> {
+    match literal_arg(expr, fn_name, arg_name)?.cast_to(&arrow::datatypes::DataType::Float64)? {
+        ScalarValue::Float64(Some(value)) => Ok(value),
+        _ => plan_err!("{fn_name} expects a non-null {arg_name}"),
+    }
+}
+
+/// The `N` arguments of a scalar function as [`ScalarValue`]s if they are all scalars, as when the
+/// function is invoked on literals while constant folding.
+///
+/// Functions check this first in `invoke` so they can compute a single value directly, instead
+/// of converting every argument into a 1-row array and the result back into a scalar.
+pub fn scalar_args<const N: usize>(args: &[ColumnarValue]) -> Option<[&ScalarValue; N]> {
+    if args.len() != N || !args.iter().all(|arg| matches!(arg, ColumnarValue::Scalar(_))) {
+        return None;
+    }
+    Some(std::array::from_fn(|i| match &args[i] {
+        ColumnarValue::Scalar(value) => value,
+        ColumnarValue::Array(_) => unreachable!("every argument is a scalar"),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::Int64Array;
+
+    #[test]
+    fn test_scalar_args_requires_only_scalars() {
+        let one = ColumnarValue::Scalar(ScalarValue::Int64(Some(1)));
+        let null = ColumnarValue::Scalar(ScalarValue::Int64(None));
+        let array = ColumnarValue::Array(Arc::new(Int64Array::from(vec![1])));
+
+        let args = [one.clone(), null];
+        assert_eq!(
+            scalar_args(&args),
+            Some([&ScalarValue::Int64(Some(1)), &ScalarValue::Int64(None)])
+        );
+        assert_eq!(scalar_args::<3>(&args), None);
+        assert_eq!(scalar_args::<2>(&[one, array]), None);
+    }
+}
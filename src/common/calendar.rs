@@ -0,0 +1,346 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Holiday calendars of the business day functions, precomputed into lookup tables so that a
+//! batch is evaluated with two array lookups per row.
+//!
+//! Days are counted from 1970-01-01, as in `Date32`, and converted to civil dates with the
+//! algorithms of Howard Hinnant's "chrono-Compatible Low-Level Date Algorithms".
+
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::sync::{Arc, OnceLock};
+
+use datafusion::arrow::buffer::BooleanBuffer;
+use datafusion::common::{plan_err, Result};
+
+/// First day of the tables, 1900-01-01
+pub const FIRST_DAY: i32 = -25_567;
+
+/// Day after the last day of the tables, 2200-01-01
+pub const END_DAY: i32 = 84_006;
+
+/// Names of the built-in calendars
+pub const BUILTIN_CALENDARS: [&str; 3] = ["us", "eu", "weekends"];
+
+/// Whether the tables cover `day`
+pub fn is_supported(day: i32) -> bool {
+    (FIRST_DAY..END_DAY).contains(&day)
+}
+
+/// The day of `year-month-day`
+pub fn days_from_civil(year: i32, month: u32, day: u32) -> i32 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let month = month as i32;
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day as i32 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// The year, month and day of `day`
+pub fn civil_from_days(day: i32) -> (i32, u32, u32) {
+    let day = day + 719_468;
+    let era = day.div_euclid(146_097);
+    let day_of_era = day.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day_of_month = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + i32::from(month <= 2);
+    (year, month as u32, day_of_month as u32)
+}
+
+/// The day of the week of `day`, from 0 for Monday to 6 for Sunday
+pub fn weekday(day: i32) -> u32 {
+    // 1970-01-01 was a Thursday
+    (day + 3).rem_euclid(7) as u32
+}
+
+fn is_weekend(day: i32) -> bool {
+    weekday(day) >= 5
+}
+
+/// The `n`-th (from 1) `weekday` of a month
+fn nth_weekday(year: i32, month: u32, weekday_of: u32, n: i32) -> i32 {
+    let first = days_from_civil(year, month, 1);
+    first + (weekday_of as i32 - weekday(first) as i32).rem_euclid(7) + 7 * (n - 1)
+}
+
+/// The last `weekday` of a month
+fn last_weekday(year: i32, month: u32, weekday_of: u32) -> i32 {
+    let last = match month {
+        12 => days_from_civil(year + 1, 1, 1) - 1,
+        month => days_from_civil(year, month + 1, 1) - 1,
+    };
+    last - (weekday(last) as i32 - weekday_of as i32).rem_euclid(7)
+}
+
+/// Easter Sunday of the Gregorian calendar, by the anonymous algorithm of "Butcher's Ecclesiastical
+/// Calendar"
+fn easter(year: i32) -> i32 {
+    let a = year % 19;
+    let (b, c) = (year / 100, year % 100);
+    let (d, e) = (b / 4, b % 4);
+    let g = (8 * b + 13) / 25;
+    let h = (19 * a + b - d - g + 15) % 30;
+    let (i, k) = (c / 4, c % 4);
+    let l = (32 + 2 * e + 2 * i - h - k) % 7;
+    let m = (a + 11 * h + 19 * l) / 433;
+    let month = (h + l - 7 * m + 90) / 25;
+    let day = (h + l - 7 * m + 33 * month + 19) % 32;
+    days_from_civil(year, month as u32, day as u32)
+}
+
+/// The weekday a holiday falling on a weekend is observed on: the Friday before a Saturday and
+/// the Monday after a Sunday
+fn observed(day: i32) -> i32 {
+    match weekday(day) {
+        5 => day - 1,
+        6 => day + 1,
+        _ => day,
+    }
+}
+
+/// The federal holidays of the United States, as observed by federal employees
+fn us_holidays(year: i32) -> Vec<i32> {
+    const MONDAY: u32 = 0;
+    const THURSDAY: u32 = 3;
+    let mut holidays = vec![
+        observed(days_from_civil(year, 1, 1)),
+        nth_weekday(year, 2, MONDAY, 3),
+        last_weekday(year, 5, MONDAY),
+        observed(days_from_civil(year, 7, 4)),
+        nth_weekday(year, 9, MONDAY, 1),
+        nth_weekday(year, 10, MONDAY, 2),
+        observed(days_from_civil(year, 11, 11)),
+        nth_weekday(year, 11, THURSDAY, 4),
+        observed(days_from_civil(year, 12, 25)),
+    ];
+    if year >= 1986 {
+        holidays.push(nth_weekday(year, 1, MONDAY, 3));
+    }
+    if year >= 2021 {
+        holidays.push(observed(days_from_civil(year, 6, 19)));
+    }
+    holidays
+}
+
+/// The closing days of TARGET, the payment system of the euro area
+fn eu_holidays(year: i32) -> Vec<i32> {
+    let easter = easter(year);
+    vec![
+        days_from_civil(year, 1, 1),
+        easter - 2,
+        easter + 1,
+        days_from_civil(year, 5, 1),
+        days_from_civil(year, 12, 25),
+        days_from_civil(year, 12, 26),
+    ]
+}
+
+/// The holidays and business days of a calendar from [`FIRST_DAY`] to [`END_DAY`]. Saturdays and
+/// Sundays are never business days, and holidays are the other days that aren't.
+pub struct CalendarTable {
+    /// Whether every day is a holiday
+    holidays: BooleanBuffer,
+    /// The number of business days from [`FIRST_DAY`] to every day, and to [`END_DAY`]
+    business_days_before: Vec<u32>,
+}
+
+impl Debug for CalendarTable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CalendarTable")
+            .field("holidays", &self.holidays.count_set_bits())
+            .finish()
+    }
+}
+
+impl CalendarTable {
+    /// The table of a calendar with `holidays`, ignoring the days that aren't supported and
+    /// the holidays falling on weekends
+    pub fn new(holidays: impl IntoIterator<Item = i32>) -> Self {
+        let len = (END_DAY - FIRST_DAY) as usize;
+        let mut is_holiday = vec![false; len];
+        for day in holidays
+            .into_iter()
+            .filter(|day| is_supported(*day) && !is_weekend(*day))
+        {
+            is_holiday[(day - FIRST_DAY) as usize] = true;
+        }
+        let mut business_days_before = Vec::with_capacity(len + 1);
+        business_days_before.push(0);
+        let mut business_days = 0;
+        for (i, holiday) in is_holiday.iter().enumerate() {
+            if !holiday && !is_weekend(FIRST_DAY + i as i32) {
+                business_days += 1;
+            }
+            business_days_before.push(business_days);
+        }
+        Self {
+            holidays: BooleanBuffer::from(is_holiday),
+            business_days_before,
+        }
+    }
+
+    /// Whether `day` is a holiday, `None` if it isn't supported
+    pub fn is_holiday(&self, day: i32) -> Option<bool> {
+        is_supported(day).then(|| self.holidays.value((day - FIRST_DAY) as usize))
+    }
+
+    /// The number of business days from `start`, included, to `end`, excluded, negative if
+    /// `end` is before `start`. `None` if either isn't supported, with [`END_DAY`] supported as
+    /// the end.
+    pub fn business_days_between(&self, start: i32, end: i32) -> Option<i64> {
+        let index = |day: i32| (FIRST_DAY..=END_DAY).contains(&day).then(|| (day - FIRST_DAY) as usize);
+        let (start, end) = (index(start)?, index(end)?);
+        Some(self.business_days_before[end] as i64 - self.business_days_before[start] as i64)
+    }
+
+    pub fn size(&self) -> usize {
+        self.holidays.inner().capacity() + self.business_days_before.capacity() * std::mem::size_of::<u32>()
+    }
+}
+
+/// The table of the built-in calendar `name`, built on first use
+fn builtin_calendar(name: &str) -> Option<Arc<CalendarTable>> {
+    static US: OnceLock<Arc<CalendarTable>> = OnceLock::new();
+    static EU: OnceLock<Arc<CalendarTable>> = OnceLock::new();
+    static WEEKENDS: OnceLock<Arc<CalendarTable>> = OnceLock::new();
+    // Holidays observed on the last day of the year before are listed with the next year
+    let years = || 1900..=2200;
+    let table = match name {
+        "us" => US.get_or_init(|| Arc::new(CalendarTable::new(years().flat_map(us_holidays)))),
+        "eu" => EU.get_or_init(|| Arc::new(CalendarTable::new(years().flat_map(eu_holidays)))),
+        "weekends" => WEEKENDS.get_or_init(|| Arc::new(CalendarTable::new([]))),
+        _ => return None,
+    };
+    Some(Arc::clone(table))
+}
+
+/// The calendars the business day functions look up by name: the built-in calendars of
+/// [`BUILTIN_CALENDARS`] and custom ones.
+///
+/// - `us` has the federal holidays of the United States, moved to the Friday before or the
+///   Monday after when they fall on a weekend.
+/// - `eu` has the closing days of TARGET, the payment system of the euro area: New Year's Day,
+///   Good Friday, Easter Monday, Labour Day, Christmas and the day after.
+/// - `weekends` has no holidays.
+///
+/// Names are case-insensitive, and every calendar has Saturdays and Sundays off.
+#[derive(Debug, Clone, Default)]
+pub struct Calendars {
+    custom: BTreeMap<String, Arc<CalendarTable>>,
+}
+
+impl Calendars {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the custom calendar `name` with the days `holidays`, replacing any custom calendar
+    /// of the same name. Built-in calendars can't be replaced.
+    pub fn with_calendar(mut self, name: &str, holidays: impl IntoIterator<Item = i32>) -> Result<Self> {
+        let name = name.to_ascii_lowercase();
+        if BUILTIN_CALENDARS.contains(&name.as_str()) {
+            return plan_err!("Can't replace the built-in calendar {name}");
+        }
+        self.custom.insert(name, Arc::new(CalendarTable::new(holidays)));
+        Ok(self)
+    }
+
+    /// Whether there are no custom calendars
+    pub fn is_empty(&self) -> bool {
+        self.custom.is_empty()
+    }
+
+    /// The calendar `name`, `None` if there is none
+    pub fn get(&self, name: &str) -> Option<Arc<CalendarTable>> {
+        let name = name.to_ascii_lowercase();
+        builtin_calendar(&name).or_else(|| self.custom.get(&name).cloned())
+    }
+
+    /// The names of the calendars, for error messages
+    pub fn names(&self) -> Vec<&str> {
+        BUILTIN_CALENDARS
+            .into_iter()
+            .chain(self.custom.keys().map(String::as_str))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_civil_days() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(days_from_civil(1900, 1, 1), FIRST_DAY);
+        assert_eq!(days_from_civil(2200, 1, 1), END_DAY);
+        assert_eq!(days_from_civil(2024, 2, 29), 19_782);
+        for day in [FIRST_DAY, -1, 0, 59, 19_782, END_DAY] {
+            let (year, month, day_of_month) = civil_from_days(day);
+            assert_eq!(days_from_civil(year, month, day_of_month), day);
+        }
+        assert_eq!(civil_from_days(19_782), (2024, 2, 29));
+        // 2024-03-31 was Easter Sunday
+        assert_eq!(easter(2024), days_from_civil(2024, 3, 31));
+        assert_eq!(weekday(easter(2024)), 6);
+        assert_eq!(easter(2000), days_from_civil(2000, 4, 23));
+    }
+
+    #[test]
+    fn test_us_calendar() {
+        let us = Calendars::new().get("US").unwrap();
+        // 2022-01-01 was a Saturday, observed on 2021-12-31
+        assert_eq!(us.is_holiday(days_from_civil(2021, 12, 31)), Some(true));
+        // Thanksgiving, the fourth Thursday of November
+        assert_eq!(us.is_holiday(days_from_civil(2024, 11, 28)), Some(true));
+        assert_eq!(us.is_holiday(days_from_civil(2024, 11, 21)), Some(false));
+        // Juneteenth from 2021 only
+        assert_eq!(us.is_holiday(days_from_civil(2024, 6, 19)), Some(true));
+        assert_eq!(us.is_holiday(days_from_civil(2019, 6, 19)), Some(false));
+        assert_eq!(us.is_holiday(END_DAY), None);
+
+        // July 2024 has 23 weekdays and Independence Day
+        let (start, end) = (days_from_civil(2024, 7, 1), days_from_civil(2024, 8, 1));
+        assert_eq!(us.business_days_between(start, end), Some(22));
+        assert_eq!(us.business_days_between(end, start), Some(-22));
+        assert_eq!(us.business_days_between(start, start), Some(0));
+    }
+
+    #[test]
+    fn test_custom_calendars() -> Result<()> {
+        let day = days_from_civil(2024, 12, 24);
+        let calendars = Calendars::new().with_calendar("Acme", [day, day + 5])?;
+        let acme = calendars.get("acme").unwrap();
+        assert_eq!(acme.is_holiday(day), Some(true));
+        // 2024-12-29 is a Sunday
+        assert_eq!(acme.is_holiday(day + 5), Some(false));
+        assert_eq!(acme.business_days_between(day - 1, day + 7), Some(5));
+        assert!(calendars.get("other").is_none());
+        assert!(Calendars::new().with_calendar("EU", [day]).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,144 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+//! Coercion of the numeric arguments of aggregates, so that functions computing the same kind of
+//! result take and return the same types.
+//!
+//! | Argument                    | [`Exact`]         | [`Int64OrFloat64`] | [`Float64`] |
+//! |-----------------------------|-------------------|--------------------|-------------|
+//! | `NULL`                      | `Int64`           | `Int64`            | `Float64`   |
+//! | signed integers             | `Int64`           | `Int64`            | `Float64`   |
+//! | unsigned integers           | `UInt64`          | `Int64`            | `Float64`   |
+//! | `Decimal128(p, s)`          | `Decimal128(p, s)`| `Float64`          | `Float64`   |
+//! | `Decimal256(p, s)`          | `Decimal256(p, s)`| `Float64`          | `Float64`   |
+//! | `Float16`, `Float32`, `Float64` | `Float64`     | `Float64`          | `Float64`   |
+//!
+//! Dictionaries are coerced as their value type and other types are rejected.
+//!
+//! [`Exact`]: NumericCoercion::Exact
+//! [`Int64OrFloat64`]: NumericCoercion::Int64OrFloat64
+//! [`Float64`]: NumericCoercion::Float64
+
+use datafusion::arrow::datatypes::DataType;
+use datafusion::common::{plan_err, Result};
+
+/// The rule coercing a numeric argument, from the one keeping the most precision to the one
+/// keeping the least
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NumericCoercion {
+    /// For results computed exactly in the type of the input, such as sums: integers widen to 64
+    /// bits keeping their signedness and decimals keep their precision and scale.
+    Exact,
+    /// For results exact for integers only, such as products, whose scale grows with every
+    /// decimal: integers widen to `Int64`, other types promote to `Float64`.
+    Int64OrFloat64,
+    /// For results that are not exact, such as means and moments: every type promotes to
+    /// `Float64`.
+    Float64,
+}
+
+impl NumericCoercion {
+    /// The type the argument `arg_name` of `fn_name` of type `arg_type` is coerced to
+    pub fn coerce(self, fn_name: &str, arg_name: &str, arg_type: &DataType) -> Result<DataType> {
+        let value_type = match arg_type {
+            DataType::Dictionary(_, value_type) => value_type.as_ref(),
+            arg_type => arg_type,
+        };
+        let coerced = match (self, value_type) {
+            (Self::Float64, DataType::Null) => DataType::Float64,
+            (_, DataType::Null) => DataType::Int64,
+            (Self::Exact, t) if t.is_unsigned_integer() => DataType::UInt64,
+            (Self::Exact | Self::Int64OrFloat64, t) if t.is_integer() => DataType::Int64,
+            (Self::Exact, DataType::Decimal128(_, _) | DataType::Decimal256(_, _)) => value_type.clone(),
+            (_, t) if t.is_numeric() => DataType::Float64,
+            _ => return plan_err!("{fn_name} expects a numeric {arg_name}, got {arg_type}"),
+        };
+        Ok(coerced)
+    }
+
+    /// Coerces every argument of `fn_name`, which must have one argument per name of `arg_names`
+    pub fn coerce_all(self, fn_name: &str, arg_names: &[&str], arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        if arg_types.len() != arg_names.len() {
+            return plan_err!(
+                "{fn_name} expects {} argument{}, got {}",
+                arg_names.len(),
+                if arg_names.len() == 1 { "" } else { "s" },
+                arg_types.len()
+            );
+        }
+        arg_names
+            .iter()
+            .zip(arg_types)
+            .map(|(arg_name, arg_type)| self.coerce(fn_name, arg_name, arg_type))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_numeric_coercion() {
+        let dictionary = DataType::Dictionary(Box::new(DataType::Int32), Box::new(DataType::UInt8));
+        let cases = [
+            (DataType::Null, [DataType::Int64, DataType::Int64, DataType::Float64]),
+            (DataType::Int8, [DataType::Int64, DataType::Int64, DataType::Float64]),
+            (DataType::UInt32, [DataType::UInt64, DataType::Int64, DataType::Float64]),
+            (dictionary, [DataType::UInt64, DataType::Int64, DataType::Float64]),
+            (
+                DataType::Decimal128(10, 2),
+                [DataType::Decimal128(10, 2), DataType::Float64, DataType::Float64],
+            ),
+            (
+                DataType::Decimal256(50, 4),
+                [DataType::Decimal256(50, 4), DataType::Float64, DataType::Float64],
+            ),
+            (
+                DataType::Float16,
+                [DataType::Float64, DataType::Float64, DataType::Float64],
+            ),
+        ];
+        let rules = [
+            NumericCoercion::Exact,
+            NumericCoercion::Int64OrFloat64,
+            NumericCoercion::Float64,
+        ];
+        for (arg_type, expected) in cases {
+            for (rule, expected) in rules.iter().zip(expected) {
+                assert_eq!(
+                    rule.coerce("f", "x", &arg_type).unwrap(),
+                    expected,
+                    "{rule:?} of {arg_type}"
+                );
+            }
+        }
+        for rule in rules {
+            let err = rule.coerce("f", "x", &DataType::Utf8).unwrap_err();
+            assert!(err.to_string().contains("f expects a numeric x, got Utf8"), "{err}");
+        }
+    }
+
+    #[test]
+    fn test_coerce_all_checks_arity() {
+        let coerced = NumericCoercion::Float64.coerce_all("f", &["x", "w"], &[DataType::Int32, DataType::Float32]);
+        assert_eq!(coerced.unwrap(), vec![DataType::Float64, DataType::Float64]);
+        let err = NumericCoercion::Float64
+            .coerce_all("f", &["x"], &[DataType::Int32, DataType::Int32])
+            .unwrap_err();
+        assert!(err.to_string().contains("f expects 1 argument, got 2"), "{err}");
+    }
+}
@@ -17,6 +17,8 @@
 
 mod binary_map;
 mod binary_view_map;
+mod payload_map;
 
 pub use binary_map::ArrowBytesMap;
-pub use binary_view_map::ArrowBytesViewMap;
+pub use binary_view_map::{compact_view_array, ArrowBytesViewMap};
+pub use payload_map::{ArrowBytesPayloadMap, ArrowBytesViewPayloadMap, IndexMap, PayloadMap};
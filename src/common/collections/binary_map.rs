@@ -30,13 +30,14 @@ use arrow::datatypes::DataType;
 use datafusion::arrow;
 use datafusion::common::hash_utils::create_hashes;
 use datafusion::common::utils::proxy::{RawTableAllocExt, VecAllocExt};
-use datafusion::physical_expr::binary_map::OutputType;
 use std::any::type_name;
 use std::fmt::Debug;
 use std::mem;
 use std::ops::Range;
 use std::sync::Arc;
 
+use crate::compat::OutputType;
+
 /// Optimized map for storing Arrow "bytes" types (`String`, `LargeString`,
 /// `Binary`, and `LargeBinary`) values that can produce the set of keys on
 /// output as `GenericBinaryArray` without copies.
@@ -143,7 +144,6 @@ use std::sync::Arc;
 ///                         └───────────────┴─┴─┴─┴─┴─┴─┴─┴─┴───────────────┘
 ///                              8 bytes         8 bytes        4 or 8
 /// ```
-
 // TODO: Remove after DataFusion next release once insert_or_update and get_payloads are added to the collection.
 // Copied from datafusion/physical-expr-common/binary_map.rs.
 pub struct ArrowBytesMap<O, V>
@@ -266,7 +266,7 @@ where
         let batch_hashes = &mut self.hashes_buffer;
         batch_hashes.clear();
         batch_hashes.resize(values.len(), 0);
-        create_hashes(&[values.clone()], &self.random_state, batch_hashes)
+        create_hashes(std::slice::from_ref(values), &self.random_state, batch_hashes)
             // hash is supported for all types and create_hashes only
             // returns errors for unsupported types
             .unwrap();
@@ -446,7 +446,7 @@ where
         let batch_hashes = &mut self.hashes_buffer;
         batch_hashes.clear();
         batch_hashes.resize(values.len(), 0);
-        create_hashes(&[values.clone()], &self.random_state, batch_hashes).unwrap(); // Compute the hashes for the values
+        create_hashes(std::slice::from_ref(values), &self.random_state, batch_hashes).unwrap(); // Compute the hashes for the values
 
         // Step 2: Insert or update each value
         let values = values.as_bytes::<B>();
@@ -568,7 +568,7 @@ where
         let mut batch_hashes = vec![0u64; values.len()];
         batch_hashes.clear();
         batch_hashes.resize(values.len(), 0);
-        create_hashes(&[values.clone()], &self.random_state, &mut batch_hashes).unwrap(); // Compute the hashes for the values
+        create_hashes(std::slice::from_ref(values), &self.random_state, &mut batch_hashes).unwrap(); // Compute the hashes for the values
 
         // Step 2: Get payloads for each value
         let values = values.as_bytes::<B>();
@@ -21,15 +21,17 @@
 //! [`GenericByteViewBuilder`].
 use ahash::RandomState;
 use arrow::array::cast::AsArray;
-use arrow::array::{Array, ArrayBuilder, ArrayRef, GenericByteViewBuilder};
+use arrow::array::{Array, ArrayBuilder, ArrayRef, GenericByteViewArray, GenericByteViewBuilder};
 use arrow::datatypes::{BinaryViewType, ByteViewType, DataType, StringViewType};
 use datafusion::arrow;
 use datafusion::common::hash_utils::create_hashes;
 use datafusion::common::utils::proxy::{RawTableAllocExt, VecAllocExt};
-use datafusion::physical_expr::binary_map::OutputType;
+use std::cmp::Ordering;
 use std::fmt::Debug;
 use std::sync::Arc;
 
+use crate::compat::OutputType;
+
 /// Optimized map for storing Arrow "byte view" types (`StringView`, `BinaryView`)
 /// values that can produce the set of keys on
 /// output as `GenericBinaryViewArray` without copies.
@@ -59,7 +61,6 @@ use std::sync::Arc;
 /// This map is used by the special `COUNT DISTINCT` aggregate function to
 /// store the distinct values, and by the `GROUP BY` operator to store
 /// group values when they are a single string array.
-
 // TODO: Remove after DataFusion next release once insert_or_update and get_payloads are added to the collection.
 // Copied from datafusion/physical-expr-common/binary_view_map.rs.
 pub struct ArrowBytesViewMap<V>
@@ -83,31 +84,92 @@ where
     /// NOTE null_index is the logical index in the final array, not the index
     /// in the buffer
     null: Option<(V, usize)>,
+    /// Whether [`Self::into_state`] compacts the data buffers of the output
+    compact_on_emit: bool,
 }
 
 /// The size, in number of entries, of the initial hash table
 const INITIAL_MAP_CAPACITY: usize = 512;
 
+/// Copies the long values of `array` into new, tightly sized data buffers if its buffers
+/// allocate more bytes than its views reference, for example because of the unused
+/// capacity of the builder's last block or because several views share the same value.
+///
+/// Returns `array` unchanged if its buffers are already compact.
+pub fn compact_view_array<T: ByteViewType + ?Sized>(array: GenericByteViewArray<T>) -> GenericByteViewArray<T> {
+    let referenced: usize = array
+        .views()
+        .iter()
+        .map(|view| *view as u32 as usize)
+        .filter(|&len| len > MAX_INLINE_VIEW_LEN)
+        .sum();
+    let allocated: usize = array.data_buffers().iter().map(|buffer| buffer.capacity()).sum();
+    if allocated <= referenced {
+        return array;
+    }
+    // Unlike `GenericByteViewArray::gc`, which copies into blocks of the default size, copy
+    // the values into a single block of the referenced size where possible
+    let block_size = referenced.clamp(1, u32::MAX as usize) as u32;
+    let mut builder = GenericByteViewBuilder::<T>::with_capacity(array.len()).with_fixed_block_size(block_size);
+    for value in array.iter() {
+        match value {
+            Some(value) => builder.append_value(value),
+            None => builder.append_null(),
+        }
+    }
+    builder.finish()
+}
+
+/// The length up to which values are stored inline in their view
+const MAX_INLINE_VIEW_LEN: usize = 12;
+
 impl<V> ArrowBytesViewMap<V>
 where
     V: Debug + PartialEq + Eq + Clone + Copy + Default,
 {
     pub fn new(output_type: OutputType) -> Self {
+        Self::with_random_state(output_type, RandomState::new())
+    }
+
+    /// Create an empty map hashing values with `random_state`.
+    ///
+    /// Maps created from the same `random_state` (or from
+    /// `RandomState::with_seeds` with the same seeds) compute the same hash
+    /// for the same value, so hashes computed for one map can be used to
+    /// probe another.
+    pub fn with_random_state(output_type: OutputType, random_state: RandomState) -> Self {
         Self {
             output_type,
             map: hashbrown::raw::RawTable::with_capacity(INITIAL_MAP_CAPACITY),
             map_size: 0,
             builder: GenericByteViewBuilder::new(),
-            random_state: RandomState::new(),
+            random_state,
             hashes_buffer: vec![],
             null: None,
+            compact_on_emit: false,
         }
     }
 
+    /// Compact the data buffers of the array returned by [`Self::into_state`], see
+    /// [`compact_view_array`].
+    ///
+    /// This copies the long values once more on emission, in exchange for not retaining
+    /// partially filled blocks in the emitted state.
+    pub fn with_compact_on_emit(mut self, compact_on_emit: bool) -> Self {
+        self.compact_on_emit = compact_on_emit;
+        self
+    }
+
+    /// The random state used to hash the values of this map
+    pub fn random_state(&self) -> &RandomState {
+        &self.random_state
+    }
+
     /// Return the contents of this map and replace it with a new empty map with
-    /// the same output type
+    /// the same output type, random state and compaction setting
     pub fn take(&mut self) -> Self {
-        let mut new_self = Self::new(self.output_type);
+        let mut new_self = Self::with_random_state(self.output_type, self.random_state.clone())
+            .with_compact_on_emit(self.compact_on_emit);
         std::mem::swap(self, &mut new_self);
         new_self
     }
@@ -175,7 +237,7 @@ where
         let batch_hashes = &mut self.hashes_buffer;
         batch_hashes.clear();
         batch_hashes.resize(values.len(), 0);
-        create_hashes(&[values.clone()], &self.random_state, batch_hashes)
+        create_hashes(std::slice::from_ref(values), &self.random_state, batch_hashes)
             // hash is supported for all types and create_hashes only
             // returns errors for unsupported types
             .unwrap();
@@ -297,7 +359,7 @@ where
         let batch_hashes = &mut self.hashes_buffer;
         batch_hashes.clear();
         batch_hashes.resize(values.len(), 0);
-        create_hashes(&[values.clone()], &self.random_state, batch_hashes)
+        create_hashes(std::slice::from_ref(values), &self.random_state, batch_hashes)
             // hash is supported for all types and create_hashes only
             // returns errors for unsupported types
             .unwrap();
@@ -378,7 +440,7 @@ where
     {
         // Step 1: Compute hashes
         let mut batch_hashes = vec![0u64; values.len()];
-        create_hashes(&[values.clone()], &self.random_state, &mut batch_hashes).unwrap(); // Compute the hashes for the values
+        create_hashes(std::slice::from_ref(values), &self.random_state, &mut batch_hashes).unwrap(); // Compute the hashes for the values
 
         // Step 2: Get payloads for each value
         let values = values.as_byte_view::<B>();
@@ -449,6 +511,11 @@ where
         match self.output_type {
             OutputType::BinaryView => {
                 let array = builder.finish();
+                let array = if self.compact_on_emit {
+                    compact_view_array(array)
+                } else {
+                    array
+                };
 
                 Arc::new(array)
             }
@@ -459,6 +526,11 @@ where
                 // so are all the values that come out
                 let array = builder.finish();
                 let array = unsafe { array.to_string_view_unchecked() };
+                let array = if self.compact_on_emit {
+                    compact_view_array(array)
+                } else {
+                    array
+                };
                 Arc::new(array)
             }
             _ => {
@@ -467,6 +539,43 @@ where
         }
     }
 
+    /// Returns the `n` entries with the largest payloads according to `cmp`, without emitting
+    /// or sorting the whole map.
+    ///
+    /// Each entry is returned as `(index, payload)`, where `index` is the position of its value
+    /// in the array returned by [`Self::into_state`]. Entries are ordered from the largest
+    /// payload, and entries with equal payloads from the first inserted. The null entry is
+    /// included if present.
+    ///
+    /// Runs in `O(len * log(n))` time and `O(n)` memory, keeping the candidates in a bounded
+    /// min-heap whose root is the smallest of them.
+    pub fn top_n_by_payload<F>(&self, n: usize, mut cmp: F) -> Vec<(usize, V)>
+    where
+        F: FnMut(&V, &V) -> Ordering,
+    {
+        let mut order = |a: &(usize, V), b: &(usize, V)| cmp(&a.1, &b.1).then_with(|| b.0.cmp(&a.0));
+        let mut heap: Vec<(usize, V)> = Vec::with_capacity(n.min(self.len()));
+
+        // SAFETY: the table outlives its buckets and is not modified while iterating
+        let entries = unsafe { self.map.iter() }.map(|bucket| {
+            let entry = unsafe { bucket.as_ref() };
+            (entry.view_idx, entry.payload)
+        });
+        let null = self.null.map(|(payload, null_index)| (null_index, payload));
+        for entry in entries.chain(null) {
+            if heap.len() < n {
+                heap.push(entry);
+                let last = heap.len() - 1;
+                sift_up(&mut heap, last, &mut order);
+            } else if n > 0 && order(&entry, &heap[0]).is_gt() {
+                heap[0] = entry;
+                sift_down(&mut heap, 0, &mut order);
+            }
+        }
+        heap.sort_unstable_by(|a, b| order(b, a));
+        heap
+    }
+
     /// Total number of entries (including null, if present)
     pub fn len(&self) -> usize {
         self.non_null_len() + self.null.map(|_| 1).unwrap_or(0)
@@ -500,10 +609,46 @@ where
             .field("view_builder", &self.builder)
             .field("random_state", &self.random_state)
             .field("hashes_buffer", &self.hashes_buffer)
+            .field("compact_on_emit", &self.compact_on_emit)
             .finish()
     }
 }
 
+/// Moves `heap[i]` up until its parent is not greater under `order`, restoring a min-heap
+fn sift_up<T, F>(heap: &mut [T], mut i: usize, order: &mut F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    while i > 0 {
+        let parent = (i - 1) / 2;
+        if order(&heap[i], &heap[parent]).is_ge() {
+            break;
+        }
+        heap.swap(i, parent);
+        i = parent;
+    }
+}
+
+/// Moves `heap[i]` down until its children are not less under `order`, restoring a min-heap
+fn sift_down<T, F>(heap: &mut [T], mut i: usize, order: &mut F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    loop {
+        let mut least = i;
+        for child in [2 * i + 1, 2 * i + 2] {
+            if child < heap.len() && order(&heap[child], &heap[least]).is_lt() {
+                least = child;
+            }
+        }
+        if least == i {
+            break;
+        }
+        heap.swap(i, least);
+        i = least;
+    }
+}
+
 /// Entry in the hash table -- see [`ArrowBytesViewMap`] for more details
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 struct Entry<V>
@@ -526,6 +671,88 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn test_shared_random_state() {
+        let values: ArrayRef = Arc::new(StringViewArray::from(vec![
+            Some("a"),
+            Some("a string longer than twelve bytes"),
+            None,
+        ]));
+        let map: ArrowBytesViewMap<u8> =
+            ArrowBytesViewMap::with_random_state(OutputType::Utf8View, RandomState::with_seeds(1, 2, 3, 4));
+        let mut other: ArrowBytesViewMap<u8> =
+            ArrowBytesViewMap::with_random_state(OutputType::Utf8View, map.random_state().clone());
+
+        let hashes = |state: &RandomState| {
+            let mut hashes = vec![0; values.len()];
+            create_hashes(std::slice::from_ref(&values), state, &mut hashes).unwrap();
+            hashes
+        };
+        assert_eq!(hashes(map.random_state()), hashes(other.random_state()));
+
+        // take() keeps the random state of the map
+        let taken = other.take();
+        assert_eq!(hashes(taken.random_state()), hashes(other.random_state()));
+    }
+
+    #[test]
+    fn test_compact_on_emit() {
+        let long = "a string longer than twelve bytes";
+        let values: ArrayRef = Arc::new(StringViewArray::from(vec![Some("short"), Some(long), None]));
+        let capacity = |array: &ArrayRef| -> usize {
+            array
+                .as_string_view()
+                .data_buffers()
+                .iter()
+                .map(|buffer| buffer.capacity())
+                .sum()
+        };
+
+        let mut map: ArrowBytesViewMap<u8> = ArrowBytesViewMap::new(OutputType::Utf8View);
+        map.insert_or_update(&values, |_| 1, |count| *count += 1);
+        let bloated = map.into_state();
+        assert!(capacity(&bloated) > long.len());
+
+        let mut map: ArrowBytesViewMap<u8> = ArrowBytesViewMap::new(OutputType::Utf8View).with_compact_on_emit(true);
+        map.insert_or_update(&values, |_| 1, |count| *count += 1);
+        let compacted = map.take().into_state();
+        assert_eq!(capacity(&compacted), long.len());
+        assert_eq!(&compacted, &bloated);
+        assert_eq!(&compacted, &values);
+    }
+
+    #[test]
+    fn test_top_n_by_payload() {
+        let values: Vec<Option<String>> = (0..200)
+            .map(|i| (i % 37 != 0).then(|| format!("value {}", (i * 7919) % 113)))
+            .collect();
+        let array: ArrayRef = Arc::new(StringViewArray::from(values.clone()));
+        let mut map: ArrowBytesViewMap<u8> = ArrowBytesViewMap::new(OutputType::Utf8View);
+        map.insert_or_update(&array, |_| 1, |count| *count += 1);
+
+        let all = map.top_n_by_payload(usize::MAX, |a, b| a.cmp(b));
+        assert_eq!(all.len(), map.len());
+        let mut expected = all.clone();
+        expected.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        assert_eq!(all, expected);
+        for n in [0, 1, 5, 17] {
+            assert_eq!(map.top_n_by_payload(n, |a, b| a.cmp(b)), expected[..n]);
+        }
+        // A reversed comparator returns the smallest payloads
+        assert!(map
+            .top_n_by_payload(3, |a, b| b.cmp(a))
+            .iter()
+            .all(|(_, count)| *count == 1));
+
+        // The indexes point at the emitted values, including the null entry
+        let emitted = map.into_state();
+        let emitted = emitted.as_string_view();
+        for (index, count) in expected {
+            let value = emitted.is_valid(index).then(|| emitted.value(index).to_string());
+            assert_eq!(values.iter().filter(|v| **v == value).count(), count as usize);
+        }
+    }
+
     #[test]
     fn test_insert_or_update_count_u8() {
         let values = GenericByteViewArray::from(vec![
@@ -0,0 +1,250 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::fmt::Debug;
+use std::mem;
+
+use arrow::array::{ArrayRef, BooleanArray, OffsetSizeTrait};
+use arrow::compute::filter;
+use datafusion::arrow;
+use datafusion::error::Result;
+
+use super::{ArrowBytesMap, ArrowBytesViewMap};
+
+use crate::compat::OutputType;
+
+/// A map of distinct string or binary values to the `usize` payload indices of a
+/// [`PayloadMap`]
+pub trait IndexMap: Debug {
+    fn new(output_type: OutputType) -> Self;
+
+    /// See [`ArrowBytesMap::insert_if_new`]
+    fn insert_if_new<MP, OP>(&mut self, values: &ArrayRef, make_payload_fn: MP, observe_payload_fn: OP)
+    where
+        MP: FnMut(Option<&[u8]>) -> usize,
+        OP: FnMut(usize);
+
+    fn into_state(self) -> ArrayRef;
+
+    fn size(&self) -> usize;
+}
+
+impl<O: OffsetSizeTrait> IndexMap for ArrowBytesMap<O, usize> {
+    fn new(output_type: OutputType) -> Self {
+        ArrowBytesMap::new(output_type)
+    }
+
+    fn insert_if_new<MP, OP>(&mut self, values: &ArrayRef, make_payload_fn: MP, observe_payload_fn: OP)
+    where
+        MP: FnMut(Option<&[u8]>) -> usize,
+        OP: FnMut(usize),
+    {
+        ArrowBytesMap::insert_if_new(self, values, make_payload_fn, observe_payload_fn)
+    }
+
+    fn into_state(self) -> ArrayRef {
+        ArrowBytesMap::into_state(self)
+    }
+
+    fn size(&self) -> usize {
+        ArrowBytesMap::size(self)
+    }
+}
+
+impl IndexMap for ArrowBytesViewMap<usize> {
+    fn new(output_type: OutputType) -> Self {
+        ArrowBytesViewMap::new(output_type)
+    }
+
+    fn insert_if_new<MP, OP>(&mut self, values: &ArrayRef, make_payload_fn: MP, observe_payload_fn: OP)
+    where
+        MP: FnMut(Option<&[u8]>) -> usize,
+        OP: FnMut(usize),
+    {
+        ArrowBytesViewMap::insert_if_new(self, values, make_payload_fn, observe_payload_fn)
+    }
+
+    fn into_state(self) -> ArrayRef {
+        ArrowBytesViewMap::into_state(self)
+    }
+
+    fn size(&self) -> usize {
+        ArrowBytesViewMap::size(self)
+    }
+}
+
+/// Maps distinct string or binary values to payloads that are not `Copy`, such as sketches.
+///
+/// [`ArrowBytesMap`] and [`ArrowBytesViewMap`] require `Copy` payloads, so this map stores the
+/// index of each payload in a side vector instead. Payloads are in the order their values were
+/// first seen, which is also the order of the values returned by [`Self::into_parts`].
+pub struct PayloadMap<M, P> {
+    map: M,
+    payloads: Vec<P>,
+    /// Payload index of each row of the batch being inserted, reused between batches
+    indices: Vec<usize>,
+}
+
+/// A [`PayloadMap`] of `Utf8` or `Binary` values
+pub type ArrowBytesPayloadMap<O, P> = PayloadMap<ArrowBytesMap<O, usize>, P>;
+
+/// A [`PayloadMap`] of `Utf8View` or `BinaryView` values
+pub type ArrowBytesViewPayloadMap<P> = PayloadMap<ArrowBytesViewMap<usize>, P>;
+
+impl<M: IndexMap, P> PayloadMap<M, P> {
+    pub fn new(output_type: OutputType) -> Self {
+        Self {
+            map: M::new(output_type),
+            payloads: vec![],
+            indices: vec![],
+        }
+    }
+
+    /// Inserts each value of `values`, creating its payload with `make_payload_fn` if it is new,
+    /// then calls `update_payload_fn` with the row index and payload of every value.
+    pub fn update<MP, UP>(&mut self, values: &ArrayRef, mut make_payload_fn: MP, mut update_payload_fn: UP)
+    where
+        MP: FnMut() -> P,
+        UP: FnMut(usize, &mut P),
+    {
+        let first_new = self.payloads.len();
+        let mut next_new = first_new;
+        let indices = &mut self.indices;
+        indices.clear();
+        self.map.insert_if_new(
+            values,
+            |_| {
+                next_new += 1;
+                next_new - 1
+            },
+            |index| indices.push(index),
+        );
+
+        self.payloads.extend((first_new..next_new).map(|_| make_payload_fn()));
+        for (row, &index) in self.indices.iter().enumerate() {
+            update_payload_fn(row, &mut self.payloads[index]);
+        }
+    }
+
+    /// Returns the distinct values, as from [`IndexMap::into_state`], and their payloads
+    pub fn into_parts(self) -> (ArrayRef, Vec<P>) {
+        (self.map.into_state(), self.payloads)
+    }
+
+    /// Returns the contents of this map and replaces it with a new empty map
+    pub fn take(&mut self, output_type: OutputType) -> Self {
+        mem::replace(self, Self::new(output_type))
+    }
+
+    /// Keeps only the values whose index and payload are accepted by `keep`, preserving their
+    /// order.
+    ///
+    /// Returns the values before the removal, as from [`Self::into_parts`], and the new index of
+    /// every former index, `None` for removed values, so that indices held outside the map can
+    /// be remapped.
+    pub fn retain<K>(&mut self, output_type: OutputType, mut keep: K) -> Result<(ArrayRef, Vec<Option<usize>>)>
+    where
+        K: FnMut(usize, &P) -> bool,
+    {
+        let (values, payloads) = self.take(output_type).into_parts();
+        let mut remapped = Vec::with_capacity(payloads.len());
+        let mut kept = vec![];
+        for (index, payload) in payloads.into_iter().enumerate() {
+            if keep(index, &payload) {
+                remapped.push(Some(kept.len()));
+                kept.push(payload);
+            } else {
+                remapped.push(None);
+            }
+        }
+        let mask: BooleanArray = remapped.iter().map(|index| Some(index.is_some())).collect();
+        let kept_values = filter(&values, &mask)?;
+        // The kept values are distinct, so each one is new and takes the next payload in order
+        let mut kept = kept.into_iter();
+        self.update(
+            &kept_values,
+            || kept.next().expect("one payload per kept value"),
+            |_, _| {},
+        );
+        Ok((values, remapped))
+    }
+
+    /// The index of the payload of every row of the last batch passed to [`Self::update`]
+    pub fn indices(&self) -> &[usize] {
+        &self.indices
+    }
+
+    pub fn payloads(&self) -> &[P] {
+        &self.payloads
+    }
+
+    /// Size in bytes of the map, not including the heap allocations of the payloads
+    pub fn size(&self) -> usize {
+        self.map.size()
+            + self.payloads.capacity() * mem::size_of::<P>()
+            + self.indices.capacity() * mem::size_of::<usize>()
+    }
+}
+
+impl<M: Debug, P: Debug> Debug for PayloadMap<M, P> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PayloadMap")
+            .field("map", &self.map)
+            .field("payloads", &self.payloads)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{AsArray, StringArray};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_payload_map() {
+        let mut map = ArrowBytesPayloadMap::<i32, Vec<usize>>::new(OutputType::Utf8);
+        let values: ArrayRef = Arc::new(StringArray::from(vec![Some("a"), None, Some("b"), Some("a")]));
+        map.update(&values, Vec::new, |row, rows| rows.push(row));
+        let values: ArrayRef = Arc::new(StringArray::from(vec![Some("b"), Some("c")]));
+        map.update(&values, Vec::new, |row, rows| rows.push(row));
+
+        let (values, payloads) = map.into_parts();
+        let values: Vec<_> = values.as_string::<i32>().iter().collect();
+        assert_eq!(values, vec![Some("a"), None, Some("b"), Some("c")]);
+        assert_eq!(payloads, vec![vec![0, 3], vec![1], vec![2, 0], vec![1]]);
+    }
+
+    #[test]
+    fn test_retain_remaps_indices() -> Result<()> {
+        let mut map = ArrowBytesPayloadMap::<i32, usize>::new(OutputType::Utf8);
+        let values: ArrayRef = Arc::new(StringArray::from(vec![Some("a"), None, Some("b"), Some("c")]));
+        map.update(&values, || 0, |_, count| *count += 1);
+
+        let (former, remapped) = map.retain(OutputType::Utf8, |index, _| index != 0)?;
+        assert_eq!(former.len(), 4);
+        assert_eq!(remapped, vec![None, Some(0), Some(1), Some(2)]);
+
+        let values: ArrayRef = Arc::new(StringArray::from(vec![Some("c"), Some("a")]));
+        map.update(&values, || 0, |_, count| *count += 1);
+        let (values, payloads) = map.into_parts();
+        let values: Vec<_> = values.as_string::<i32>().iter().collect();
+        assert_eq!(values, vec![None, Some("b"), Some("c"), Some("a")]);
+        assert_eq!(payloads, vec![1, 1, 2, 1]);
+        Ok(())
+    }
+}
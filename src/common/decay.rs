@@ -0,0 +1,177 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+//! Exponential time decay, where a row `age` older than the most recent one weighs
+//! `2^(-age / half_life)`.
+//!
+//! Decayed values are kept along with the time they are decayed to, and only decayed when they
+//! are updated or compared, so that states are mergeable whatever their reference times.
+
+use std::sync::Arc;
+
+use arrow::array::{ArrayRef, AsArray, Float64Array};
+use arrow::compute::cast;
+use arrow::datatypes::{DataType, Int64Type, TimeUnit};
+use datafusion::arrow;
+use datafusion::common::{plan_err, Result, ScalarValue};
+use datafusion::physical_expr::PhysicalExpr;
+
+use crate::common::args::literal_arg;
+
+/// A sum of decayed values as of `time`, the most recent time added
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Decayed {
+    pub value: f64,
+    /// In seconds, or in the unit of integer timestamps. `-inf` until a value is added.
+    pub time: f64,
+}
+
+impl Default for Decayed {
+    fn default() -> Self {
+        Self {
+            value: 0.0,
+            time: f64::NEG_INFINITY,
+        }
+    }
+}
+
+impl Decayed {
+    /// Whether no value was added
+    pub fn is_empty(&self) -> bool {
+        self.time == f64::NEG_INFINITY
+    }
+
+    /// Adds `value` as of `time`, decaying whichever of the two is older to the newer time
+    pub fn add(&mut self, value: f64, time: f64, half_life: f64) {
+        if time >= self.time {
+            self.value = self.value * decay(time - self.time, half_life) + value;
+            self.time = time;
+        } else {
+            self.value += value * decay(self.time - time, half_life);
+        }
+    }
+
+    /// The sum decayed to `time`, which must not be before [`Self::time`]
+    pub fn value_at(&self, time: f64, half_life: f64) -> f64 {
+        self.value * decay(time - self.time, half_life)
+    }
+}
+
+/// The weight of a row `age` old
+pub fn decay(age: f64, half_life: f64) -> f64 {
+    (-age / half_life).exp2()
+}
+
+/// Coerces the `ts` argument of `fn_name` to a timestamp, a date or `Int64`
+pub fn coerce_ts(fn_name: &str, ts_type: &DataType) -> Result<DataType> {
+    match ts_type {
+        DataType::Timestamp(_, _) | DataType::Date32 | DataType::Date64 => Ok(ts_type.clone()),
+        DataType::Null => Ok(DataType::Int64),
+        _ if ts_type.is_integer() => Ok(DataType::Int64),
+        _ => plan_err!("{fn_name} expects a timestamp, date or integer ts, got {ts_type}"),
+    }
+}
+
+/// Seconds per unit of a coerced `ts` argument, `None` for integers which share the unit of
+/// the half-life
+pub fn seconds_per_unit(ts_type: &DataType) -> Option<f64> {
+    match ts_type {
+        DataType::Timestamp(TimeUnit::Second, _) => Some(1.0),
+        DataType::Timestamp(TimeUnit::Millisecond, _) | DataType::Date64 => Some(1e-3),
+        DataType::Timestamp(TimeUnit::Microsecond, _) => Some(1e-6),
+        DataType::Timestamp(TimeUnit::Nanosecond, _) => Some(1e-9),
+        DataType::Date32 => Some(86_400.0),
+        _ => None,
+    }
+}
+
+/// The times of a coerced `ts` argument, in seconds or in the unit of integers
+pub fn times(ts: &ArrayRef, time_scale: f64) -> Result<Float64Array> {
+    let ts = cast(ts, &DataType::Int64)?;
+    Ok(ts.as_primitive::<Int64Type>().unary(|t| t as f64 * time_scale))
+}
+
+/// The literal `half_life` of `fn_name`: an interval without months or a number of seconds if
+/// `time_based`, and a number in the unit of the integer timestamps otherwise
+pub fn half_life_arg(expr: &Arc<dyn PhysicalExpr>, fn_name: &str, time_based: bool) -> Result<f64> {
+    duration_arg(expr, fn_name, "half_life", time_based)
+}
+
+/// The literal positive duration `arg_name` of `fn_name`, in seconds if `time_based`, see
+/// [`half_life_arg`]
+pub fn duration_arg(expr: &Arc<dyn PhysicalExpr>, fn_name: &str, arg_name: &str, time_based: bool) -> Result<f64> {
+    duration(literal_arg(expr, fn_name, arg_name)?, fn_name, arg_name, time_based)
+}
+
+/// The positive duration `arg_name` of `fn_name` of the value of a constant argument, see
+/// [`duration_arg`]
+pub fn duration(value: ScalarValue, fn_name: &str, arg_name: &str, time_based: bool) -> Result<f64> {
+    let duration = match value {
+        ScalarValue::IntervalMonthDayNano(Some(interval)) if time_based && interval.months == 0 => {
+            interval.days as f64 * 86_400.0 + interval.nanoseconds as f64 * 1e-9
+        }
+        ScalarValue::IntervalDayTime(Some(interval)) if time_based => {
+            interval.days as f64 * 86_400.0 + interval.milliseconds as f64 * 1e-3
+        }
+        value if value.data_type().is_numeric() => match value.cast_to(&DataType::Float64)? {
+            ScalarValue::Float64(Some(duration)) => duration,
+            _ => return plan_err!("{fn_name} expects a non-null {arg_name}"),
+        },
+        value => {
+            return plan_err!(
+                "{fn_name} expects a {arg_name} in days, hours, minutes or seconds for timestamps \
+                 and a number for integers, got {value}"
+            )
+        }
+    };
+    if !(duration.is_finite() && duration > 0.0) {
+        return plan_err!("{fn_name} expects a positive {arg_name}, got {duration}");
+    }
+    Ok(duration)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decayed_add_in_any_order() {
+        let mut decayed = Decayed::default();
+        assert!(decayed.is_empty());
+        for (value, time) in [(1.0, 20.0), (1.0, 10.0), (1.0, 20.0), (1.0, 0.0)] {
+            decayed.add(value, time, 10.0);
+        }
+        assert_eq!(decayed.time, 20.0);
+        assert_eq!(decayed.value, 2.75);
+        assert_eq!(decayed.value_at(30.0, 10.0), 1.375);
+
+        let mut other = Decayed::default();
+        other.add(4.0, 40.0, 10.0);
+        other.add(decayed.value, decayed.time, 10.0);
+        assert_eq!(other.value, 4.6875);
+    }
+
+    #[test]
+    fn test_seconds_per_unit() {
+        let ts = Arc::new(arrow::array::Date32Array::from(vec![Some(2), None])) as ArrayRef;
+        let scale = seconds_per_unit(&DataType::Date32).unwrap();
+        assert_eq!(
+            times(&ts, scale).unwrap(),
+            Float64Array::from(vec![Some(172_800.0), None])
+        );
+        assert_eq!(seconds_per_unit(&DataType::Int64), None);
+    }
+}
@@ -0,0 +1,135 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Cancellation of the emission of large accumulator states.
+//!
+//! DataFusion cancels a query by dropping its stream, which only takes effect once the task
+//! awaits. Emitting the state or result of an accumulator holding millions of distinct values
+//! doesn't await and can block a worker for seconds, so accumulators that emit large states
+//! iterate over them with [`CancellationToken::chunked`], which stops the emission soon after
+//! [`CancellationToken::cancel`] is called.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use datafusion::common::{exec_err, Result};
+
+/// Number of entries emitted between two checks of a [`CancellationToken`]
+pub const EMIT_CHUNK_SIZE: usize = 4096;
+
+/// A flag shared between the caller running a query and the accumulators of the query, which
+/// fail their emission once it is set.
+///
+/// Tokens are cheap to clone and clones share the flag. The default token is never cancelled.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Makes the emissions checking this token fail
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Returns an error if the token is cancelled
+    pub fn check(&self) -> Result<()> {
+        if self.is_cancelled() {
+            return exec_err!("Aggregate emission was cancelled");
+        }
+        Ok(())
+    }
+
+    /// Wraps `items` to check this token before every chunk of [`EMIT_CHUNK_SIZE`] items.
+    ///
+    /// The iterator yields `Ok` items until the token is found cancelled, then a single error.
+    pub fn chunked<I: IntoIterator>(&self, items: I) -> Chunked<'_, I::IntoIter> {
+        Chunked {
+            items: items.into_iter(),
+            token: self,
+            position: 0,
+            failed: false,
+        }
+    }
+}
+
+/// Iterator returned by [`CancellationToken::chunked`]
+#[derive(Debug)]
+pub struct Chunked<'a, I> {
+    items: I,
+    token: &'a CancellationToken,
+    position: usize,
+    failed: bool,
+}
+
+impl<I: Iterator> Iterator for Chunked<'_, I> {
+    type Item = Result<I::Item>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        if self.position % EMIT_CHUNK_SIZE == 0 {
+            if let Err(e) = self.token.check() {
+                self.failed = true;
+                return Some(Err(e));
+            }
+        }
+        self.position += 1;
+        self.items.next().map(Ok)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (lower, upper) = self.items.size_hint();
+        (lower, upper.map(|upper| upper + 1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunked_stops_at_the_next_chunk() {
+        let token = CancellationToken::new();
+        let items: Result<Vec<usize>> = token.chunked(0..3 * EMIT_CHUNK_SIZE).collect();
+        assert_eq!(items.unwrap().len(), 3 * EMIT_CHUNK_SIZE);
+
+        let mut emitted = 0;
+        let clone = token.clone();
+        let result: Result<Vec<usize>> = token
+            .chunked(0..3 * EMIT_CHUNK_SIZE)
+            .inspect(|_| {
+                emitted += 1;
+                if emitted == 10 {
+                    clone.cancel();
+                }
+            })
+            .collect();
+        assert!(result.is_err());
+        // The first chunk is completed before the cancellation is noticed
+        assert_eq!(emitted, EMIT_CHUNK_SIZE + 1);
+    }
+}
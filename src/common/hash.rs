@@ -0,0 +1,326 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Cross-engine stable hashing.
+//!
+//! Unlike [`create_hashes`](datafusion::common::hash_utils::create_hashes), which is seeded with a
+//! random state and may change between releases, the functions in this module are stable and
+//! reproduce Spark's `hash()` function (Murmur3 x86_32) bit-for-bit, so values derived from them
+//! match on both engines.
+
+use arrow::array::{Array, ArrayRef, ArrowPrimitiveType, AsArray};
+use arrow::datatypes::{
+    DataType, Date32Type, Date64Type, Decimal128Type, Float32Type, Float64Type, Int16Type, Int32Type, Int64Type,
+    Int8Type, TimeUnit, TimestampMicrosecondType, TimestampMillisecondType, TimestampNanosecondType,
+    TimestampSecondType, UInt16Type, UInt32Type, UInt8Type,
+};
+use datafusion::arrow;
+use datafusion::common::{not_impl_err, ScalarValue};
+use datafusion::error::Result;
+
+/// Seed used by Spark's `hash()` function.
+pub const SPARK_MURMUR3_SEED: i32 = 42;
+
+const C1: u32 = 0xcc9e_2d51;
+const C2: u32 = 0x1b87_3593;
+
+const MILLIS_PER_DAY: i64 = 86_400_000;
+
+fn mix_k1(k1: u32) -> u32 {
+    k1.wrapping_mul(C1).rotate_left(15).wrapping_mul(C2)
+}
+
+fn mix_h1(h1: u32, k1: u32) -> u32 {
+    (h1 ^ k1).rotate_left(13).wrapping_mul(5).wrapping_add(0xe654_6b64)
+}
+
+fn fmix(h1: u32, length: u32) -> u32 {
+    let mut h1 = h1 ^ length;
+    h1 ^= h1 >> 16;
+    h1 = h1.wrapping_mul(0x85eb_ca6b);
+    h1 ^= h1 >> 13;
+    h1 = h1.wrapping_mul(0xc2b2_ae35);
+    h1 ^ (h1 >> 16)
+}
+
+/// Hashes a 32-bit integer, as Spark does for `INT`, `SMALLINT`, `TINYINT`, `BOOLEAN` and `DATE`.
+pub fn spark_hash_int(value: i32, seed: i32) -> i32 {
+    let h1 = mix_h1(seed as u32, mix_k1(value as u32));
+    fmix(h1, 4) as i32
+}
+
+/// Hashes a 64-bit integer, as Spark does for `BIGINT` and `TIMESTAMP`.
+pub fn spark_hash_long(value: i64, seed: i32) -> i32 {
+    let low = value as u32;
+    let high = (value as u64 >> 32) as u32;
+    let h1 = mix_h1(seed as u32, mix_k1(low));
+    let h1 = mix_h1(h1, mix_k1(high));
+    fmix(h1, 8) as i32
+}
+
+/// Hashes a byte slice, as Spark does for `STRING` and `BINARY`.
+///
+/// Note Spark deviates from reference Murmur3 for the trailing bytes: each one is
+/// mixed in individually as a sign-extended integer.
+pub fn spark_hash_bytes(bytes: &[u8], seed: i32) -> i32 {
+    let aligned = bytes.len() - bytes.len() % 4;
+    let mut h1 = seed as u32;
+    for chunk in bytes[..aligned].chunks_exact(4) {
+        let half_word = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        h1 = mix_h1(h1, mix_k1(half_word));
+    }
+    for &byte in &bytes[aligned..] {
+        h1 = mix_h1(h1, mix_k1(byte as i8 as i32 as u32));
+    }
+    fmix(h1, bytes.len() as u32) as i32
+}
+
+fn spark_hash_float(value: f32, seed: i32) -> i32 {
+    // Spark normalizes -0.0 and uses Java's canonical NaN
+    let bits = if value == 0.0 {
+        0
+    } else if value.is_nan() {
+        f32::NAN.to_bits()
+    } else {
+        value.to_bits()
+    };
+    spark_hash_int(bits as i32, seed)
+}
+
+fn spark_hash_double(value: f64, seed: i32) -> i32 {
+    let bits = if value == 0.0 {
+        0
+    } else if value.is_nan() {
+        f64::NAN.to_bits()
+    } else {
+        value.to_bits()
+    };
+    spark_hash_long(bits as i64, seed)
+}
+
+/// Spark hashes high precision decimals via `BigInteger::toByteArray`, the minimal
+/// big-endian two's complement representation of the unscaled value.
+fn spark_hash_big_decimal(value: i128, seed: i32) -> i32 {
+    let bytes = value.to_be_bytes();
+    let mut start = 0;
+    while start < bytes.len() - 1 {
+        let redundant = (bytes[start] == 0x00 && bytes[start + 1] & 0x80 == 0)
+            || (bytes[start] == 0xff && bytes[start + 1] & 0x80 != 0);
+        if !redundant {
+            break;
+        }
+        start += 1;
+    }
+    spark_hash_bytes(&bytes[start..], seed)
+}
+
+fn hash_primitive<T, F>(array: &ArrayRef, hashes: &mut [i32], hash_fn: F)
+where
+    T: ArrowPrimitiveType,
+    F: Fn(T::Native, i32) -> i32,
+{
+    let array = array.as_primitive::<T>();
+    for (i, hash) in hashes.iter_mut().enumerate() {
+        if array.is_valid(i) {
+            *hash = hash_fn(array.value(i), *hash);
+        }
+    }
+}
+
+fn hash_bytes<'a>(values: impl Iterator<Item = Option<&'a [u8]>>, hashes: &mut [i32]) {
+    for (value, hash) in values.zip(hashes.iter_mut()) {
+        if let Some(value) = value {
+            *hash = spark_hash_bytes(value, *hash);
+        }
+    }
+}
+
+/// Updates `hashes` with the Spark-compatible Murmur3 hash of each row in `arrays`.
+///
+/// Mirrors [`create_hashes`](datafusion::common::hash_utils::create_hashes): `hashes` holds the
+/// seed for each row on entry (usually [`SPARK_MURMUR3_SEED`]) and each column is folded in
+/// using the previous value as its seed, which is how Spark hashes multiple columns. NULL values
+/// leave the hash unchanged.
+pub fn spark_murmur3_hash(arrays: &[ArrayRef], hashes: &mut [i32]) -> Result<()> {
+    for array in arrays {
+        match array.data_type() {
+            DataType::Null => {}
+            DataType::Boolean => {
+                let array = array.as_boolean();
+                for (i, hash) in hashes.iter_mut().enumerate() {
+                    if array.is_valid(i) {
+                        *hash = spark_hash_int(array.value(i) as i32, *hash);
+                    }
+                }
+            }
+            DataType::Int8 => hash_primitive::<Int8Type, _>(array, hashes, |v, h| spark_hash_int(v as i32, h)),
+            DataType::Int16 => hash_primitive::<Int16Type, _>(array, hashes, |v, h| spark_hash_int(v as i32, h)),
+            DataType::Int32 => hash_primitive::<Int32Type, _>(array, hashes, spark_hash_int),
+            DataType::Int64 => hash_primitive::<Int64Type, _>(array, hashes, spark_hash_long),
+            DataType::UInt8 => hash_primitive::<UInt8Type, _>(array, hashes, |v, h| spark_hash_int(v as i32, h)),
+            DataType::UInt16 => hash_primitive::<UInt16Type, _>(array, hashes, |v, h| spark_hash_int(v as i32, h)),
+            DataType::UInt32 => hash_primitive::<UInt32Type, _>(array, hashes, |v, h| spark_hash_long(v as i64, h)),
+            DataType::Float32 => hash_primitive::<Float32Type, _>(array, hashes, spark_hash_float),
+            DataType::Float64 => hash_primitive::<Float64Type, _>(array, hashes, spark_hash_double),
+            DataType::Date32 => hash_primitive::<Date32Type, _>(array, hashes, spark_hash_int),
+            DataType::Date64 => hash_primitive::<Date64Type, _>(array, hashes, |v, h| {
+                spark_hash_int(v.div_euclid(MILLIS_PER_DAY) as i32, h)
+            }),
+            // Spark timestamps are microseconds since the epoch
+            DataType::Timestamp(TimeUnit::Second, _) => {
+                hash_primitive::<TimestampSecondType, _>(array, hashes, |v, h| {
+                    spark_hash_long(v.wrapping_mul(1_000_000), h)
+                })
+            }
+            DataType::Timestamp(TimeUnit::Millisecond, _) => {
+                hash_primitive::<TimestampMillisecondType, _>(array, hashes, |v, h| {
+                    spark_hash_long(v.wrapping_mul(1_000), h)
+                })
+            }
+            DataType::Timestamp(TimeUnit::Microsecond, _) => {
+                hash_primitive::<TimestampMicrosecondType, _>(array, hashes, spark_hash_long)
+            }
+            DataType::Timestamp(TimeUnit::Nanosecond, _) => {
+                hash_primitive::<TimestampNanosecondType, _>(array, hashes, |v, h| {
+                    spark_hash_long(v.div_euclid(1_000), h)
+                })
+            }
+            DataType::Decimal128(precision, _) if *precision <= 18 => {
+                hash_primitive::<Decimal128Type, _>(array, hashes, |v, h| spark_hash_long(v as i64, h))
+            }
+            DataType::Decimal128(_, _) => hash_primitive::<Decimal128Type, _>(array, hashes, spark_hash_big_decimal),
+            DataType::Utf8 => hash_bytes(array.as_string::<i32>().iter().map(|v| v.map(str::as_bytes)), hashes),
+            DataType::LargeUtf8 => hash_bytes(array.as_string::<i64>().iter().map(|v| v.map(str::as_bytes)), hashes),
+            DataType::Utf8View => hash_bytes(array.as_string_view().iter().map(|v| v.map(str::as_bytes)), hashes),
+            DataType::Binary => hash_bytes(array.as_binary::<i32>().iter(), hashes),
+            DataType::LargeBinary => hash_bytes(array.as_binary::<i64>().iter(), hashes),
+            DataType::BinaryView => hash_bytes(array.as_binary_view().iter(), hashes),
+            data_type => {
+                return not_impl_err!("Unsupported data type: {:?} for Spark-compatible hash", data_type);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The Spark-compatible Murmur3 hash of a single `value` seeded with `seed`, equal to what
+/// [`spark_murmur3_hash`] computes for a 1-row array holding `value`. A NULL value returns `seed`.
+pub fn spark_murmur3_hash_scalar(value: &ScalarValue, seed: i32) -> Result<i32> {
+    let hash = match value {
+        ScalarValue::Null => seed,
+        ScalarValue::Boolean(v) => v.map_or(seed, |v| spark_hash_int(v as i32, seed)),
+        ScalarValue::Int8(v) => v.map_or(seed, |v| spark_hash_int(v as i32, seed)),
+        ScalarValue::Int16(v) => v.map_or(seed, |v| spark_hash_int(v as i32, seed)),
+        ScalarValue::Int32(v) | ScalarValue::Date32(v) => v.map_or(seed, |v| spark_hash_int(v, seed)),
+        ScalarValue::Int64(v) => v.map_or(seed, |v| spark_hash_long(v, seed)),
+        ScalarValue::UInt8(v) => v.map_or(seed, |v| spark_hash_int(v as i32, seed)),
+        ScalarValue::UInt16(v) => v.map_or(seed, |v| spark_hash_int(v as i32, seed)),
+        ScalarValue::UInt32(v) => v.map_or(seed, |v| spark_hash_long(v as i64, seed)),
+        ScalarValue::Float32(v) => v.map_or(seed, |v| spark_hash_float(v, seed)),
+        ScalarValue::Float64(v) => v.map_or(seed, |v| spark_hash_double(v, seed)),
+        ScalarValue::Date64(v) => v.map_or(seed, |v| spark_hash_int(v.div_euclid(MILLIS_PER_DAY) as i32, seed)),
+        ScalarValue::TimestampSecond(v, _) => v.map_or(seed, |v| spark_hash_long(v.wrapping_mul(1_000_000), seed)),
+        ScalarValue::TimestampMillisecond(v, _) => v.map_or(seed, |v| spark_hash_long(v.wrapping_mul(1_000), seed)),
+        ScalarValue::TimestampMicrosecond(v, _) => v.map_or(seed, |v| spark_hash_long(v, seed)),
+        ScalarValue::TimestampNanosecond(v, _) => v.map_or(seed, |v| spark_hash_long(v.div_euclid(1_000), seed)),
+        ScalarValue::Decimal128(v, precision, _) if *precision <= 18 => {
+            v.map_or(seed, |v| spark_hash_long(v as i64, seed))
+        }
+        ScalarValue::Decimal128(v, _, _) => v.map_or(seed, |v| spark_hash_big_decimal(v, seed)),
+        ScalarValue::Utf8(v) | ScalarValue::LargeUtf8(v) | ScalarValue::Utf8View(v) => {
+            v.as_ref().map_or(seed, |v| spark_hash_bytes(v.as_bytes(), seed))
+        }
+        ScalarValue::Binary(v) | ScalarValue::LargeBinary(v) | ScalarValue::BinaryView(v) => {
+            v.as_ref().map_or(seed, |v| spark_hash_bytes(v, seed))
+        }
+        value => {
+            return not_impl_err!(
+                "Unsupported data type: {:?} for Spark-compatible hash",
+                value.data_type()
+            );
+        }
+    };
+    Ok(hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{Int32Array, StringArray};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_spark_hash_int() {
+        // SELECT hash(1)
+        assert_eq!(spark_hash_int(1, SPARK_MURMUR3_SEED), -559580957);
+    }
+
+    #[test]
+    fn test_spark_hash_long() {
+        // SELECT hash(1L)
+        assert_eq!(spark_hash_long(1, SPARK_MURMUR3_SEED), -1712319331);
+    }
+
+    #[test]
+    fn test_spark_hash_negative_zero() {
+        assert_eq!(
+            spark_hash_double(-0.0, SPARK_MURMUR3_SEED),
+            spark_hash_double(0.0, SPARK_MURMUR3_SEED)
+        );
+    }
+
+    #[test]
+    fn test_spark_murmur3_hash_chains_columns_and_skips_nulls() -> Result<()> {
+        let ints: ArrayRef = Arc::new(Int32Array::from(vec![Some(1), None]));
+        let strings: ArrayRef = Arc::new(StringArray::from(vec![Some("Spark"), Some("Spark")]));
+        let mut hashes = vec![SPARK_MURMUR3_SEED; 2];
+        spark_murmur3_hash(&[ints, strings], &mut hashes)?;
+
+        let first = spark_hash_bytes(b"Spark", spark_hash_int(1, SPARK_MURMUR3_SEED));
+        let second = spark_hash_bytes(b"Spark", SPARK_MURMUR3_SEED);
+        assert_eq!(hashes, vec![first, second]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_spark_murmur3_hash_scalar_matches_arrays() -> Result<()> {
+        let values = [
+            ScalarValue::Null,
+            ScalarValue::Boolean(Some(true)),
+            ScalarValue::Int16(Some(-3)),
+            ScalarValue::Int64(None),
+            ScalarValue::UInt32(Some(u32::MAX)),
+            ScalarValue::Float64(Some(-0.0)),
+            ScalarValue::Date64(Some(-1)),
+            ScalarValue::TimestampNanosecond(Some(-1_500), None),
+            ScalarValue::Decimal128(Some(12_345), 10, 2),
+            ScalarValue::Decimal128(Some(-12_345), 38, 2),
+            ScalarValue::Utf8View(Some("Spark".to_string())),
+            ScalarValue::LargeBinary(Some(vec![0, 1, 2])),
+        ];
+        for value in values {
+            let mut hashes = vec![SPARK_MURMUR3_SEED];
+            spark_murmur3_hash(&[value.to_array()?], &mut hashes)?;
+            assert_eq!(
+                spark_murmur3_hash_scalar(&value, SPARK_MURMUR3_SEED)?,
+                hashes[0],
+                "{value:?}"
+            );
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,177 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Interning of low-cardinality strings across batches.
+//!
+//! Functions producing a few distinct strings over many rows, such as the `key` column of
+//! `unpivot`, repeat every string in every batch. Interned through a [`StringInterner`], they
+//! return dictionary arrays whose keys index values shared by every batch instead, so the
+//! strings are stored once per session rather than once per row, and downstream operators
+//! concatenating or comparing the batches find the same dictionary.
+//!
+//! Interning is opt-in: set `datafusion_functions_extra.intern_strings` in the
+//! [`crate::config::ExtraFunctionsConfig`] of the session, read when queries are planned, or
+//! register the functions with [`crate::package::RegistrationOptions::with_string_interner`].
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use arrow::array::{Array, ArrayRef, DictionaryArray, Int32Array, StringArray};
+use arrow::datatypes::{DataType, Int32Type};
+use datafusion::arrow;
+use datafusion::arrow::compute::cast;
+use datafusion::common::cast::as_string_array;
+use datafusion::common::Result;
+
+/// Default number of distinct strings a [`StringInterner`] keeps
+pub const DEFAULT_MAX_ENTRIES: usize = 1 << 16;
+
+/// The type of interned strings
+pub fn interned_type() -> DataType {
+    DataType::Dictionary(Box::new(DataType::Int32), Box::new(DataType::Utf8))
+}
+
+/// A session-scoped table of the strings interned so far, shared by the functions registered
+/// with it.
+///
+/// Interners are cheap to clone and clones share the table. Once it holds `max_entries`
+/// strings, batches with strings outside the table get a dictionary of their own, so a column
+/// of unexpectedly many distinct strings costs no more than without interning.
+#[derive(Debug, Clone)]
+pub struct StringInterner {
+    state: Arc<Mutex<InternerState>>,
+    max_entries: usize,
+}
+
+#[derive(Debug, Default)]
+struct InternerState {
+    ids: HashMap<String, i32>,
+    values: Vec<String>,
+    /// The values as an array, shared by the batches interned since the last new string
+    snapshot: Option<ArrayRef>,
+}
+
+impl Default for StringInterner {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_ENTRIES)
+    }
+}
+
+impl StringInterner {
+    pub fn new(max_entries: usize) -> Self {
+        Self {
+            state: Arc::default(),
+            max_entries: max_entries.min(i32::MAX as usize),
+        }
+    }
+
+    /// Whether `other` interns into the same table as this interner
+    pub fn shares_table(&self, other: &StringInterner) -> bool {
+        Arc::ptr_eq(&self.state, &other.state)
+    }
+
+    /// Number of distinct strings interned
+    pub fn len(&self) -> usize {
+        self.lock().values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, InternerState> {
+        // The table is consistent between statements, so a panic holding the lock leaves
+        // nothing to recover
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Interns the strings of `values`, of any string or string dictionary type, returning an
+    /// array of [`interned_type`]
+    pub fn intern(&self, values: &ArrayRef) -> Result<ArrayRef> {
+        let values = cast(values, &DataType::Utf8)?;
+        let strings = as_string_array(&values)?;
+        let mut state = self.lock();
+
+        let mut keys = Vec::with_capacity(strings.len());
+        for value in strings.iter() {
+            let Some(value) = value else {
+                keys.push(None);
+                continue;
+            };
+            let id = match state.ids.get(value) {
+                Some(id) => *id,
+                None if state.values.len() < self.max_entries => {
+                    let id = state.values.len() as i32;
+                    state.ids.insert(value.to_string(), id);
+                    state.values.push(value.to_string());
+                    state.snapshot = None;
+                    id
+                }
+                None => {
+                    drop(state);
+                    return Ok(cast(&values, &interned_type())?);
+                }
+            };
+            keys.push(Some(id));
+        }
+
+        let state = &mut *state;
+        let dictionary = state
+            .snapshot
+            .get_or_insert_with(|| Arc::new(StringArray::from_iter_values(&state.values)));
+        let interned = DictionaryArray::<Int32Type>::try_new(Int32Array::from(keys), Arc::clone(dictionary))?;
+        Ok(Arc::new(interned))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::AsArray;
+
+    fn strings(values: &[Option<&str>]) -> ArrayRef {
+        Arc::new(StringArray::from(values.to_vec()))
+    }
+
+    #[test]
+    fn test_batches_share_the_dictionary() -> Result<()> {
+        let interner = StringInterner::default();
+        let first = interner.intern(&strings(&[Some("us"), None, Some("eu"), Some("us")]))?;
+        let second = interner.intern(&strings(&[Some("eu"), Some("us")]))?;
+        assert_eq!(interner.len(), 2);
+
+        let (first, second) = (first.as_dictionary::<Int32Type>(), second.as_dictionary::<Int32Type>());
+        assert!(Arc::ptr_eq(first.values(), second.values()));
+        assert_eq!(first.keys(), &Int32Array::from(vec![Some(0), None, Some(1), Some(0)]));
+        assert_eq!(second.keys(), &Int32Array::from(vec![1, 0]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_full_interner_falls_back_to_batch_dictionaries() -> Result<()> {
+        let interner = StringInterner::new(1);
+        interner.intern(&strings(&[Some("us")]))?;
+        let overflow = interner.intern(&strings(&[Some("eu"), Some("us")]))?;
+        assert_eq!(interner.len(), 1);
+        assert_eq!(overflow.data_type(), &interned_type());
+        assert_eq!(
+            cast(&overflow, &DataType::Utf8)?.as_ref(),
+            strings(&[Some("eu"), Some("us")]).as_ref()
+        );
+        Ok(())
+    }
+}
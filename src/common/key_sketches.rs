@@ -0,0 +1,126 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Distinct count and heavy hitter sketches over the hashes of key columns, shared by the key
+//! diagnostic aggregates.
+
+use ahash::RandomState;
+use arrow::array::{Array, ArrayRef};
+use datafusion::arrow;
+use datafusion::arrow::datatypes::{DataType, Field};
+use datafusion::common::cast::{as_binary_array, as_uint64_array};
+use datafusion::common::hash_utils::create_hashes;
+use datafusion::common::{Result, ScalarValue};
+
+use crate::sketches::hyperloglog::HyperLogLog;
+use crate::sketches::space_saving::SpaceSaving;
+
+/// Seeds hashing the keys, which must be the same for every partial aggregate of a query
+const KEY_HASH_SEEDS: (u64, u64, u64, u64) = (0x5DEE_CE66, 0xB, 0x2545_F491, 0x4F6C_DD1D);
+
+/// Sketches of the rows of one or more key columns, ignoring rows with a NULL key column as
+/// they never join.
+#[derive(Debug)]
+pub struct KeySketches {
+    distinct_keys: HyperLogLog,
+    top_keys: SpaceSaving<u64>,
+    rows: u64,
+    hashes: Vec<u64>,
+}
+
+impl KeySketches {
+    /// Creates sketches reporting the counts of up to `top_k` most frequent keys, overcounting
+    /// them by at most 1% of the rows
+    pub fn new(top_k: usize) -> Self {
+        Self {
+            distinct_keys: HyperLogLog::default(),
+            top_keys: SpaceSaving::new(top_k.saturating_mul(10).max(100)),
+            rows: 0,
+            hashes: vec![],
+        }
+    }
+
+    pub fn state_fields() -> Vec<Field> {
+        vec![
+            Field::new("distinct_keys", DataType::Binary, true),
+            Field::new("top_keys", DataType::Binary, true),
+            Field::new("rows", DataType::UInt64, true),
+        ]
+    }
+
+    /// Adds the rows of the key columns `keys`
+    pub fn update(&mut self, keys: &[ArrayRef]) -> Result<()> {
+        self.hashes.clear();
+        self.hashes.resize(keys[0].len(), 0);
+        let (k0, k1, k2, k3) = KEY_HASH_SEEDS;
+        create_hashes(keys, &RandomState::with_seeds(k0, k1, k2, k3), &mut self.hashes)?;
+        for (row, &hash) in self.hashes.iter().enumerate() {
+            if keys.iter().all(|keys| keys.is_valid(row)) {
+                self.distinct_keys.add_hash(hash);
+                self.top_keys.add(hash, 1);
+                self.rows += 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// Merges the states emitted by [`Self::state`]
+    pub fn merge(&mut self, states: &[ArrayRef]) -> Result<()> {
+        for bytes in as_binary_array(&states[0])?.iter().flatten() {
+            self.distinct_keys.merge(&HyperLogLog::from_bytes(bytes)?)?;
+        }
+        for bytes in as_binary_array(&states[1])?.iter().flatten() {
+            self.top_keys.merge(SpaceSaving::from_bytes(bytes)?);
+        }
+        self.rows += as_uint64_array(&states[2])?.iter().flatten().sum::<u64>();
+        Ok(())
+    }
+
+    pub fn state(&self) -> Vec<ScalarValue> {
+        vec![
+            ScalarValue::Binary(Some(self.distinct_keys.to_bytes())),
+            ScalarValue::Binary(Some(self.top_keys.to_bytes())),
+            ScalarValue::UInt64(Some(self.rows)),
+        ]
+    }
+
+    /// Number of rows added
+    pub fn rows(&self) -> u64 {
+        self.rows
+    }
+
+    /// Estimated number of distinct keys
+    pub fn distinct_keys(&self) -> u64 {
+        self.distinct_keys.count()
+    }
+
+    /// Fraction of the rows holding one of the `k` most frequent keys, NULL if there are no rows
+    pub fn top_share(&self, k: usize) -> Option<f64> {
+        let mut counts: Vec<u64> = self.top_keys.counters().map(|counter| counter.count).collect();
+        counts.sort_unstable_by(|a, b| b.cmp(a));
+        let count: u64 = counts.iter().take(k).sum();
+        // The counts are overestimates, which may add up to more than the rows once merged
+        (self.rows > 0).then(|| (count as f64 / self.rows as f64).min(1.0))
+    }
+
+    pub fn size(&self) -> usize {
+        std::mem::size_of_val(self)
+            + self.distinct_keys.size()
+            + self.top_keys.size()
+            + self.hashes.capacity() * std::mem::size_of::<u64>()
+    }
+}
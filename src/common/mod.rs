@@ -15,5 +15,22 @@
 // specific language governing permissions and limitations
 // under the License.
 
+pub mod args;
+pub mod calendar;
+pub mod coercion;
 pub mod collections;
+pub mod decay;
+pub mod emit;
+pub mod hash;
+pub mod interner;
+pub mod key_sketches;
 pub mod mode;
+pub mod moments;
+pub mod nulls;
+pub mod output_width;
+pub mod pairs;
+pub mod quantile_buffer;
+pub mod samples;
+pub mod sketch_lookup;
+pub mod student_t;
+pub mod tables;
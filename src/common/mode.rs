@@ -16,9 +16,22 @@
 // under the License.
 
 mod bytes;
+mod decay;
+mod dictionary;
+mod groups;
+mod latest;
 mod native;
+mod rolling;
 
 pub use bytes::BytesModeAccumulator;
 pub use bytes::BytesViewModeAccumulator;
+pub use decay::BytesModeDecayAccumulator;
+pub use dictionary::DictionaryModeAccumulator;
+pub use groups::BytesModeGroupsAccumulator;
+pub use groups::PrimitiveModeGroupsAccumulator;
+pub use latest::BytesModeLatestAccumulator;
+pub use latest::PrimitiveModeLatestAccumulator;
 pub use native::FloatModeAccumulator;
 pub use native::PrimitiveModeAccumulator;
+pub use rolling::RollingFrequencies;
+pub use rolling::RollingModeAccumulator;
@@ -24,72 +24,109 @@ use arrow::datatypes::DataType;
 use datafusion::arrow;
 use datafusion::common::cast::as_list_array;
 use datafusion::common::cast::as_primitive_array;
-use datafusion::common::utils::array_into_list_array_nullable;
 use datafusion::error::Result;
 use datafusion::logical_expr::Accumulator;
-use datafusion::physical_expr::binary_map::ArrowBytesSet;
-use datafusion::physical_expr::binary_map::OutputType;
-use datafusion::physical_expr_common::binary_view_map::ArrowBytesViewSet;
 use datafusion::scalar::ScalarValue;
 
-use crate::common::collections::ArrowBytesMap;
-use crate::common::collections::ArrowBytesViewMap;
+use crate::common::collections::compact_view_array;
+use crate::common::collections::{ArrowBytesPayloadMap, ArrowBytesViewPayloadMap, IndexMap, PayloadMap};
+use crate::common::emit::CancellationToken;
+use crate::common::nulls::all_null_or_filtered;
+use crate::compat::{single_row_list, OutputType};
+
+/// Counts every row of `values`, NULL with a negative count so that it is never the mode
+fn update_counts<M: IndexMap>(value_counts: &mut PayloadMap<M, i64>, values: &ArrayRef) {
+    value_counts.update(
+        values,
+        || 0,
+        |row, count| *count = if values.is_null(row) { i64::MIN } else { *count + 1 },
+    );
+}
+
+/// Adds the counts of the mode states `states` to `value_counts`
+fn merge_counts<M: IndexMap>(value_counts: &mut PayloadMap<M, i64>, states: &[ArrayRef]) -> Result<()> {
+    let values_lists = as_list_array(&states[0])?;
+    let counts_lists = as_list_array(&states[1])?;
+
+    for (values, counts) in values_lists.iter().zip(counts_lists.iter()) {
+        let (Some(values), Some(counts)) = (values, counts) else {
+            continue;
+        };
+        let counts = as_primitive_array::<arrow::datatypes::Int64Type>(&counts)?;
+        // NULL keeps its negative sentinel count
+        value_counts.update(
+            &values,
+            || 0,
+            |row, count| *count = count.saturating_add(counts.value(row)),
+        );
+    }
+    Ok(())
+}
+
+/// The mode state of `values` and their `counts`
+fn counts_state(values: ArrayRef, counts: Vec<i64>, cancellation: &CancellationToken) -> Result<Vec<ScalarValue>> {
+    let payloads: Vec<ScalarValue> = cancellation
+        .chunked(counts)
+        .map(|count| Ok(ScalarValue::Int64(Some(count?))))
+        .collect::<Result<_>>()?;
+
+    let values_list = Arc::new(single_row_list(values));
+    let payloads_list = ScalarValue::new_list_nullable(&payloads, &DataType::Int64);
+
+    Ok(vec![ScalarValue::List(values_list), ScalarValue::List(payloads_list)])
+}
+
+/// The index of the first of the most frequent values, NULL's negative count never wins
+fn max_count_index(counts: Vec<i64>, cancellation: &CancellationToken) -> Result<Option<usize>> {
+    let mut max_index: Option<usize> = None;
+    let mut max_count: i64 = 0;
+
+    for (i, count) in cancellation.chunked(counts).enumerate() {
+        let count = count?;
+        if count > max_count {
+            max_count = count;
+            max_index = Some(i);
+        }
+    }
+    Ok(max_index)
+}
 
 #[derive(Debug)]
 pub struct BytesModeAccumulator<O: OffsetSizeTrait> {
-    values: ArrowBytesSet<O>,
-    value_counts: ArrowBytesMap<O, i64>,
+    output_type: OutputType,
+    value_counts: ArrowBytesPayloadMap<O, i64>,
+    cancellation: CancellationToken,
 }
 
 impl<O: OffsetSizeTrait> BytesModeAccumulator<O> {
     pub fn new(output_type: OutputType) -> Self {
         Self {
-            values: ArrowBytesSet::new(output_type),
-            value_counts: ArrowBytesMap::new(output_type),
+            output_type,
+            value_counts: ArrowBytesPayloadMap::new(output_type),
+            cancellation: CancellationToken::default(),
         }
     }
+
+    /// Fails the emission of the state and result once `cancellation` is cancelled
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = cancellation;
+        self
+    }
 }
 
 impl<O: OffsetSizeTrait> Accumulator for BytesModeAccumulator<O> {
     fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
-        if values.is_empty() {
+        if values.is_empty() || all_null_or_filtered(&values[0], None) {
             return Ok(());
         }
 
-        self.values.insert(&values[0]);
-
-        self.value_counts.insert_or_update(
-            &values[0],
-            |maybe_value| {
-                if maybe_value.is_none() {
-                    i64::MIN
-                } else {
-                    1i64
-                }
-            },
-            |count| *count += 1,
-        );
-
+        update_counts(&mut self.value_counts, &values[0]);
         Ok(())
     }
 
     fn state(&mut self) -> Result<Vec<ScalarValue>> {
-        let values = self.values.take().into_state();
-        let payloads: Vec<ScalarValue> = self
-            .value_counts
-            .take()
-            .get_payloads(&values)
-            .into_iter()
-            .map(|count| match count {
-                Some(c) => ScalarValue::Int64(Some(c)),
-                None => ScalarValue::Int64(None),
-            })
-            .collect();
-
-        let values_list = Arc::new(array_into_list_array_nullable(values));
-        let payloads_list = ScalarValue::new_list_nullable(&payloads, &DataType::Int64);
-
-        Ok(vec![ScalarValue::List(values_list), ScalarValue::List(payloads_list)])
+        let (values, counts) = self.value_counts.take(self.output_type).into_parts();
+        counts_state(values, counts, &self.cancellation)
     }
 
     fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
@@ -97,36 +134,13 @@ impl<O: OffsetSizeTrait> Accumulator for BytesModeAccumulator<O> {
             return Ok(());
         }
 
-        let arr = as_list_array(&states[0])?;
-        let counts = as_primitive_array::<arrow::datatypes::Int64Type>(&states[1])?;
-
-        arr.iter().zip(counts.iter()).try_for_each(|(maybe_list, maybe_count)| {
-            if let (Some(list), Some(count)) = (maybe_list, maybe_count) {
-                // Insert or update the count for each value
-                self.value_counts
-                    .insert_or_update(&list, |_| count, |existing_count| *existing_count += count);
-            }
-            Ok(())
-        })
+        merge_counts(&mut self.value_counts, states)
     }
 
     fn evaluate(&mut self) -> Result<ScalarValue> {
-        let mut max_index: Option<usize> = None;
-        let mut max_count: i64 = 0;
-
-        let values = self.values.take().into_state();
-        let counts = self.value_counts.take().get_payloads(&values);
+        let (values, counts) = self.value_counts.take(self.output_type).into_parts();
 
-        for (i, count) in counts.into_iter().enumerate() {
-            if let Some(c) = count {
-                if c > max_count {
-                    max_count = c;
-                    max_index = Some(i);
-                }
-            }
-        }
-
-        match max_index {
+        match max_count_index(counts, &self.cancellation)? {
             Some(index) => {
                 let array = values.as_string::<O>();
                 let mode_value = array.value(index);
@@ -149,64 +163,48 @@ impl<O: OffsetSizeTrait> Accumulator for BytesModeAccumulator<O> {
     }
 
     fn size(&self) -> usize {
-        self.values.size() + self.value_counts.size()
+        self.value_counts.size()
     }
 }
 
 #[derive(Debug)]
 pub struct BytesViewModeAccumulator {
-    values: ArrowBytesViewSet,
-    value_counts: ArrowBytesViewMap<i64>,
+    output_type: OutputType,
+    value_counts: ArrowBytesViewPayloadMap<i64>,
+    cancellation: CancellationToken,
 }
 
 impl BytesViewModeAccumulator {
     pub fn new(output_type: OutputType) -> Self {
         Self {
-            values: ArrowBytesViewSet::new(output_type),
-            value_counts: ArrowBytesViewMap::new(output_type),
+            output_type,
+            value_counts: ArrowBytesViewPayloadMap::new(output_type),
+            cancellation: CancellationToken::default(),
         }
     }
+
+    /// Fails the emission of the state and result once `cancellation` is cancelled
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = cancellation;
+        self
+    }
 }
 
 impl Accumulator for BytesViewModeAccumulator {
     fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
-        if values.is_empty() {
+        if values.is_empty() || all_null_or_filtered(&values[0], None) {
             return Ok(());
         }
 
-        self.values.insert(&values[0]);
-
-        self.value_counts.insert_or_update(
-            &values[0],
-            |maybe_value| {
-                if maybe_value.is_none() {
-                    i64::MIN
-                } else {
-                    1i64
-                }
-            },
-            |count| *count += 1,
-        );
+        update_counts(&mut self.value_counts, &values[0]);
         Ok(())
     }
 
     fn state(&mut self) -> Result<Vec<ScalarValue>> {
-        let values = self.values.take().into_state();
-        let payloads: Vec<ScalarValue> = self
-            .value_counts
-            .take()
-            .get_payloads(&values)
-            .into_iter()
-            .map(|count| match count {
-                Some(c) => ScalarValue::Int64(Some(c)),
-                None => ScalarValue::Int64(None),
-            })
-            .collect();
-
-        let values_list = Arc::new(array_into_list_array_nullable(values));
-        let payloads_list = ScalarValue::new_list_nullable(&payloads, &DataType::Int64);
-
-        Ok(vec![ScalarValue::List(values_list), ScalarValue::List(payloads_list)])
+        let (values, counts) = self.value_counts.take(self.output_type).into_parts();
+        // The state is held until the final aggregation, so don't retain partially filled blocks
+        let values: ArrayRef = Arc::new(compact_view_array(values.as_string_view().clone()));
+        counts_state(values, counts, &self.cancellation)
     }
 
     fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
@@ -214,36 +212,13 @@ impl Accumulator for BytesViewModeAccumulator {
             return Ok(());
         }
 
-        let arr = as_list_array(&states[0])?;
-        let counts = as_primitive_array::<arrow::datatypes::Int64Type>(&states[1])?;
-
-        arr.iter().zip(counts.iter()).try_for_each(|(maybe_list, maybe_count)| {
-            if let (Some(list), Some(count)) = (maybe_list, maybe_count) {
-                // Insert or update the count for each value
-                self.value_counts
-                    .insert_or_update(&list, |_| count, |existing_count| *existing_count += count);
-            }
-            Ok(())
-        })
+        merge_counts(&mut self.value_counts, states)
     }
 
     fn evaluate(&mut self) -> Result<ScalarValue> {
-        let mut max_index: Option<usize> = None;
-        let mut max_count: i64 = 0;
-
-        let values = self.values.take().into_state();
-        let counts = self.value_counts.take().get_payloads(&values);
+        let (values, counts) = self.value_counts.take(self.output_type).into_parts();
 
-        for (i, count) in counts.into_iter().enumerate() {
-            if let Some(c) = count {
-                if c > max_count {
-                    max_count = c;
-                    max_index = Some(i);
-                }
-            }
-        }
-
-        match max_index {
+        match max_count_index(counts, &self.cancellation)? {
             Some(index) => {
                 let array = values.as_string_view();
                 let mode_value = array.value(index);
@@ -258,7 +233,7 @@ impl Accumulator for BytesViewModeAccumulator {
     }
 
     fn size(&self) -> usize {
-        self.values.size() + self.value_counts.size()
+        self.value_counts.size()
     }
 }
 
@@ -407,4 +382,30 @@ mod tests {
         assert_eq!(result, ScalarValue::Utf8View(Some("apple".to_string())));
         Ok(())
     }
+
+    #[test]
+    fn test_mode_accumulator_merge_utf8view() -> Result<()> {
+        // "banana" is more frequent in the first state, "apple" over both states
+        let mut states = vec![];
+        for values in [
+            vec![Some("banana"), None, Some("apple"), Some("banana"), None],
+            vec![Some("cherry"), Some("apple"), None, Some("apple")],
+        ] {
+            let mut acc = BytesViewModeAccumulator::new(OutputType::Utf8View);
+            acc.update_batch(&[Arc::new(GenericByteViewArray::from(values))])?;
+            let state = acc
+                .state()?
+                .iter()
+                .map(|value| value.to_array())
+                .collect::<Result<Vec<_>>>()?;
+            states.push(state);
+        }
+
+        let mut acc = BytesViewModeAccumulator::new(OutputType::Utf8View);
+        for state in &states {
+            acc.merge_batch(state)?;
+        }
+        assert_eq!(acc.evaluate()?, ScalarValue::Utf8View(Some("apple".to_string())));
+        Ok(())
+    }
 }
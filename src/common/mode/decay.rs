@@ -0,0 +1,206 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use arrow::array::{Array, ArrayRef, AsArray, Float64Array, OffsetSizeTrait};
+use arrow::datatypes::Float64Type;
+use datafusion::arrow;
+use datafusion::common::cast::as_list_array;
+use datafusion::error::Result;
+use datafusion::logical_expr::Accumulator;
+use datafusion::scalar::ScalarValue;
+
+use crate::common::collections::ArrowBytesPayloadMap;
+use crate::common::decay::{times, Decayed};
+use crate::common::nulls::all_null_or_filtered;
+use crate::compat::{single_row_list, OutputType};
+
+/// Accumulator for `approx_mode_by_time_decay` over `Utf8` and `LargeUtf8` values.
+///
+/// Every occurrence counts `2^(-age / half_life)`, where `age` is the time elapsed until the most
+/// recent timestamp of all rows. Rows with a NULL value or timestamp are ignored, and if the
+/// decayed frequencies tie the lexicographically smallest value is returned.
+#[derive(Debug)]
+pub struct BytesModeDecayAccumulator<O: OffsetSizeTrait> {
+    value_counts: ArrowBytesPayloadMap<O, Decayed>,
+    /// Seconds per unit of the timestamps, 1 for integer timestamps
+    time_scale: f64,
+    half_life: f64,
+}
+
+impl<O: OffsetSizeTrait> BytesModeDecayAccumulator<O> {
+    /// Creates an accumulator for timestamps of `time_scale` seconds per unit and a `half_life`
+    /// in seconds
+    pub fn new(time_scale: f64, half_life: f64) -> Self {
+        Self {
+            value_counts: ArrowBytesPayloadMap::new(OutputType::Utf8),
+            time_scale,
+            half_life,
+        }
+    }
+}
+
+impl<O: OffsetSizeTrait> Accumulator for BytesModeDecayAccumulator<O> {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        if all_null_or_filtered(&values[0], None) {
+            return Ok(());
+        }
+        let times = times(&values[1], self.time_scale)?;
+        let half_life = self.half_life;
+
+        self.value_counts.update(&values[0], Decayed::default, |row, count| {
+            if times.is_valid(row) {
+                count.add(1.0, times.value(row), half_life)
+            }
+        });
+        Ok(())
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        let (values, counts) = self.value_counts.take(OutputType::Utf8).into_parts();
+        let scores = Float64Array::from_iter_values(counts.iter().map(|c| c.value));
+        let times = Float64Array::from_iter_values(counts.iter().map(|c| c.time));
+
+        Ok(vec![
+            ScalarValue::List(Arc::new(single_row_list(values))),
+            ScalarValue::List(Arc::new(single_row_list(Arc::new(scores)))),
+            ScalarValue::List(Arc::new(single_row_list(Arc::new(times)))),
+        ])
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        let values = as_list_array(&states[0])?;
+        let scores = as_list_array(&states[1])?;
+        let times = as_list_array(&states[2])?;
+        let half_life = self.half_life;
+        for i in 0..values.len() {
+            if values.is_null(i) {
+                continue;
+            }
+            let scores = scores.value(i);
+            let scores = scores.as_primitive::<Float64Type>();
+            let times = times.value(i);
+            let times = times.as_primitive::<Float64Type>();
+            self.value_counts
+                .update(&values.value(i), Decayed::default, |row, count| {
+                    count.add(scores.value(row), times.value(row), half_life)
+                });
+        }
+        Ok(())
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        let (values, counts) = self.value_counts.take(OutputType::Utf8).into_parts();
+        let strings = values.as_string::<O>();
+        // Decays every value to the most recent timestamp, scores are only comparable at a
+        // common time
+        let now = counts.iter().map(|c| c.time).fold(f64::NEG_INFINITY, f64::max);
+
+        let mut best: Option<(usize, f64)> = None;
+        for (i, count) in counts.iter().enumerate() {
+            // NULL values are kept in the map but never counted as the mode
+            if strings.is_null(i) || count.is_empty() {
+                continue;
+            }
+            let score = count.value_at(now, self.half_life);
+            let better = match best {
+                None => true,
+                Some((b, best_score)) => {
+                    score > best_score || (score == best_score && strings.value(i) < strings.value(b))
+                }
+            };
+            if better {
+                best = Some((i, score));
+            }
+        }
+
+        match best {
+            Some((index, _)) => ScalarValue::try_from_array(&values, index),
+            None => ScalarValue::try_from(values.data_type()),
+        }
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self) + self.value_counts.size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{Int64Array, StringArray};
+
+    fn state_arrays(acc: &mut dyn Accumulator) -> Result<Vec<ArrayRef>> {
+        acc.state()?.iter().map(|s| s.to_array()).collect()
+    }
+
+    #[test]
+    fn test_recent_values_outweigh_old_ones() -> Result<()> {
+        // Three old occurrences of "books" weigh 3/8 after three half-lives, less than one
+        // recent "games"
+        let mut acc = BytesModeDecayAccumulator::<i32>::new(1.0, 10.0);
+        acc.update_batch(&[
+            Arc::new(StringArray::from(vec![
+                Some("books"),
+                Some("books"),
+                Some("games"),
+                None,
+            ])),
+            Arc::new(Int64Array::from(vec![Some(0), Some(0), Some(30), Some(40)])),
+        ])?;
+        acc.update_batch(&[
+            Arc::new(StringArray::from(vec!["books", "books"])),
+            Arc::new(Int64Array::from(vec![Some(0), None])),
+        ])?;
+        assert_eq!(acc.evaluate()?, ScalarValue::from("games"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_decays_to_the_latest_time() -> Result<()> {
+        let mut first = BytesModeDecayAccumulator::<i32>::new(1.0, 10.0);
+        first.update_batch(&[
+            Arc::new(StringArray::from(vec!["books", "books", "books"])),
+            Arc::new(Int64Array::from(vec![20, 10, 20])),
+        ])?;
+        let mut second = BytesModeDecayAccumulator::<i32>::new(1.0, 10.0);
+        second.update_batch(&[
+            Arc::new(StringArray::from(vec!["games", "games", "books"])),
+            Arc::new(Int64Array::from(vec![30, 30, 0])),
+        ])?;
+
+        // At 30, books weighs 0.5 + 0.25 + 0.5 + 0.125 and games 2
+        let mut merged = BytesModeDecayAccumulator::<i32>::new(1.0, 10.0);
+        merged.merge_batch(&state_arrays(&mut first)?)?;
+        merged.merge_batch(&state_arrays(&mut second)?)?;
+        assert_eq!(merged.evaluate()?, ScalarValue::from("games"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_no_timestamps() -> Result<()> {
+        let mut acc = BytesModeDecayAccumulator::<i64>::new(1.0, 10.0);
+        acc.update_batch(&[
+            Arc::new(arrow::array::LargeStringArray::from(vec!["books"])),
+            Arc::new(Int64Array::from(vec![None])),
+        ])?;
+        assert_eq!(acc.evaluate()?, ScalarValue::LargeUtf8(None));
+        Ok(())
+    }
+}
@@ -0,0 +1,76 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use arrow::array::ArrayRef;
+use arrow::compute::cast;
+use arrow::datatypes::DataType;
+use datafusion::arrow;
+use datafusion::error::Result;
+use datafusion::logical_expr::Accumulator;
+use datafusion::scalar::ScalarValue;
+
+/// Computes the mode of a `Dictionary` input with an accumulator for its decoded values.
+///
+/// Input batches are cast to `value_type` before being passed to `inner`, whose states are
+/// used unchanged. If `key_type` is set, the result is re-encoded as a single-entry
+/// dictionary with that key type.
+#[derive(Debug)]
+pub struct DictionaryModeAccumulator {
+    inner: Box<dyn Accumulator>,
+    value_type: DataType,
+    key_type: Option<DataType>,
+}
+
+impl DictionaryModeAccumulator {
+    pub fn new(inner: Box<dyn Accumulator>, value_type: DataType, key_type: Option<DataType>) -> Self {
+        Self {
+            inner,
+            value_type,
+            key_type,
+        }
+    }
+}
+
+impl Accumulator for DictionaryModeAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        if values.is_empty() {
+            return Ok(());
+        }
+        let values = cast(&values[0], &self.value_type)?;
+        self.inner.update_batch(&[values])
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        self.inner.state()
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        self.inner.merge_batch(states)
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        let value = self.inner.evaluate()?;
+        match &self.key_type {
+            Some(key_type) => Ok(ScalarValue::Dictionary(Box::new(key_type.clone()), Box::new(value))),
+            None => Ok(value),
+        }
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self) + self.inner.size()
+    }
+}
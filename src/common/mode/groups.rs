@@ -0,0 +1,583 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! [`GroupsAccumulator`]s for `mode`, keeping the value counts of all groups in one accumulator
+//! instead of allocating an [`datafusion::logical_expr::Accumulator`] per group.
+//!
+//! Both support [`EmitTo::First`], so plans aggregating input sorted by the group keys emit
+//! the groups they have completed instead of buffering all of them until the end of the input.
+//! The states have the layout of the states of the per-group accumulators.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use arrow::array::{
+    Array, ArrayRef, ArrowPrimitiveType, AsArray, BooleanArray, GenericStringArray, GenericStringBuilder, Int64Array,
+    ListArray, OffsetSizeTrait, PrimitiveArray,
+};
+use arrow::buffer::OffsetBuffer;
+use arrow::datatypes::{DataType, Field, Int64Type};
+use datafusion::arrow;
+use datafusion::common::cast::{as_list_array, as_primitive_array};
+use datafusion::error::Result;
+
+use crate::common::collections::ArrowBytesPayloadMap;
+use crate::common::emit::CancellationToken;
+use crate::common::nulls::all_null_or_filtered;
+use crate::compat::{EmitTo, GroupsAccumulator, Hashable, OutputType};
+
+/// Whether row `i` is selected by `opt_filter`, see [`all_null_or_filtered`]
+fn selected(opt_filter: Option<&BooleanArray>, i: usize) -> bool {
+    opt_filter.map_or(true, |filter| filter.is_valid(i) && filter.value(i))
+}
+
+/// The state lists of the values and counts of every group, from the flattened values and
+/// counts and the number of entries of every group
+fn state_lists(values: ArrayRef, counts: Vec<i64>, lengths: Vec<usize>) -> Vec<ArrayRef> {
+    let offsets = OffsetBuffer::<i32>::from_lengths(lengths);
+    let value_field = Arc::new(Field::new("item", values.data_type().clone(), true));
+    let count_field = Arc::new(Field::new("item", DataType::Int64, true));
+    vec![
+        Arc::new(ListArray::new(value_field, offsets.clone(), values, None)),
+        Arc::new(ListArray::new(
+            count_field,
+            offsets,
+            Arc::new(Int64Array::from(counts)),
+            None,
+        )),
+    ]
+}
+
+/// Calls `add` with the group, the values and the counts of every selected row of a state
+fn for_each_state_row(
+    states: &[ArrayRef],
+    group_indices: &[usize],
+    opt_filter: Option<&BooleanArray>,
+    mut add: impl FnMut(usize, ArrayRef, &Int64Array) -> Result<()>,
+) -> Result<()> {
+    let (values_lists, counts_lists) = (as_list_array(&states[0])?, as_list_array(&states[1])?);
+    for (i, &group_index) in group_indices.iter().enumerate() {
+        if !selected(opt_filter, i) || values_lists.is_null(i) || counts_lists.is_null(i) {
+            continue;
+        }
+        let counts = counts_lists.value(i);
+        add(
+            group_index,
+            values_lists.value(i),
+            as_primitive_array::<Int64Type>(&counts)?,
+        )?;
+    }
+    Ok(())
+}
+
+/// [`GroupsAccumulator`] for the mode of primitive values, including floats.
+///
+/// Ties are broken by the smallest value, as with [`super::PrimitiveModeAccumulator`].
+#[derive(Debug)]
+pub struct PrimitiveModeGroupsAccumulator<T: ArrowPrimitiveType> {
+    groups: Vec<HashMap<Hashable<T::Native>, i64>>,
+    data_type: DataType,
+    cancellation: CancellationToken,
+}
+
+impl<T: ArrowPrimitiveType> PrimitiveModeGroupsAccumulator<T> {
+    pub fn new(data_type: &DataType) -> Self {
+        Self {
+            groups: vec![],
+            data_type: data_type.clone(),
+            cancellation: CancellationToken::default(),
+        }
+    }
+
+    /// Fails the emission of the state and result once `cancellation` is cancelled
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = cancellation;
+        self
+    }
+}
+
+impl<T> GroupsAccumulator for PrimitiveModeGroupsAccumulator<T>
+where
+    T: ArrowPrimitiveType + Send,
+    T::Native: PartialOrd,
+{
+    fn update_batch(
+        &mut self,
+        values: &[ArrayRef],
+        group_indices: &[usize],
+        opt_filter: Option<&BooleanArray>,
+        total_num_groups: usize,
+    ) -> Result<()> {
+        self.groups.resize_with(total_num_groups, HashMap::new);
+        if all_null_or_filtered(&values[0], opt_filter) {
+            return Ok(());
+        }
+        let values = as_primitive_array::<T>(&values[0])?;
+        for (i, &group_index) in group_indices.iter().enumerate() {
+            if selected(opt_filter, i) && values.is_valid(i) {
+                *self.groups[group_index].entry(Hashable(values.value(i))).or_insert(0) += 1;
+            }
+        }
+        Ok(())
+    }
+
+    fn merge_batch(
+        &mut self,
+        values: &[ArrayRef],
+        group_indices: &[usize],
+        opt_filter: Option<&BooleanArray>,
+        total_num_groups: usize,
+    ) -> Result<()> {
+        self.groups.resize_with(total_num_groups, HashMap::new);
+        for_each_state_row(values, group_indices, opt_filter, |group_index, values, counts| {
+            let group = &mut self.groups[group_index];
+            for (value, count) in as_primitive_array::<T>(&values)?.iter().zip(counts.iter()) {
+                if let (Some(value), Some(count)) = (value, count) {
+                    let total = group.entry(Hashable(value)).or_insert(0);
+                    *total = total.saturating_add(count);
+                }
+            }
+            Ok(())
+        })
+    }
+
+    fn evaluate(&mut self, emit_to: EmitTo) -> Result<ArrayRef> {
+        let groups = emit_to.take_needed(&mut self.groups);
+        let modes = self
+            .cancellation
+            .chunked(&groups)
+            .map(|group| {
+                let mode = group?
+                    .iter()
+                    .max_by(|(a, a_count), (b, b_count)| {
+                        // The smallest value wins ties, so it must compare as the greatest
+                        a_count
+                            .cmp(b_count)
+                            .then_with(|| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal))
+                    })
+                    .map(|(value, _)| value.0);
+                Ok(mode)
+            })
+            .collect::<Result<PrimitiveArray<T>>>()?;
+        Ok(Arc::new(modes.with_data_type(self.data_type.clone())))
+    }
+
+    fn state(&mut self, emit_to: EmitTo) -> Result<Vec<ArrayRef>> {
+        let groups = emit_to.take_needed(&mut self.groups);
+        let (mut values, mut counts, mut lengths) = (vec![], vec![], Vec::with_capacity(groups.len()));
+        for group in self.cancellation.chunked(groups) {
+            let group = group?;
+            lengths.push(group.len());
+            for (value, count) in group {
+                values.push(value.0);
+                counts.push(count);
+            }
+        }
+        let values = PrimitiveArray::<T>::from_iter_values(values).with_data_type(self.data_type.clone());
+        Ok(state_lists(Arc::new(values), counts, lengths))
+    }
+
+    fn size(&self) -> usize {
+        self.groups.capacity() * std::mem::size_of::<HashMap<Hashable<T::Native>, i64>>()
+            + self
+                .groups
+                .iter()
+                .map(|group| group.capacity() * std::mem::size_of::<(Hashable<T::Native>, i64)>())
+                .sum::<usize>()
+    }
+}
+
+/// Count of a value of a group, and the number of distinct values the group had when the value
+/// was first seen, so that ties are broken by the first value seen
+#[derive(Debug, Clone, Copy)]
+struct Count {
+    count: i64,
+    rank: usize,
+}
+
+/// Adds `count` occurrences of the value at `index` to `group`, returning whether the group
+/// had not counted the value yet
+fn add_count(group: &mut HashMap<usize, Count>, index: usize, count: i64) -> bool {
+    let rank = group.len();
+    let entry = group.entry(index).or_insert(Count { count: 0, rank });
+    entry.count = entry.count.saturating_add(count);
+    group.len() > rank
+}
+
+/// The distinct values counted by the groups of a [`BytesModeGroupsAccumulator`], by index,
+/// with the number of groups counting each of them
+#[derive(Debug)]
+struct DistinctValues<O: OffsetSizeTrait> {
+    /// The index of every value
+    map: ArrowBytesPayloadMap<O, ()>,
+    /// The values by index, with NULL as an empty string as no group counts it
+    strings: GenericStringBuilder<O>,
+    /// The number of groups counting every value
+    groups_counting: Vec<usize>,
+    /// The number of values counted by the groups, summed over the groups
+    counted: usize,
+    /// The number of values no group counts
+    unused: usize,
+}
+
+impl<O: OffsetSizeTrait> DistinctValues<O> {
+    fn new() -> Self {
+        Self {
+            map: ArrowBytesPayloadMap::new(OutputType::Utf8),
+            strings: GenericStringBuilder::new(),
+            groups_counting: vec![],
+            counted: 0,
+            unused: 0,
+        }
+    }
+
+    /// The value at `index`
+    fn string(&self, index: usize) -> &str {
+        let offsets = self.strings.offsets_slice();
+        let (start, end) = (offsets[index].as_usize(), offsets[index + 1].as_usize());
+        std::str::from_utf8(&self.strings.values_slice()[start..end]).expect("the values are strings")
+    }
+
+    /// Adds `count` occurrences of the value of `values` at `row` to `groups[group_index]`, for
+    /// every `(row, group_index, count)` of `rows`
+    fn add_counts(
+        &mut self,
+        values: &ArrayRef,
+        groups: &mut [HashMap<usize, Count>],
+        rows: impl IntoIterator<Item = (usize, usize, i64)>,
+    ) {
+        self.map.update(values, || (), |_, _| {});
+        let strings = values.as_string::<O>();
+        for (row, &index) in self.map.indices().iter().enumerate() {
+            // New values take the next index, in the order of their first row
+            if index == self.groups_counting.len() {
+                self.strings.append_value(match strings.is_valid(row) {
+                    true => strings.value(row),
+                    false => "",
+                });
+                self.groups_counting.push(0);
+                self.unused += 1;
+            }
+        }
+        for (row, group_index, count) in rows {
+            let index = self.map.indices()[row];
+            if add_count(&mut groups[group_index], index, count) {
+                if self.groups_counting[index] == 0 {
+                    self.unused -= 1;
+                }
+                self.groups_counting[index] += 1;
+                self.counted += 1;
+            }
+        }
+    }
+
+    /// Releases the values counted by `group`, which was taken from the groups
+    fn release(&mut self, group: &HashMap<usize, Count>) {
+        for &index in group.keys() {
+            self.groups_counting[index] -= 1;
+            if self.groups_counting[index] == 0 {
+                self.unused += 1;
+            }
+        }
+        self.counted -= group.len();
+    }
+
+    /// Removes the values no group counts once they outnumber the values counted by `groups`,
+    /// remapping the indices of `groups`. The removal costs in proportion to both, so it is
+    /// amortized over the emissions that released the removed values.
+    fn remove_unused(&mut self, groups: &mut [HashMap<usize, Count>]) -> Result<()> {
+        if self.unused <= self.counted {
+            return Ok(());
+        }
+        let groups_counting = std::mem::take(&mut self.groups_counting);
+        let (_, remapped) = self
+            .map
+            .retain(OutputType::Utf8, |index, _| groups_counting[index] > 0)?;
+        let mut strings = GenericStringBuilder::new();
+        for (index, &count) in groups_counting.iter().enumerate() {
+            if count > 0 {
+                strings.append_value(self.string(index));
+                self.groups_counting.push(count);
+            }
+        }
+        self.strings = strings;
+        self.unused = 0;
+        for group in groups {
+            *group = group
+                .drain()
+                .map(|(index, count)| (remapped[index].expect("counted values are kept"), count))
+                .collect();
+        }
+        Ok(())
+    }
+
+    /// Size in bytes of the values and their counts
+    fn size(&self) -> usize {
+        self.map.size()
+            + self.strings.values_slice().len()
+            + std::mem::size_of_val(self.strings.offsets_slice())
+            + self.groups_counting.capacity() * std::mem::size_of::<usize>()
+    }
+}
+
+/// [`GroupsAccumulator`] for the mode of `Utf8` and `LargeUtf8` values.
+///
+/// The distinct values of all groups are stored once, and the groups count them by their
+/// index. Every value keeps the number of groups counting it, so emitting groups only visits
+/// the emitted groups. The values no remaining group counts are removed, and the indices of the
+/// remaining groups remapped, once they outnumber the values the remaining groups count: the
+/// memory of a plan emitting groups early stays bounded by its open groups, and emitting a
+/// chunk of groups costs in proportion to the chunk.
+///
+/// Ties are broken by the first value seen by the group, as with [`super::BytesModeAccumulator`].
+#[derive(Debug)]
+pub struct BytesModeGroupsAccumulator<O: OffsetSizeTrait> {
+    values: DistinctValues<O>,
+    groups: Vec<HashMap<usize, Count>>,
+    cancellation: CancellationToken,
+}
+
+impl<O: OffsetSizeTrait> Default for BytesModeGroupsAccumulator<O> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<O: OffsetSizeTrait> BytesModeGroupsAccumulator<O> {
+    pub fn new() -> Self {
+        Self {
+            values: DistinctValues::new(),
+            groups: vec![],
+            cancellation: CancellationToken::default(),
+        }
+    }
+
+    /// Fails the emission of the state and result once `cancellation` is cancelled
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = cancellation;
+        self
+    }
+
+    /// Takes the groups of `emit_to`, releasing the values they counted. The indices of the
+    /// taken groups stay valid until [`DistinctValues::remove_unused`].
+    fn take_groups(&mut self, emit_to: EmitTo) -> Vec<HashMap<usize, Count>> {
+        let emitted = emit_to.take_needed(&mut self.groups);
+        emitted.iter().for_each(|group| self.values.release(group));
+        emitted
+    }
+}
+
+impl<O: OffsetSizeTrait> GroupsAccumulator for BytesModeGroupsAccumulator<O> {
+    fn update_batch(
+        &mut self,
+        values: &[ArrayRef],
+        group_indices: &[usize],
+        opt_filter: Option<&BooleanArray>,
+        total_num_groups: usize,
+    ) -> Result<()> {
+        self.groups.resize_with(total_num_groups, HashMap::new);
+        let values = &values[0];
+        if all_null_or_filtered(values, opt_filter) {
+            return Ok(());
+        }
+        let rows = group_indices
+            .iter()
+            .enumerate()
+            .filter(|&(i, _)| selected(opt_filter, i) && values.is_valid(i))
+            .map(|(i, &group_index)| (i, group_index, 1));
+        self.values.add_counts(values, &mut self.groups, rows);
+        Ok(())
+    }
+
+    fn merge_batch(
+        &mut self,
+        values: &[ArrayRef],
+        group_indices: &[usize],
+        opt_filter: Option<&BooleanArray>,
+        total_num_groups: usize,
+    ) -> Result<()> {
+        self.groups.resize_with(total_num_groups, HashMap::new);
+        for_each_state_row(values, group_indices, opt_filter, |group_index, values, counts| {
+            // The per-group accumulator counts NULL with a negative sentinel
+            let rows = counts
+                .iter()
+                .enumerate()
+                .filter_map(|(i, count)| Some((i, group_index, count?)))
+                .filter(|&(i, _, _)| values.is_valid(i));
+            self.values.add_counts(&values, &mut self.groups, rows);
+            Ok(())
+        })
+    }
+
+    fn evaluate(&mut self, emit_to: EmitTo) -> Result<ArrayRef> {
+        let groups = self.take_groups(emit_to);
+        let modes = self
+            .cancellation
+            .chunked(&groups)
+            .map(|group| {
+                let mode = group?
+                    .iter()
+                    .max_by(|(_, a), (_, b)| a.count.cmp(&b.count).then(b.rank.cmp(&a.rank)))
+                    .map(|(index, _)| self.values.string(*index));
+                Ok(mode)
+            })
+            .collect::<Result<GenericStringArray<O>>>()?;
+        self.values.remove_unused(&mut self.groups)?;
+        Ok(Arc::new(modes))
+    }
+
+    fn state(&mut self, emit_to: EmitTo) -> Result<Vec<ArrayRef>> {
+        let groups = self.take_groups(emit_to);
+        let (mut values, mut counts, mut lengths) = (vec![], vec![], Vec::with_capacity(groups.len()));
+        for group in self.cancellation.chunked(groups) {
+            let mut group: Vec<_> = group?.into_iter().collect();
+            // In the order the group first saw the values, so the merged state breaks ties alike
+            group.sort_unstable_by_key(|(_, count)| count.rank);
+            lengths.push(group.len());
+            for (index, count) in group {
+                values.push(self.values.string(index));
+                counts.push(count.count);
+            }
+        }
+        let values: ArrayRef = Arc::new(GenericStringArray::<O>::from_iter_values(values));
+        self.values.remove_unused(&mut self.groups)?;
+        Ok(state_lists(values, counts, lengths))
+    }
+
+    fn size(&self) -> usize {
+        self.values.size()
+            + self.groups.capacity() * std::mem::size_of::<HashMap<usize, Count>>()
+            + self
+                .groups
+                .iter()
+                .map(|group| group.capacity() * std::mem::size_of::<(usize, Count)>())
+                .sum::<usize>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::array::{AsArray, Float64Array, StringArray};
+    use arrow::datatypes::Float64Type;
+
+    use super::*;
+
+    #[test]
+    fn test_primitive_emits_first_groups() -> Result<()> {
+        let mut acc = PrimitiveModeGroupsAccumulator::<Float64Type>::new(&DataType::Float64);
+        let values: ArrayRef = Arc::new(Float64Array::from(vec![
+            Some(2.0),
+            Some(1.0),
+            Some(3.0),
+            None,
+            Some(3.0),
+            Some(5.0),
+        ]));
+        let filter = BooleanArray::from(vec![true, true, true, true, true, false]);
+        acc.update_batch(&[values], &[0, 0, 1, 1, 1, 2], Some(&filter), 3)?;
+
+        // Ties are broken by the smallest value
+        let first = acc.evaluate(EmitTo::First(2))?;
+        assert_eq!(
+            first.as_primitive::<Float64Type>(),
+            &Float64Array::from(vec![Some(1.0), Some(3.0)])
+        );
+
+        // The remaining group keeps its index shifted down, and merges into it
+        let state = acc.state(EmitTo::All)?;
+        let mut merged = PrimitiveModeGroupsAccumulator::<Float64Type>::new(&DataType::Float64);
+        merged.merge_batch(&state, &[0], None, 1)?;
+        let values: ArrayRef = Arc::new(Float64Array::from(vec![4.0, 4.0]));
+        merged.update_batch(&[values], &[0, 0], None, 1)?;
+        assert_eq!(
+            merged.evaluate(EmitTo::All)?.as_primitive::<Float64Type>(),
+            &Float64Array::from(vec![Some(4.0)])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_bytes_emits_first_groups_and_removes_unused_values() -> Result<()> {
+        let mut acc = BytesModeGroupsAccumulator::<i32>::new();
+        let values: ArrayRef = Arc::new(StringArray::from(vec![
+            Some("a"),
+            Some("b"),
+            Some("c"),
+            None,
+            Some("d"),
+            Some("c"),
+        ]));
+        acc.update_batch(&[values], &[0, 0, 1, 1, 2, 2], None, 3)?;
+
+        // Ties are broken by the first value seen by the group
+        let first = acc.evaluate(EmitTo::First(1))?;
+        assert_eq!(first.as_string::<i32>(), &StringArray::from(vec!["a"]));
+        // "a", "b" and NULL are unused, but kept as long as the remaining groups count as many
+        // values
+        assert_eq!((acc.values.unused, acc.values.counted), (3, 3));
+        assert_eq!(acc.values.groups_counting.len(), 5);
+
+        let values: ArrayRef = Arc::new(StringArray::from(vec!["d", "e", "d"]));
+        // Groups 1 and 2 are now groups 0 and 1
+        acc.update_batch(&[values], &[0, 1, 1], None, 2)?;
+        let state = acc.state(EmitTo::First(1))?;
+        let mut merged = BytesModeGroupsAccumulator::<i32>::new();
+        merged.merge_batch(&state, &[0], None, 1)?;
+        assert_eq!(
+            merged.evaluate(EmitTo::All)?.as_string::<i32>(),
+            &StringArray::from(vec!["c"])
+        );
+
+        let rest = acc.evaluate(EmitTo::All)?;
+        assert_eq!(rest.as_string::<i32>(), &StringArray::from(vec!["d"]));
+        assert_eq!(acc.values.groups_counting.len(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_bytes_emits_chunks_in_a_row() -> Result<()> {
+        let mut acc = BytesModeGroupsAccumulator::<i32>::new();
+        // Every group counts a value of its own twice and the shared value "s" once
+        let (mut values, mut group_indices) = (vec![], vec![]);
+        for group in 0..8 {
+            values.extend([format!("v{group}"), "s".to_string(), format!("v{group}")]);
+            group_indices.extend([group; 3]);
+        }
+        let values: ArrayRef = Arc::new(StringArray::from(values));
+        acc.update_batch(&[values], &group_indices, None, 8)?;
+
+        let mut emitted = vec![];
+        for _ in 0..3 {
+            let chunk = acc.evaluate(EmitTo::First(2))?;
+            emitted.extend(chunk.as_string::<i32>().iter().map(|mode| mode.unwrap().to_string()));
+        }
+        assert_eq!(emitted, ["v0", "v1", "v2", "v3", "v4", "v5"]);
+        // The values of the emitted groups were kept until the third chunk, after which they
+        // outnumbered the 4 values counted by the remaining groups
+        assert_eq!((acc.values.unused, acc.values.counted), (0, 4));
+        assert_eq!(acc.values.groups_counting.len(), 3);
+        assert_eq!(acc.values.string(0), "s");
+
+        // The remaining groups are groups 6 and 7, now 0 and 1
+        let values: ArrayRef = Arc::new(StringArray::from(vec!["s", "s", "s"]));
+        acc.update_batch(&[values], &[1, 1, 1], None, 2)?;
+        let rest = acc.evaluate(EmitTo::All)?;
+        assert_eq!(rest.as_string::<i32>(), &StringArray::from(vec!["v6", "s"]));
+        assert_eq!(acc.values.groups_counting.len(), 0);
+        Ok(())
+    }
+}
@@ -0,0 +1,359 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use arrow::array::{Array, ArrayRef, ArrowNativeTypeOp, ArrowPrimitiveType, AsArray, Int64Array, OffsetSizeTrait};
+use arrow::compute::cast;
+use arrow::datatypes::{DataType, Int64Type};
+use datafusion::arrow;
+use datafusion::common::cast::{as_list_array, as_primitive_array};
+use datafusion::error::Result;
+use datafusion::logical_expr::Accumulator;
+use datafusion::scalar::ScalarValue;
+
+use crate::common::collections::ArrowBytesPayloadMap;
+use crate::common::nulls::all_null_or_filtered;
+use crate::compat::{single_row_list, Hashable, OutputType};
+
+/// The frequency of a value and the most recent timestamp it was seen at.
+///
+/// Values are ranked by frequency first and by recency second, so a value whose timestamps are
+/// all NULL loses every tie against a value with a timestamp.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct LatestCount {
+    count: i64,
+    latest: Option<i64>,
+}
+
+impl LatestCount {
+    fn observe(&mut self, count: i64, latest: Option<i64>) {
+        self.count += count;
+        self.latest = self.latest.max(latest);
+    }
+
+    fn rank(&self) -> (i64, Option<i64>) {
+        (self.count, self.latest)
+    }
+}
+
+/// Casts the ordering argument of `mode_latest` to the `Int64` it is compared as
+fn timestamps(array: &ArrayRef) -> Result<Int64Array> {
+    Ok(cast(array, &DataType::Int64)?.as_primitive::<Int64Type>().clone())
+}
+
+/// Returns the `counts` and `latest` state lists of one accumulator
+fn state_lists(stats: impl Iterator<Item = LatestCount> + Clone) -> [ScalarValue; 2] {
+    let counts = Int64Array::from_iter_values(stats.clone().map(|s| s.count));
+    let latest = Int64Array::from_iter(stats.map(|s| s.latest));
+    [
+        ScalarValue::List(Arc::new(single_row_list(Arc::new(counts)))),
+        ScalarValue::List(Arc::new(single_row_list(Arc::new(latest)))),
+    ]
+}
+
+/// Calls `f` with the values, counts and latest timestamps of every accumulator in `states`
+fn for_each_state<F>(states: &[ArrayRef], mut f: F) -> Result<()>
+where
+    F: FnMut(&ArrayRef, &Int64Array, &Int64Array) -> Result<()>,
+{
+    let values = as_list_array(&states[0])?;
+    let counts = as_list_array(&states[1])?;
+    let latest = as_list_array(&states[2])?;
+    for i in 0..values.len() {
+        if values.is_null(i) {
+            continue;
+        }
+        let counts = counts.value(i);
+        let latest = latest.value(i);
+        f(
+            &values.value(i),
+            as_primitive_array::<Int64Type>(&counts)?,
+            as_primitive_array::<Int64Type>(&latest)?,
+        )?;
+    }
+    Ok(())
+}
+
+/// Accumulator for `mode_latest` over primitive values, including floats.
+///
+/// If frequency and recency both tie, the smallest value is returned.
+#[derive(Debug)]
+pub struct PrimitiveModeLatestAccumulator<T>
+where
+    T: ArrowPrimitiveType,
+{
+    value_counts: HashMap<Hashable<T::Native>, LatestCount>,
+    data_type: DataType,
+}
+
+impl<T> PrimitiveModeLatestAccumulator<T>
+where
+    T: ArrowPrimitiveType,
+{
+    pub fn new(data_type: &DataType) -> Self {
+        Self {
+            value_counts: HashMap::default(),
+            data_type: data_type.clone(),
+        }
+    }
+
+    fn observe(&mut self, value: T::Native, count: i64, latest: Option<i64>) {
+        self.value_counts
+            .entry(Hashable(value))
+            .or_default()
+            .observe(count, latest);
+    }
+}
+
+impl<T> Accumulator for PrimitiveModeLatestAccumulator<T>
+where
+    T: ArrowPrimitiveType + Send + Debug,
+{
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        if all_null_or_filtered(&values[0], None) {
+            return Ok(());
+        }
+        let arr = as_primitive_array::<T>(&values[0])?;
+        let ts = timestamps(&values[1])?;
+
+        for (value, ts) in arr.iter().zip(ts.iter()) {
+            if let Some(value) = value {
+                self.observe(value, 1, ts);
+            }
+        }
+        Ok(())
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        let values = arrow::array::PrimitiveArray::<T>::from_iter_values(self.value_counts.keys().map(|v| v.0))
+            .with_data_type(self.data_type.clone());
+        let [counts, latest] = state_lists(self.value_counts.values().copied());
+
+        Ok(vec![
+            ScalarValue::List(Arc::new(single_row_list(Arc::new(values)))),
+            counts,
+            latest,
+        ])
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        for_each_state(states, |values, counts, latest| {
+            let values = as_primitive_array::<T>(values)?;
+            for i in 0..values.len() {
+                self.observe(
+                    values.value(i),
+                    counts.value(i),
+                    latest.is_valid(i).then(|| latest.value(i)),
+                );
+            }
+            Ok(())
+        })
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        let mut best: Option<(T::Native, LatestCount)> = None;
+
+        for (value, stats) in &self.value_counts {
+            let better = match &best {
+                None => true,
+                Some((best_value, best_stats)) => {
+                    stats.rank().cmp(&best_stats.rank()).then(best_value.compare(value.0))
+                        == std::cmp::Ordering::Greater
+                }
+            };
+            if better {
+                best = Some((value.0, *stats));
+            }
+        }
+
+        ScalarValue::new_primitive::<T>(best.map(|(value, _)| value), &self.data_type)
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self)
+            + self.value_counts.capacity() * std::mem::size_of::<(Hashable<T::Native>, LatestCount)>()
+    }
+}
+
+/// Accumulator for `mode_latest` over `Utf8` and `LargeUtf8` values.
+///
+/// If frequency and recency both tie, the lexicographically smallest value is returned.
+#[derive(Debug)]
+pub struct BytesModeLatestAccumulator<O: OffsetSizeTrait> {
+    value_counts: ArrowBytesPayloadMap<O, LatestCount>,
+}
+
+impl<O: OffsetSizeTrait> BytesModeLatestAccumulator<O> {
+    pub fn new() -> Self {
+        Self {
+            value_counts: ArrowBytesPayloadMap::new(OutputType::Utf8),
+        }
+    }
+}
+
+impl<O: OffsetSizeTrait> Default for BytesModeLatestAccumulator<O> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<O: OffsetSizeTrait> Accumulator for BytesModeLatestAccumulator<O> {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        if all_null_or_filtered(&values[0], None) {
+            return Ok(());
+        }
+        let ts = timestamps(&values[1])?;
+
+        self.value_counts
+            .update(&values[0], LatestCount::default, |row, stats| {
+                stats.observe(1, ts.is_valid(row).then(|| ts.value(row)))
+            });
+        Ok(())
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        let (values, stats) = self.value_counts.take(OutputType::Utf8).into_parts();
+        let [counts, latest] = state_lists(stats.into_iter());
+
+        Ok(vec![
+            ScalarValue::List(Arc::new(single_row_list(values))),
+            counts,
+            latest,
+        ])
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        for_each_state(states, |values, counts, latest| {
+            self.value_counts.update(values, LatestCount::default, |row, stats| {
+                stats.observe(counts.value(row), latest.is_valid(row).then(|| latest.value(row)))
+            });
+            Ok(())
+        })
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        let (values, stats) = self.value_counts.take(OutputType::Utf8).into_parts();
+        let strings = values.as_string::<O>();
+
+        let mut best: Option<usize> = None;
+        for (i, stats_i) in stats.iter().enumerate() {
+            // NULL values are kept in the map but never counted as the mode
+            if strings.is_null(i) {
+                continue;
+            }
+            let better = match best {
+                None => true,
+                Some(b) => {
+                    stats_i
+                        .rank()
+                        .cmp(&stats[b].rank())
+                        .then(strings.value(b).cmp(strings.value(i)))
+                        == std::cmp::Ordering::Greater
+                }
+            };
+            if better {
+                best = Some(i);
+            }
+        }
+
+        match best {
+            Some(index) => ScalarValue::try_from_array(&values, index),
+            None => ScalarValue::try_from(values.data_type()),
+        }
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self) + self.value_counts.size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{Float64Array, StringArray, TimestampMillisecondArray};
+    use arrow::datatypes::Float64Type;
+
+    fn state_arrays(acc: &mut dyn Accumulator) -> Result<Vec<ArrayRef>> {
+        acc.state()?.iter().map(|s| s.to_array()).collect()
+    }
+
+    #[test]
+    fn test_primitive_mode_latest_breaks_ties_by_recency() -> Result<()> {
+        let values: ArrayRef = Arc::new(Float64Array::from(vec![
+            Some(1.0),
+            Some(2.0),
+            Some(2.0),
+            Some(1.0),
+            None,
+        ]));
+        let ts: ArrayRef = Arc::new(TimestampMillisecondArray::from(vec![
+            Some(40),
+            Some(10),
+            Some(30),
+            None,
+            Some(99),
+        ]));
+
+        let mut acc = PrimitiveModeLatestAccumulator::<Float64Type>::new(&DataType::Float64);
+        acc.update_batch(&[values, ts])?;
+        assert_eq!(acc.evaluate()?, ScalarValue::Float64(Some(1.0)));
+
+        // One more occurrence of 2.0 breaks the tie on frequency alone
+        let mut merged = PrimitiveModeLatestAccumulator::<Float64Type>::new(&DataType::Float64);
+        merged.merge_batch(&state_arrays(&mut acc)?)?;
+        merged.update_batch(&[
+            Arc::new(Float64Array::from(vec![2.0])),
+            Arc::new(TimestampMillisecondArray::from(vec![None])),
+        ])?;
+        assert_eq!(merged.evaluate()?, ScalarValue::Float64(Some(2.0)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_bytes_mode_latest_merge() -> Result<()> {
+        let mut first = BytesModeLatestAccumulator::<i32>::new();
+        first.update_batch(&[
+            Arc::new(StringArray::from(vec![Some("pixel"), Some("iphone"), None])),
+            Arc::new(Int64Array::from(vec![5, 1, 10])),
+        ])?;
+        let mut second = BytesModeLatestAccumulator::<i32>::new();
+        second.update_batch(&[
+            Arc::new(StringArray::from(vec!["iphone", "pixel"])),
+            Arc::new(Int64Array::from(vec![Some(3), None])),
+        ])?;
+
+        let mut merged = BytesModeLatestAccumulator::<i32>::new();
+        merged.merge_batch(&state_arrays(&mut first)?)?;
+        merged.merge_batch(&state_arrays(&mut second)?)?;
+        assert_eq!(merged.evaluate()?, ScalarValue::from("pixel"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_bytes_mode_latest_empty() -> Result<()> {
+        let mut acc = BytesModeLatestAccumulator::<i64>::new();
+        acc.update_batch(&[
+            Arc::new(arrow::array::LargeStringArray::from(vec![None::<&str>])),
+            Arc::new(Int64Array::from(vec![1])),
+        ])?;
+        assert_eq!(acc.evaluate()?, ScalarValue::LargeUtf8(None));
+        Ok(())
+    }
+}
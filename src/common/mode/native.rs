@@ -19,14 +19,18 @@ use std::collections::HashMap;
 use std::fmt::Debug;
 use std::hash::Hash;
 
-use datafusion::common::cast::as_primitive_array;
+use datafusion::common::cast::{as_list_array, as_primitive_array};
 use datafusion::error::Result;
 
 use arrow::{
     array::{ArrayRef, ArrowPrimitiveType},
     datatypes::DataType,
 };
-use datafusion::{arrow, logical_expr::Accumulator, physical_expr::aggregate::utils::Hashable, scalar::ScalarValue};
+use datafusion::{arrow, logical_expr::Accumulator, scalar::ScalarValue};
+
+use crate::common::emit::CancellationToken;
+use crate::common::nulls::all_null_or_filtered;
+use crate::compat::Hashable;
 
 #[derive(Debug)]
 pub struct PrimitiveModeAccumulator<T>
@@ -36,6 +40,7 @@ where
 {
     value_counts: HashMap<T::Native, i64>,
     data_type: DataType,
+    cancellation: CancellationToken,
 }
 
 impl<T> PrimitiveModeAccumulator<T>
@@ -47,8 +52,15 @@ where
         Self {
             value_counts: HashMap::default(),
             data_type: data_type.clone(),
+            cancellation: CancellationToken::default(),
         }
     }
+
+    /// Fails the emission of the state and result once `cancellation` is cancelled
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = cancellation;
+        self
+    }
 }
 
 impl<T> Accumulator for PrimitiveModeAccumulator<T>
@@ -57,7 +69,7 @@ where
     T::Native: Eq + Hash + Clone + PartialOrd + Debug,
 {
     fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
-        if values.is_empty() {
+        if values.is_empty() || all_null_or_filtered(&values[0], None) {
             return Ok(());
         }
         let arr = as_primitive_array::<T>(&values[0])?;
@@ -72,9 +84,9 @@ where
 
     fn state(&mut self) -> Result<Vec<ScalarValue>> {
         let values: Vec<ScalarValue> = self
-            .value_counts
-            .keys()
-            .map(|key| ScalarValue::new_primitive::<T>(Some(*key), &self.data_type))
+            .cancellation
+            .chunked(self.value_counts.keys())
+            .map(|key| ScalarValue::new_primitive::<T>(Some(*key?), &self.data_type))
             .collect::<Result<Vec<_>>>()?;
 
         let frequencies: Vec<ScalarValue> = self
@@ -97,14 +109,20 @@ where
             return Ok(());
         }
 
-        let values_array = as_primitive_array::<T>(&states[0])?;
-        let counts_array = as_primitive_array::<arrow::datatypes::Int64Type>(&states[1])?;
-
-        for i in 0..values_array.len() {
-            let value = values_array.value(i);
-            let count = counts_array.value(i);
-            let entry = self.value_counts.entry(value).or_insert(0);
-            *entry += count;
+        let values_lists = as_list_array(&states[0])?;
+        let counts_lists = as_list_array(&states[1])?;
+
+        for (values, counts) in values_lists.iter().zip(counts_lists.iter()) {
+            let (Some(values), Some(counts)) = (values, counts) else {
+                continue;
+            };
+            let values_array = as_primitive_array::<T>(&values)?;
+            let counts_array = as_primitive_array::<arrow::datatypes::Int64Type>(&counts)?;
+            for (value, count) in values_array.iter().zip(counts_array.iter()) {
+                if let (Some(value), Some(count)) = (value, count) {
+                    *self.value_counts.entry(value).or_insert(0) += count;
+                }
+            }
         }
 
         Ok(())
@@ -114,7 +132,8 @@ where
         let mut max_value: Option<T::Native> = None;
         let mut max_count: i64 = 0;
 
-        self.value_counts.iter().for_each(|(value, &count)| {
+        for entry in self.cancellation.chunked(&self.value_counts) {
+            let (value, &count) = entry?;
             match count.cmp(&max_count) {
                 std::cmp::Ordering::Greater => {
                     max_value = Some(*value);
@@ -129,7 +148,7 @@ where
                 }
                 _ => {} // Do nothing if count is less than max_count
             }
-        });
+        }
 
         match max_value {
             Some(val) => ScalarValue::new_primitive::<T>(Some(val), &self.data_type),
@@ -149,6 +168,7 @@ where
 {
     value_counts: HashMap<Hashable<T::Native>, i64>,
     data_type: DataType,
+    cancellation: CancellationToken,
 }
 
 impl<T> FloatModeAccumulator<T>
@@ -159,8 +179,15 @@ where
         Self {
             value_counts: HashMap::default(),
             data_type: data_type.clone(),
+            cancellation: CancellationToken::default(),
         }
     }
+
+    /// Fails the emission of the state and result once `cancellation` is cancelled
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = cancellation;
+        self
+    }
 }
 
 impl<T> Accumulator for FloatModeAccumulator<T>
@@ -169,7 +196,7 @@ where
     T::Native: PartialOrd + Debug + Clone,
 {
     fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
-        if values.is_empty() {
+        if values.is_empty() || all_null_or_filtered(&values[0], None) {
             return Ok(());
         }
 
@@ -185,9 +212,9 @@ where
 
     fn state(&mut self) -> Result<Vec<ScalarValue>> {
         let values: Vec<ScalarValue> = self
-            .value_counts
-            .keys()
-            .map(|key| ScalarValue::new_primitive::<T>(Some(key.0), &self.data_type))
+            .cancellation
+            .chunked(self.value_counts.keys())
+            .map(|key| ScalarValue::new_primitive::<T>(Some(key?.0), &self.data_type))
             .collect::<Result<Vec<_>>>()?;
 
         let frequencies: Vec<ScalarValue> = self
@@ -210,13 +237,20 @@ where
             return Ok(());
         }
 
-        let values_array = as_primitive_array::<T>(&states[0])?;
-        let counts_array = as_primitive_array::<arrow::datatypes::Int64Type>(&states[1])?;
-
-        for i in 0..values_array.len() {
-            let count = counts_array.value(i);
-            let entry = self.value_counts.entry(Hashable(values_array.value(i))).or_insert(0);
-            *entry += count;
+        let values_lists = as_list_array(&states[0])?;
+        let counts_lists = as_list_array(&states[1])?;
+
+        for (values, counts) in values_lists.iter().zip(counts_lists.iter()) {
+            let (Some(values), Some(counts)) = (values, counts) else {
+                continue;
+            };
+            let values_array = as_primitive_array::<T>(&values)?;
+            let counts_array = as_primitive_array::<arrow::datatypes::Int64Type>(&counts)?;
+            for (value, count) in values_array.iter().zip(counts_array.iter()) {
+                if let (Some(value), Some(count)) = (value, count) {
+                    *self.value_counts.entry(Hashable(value)).or_insert(0) += count;
+                }
+            }
         }
 
         Ok(())
@@ -226,7 +260,8 @@ where
         let mut max_value: Option<T::Native> = None;
         let mut max_count: i64 = 0;
 
-        self.value_counts.iter().for_each(|(value, &count)| {
+        for entry in self.cancellation.chunked(&self.value_counts) {
+            let (value, &count) = entry?;
             match count.cmp(&max_count) {
                 std::cmp::Ordering::Greater => {
                     max_value = Some(value.0);
@@ -241,7 +276,7 @@ where
                 }
                 _ => {} // Do nothing if count is less than max_count
             }
-        });
+        }
 
         match max_value {
             Some(val) => ScalarValue::new_primitive::<T>(Some(val), &self.data_type),
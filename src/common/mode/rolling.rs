@@ -0,0 +1,280 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::sync::Arc;
+
+use arrow::array::{Array, ArrayRef, Int64Array};
+use arrow::datatypes::DataType;
+use arrow::row::{RowConverter, SortField};
+use datafusion::arrow;
+use datafusion::common::cast::{as_int64_array, as_list_array};
+use datafusion::common::{exec_err, Result, ScalarValue};
+use datafusion::logical_expr::Accumulator;
+
+use crate::compat::single_row_list;
+
+/// Frequencies of values that can be retracted, for sliding window frames.
+///
+/// The values are counted in the row format, so a single implementation supports every type
+/// that can be ordered, and values whose count drops to zero are removed. The most frequent
+/// values are found with a heap that is updated lazily: every change of a count pushes a new
+/// entry, and the outdated ones are dropped when they reach the top.
+#[derive(Debug)]
+pub struct RollingFrequencies {
+    converter: RowConverter,
+    counts: HashMap<Box<[u8]>, i64>,
+    heap: BinaryHeap<HeapEntry>,
+}
+
+/// A count of a value when it was pushed to the heap of [`RollingFrequencies`], outdated if it
+/// changed since
+#[derive(Debug, PartialEq, Eq)]
+struct HeapEntry {
+    count: i64,
+    row: Box<[u8]>,
+}
+
+impl Ord for HeapEntry {
+    /// The most frequent values first, and the smallest ones on ties
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.count.cmp(&other.count).then_with(|| other.row.cmp(&self.row))
+    }
+}
+
+impl PartialOrd for HeapEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl RollingFrequencies {
+    pub fn try_new(data_type: &DataType) -> Result<Self> {
+        Ok(Self {
+            converter: RowConverter::new(vec![SortField::new(data_type.clone())])?,
+            counts: HashMap::new(),
+            heap: BinaryHeap::new(),
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.counts.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.counts.clear();
+        self.heap.clear();
+    }
+
+    /// Adds `delta(index)` to the count of the value at `index` of `values`, for every non-null
+    /// value, removing the values whose count drops to zero
+    pub fn add(&mut self, values: &ArrayRef, delta: impl Fn(usize) -> i64) -> Result<()> {
+        let rows = self.converter.convert_columns(&[Arc::clone(values)])?;
+        for (index, row) in rows.iter().enumerate() {
+            if values.is_null(index) {
+                continue;
+            }
+            let count = match self.counts.get_mut(row.as_ref()) {
+                Some(count) => {
+                    *count += delta(index);
+                    *count
+                }
+                None => *self.counts.entry(row.as_ref().into()).or_insert(delta(index)),
+            };
+            if count <= 0 {
+                self.counts.remove(row.as_ref());
+            } else {
+                self.heap.push(HeapEntry {
+                    count,
+                    row: row.as_ref().into(),
+                });
+            }
+        }
+        // Rebuild the heap once it is mostly outdated entries, to bound its size
+        if self.heap.len() > 2 * self.counts.len() + 64 {
+            self.heap = self
+                .counts
+                .iter()
+                .map(|(row, &count)| HeapEntry {
+                    count,
+                    row: row.clone(),
+                })
+                .collect();
+        }
+        Ok(())
+    }
+
+    /// The `k` most frequent values with their counts, the smallest values first on ties
+    pub fn top_k(&mut self, k: usize) -> Vec<(Box<[u8]>, i64)> {
+        let mut top = Vec::with_capacity(k.min(self.counts.len()));
+        let mut seen = HashSet::new();
+        while top.len() < k {
+            let Some(entry) = self.heap.pop() else {
+                break;
+            };
+            // A value can have several current entries if its count went back to a previous one
+            if self.counts.get(&entry.row) == Some(&entry.count) && seen.insert(entry.row.clone()) {
+                top.push((entry.row, entry.count));
+            }
+        }
+        self.heap.extend(top.iter().map(|(row, count)| HeapEntry {
+            count: *count,
+            row: row.clone(),
+        }));
+        top
+    }
+
+    /// Converts values in the row format back to an array
+    pub fn values<'a>(&self, rows: impl IntoIterator<Item = &'a Box<[u8]>>) -> Result<ArrayRef> {
+        let parser = self.converter.parser();
+        let mut columns = self
+            .converter
+            .convert_rows(rows.into_iter().map(|row| parser.parse(row)))?;
+        Ok(columns.remove(0))
+    }
+
+    pub fn size(&self) -> usize {
+        std::mem::size_of_val(self)
+            + self.converter.size()
+            + self.counts.capacity() * std::mem::size_of::<(Box<[u8]>, i64)>()
+            + self.counts.keys().map(|row| row.len()).sum::<usize>()
+            + self.heap.capacity() * std::mem::size_of::<HeapEntry>()
+            + self.heap.iter().map(|entry| entry.row.len()).sum::<usize>()
+    }
+}
+
+/// Accumulator of the mode that supports retracting values, for sliding window frames.
+///
+/// The values are counted by [`RollingFrequencies`], and ties go to the smallest value.
+/// [`Accumulator::evaluate`] doesn't consume the counts, as it is called once per frame.
+#[derive(Debug)]
+pub struct RollingModeAccumulator {
+    data_type: DataType,
+    frequencies: RollingFrequencies,
+}
+
+impl RollingModeAccumulator {
+    pub fn try_new(data_type: &DataType) -> Result<Self> {
+        Ok(Self {
+            data_type: data_type.clone(),
+            frequencies: RollingFrequencies::try_new(data_type)?,
+        })
+    }
+}
+
+impl Accumulator for RollingModeAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        self.frequencies.add(&values[0], |_| 1)
+    }
+
+    fn retract_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        self.frequencies.add(&values[0], |_| -1)
+    }
+
+    fn supports_retract_batch(&self) -> bool {
+        true
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        let (values, counts) = (as_list_array(&states[0])?, as_list_array(&states[1])?);
+        for (values, counts) in values.iter().zip(counts.iter()) {
+            let (Some(values), Some(counts)) = (values, counts) else {
+                continue;
+            };
+            if values.len() != counts.len() {
+                return exec_err!(
+                    "rolling_mode state has {} values but {} frequencies",
+                    values.len(),
+                    counts.len()
+                );
+            }
+            let counts = as_int64_array(&counts)?;
+            self.frequencies.add(&values, |index| counts.value(index))?;
+        }
+        Ok(())
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        let counts = &self.frequencies.counts;
+        let values = self.frequencies.values(counts.keys())?;
+        let counts = Int64Array::from_iter_values(counts.values().copied());
+        Ok(vec![
+            ScalarValue::List(Arc::new(single_row_list(values))),
+            ScalarValue::List(Arc::new(single_row_list(Arc::new(counts)))),
+        ])
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        match self.frequencies.top_k(1).first() {
+            Some((row, _)) => ScalarValue::try_from_array(&self.frequencies.values([row])?, 0),
+            None => ScalarValue::try_from(&self.data_type),
+        }
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self) - std::mem::size_of_val(&self.frequencies) + self.frequencies.size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::StringArray;
+
+    #[test]
+    fn test_rolling_mode_retract() -> Result<()> {
+        let mut acc = RollingModeAccumulator::try_new(&DataType::Utf8)?;
+        let values: ArrayRef = Arc::new(StringArray::from(vec![
+            Some("b"),
+            Some("a"),
+            None,
+            Some("b"),
+            Some("a"),
+        ]));
+        acc.update_batch(&[values])?;
+        // "a" and "b" tie, the smallest wins
+        assert_eq!(acc.evaluate()?, ScalarValue::Utf8(Some("a".into())));
+
+        acc.retract_batch(&[Arc::new(StringArray::from(vec![Some("a"), None]))])?;
+        assert_eq!(acc.evaluate()?, ScalarValue::Utf8(Some("b".into())));
+
+        acc.retract_batch(&[Arc::new(StringArray::from(vec!["b", "a", "b"]))])?;
+        assert!(acc.frequencies.is_empty());
+        assert_eq!(acc.evaluate()?, ScalarValue::Utf8(None));
+        Ok(())
+    }
+
+    #[test]
+    fn test_rolling_frequencies_top_k() -> Result<()> {
+        let mut frequencies = RollingFrequencies::try_new(&DataType::Int64)?;
+        let top_k = |frequencies: &mut RollingFrequencies, k| -> Result<ArrayRef> {
+            let top = frequencies.top_k(k);
+            frequencies.values(top.iter().map(|(row, _)| row))
+        };
+        let values: ArrayRef = Arc::new(Int64Array::from(vec![3, 1, 3, 2, 1, 3]));
+        frequencies.add(&values, |_| 1)?;
+        assert_eq!(top_k(&mut frequencies, 2)?.as_ref(), &Int64Array::from(vec![3, 1]));
+
+        // The outdated entries of 3 are skipped, and 3 is counted once after going back to 2
+        frequencies.add(&(Arc::new(Int64Array::from(vec![3, 3])) as ArrayRef), |_| -1)?;
+        frequencies.add(&(Arc::new(Int64Array::from(vec![3, 2])) as ArrayRef), |_| 1)?;
+        assert_eq!(top_k(&mut frequencies, 5)?.as_ref(), &Int64Array::from(vec![1, 2, 3]));
+        assert_eq!(top_k(&mut frequencies, 1)?.as_ref(), &Int64Array::from(vec![1]));
+        Ok(())
+    }
+}
@@ -0,0 +1,465 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::collections::VecDeque;
+
+use arrow::array::{Array, ArrayRef, Float64Array, UInt64Array};
+use datafusion::arrow;
+use datafusion::arrow::datatypes::{DataType, Field};
+use datafusion::common::{downcast_value, DataFusionError, Result, ScalarValue};
+
+use crate::common::nulls::all_null_or_filtered;
+use crate::sketches::format::{self, SketchKind};
+
+/// Count, mean and the second to fourth central moments (as sums of powered deviations) of a
+/// stream of values.
+///
+/// Values are accumulated in a single pass with the streaming update of [Welford] extended to
+/// higher moments by [Pébay], and sketches are combined with the pairwise formulas of
+/// [Chan et al.]. Unlike summing raw powers of the input, this does not lose precision when the
+/// mean is large relative to the variance.
+///
+/// [Welford]: https://en.wikipedia.org/wiki/Algorithms_for_calculating_variance#Welford's_online_algorithm
+/// [Pébay]: https://www.osti.gov/biblio/1028931
+/// [Chan et al.]: https://en.wikipedia.org/wiki/Algorithms_for_calculating_variance#Parallel_algorithm
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct MomentsSketch {
+    count: u64,
+    mean: f64,
+    m2: f64,
+    m3: f64,
+    m4: f64,
+}
+
+impl MomentsSketch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a sketch from its raw parts, as produced by [`Self::state`]
+    pub fn from_parts(count: u64, mean: f64, m2: f64, m3: f64, m4: f64) -> Self {
+        Self {
+            count,
+            mean,
+            m2,
+            m3,
+            m4,
+        }
+    }
+
+    /// Serializes the sketch in the versioned format of [`crate::sketches::format`]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        format::encode(SketchKind::Moments, |writer| {
+            writer.put_u64(self.count);
+            for value in [self.mean, self.m2, self.m3, self.m4] {
+                writer.put_f64(value);
+            }
+        })
+    }
+
+    /// Deserializes a sketch written by [`Self::to_bytes`]
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        format::decode(bytes, SketchKind::Moments, |_version, reader| {
+            let count = reader.get_u64()?;
+            Ok(Self::from_parts(
+                count,
+                reader.get_f64()?,
+                reader.get_f64()?,
+                reader.get_f64()?,
+                reader.get_f64()?,
+            ))
+        })
+    }
+
+    /// Fields of the intermediate aggregate state, in the order of [`Self::state`]
+    pub fn state_fields() -> Vec<Field> {
+        vec![
+            Field::new("count", DataType::UInt64, true),
+            Field::new("mean", DataType::Float64, true),
+            Field::new("m2", DataType::Float64, true),
+            Field::new("m3", DataType::Float64, true),
+            Field::new("m4", DataType::Float64, true),
+        ]
+    }
+
+    /// Intermediate aggregate state, see [`Self::state_fields`]
+    pub fn state(&self) -> Vec<ScalarValue> {
+        vec![
+            ScalarValue::from(self.count),
+            ScalarValue::from(self.mean),
+            ScalarValue::from(self.m2),
+            ScalarValue::from(self.m3),
+            ScalarValue::from(self.m4),
+        ]
+    }
+
+    /// Merges every sketch in `states`, the arrays of [`Self::state_fields`]
+    pub fn merge_state(&mut self, states: &[ArrayRef]) -> Result<()> {
+        if all_null_or_filtered(&states[0], None) {
+            return Ok(());
+        }
+        let counts = downcast_value!(states[0], UInt64Array);
+        let means = downcast_value!(states[1], Float64Array);
+        let m2s = downcast_value!(states[2], Float64Array);
+        let m3s = downcast_value!(states[3], Float64Array);
+        let m4s = downcast_value!(states[4], Float64Array);
+
+        for i in 0..counts.len() {
+            if counts.is_null(i) {
+                continue;
+            }
+            self.merge(&Self::from_parts(
+                counts.value(i),
+                means.value(i),
+                m2s.value(i),
+                m3s.value(i),
+                m4s.value(i),
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn update(&mut self, value: f64) {
+        let n1 = self.count as f64;
+        self.count += 1;
+        let n = self.count as f64;
+
+        let delta = value - self.mean;
+        let delta_n = delta / n;
+        let delta_n2 = delta_n * delta_n;
+        let term1 = delta * delta_n * n1;
+
+        self.mean += delta_n;
+        self.m4 += term1 * delta_n2 * (n * n - 3.0 * n + 3.0) + 6.0 * delta_n2 * self.m2 - 4.0 * delta_n * self.m3;
+        self.m3 += term1 * delta_n * (n - 2.0) - 3.0 * delta_n * self.m2;
+        self.m2 += term1;
+    }
+
+    /// Removes a value previously added with [`Self::update`], the inverse of the update.
+    ///
+    /// Retracting subtracts and so loses precision over time, see [`SlidingMomentsSketch`].
+    pub fn retract(&mut self, value: f64) {
+        if self.count <= 1 {
+            *self = Self::default();
+            return;
+        }
+
+        let n = self.count as f64;
+        let n1 = n - 1.0;
+        let mean = self.mean + (self.mean - value) / n1;
+
+        let delta = value - mean;
+        let delta_n = delta / n;
+        let delta_n2 = delta_n * delta_n;
+        let term1 = delta * delta_n * n1;
+
+        let m2 = self.m2 - term1;
+        let m3 = self.m3 - (term1 * delta_n * (n - 2.0) - 3.0 * delta_n * m2);
+        let m4 = self.m4 - (term1 * delta_n2 * (n * n - 3.0 * n + 3.0) + 6.0 * delta_n2 * m2 - 4.0 * delta_n * m3);
+
+        self.count -= 1;
+        self.mean = mean;
+        self.m2 = m2;
+        self.m3 = m3;
+        self.m4 = m4;
+    }
+
+    pub fn merge(&mut self, other: &Self) {
+        if other.count == 0 {
+            return;
+        }
+        if self.count == 0 {
+            *self = *other;
+            return;
+        }
+
+        let na = self.count as f64;
+        let nb = other.count as f64;
+        let n = na + nb;
+        let delta = other.mean - self.mean;
+        let delta2 = delta * delta;
+
+        let m4 = self.m4
+            + other.m4
+            + delta2 * delta2 * na * nb * (na * na - na * nb + nb * nb) / (n * n * n)
+            + 6.0 * delta2 * (na * na * other.m2 + nb * nb * self.m2) / (n * n)
+            + 4.0 * delta * (na * other.m3 - nb * self.m3) / n;
+        let m3 = self.m3
+            + other.m3
+            + delta2 * delta * na * nb * (na - nb) / (n * n)
+            + 3.0 * delta * (na * other.m2 - nb * self.m2) / n;
+        let m2 = self.m2 + other.m2 + delta2 * na * nb / n;
+
+        self.count += other.count;
+        self.mean += delta * nb / n;
+        self.m2 = m2;
+        self.m3 = m3;
+        self.m4 = m4;
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Mean of the values, `None` if there are none
+    pub fn mean(&self) -> Option<f64> {
+        (self.count > 0).then_some(self.mean)
+    }
+
+    /// Population variance, `None` if there are no values
+    pub fn variance_pop(&self) -> Option<f64> {
+        (self.count > 0).then(|| self.m2 / self.count as f64)
+    }
+
+    /// Sample variance, `None` if there are fewer than two values
+    pub fn variance_sample(&self) -> Option<f64> {
+        (self.count > 1).then(|| self.m2 / (self.count - 1) as f64)
+    }
+
+    /// Sample standard deviation divided by the mean, `None` if there are fewer than two values
+    /// or the mean is zero
+    pub fn coefficient_of_variation(&self) -> Option<f64> {
+        let mean = self.mean().filter(|mean| *mean != 0.0)?;
+        Some(self.variance_sample()?.sqrt() / mean)
+    }
+
+    /// Standard error of the mean, the sample standard deviation divided by `sqrt(n)`, `None` if
+    /// there are fewer than two values
+    pub fn standard_error(&self) -> Option<f64> {
+        Some((self.variance_sample()? / self.count as f64).sqrt())
+    }
+
+    /// Skewness without bias correction, `None` if the variance is zero
+    pub fn skewness_pop(&self) -> Option<f64> {
+        if self.count < 1 || self.m2 <= 0.0 {
+            return None;
+        }
+        let n = self.count as f64;
+        Some(n.sqrt() * self.m3 / self.m2.powf(1.5))
+    }
+
+    /// Excess kurtosis (Fisher’s definition) without bias correction, `None` if the variance is
+    /// zero
+    pub fn kurtosis_pop(&self) -> Option<f64> {
+        if self.count < 1 || self.m2 <= 0.0 {
+            return None;
+        }
+        Some(self.count as f64 * self.m4 / (self.m2 * self.m2) - 3.0)
+    }
+}
+
+/// Minimum number of retractions between two periodic rebuilds of a [`SlidingMomentsSketch`]
+const MIN_RETRACTIONS_BEFORE_REBUILD: usize = 64;
+
+/// `m2` falling below this fraction of its peak means most of its digits cancelled out
+const DRIFT_TOLERANCE: f64 = 1e-10;
+
+/// A [`MomentsSketch`] over a sliding window, for aggregates evaluated over window frames.
+///
+/// Values are added and retracted incrementally. Retracting cancels digits, so the sketch is
+/// rebuilt from the buffered window when `m2` loses most of its magnitude (e.g. the window
+/// becomes constant) or goes negative, and periodically once as many values were retracted as
+/// remain in the window, which keeps the amortized cost of a retraction constant.
+#[derive(Debug, Default)]
+pub struct SlidingMomentsSketch {
+    moments: MomentsSketch,
+    window: VecDeque<f64>,
+    retractions: usize,
+    peak_m2: f64,
+}
+
+impl SlidingMomentsSketch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn moments(&self) -> &MomentsSketch {
+        &self.moments
+    }
+
+    pub fn update(&mut self, value: f64) {
+        self.moments.update(value);
+        self.window.push_back(value);
+        self.peak_m2 = self.peak_m2.max(self.moments.m2);
+    }
+
+    /// Removes the oldest value of the window, which must be `value`
+    pub fn retract(&mut self, value: f64) {
+        self.window.pop_front();
+        self.moments.retract(value);
+        self.retractions += 1;
+
+        if self.has_drifted() {
+            self.rebuild();
+        }
+    }
+
+    fn has_drifted(&self) -> bool {
+        self.moments.m2 < 0.0
+            || self.moments.m4 < 0.0
+            || self.moments.m2 < self.peak_m2 * DRIFT_TOLERANCE
+            || self.retractions >= self.window.len().max(MIN_RETRACTIONS_BEFORE_REBUILD)
+    }
+
+    fn rebuild(&mut self) {
+        self.moments = MomentsSketch::new();
+        self.window.iter().for_each(|value| self.moments.update(*value));
+        self.retractions = 0;
+        self.peak_m2 = self.moments.m2;
+    }
+
+    /// Size of the buffered window in bytes, not including `self`
+    pub fn size(&self) -> usize {
+        self.window.capacity() * std::mem::size_of::<f64>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sketch_of(values: &[f64]) -> MomentsSketch {
+        let mut sketch = MomentsSketch::new();
+        values.iter().for_each(|v| sketch.update(*v));
+        sketch
+    }
+
+    fn assert_close(actual: Option<f64>, expected: f64) {
+        let actual = actual.expect("expected a value");
+        assert!(
+            (actual - expected).abs() <= 1e-6 * expected.abs().max(1.0),
+            "expected {expected}, got {actual}"
+        );
+    }
+
+    #[test]
+    fn test_moments_large_mean_small_variance() {
+        // Reference values for [1, 2, 4, 8] computed with exact rational arithmetic
+        for shift in [0.0, 1e9] {
+            let sketch = sketch_of(&[shift + 1.0, shift + 2.0, shift + 4.0, shift + 8.0]);
+            assert_close(sketch.mean(), shift + 3.75);
+            assert_close(sketch.variance_pop(), 7.1875);
+            assert_close(sketch.variance_sample(), 9.583333333333334);
+            assert_close(sketch.skewness_pop(), 0.6568077344996993);
+            assert_close(sketch.kurtosis_pop(), -1.0989792060491494);
+        }
+    }
+
+    #[test]
+    fn test_moments_dispersion() {
+        let sketch = sketch_of(&[1.0, 2.0, 4.0, 8.0]);
+        assert_close(sketch.coefficient_of_variation(), 0.8255189164891872);
+        assert_close(sketch.standard_error(), 1.547847968417226);
+
+        assert_eq!(sketch_of(&[3.0]).standard_error(), None);
+        assert_eq!(sketch_of(&[-1.0, 1.0]).coefficient_of_variation(), None);
+    }
+
+    #[test]
+    fn test_moments_bytes_round_trip() -> Result<()> {
+        for sketch in [MomentsSketch::new(), sketch_of(&[1e9 + 1.0, -0.0, 4.0, 1e-300])] {
+            let bytes = sketch.to_bytes();
+            assert_eq!(bytes.len(), format::HEADER_SIZE + 40);
+            assert_eq!(bytes[format::HEADER_SIZE..][..8], sketch.count().to_le_bytes());
+            assert_eq!(MomentsSketch::from_bytes(&bytes)?, sketch);
+            assert!(MomentsSketch::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_moments_merge_matches_single_pass() {
+        let values: Vec<f64> = (0..100).map(|i| 1e8 + ((i * 37) % 11) as f64 * 0.5).collect();
+        let expected = sketch_of(&values);
+
+        let mut merged = MomentsSketch::new();
+        for chunk in values.chunks(7) {
+            merged.merge(&sketch_of(chunk));
+        }
+        merged.merge(&MomentsSketch::new());
+
+        assert_eq!(merged.count(), expected.count());
+        assert_close(merged.mean(), expected.mean().unwrap());
+        assert_close(merged.variance_pop(), expected.variance_pop().unwrap());
+        assert_close(merged.skewness_pop(), expected.skewness_pop().unwrap());
+        assert_close(merged.kurtosis_pop(), expected.kurtosis_pop().unwrap());
+    }
+
+    #[test]
+    fn test_moments_retract_matches_single_pass() {
+        let values: Vec<f64> = (0..50).map(|i| 1e6 + ((i * 37) % 11) as f64 * 0.25).collect();
+        let mut sketch = sketch_of(&values);
+        values[..20].iter().for_each(|v| sketch.retract(*v));
+
+        let expected = sketch_of(&values[20..]);
+        assert_eq!(sketch.count(), expected.count());
+        assert_close(sketch.mean(), expected.mean().unwrap());
+        assert_close(sketch.variance_pop(), expected.variance_pop().unwrap());
+        assert_close(sketch.skewness_pop(), expected.skewness_pop().unwrap());
+        assert_close(sketch.kurtosis_pop(), expected.kurtosis_pop().unwrap());
+
+        values[20..].iter().for_each(|v| sketch.retract(*v));
+        assert_eq!(sketch, MomentsSketch::new());
+    }
+
+    #[test]
+    fn test_sliding_moments_over_large_frame() {
+        let frame = 1000;
+        let values: Vec<f64> = (0..5000).map(|i| 1e9 + ((i * 7919) % 1013) as f64).collect();
+
+        let mut sliding = SlidingMomentsSketch::new();
+        for (i, value) in values.iter().enumerate() {
+            sliding.update(*value);
+            if i >= frame {
+                sliding.retract(values[i - frame]);
+            }
+            if i >= frame && i % 250 == 0 {
+                let expected = sketch_of(&values[i + 1 - frame..=i]);
+                assert_eq!(sliding.moments().count(), frame as u64);
+                assert_close(sliding.moments().variance_pop(), expected.variance_pop().unwrap());
+                assert_close(sliding.moments().skewness_pop(), expected.skewness_pop().unwrap());
+                assert_close(sliding.moments().kurtosis_pop(), expected.kurtosis_pop().unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn test_sliding_moments_rebuilds_when_window_becomes_constant() {
+        let mut sliding = SlidingMomentsSketch::new();
+        for value in [1e9, -1e9, 5.0, 5.0, 5.0] {
+            sliding.update(value);
+        }
+        sliding.retract(1e9);
+        sliding.retract(-1e9);
+
+        assert_eq!(sliding.moments().variance_pop(), Some(0.0));
+        assert_eq!(sliding.moments().kurtosis_pop(), None);
+    }
+
+    #[test]
+    fn test_moments_empty_and_constant() {
+        let empty = MomentsSketch::new();
+        assert_eq!(empty.mean(), None);
+        assert_eq!(empty.variance_pop(), None);
+        assert_eq!(empty.kurtosis_pop(), None);
+
+        let constant = sketch_of(&[1e12; 5]);
+        assert_eq!(constant.variance_pop(), Some(0.0));
+        assert_eq!(constant.skewness_pop(), None);
+        assert_eq!(constant.kurtosis_pop(), None);
+    }
+}
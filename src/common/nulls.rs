@@ -0,0 +1,68 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use arrow::array::{Array, BooleanArray};
+use datafusion::arrow;
+
+/// Returns `true` if no row of `values` is both non-null and selected by `opt_filter`, in which
+/// case an accumulator can return before touching its state.
+///
+/// `opt_filter` follows the `GroupsAccumulator` convention: a row is selected only if the
+/// filter is `true` (not `false` or NULL). Checking uses the validity bitmaps only and is
+/// much cheaper than the per-value loop it guards on sparse inputs.
+pub fn all_null_or_filtered(values: &dyn Array, opt_filter: Option<&BooleanArray>) -> bool {
+    if values.logical_null_count() == values.len() {
+        return true;
+    }
+    let Some(filter) = opt_filter else {
+        return false;
+    };
+
+    let selected = match filter.nulls() {
+        Some(nulls) => filter.values() & nulls.inner(),
+        None => filter.values().clone(),
+    };
+    match values.logical_nulls() {
+        Some(nulls) => (&selected & nulls.inner()).count_set_bits() == 0,
+        None => selected.count_set_bits() == 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{Int64Array, NullArray};
+
+    #[test]
+    fn test_all_null_or_filtered_without_filter() {
+        assert!(all_null_or_filtered(&Int64Array::from(Vec::<i64>::new()), None));
+        assert!(all_null_or_filtered(&Int64Array::from(vec![None, None]), None));
+        assert!(all_null_or_filtered(&NullArray::new(3), None));
+        assert!(!all_null_or_filtered(&Int64Array::from(vec![None, Some(1)]), None));
+    }
+
+    #[test]
+    fn test_all_null_or_filtered_with_filter() {
+        let values = Int64Array::from(vec![Some(1), None, Some(3)]);
+
+        let filter = BooleanArray::from(vec![Some(false), Some(true), None]);
+        assert!(all_null_or_filtered(&values, Some(&filter)));
+
+        let filter = BooleanArray::from(vec![Some(false), Some(true), Some(true)]);
+        assert!(!all_null_or_filtered(&values, Some(&filter)));
+    }
+}
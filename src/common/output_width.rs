@@ -0,0 +1,151 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! The width of the offsets of the strings and lists an aggregate emits.
+//!
+//! Every aggregate emitting strings or lists can be configured with an [`OutputWidth`] through
+//! its `new_with_output_width` constructor: `string_agg_ext`, `array_agg_ext`,
+//! `list_flatten_agg`, `listagg`, `group_concat`, `struct_agg`, `collect_set` and
+//! `array_union_agg`. The map aggregates, `map_agg` and `map_union_agg`, are exempt as Arrow
+//! has no `Map` with 64-bit offsets; they fail with an error once a map exceeds the 32-bit
+//! offsets instead.
+
+use std::sync::Arc;
+
+use arrow::array::{ArrayRef, GenericListArray, OffsetSizeTrait};
+use arrow::buffer::OffsetBuffer;
+use arrow::datatypes::{DataType, Field, FieldRef};
+use datafusion::arrow;
+use datafusion::common::cast::{as_large_list_array, as_list_array};
+use datafusion::common::{exec_err, Result, ScalarValue};
+
+/// Whether an aggregate emitting strings or lists uses 32-bit or 64-bit offsets.
+///
+/// The output type is fixed when the query is planned, so an aggregate can't switch to the
+/// large variants once a batch turns out to exceed the 32-bit offsets; aggregates that may
+/// emit more than 2 GiB per batch should be configured with [`OutputWidth::Large`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum OutputWidth {
+    /// `Utf8` and `List`, whose offsets address up to `i32::MAX` bytes or elements per array
+    #[default]
+    Regular,
+    /// `LargeUtf8` and `LargeList`, with 64-bit offsets
+    Large,
+}
+
+impl OutputWidth {
+    /// The width of the offsets `O`
+    pub fn of<O: OffsetSizeTrait>() -> Self {
+        if O::IS_LARGE {
+            OutputWidth::Large
+        } else {
+            OutputWidth::Regular
+        }
+    }
+
+    /// The string type of this width
+    pub fn string_type(self) -> DataType {
+        match self {
+            OutputWidth::Regular => DataType::Utf8,
+            OutputWidth::Large => DataType::LargeUtf8,
+        }
+    }
+
+    /// The list type of this width with items `field`
+    pub fn list_type(self, field: FieldRef) -> DataType {
+        match self {
+            OutputWidth::Regular => DataType::List(field),
+            OutputWidth::Large => DataType::LargeList(field),
+        }
+    }
+
+    /// The largest offset an array of this width can address
+    pub fn max_offset(self) -> usize {
+        match self {
+            OutputWidth::Regular => i32::MAX as usize,
+            OutputWidth::Large => i64::MAX as usize,
+        }
+    }
+
+    /// Fails with an error naming `fn_name` if an array of this width can't hold `len` bytes
+    /// or elements, rather than letting the array builder panic on overflow
+    pub fn check_len(self, len: usize, fn_name: &str) -> Result<()> {
+        if len <= self.max_offset() {
+            return Ok(());
+        }
+        match self {
+            OutputWidth::Regular => exec_err!(
+                "{fn_name} output of {len} bytes or elements exceeds the 32-bit offsets of its output, \
+                configure it with OutputWidth::Large"
+            ),
+            OutputWidth::Large => exec_err!("{fn_name} output of {len} bytes or elements exceeds the 64-bit offsets"),
+        }
+    }
+
+    /// `value` as a string of this width, failing with an error naming `fn_name` if it is
+    /// longer than its offsets can address
+    pub fn string_scalar(self, value: Option<String>, fn_name: &str) -> Result<ScalarValue> {
+        self.check_len(value.as_ref().map_or(0, String::len), fn_name)?;
+        Ok(match self {
+            OutputWidth::Regular => ScalarValue::Utf8(value),
+            OutputWidth::Large => ScalarValue::LargeUtf8(value),
+        })
+    }
+
+    /// `values` as a single list of this width, failing with an error naming `fn_name` if it
+    /// has more elements than its offsets can address
+    pub fn list_scalar(self, values: ArrayRef, fn_name: &str) -> Result<ScalarValue> {
+        self.check_len(values.len(), fn_name)?;
+        let field = Arc::new(Field::new("item", values.data_type().clone(), true));
+        Ok(match self {
+            OutputWidth::Regular => ScalarValue::List(Arc::new(single_row_list::<i32>(field, values))),
+            OutputWidth::Large => ScalarValue::LargeList(Arc::new(single_row_list::<i64>(field, values))),
+        })
+    }
+
+    /// A NULL list of this width with items of `item_type`
+    pub fn null_list(self, item_type: &DataType) -> Result<ScalarValue> {
+        ScalarValue::try_from(self.list_type(Arc::new(Field::new("item", item_type.clone(), true))))
+    }
+}
+
+fn single_row_list<O: OffsetSizeTrait>(field: FieldRef, values: ArrayRef) -> GenericListArray<O> {
+    GenericListArray::new(field, OffsetBuffer::from_lengths([values.len()]), values, None)
+}
+
+/// The non-null lists of `lists`, of either width
+pub fn list_values(lists: &ArrayRef) -> Result<Vec<ArrayRef>> {
+    Ok(match lists.data_type() {
+        DataType::LargeList(_) => as_large_list_array(lists)?.iter().flatten().collect(),
+        _ => as_list_array(lists)?.iter().flatten().collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_len_at_2gb_boundary() {
+        let boundary = i32::MAX as usize;
+        assert!(OutputWidth::Regular.check_len(boundary, "f").is_ok());
+        let err = OutputWidth::Regular.check_len(boundary + 1, "f").unwrap_err();
+        assert!(err.to_string().contains("OutputWidth::Large"), "{err}");
+        assert!(OutputWidth::Large.check_len(boundary + 1, "f").is_ok());
+        assert!(OutputWidth::Large.check_len(4 * boundary, "f").is_ok());
+    }
+}
@@ -0,0 +1,176 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Pair counting for rank correlation statistics such as Kendall's tau.
+
+use std::cmp::Ordering;
+
+/// Counts of the `n * (n - 1) / 2` pairs of observations `(x, y)`, classified by how the two
+/// observations of a pair order relative to each other.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PairCounts {
+    /// Pairs ordered the same way by `x` and `y`
+    pub concordant: u64,
+    /// Pairs ordered in opposite ways by `x` and `y`
+    pub discordant: u64,
+    /// Pairs tied in `x` (including those also tied in `y`)
+    pub ties_x: u64,
+    /// Pairs tied in `y` (including those also tied in `x`)
+    pub ties_y: u64,
+    /// Pairs tied in both `x` and `y`
+    pub ties_xy: u64,
+}
+
+impl PairCounts {
+    /// Total number of pairs
+    pub fn total(&self) -> u64 {
+        self.concordant + self.discordant + self.ties_x + self.ties_y - self.ties_xy
+    }
+}
+
+fn pairs_in(group_len: u64) -> u64 {
+    group_len * group_len.saturating_sub(1) / 2
+}
+
+/// Sums `pairs_in` over each run of equal elements of a sorted slice
+fn tied_pairs<T>(sorted: &[T], eq: impl Fn(&T, &T) -> bool) -> u64 {
+    let mut pairs = 0;
+    let mut run_len = 1;
+    for window in sorted.windows(2) {
+        if eq(&window[0], &window[1]) {
+            run_len += 1;
+        } else {
+            pairs += pairs_in(run_len);
+            run_len = 1;
+        }
+    }
+    pairs + pairs_in(run_len)
+}
+
+/// Sorts `values` with a merge sort and returns the number of inversions, i.e. pairs that
+/// compare greater before the sort
+fn sort_counting_inversions(values: &mut [f64], buffer: &mut [f64]) -> u64 {
+    let len = values.len();
+    if len < 2 {
+        return 0;
+    }
+    let mid = len / 2;
+    let mut inversions = sort_counting_inversions(&mut values[..mid], &mut buffer[..mid])
+        + sort_counting_inversions(&mut values[mid..], &mut buffer[mid..]);
+
+    let (mut left, mut right) = (0, mid);
+    for slot in buffer[..len].iter_mut() {
+        if right == len || (left < mid && values[left].total_cmp(&values[right]) != Ordering::Greater) {
+            *slot = values[left];
+            left += 1;
+        } else {
+            *slot = values[right];
+            inversions += (mid - left) as u64;
+            right += 1;
+        }
+    }
+    values.copy_from_slice(&buffer[..len]);
+    inversions
+}
+
+/// Classifies every pair of `pairs` in `O(n log n)` using Knight's algorithm.
+///
+/// Values are compared with [`f64::total_cmp`], so NaN ties with NaN and orders after every
+/// other value. `pairs` is reordered.
+///
+/// See Knight, W. R. (1966). "A Computer Method for Calculating Kendall's Tau with Ungrouped
+/// Data". Journal of the American Statistical Association, 61(314), 436–439.
+pub fn count_pairs(pairs: &mut [(f64, f64)]) -> PairCounts {
+    let total = pairs_in(pairs.len() as u64);
+
+    pairs.sort_unstable_by(|a, b| a.0.total_cmp(&b.0).then(a.1.total_cmp(&b.1)));
+    let ties_x = tied_pairs(pairs, |a, b| a.0.total_cmp(&b.0) == Ordering::Equal);
+    let ties_xy = tied_pairs(pairs, |a, b| {
+        a.0.total_cmp(&b.0) == Ordering::Equal && a.1.total_cmp(&b.1) == Ordering::Equal
+    });
+
+    // Pairs tied in x are already ordered by y, so every inversion of y is a discordant pair
+    let mut ys: Vec<f64> = pairs.iter().map(|(_, y)| *y).collect();
+    let mut buffer = vec![0.0; ys.len()];
+    let discordant = sort_counting_inversions(&mut ys, &mut buffer);
+    let ties_y = tied_pairs(&ys, |a, b| a.total_cmp(b) == Ordering::Equal);
+
+    PairCounts {
+        concordant: total + ties_xy - ties_x - ties_y - discordant,
+        discordant,
+        ties_x,
+        ties_y,
+        ties_xy,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn count_pairs_naive(pairs: &[(f64, f64)]) -> PairCounts {
+        let mut counts = PairCounts::default();
+        for (i, a) in pairs.iter().enumerate() {
+            for b in &pairs[i + 1..] {
+                let x = a.0.total_cmp(&b.0);
+                let y = a.1.total_cmp(&b.1);
+                match (x, y) {
+                    (Ordering::Equal, Ordering::Equal) => {
+                        counts.ties_x += 1;
+                        counts.ties_y += 1;
+                        counts.ties_xy += 1;
+                    }
+                    (Ordering::Equal, _) => counts.ties_x += 1,
+                    (_, Ordering::Equal) => counts.ties_y += 1,
+                    (x, y) if x == y => counts.concordant += 1,
+                    _ => counts.discordant += 1,
+                }
+            }
+        }
+        counts
+    }
+
+    #[test]
+    fn test_count_pairs() {
+        let mut pairs = vec![(1.0, 1.0), (2.0, 3.0), (3.0, 2.0)];
+        let counts = count_pairs(&mut pairs);
+        assert_eq!(counts.concordant, 2);
+        assert_eq!(counts.discordant, 1);
+        assert_eq!(counts.total(), 3);
+        assert_eq!(count_pairs(&mut []), PairCounts::default());
+    }
+
+    #[test]
+    fn test_count_pairs_matches_naive_with_ties() {
+        // deterministic pseudo-random values with many ties
+        let pairs: Vec<(f64, f64)> = (0..200u64)
+            .map(|i| {
+                let x = (i * 7919 % 13) as f64;
+                let y = if i % 17 == 0 {
+                    f64::NAN
+                } else {
+                    (i * 104729 % 11) as f64
+                };
+                (x, y)
+            })
+            .collect();
+        let expected = count_pairs_naive(&pairs);
+        let actual = count_pairs(&mut pairs.clone());
+        assert_eq!(actual, expected);
+        assert_eq!(actual.total(), 200 * 199 / 2);
+    }
+}
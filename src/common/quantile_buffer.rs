@@ -0,0 +1,188 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Buffers of the exact quantile accumulators, which keep every value until they are sorted
+//! when the result is emitted.
+//!
+//! The state of a buffer is a list of its values, so DataFusion can spill it to disk like any
+//! other aggregate state when the memory of a query runs out.
+
+use std::sync::Arc;
+
+use arrow::array::{Array, ArrayRef, Float64Array};
+use datafusion::arrow;
+use datafusion::common::cast::{as_float64_array, as_list_array};
+use datafusion::common::{exec_err, Result, ScalarValue};
+
+use crate::common::nulls::all_null_or_filtered;
+use crate::compat::single_row_list;
+
+fn list_state(values: Vec<f64>) -> ScalarValue {
+    ScalarValue::List(Arc::new(single_row_list(Arc::new(Float64Array::from(values)))))
+}
+
+/// The non-null values of an exact quantile accumulator.
+#[derive(Debug, Default)]
+pub struct QuantileBuffer {
+    values: Vec<f64>,
+}
+
+impl QuantileBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Buffers the non-null values of the `Float64` array `values`
+    pub fn update_batch(&mut self, values: &ArrayRef) -> Result<()> {
+        if all_null_or_filtered(values, None) {
+            return Ok(());
+        }
+        self.values.extend(as_float64_array(values)?.iter().flatten());
+        Ok(())
+    }
+
+    /// Buffers the values of the lists of `states`, as returned by [`Self::state`]
+    pub fn merge_batch(&mut self, states: &ArrayRef) -> Result<()> {
+        for values in as_list_array(states)?.iter().flatten() {
+            self.values.extend(as_float64_array(&values)?.iter().flatten());
+        }
+        Ok(())
+    }
+
+    /// Moves the values into a list, leaving the buffer empty
+    pub fn state(&mut self) -> ScalarValue {
+        list_state(std::mem::take(&mut self.values))
+    }
+
+    /// The values in ascending order
+    pub fn sorted(&mut self) -> &[f64] {
+        self.values.sort_unstable_by(f64::total_cmp);
+        &self.values
+    }
+
+    pub fn size(&self) -> usize {
+        self.values.capacity() * std::mem::size_of::<f64>()
+    }
+}
+
+/// The `(value, weight)` pairs of an exact weighted quantile accumulator.
+///
+/// Rows where the value or the weight is NULL and rows of zero weight are skipped. Negative
+/// weights are an error.
+#[derive(Debug, Default)]
+pub struct WeightedQuantileBuffer {
+    entries: Vec<(f64, f64)>,
+    total_weight: f64,
+}
+
+impl WeightedQuantileBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn push_all(&mut self, values: &ArrayRef, weights: &ArrayRef, fn_name: &str) -> Result<()> {
+        let (values, weights) = (as_float64_array(values)?, as_float64_array(weights)?);
+        for (value, weight) in values.iter().zip(weights.iter()) {
+            let (Some(value), Some(weight)) = (value, weight) else {
+                continue;
+            };
+            if weight < 0.0 || weight.is_nan() {
+                return exec_err!("{fn_name} expects non-negative weights, got {weight}");
+            }
+            if weight > 0.0 {
+                self.entries.push((value, weight));
+                self.total_weight += weight;
+            }
+        }
+        Ok(())
+    }
+
+    /// Buffers the rows of the `Float64` arrays `values` and `weights`
+    pub fn update_batch(&mut self, values: &ArrayRef, weights: &ArrayRef, fn_name: &str) -> Result<()> {
+        if all_null_or_filtered(values, None) || all_null_or_filtered(weights, None) {
+            return Ok(());
+        }
+        self.push_all(values, weights, fn_name)
+    }
+
+    /// Buffers the pairs of the lists of `values` and `weights`, as returned by [`Self::state`]
+    pub fn merge_batch(&mut self, values: &ArrayRef, weights: &ArrayRef, fn_name: &str) -> Result<()> {
+        let (values, weights) = (as_list_array(values)?, as_list_array(weights)?);
+        for (values, weights) in values.iter().zip(weights.iter()) {
+            let (Some(values), Some(weights)) = (values, weights) else {
+                continue;
+            };
+            if values.len() != weights.len() {
+                return exec_err!(
+                    "{fn_name} state has {} values but {} weights",
+                    values.len(),
+                    weights.len()
+                );
+            }
+            self.push_all(&values, &weights, fn_name)?;
+        }
+        Ok(())
+    }
+
+    /// Moves the values and the weights into two lists, leaving the buffer empty
+    pub fn state(&mut self) -> [ScalarValue; 2] {
+        let (values, weights) = std::mem::take(&mut self.entries).into_iter().unzip();
+        self.total_weight = 0.0;
+        [list_state(values), list_state(weights)]
+    }
+
+    /// The sum of the weights
+    pub fn total_weight(&self) -> f64 {
+        self.total_weight
+    }
+
+    /// The pairs in ascending order of value
+    pub fn sorted(&mut self) -> &[(f64, f64)] {
+        self.entries.sort_unstable_by(|a, b| a.0.total_cmp(&b.0));
+        &self.entries
+    }
+
+    pub fn size(&self) -> usize {
+        self.entries.capacity() * std::mem::size_of::<(f64, f64)>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_weighted_buffer_merges_its_state() -> Result<()> {
+        let mut partial = WeightedQuantileBuffer::new();
+        partial.update_batch(
+            &(Arc::new(Float64Array::from(vec![Some(3.0), Some(1.0), None, Some(2.0)])) as ArrayRef),
+            &(Arc::new(Float64Array::from(vec![Some(1.0), Some(2.0), Some(5.0), Some(0.0)])) as ArrayRef),
+            "test",
+        )?;
+        assert_eq!(partial.total_weight(), 3.0);
+        let [values, weights] = partial.state();
+
+        let mut merged = WeightedQuantileBuffer::new();
+        merged.merge_batch(&values.to_array()?, &weights.to_array()?, "test")?;
+        assert_eq!(merged.sorted(), &[(1.0, 2.0), (3.0, 1.0)]);
+        assert_eq!(merged.total_weight(), 3.0);
+
+        let negative: ArrayRef = Arc::new(Float64Array::from(vec![-1.0]));
+        assert!(merged.update_batch(&negative, &negative, "test").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,129 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+//! Buffers of the time series aggregates, which keep every `(time, value)` sample until they are
+//! sorted by time when the result is emitted, as the samples of different partitions interleave
+//! in time.
+
+use std::sync::Arc;
+
+use arrow::array::{ArrayRef, Float64Array};
+use arrow::datatypes::{DataType, Field};
+use datafusion::arrow;
+use datafusion::common::cast::{as_float64_array, as_list_array};
+use datafusion::common::{exec_err, Result, ScalarValue};
+
+use crate::common::decay::times;
+use crate::compat::single_row_list;
+
+/// The `(time, value)` samples of a time series aggregate, with times in seconds, or in the unit
+/// of integer timestamps.
+///
+/// Rows where the value or the time is NULL are skipped.
+#[derive(Debug, Default)]
+pub struct SampleBuffer {
+    samples: Vec<(f64, f64)>,
+}
+
+impl SampleBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The fields of [`Self::state`]
+    pub fn state_fields() -> Vec<Field> {
+        vec![
+            Field::new_list("times", Field::new("item", DataType::Float64, true), true),
+            Field::new_list("values", Field::new("item", DataType::Float64, true), true),
+        ]
+    }
+
+    fn push_all(&mut self, times: &Float64Array, values: &Float64Array) {
+        for (time, value) in times.iter().zip(values.iter()) {
+            if let (Some(time), Some(value)) = (time, value) {
+                self.samples.push((time, value));
+            }
+        }
+    }
+
+    /// Buffers the rows of the `Float64` array `values`, at the times of `ts`, a timestamp, date
+    /// or `Int64` array of `time_scale` seconds per unit
+    pub fn update_batch(&mut self, values: &ArrayRef, ts: &ArrayRef, time_scale: f64) -> Result<()> {
+        self.push_all(&times(ts, time_scale)?, as_float64_array(values)?);
+        Ok(())
+    }
+
+    /// Buffers the samples of the lists of `times` and `values`, as returned by [`Self::state`]
+    pub fn merge_batch(&mut self, times: &ArrayRef, values: &ArrayRef, fn_name: &str) -> Result<()> {
+        let (times, values) = (as_list_array(times)?, as_list_array(values)?);
+        for (times, values) in times.iter().zip(values.iter()) {
+            let (Some(times), Some(values)) = (times, values) else {
+                continue;
+            };
+            if times.len() != values.len() {
+                return exec_err!("{fn_name} state has {} times but {} values", times.len(), values.len());
+            }
+            self.push_all(as_float64_array(&times)?, as_float64_array(&values)?);
+        }
+        Ok(())
+    }
+
+    /// Moves the times and the values into two lists, leaving the buffer empty
+    pub fn state(&mut self) -> [ScalarValue; 2] {
+        let (times, values): (Vec<f64>, Vec<f64>) = std::mem::take(&mut self.samples).into_iter().unzip();
+        [times, values].map(|list| ScalarValue::List(Arc::new(single_row_list(Arc::new(Float64Array::from(list))))))
+    }
+
+    /// The samples in ascending order of time, and of value for the same time, so the order
+    /// doesn't depend on the order they were aggregated in
+    pub fn sorted(&mut self) -> &[(f64, f64)] {
+        self.samples
+            .sort_unstable_by(|a, b| a.0.total_cmp(&b.0).then(a.1.total_cmp(&b.1)));
+        &self.samples
+    }
+
+    pub fn size(&self) -> usize {
+        self.samples.capacity() * std::mem::size_of::<(f64, f64)>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::array::Int64Array;
+
+    use super::*;
+
+    #[test]
+    fn test_sample_buffer_merges_its_state() -> Result<()> {
+        let mut partial = SampleBuffer::new();
+        partial.update_batch(
+            &(Arc::new(Float64Array::from(vec![Some(3.0), Some(1.0), None])) as ArrayRef),
+            &(Arc::new(Int64Array::from(vec![Some(2), None, Some(1)])) as ArrayRef),
+            10.0,
+        )?;
+        let [times, values] = partial.state();
+
+        let mut merged = SampleBuffer::new();
+        merged.update_batch(
+            &(Arc::new(Float64Array::from(vec![2.0])) as ArrayRef),
+            &(Arc::new(Int64Array::from(vec![2])) as ArrayRef),
+            10.0,
+        )?;
+        merged.merge_batch(&times.to_array()?, &values.to_array()?, "test")?;
+        assert_eq!(merged.sorted(), &[(20.0, 2.0), (20.0, 3.0)]);
+        Ok(())
+    }
+}
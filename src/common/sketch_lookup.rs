@@ -0,0 +1,94 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Lookups of values in serialized sketches, shared by scalar functions such as `cm_estimate`
+//! and `bloom_contains`.
+
+use arrow::array::{Array, ArrayRef};
+use datafusion::arrow;
+use datafusion::arrow::datatypes::DataType;
+use datafusion::common::cast::as_binary_array;
+use datafusion::common::{exec_err, plan_err, Result, ScalarValue};
+use datafusion::logical_expr::ColumnarValue;
+
+use crate::fingerprint::{hash_lookup_values, lookup_value_type};
+
+/// Coerces the arguments of a lookup of a value in a serialized sketch of `sketch_name`
+pub fn coerce_lookup_types(fn_name: &str, sketch_name: &str, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+    let [sketch, value] = arg_types else {
+        return plan_err!(
+            "{fn_name} expects a {sketch_name} and a value, got {} arguments",
+            arg_types.len()
+        );
+    };
+    match sketch {
+        DataType::Binary | DataType::LargeBinary | DataType::BinaryView | DataType::Null => {
+            Ok(vec![DataType::Binary, lookup_value_type(value)])
+        }
+        _ => plan_err!("{fn_name} expects a {sketch_name}, got {sketch}"),
+    }
+}
+
+/// Looks up the values of `args[1]` in the serialized sketches of `args[0]`, as coerced by
+/// [`coerce_lookup_types`].
+///
+/// `from_bytes` deserializes a sketch and `lookup` looks up the hash of a value in it. The
+/// results are collected into an array with `to_array`, NULL where the sketch or the value is
+/// NULL. A scalar sketch, typically of a subquery, is only deserialized once.
+pub fn lookup_values<S, T>(
+    fn_name: &str,
+    args: &[ColumnarValue],
+    from_bytes: impl Fn(&[u8]) -> Result<S>,
+    lookup: impl Fn(&S, u64) -> T,
+    to_array: impl FnOnce(Vec<Option<T>>) -> ArrayRef,
+) -> Result<ColumnarValue> {
+    let scalar_sketch = match &args[0] {
+        ColumnarValue::Scalar(ScalarValue::Binary(bytes)) => Some(bytes.as_deref().map(&from_bytes).transpose()?),
+        ColumnarValue::Scalar(sketch) => return exec_err!("{fn_name} expects a serialized sketch, got {sketch:?}"),
+        ColumnarValue::Array(_) => None,
+    };
+    let all_scalar = args.iter().all(|arg| matches!(arg, ColumnarValue::Scalar(_)));
+
+    let arrays = ColumnarValue::values_to_arrays(args)?;
+    let (sketches, values) = (as_binary_array(&arrays[0])?, &arrays[1]);
+    let hashes = hash_lookup_values(values)?;
+    let mut results = Vec::with_capacity(values.len());
+    for (row, hash) in hashes.into_iter().enumerate() {
+        let parsed;
+        let sketch = match &scalar_sketch {
+            Some(sketch) => sketch.as_ref(),
+            None => {
+                parsed = sketches
+                    .is_valid(row)
+                    .then(|| from_bytes(sketches.value(row)))
+                    .transpose()?;
+                parsed.as_ref()
+            }
+        };
+        results.push(
+            sketch
+                .filter(|_| values.is_valid(row))
+                .map(|sketch| lookup(sketch, hash)),
+        );
+    }
+    let results = to_array(results);
+
+    if all_scalar {
+        return Ok(ColumnarValue::Scalar(ScalarValue::try_from_array(&results, 0)?));
+    }
+    Ok(ColumnarValue::Array(results))
+}
@@ -0,0 +1,117 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Arguments of table functions reading tables: the table, resolved in the catalogs of a
+//! session, and the names of its columns.
+
+use std::fmt::Debug;
+use std::future::Future;
+use std::pin::pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+use datafusion::catalog::{CatalogProviderList, TableProvider};
+use datafusion::common::{plan_datafusion_err, plan_err, Column, Result, ScalarValue, TableReference};
+use datafusion::execution::context::SessionContext;
+use datafusion::logical_expr::Expr;
+
+/// The tables of a [`SessionContext`], for table functions, which aren't given the session
+/// they're called in.
+///
+/// Tables must be available without waiting on I/O, as tables of in-memory and listing schemas
+/// are.
+pub struct SessionTables {
+    catalog_list: Arc<dyn CatalogProviderList>,
+    default_catalog: String,
+    default_schema: String,
+}
+
+impl Debug for SessionTables {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SessionTables")
+            .field("default_catalog", &self.default_catalog)
+            .field("default_schema", &self.default_schema)
+            .finish()
+    }
+}
+
+impl SessionTables {
+    /// The tables of the catalogs and default schema of `ctx`
+    pub fn new(ctx: &SessionContext) -> Self {
+        let state = ctx.state();
+        let options = &state.config_options().catalog;
+        Self {
+            catalog_list: Arc::clone(state.catalog_list()),
+            default_catalog: options.default_catalog.clone(),
+            default_schema: options.default_schema.clone(),
+        }
+    }
+
+    /// The table `table_ref` read by the function `fn_name`
+    pub fn table(&self, fn_name: &str, table_ref: &TableReference) -> Result<Arc<dyn TableProvider>> {
+        let resolved = table_ref.clone().resolve(&self.default_catalog, &self.default_schema);
+        let schema = self
+            .catalog_list
+            .catalog(&resolved.catalog)
+            .and_then(|catalog| catalog.schema(&resolved.schema))
+            .ok_or_else(|| plan_datafusion_err!("{fn_name} can't find the schema of table {table_ref}"))?;
+        let Poll::Ready(table) = poll_once(schema.table(&resolved.table)) else {
+            return plan_err!("{fn_name} can't read table {table_ref} without waiting on its catalog");
+        };
+        table?.ok_or_else(|| plan_datafusion_err!("{fn_name} can't find table {table_ref}"))
+    }
+}
+
+/// The table named by the argument `arg` of the function `fn_name`, an identifier, possibly
+/// qualified, or a string
+pub fn table_arg(fn_name: &str, arg: &Expr) -> Result<TableReference> {
+    match arg {
+        // `schema.table` is parsed as the column `table` of the relation `schema`
+        Expr::Column(Column {
+            relation: Some(relation),
+            name,
+        }) => match relation {
+            TableReference::Bare { table } => Ok(TableReference::partial(table.as_ref(), name.as_str())),
+            TableReference::Partial { schema, table } => {
+                Ok(TableReference::full(schema.as_ref(), table.as_ref(), name.as_str()))
+            }
+            TableReference::Full { .. } => plan_err!("{fn_name} expects a table, got {arg}"),
+        },
+        Expr::Column(Column { relation: None, name }) => Ok(TableReference::bare(name.as_str())),
+        Expr::Literal(ScalarValue::Utf8(Some(name))) => Ok(TableReference::from(name.as_str())),
+        _ => plan_err!("{fn_name} expects a table, got {arg}"),
+    }
+}
+
+/// The column named by the argument `arg` of the function `fn_name`, an identifier or a string
+pub fn column_arg<'a>(fn_name: &str, arg: &'a Expr) -> Result<&'a str> {
+    match arg {
+        Expr::Column(Column { relation: None, name }) | Expr::Literal(ScalarValue::Utf8(Some(name))) => {
+            Ok(name.as_str())
+        }
+        _ => plan_err!("{fn_name} expects column names, got {arg}"),
+    }
+}
+
+/// Polls `future` once, for catalog lookups that complete without waiting
+fn poll_once<F: Future>(future: F) -> Poll<F::Output> {
+    const VTABLE: RawWakerVTable =
+        RawWakerVTable::new(|_| RawWaker::new(std::ptr::null(), &VTABLE), |_| {}, |_| {}, |_| {});
+    // SAFETY: the vtable functions ignore the data pointer and do nothing
+    let waker = unsafe { Waker::from_raw(RawWaker::new(std::ptr::null(), &VTABLE)) };
+    pin!(future).poll(&mut Context::from_waker(&waker))
+}
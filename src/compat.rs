@@ -0,0 +1,70 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Shims for the DataFusion APIs that move or change between releases.
+//!
+//! The rest of the crate reaches these APIs only through this module, so upgrading to another
+//! DataFusion release means adapting the items below rather than every function. Only DataFusion
+//! 42.x is supported, see [`SUPPORTED_DATAFUSION_VERSIONS`]: the `compat` CI job tests its patch
+//! releases. Older minor releases are not, as nothing below is gated by release.
+
+use arrow::array::{ArrayRef, ListArray};
+use datafusion::arrow;
+use datafusion::arrow::datatypes::DataType;
+use datafusion::error::Result;
+use datafusion::logical_expr::function::AccumulatorArgs;
+
+// Moved to `datafusion::functions_aggregate_common` in later releases
+pub use datafusion::physical_expr::aggregate::utils::Hashable;
+// Moved to `datafusion::physical_expr_common` in later releases
+pub use datafusion::physical_expr::binary_map::{ArrowBytesSet, OutputType};
+pub use datafusion::physical_expr_common::binary_view_map::ArrowBytesViewSet;
+// Gained a `GroupsAccumulator::convert_to_state` hook in later releases
+pub use datafusion::logical_expr::{EmitTo, GroupsAccumulator};
+
+/// The DataFusion releases this crate builds against, 42.x only
+pub const SUPPORTED_DATAFUSION_VERSIONS: &[&str] = &["42"];
+
+/// Wraps `values` in a list array with a single, nullable row.
+///
+/// Replaced by `SingleRowListArrayBuilder` in later releases.
+pub fn single_row_list(values: ArrayRef) -> ListArray {
+    datafusion::common::utils::array_into_list_array_nullable(values)
+}
+
+/// The type of the `index`-th argument of an aggregate.
+///
+/// Later releases expose the argument fields on [`AccumulatorArgs`] directly.
+pub fn arg_type(acc_args: &AccumulatorArgs, index: usize) -> Result<DataType> {
+    acc_args.exprs[index].data_type(acc_args.schema)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::DATAFUSION_VERSION;
+
+    #[test]
+    fn test_datafusion_version_is_supported() {
+        assert!(
+            SUPPORTED_DATAFUSION_VERSIONS
+                .iter()
+                .any(|version| DATAFUSION_VERSION.starts_with(&format!("{version}."))),
+            "DataFusion {DATAFUSION_VERSION} is not in {SUPPORTED_DATAFUSION_VERSIONS:?}, update src/compat.rs"
+        );
+    }
+}
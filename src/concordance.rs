@@ -0,0 +1,302 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::any::Any;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use arrow::array::{ArrayRef, Float64Array};
+use datafusion::arrow;
+use datafusion::arrow::datatypes::{DataType, Field};
+use datafusion::common::cast::{as_float64_array, as_list_array};
+use datafusion::common::{Result, ScalarValue};
+use datafusion::logical_expr::function::{AccumulatorArgs, StateFieldsArgs};
+use datafusion::logical_expr::{Accumulator, AggregateUDFImpl, Signature, Volatility};
+
+use crate::common::nulls::all_null_or_filtered;
+use crate::common::pairs::{count_pairs, PairCounts};
+use crate::compat::single_row_list;
+
+make_udaf_expr_and_func!(
+    ConcordanceFunction,
+    concordance,
+    x y,
+    "Calculates the fraction of pairs of rows ordered the same way by both columns.",
+    concordance_udaf
+);
+
+make_udaf_expr_and_func!(
+    DiscordanceFunction,
+    discordance,
+    x y,
+    "Calculates the fraction of pairs of rows ordered in opposite ways by the two columns.",
+    discordance_udaf
+);
+
+/// Which fraction of pairs a [`PairRateAccumulator`] evaluates to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PairRate {
+    Concordant,
+    Discordant,
+}
+
+/// The `ConcordanceFunction` calculates the fraction of the `n * (n - 1) / 2` pairs of rows
+/// that are concordant, i.e. ordered the same way by `x` and by `y`.
+///
+/// - Rows where `x` or `y` is NULL are ignored.
+/// - Pairs tied in `x` or `y` are neither concordant nor discordant but count towards the total,
+///   so `concordance(x, y) - discordance(x, y)` is Kendall's tau-a.
+/// - The result is NULL for fewer than two rows.
+pub struct ConcordanceFunction {
+    signature: Signature,
+}
+
+impl Debug for ConcordanceFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ConcordanceFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for ConcordanceFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ConcordanceFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::coercible(vec![DataType::Float64, DataType::Float64], Volatility::Immutable),
+        }
+    }
+}
+
+impl AggregateUDFImpl for ConcordanceFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "concordance"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Float64)
+    }
+
+    fn state_fields(&self, _args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(PairRateAccumulator::state_fields())
+    }
+
+    fn accumulator(&self, _acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        Ok(Box::new(PairRateAccumulator::new(PairRate::Concordant)))
+    }
+}
+
+/// The `DiscordanceFunction` calculates the fraction of the `n * (n - 1) / 2` pairs of rows
+/// that are discordant, i.e. ordered in opposite ways by `x` and by `y`.
+///
+/// See [`ConcordanceFunction`] for how NULLs and ties are handled.
+pub struct DiscordanceFunction {
+    signature: Signature,
+}
+
+impl Debug for DiscordanceFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DiscordanceFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for DiscordanceFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DiscordanceFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::coercible(vec![DataType::Float64, DataType::Float64], Volatility::Immutable),
+        }
+    }
+}
+
+impl AggregateUDFImpl for DiscordanceFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "discordance"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Float64)
+    }
+
+    fn state_fields(&self, _args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(PairRateAccumulator::state_fields())
+    }
+
+    fn accumulator(&self, _acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        Ok(Box::new(PairRateAccumulator::new(PairRate::Discordant)))
+    }
+}
+
+/// Accumulator for [`ConcordanceFunction`] and [`DiscordanceFunction`].
+///
+/// Pairs can only be classified once every row is known, so the rows are buffered and counted
+/// with [`count_pairs`] on evaluation.
+#[derive(Debug)]
+pub struct PairRateAccumulator {
+    pairs: Vec<(f64, f64)>,
+    rate: PairRate,
+}
+
+impl PairRateAccumulator {
+    pub fn new(rate: PairRate) -> Self {
+        Self { pairs: vec![], rate }
+    }
+
+    fn state_fields() -> Vec<Field> {
+        let item = Arc::new(Field::new("item", DataType::Float64, true));
+        vec![
+            Field::new("x", DataType::List(Arc::clone(&item)), true),
+            Field::new("y", DataType::List(item), true),
+        ]
+    }
+
+    fn extend(&mut self, xs: &Float64Array, ys: &Float64Array) {
+        let pairs = xs.iter().zip(ys.iter()).filter_map(|pair| match pair {
+            (Some(x), Some(y)) => Some((x, y)),
+            _ => None,
+        });
+        self.pairs.extend(pairs);
+    }
+
+    fn rate_of(&self, counts: &PairCounts) -> u64 {
+        match self.rate {
+            PairRate::Concordant => counts.concordant,
+            PairRate::Discordant => counts.discordant,
+        }
+    }
+}
+
+impl Accumulator for PairRateAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        if all_null_or_filtered(&values[0], None) || all_null_or_filtered(&values[1], None) {
+            return Ok(());
+        }
+        self.extend(as_float64_array(&values[0])?, as_float64_array(&values[1])?);
+        Ok(())
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        let xs = as_list_array(&states[0])?;
+        let ys = as_list_array(&states[1])?;
+        for (x, y) in xs.iter().zip(ys.iter()) {
+            if let (Some(x), Some(y)) = (x, y) {
+                self.extend(as_float64_array(&x)?, as_float64_array(&y)?);
+            }
+        }
+        Ok(())
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        let counts = count_pairs(&mut self.pairs);
+        if counts.total() == 0 {
+            return Ok(ScalarValue::Float64(None));
+        }
+        Ok(ScalarValue::Float64(Some(
+            self.rate_of(&counts) as f64 / counts.total() as f64,
+        )))
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self) + self.pairs.capacity() * std::mem::size_of::<(f64, f64)>()
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        let (xs, ys): (Vec<f64>, Vec<f64>) = self.pairs.iter().copied().unzip();
+        Ok(vec![
+            ScalarValue::List(Arc::new(single_row_list(Arc::new(Float64Array::from(xs))))),
+            ScalarValue::List(Arc::new(single_row_list(Arc::new(Float64Array::from(ys))))),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pair_rate_of(chunks: Vec<Vec<(Option<f64>, Option<f64>)>>, rate: PairRate) -> Result<ScalarValue> {
+        let mut merged = PairRateAccumulator::new(rate);
+        for chunk in chunks {
+            let (xs, ys): (Vec<_>, Vec<_>) = chunk.into_iter().unzip();
+            let mut partial = PairRateAccumulator::new(rate);
+            partial.update_batch(&[Arc::new(Float64Array::from(xs)), Arc::new(Float64Array::from(ys))])?;
+            let state = partial
+                .state()?
+                .iter()
+                .map(|s| s.to_array())
+                .collect::<Result<Vec<_>>>()?;
+            merged.merge_batch(&state)?;
+        }
+        merged.evaluate()
+    }
+
+    #[test]
+    fn test_pair_rates_merge() -> Result<()> {
+        // pairs (1, 1), (2, 3), (3, 2), (4, 4) with one NULL row: 5 concordant and 1 discordant
+        let chunks = || {
+            vec![
+                vec![(Some(1.0), Some(1.0)), (Some(2.0), Some(3.0))],
+                vec![(None, Some(5.0)), (Some(3.0), Some(2.0))],
+                vec![],
+                vec![(Some(4.0), Some(4.0))],
+            ]
+        };
+        assert_eq!(
+            pair_rate_of(chunks(), PairRate::Concordant)?,
+            ScalarValue::Float64(Some(5.0 / 6.0))
+        );
+        assert_eq!(
+            pair_rate_of(chunks(), PairRate::Discordant)?,
+            ScalarValue::Float64(Some(1.0 / 6.0))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_pair_rates_single_row() -> Result<()> {
+        let chunks = vec![vec![(Some(1.0), Some(1.0))]];
+        assert_eq!(pair_rate_of(chunks, PairRate::Concordant)?, ScalarValue::Float64(None));
+        Ok(())
+    }
+}
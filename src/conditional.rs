@@ -0,0 +1,654 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::any::Any;
+use std::fmt::Debug;
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use arrow::array::{Array, ArrayRef, ArrowNativeTypeOp, ArrowPrimitiveType, BooleanArray, Int64Array, PrimitiveArray};
+use arrow::compute::{cast, kernels::numeric::div};
+use datafusion::arrow;
+use datafusion::arrow::datatypes::{DataType, Field, Float64Type, Int64Type, UInt64Type};
+use datafusion::common::cast::{as_boolean_array, as_int64_array, as_primitive_array};
+use datafusion::common::{internal_err, plan_err, Result, ScalarValue};
+use datafusion::functions_aggregate::average::avg_udaf;
+use datafusion::functions_aggregate::count::count_udaf;
+use datafusion::functions_aggregate::sum::sum_udaf;
+use datafusion::logical_expr::expr::AggregateFunction;
+use datafusion::logical_expr::function::{AccumulatorArgs, AggregateFunctionSimplification, StateFieldsArgs};
+use datafusion::logical_expr::simplify::SimplifyInfo;
+use datafusion::logical_expr::{Accumulator, AggregateUDFImpl, Expr, Signature, Volatility};
+
+use crate::compat::{EmitTo, GroupsAccumulator};
+
+make_udaf_expr_and_func!(
+    CountIfFunction,
+    count_if,
+    x condition,
+    "Counts the non-null values of rows where the condition is true.",
+    count_if_udaf
+);
+
+make_udaf_expr_and_func!(
+    SumIfFunction,
+    sum_if,
+    x condition,
+    "Sums the values of rows where the condition is true.",
+    sum_if_udaf
+);
+
+make_udaf_expr_and_func!(
+    AvgIfFunction,
+    avg_if,
+    x condition,
+    "Averages the values of rows where the condition is true.",
+    avg_if_udaf
+);
+
+/// Which aggregate a conditional aggregate applies to the rows where its condition is true.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConditionalStat {
+    Count,
+    Sum,
+    Avg,
+}
+
+impl ConditionalStat {
+    fn name(self) -> &'static str {
+        match self {
+            ConditionalStat::Count => "count_if",
+            ConditionalStat::Sum => "sum_if",
+            ConditionalStat::Avg => "avg_if",
+        }
+    }
+
+    /// Coerces the value to the type the matching built-in aggregate takes, so the rewritten
+    /// aggregate returns the same type
+    fn coerce_types(self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        let [value_type, condition_type] = arg_types else {
+            return plan_err!("{} expects a value and a condition", self.name());
+        };
+        let value_type = match (self, value_type) {
+            (_, DataType::Dictionary(_, value_type)) => {
+                self.coerce_types(&[*value_type.clone(), DataType::Boolean])?[0].clone()
+            }
+            (ConditionalStat::Count, value_type) => value_type.clone(),
+            (ConditionalStat::Sum, dt) if dt.is_signed_integer() || dt.is_null() => DataType::Int64,
+            (ConditionalStat::Sum, dt) if dt.is_unsigned_integer() => DataType::UInt64,
+            (ConditionalStat::Sum, dt) | (ConditionalStat::Avg, dt) if dt.is_numeric() || dt.is_null() => {
+                DataType::Float64
+            }
+            (_, value_type) => return plan_err!("{} does not support values of type {value_type}", self.name()),
+        };
+        match condition_type {
+            DataType::Boolean | DataType::Null => Ok(vec![value_type, DataType::Boolean]),
+            _ => plan_err!("{} expects a boolean condition, got {condition_type}", self.name()),
+        }
+    }
+
+    fn return_type(self, arg_types: &[DataType]) -> DataType {
+        match self {
+            ConditionalStat::Count => DataType::Int64,
+            ConditionalStat::Sum => arg_types[0].clone(),
+            ConditionalStat::Avg => DataType::Float64,
+        }
+    }
+
+    fn state_fields(self, args: &StateFieldsArgs) -> Vec<Field> {
+        let count = Field::new("count", DataType::Int64, true);
+        match self {
+            ConditionalStat::Count => vec![count],
+            ConditionalStat::Sum | ConditionalStat::Avg => {
+                vec![Field::new("sum", args.input_types[0].clone(), true), count]
+            }
+        }
+    }
+
+    fn accumulator(self, acc_args: &AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        match (self, acc_args.return_type) {
+            (ConditionalStat::Count, _) => Ok(Box::new(ConditionalAccumulator::<Int64Type>::new(self))),
+            (_, DataType::Int64) => Ok(Box::new(ConditionalAccumulator::<Int64Type>::new(self))),
+            (_, DataType::UInt64) => Ok(Box::new(ConditionalAccumulator::<UInt64Type>::new(self))),
+            (_, DataType::Float64) => Ok(Box::new(ConditionalAccumulator::<Float64Type>::new(self))),
+            (_, return_type) => internal_err!("{} does not support {return_type}", self.name()),
+        }
+    }
+
+    fn groups_accumulator(self, acc_args: &AccumulatorArgs) -> Result<Box<dyn GroupsAccumulator>> {
+        match (self, acc_args.return_type) {
+            (ConditionalStat::Count, _) => Ok(Box::new(ConditionalGroupsAccumulator::<Int64Type>::new(self))),
+            (_, DataType::Int64) => Ok(Box::new(ConditionalGroupsAccumulator::<Int64Type>::new(self))),
+            (_, DataType::UInt64) => Ok(Box::new(ConditionalGroupsAccumulator::<UInt64Type>::new(self))),
+            (_, DataType::Float64) => Ok(Box::new(ConditionalGroupsAccumulator::<Float64Type>::new(self))),
+            (_, return_type) => internal_err!("{} does not support {return_type}", self.name()),
+        }
+    }
+
+    /// Rewrites `count_if(x, condition)` into `count(x) FILTER (WHERE condition)`, and
+    /// likewise for `sum` and `avg`, so the built-in aggregates evaluate the filtered rows.
+    ///
+    /// A `FILTER` clause of the conditional aggregate itself is combined with the condition.
+    fn simplification(self) -> AggregateFunctionSimplification {
+        Box::new(move |mut aggr_func: AggregateFunction, _info: &dyn SimplifyInfo| {
+            let (condition, value) = (aggr_func.args.remove(1), aggr_func.args.remove(0));
+            let filter = match aggr_func.filter {
+                Some(filter) => condition.and(*filter),
+                None => condition,
+            };
+            let udaf = match self {
+                ConditionalStat::Count => count_udaf(),
+                ConditionalStat::Sum => sum_udaf(),
+                ConditionalStat::Avg => avg_udaf(),
+            };
+            Ok(Expr::AggregateFunction(AggregateFunction::new_udf(
+                udaf,
+                vec![value],
+                aggr_func.distinct,
+                Some(Box::new(filter)),
+                aggr_func.order_by,
+                aggr_func.null_treatment,
+            )))
+        })
+    }
+}
+
+/// The `CountIfFunction` counts the non-null values of the rows where a condition is true,
+/// like ClickHouse's `countIf`.
+///
+/// - Rows where the condition is false or NULL are skipped.
+/// - The result is 0 if no row matches, as for `count`.
+pub struct CountIfFunction {
+    signature: Signature,
+}
+
+impl Debug for CountIfFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CountIfFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for CountIfFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CountIfFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+        }
+    }
+}
+
+impl AggregateUDFImpl for CountIfFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        ConditionalStat::Count.name()
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        ConditionalStat::Count.coerce_types(arg_types)
+    }
+
+    fn return_type(&self, arg_types: &[DataType]) -> Result<DataType> {
+        Ok(ConditionalStat::Count.return_type(arg_types))
+    }
+
+    fn is_nullable(&self) -> bool {
+        false
+    }
+
+    fn default_value(&self, _data_type: &DataType) -> Result<ScalarValue> {
+        Ok(ScalarValue::Int64(Some(0)))
+    }
+
+    fn state_fields(&self, args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(ConditionalStat::Count.state_fields(&args))
+    }
+
+    fn accumulator(&self, acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        ConditionalStat::Count.accumulator(&acc_args)
+    }
+
+    fn groups_accumulator_supported(&self, _args: AccumulatorArgs) -> bool {
+        true
+    }
+
+    fn create_groups_accumulator(&self, args: AccumulatorArgs) -> Result<Box<dyn GroupsAccumulator>> {
+        ConditionalStat::Count.groups_accumulator(&args)
+    }
+
+    fn simplify(&self) -> Option<AggregateFunctionSimplification> {
+        Some(ConditionalStat::Count.simplification())
+    }
+}
+
+/// The `SumIfFunction` sums the values of the rows where a condition is true, like
+/// ClickHouse's `sumIf`.
+///
+/// - Integers are summed as `Int64` or `UInt64` and other numbers as `Float64`, as by `sum`.
+/// - The result is NULL if no row matches.
+pub struct SumIfFunction {
+    signature: Signature,
+}
+
+impl Debug for SumIfFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SumIfFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for SumIfFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SumIfFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+        }
+    }
+}
+
+impl AggregateUDFImpl for SumIfFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        ConditionalStat::Sum.name()
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        ConditionalStat::Sum.coerce_types(arg_types)
+    }
+
+    fn return_type(&self, arg_types: &[DataType]) -> Result<DataType> {
+        Ok(ConditionalStat::Sum.return_type(arg_types))
+    }
+
+    fn state_fields(&self, args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(ConditionalStat::Sum.state_fields(&args))
+    }
+
+    fn accumulator(&self, acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        ConditionalStat::Sum.accumulator(&acc_args)
+    }
+
+    fn groups_accumulator_supported(&self, _args: AccumulatorArgs) -> bool {
+        true
+    }
+
+    fn create_groups_accumulator(&self, args: AccumulatorArgs) -> Result<Box<dyn GroupsAccumulator>> {
+        ConditionalStat::Sum.groups_accumulator(&args)
+    }
+
+    fn simplify(&self) -> Option<AggregateFunctionSimplification> {
+        Some(ConditionalStat::Sum.simplification())
+    }
+}
+
+/// The `AvgIfFunction` averages the values of the rows where a condition is true, like
+/// ClickHouse's `avgIf`.
+///
+/// - The result is NULL if no row matches.
+pub struct AvgIfFunction {
+    signature: Signature,
+}
+
+impl Debug for AvgIfFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AvgIfFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for AvgIfFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AvgIfFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+        }
+    }
+}
+
+impl AggregateUDFImpl for AvgIfFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        ConditionalStat::Avg.name()
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        ConditionalStat::Avg.coerce_types(arg_types)
+    }
+
+    fn return_type(&self, arg_types: &[DataType]) -> Result<DataType> {
+        Ok(ConditionalStat::Avg.return_type(arg_types))
+    }
+
+    fn state_fields(&self, args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(ConditionalStat::Avg.state_fields(&args))
+    }
+
+    fn accumulator(&self, acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        ConditionalStat::Avg.accumulator(&acc_args)
+    }
+
+    fn groups_accumulator_supported(&self, _args: AccumulatorArgs) -> bool {
+        true
+    }
+
+    fn create_groups_accumulator(&self, args: AccumulatorArgs) -> Result<Box<dyn GroupsAccumulator>> {
+        ConditionalStat::Avg.groups_accumulator(&args)
+    }
+
+    fn simplify(&self) -> Option<AggregateFunctionSimplification> {
+        Some(ConditionalStat::Avg.simplification())
+    }
+}
+
+/// Calls `f` with the row index of every row whose value is non-null and whose condition and
+/// `opt_filter` are true.
+fn for_each_matching(
+    values: &dyn Array,
+    conditions: &BooleanArray,
+    opt_filter: Option<&BooleanArray>,
+    mut f: impl FnMut(usize),
+) {
+    for i in 0..values.len() {
+        let selected = opt_filter.map_or(true, |filter| filter.is_valid(i) && filter.value(i));
+        if selected && conditions.is_valid(i) && conditions.value(i) && values.is_valid(i) {
+            f(i);
+        }
+    }
+}
+
+/// Evaluates the sums and counts of the matching rows of one or more groups.
+fn evaluate<T: ArrowPrimitiveType>(stat: ConditionalStat, sums: Vec<T::Native>, counts: Vec<i64>) -> Result<ArrayRef> {
+    if stat == ConditionalStat::Count {
+        return Ok(Arc::new(Int64Array::from(counts)));
+    }
+    let sums: PrimitiveArray<T> = sums
+        .into_iter()
+        .zip(&counts)
+        .map(|(sum, &count)| (count > 0).then_some(sum))
+        .collect();
+    match stat {
+        ConditionalStat::Avg => Ok(div(
+            &cast(&sums, &DataType::Float64)?,
+            &cast(&Int64Array::from(counts), &DataType::Float64)?,
+        )?),
+        _ => Ok(Arc::new(sums)),
+    }
+}
+
+/// Accumulator of the sum and count of the matching rows, shared by [`CountIfFunction`],
+/// [`SumIfFunction`] and [`AvgIfFunction`].
+#[derive(Debug)]
+pub struct ConditionalAccumulator<T: ArrowPrimitiveType> {
+    sum: T::Native,
+    count: i64,
+    stat: ConditionalStat,
+}
+
+impl<T: ArrowPrimitiveType> ConditionalAccumulator<T> {
+    pub fn new(stat: ConditionalStat) -> Self {
+        Self {
+            sum: T::default_value(),
+            count: 0,
+            stat,
+        }
+    }
+}
+
+impl<T: ArrowPrimitiveType + Debug> Accumulator for ConditionalAccumulator<T> {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        let conditions = as_boolean_array(&values[1])?;
+        if self.stat == ConditionalStat::Count {
+            for_each_matching(&values[0], conditions, None, |_| self.count += 1);
+            return Ok(());
+        }
+        let values = as_primitive_array::<T>(&values[0])?;
+        for_each_matching(values, conditions, None, |i| {
+            self.sum = self.sum.add_wrapping(values.value(i));
+            self.count += 1;
+        });
+        Ok(())
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        let counts = as_int64_array(&states[states.len() - 1])?;
+        if self.stat == ConditionalStat::Count {
+            self.count += counts.iter().flatten().sum::<i64>();
+            return Ok(());
+        }
+        let sums = as_primitive_array::<T>(&states[0])?;
+        for i in 0..counts.len() {
+            if counts.is_valid(i) && sums.is_valid(i) {
+                self.sum = self.sum.add_wrapping(sums.value(i));
+                self.count += counts.value(i);
+            }
+        }
+        Ok(())
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        let count = ScalarValue::Int64(Some(self.count));
+        match self.stat {
+            ConditionalStat::Count => Ok(vec![count]),
+            _ => Ok(vec![
+                ScalarValue::new_primitive::<T>(Some(self.sum), &T::DATA_TYPE)?,
+                count,
+            ]),
+        }
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        let result = evaluate::<T>(self.stat, vec![self.sum], vec![self.count])?;
+        ScalarValue::try_from_array(&result, 0)
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self)
+    }
+}
+
+/// [`GroupsAccumulator`] for [`ConditionalAccumulator`], which keeps the sums and counts of
+/// every group in contiguous vectors instead of allocating an [`Accumulator`] per group.
+#[derive(Debug)]
+pub struct ConditionalGroupsAccumulator<T: ArrowPrimitiveType> {
+    sums: Vec<T::Native>,
+    counts: Vec<i64>,
+    stat: ConditionalStat,
+    phantom: PhantomData<T>,
+}
+
+impl<T: ArrowPrimitiveType> ConditionalGroupsAccumulator<T> {
+    pub fn new(stat: ConditionalStat) -> Self {
+        Self {
+            sums: vec![],
+            counts: vec![],
+            stat,
+            phantom: PhantomData,
+        }
+    }
+
+    fn resize(&mut self, total_num_groups: usize) {
+        if self.stat != ConditionalStat::Count {
+            self.sums.resize(total_num_groups, T::default_value());
+        }
+        self.counts.resize(total_num_groups, 0);
+    }
+}
+
+impl<T: ArrowPrimitiveType + Debug + Send> GroupsAccumulator for ConditionalGroupsAccumulator<T> {
+    fn update_batch(
+        &mut self,
+        values: &[ArrayRef],
+        group_indices: &[usize],
+        opt_filter: Option<&BooleanArray>,
+        total_num_groups: usize,
+    ) -> Result<()> {
+        self.resize(total_num_groups);
+        let conditions = as_boolean_array(&values[1])?;
+        if self.stat == ConditionalStat::Count {
+            for_each_matching(&values[0], conditions, opt_filter, |i| {
+                self.counts[group_indices[i]] += 1
+            });
+            return Ok(());
+        }
+        let values = as_primitive_array::<T>(&values[0])?;
+        for_each_matching(values, conditions, opt_filter, |i| {
+            let group_index = group_indices[i];
+            self.sums[group_index] = self.sums[group_index].add_wrapping(values.value(i));
+            self.counts[group_index] += 1;
+        });
+        Ok(())
+    }
+
+    fn merge_batch(
+        &mut self,
+        values: &[ArrayRef],
+        group_indices: &[usize],
+        opt_filter: Option<&BooleanArray>,
+        total_num_groups: usize,
+    ) -> Result<()> {
+        self.resize(total_num_groups);
+        let counts = as_int64_array(&values[values.len() - 1])?;
+        let sums = (self.stat != ConditionalStat::Count)
+            .then(|| as_primitive_array::<T>(&values[0]))
+            .transpose()?;
+        for (i, &group_index) in group_indices.iter().enumerate() {
+            let selected = opt_filter.map_or(true, |filter| filter.is_valid(i) && filter.value(i));
+            if !selected || counts.is_null(i) {
+                continue;
+            }
+            if let Some(sums) = sums {
+                self.sums[group_index] = self.sums[group_index].add_wrapping(sums.value(i));
+            }
+            self.counts[group_index] += counts.value(i);
+        }
+        Ok(())
+    }
+
+    fn evaluate(&mut self, emit_to: EmitTo) -> Result<ArrayRef> {
+        let sums = emit_to.take_needed(&mut self.sums);
+        let counts = emit_to.take_needed(&mut self.counts);
+        evaluate::<T>(self.stat, sums, counts)
+    }
+
+    fn state(&mut self, emit_to: EmitTo) -> Result<Vec<ArrayRef>> {
+        let sums = emit_to.take_needed(&mut self.sums);
+        let counts: ArrayRef = Arc::new(Int64Array::from(emit_to.take_needed(&mut self.counts)));
+        match self.stat {
+            ConditionalStat::Count => Ok(vec![counts]),
+            _ => Ok(vec![Arc::new(PrimitiveArray::<T>::from_iter_values(sums)), counts]),
+        }
+    }
+
+    fn size(&self) -> usize {
+        self.sums.capacity() * std::mem::size_of::<T::Native>() + self.counts.capacity() * std::mem::size_of::<i64>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::array::Float64Array;
+
+    use super::*;
+
+    #[test]
+    fn test_conditional_groups_accumulator() -> Result<()> {
+        let values: ArrayRef = Arc::new(Int64Array::from(vec![
+            Some(1),
+            Some(2),
+            None,
+            Some(4),
+            Some(5),
+            Some(6),
+        ]));
+        let conditions: ArrayRef = Arc::new(BooleanArray::from(vec![
+            Some(true),
+            Some(true),
+            Some(true),
+            None,
+            Some(false),
+            Some(true),
+        ]));
+        let filter = BooleanArray::from(vec![true, true, true, true, true, false]);
+
+        let mut acc = ConditionalGroupsAccumulator::<Int64Type>::new(ConditionalStat::Sum);
+        acc.update_batch(&[values, conditions], &[0, 0, 1, 1, 2, 2], Some(&filter), 3)?;
+
+        // merging the state into a new accumulator keeps the sums of each group
+        let state = acc.state(EmitTo::All)?;
+        let mut merged = ConditionalGroupsAccumulator::<Int64Type>::new(ConditionalStat::Sum);
+        merged.merge_batch(&state, &[0, 1, 2], None, 3)?;
+
+        let expected: ArrayRef = Arc::new(Int64Array::from(vec![Some(3), None, None]));
+        assert_eq!(&merged.evaluate(EmitTo::All)?, &expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_conditional_accumulator() -> Result<()> {
+        let values: ArrayRef = Arc::new(Float64Array::from(vec![Some(1.0), Some(2.0), None, Some(6.0)]));
+        let conditions: ArrayRef = Arc::new(BooleanArray::from(vec![true, false, true, true]));
+
+        let mut avg = ConditionalAccumulator::<Float64Type>::new(ConditionalStat::Avg);
+        avg.update_batch(&[Arc::clone(&values), Arc::clone(&conditions)])?;
+        assert_eq!(avg.evaluate()?, ScalarValue::Float64(Some(3.5)));
+
+        let mut count = ConditionalAccumulator::<Int64Type>::new(ConditionalStat::Count);
+        assert_eq!(count.evaluate()?, ScalarValue::Int64(Some(0)));
+        count.update_batch(&[values, conditions])?;
+        assert_eq!(count.evaluate()?, ScalarValue::Int64(Some(2)));
+        Ok(())
+    }
+}
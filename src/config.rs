@@ -0,0 +1,268 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Session options of this crate, set with `SET datafusion_functions_extra.<option> = <value>`
+//! once [`ExtraFunctionsConfig`] is added to the session with
+//! `SessionConfig::with_option_extension`.
+
+use std::any::Any;
+use std::collections::BTreeMap;
+
+use datafusion::arrow::datatypes::DataType;
+use datafusion::common::config::{ConfigEntry, ConfigExtension, ConfigOptions, ExtensionOptions};
+use datafusion::common::{config_err, Result, ScalarValue};
+
+use crate::common::calendar::{self, BUILTIN_CALENDARS};
+
+/// Options of the functions of this crate.
+///
+/// Implements [`ExtensionOptions`] by hand as `extensions_options!` does not support optional
+/// values.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ExtraFunctionsConfig {
+    /// Seed of the randomized functions, such as `reservoir_sample`, random for every query if
+    /// unset. `reservoir_sample` keys its values by a hash of the seed and the value, so its
+    /// results are reproducible for the same input whatever its order and partitioning. Read
+    /// when queries are planned.
+    pub seed: Option<u64>,
+    /// Whether functions returning low-cardinality strings return them as dictionaries shared
+    /// across batches, see [`crate::common::interner`]. Read when queries are planned.
+    pub intern_strings: bool,
+    /// Holidays of the custom calendars of the business day functions by name, as days since
+    /// 1970-01-01, see [`crate::common::calendar::Calendars`]. Set with
+    /// `SET datafusion_functions_extra.calendars.<name> = '2024-12-24, 2024-12-31'` and read
+    /// when queries are planned.
+    pub calendars: BTreeMap<String, Vec<i32>>,
+    /// Month fiscal years start in, from 1 to 12, for `fiscal_year` and `fiscal_quarter` called
+    /// without a start month. January if unset. Read when queries are planned.
+    pub fiscal_year_start_month: Option<u32>,
+}
+
+impl ConfigExtension for ExtraFunctionsConfig {
+    const PREFIX: &'static str = "datafusion_functions_extra";
+}
+
+impl ExtensionOptions for ExtraFunctionsConfig {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn cloned(&self) -> Box<dyn ExtensionOptions> {
+        Box::new(self.clone())
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        match key {
+            "seed" if value.is_empty() || value.eq_ignore_ascii_case("null") => self.seed = None,
+            "seed" => match value.parse() {
+                Ok(seed) => self.seed = Some(seed),
+                Err(e) => return config_err!("Invalid {}.seed '{value}': {e}", Self::PREFIX),
+            },
+            "fiscal_year_start_month" if value.is_empty() || value.eq_ignore_ascii_case("null") => {
+                self.fiscal_year_start_month = None
+            }
+            "fiscal_year_start_month" => match value.parse() {
+                Ok(month) if (1..=12).contains(&month) => self.fiscal_year_start_month = Some(month),
+                _ => {
+                    return config_err!(
+                        "Invalid {}.fiscal_year_start_month '{value}': expected a month from 1 to 12",
+                        Self::PREFIX
+                    )
+                }
+            },
+            "intern_strings" => match value.parse() {
+                Ok(intern_strings) => self.intern_strings = intern_strings,
+                Err(e) => return config_err!("Invalid {}.intern_strings '{value}': {e}", Self::PREFIX),
+            },
+            key => match key.strip_prefix("calendars.") {
+                Some(name) if BUILTIN_CALENDARS.contains(&name.to_ascii_lowercase().as_str()) => {
+                    return config_err!(
+                        "Invalid {}.{key}: can't replace the built-in calendar {name}",
+                        Self::PREFIX
+                    )
+                }
+                Some(name) if !name.is_empty() => match parse_dates(value) {
+                    Ok(holidays) => {
+                        self.calendars.insert(name.to_ascii_lowercase(), holidays);
+                    }
+                    Err(e) => return config_err!("Invalid {}.{key} '{value}': {e}", Self::PREFIX),
+                },
+                _ => return config_err!("Unknown option {}.{key}", Self::PREFIX),
+            },
+        }
+        Ok(())
+    }
+
+    fn entries(&self) -> Vec<ConfigEntry> {
+        vec![
+            ConfigEntry {
+                key: format!("{}.seed", Self::PREFIX),
+                value: self.seed.map(|seed| seed.to_string()),
+                description: "Seed of the randomized functions, random for every query if unset, \
+                    read when queries are planned",
+            },
+            ConfigEntry {
+                key: format!("{}.intern_strings", Self::PREFIX),
+                value: Some(self.intern_strings.to_string()),
+                description: "Whether functions returning low-cardinality strings return shared dictionaries, \
+                    read when queries are planned",
+            },
+            ConfigEntry {
+                key: format!("{}.fiscal_year_start_month", Self::PREFIX),
+                value: self.fiscal_year_start_month.map(|month| month.to_string()),
+                description: "Month fiscal years start in for fiscal_year and fiscal_quarter, January if unset, \
+                    read when queries are planned",
+            },
+        ]
+        .into_iter()
+        .chain(self.calendars.iter().map(|(name, holidays)| {
+            ConfigEntry {
+                key: format!("{}.calendars.{name}", Self::PREFIX),
+                value: Some(
+                    holidays
+                        .iter()
+                        .map(|day| ScalarValue::Date32(Some(*day)).to_string())
+                        .collect::<Vec<_>>()
+                        .join(","),
+                ),
+                description: "Holidays of a custom calendar of the business day functions, \
+                read when queries are planned",
+            }
+        }))
+        .collect()
+    }
+}
+
+impl ExtraFunctionsConfig {
+    /// The options of this crate in `config`, or `None` if the extension is not registered
+    pub fn from_config(config: &ConfigOptions) -> Option<&Self> {
+        config.extensions.get::<Self>()
+    }
+}
+
+/// Parses the comma-separated dates `value`, which must be supported by the calendars
+fn parse_dates(value: &str) -> std::result::Result<Vec<i32>, String> {
+    let mut days = vec![];
+    for date in value.split(',').map(str::trim).filter(|date| !date.is_empty()) {
+        match ScalarValue::from(date).cast_to(&DataType::Date32) {
+            Ok(ScalarValue::Date32(Some(day))) if calendar::is_supported(day) => days.push(day),
+            Ok(_) => return Err(format!("{date} is not between 1900-01-01 and 2199-12-31")),
+            Err(_) => return Err(format!("{date} is not a date")),
+        }
+    }
+    Ok(days)
+}
+
+/// Derives the seed of `partition` from the query `seed`, so that partitions, or any other units
+/// such as accumulators, draw decorrelated yet reproducible random streams.
+///
+/// Uses the SplitMix64 finalizer, which maps consecutive inputs to unrelated outputs.
+pub fn partition_seed(seed: u64, partition: usize) -> u64 {
+    let mut z = seed.wrapping_add((partition as u64 + 1).wrapping_mul(0x9E37_79B9_7F4A_7C15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::common::config::Extensions;
+
+    #[test]
+    fn test_seed_option() {
+        let mut extensions = Extensions::new();
+        extensions.insert(ExtraFunctionsConfig::default());
+        let mut config = ConfigOptions::new().with_extensions(extensions);
+        assert_eq!(ExtraFunctionsConfig::from_config(&config).unwrap().seed, None);
+
+        config.set("datafusion_functions_extra.seed", "42").unwrap();
+        assert_eq!(ExtraFunctionsConfig::from_config(&config).unwrap().seed, Some(42));
+        assert!(config.set("datafusion_functions_extra.seed", "-1").is_err());
+        config.set("datafusion_functions_extra.seed", "NULL").unwrap();
+        assert_eq!(ExtraFunctionsConfig::from_config(&config).unwrap().seed, None);
+        assert!(ExtraFunctionsConfig::from_config(&ConfigOptions::new()).is_none());
+    }
+
+    #[test]
+    fn test_intern_strings_option() {
+        let mut extensions = Extensions::new();
+        extensions.insert(ExtraFunctionsConfig::default());
+        let mut config = ConfigOptions::new().with_extensions(extensions);
+        assert!(!ExtraFunctionsConfig::from_config(&config).unwrap().intern_strings);
+
+        config.set("datafusion_functions_extra.intern_strings", "true").unwrap();
+        assert!(ExtraFunctionsConfig::from_config(&config).unwrap().intern_strings);
+        assert!(config.set("datafusion_functions_extra.intern_strings", "yes").is_err());
+    }
+
+    #[test]
+    fn test_calendars_option() {
+        let mut extensions = Extensions::new();
+        extensions.insert(ExtraFunctionsConfig::default());
+        let mut config = ConfigOptions::new().with_extensions(extensions);
+        config
+            .set("datafusion_functions_extra.calendars.Acme", "2024-12-24, 1970-01-02")
+            .unwrap();
+        let extra = ExtraFunctionsConfig::from_config(&config).unwrap();
+        assert_eq!(extra.calendars["acme"], vec![20_081, 1]);
+        assert!(config
+            .set("datafusion_functions_extra.calendars.us", "2024-12-24")
+            .is_err());
+        assert!(config
+            .set("datafusion_functions_extra.calendars.acme", "christmas")
+            .is_err());
+        assert!(config
+            .set("datafusion_functions_extra.calendars.acme", "2200-01-01")
+            .is_err());
+    }
+
+    #[test]
+    fn test_fiscal_year_start_month_option() {
+        let mut extensions = Extensions::new();
+        extensions.insert(ExtraFunctionsConfig::default());
+        let mut config = ConfigOptions::new().with_extensions(extensions);
+        config
+            .set("datafusion_functions_extra.fiscal_year_start_month", "10")
+            .unwrap();
+        let extra = ExtraFunctionsConfig::from_config(&config).unwrap();
+        assert_eq!(extra.fiscal_year_start_month, Some(10));
+        assert!(config
+            .set("datafusion_functions_extra.fiscal_year_start_month", "0")
+            .is_err());
+        config
+            .set("datafusion_functions_extra.fiscal_year_start_month", "")
+            .unwrap();
+        assert_eq!(
+            ExtraFunctionsConfig::from_config(&config)
+                .unwrap()
+                .fiscal_year_start_month,
+            None
+        );
+    }
+
+    #[test]
+    fn test_partition_seed() {
+        assert_eq!(partition_seed(42, 3), partition_seed(42, 3));
+        assert_ne!(partition_seed(42, 0), partition_seed(42, 1));
+        assert_ne!(partition_seed(42, 0), partition_seed(43, 0));
+    }
+}
@@ -0,0 +1,357 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::any::Any;
+use std::collections::HashSet;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use arrow::array::{Array, ArrayRef, ArrowPrimitiveType, OffsetSizeTrait, PrimitiveArray};
+use arrow::compute::kernels::boolean::{and, is_not_null};
+use arrow::compute::{filter, prep_null_mask_filter};
+use arrow::datatypes::*;
+use datafusion::arrow;
+use datafusion::common::cast::{as_boolean_array, as_list_array, as_primitive_array};
+use datafusion::common::{not_impl_err, plan_err, Result, ScalarValue};
+use datafusion::logical_expr::function::{AccumulatorArgs, StateFieldsArgs};
+use datafusion::logical_expr::{Accumulator, AggregateUDFImpl, Signature, Volatility};
+
+use crate::compat::{single_row_list, ArrowBytesSet, ArrowBytesViewSet, Hashable, OutputType};
+
+make_udaf_expr_and_func!(
+    CountDistinctIfFunction,
+    count_distinct_if,
+    x condition,
+    "Counts the distinct non-null values of rows where the condition is true.",
+    count_distinct_if_udaf
+);
+
+/// The `CountDistinctIfFunction` counts the distinct non-null values of the rows where a
+/// condition is true, like ClickHouse's `uniqExactIf`.
+///
+/// - Rows where the condition is false or NULL are skipped.
+/// - Strings and binaries are deduplicated with the byte sets of [`crate::common::collections`],
+///   so `Utf8View` and `BinaryView` values are filtered and hashed without being copied.
+/// - The result is 0 if no row matches, as for `count(DISTINCT x)`.
+pub struct CountDistinctIfFunction {
+    signature: Signature,
+}
+
+impl Debug for CountDistinctIfFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CountDistinctIfFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for CountDistinctIfFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CountDistinctIfFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+        }
+    }
+}
+
+impl AggregateUDFImpl for CountDistinctIfFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "count_distinct_if"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        let [value_type, condition_type] = arg_types else {
+            return plan_err!("count_distinct_if expects a value and a condition");
+        };
+        let value_type = match value_type {
+            DataType::Dictionary(_, value_type) => value_type.as_ref().clone(),
+            DataType::Null => DataType::Int64,
+            value_type => value_type.clone(),
+        };
+        match condition_type {
+            DataType::Boolean | DataType::Null => Ok(vec![value_type, DataType::Boolean]),
+            _ => plan_err!("count_distinct_if expects a boolean condition, got {condition_type}"),
+        }
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Int64)
+    }
+
+    fn is_nullable(&self) -> bool {
+        false
+    }
+
+    fn default_value(&self, _data_type: &DataType) -> Result<ScalarValue> {
+        Ok(ScalarValue::Int64(Some(0)))
+    }
+
+    fn state_fields(&self, args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(vec![Field::new_list(
+            "distinct_values",
+            Field::new("item", args.input_types[0].clone(), true),
+            true,
+        )])
+    }
+
+    fn accumulator(&self, acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        create_count_distinct_if_accumulator(&crate::compat::arg_type(&acc_args, 0)?)
+    }
+}
+
+fn create_count_distinct_if_accumulator(data_type: &DataType) -> Result<Box<dyn Accumulator>> {
+    macro_rules! primitive {
+        ($t:ty) => {
+            Box::new(PrimitiveDistinctIfAccumulator::<$t>::new(data_type))
+        };
+    }
+    let accumulator: Box<dyn Accumulator> = match data_type {
+        DataType::Int8 => primitive!(Int8Type),
+        DataType::Int16 => primitive!(Int16Type),
+        DataType::Int32 => primitive!(Int32Type),
+        DataType::Int64 => primitive!(Int64Type),
+        DataType::UInt8 => primitive!(UInt8Type),
+        DataType::UInt16 => primitive!(UInt16Type),
+        DataType::UInt32 => primitive!(UInt32Type),
+        DataType::UInt64 => primitive!(UInt64Type),
+        DataType::Float16 => primitive!(Float16Type),
+        DataType::Float32 => primitive!(Float32Type),
+        DataType::Float64 => primitive!(Float64Type),
+        DataType::Decimal128(_, _) => primitive!(Decimal128Type),
+        DataType::Date32 => primitive!(Date32Type),
+        DataType::Date64 => primitive!(Date64Type),
+        DataType::Time32(TimeUnit::Second) => primitive!(Time32SecondType),
+        DataType::Time32(TimeUnit::Millisecond) => primitive!(Time32MillisecondType),
+        DataType::Time64(TimeUnit::Microsecond) => primitive!(Time64MicrosecondType),
+        DataType::Time64(TimeUnit::Nanosecond) => primitive!(Time64NanosecondType),
+        DataType::Timestamp(TimeUnit::Second, _) => primitive!(TimestampSecondType),
+        DataType::Timestamp(TimeUnit::Millisecond, _) => primitive!(TimestampMillisecondType),
+        DataType::Timestamp(TimeUnit::Microsecond, _) => primitive!(TimestampMicrosecondType),
+        DataType::Timestamp(TimeUnit::Nanosecond, _) => primitive!(TimestampNanosecondType),
+
+        DataType::Utf8 => Box::new(BytesDistinctIfAccumulator::<i32>::new(OutputType::Utf8)),
+        DataType::LargeUtf8 => Box::new(BytesDistinctIfAccumulator::<i64>::new(OutputType::Utf8)),
+        DataType::Binary => Box::new(BytesDistinctIfAccumulator::<i32>::new(OutputType::Binary)),
+        DataType::LargeBinary => Box::new(BytesDistinctIfAccumulator::<i64>::new(OutputType::Binary)),
+        DataType::Utf8View => Box::new(BytesViewDistinctIfAccumulator::new(OutputType::Utf8View)),
+        DataType::BinaryView => Box::new(BytesViewDistinctIfAccumulator::new(OutputType::BinaryView)),
+        _ => return not_impl_err!("Unsupported data type: {data_type} for count_distinct_if function"),
+    };
+    Ok(accumulator)
+}
+
+/// The non-null values of the rows whose condition is true.
+///
+/// Filtering a view array keeps referencing its data buffers, so long strings aren't copied.
+fn matching_values(values: &[ArrayRef]) -> Result<ArrayRef> {
+    let conditions = as_boolean_array(&values[1])?;
+    let conditions = match conditions.nulls() {
+        Some(_) => prep_null_mask_filter(conditions),
+        None => conditions.clone(),
+    };
+    let mask = and(&conditions, &is_not_null(&values[0])?)?;
+    Ok(filter(&values[0], &mask)?)
+}
+
+/// Accumulator of the distinct matching values of a primitive type.
+#[derive(Debug)]
+pub struct PrimitiveDistinctIfAccumulator<T: ArrowPrimitiveType> {
+    values: HashSet<Hashable<T::Native>>,
+    data_type: DataType,
+}
+
+impl<T: ArrowPrimitiveType> PrimitiveDistinctIfAccumulator<T> {
+    pub fn new(data_type: &DataType) -> Self {
+        Self {
+            values: HashSet::default(),
+            data_type: data_type.clone(),
+        }
+    }
+
+    fn insert(&mut self, values: &ArrayRef) -> Result<()> {
+        let values = as_primitive_array::<T>(values)?;
+        self.values.extend(values.iter().flatten().map(Hashable));
+        Ok(())
+    }
+}
+
+impl<T: ArrowPrimitiveType + Debug> Accumulator for PrimitiveDistinctIfAccumulator<T> {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        self.insert(&matching_values(values)?)
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        for values in as_list_array(&states[0])?.iter().flatten() {
+            self.insert(&values)?;
+        }
+        Ok(())
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        let values = PrimitiveArray::<T>::from_iter_values(self.values.iter().map(|value| value.0))
+            .with_data_type(self.data_type.clone());
+        Ok(vec![ScalarValue::List(Arc::new(single_row_list(Arc::new(values))))])
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        Ok(ScalarValue::Int64(Some(self.values.len() as i64)))
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self) + self.values.capacity() * std::mem::size_of::<T::Native>()
+    }
+}
+
+/// Accumulator of the distinct matching strings or binaries with offsets `O`.
+#[derive(Debug)]
+pub struct BytesDistinctIfAccumulator<O: OffsetSizeTrait> {
+    values: ArrowBytesSet<O>,
+}
+
+impl<O: OffsetSizeTrait> BytesDistinctIfAccumulator<O> {
+    pub fn new(output_type: OutputType) -> Self {
+        Self {
+            values: ArrowBytesSet::new(output_type),
+        }
+    }
+}
+
+impl<O: OffsetSizeTrait> Accumulator for BytesDistinctIfAccumulator<O> {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        self.values.insert(&matching_values(values)?);
+        Ok(())
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        for values in as_list_array(&states[0])?.iter().flatten() {
+            self.values.insert(&values);
+        }
+        Ok(())
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        let values = self.values.take().into_state();
+        Ok(vec![ScalarValue::List(Arc::new(single_row_list(values)))])
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        Ok(ScalarValue::Int64(Some(self.values.non_null_len() as i64)))
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self) + self.values.size()
+    }
+}
+
+/// Accumulator of the distinct matching `Utf8View` or `BinaryView` values.
+#[derive(Debug)]
+pub struct BytesViewDistinctIfAccumulator {
+    values: ArrowBytesViewSet,
+}
+
+impl BytesViewDistinctIfAccumulator {
+    pub fn new(output_type: OutputType) -> Self {
+        Self {
+            values: ArrowBytesViewSet::new(output_type),
+        }
+    }
+}
+
+impl Accumulator for BytesViewDistinctIfAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        self.values.insert(&matching_values(values)?);
+        Ok(())
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        for values in as_list_array(&states[0])?.iter().flatten() {
+            self.values.insert(&values);
+        }
+        Ok(())
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        let values = self.values.take().into_state();
+        Ok(vec![ScalarValue::List(Arc::new(single_row_list(values)))])
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        Ok(ScalarValue::Int64(Some(self.values.non_null_len() as i64)))
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self) + self.values.size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::array::{BooleanArray, Float64Array, StringViewArray};
+
+    use super::*;
+
+    #[test]
+    fn test_count_distinct_if_string_view() -> Result<()> {
+        let values: ArrayRef = Arc::new(StringViewArray::from(vec![
+            Some("a string longer than twelve bytes"),
+            Some("short"),
+            Some("a string longer than twelve bytes"),
+            None,
+            Some("skipped"),
+        ]));
+        let conditions: ArrayRef = Arc::new(BooleanArray::from(vec![
+            Some(true),
+            Some(true),
+            Some(true),
+            Some(true),
+            None,
+        ]));
+        let mut acc = create_count_distinct_if_accumulator(&DataType::Utf8View)?;
+        acc.update_batch(&[values, conditions])?;
+
+        let state: Vec<ArrayRef> = acc.state()?.iter().map(|s| s.to_array()).collect::<Result<_>>()?;
+        let mut merged = create_count_distinct_if_accumulator(&DataType::Utf8View)?;
+        merged.merge_batch(&state)?;
+        merged.merge_batch(&state)?;
+        assert_eq!(merged.evaluate()?, ScalarValue::Int64(Some(2)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_count_distinct_if_floats() -> Result<()> {
+        let values: ArrayRef = Arc::new(Float64Array::from(vec![0.0, -0.0, 1.0, 1.0, f64::NAN, f64::NAN]));
+        let conditions: ArrayRef = Arc::new(BooleanArray::from(vec![true, true, true, false, true, true]));
+        let mut acc = create_count_distinct_if_accumulator(&DataType::Float64)?;
+        acc.update_batch(&[values, conditions])?;
+        // the values are compared by their bits, as by `count(DISTINCT x)`
+        assert_eq!(acc.evaluate()?, ScalarValue::Int64(Some(4)));
+        Ok(())
+    }
+}
@@ -0,0 +1,411 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+use std::any::Any;
+use std::fmt::Debug;
+
+use arrow::array::ArrayRef;
+use arrow::datatypes::{DataType, Field};
+use datafusion::arrow;
+use datafusion::common::{plan_err, Result, ScalarValue};
+use datafusion::logical_expr::function::{AccumulatorArgs, StateFieldsArgs};
+use datafusion::logical_expr::{Accumulator, AggregateUDFImpl, Signature, Volatility};
+
+use crate::common::decay::{coerce_ts, duration_arg, seconds_per_unit};
+use crate::common::samples::SampleBuffer;
+use crate::compat::arg_type;
+
+make_udaf_expr_and_func!(
+    DeltaSumFunction,
+    delta_sum,
+    value ts,
+    "Sums the increases of a monotonic counter in the order of ts, counting a decrease as a reset to zero.",
+    delta_sum_udaf
+);
+
+make_udaf_expr_and_func!(
+    CounterRateFunction,
+    counter_rate,
+    value ts,
+    "Computes the per-second rate of increase of a monotonic counter, handling resets to zero.",
+    counter_rate_udaf
+);
+
+make_udaf_expr_and_func!(
+    IncreaseFunction,
+    increase,
+    value ts window,
+    "Computes the increase of a counter over windows of ts, extrapolated to their boundaries like PromQL's increase.",
+    increase_udaf
+);
+
+make_udaf_expr_and_func!(
+    RateFunction,
+    rate,
+    value ts window,
+    "Computes the per-second rate of a counter over windows of ts, extrapolated to their boundaries like PromQL's rate.",
+    rate_udaf
+);
+
+/// What the counter aggregates return, [`DeltaSumFunction`], [`CounterRateFunction`],
+/// [`IncreaseFunction`] or [`RateFunction`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CounterOutput {
+    /// The increase of the counter
+    Increase,
+    /// The increase divided by the time between the first and the last sample
+    Rate,
+    /// The increase extrapolated to the boundaries of the windows of the samples
+    ExtrapolatedIncrease,
+    /// The extrapolated increase divided by the duration of the windows
+    ExtrapolatedRate,
+}
+
+impl CounterOutput {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Increase => "delta_sum",
+            Self::Rate => "counter_rate",
+            Self::ExtrapolatedIncrease => "increase",
+            Self::ExtrapolatedRate => "rate",
+        }
+    }
+
+    fn is_extrapolated(&self) -> bool {
+        matches!(self, Self::ExtrapolatedIncrease | Self::ExtrapolatedRate)
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        let (value_type, ts_type, window_type) = match (self.is_extrapolated(), arg_types) {
+            (false, [value_type, ts_type]) => (value_type, ts_type, None),
+            (true, [value_type, ts_type, window_type]) => (value_type, ts_type, Some(window_type)),
+            (false, _) => {
+                return plan_err!(
+                    "{} expects a value and a ts, got {} arguments",
+                    self.name(),
+                    arg_types.len()
+                )
+            }
+            (true, _) => {
+                return plan_err!(
+                    "{} expects a value, a ts and a window, got {} arguments",
+                    self.name(),
+                    arg_types.len()
+                )
+            }
+        };
+        if !value_type.is_numeric() && !value_type.is_null() {
+            return plan_err!("{} expects a numeric value, got {value_type}", self.name());
+        }
+        let mut coerced = vec![DataType::Float64, coerce_ts(self.name(), ts_type)?];
+        coerced.extend(window_type.cloned());
+        Ok(coerced)
+    }
+
+    fn accumulator(&self, acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        let time_scale = seconds_per_unit(&arg_type(&acc_args, 1)?);
+        let acc = CounterAccumulator::new(*self, time_scale.unwrap_or(1.0));
+        if !self.is_extrapolated() {
+            return Ok(Box::new(acc));
+        }
+        let window = duration_arg(&acc_args.exprs[2], self.name(), "window", time_scale.is_some())?;
+        Ok(Box::new(acc.with_window(window)))
+    }
+}
+
+/// The `DeltaSumFunction` sums the increases of a monotonic counter, `delta_sum(value, ts)`, like
+/// Prometheus' `increase` over the samples of a group.
+///
+/// - The samples are ordered by `ts`, a timestamp, a date or an integer, and every increase from
+///   one sample to the next is summed. A decrease is a reset of the counter to zero, so the value
+///   after it is its increase.
+/// - The samples are buffered until the result is emitted, as those of different partitions
+///   interleave in time.
+/// - Rows with a NULL value or `ts` are ignored. The result is NULL if every row is ignored, and
+///   0 for a single sample.
+pub struct DeltaSumFunction {
+    signature: Signature,
+}
+
+/// The `CounterRateFunction` computes the rate of increase of a monotonic counter,
+/// `counter_rate(value, ts)`, like Prometheus' `rate` over the samples of a group.
+///
+/// - The increase is that of [`DeltaSumFunction`], divided by the time between the first and
+///   the last sample: per second for timestamps and dates, per unit of `ts` for integers.
+/// - Rows with a NULL value or `ts` are ignored. The result is NULL unless the samples span some
+///   time.
+pub struct CounterRateFunction {
+    signature: Signature,
+}
+
+/// The `IncreaseFunction` computes the increase of a counter, `increase(value, ts, window)`,
+/// replicating PromQL's `increase(value[window])`.
+///
+/// - The group is expected to be a window of `ts`, aligned on the epoch like the bins of
+///   `date_bin(window, ts)`. Groups spanning several windows are extrapolated to the boundaries
+///   of the first and the last window of their samples.
+/// - The increase of [`DeltaSumFunction`] is extrapolated to the start and the end of the window
+///   as PromQL does: by the gap to the boundary if it's within 110% of the average interval
+///   between samples, by half that interval otherwise, and never to before the counter was 0.
+/// - `window` is a literal interval without months or a number of seconds, or a number in the
+///   unit of `ts` for integers.
+/// - Rows with a NULL value or `ts` are ignored. The result is NULL unless there are samples at
+///   two different times.
+pub struct IncreaseFunction {
+    signature: Signature,
+}
+
+/// The `RateFunction` computes the per-second rate of a counter, `rate(value, ts, window)`,
+/// replicating PromQL's `rate(value[window])`.
+///
+/// - The increase of [`IncreaseFunction`] is divided by the duration of the windows, in seconds
+///   for timestamps and dates and in the unit of `ts` for integers.
+/// - Rows with a NULL value or `ts` are ignored. The result is NULL unless there are samples at
+///   two different times.
+pub struct RateFunction {
+    signature: Signature,
+}
+
+macro_rules! counter_function {
+    ($UDAF:ident, $OUTPUT:expr) => {
+        impl Debug for $UDAF {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.debug_struct(stringify!($UDAF))
+                    .field("signature", &self.signature)
+                    .finish()
+            }
+        }
+
+        impl Default for $UDAF {
+            fn default() -> Self {
+                Self::new()
+            }
+        }
+
+        impl $UDAF {
+            pub fn new() -> Self {
+                Self {
+                    signature: Signature::user_defined(Volatility::Immutable),
+                }
+            }
+        }
+
+        impl AggregateUDFImpl for $UDAF {
+            fn as_any(&self) -> &dyn Any {
+                self
+            }
+
+            fn name(&self) -> &str {
+                $OUTPUT.name()
+            }
+
+            fn signature(&self) -> &Signature {
+                &self.signature
+            }
+
+            fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+                $OUTPUT.coerce_types(arg_types)
+            }
+
+            fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+                Ok(DataType::Float64)
+            }
+
+            fn state_fields(&self, _args: StateFieldsArgs) -> Result<Vec<Field>> {
+                Ok(SampleBuffer::state_fields())
+            }
+
+            fn accumulator(&self, acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+                $OUTPUT.accumulator(acc_args)
+            }
+        }
+    };
+}
+
+counter_function!(DeltaSumFunction, CounterOutput::Increase);
+counter_function!(CounterRateFunction, CounterOutput::Rate);
+counter_function!(IncreaseFunction, CounterOutput::ExtrapolatedIncrease);
+counter_function!(RateFunction, CounterOutput::ExtrapolatedRate);
+
+/// Accumulator for the counter aggregates, buffering the samples
+#[derive(Debug)]
+pub struct CounterAccumulator {
+    output: CounterOutput,
+    time_scale: f64,
+    /// The duration of the windows extrapolated to, in the unit of the times
+    window: f64,
+    samples: SampleBuffer,
+}
+
+impl CounterAccumulator {
+    pub fn new(output: CounterOutput, time_scale: f64) -> Self {
+        Self {
+            output,
+            time_scale,
+            window: f64::INFINITY,
+            samples: SampleBuffer::new(),
+        }
+    }
+
+    /// Sets the duration of the windows of [`CounterOutput::ExtrapolatedIncrease`] and
+    /// [`CounterOutput::ExtrapolatedRate`]
+    pub fn with_window(mut self, window: f64) -> Self {
+        self.window = window;
+        self
+    }
+}
+
+/// Extrapolates `increase` from the samples, sorted by time, to the boundaries of their windows
+/// of duration `window`, following `extrapolatedRate` in Prometheus' `promql/functions.go`
+fn extrapolate(output: CounterOutput, samples: &[(f64, f64)], window: f64, increase: f64) -> Option<f64> {
+    let (first, last) = (samples.first()?, samples.last()?);
+    let sampled = last.0 - first.0;
+    if sampled <= 0.0 {
+        return None;
+    }
+    let range_start = (first.0 / window).floor() * window;
+    let range_end = ((last.0 / window).floor() + 1.0) * window;
+    let mut to_start = first.0 - range_start;
+    let to_end = range_end - last.0;
+    let average_interval = sampled / (samples.len() - 1) as f64;
+    // a counter can't be extrapolated to before it was 0
+    if increase > 0.0 && first.1 >= 0.0 {
+        to_start = to_start.min(sampled * first.1 / increase);
+    }
+    let threshold = average_interval * 1.1;
+    let extrapolated = [to_start, to_end]
+        .into_iter()
+        .map(|gap| if gap < threshold { gap } else { average_interval / 2.0 })
+        .sum::<f64>()
+        + sampled;
+    let increase = increase * extrapolated / sampled;
+    Some(match output {
+        CounterOutput::ExtrapolatedRate => increase / (range_end - range_start),
+        _ => increase,
+    })
+}
+
+impl Accumulator for CounterAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        self.samples.update_batch(&values[0], &values[1], self.time_scale)
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        self.samples.merge_batch(&states[0], &states[1], self.output.name())
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        Ok(self.samples.state().to_vec())
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        let samples = self.samples.sorted();
+        let (Some(first), Some(last)) = (samples.first(), samples.last()) else {
+            return Ok(ScalarValue::Float64(None));
+        };
+        let increase = samples
+            .windows(2)
+            .map(|pair| match pair[1].1 - pair[0].1 {
+                delta if delta >= 0.0 => delta,
+                _ => pair[1].1,
+            })
+            .sum::<f64>();
+        let elapsed = last.0 - first.0;
+        Ok(ScalarValue::Float64(match self.output {
+            CounterOutput::Increase => Some(increase),
+            CounterOutput::Rate => (elapsed > 0.0).then(|| increase / elapsed),
+            CounterOutput::ExtrapolatedIncrease | CounterOutput::ExtrapolatedRate => {
+                extrapolate(self.output, samples, self.window, increase)
+            }
+        }))
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self) + self.samples.size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow::array::{Float64Array, Int64Array};
+
+    use super::*;
+
+    #[test]
+    fn test_counter_resets_across_partitions() -> Result<()> {
+        let batch = |times: Vec<i64>, values: Vec<Option<f64>>| -> Vec<ArrayRef> {
+            vec![Arc::new(Float64Array::from(values)), Arc::new(Int64Array::from(times))]
+        };
+        let mut partial = CounterAccumulator::new(CounterOutput::Increase, 1.0);
+        partial.update_batch(&batch(vec![30, 0], vec![Some(7.0), Some(10.0)]))?;
+        let state = partial
+            .state()?
+            .iter()
+            .map(|state| state.to_array())
+            .collect::<Result<Vec<_>>>()?;
+
+        for (output, expected) in [(CounterOutput::Increase, 12.0), (CounterOutput::Rate, 0.4)] {
+            let mut acc = CounterAccumulator::new(output, 1.0);
+            // 10 at 0, 15 at 10, reset and up to 7 at 30, NULL ignored
+            acc.update_batch(&batch(vec![10, 20], vec![Some(15.0), None]))?;
+            acc.merge_batch(&state)?;
+            assert_eq!(acc.evaluate()?, ScalarValue::Float64(Some(expected)));
+        }
+
+        let mut single = CounterAccumulator::new(CounterOutput::Rate, 1.0);
+        single.update_batch(&batch(vec![0], vec![Some(1.0)]))?;
+        assert_eq!(single.evaluate()?, ScalarValue::Float64(None));
+        Ok(())
+    }
+
+    #[test]
+    fn test_extrapolation_to_window_boundaries() -> Result<()> {
+        let evaluate = |output: CounterOutput, times: Vec<f64>, values: Vec<f64>| -> Result<ScalarValue> {
+            let mut acc = CounterAccumulator::new(output, 1.0).with_window(60.0);
+            acc.update_batch(&[
+                Arc::new(Float64Array::from(values)),
+                Arc::new(Float64Array::from(times)),
+            ])?;
+            acc.evaluate()
+        };
+        // samples every 10s, 5s from both boundaries, are extrapolated to the whole window
+        let (times, values) = (
+            (0..6).map(|i| 5.0 + i as f64 * 10.0).collect::<Vec<_>>(),
+            (1..7).map(|i| i as f64 * 10.0).collect::<Vec<_>>(),
+        );
+        assert_eq!(
+            evaluate(CounterOutput::ExtrapolatedIncrease, times.clone(), values.clone())?,
+            ScalarValue::Float64(Some(60.0))
+        );
+        assert_eq!(
+            evaluate(CounterOutput::ExtrapolatedRate, times, values)?,
+            ScalarValue::Float64(Some(1.0))
+        );
+        // the gap to the end is too long, so only half an interval is extrapolated, and the
+        // start is bounded by the counter reaching 0 at 4s
+        assert_eq!(
+            evaluate(CounterOutput::ExtrapolatedIncrease, vec![5.0, 15.0], vec![1.0, 11.0])?,
+            ScalarValue::Float64(Some(16.0))
+        );
+        assert_eq!(
+            evaluate(CounterOutput::ExtrapolatedIncrease, vec![5.0], vec![1.0])?,
+            ScalarValue::Float64(None)
+        );
+        Ok(())
+    }
+}
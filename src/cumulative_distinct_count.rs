@@ -0,0 +1,224 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::any::Any;
+use std::collections::HashSet;
+use std::fmt::Debug;
+use std::ops::Range;
+
+use arrow::array::{ArrayRef, ArrowPrimitiveType, OffsetSizeTrait};
+use arrow::datatypes::*;
+use datafusion::arrow;
+use datafusion::common::cast::as_primitive_array;
+use datafusion::common::{exec_err, not_impl_err, plan_err, Result, ScalarValue};
+use datafusion::logical_expr::{PartitionEvaluator, Signature, Volatility, WindowUDFImpl};
+
+use crate::compat::{ArrowBytesSet, ArrowBytesViewSet, Hashable, OutputType};
+
+make_udwf_expr_and_func!(
+    CumulativeDistinctCountFunction,
+    cumulative_distinct_count,
+    expression,
+    "Counts the distinct non-null values from the start of the window partition to the end of the frame.",
+    cumulative_distinct_count_udwf
+);
+
+/// The `CumulativeDistinctCountFunction` is a running `count(DISTINCT expression)` over a window
+/// partition, `cumulative_distinct_count(expression) OVER (ORDER BY ...)`.
+///
+/// - The distinct values are kept in a set as for `count_distinct_if`, and only the rows that
+///   enter the frame are inserted as it advances, rather than recounting every frame.
+/// - Frames must start at `UNBOUNDED PRECEDING`, since values can't be removed from the set. With
+///   the default frame, rows with equal `ORDER BY` values are counted together.
+/// - NULL values aren't counted, so the count is 0 until the first non-null value.
+pub struct CumulativeDistinctCountFunction {
+    signature: Signature,
+}
+
+impl Debug for CumulativeDistinctCountFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CumulativeDistinctCountFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for CumulativeDistinctCountFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CumulativeDistinctCountFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+        }
+    }
+}
+
+impl WindowUDFImpl for CumulativeDistinctCountFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "cumulative_distinct_count"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        match arg_types {
+            [DataType::Dictionary(_, value_type)] => Ok(vec![value_type.as_ref().clone()]),
+            [DataType::Null] => Ok(vec![DataType::Int64]),
+            [arg_type] => Ok(vec![arg_type.clone()]),
+            _ => plan_err!("cumulative_distinct_count expects 1 argument, got {}", arg_types.len()),
+        }
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Int64)
+    }
+
+    fn nullable(&self) -> bool {
+        false
+    }
+
+    fn partition_evaluator(&self) -> Result<Box<dyn PartitionEvaluator>> {
+        Ok(Box::<CumulativeDistinctCountEvaluator>::default())
+    }
+}
+
+/// The distinct non-null values seen by a [`CumulativeDistinctCountEvaluator`]
+trait DistinctSet: Debug + Send {
+    fn insert(&mut self, values: &ArrayRef) -> Result<()>;
+
+    fn non_null_len(&self) -> usize;
+}
+
+#[derive(Debug)]
+struct PrimitiveDistinctSet<T: ArrowPrimitiveType>(HashSet<Hashable<T::Native>>);
+
+impl<T: ArrowPrimitiveType + Debug> DistinctSet for PrimitiveDistinctSet<T> {
+    fn insert(&mut self, values: &ArrayRef) -> Result<()> {
+        self.0
+            .extend(as_primitive_array::<T>(values)?.iter().flatten().map(Hashable));
+        Ok(())
+    }
+
+    fn non_null_len(&self) -> usize {
+        self.0.len()
+    }
+}
+
+impl<O: OffsetSizeTrait> DistinctSet for ArrowBytesSet<O> {
+    fn insert(&mut self, values: &ArrayRef) -> Result<()> {
+        ArrowBytesSet::insert(self, values);
+        Ok(())
+    }
+
+    fn non_null_len(&self) -> usize {
+        ArrowBytesSet::non_null_len(self)
+    }
+}
+
+impl DistinctSet for ArrowBytesViewSet {
+    fn insert(&mut self, values: &ArrayRef) -> Result<()> {
+        ArrowBytesViewSet::insert(self, values);
+        Ok(())
+    }
+
+    fn non_null_len(&self) -> usize {
+        ArrowBytesViewSet::non_null_len(self)
+    }
+}
+
+fn create_distinct_set(data_type: &DataType) -> Result<Box<dyn DistinctSet>> {
+    macro_rules! primitive {
+        ($t:ty) => {
+            Box::new(PrimitiveDistinctSet::<$t>(HashSet::default()))
+        };
+    }
+    let set: Box<dyn DistinctSet> = match data_type {
+        DataType::Int8 => primitive!(Int8Type),
+        DataType::Int16 => primitive!(Int16Type),
+        DataType::Int32 => primitive!(Int32Type),
+        DataType::Int64 => primitive!(Int64Type),
+        DataType::UInt8 => primitive!(UInt8Type),
+        DataType::UInt16 => primitive!(UInt16Type),
+        DataType::UInt32 => primitive!(UInt32Type),
+        DataType::UInt64 => primitive!(UInt64Type),
+        DataType::Float16 => primitive!(Float16Type),
+        DataType::Float32 => primitive!(Float32Type),
+        DataType::Float64 => primitive!(Float64Type),
+        DataType::Decimal128(_, _) => primitive!(Decimal128Type),
+        DataType::Date32 => primitive!(Date32Type),
+        DataType::Date64 => primitive!(Date64Type),
+        DataType::Time32(TimeUnit::Second) => primitive!(Time32SecondType),
+        DataType::Time32(TimeUnit::Millisecond) => primitive!(Time32MillisecondType),
+        DataType::Time64(TimeUnit::Microsecond) => primitive!(Time64MicrosecondType),
+        DataType::Time64(TimeUnit::Nanosecond) => primitive!(Time64NanosecondType),
+        DataType::Timestamp(TimeUnit::Second, _) => primitive!(TimestampSecondType),
+        DataType::Timestamp(TimeUnit::Millisecond, _) => primitive!(TimestampMillisecondType),
+        DataType::Timestamp(TimeUnit::Microsecond, _) => primitive!(TimestampMicrosecondType),
+        DataType::Timestamp(TimeUnit::Nanosecond, _) => primitive!(TimestampNanosecondType),
+
+        DataType::Utf8 => Box::new(ArrowBytesSet::<i32>::new(OutputType::Utf8)),
+        DataType::LargeUtf8 => Box::new(ArrowBytesSet::<i64>::new(OutputType::Utf8)),
+        DataType::Binary => Box::new(ArrowBytesSet::<i32>::new(OutputType::Binary)),
+        DataType::LargeBinary => Box::new(ArrowBytesSet::<i64>::new(OutputType::Binary)),
+        DataType::Utf8View => Box::new(ArrowBytesViewSet::new(OutputType::Utf8View)),
+        DataType::BinaryView => Box::new(ArrowBytesViewSet::new(OutputType::BinaryView)),
+        _ => return not_impl_err!("Unsupported data type: {data_type} for cumulative_distinct_count function"),
+    };
+    Ok(set)
+}
+
+/// Evaluator for [`CumulativeDistinctCountFunction`], inserting the rows of every frame from
+/// the end of the previous one.
+#[derive(Debug, Default)]
+pub struct CumulativeDistinctCountEvaluator {
+    /// Created from the type of the first values evaluated
+    set: Option<Box<dyn DistinctSet>>,
+    /// The end of the rows inserted into `set`
+    end: usize,
+}
+
+impl PartitionEvaluator for CumulativeDistinctCountEvaluator {
+    fn uses_window_frame(&self) -> bool {
+        true
+    }
+
+    fn evaluate(&mut self, values: &[ArrayRef], range: &Range<usize>) -> Result<ScalarValue> {
+        if range.start != 0 || range.end < self.end {
+            return exec_err!("cumulative_distinct_count expects frames starting at UNBOUNDED PRECEDING");
+        }
+        let values = &values[0];
+        let set = match &mut self.set {
+            Some(set) => set,
+            set => set.insert(create_distinct_set(values.data_type())?),
+        };
+        if range.end > self.end {
+            set.insert(&values.slice(self.end, range.end - self.end))?;
+            self.end = range.end;
+        }
+        Ok(ScalarValue::Int64(Some(set.non_null_len() as i64)))
+    }
+}
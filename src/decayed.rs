@@ -0,0 +1,527 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+use std::any::Any;
+use std::fmt::Debug;
+
+use arrow::array::ArrayRef;
+use arrow::datatypes::{DataType, Field};
+use datafusion::arrow;
+use datafusion::common::cast::as_float64_array;
+use datafusion::common::{plan_err, Result, ScalarValue};
+use datafusion::logical_expr::function::{AccumulatorArgs, StateFieldsArgs};
+use datafusion::logical_expr::{Accumulator, AggregateUDFImpl, Signature, Volatility};
+
+use crate::common::decay::{coerce_ts, half_life_arg, seconds_per_unit, times, Decayed};
+use crate::compat::arg_type;
+
+make_udaf_expr_and_func!(
+    DecayedSumFunction,
+    decayed_sum,
+    value ts half_life,
+    "Sums the values weighted by an exponential decay of the age of each row.",
+    decayed_sum_udaf
+);
+
+make_udaf_expr_and_func!(
+    DecayedCountFunction,
+    decayed_count,
+    ts half_life,
+    "Counts the rows weighted by an exponential decay of their age.",
+    decayed_count_udaf
+);
+
+make_udaf_expr_and_func!(
+    DecayedAvgFunction,
+    decayed_avg,
+    value ts half_life,
+    "Averages the values weighted by an exponential decay of the age of each row.",
+    decayed_avg_udaf
+);
+
+/// The `DecayedSumFunction` sums values weighted by the decay of their age,
+/// `decayed_sum(value, ts, half_life)`.
+///
+/// - A value weighs `2^(-age / half_life)`, where `age` is the time between its `ts` and the most
+///   recent `ts` of the group, so a value one `half_life` older than another weighs half as much.
+/// - `ts` can be a timestamp, a date or an integer. `half_life` is a literal interval without
+///   months or a number of seconds, or a number in the unit of `ts` for integers.
+/// - The state is the sum decayed to the most recent `ts` of the partial group, so partial sums
+///   are merged by decaying the older one to the time of the newer one.
+/// - Rows with a NULL value or `ts` are ignored. The result is NULL if every row is ignored.
+pub struct DecayedSumFunction {
+    signature: Signature,
+}
+
+impl Debug for DecayedSumFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DecayedSumFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for DecayedSumFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DecayedSumFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+        }
+    }
+}
+
+impl AggregateUDFImpl for DecayedSumFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "decayed_sum"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        let [value_type, ts_type, half_life_type] = arg_types else {
+            return plan_err!("decayed_sum expects 3 arguments, got {}", arg_types.len());
+        };
+        if !(value_type.is_numeric() || value_type == &DataType::Null) {
+            return plan_err!("decayed_sum expects a numeric value, got {value_type}");
+        }
+        Ok(vec![
+            DataType::Float64,
+            coerce_ts(self.name(), ts_type)?,
+            half_life_type.clone(),
+        ])
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Float64)
+    }
+
+    fn state_fields(&self, _args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(decayed_state_fields())
+    }
+
+    fn accumulator(&self, acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        DecayedSumAccumulator::try_new(&acc_args, self.name(), false).map(|acc| Box::new(acc) as _)
+    }
+}
+
+/// The `DecayedCountFunction` counts rows weighted by the decay of their age,
+/// `decayed_count(ts, half_life)`.
+///
+/// Rows weigh as the values of [`DecayedSumFunction`], which `decayed_count(ts, half_life)` is
+/// equal to with a value of 1 on every row. Rows with a NULL `ts` are ignored, and the result is
+/// 0 if every row is ignored.
+pub struct DecayedCountFunction {
+    signature: Signature,
+}
+
+impl Debug for DecayedCountFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DecayedCountFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for DecayedCountFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DecayedCountFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+        }
+    }
+}
+
+impl AggregateUDFImpl for DecayedCountFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "decayed_count"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        let [ts_type, half_life_type] = arg_types else {
+            return plan_err!("decayed_count expects 2 arguments, got {}", arg_types.len());
+        };
+        Ok(vec![coerce_ts(self.name(), ts_type)?, half_life_type.clone()])
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Float64)
+    }
+
+    fn is_nullable(&self) -> bool {
+        false
+    }
+
+    fn state_fields(&self, _args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(decayed_state_fields())
+    }
+
+    fn accumulator(&self, acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        DecayedSumAccumulator::try_new(&acc_args, self.name(), true).map(|acc| Box::new(acc) as _)
+    }
+
+    fn default_value(&self, _data_type: &DataType) -> Result<ScalarValue> {
+        Ok(ScalarValue::Float64(Some(0.0)))
+    }
+}
+
+/// The `DecayedAvgFunction` averages values weighted by the decay of their age,
+/// `decayed_avg(value, ts, half_life)`.
+///
+/// - Values weigh as in [`DecayedSumFunction`], so the result is the decayed sum of the values
+///   divided by the decayed count of the rows with a value, and recent values count more.
+/// - The state is the decayed sum and weight, with the most recent `ts` of the partial group
+///   they are decayed to, so partial averages are merged as partial sums are.
+/// - Rows with a NULL value or `ts` are ignored. The result is NULL if every row is ignored.
+pub struct DecayedAvgFunction {
+    signature: Signature,
+}
+
+impl Debug for DecayedAvgFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DecayedAvgFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for DecayedAvgFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DecayedAvgFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+        }
+    }
+}
+
+impl AggregateUDFImpl for DecayedAvgFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "decayed_avg"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        let [value_type, ts_type, half_life_type] = arg_types else {
+            return plan_err!("decayed_avg expects 3 arguments, got {}", arg_types.len());
+        };
+        if !(value_type.is_numeric() || value_type == &DataType::Null) {
+            return plan_err!("decayed_avg expects a numeric value, got {value_type}");
+        }
+        Ok(vec![
+            DataType::Float64,
+            coerce_ts(self.name(), ts_type)?,
+            half_life_type.clone(),
+        ])
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Float64)
+    }
+
+    fn state_fields(&self, _args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(vec![
+            Field::new("sum", DataType::Float64, true),
+            Field::new("weight", DataType::Float64, true),
+            Field::new("time", DataType::Float64, true),
+        ])
+    }
+
+    fn accumulator(&self, acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        let time_scale = seconds_per_unit(&arg_type(&acc_args, 1)?);
+        let half_life = half_life_arg(&acc_args.exprs[2], self.name(), time_scale.is_some())?;
+        Ok(Box::new(DecayedAvgAccumulator::new(
+            time_scale.unwrap_or(1.0),
+            half_life,
+        )))
+    }
+}
+
+/// The sum and the time it is decayed to
+fn decayed_state_fields() -> Vec<Field> {
+    vec![
+        Field::new("sum", DataType::Float64, true),
+        Field::new("time", DataType::Float64, true),
+    ]
+}
+
+/// Accumulator for [`DecayedSumFunction`] and [`DecayedCountFunction`]
+#[derive(Debug)]
+pub struct DecayedSumAccumulator {
+    sum: Decayed,
+    /// Whether every row counts 1, without a value argument
+    count: bool,
+    /// Seconds per unit of the timestamps, 1 for integer timestamps
+    time_scale: f64,
+    half_life: f64,
+}
+
+impl DecayedSumAccumulator {
+    /// Creates an accumulator for timestamps of `time_scale` seconds per unit and a `half_life`
+    /// in seconds, counting the rows if `count`
+    pub fn new(time_scale: f64, half_life: f64, count: bool) -> Self {
+        Self {
+            sum: Decayed::default(),
+            count,
+            time_scale,
+            half_life,
+        }
+    }
+
+    fn try_new(acc_args: &AccumulatorArgs, fn_name: &str, count: bool) -> Result<Self> {
+        let ts_index = if count { 0 } else { 1 };
+        let time_scale = seconds_per_unit(&arg_type(acc_args, ts_index)?);
+        let half_life = half_life_arg(&acc_args.exprs[ts_index + 1], fn_name, time_scale.is_some())?;
+        Ok(Self::new(time_scale.unwrap_or(1.0), half_life, count))
+    }
+}
+
+impl Accumulator for DecayedSumAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        if self.count {
+            let times = times(&values[0], self.time_scale)?;
+            for time in times.iter().flatten() {
+                self.sum.add(1.0, time, self.half_life);
+            }
+            return Ok(());
+        }
+        let times = times(&values[1], self.time_scale)?;
+        for (value, time) in as_float64_array(&values[0])?.iter().zip(times.iter()) {
+            if let (Some(value), Some(time)) = (value, time) {
+                self.sum.add(value, time, self.half_life);
+            }
+        }
+        Ok(())
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        let sums = as_float64_array(&states[0])?;
+        let times = as_float64_array(&states[1])?;
+        for (sum, time) in sums.iter().zip(times.iter()) {
+            if let (Some(sum), Some(time)) = (sum, time) {
+                self.sum.add(sum, time, self.half_life);
+            }
+        }
+        Ok(())
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        if self.sum.is_empty() {
+            return Ok(vec![ScalarValue::Float64(None), ScalarValue::Float64(None)]);
+        }
+        Ok(vec![
+            ScalarValue::Float64(Some(self.sum.value)),
+            ScalarValue::Float64(Some(self.sum.time)),
+        ])
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        Ok(match (self.sum.is_empty(), self.count) {
+            (true, true) => ScalarValue::Float64(Some(0.0)),
+            (true, false) => ScalarValue::Float64(None),
+            (false, _) => ScalarValue::Float64(Some(self.sum.value)),
+        })
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self)
+    }
+}
+
+/// Accumulator for [`DecayedAvgFunction`], decaying the sum of the values and their weight to
+/// the same time
+#[derive(Debug)]
+pub struct DecayedAvgAccumulator {
+    sum: Decayed,
+    weight: Decayed,
+    /// Seconds per unit of the timestamps, 1 for integer timestamps
+    time_scale: f64,
+    half_life: f64,
+}
+
+impl DecayedAvgAccumulator {
+    /// Creates an accumulator for timestamps of `time_scale` seconds per unit and a `half_life`
+    /// in seconds
+    pub fn new(time_scale: f64, half_life: f64) -> Self {
+        Self {
+            sum: Decayed::default(),
+            weight: Decayed::default(),
+            time_scale,
+            half_life,
+        }
+    }
+
+    fn add(&mut self, sum: f64, weight: f64, time: f64) {
+        self.sum.add(sum, time, self.half_life);
+        self.weight.add(weight, time, self.half_life);
+    }
+}
+
+impl Accumulator for DecayedAvgAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        let times = times(&values[1], self.time_scale)?;
+        for (value, time) in as_float64_array(&values[0])?.iter().zip(times.iter()) {
+            if let (Some(value), Some(time)) = (value, time) {
+                self.add(value, 1.0, time);
+            }
+        }
+        Ok(())
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        let sums = as_float64_array(&states[0])?;
+        let weights = as_float64_array(&states[1])?;
+        let times = as_float64_array(&states[2])?;
+        for ((sum, weight), time) in sums.iter().zip(weights.iter()).zip(times.iter()) {
+            if let (Some(sum), Some(weight), Some(time)) = (sum, weight, time) {
+                self.add(sum, weight, time);
+            }
+        }
+        Ok(())
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        if self.sum.is_empty() {
+            return Ok(vec![ScalarValue::Float64(None); 3]);
+        }
+        Ok(vec![
+            ScalarValue::Float64(Some(self.sum.value)),
+            ScalarValue::Float64(Some(self.weight.value)),
+            ScalarValue::Float64(Some(self.sum.time)),
+        ])
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        if self.sum.is_empty() {
+            return Ok(ScalarValue::Float64(None));
+        }
+        Ok(ScalarValue::Float64(Some(self.sum.value / self.weight.value)))
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow::array::{Float64Array, Int64Array};
+
+    use super::*;
+
+    fn state_arrays(acc: &mut dyn Accumulator) -> Result<Vec<ArrayRef>> {
+        acc.state()?.iter().map(|s| s.to_array()).collect()
+    }
+
+    #[test]
+    fn test_decayed_sum_merges_partial_sums() -> Result<()> {
+        let mut first = DecayedSumAccumulator::new(1.0, 10.0, false);
+        first.update_batch(&[
+            Arc::new(Float64Array::from(vec![Some(8.0), Some(2.0), None])),
+            Arc::new(Int64Array::from(vec![Some(0), None, Some(50)])),
+        ])?;
+        let mut second = DecayedSumAccumulator::new(1.0, 10.0, false);
+        second.update_batch(&[
+            Arc::new(Float64Array::from(vec![3.0])),
+            Arc::new(Int64Array::from(vec![20])),
+        ])?;
+        let mut empty = DecayedSumAccumulator::new(1.0, 10.0, false);
+        assert_eq!(empty.evaluate()?, ScalarValue::Float64(None));
+
+        // 8 decayed by 2 half-lives, plus 3
+        let mut merged = DecayedSumAccumulator::new(1.0, 10.0, false);
+        merged.merge_batch(&state_arrays(&mut second)?)?;
+        merged.merge_batch(&state_arrays(&mut empty)?)?;
+        merged.merge_batch(&state_arrays(&mut first)?)?;
+        assert_eq!(merged.evaluate()?, ScalarValue::Float64(Some(5.0)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_decayed_avg_merges_partial_averages() -> Result<()> {
+        let mut first = DecayedAvgAccumulator::new(1.0, 10.0);
+        first.update_batch(&[
+            Arc::new(Float64Array::from(vec![Some(8.0), None])),
+            Arc::new(Int64Array::from(vec![Some(0), Some(10)])),
+        ])?;
+        let mut second = DecayedAvgAccumulator::new(1.0, 10.0);
+        second.update_batch(&[
+            Arc::new(Float64Array::from(vec![2.0])),
+            Arc::new(Int64Array::from(vec![10])),
+        ])?;
+
+        // 8 weighs 1/2 and 2 weighs 1
+        let mut merged = DecayedAvgAccumulator::new(1.0, 10.0);
+        merged.merge_batch(&state_arrays(&mut second)?)?;
+        merged.merge_batch(&state_arrays(&mut first)?)?;
+        assert_eq!(merged.evaluate()?, ScalarValue::Float64(Some(4.0)));
+        assert_eq!(
+            DecayedAvgAccumulator::new(1.0, 10.0).evaluate()?,
+            ScalarValue::Float64(None)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_decayed_count() -> Result<()> {
+        let mut acc = DecayedSumAccumulator::new(1e-3, 1.0, true);
+        assert_eq!(acc.evaluate()?, ScalarValue::Float64(Some(0.0)));
+        acc.update_batch(&[Arc::new(Int64Array::from(vec![
+            Some(3000),
+            None,
+            Some(1000),
+            Some(3000),
+        ]))])?;
+        assert_eq!(acc.evaluate()?, ScalarValue::Float64(Some(2.25)));
+        Ok(())
+    }
+}
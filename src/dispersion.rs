@@ -0,0 +1,306 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::any::Any;
+use std::fmt::Debug;
+
+use arrow::array::ArrayRef;
+use datafusion::arrow;
+use datafusion::arrow::datatypes::{DataType, Field};
+use datafusion::common::cast::as_float64_array;
+use datafusion::common::{internal_err, Result, ScalarValue};
+use datafusion::logical_expr::function::{AccumulatorArgs, StateFieldsArgs};
+use datafusion::logical_expr::{Accumulator, AggregateUDFImpl, Signature, Volatility};
+
+use crate::common::coercion::NumericCoercion;
+use crate::common::moments::{MomentsSketch, SlidingMomentsSketch};
+use crate::common::nulls::all_null_or_filtered;
+
+make_udaf_expr_and_func!(
+    CoefficientOfVariationFunction,
+    cv,
+    x,
+    "Calculates the coefficient of variation, the sample standard deviation divided by the mean.",
+    cv_udaf
+);
+
+make_udaf_expr_and_func!(
+    StandardErrorFunction,
+    stderr,
+    x,
+    "Calculates the standard error of the mean, the sample standard deviation divided by sqrt(n).",
+    stderr_udaf
+);
+
+/// A statistic evaluated from the moments of the values
+type MomentsStat = fn(&MomentsSketch) -> Option<f64>;
+
+/// The `CoefficientOfVariationFunction` calculates `stddev(x) / avg(x)`, using the sample
+/// standard deviation.
+///
+/// - NULL values are ignored.
+/// - The result is NULL for fewer than two values or a mean of zero.
+pub struct CoefficientOfVariationFunction {
+    signature: Signature,
+    aliases: Vec<String>,
+}
+
+impl Debug for CoefficientOfVariationFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CoefficientOfVariationFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for CoefficientOfVariationFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CoefficientOfVariationFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+            aliases: vec!["coefficient_of_variation".to_string()],
+        }
+    }
+}
+
+impl AggregateUDFImpl for CoefficientOfVariationFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "cv"
+    }
+
+    fn aliases(&self) -> &[String] {
+        &self.aliases
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        NumericCoercion::Float64.coerce_all(self.name(), &["x"], arg_types)
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Float64)
+    }
+
+    fn state_fields(&self, _args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(MomentsSketch::state_fields())
+    }
+
+    fn accumulator(&self, _acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        Ok(Box::new(MomentsStatAccumulator::new(
+            MomentsSketch::coefficient_of_variation,
+        )))
+    }
+
+    fn create_sliding_accumulator(&self, _args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        Ok(Box::new(SlidingMomentsStatAccumulator::new(
+            MomentsSketch::coefficient_of_variation,
+        )))
+    }
+}
+
+/// The `StandardErrorFunction` calculates `stddev(x) / sqrt(count(x))`, using the sample
+/// standard deviation.
+///
+/// - NULL values are ignored.
+/// - The result is NULL for fewer than two values.
+pub struct StandardErrorFunction {
+    signature: Signature,
+    aliases: Vec<String>,
+}
+
+impl Debug for StandardErrorFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StandardErrorFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for StandardErrorFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StandardErrorFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+            aliases: vec!["stderr_mean".to_string()],
+        }
+    }
+}
+
+impl AggregateUDFImpl for StandardErrorFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "stderr"
+    }
+
+    fn aliases(&self) -> &[String] {
+        &self.aliases
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        NumericCoercion::Float64.coerce_all(self.name(), &["x"], arg_types)
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Float64)
+    }
+
+    fn state_fields(&self, _args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(MomentsSketch::state_fields())
+    }
+
+    fn accumulator(&self, _acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        Ok(Box::new(MomentsStatAccumulator::new(MomentsSketch::standard_error)))
+    }
+
+    fn create_sliding_accumulator(&self, _args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        Ok(Box::new(SlidingMomentsStatAccumulator::new(
+            MomentsSketch::standard_error,
+        )))
+    }
+}
+
+/// Accumulator evaluating a statistic of the moments of the values.
+///
+/// See [`MomentsSketch`] for how the moments are accumulated.
+#[derive(Debug)]
+pub struct MomentsStatAccumulator {
+    moments: MomentsSketch,
+    stat: MomentsStat,
+}
+
+impl MomentsStatAccumulator {
+    pub fn new(stat: MomentsStat) -> Self {
+        Self {
+            moments: MomentsSketch::new(),
+            stat,
+        }
+    }
+}
+
+impl Accumulator for MomentsStatAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        if all_null_or_filtered(&values[0], None) {
+            return Ok(());
+        }
+        let array = as_float64_array(&values[0])?;
+        for value in array.iter().flatten() {
+            self.moments.update(value);
+        }
+        Ok(())
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        self.moments.merge_state(states)
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        Ok(ScalarValue::Float64((self.stat)(&self.moments)))
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self)
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        Ok(self.moments.state())
+    }
+}
+
+/// Accumulator for [`MomentsStatAccumulator`] over sliding window frames, which supports
+/// retracting values that leave the frame.
+#[derive(Debug)]
+pub struct SlidingMomentsStatAccumulator {
+    moments: SlidingMomentsSketch,
+    stat: MomentsStat,
+}
+
+impl SlidingMomentsStatAccumulator {
+    pub fn new(stat: MomentsStat) -> Self {
+        Self {
+            moments: SlidingMomentsSketch::new(),
+            stat,
+        }
+    }
+}
+
+impl Accumulator for SlidingMomentsStatAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        if all_null_or_filtered(&values[0], None) {
+            return Ok(());
+        }
+        let array = as_float64_array(&values[0])?;
+        for value in array.iter().flatten() {
+            self.moments.update(value);
+        }
+        Ok(())
+    }
+
+    fn retract_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        if all_null_or_filtered(&values[0], None) {
+            return Ok(());
+        }
+        let array = as_float64_array(&values[0])?;
+        for value in array.iter().flatten() {
+            self.moments.retract(value);
+        }
+        Ok(())
+    }
+
+    fn supports_retract_batch(&self) -> bool {
+        true
+    }
+
+    fn merge_batch(&mut self, _states: &[ArrayRef]) -> Result<()> {
+        internal_err!("moment statistics do not merge states over sliding window frames")
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        Ok(ScalarValue::Float64((self.stat)(self.moments.moments())))
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self) + self.moments.size()
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        Ok(self.moments.moments().state())
+    }
+}
@@ -0,0 +1,233 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::any::Any;
+use std::fmt::Debug;
+
+use arrow::array::ArrayRef;
+use datafusion::arrow;
+use datafusion::arrow::datatypes::{DataType, Field};
+use datafusion::common::cast::as_int64_array;
+use datafusion::common::{internal_err, plan_err, Result, ScalarValue};
+use datafusion::logical_expr::function::{AccumulatorArgs, StateFieldsArgs};
+use datafusion::logical_expr::{Accumulator, AggregateUDFImpl, Signature, Volatility};
+
+use crate::common::args::literal_f64_arg;
+use crate::common::emit::CancellationToken;
+use crate::compat::arg_type;
+use crate::mode::create_mode_accumulator;
+
+make_udaf_expr_and_func!(
+    EntropyFunction,
+    entropy,
+    "Calculates the Shannon entropy of the distribution of values.",
+    entropy_udaf
+);
+
+/// The `EntropyFunction` calculates the Shannon entropy `-Σ p log(p)` of the empirical
+/// distribution of the values, where `p` is the fraction of the values equal to each distinct
+/// value.
+///
+/// - NULL values are ignored and the result is NULL if there are no values.
+/// - `entropy(x)` is in nats, `entropy(x, base)` uses the logarithm to the literal `base`, e.g.
+///   `entropy(x, 2)` is in bits.
+/// - The values are counted with the accumulators of [`ModeFunction`](crate::mode::ModeFunction),
+///   so the same input types are supported.
+pub struct EntropyFunction {
+    signature: Signature,
+}
+
+impl Debug for EntropyFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EntropyFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for EntropyFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EntropyFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+        }
+    }
+}
+
+impl AggregateUDFImpl for EntropyFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "entropy"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        let (value_type, base_type) = match arg_types {
+            [value_type] => (value_type, None),
+            [value_type, base_type] => (value_type, Some(base_type)),
+            _ => return plan_err!("entropy expects 1 or 2 arguments, got {}", arg_types.len()),
+        };
+        let value_type = match value_type {
+            DataType::Dictionary(_, value_type) => value_type.as_ref().clone(),
+            _ => value_type.clone(),
+        };
+        match base_type {
+            None => Ok(vec![value_type]),
+            Some(base_type) if base_type.is_numeric() || base_type == &DataType::Null => {
+                Ok(vec![value_type, DataType::Float64])
+            }
+            Some(base_type) => plan_err!("entropy expects a numeric base, got {base_type}"),
+        }
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Float64)
+    }
+
+    fn state_fields(&self, args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(vec![
+            Field::new_list("values", Field::new("item", args.input_types[0].clone(), true), true),
+            Field::new_list("frequencies", Field::new("item", DataType::Int64, true), true),
+        ])
+    }
+
+    fn accumulator(&self, acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        let base = match acc_args.exprs.get(1) {
+            Some(expr) => literal_f64_arg(expr, "entropy", "base")?,
+            None => std::f64::consts::E,
+        };
+        if !(base > 0.0 && base != 1.0 && base.is_finite()) {
+            return plan_err!("entropy expects a positive base other than 1, got {base}");
+        }
+        let counts = create_mode_accumulator(&arg_type(&acc_args, 0)?, &CancellationToken::default())?;
+        Ok(Box::new(EntropyAccumulator::new(counts, base)))
+    }
+}
+
+/// Accumulator counting the distinct values with a mode accumulator, whose frequencies are read
+/// back from its state to evaluate the entropy.
+#[derive(Debug)]
+pub struct EntropyAccumulator {
+    counts: Box<dyn Accumulator>,
+    base: f64,
+}
+
+impl EntropyAccumulator {
+    pub fn new(counts: Box<dyn Accumulator>, base: f64) -> Self {
+        Self { counts, base }
+    }
+}
+
+impl Accumulator for EntropyAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        self.counts.update_batch(&values[..1])
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        self.counts.merge_batch(states)
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        self.counts.state()
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        let state = self.counts.state()?;
+        let Some(ScalarValue::List(frequencies)) = state.get(1) else {
+            return internal_err!("entropy expects the frequencies of the values in the mode state");
+        };
+        let frequencies = frequencies.value(0);
+        // The mode accumulators for strings count NULL with a negative sentinel frequency
+        let frequencies: Vec<i64> = as_int64_array(&frequencies)?
+            .iter()
+            .flatten()
+            .filter(|&count| count > 0)
+            .collect();
+        let total: i64 = frequencies.iter().sum();
+        if total == 0 {
+            return Ok(ScalarValue::Float64(None));
+        }
+        let nats: f64 = frequencies
+            .iter()
+            .map(|&count| {
+                let p = count as f64 / total as f64;
+                -p * p.ln()
+            })
+            .sum();
+        // A single distinct value gives -0.0
+        Ok(ScalarValue::Float64(Some(nats / self.base.ln() + 0.0)))
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self) + self.counts.size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{Int32Array, StringArray};
+    use std::sync::Arc;
+
+    fn evaluate(values: ArrayRef, base: f64) -> Result<ScalarValue> {
+        let mut acc = EntropyAccumulator::new(
+            create_mode_accumulator(values.data_type(), &CancellationToken::default())?,
+            base,
+        );
+        acc.update_batch(&[values])?;
+        acc.evaluate()
+    }
+
+    #[test]
+    fn test_entropy_bits() -> Result<()> {
+        // A fair coin carries one bit, two fair coins carry two
+        let coin = Arc::new(StringArray::from(vec![
+            Some("h"),
+            Some("t"),
+            None,
+            Some("t"),
+            Some("h"),
+        ]));
+        assert_eq!(evaluate(coin, 2.0)?, ScalarValue::Float64(Some(1.0)));
+        let coins = Arc::new(Int32Array::from(vec![0, 1, 2, 3, 3, 2, 1, 0]));
+        assert_eq!(evaluate(coins, 2.0)?, ScalarValue::Float64(Some(2.0)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_entropy_degenerate() -> Result<()> {
+        let constant = Arc::new(Int32Array::from(vec![7, 7, 7]));
+        assert_eq!(
+            evaluate(constant, std::f64::consts::E)?,
+            ScalarValue::Float64(Some(0.0))
+        );
+        let empty = Arc::new(Int32Array::from(vec![None, None]));
+        assert_eq!(evaluate(empty, std::f64::consts::E)?, ScalarValue::Float64(None));
+        Ok(())
+    }
+}
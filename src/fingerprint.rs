@@ -0,0 +1,369 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::any::Any;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use arrow::array::{Array, ArrayRef, FixedSizeBinaryArray};
+use datafusion::arrow;
+use datafusion::arrow::datatypes::{DataType, Field};
+use datafusion::common::cast::as_fixed_size_binary_array;
+use datafusion::common::{exec_err, plan_err, Result, ScalarValue};
+use datafusion::logical_expr::function::{AccumulatorArgs, StateFieldsArgs};
+use datafusion::logical_expr::{Accumulator, AggregateUDFImpl, ColumnarValue, ScalarUDFImpl, Signature, Volatility};
+
+use crate::common::args::scalar_args;
+use crate::common::hash::{spark_hash_int, spark_murmur3_hash};
+
+make_udaf_expr_and_func!(
+    ColumnFingerprintFunction,
+    column_fingerprint,
+    x,
+    "Calculates an order-independent 128-bit fingerprint of the values.",
+    column_fingerprint_udaf
+);
+
+make_udf_expr_and_func!(
+    FingerprintCombineFunction,
+    fingerprint_combine,
+    a b,
+    "Combines two fingerprints into the fingerprint of the union of their values.",
+    fingerprint_combine_udf
+);
+
+/// Size in bytes of a fingerprint
+pub const FINGERPRINT_SIZE: i32 = 16;
+
+/// Seeds of the four 32-bit hashes of a value making up its 128-bit hash. These are part of the
+/// fingerprint format and must never change.
+const FINGERPRINT_SEEDS: [i32; 4] = [0x5EED_0001, 0x5EED_0002, 0x5EED_0003, 0x5EED_0004];
+
+pub(crate) fn fingerprint_type() -> DataType {
+    DataType::FixedSizeBinary(FINGERPRINT_SIZE)
+}
+
+/// The hash of each row of `columns` made of the first `words` 32-bit hashes, with NULL hashed to
+/// a constant.
+///
+/// Columns are chained as in [`spark_murmur3_hash`], after mixing in the position of every column
+/// but the first so that NULLs in different columns don't hash alike. A single column hashes as
+/// `column_fingerprint` hashes it.
+pub(crate) fn hash_rows(columns: &[ArrayRef], words: usize) -> Result<Vec<u128>> {
+    let num_rows = columns.first().map_or(0, |column| column.len());
+    let mut hashes = vec![0u128; num_rows];
+    for (word, seed) in FINGERPRINT_SEEDS.iter().take(words).enumerate() {
+        let mut word_hashes = vec![*seed; num_rows];
+        for (position, column) in columns.iter().enumerate() {
+            if position > 0 {
+                for hash in word_hashes.iter_mut() {
+                    *hash = spark_hash_int(position as i32, *hash);
+                }
+            }
+            spark_murmur3_hash(std::slice::from_ref(column), &mut word_hashes)?;
+        }
+        for (hash, word_hash) in hashes.iter_mut().zip(word_hashes) {
+            *hash |= (word_hash as u32 as u128) << (32 * word);
+        }
+    }
+    Ok(hashes)
+}
+
+pub(crate) fn fingerprint_scalar(fingerprint: u128) -> ScalarValue {
+    ScalarValue::FixedSizeBinary(FINGERPRINT_SIZE, Some(fingerprint.to_be_bytes().to_vec()))
+}
+
+pub(crate) fn fingerprint_value(bytes: &[u8]) -> Result<u128> {
+    match <[u8; 16]>::try_from(bytes) {
+        Ok(bytes) => Ok(u128::from_be_bytes(bytes)),
+        Err(_) => exec_err!("Invalid fingerprint of {} bytes", bytes.len()),
+    }
+}
+
+/// Fingerprints dictionaries by their values, so re-encoding a column keeps its fingerprint
+pub(crate) fn fingerprint_value_type(value_type: &DataType) -> DataType {
+    match value_type {
+        DataType::Dictionary(_, value_type) => value_type.as_ref().clone(),
+        _ => value_type.clone(),
+    }
+}
+
+/// The type sketches looked up by value hash their values as, so that a value is found whatever
+/// the width of the integer, float or string type it was added as
+pub(crate) fn lookup_value_type(value_type: &DataType) -> DataType {
+    match value_type {
+        DataType::Dictionary(_, value_type) => lookup_value_type(value_type),
+        DataType::Int8
+        | DataType::Int16
+        | DataType::Int32
+        | DataType::Int64
+        | DataType::UInt8
+        | DataType::UInt16
+        | DataType::UInt32 => DataType::Int64,
+        DataType::Float16 | DataType::Float32 | DataType::Float64 => DataType::Float64,
+        DataType::Utf8 | DataType::LargeUtf8 | DataType::Utf8View => DataType::Utf8,
+        DataType::LargeBinary | DataType::BinaryView => DataType::Binary,
+        _ => value_type.clone(),
+    }
+}
+
+/// The 64-bit hashes of `values`, which must be of a [`lookup_value_type`]
+pub(crate) fn hash_lookup_values(values: &ArrayRef) -> Result<Vec<u64>> {
+    Ok(hash_rows(std::slice::from_ref(values), 2)?
+        .into_iter()
+        .map(|hash| hash as u64)
+        .collect())
+}
+
+/// The fingerprint of the union of the rows fingerprinted as `a` and `b`
+fn combine(a: &[u8], b: &[u8]) -> Result<u128> {
+    Ok(fingerprint_value(a)?.wrapping_add(fingerprint_value(b)?))
+}
+
+/// The `ColumnFingerprintFunction` calculates a 128-bit fingerprint of the multiset of values,
+/// to check whether two snapshots of a column hold the same values without comparing them.
+///
+/// - The fingerprint is the sum modulo 2^128 of a 128-bit hash of every value, so it doesn't
+///   depend on the order of the rows or how they are partitioned, and fingerprints of disjoint
+///   parts of a table add up with `fingerprint_combine`.
+/// - Values are hashed with the Spark-compatible Murmur3 hash in [`crate::common::hash`] under
+///   fixed seeds, so the fingerprint doesn't change between releases. Values of different types,
+///   such as `INT` 1 and `BIGINT` 1, have different hashes.
+/// - NULL values are hashed to a constant, so they change the fingerprint. The fingerprint of no
+///   rows is 0.
+/// - It is not a cryptographic hash: it detects accidental differences, not deliberate ones.
+pub struct ColumnFingerprintFunction {
+    signature: Signature,
+}
+
+impl Debug for ColumnFingerprintFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ColumnFingerprintFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for ColumnFingerprintFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ColumnFingerprintFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+        }
+    }
+}
+
+impl AggregateUDFImpl for ColumnFingerprintFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "column_fingerprint"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        let [value_type] = arg_types else {
+            return plan_err!("column_fingerprint expects 1 argument, got {}", arg_types.len());
+        };
+        Ok(vec![fingerprint_value_type(value_type)])
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(fingerprint_type())
+    }
+
+    fn state_fields(&self, _args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(vec![Field::new("fingerprint", fingerprint_type(), true)])
+    }
+
+    fn accumulator(&self, _acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        Ok(Box::new(ColumnFingerprintAccumulator::new()))
+    }
+}
+
+/// Accumulator for [`ColumnFingerprintFunction`], summing the hashes of the values.
+#[derive(Debug, Default)]
+pub struct ColumnFingerprintAccumulator {
+    fingerprint: u128,
+}
+
+impl ColumnFingerprintAccumulator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Accumulator for ColumnFingerprintAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        for hash in hash_rows(&values[..1], FINGERPRINT_SEEDS.len())? {
+            self.fingerprint = self.fingerprint.wrapping_add(hash);
+        }
+        Ok(())
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        for fingerprint in as_fixed_size_binary_array(&states[0])?.iter().flatten() {
+            self.fingerprint = self.fingerprint.wrapping_add(fingerprint_value(fingerprint)?);
+        }
+        Ok(())
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        Ok(vec![fingerprint_scalar(self.fingerprint)])
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        Ok(fingerprint_scalar(self.fingerprint))
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self)
+    }
+}
+
+/// The `FingerprintCombineFunction` adds two fingerprints of [`ColumnFingerprintFunction`], giving
+/// the fingerprint of the union of their rows, e.g. to fingerprint a table from the fingerprints
+/// of its partitions.
+///
+/// - The result is NULL if either fingerprint is NULL.
+pub struct FingerprintCombineFunction {
+    signature: Signature,
+}
+
+impl Debug for FingerprintCombineFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FingerprintCombineFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for FingerprintCombineFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FingerprintCombineFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::exact(vec![fingerprint_type(), fingerprint_type()], Volatility::Immutable),
+        }
+    }
+}
+
+impl ScalarUDFImpl for FingerprintCombineFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "fingerprint_combine"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(fingerprint_type())
+    }
+
+    fn invoke(&self, args: &[ColumnarValue]) -> Result<ColumnarValue> {
+        if let Some([a, b]) = scalar_args(args) {
+            let (ScalarValue::FixedSizeBinary(_, a), ScalarValue::FixedSizeBinary(_, b)) = (a, b) else {
+                return exec_err!("fingerprint_combine expects fingerprints, got {a:?} and {b:?}");
+            };
+            return match (a, b) {
+                (Some(a), Some(b)) => Ok(ColumnarValue::Scalar(fingerprint_scalar(combine(a, b)?))),
+                _ => Ok(ColumnarValue::Scalar(ScalarValue::FixedSizeBinary(
+                    FINGERPRINT_SIZE,
+                    None,
+                ))),
+            };
+        }
+
+        let arrays = ColumnarValue::values_to_arrays(args)?;
+        let (a, b) = (
+            as_fixed_size_binary_array(&arrays[0])?,
+            as_fixed_size_binary_array(&arrays[1])?,
+        );
+
+        let combined = a
+            .iter()
+            .zip(b.iter())
+            .map(|(a, b)| match (a, b) {
+                (Some(a), Some(b)) => Ok(Some(combine(a, b)?.to_be_bytes())),
+                _ => Ok(None),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let combined = FixedSizeBinaryArray::try_from_sparse_iter_with_size(combined.into_iter(), FINGERPRINT_SIZE)?;
+        Ok(ColumnarValue::Array(Arc::new(combined)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::StringArray;
+
+    fn fingerprint(batches: &[Vec<Option<&str>>]) -> Result<ScalarValue> {
+        let mut acc = ColumnFingerprintAccumulator::new();
+        for batch in batches {
+            let mut partial = ColumnFingerprintAccumulator::new();
+            partial.update_batch(&[Arc::new(StringArray::from(batch.clone()))])?;
+            acc.merge_batch(&[partial.state()?[0].to_array()?])?;
+        }
+        acc.evaluate()
+    }
+
+    #[test]
+    fn test_fingerprint_is_order_and_partition_independent() -> Result<()> {
+        let expected = fingerprint(&[vec![Some("a"), None, Some("b"), Some("a")]])?;
+        assert_eq!(
+            fingerprint(&[vec![Some("b"), Some("a")], vec![Some("a"), None]])?,
+            expected
+        );
+        assert_ne!(fingerprint(&[vec![Some("a"), Some("b"), Some("a")]])?, expected);
+        assert_ne!(fingerprint(&[vec![Some("a"), None, Some("b"), Some("b")]])?, expected);
+        assert_eq!(fingerprint(&[])?, fingerprint_scalar(0));
+        Ok(())
+    }
+
+    #[test]
+    fn test_fingerprint_is_stable() -> Result<()> {
+        // Changing this value breaks fingerprints stored by users
+        let ScalarValue::FixedSizeBinary(_, Some(bytes)) = fingerprint(&[vec![Some("a")]])? else {
+            panic!("expected a fingerprint");
+        };
+        assert_eq!(
+            u128::from_be_bytes(bytes.try_into().unwrap()),
+            0x0d42_4adc_533f_711f_6bc4_0c29_f627_f946
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,310 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::any::Any;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use datafusion::arrow::array::Int32Array;
+use datafusion::arrow::datatypes::DataType;
+use datafusion::common::cast::{as_date32_array, as_int64_array};
+use datafusion::common::{exec_err, plan_err, Result, ScalarValue};
+use datafusion::logical_expr::{ColumnarValue, ScalarUDFImpl, Signature, Volatility};
+
+use crate::common::args::scalar_args;
+use crate::common::calendar::civil_from_days;
+
+make_udf_expr_and_func!(
+    FiscalYearFunction,
+    fiscal_year,
+    date start_month,
+    "Returns the fiscal year of a date, for fiscal years starting on the first day of start_month.",
+    fiscal_year_udf
+);
+
+make_udf_expr_and_func!(
+    FiscalQuarterFunction,
+    fiscal_quarter,
+    date start_month,
+    "Returns the fiscal quarter of a date, for fiscal years starting on the first day of start_month.",
+    fiscal_quarter_udf
+);
+
+/// The field of a date in a fiscal year, the result of [`FiscalYearFunction`] or
+/// [`FiscalQuarterFunction`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FiscalField {
+    /// The calendar year the fiscal year ends in
+    Year,
+    /// The quarter of the fiscal year, from 1 to 4
+    Quarter,
+}
+
+impl FiscalField {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Year => "fiscal_year",
+            Self::Quarter => "fiscal_quarter",
+        }
+    }
+
+    /// Coerces the date to `Date32` and the start month to `Int64`
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        let (date_type, start_month_type) = match arg_types {
+            [date_type] => (date_type, None),
+            [date_type, start_month_type] => (date_type, Some(start_month_type)),
+            _ => return plan_err!("{} expects a date and an optional start month", self.name()),
+        };
+        if !matches!(
+            date_type,
+            DataType::Date32 | DataType::Date64 | DataType::Timestamp(_, _) | DataType::Null
+        ) {
+            return plan_err!("{} expects a date, got {date_type}", self.name());
+        }
+        match start_month_type {
+            None => Ok(vec![DataType::Date32]),
+            Some(start_month_type) if start_month_type.is_integer() || start_month_type == &DataType::Null => {
+                Ok(vec![DataType::Date32, DataType::Int64])
+            }
+            Some(start_month_type) => {
+                plan_err!("{} expects an integer start month, got {start_month_type}", self.name())
+            }
+        }
+    }
+
+    /// The field of `day` in fiscal years starting in `start_month`
+    fn value(&self, day: i32, start_month: i64) -> Result<i32> {
+        if !(1..=12).contains(&start_month) {
+            return exec_err!("{} expects a start month from 1 to 12, got {start_month}", self.name());
+        }
+        let (year, month, _) = civil_from_days(day);
+        // Months from the start of the fiscal year
+        let months = (month as i64 - start_month).rem_euclid(12) as i32;
+        Ok(match self {
+            // January fiscal years are calendar years, and later ones end in the next year
+            Self::Year if month as i64 >= start_month && start_month > 1 => year + 1,
+            Self::Year => year,
+            Self::Quarter => months / 3 + 1,
+        })
+    }
+
+    fn invoke(&self, args: &[ColumnarValue], default_start_month: u32) -> Result<ColumnarValue> {
+        let default_start_month = ScalarValue::Int64(Some(default_start_month as i64));
+        let start_month = args
+            .get(1)
+            .cloned()
+            .unwrap_or(ColumnarValue::Scalar(default_start_month));
+        let args = [args[0].clone(), start_month];
+        if let Some([date, start_month]) = scalar_args(&args) {
+            let value = match (date, start_month) {
+                (ScalarValue::Date32(Some(day)), ScalarValue::Int64(Some(start_month))) => {
+                    Some(self.value(*day, *start_month)?)
+                }
+                _ => None,
+            };
+            return Ok(ColumnarValue::Scalar(ScalarValue::Int32(value)));
+        }
+        let arrays = ColumnarValue::values_to_arrays(&args)?;
+        let (days, start_months) = (as_date32_array(&arrays[0])?, as_int64_array(&arrays[1])?);
+        let values = days
+            .iter()
+            .zip(start_months.iter())
+            .map(|row| match row {
+                (Some(day), Some(start_month)) => self.value(day, start_month).map(Some),
+                _ => Ok(None),
+            })
+            .collect::<Result<Int32Array>>()?;
+        Ok(ColumnarValue::Array(Arc::new(values)))
+    }
+}
+
+/// The `FiscalYearFunction` returns the fiscal year of a date, `fiscal_year(date[, start_month])`.
+///
+/// - Fiscal years start on the first day of `start_month`, from 1 to 12, and are named after
+///   the calendar year they end in, e.g. the fiscal year starting in October 2024 is 2025 as
+///   for the US federal government. Fiscal years starting in January are calendar years.
+/// - Without `start_month`, fiscal years start in the month the function was created with, see
+///   [`Self::new_with_start_month`], January by default.
+/// - Timestamps are truncated to their date. The result is NULL if the date or the start month
+///   is NULL.
+pub struct FiscalYearFunction {
+    signature: Signature,
+    start_month: u32,
+}
+
+impl Debug for FiscalYearFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FiscalYearFunction")
+            .field("signature", &self.signature)
+            .field("start_month", &self.start_month)
+            .finish()
+    }
+}
+
+impl Default for FiscalYearFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FiscalYearFunction {
+    pub fn new() -> Self {
+        Self::new_with_start_month(1)
+    }
+
+    /// Creates the function for fiscal years starting in `start_month` by default, from 1 to 12
+    pub fn new_with_start_month(start_month: u32) -> Self {
+        assert!((1..=12).contains(&start_month), "Invalid start month {start_month}");
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+            start_month,
+        }
+    }
+
+    /// The month fiscal years start in without a `start_month` argument
+    pub fn start_month(&self) -> u32 {
+        self.start_month
+    }
+}
+
+impl ScalarUDFImpl for FiscalYearFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        FiscalField::Year.name()
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        FiscalField::Year.coerce_types(arg_types)
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Int32)
+    }
+
+    fn invoke(&self, args: &[ColumnarValue]) -> Result<ColumnarValue> {
+        FiscalField::Year.invoke(args, self.start_month)
+    }
+}
+
+/// The `FiscalQuarterFunction` returns the fiscal quarter of a date, from 1 to 4,
+/// `fiscal_quarter(date[, start_month])`.
+///
+/// - The first quarter starts on the first day of the fiscal year, see [`FiscalYearFunction`]
+///   for the start month.
+/// - Timestamps are truncated to their date. The result is NULL if the date or the start month
+///   is NULL.
+pub struct FiscalQuarterFunction {
+    signature: Signature,
+    start_month: u32,
+}
+
+impl Debug for FiscalQuarterFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FiscalQuarterFunction")
+            .field("signature", &self.signature)
+            .field("start_month", &self.start_month)
+            .finish()
+    }
+}
+
+impl Default for FiscalQuarterFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FiscalQuarterFunction {
+    pub fn new() -> Self {
+        Self::new_with_start_month(1)
+    }
+
+    /// Creates the function for fiscal years starting in `start_month` by default, from 1 to 12
+    pub fn new_with_start_month(start_month: u32) -> Self {
+        assert!((1..=12).contains(&start_month), "Invalid start month {start_month}");
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+            start_month,
+        }
+    }
+
+    /// The month fiscal years start in without a `start_month` argument
+    pub fn start_month(&self) -> u32 {
+        self.start_month
+    }
+}
+
+impl ScalarUDFImpl for FiscalQuarterFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        FiscalField::Quarter.name()
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        FiscalField::Quarter.coerce_types(arg_types)
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Int32)
+    }
+
+    fn invoke(&self, args: &[ColumnarValue]) -> Result<ColumnarValue> {
+        FiscalField::Quarter.invoke(args, self.start_month)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::calendar::days_from_civil;
+
+    #[test]
+    fn test_fiscal_fields_around_year_boundaries() -> Result<()> {
+        let cases = [
+            // US federal fiscal years start in October
+            ((2024, 9, 30), 10, 2024, 4),
+            ((2024, 10, 1), 10, 2025, 1),
+            ((2024, 12, 31), 10, 2025, 1),
+            ((2025, 1, 1), 10, 2025, 2),
+            // UK tax years start in April
+            ((2025, 3, 31), 4, 2025, 4),
+            ((2025, 4, 1), 4, 2026, 1),
+            ((2024, 2, 29), 1, 2024, 1),
+            ((2024, 12, 31), 1, 2024, 4),
+        ];
+        for ((year, month, day), start_month, fiscal_year, fiscal_quarter) in cases {
+            let day = days_from_civil(year, month, day);
+            assert_eq!(FiscalField::Year.value(day, start_month)?, fiscal_year);
+            assert_eq!(FiscalField::Quarter.value(day, start_month)?, fiscal_quarter);
+        }
+        assert!(FiscalField::Year.value(0, 13).is_err());
+        Ok(())
+    }
+}
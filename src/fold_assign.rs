@@ -0,0 +1,147 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::any::Any;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use arrow::array::{Array, Int32Array};
+use datafusion::arrow;
+use datafusion::arrow::datatypes::DataType;
+use datafusion::common::cast::as_int64_array;
+use datafusion::common::{exec_err, plan_err, ScalarValue};
+use datafusion::error::Result;
+use datafusion::logical_expr::{ColumnarValue, ScalarUDFImpl, Signature, Volatility};
+
+use crate::common::args::scalar_args;
+use crate::common::hash::{spark_hash_long, spark_murmur3_hash, spark_murmur3_hash_scalar, SPARK_MURMUR3_SEED};
+
+make_udf_expr_and_func!(
+    FoldAssignFunction,
+    fold_assign,
+    key k seed,
+    "Assigns each row to one of `k` cross-validation folds by hashing its key.",
+    fold_assign_udf
+);
+
+/// The `FoldAssignFunction` deterministically assigns a row to a fold in `0..k` from its key.
+///
+/// - The fold is computed with the Spark-compatible hash in [`crate::common::hash`], so
+///   `fold_assign(key, k, seed)` returns the same value as Spark's
+///   `pmod(hash(CAST(seed AS BIGINT), key), k)`.
+/// - A NULL key is hashed like Spark does, all NULL keys land in the same fold.
+/// - If `k` or `seed` is NULL the result is NULL. `k` must be between 1 and `i32::MAX`.
+pub struct FoldAssignFunction {
+    signature: Signature,
+}
+
+impl Debug for FoldAssignFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FoldAssignFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for FoldAssignFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FoldAssignFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+        }
+    }
+}
+
+/// The fold in `0..k` of a row whose key and seed hash to `hash`
+fn fold(hash: i32, k: i64) -> Result<i32> {
+    if k < 1 || k > i32::MAX as i64 {
+        return exec_err!("fold_assign expects k between 1 and {}, got {}", i32::MAX, k);
+    }
+    Ok((hash as i64).rem_euclid(k) as i32)
+}
+
+impl ScalarUDFImpl for FoldAssignFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "fold_assign"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Int32)
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        let [key, k, seed] = arg_types else {
+            return plan_err!(
+                "fold_assign expects 3 arguments (key, k, seed), got {}",
+                arg_types.len()
+            );
+        };
+        for arg in [k, seed] {
+            if !arg.is_integer() && !arg.is_null() {
+                return plan_err!("fold_assign expects integer k and seed, got {:?}", arg);
+            }
+        }
+        Ok(vec![key.clone(), DataType::Int64, DataType::Int64])
+    }
+
+    fn invoke(&self, args: &[ColumnarValue]) -> Result<ColumnarValue> {
+        if let Some([key, k, seed]) = scalar_args(args) {
+            let (ScalarValue::Int64(k), ScalarValue::Int64(seed)) = (k, seed) else {
+                return exec_err!("fold_assign expects Int64 k and seed, got {k:?} and {seed:?}");
+            };
+            let fold = match (k, seed) {
+                (Some(k), Some(seed)) => {
+                    let hash = spark_murmur3_hash_scalar(key, spark_hash_long(*seed, SPARK_MURMUR3_SEED))?;
+                    Some(fold(hash, *k)?)
+                }
+                _ => None,
+            };
+            return Ok(ColumnarValue::Scalar(ScalarValue::Int32(fold)));
+        }
+
+        let arrays = ColumnarValue::values_to_arrays(args)?;
+        let (keys, ks, seeds) = (&arrays[0], as_int64_array(&arrays[1])?, as_int64_array(&arrays[2])?);
+
+        let mut hashes = vec![SPARK_MURMUR3_SEED; keys.len()];
+        spark_murmur3_hash(&[Arc::clone(&arrays[2]), Arc::clone(keys)], &mut hashes)?;
+
+        let folds = hashes
+            .iter()
+            .enumerate()
+            .map(|(i, &hash)| {
+                if ks.is_null(i) || seeds.is_null(i) {
+                    return Ok(None);
+                }
+                fold(hash, ks.value(i)).map(Some)
+            })
+            .collect::<Result<Int32Array>>()?;
+        Ok(ColumnarValue::Array(Arc::new(folds)))
+    }
+}
@@ -0,0 +1,370 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+use std::any::Any;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use arrow::array::{Array, ArrayRef, AsArray, Int64Array, MapArray, StructArray};
+use arrow::buffer::{NullBuffer, OffsetBuffer};
+use arrow::compute::{filter, is_not_null};
+use arrow::datatypes::{DataType, Field, Fields};
+use datafusion::arrow;
+use datafusion::common::cast::{as_int64_array, as_list_array};
+use datafusion::common::{plan_err, Result, ScalarValue};
+use datafusion::logical_expr::function::{AccumulatorArgs, StateFieldsArgs};
+use datafusion::logical_expr::{Accumulator, AggregateUDFImpl, Signature, Volatility};
+
+use crate::common::collections::ArrowBytesViewMap;
+use crate::common::emit::CancellationToken;
+use crate::common::nulls::all_null_or_filtered;
+use crate::compat::{arg_type, single_row_list, OutputType};
+use crate::value_counts::{SortBy, ValueCountsAccumulator};
+
+make_udaf_expr_and_func!(
+    FreqMapFunction,
+    freq_map,
+    x,
+    "Counts the occurrences of every value, returned as a map of value to count.",
+    freq_map_udaf
+);
+
+fn map_entries_field(key_type: &DataType) -> Arc<Field> {
+    let entries = Fields::from(vec![
+        Field::new("key", key_type.clone(), false),
+        Field::new("value", DataType::Int64, false),
+    ]);
+    Arc::new(Field::new("entries", DataType::Struct(entries), false))
+}
+
+/// The single-row map of `keys` to `counts`, NULL if there are no keys
+fn frequency_map(keys: ArrayRef, counts: ArrayRef) -> Result<ScalarValue> {
+    let entries_field = map_entries_field(keys.data_type());
+    let DataType::Struct(entries_fields) = entries_field.data_type() else {
+        unreachable!("map entries are a struct");
+    };
+    let len = keys.len();
+    let entries = StructArray::try_new(entries_fields.clone(), vec![keys, counts], None)?;
+    // no rows gives a NULL map, as other aggregates return NULL for empty input
+    let nulls = (len == 0).then(|| NullBuffer::new_null(1));
+    let map = MapArray::try_new(entries_field, OffsetBuffer::from_lengths([len]), entries, nulls, false)?;
+    Ok(ScalarValue::Map(Arc::new(map)))
+}
+
+/// The `FreqMapFunction` counts the occurrences of every non-null value, returning a
+/// `Map<value, Int64>`.
+///
+/// - The keys are in the order they were first aggregated in, which depends on the
+///   partitioning of the input.
+/// - String values are coerced to `Utf8View` and counted in an [`ArrowBytesViewMap`], whose
+///   distinct values become the keys of the map without being copied. Other values are counted
+///   as by `value_counts`, and `Dictionary` inputs return their value type.
+/// - `freq_map(x)['b']` doesn't coerce its key to `Utf8View`, so strings are looked up with
+///   `map_extract(freq_map(x), 'b')` instead.
+/// - The result is NULL if there are no values.
+pub struct FreqMapFunction {
+    signature: Signature,
+    cancellation: CancellationToken,
+}
+
+impl Debug for FreqMapFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FreqMapFunction")
+            .field("signature", &self.signature)
+            .field("cancellation", &self.cancellation)
+            .finish()
+    }
+}
+
+impl Default for FreqMapFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FreqMapFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+            cancellation: CancellationToken::default(),
+        }
+    }
+
+    /// Fails the emission of the accumulators once `cancellation` is cancelled, see
+    /// [`crate::common::emit`]
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = cancellation;
+        self
+    }
+}
+
+impl AggregateUDFImpl for FreqMapFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "freq_map"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        let [arg_type] = arg_types else {
+            return plan_err!("freq_map expects a single argument, got {}", arg_types.len());
+        };
+        let value_type = match arg_type {
+            DataType::Dictionary(_, value_type) => value_type.as_ref(),
+            arg_type => arg_type,
+        };
+        match value_type {
+            DataType::Utf8 | DataType::LargeUtf8 | DataType::Utf8View => Ok(vec![DataType::Utf8View]),
+            DataType::Null => Ok(vec![DataType::Int64]),
+            DataType::List(_) | DataType::LargeList(_) | DataType::Struct(_) | DataType::Map(_, _) => {
+                plan_err!("freq_map expects a primitive or string value, got {value_type}")
+            }
+            value_type => Ok(vec![value_type.clone()]),
+        }
+    }
+
+    fn return_type(&self, arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Map(map_entries_field(&arg_types[0]), false))
+    }
+
+    fn state_fields(&self, args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(vec![
+            Field::new_list("values", Field::new("item", args.input_types[0].clone(), true), true),
+            Field::new_list("counts", Field::new("item", DataType::Int64, true), true),
+        ])
+    }
+
+    fn accumulator(&self, acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        let value_type = arg_type(&acc_args, 0)?;
+        if value_type == DataType::Utf8View {
+            return Ok(Box::new(
+                BytesViewFreqMapAccumulator::new().with_cancellation(self.cancellation.clone()),
+            ));
+        }
+        Ok(Box::new(FreqMapAccumulator {
+            counts: ValueCountsAccumulator::new(value_type, SortBy::Insertion)
+                .with_cancellation(self.cancellation.clone()),
+        }))
+    }
+}
+
+/// Accumulator of [`FreqMapFunction`] over `Utf8View` values.
+///
+/// The payload of every value is the index of its count, which is also its index in the
+/// values emitted by the map as they are both in the order the values were first seen.
+#[derive(Debug)]
+pub struct BytesViewFreqMapAccumulator {
+    values: ArrowBytesViewMap<usize>,
+    counts: Vec<i64>,
+    /// Count index of every row of the batch being inserted, reused between batches
+    indices: Vec<usize>,
+    cancellation: CancellationToken,
+}
+
+impl Default for BytesViewFreqMapAccumulator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BytesViewFreqMapAccumulator {
+    pub fn new() -> Self {
+        Self {
+            values: ArrowBytesViewMap::new(OutputType::Utf8View),
+            counts: vec![],
+            indices: vec![],
+            cancellation: CancellationToken::default(),
+        }
+    }
+
+    /// Fails the emission of the state and result once `cancellation` is cancelled
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = cancellation;
+        self
+    }
+
+    /// Adds `counts` to the values, or 1 per row without counts
+    fn add(&mut self, values: &ArrayRef, counts: Option<&Int64Array>) {
+        let mut next = self.counts.len();
+        let indices = &mut self.indices;
+        indices.clear();
+        self.values.insert_if_new(
+            values,
+            |_| {
+                next += 1;
+                next - 1
+            },
+            |index| indices.push(index),
+        );
+        self.counts.resize(next, 0);
+        for (row, &index) in self.indices.iter().enumerate() {
+            self.counts[index] += counts.map_or(1, |counts| counts.value(row));
+        }
+    }
+
+    /// The distinct non-null values and their counts, emptying the map
+    fn take_entries(&mut self) -> Result<(ArrayRef, ArrayRef)> {
+        self.cancellation.check()?;
+        let values = self.values.take().into_state();
+        let counts: ArrayRef = Arc::new(Int64Array::from(std::mem::take(&mut self.counts)));
+        if values.null_count() == 0 {
+            return Ok((values, counts));
+        }
+        let mask = is_not_null(&values)?;
+        Ok((filter(&values, &mask)?, filter(&counts, &mask)?))
+    }
+}
+
+impl Accumulator for BytesViewFreqMapAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        if all_null_or_filtered(&values[0], None) {
+            return Ok(());
+        }
+        self.add(&values[0], None);
+        Ok(())
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        let values_lists = as_list_array(&states[0])?;
+        let counts_lists = as_list_array(&states[1])?;
+        for (values, counts) in values_lists.iter().zip(counts_lists.iter()) {
+            let (Some(values), Some(counts)) = (values, counts) else {
+                continue;
+            };
+            self.add(&values, Some(as_int64_array(&counts)?));
+        }
+        Ok(())
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        let (values, counts) = self.take_entries()?;
+        Ok(vec![
+            ScalarValue::List(Arc::new(single_row_list(values))),
+            ScalarValue::List(Arc::new(single_row_list(counts))),
+        ])
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        let (values, counts) = self.take_entries()?;
+        frequency_map(values, counts)
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self)
+            + self.values.size()
+            + self.counts.capacity() * std::mem::size_of::<i64>()
+            + self.indices.capacity() * std::mem::size_of::<usize>()
+    }
+}
+
+/// Accumulator of [`FreqMapFunction`] over values other than strings, counting them as
+/// [`ValueCountsAccumulator`] does
+#[derive(Debug)]
+pub struct FreqMapAccumulator {
+    counts: ValueCountsAccumulator,
+}
+
+impl Accumulator for FreqMapAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        self.counts.update_batch(values)
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        self.counts.merge_batch(states)
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        self.counts.state()
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        let ScalarValue::List(entries) = self.counts.evaluate()? else {
+            unreachable!("value_counts returns a list");
+        };
+        let entries = entries.value(0);
+        let entries = entries.as_struct();
+        frequency_map(Arc::clone(entries.column(0)), Arc::clone(entries.column(1)))
+    }
+
+    fn size(&self) -> usize {
+        self.counts.size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::array::{StringViewArray, UInt8Array};
+    use arrow::datatypes::Int64Type;
+
+    use super::*;
+
+    fn entries(map: ScalarValue) -> Vec<(String, i64)> {
+        let ScalarValue::Map(map) = map else { unreachable!() };
+        let (keys, counts) = (map.keys(), map.values().as_primitive::<Int64Type>());
+        let keys = arrow::compute::cast(keys, &DataType::Utf8).unwrap();
+        (0..keys.len())
+            .map(|i| (keys.as_string::<i32>().value(i).to_string(), counts.value(i)))
+            .collect()
+    }
+
+    #[test]
+    fn test_freq_map_merges_string_counts() -> Result<()> {
+        let mut first = BytesViewFreqMapAccumulator::new();
+        first.update_batch(&[Arc::new(StringViewArray::from(vec![
+            Some("a value longer than 12 bytes"),
+            None,
+            Some("b"),
+            Some("a value longer than 12 bytes"),
+        ]))])?;
+        let mut second = BytesViewFreqMapAccumulator::new();
+        second.update_batch(&[Arc::new(StringViewArray::from(vec!["c", "b"]))])?;
+
+        let mut merged = BytesViewFreqMapAccumulator::new();
+        for acc in [&mut second, &mut first] {
+            let states = acc.state()?.iter().map(|s| s.to_array()).collect::<Result<Vec<_>>>()?;
+            merged.merge_batch(&states)?;
+        }
+        assert_eq!(
+            entries(merged.evaluate()?),
+            vec![
+                ("c".to_string(), 1),
+                ("b".to_string(), 2),
+                ("a value longer than 12 bytes".to_string(), 2)
+            ]
+        );
+        assert!(merged.evaluate()?.is_null());
+        Ok(())
+    }
+
+    #[test]
+    fn test_freq_map_primitives() -> Result<()> {
+        let mut acc = FreqMapAccumulator {
+            counts: ValueCountsAccumulator::new(DataType::UInt8, SortBy::Insertion),
+        };
+        acc.update_batch(&[Arc::new(UInt8Array::from(vec![Some(3), Some(1), None, Some(3)]))])?;
+        assert_eq!(
+            entries(acc.evaluate()?),
+            vec![("3".to_string(), 2), ("1".to_string(), 1)]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,344 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+use std::any::Any;
+use std::cmp::Ordering;
+use std::fmt::Debug;
+
+use arrow::array::{ArrayRef, AsArray, Float64Array, Int64Array};
+use arrow::compute::cast;
+use arrow::datatypes::{DataType, Field, Int64Type};
+use datafusion::arrow;
+use datafusion::common::cast::as_float64_array;
+use datafusion::common::{plan_err, Result, ScalarValue};
+use datafusion::logical_expr::function::{AccumulatorArgs, StateFieldsArgs};
+use datafusion::logical_expr::{Accumulator, AggregateUDFImpl, Signature, Volatility};
+
+use crate::common::args::literal_arg;
+use crate::common::decay::coerce_ts;
+
+make_udaf_expr_and_func!(
+    LocfFunction,
+    locf,
+    value ts target_ts,
+    "Returns the last non-null value in the order of ts, at or before target_ts if given.",
+    locf_udaf
+);
+
+make_udaf_expr_and_func!(
+    InterpolateAtFunction,
+    interpolate_at,
+    value ts target_ts,
+    "Linearly interpolates the value at target_ts between the samples around it in the order of ts.",
+    interpolate_at_udaf
+);
+
+/// A sample of a series: its time, as the `Int64` of its `ts`, and its value
+type Sample = (i64, f64);
+
+/// Orders samples by time, then by value, so the samples kept don't depend on the order they
+/// were aggregated in
+fn order(a: &Sample, b: &Sample) -> Ordering {
+    a.0.cmp(&b.0).then(a.1.total_cmp(&b.1))
+}
+
+/// What the gap-filling aggregates return, [`LocfFunction`] or [`InterpolateAtFunction`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GapFill {
+    /// The last observation, carried forward to the target
+    Locf,
+    /// The value linearly interpolated between the observations around the target
+    Interpolate,
+}
+
+impl GapFill {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Locf => "locf",
+            Self::Interpolate => "interpolate_at",
+        }
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        let (value_type, ts_type, target_type) = match (self, arg_types) {
+            (Self::Locf, [value_type, ts_type]) => (value_type, ts_type, None),
+            (_, [value_type, ts_type, target_type]) => (value_type, ts_type, Some(target_type)),
+            (Self::Locf, _) => {
+                return plan_err!(
+                    "locf expects a value, a ts and an optional target_ts, got {} arguments",
+                    arg_types.len()
+                )
+            }
+            (Self::Interpolate, _) => {
+                return plan_err!(
+                    "interpolate_at expects a value, a ts and a target_ts, got {} arguments",
+                    arg_types.len()
+                )
+            }
+        };
+        if !value_type.is_numeric() && !value_type.is_null() {
+            return plan_err!("{} expects a numeric value, got {value_type}", self.name());
+        }
+        let ts_type = coerce_ts(self.name(), ts_type)?;
+        let mut coerced = vec![DataType::Float64, ts_type.clone()];
+        // the target is compared to the times in the unit of `ts`
+        coerced.extend(target_type.map(|_| ts_type));
+        Ok(coerced)
+    }
+
+    fn state_fields(&self) -> Vec<Field> {
+        let mut fields = vec![
+            Field::new("before_time", DataType::Int64, true),
+            Field::new("before_value", DataType::Float64, true),
+        ];
+        if *self == Self::Interpolate {
+            fields.push(Field::new("after_time", DataType::Int64, true));
+            fields.push(Field::new("after_value", DataType::Float64, true));
+        }
+        fields
+    }
+
+    fn accumulator(&self, acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        let Some(target) = acc_args.exprs.get(2) else {
+            return Ok(Box::new(GapFillAccumulator::new(*self, None)));
+        };
+        match literal_arg(target, self.name(), "target_ts")?.cast_to(&DataType::Int64)? {
+            ScalarValue::Int64(Some(target)) => Ok(Box::new(GapFillAccumulator::new(*self, Some(target)))),
+            _ => plan_err!("{} expects a non-null target_ts", self.name()),
+        }
+    }
+}
+
+/// The `LocfFunction` carries the last observation of a series forward,
+/// `locf(value, ts[, target_ts])`, to evaluate it at a time it wasn't observed at.
+///
+/// - Returns the value of the latest row in the order of `ts`, a timestamp, a date or an
+///   integer, at or before `target_ts` if given, a literal of the type of `ts`. Values of the
+///   same `ts` are ordered too, so the largest of them is returned.
+/// - Only the latest row is kept, so the aggregate runs in constant memory.
+/// - Rows with a NULL value or `ts` are ignored. The result is NULL if there is no row left
+///   before the target.
+pub struct LocfFunction {
+    signature: Signature,
+}
+
+/// The `InterpolateAtFunction` evaluates a series at a time between its observations,
+/// `interpolate_at(value, ts, target_ts)`, by linear interpolation.
+///
+/// - Returns the value of a row at `target_ts`, a literal of the type of `ts`, or the value on
+///   the line between the latest row before and the earliest row after it in the order of `ts`.
+/// - Only the rows around the target are kept, so the aggregate runs in constant memory.
+/// - Rows with a NULL value or `ts` are ignored. The series isn't extrapolated, so the result is
+///   NULL unless there are rows on both sides of the target or at it.
+pub struct InterpolateAtFunction {
+    signature: Signature,
+}
+
+macro_rules! gap_fill_function {
+    ($UDAF:ident, $GAP_FILL:expr) => {
+        impl Debug for $UDAF {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.debug_struct(stringify!($UDAF))
+                    .field("signature", &self.signature)
+                    .finish()
+            }
+        }
+
+        impl Default for $UDAF {
+            fn default() -> Self {
+                Self::new()
+            }
+        }
+
+        impl $UDAF {
+            pub fn new() -> Self {
+                Self {
+                    signature: Signature::user_defined(Volatility::Immutable),
+                }
+            }
+        }
+
+        impl AggregateUDFImpl for $UDAF {
+            fn as_any(&self) -> &dyn Any {
+                self
+            }
+
+            fn name(&self) -> &str {
+                $GAP_FILL.name()
+            }
+
+            fn signature(&self) -> &Signature {
+                &self.signature
+            }
+
+            fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+                $GAP_FILL.coerce_types(arg_types)
+            }
+
+            fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+                Ok(DataType::Float64)
+            }
+
+            fn state_fields(&self, _args: StateFieldsArgs) -> Result<Vec<Field>> {
+                Ok($GAP_FILL.state_fields())
+            }
+
+            fn accumulator(&self, acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+                $GAP_FILL.accumulator(acc_args)
+            }
+        }
+    };
+}
+
+gap_fill_function!(LocfFunction, GapFill::Locf);
+gap_fill_function!(InterpolateAtFunction, GapFill::Interpolate);
+
+/// Accumulator for the gap-filling aggregates, keeping the samples around the target
+#[derive(Debug)]
+pub struct GapFillAccumulator {
+    gap_fill: GapFill,
+    /// The time to evaluate the series at, the latest sample if `None`
+    target: Option<i64>,
+    /// The latest sample at or before the target
+    before: Option<Sample>,
+    /// The earliest sample at or after the target, for [`GapFill::Interpolate`]
+    after: Option<Sample>,
+}
+
+impl GapFillAccumulator {
+    pub fn new(gap_fill: GapFill, target: Option<i64>) -> Self {
+        Self {
+            gap_fill,
+            target,
+            before: None,
+            after: None,
+        }
+    }
+
+    fn add(&mut self, sample: Sample) {
+        let target = self.target.unwrap_or(i64::MAX);
+        if sample.0 <= target && self.before.map_or(true, |before| order(&sample, &before).is_gt()) {
+            self.before = Some(sample);
+        }
+        if self.gap_fill == GapFill::Interpolate
+            && sample.0 >= target
+            && self.after.map_or(true, |after| order(&sample, &after).is_lt())
+        {
+            self.after = Some(sample);
+        }
+    }
+
+    fn add_all(&mut self, times: &Int64Array, values: &Float64Array) {
+        for (time, value) in times.iter().zip(values.iter()) {
+            if let (Some(time), Some(value)) = (time, value) {
+                self.add((time, value));
+            }
+        }
+    }
+}
+
+impl Accumulator for GapFillAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        let times = cast(&values[1], &DataType::Int64)?;
+        self.add_all(times.as_primitive::<Int64Type>(), as_float64_array(&values[0])?);
+        Ok(())
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        for state in states.chunks(2) {
+            self.add_all(state[0].as_primitive::<Int64Type>(), as_float64_array(&state[1])?);
+        }
+        Ok(())
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        let sample = |sample: Option<Sample>| {
+            [
+                ScalarValue::Int64(sample.map(|sample| sample.0)),
+                ScalarValue::Float64(sample.map(|sample| sample.1)),
+            ]
+        };
+        let mut state = sample(self.before).to_vec();
+        if self.gap_fill == GapFill::Interpolate {
+            state.extend(sample(self.after));
+        }
+        Ok(state)
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        let value = match (self.gap_fill, self.before, self.after) {
+            (GapFill::Locf, before, _) => before.map(|before| before.1),
+            (GapFill::Interpolate, Some(before), _) if Some(before.0) == self.target => Some(before.1),
+            (GapFill::Interpolate, Some(before), Some(after)) => {
+                let target = self.target.unwrap_or(before.0);
+                let fraction = (target - before.0) as f64 / (after.0 - before.0) as f64;
+                Some(before.1 + (after.1 - before.1) * fraction)
+            }
+            (GapFill::Interpolate, _, _) => None,
+        };
+        Ok(ScalarValue::Float64(value))
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    #[test]
+    fn test_gap_fill_across_partitions() -> Result<()> {
+        let batch = |times: Vec<i64>, values: Vec<Option<f64>>| -> Vec<ArrayRef> {
+            vec![Arc::new(Float64Array::from(values)), Arc::new(Int64Array::from(times))]
+        };
+        let evaluate = |gap_fill: GapFill, target: Option<i64>| -> Result<ScalarValue> {
+            let mut partial = GapFillAccumulator::new(gap_fill, target);
+            partial.update_batch(&batch(vec![30, 0], vec![Some(7.0), Some(10.0)]))?;
+            let state = partial
+                .state()?
+                .iter()
+                .map(|state| state.to_array())
+                .collect::<Result<Vec<_>>>()?;
+            let mut acc = GapFillAccumulator::new(gap_fill, target);
+            // 10 at 0, 15 and 20 at 10, 7 at 30, NULL ignored
+            acc.update_batch(&batch(vec![10, 20, 10], vec![Some(15.0), None, Some(20.0)]))?;
+            acc.merge_batch(&state)?;
+            acc.evaluate()
+        };
+        assert_eq!(evaluate(GapFill::Locf, None)?, ScalarValue::Float64(Some(7.0)));
+        assert_eq!(evaluate(GapFill::Locf, Some(25))?, ScalarValue::Float64(Some(20.0)));
+        assert_eq!(evaluate(GapFill::Locf, Some(-1))?, ScalarValue::Float64(None));
+        // between the largest value at 10 and 7 at 30
+        assert_eq!(
+            evaluate(GapFill::Interpolate, Some(25))?,
+            ScalarValue::Float64(Some(10.25))
+        );
+        assert_eq!(
+            evaluate(GapFill::Interpolate, Some(5))?,
+            ScalarValue::Float64(Some(12.5))
+        );
+        assert_eq!(
+            evaluate(GapFill::Interpolate, Some(30))?,
+            ScalarValue::Float64(Some(7.0))
+        );
+        assert_eq!(evaluate(GapFill::Interpolate, Some(31))?, ScalarValue::Float64(None));
+        Ok(())
+    }
+}
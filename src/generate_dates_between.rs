@@ -0,0 +1,209 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::any::Any;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use datafusion::arrow::array::{ArrayRef, Date32Array, ListArray};
+use datafusion::arrow::buffer::OffsetBuffer;
+use datafusion::arrow::datatypes::{DataType, Field};
+use datafusion::catalog::TableProvider;
+use datafusion::common::cast::as_date32_array;
+use datafusion::common::{exec_err, plan_err, Column, Result, ScalarValue, TableReference, UnnestOptions};
+use datafusion::datasource::function::TableFunctionImpl;
+use datafusion::datasource::{provider_as_source, ViewTable};
+use datafusion::execution::context::SessionContext;
+use datafusion::logical_expr::{
+    cast, ident, ColumnarValue, Expr, LogicalPlan, LogicalPlanBuilder, ScalarUDF, ScalarUDFImpl, Signature, Volatility,
+};
+
+use crate::common::tables::{column_arg, table_arg, SessionTables};
+
+/// Name of the output column holding the dates
+const DATE_COLUMN: &str = "date";
+
+/// Default largest number of dates a row expands into, about 100 years of days
+pub const DEFAULT_MAX_DAYS: i64 = 36_600;
+
+/// The `GenerateDatesBetweenFunction` expands every row of a table into one row per date between
+/// two of its columns, the table function
+/// `generate_dates_between(table, start_col, end_col[, max_days])`.
+///
+/// - Every output row is made of the columns of the input row and one of the dates from
+///   `start_col` to `end_col`, both included, as `date`, e.g. one row per night of a booking.
+/// - The date columns can be dates or timestamps, which are truncated to their date. Rows with a
+///   NULL date or ending before they start are skipped.
+/// - Rows spanning more than `max_days` dates fail the query rather than being truncated, to
+///   protect against bogus end dates, see [`DEFAULT_MAX_DAYS`].
+/// - The table and the columns are identifiers or strings, as for
+///   [`crate::unpivot::UnpivotFunction`].
+///
+/// Tables are resolved in the catalogs of the [`SessionContext`] the function was created for,
+/// see [`crate::register_extra_table_functions`] and [`SessionTables`].
+pub struct GenerateDatesBetweenFunction {
+    tables: SessionTables,
+}
+
+impl Debug for GenerateDatesBetweenFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GenerateDatesBetweenFunction")
+            .field("tables", &self.tables)
+            .finish()
+    }
+}
+
+impl GenerateDatesBetweenFunction {
+    /// Creates the function for the catalogs and default schema of `ctx`
+    pub fn new(ctx: &SessionContext) -> Self {
+        Self {
+            tables: SessionTables::new(ctx),
+        }
+    }
+}
+
+impl TableFunctionImpl for GenerateDatesBetweenFunction {
+    fn call(&self, args: &[Expr]) -> Result<Arc<dyn TableProvider>> {
+        const NAME: &str = "generate_dates_between";
+        let (table, start, end, max_days) = match args {
+            [table, start, end] => (table, start, end, DEFAULT_MAX_DAYS),
+            [table, start, end, Expr::Literal(max_days)] => match max_days.cast_to(&DataType::Int64)? {
+                ScalarValue::Int64(Some(max_days)) if max_days > 0 => (table, start, end, max_days),
+                _ => return plan_err!("{NAME} expects a positive max_days, got {max_days}"),
+            },
+            [_, _, _, max_days] => return plan_err!("{NAME} expects a literal max_days, got {max_days}"),
+            _ => return plan_err!("{NAME} expects a table, a start and an end column and an optional max_days"),
+        };
+        let table_ref = table_arg(NAME, table)?;
+        let (start, end) = (column_arg(NAME, start)?, column_arg(NAME, end)?);
+        let table = self.tables.table(NAME, &table_ref)?;
+        let plan = generate_dates_plan(table_ref, table, start, end, max_days)?;
+        Ok(Arc::new(ViewTable::try_new(plan, None)?))
+    }
+}
+
+/// The plan expanding every row of `table` into the dates from its `start` to its `end`: the
+/// dates are collected into a list, which is unnested.
+fn generate_dates_plan(
+    table_ref: TableReference,
+    table: Arc<dyn TableProvider>,
+    start: &str,
+    end: &str,
+    max_days: i64,
+) -> Result<LogicalPlan> {
+    let schema = table.schema();
+    for name in [start, end] {
+        let Ok(field) = schema.field_with_name(name) else {
+            return plan_err!("generate_dates_between can't find column {name} in table {table_ref}");
+        };
+        if !matches!(
+            field.data_type(),
+            DataType::Date32 | DataType::Date64 | DataType::Timestamp(_, _)
+        ) {
+            return plan_err!(
+                "generate_dates_between expects a date or timestamp column, got {name} of type {}",
+                field.data_type()
+            );
+        }
+    }
+    if schema.field_with_name(DATE_COLUMN).is_ok() {
+        return plan_err!(
+            "generate_dates_between can't add its {DATE_COLUMN} column, table {table_ref} already has a column named {DATE_COLUMN}"
+        );
+    }
+
+    let dates = ScalarUDF::from(DatesBetweenFunction::new(max_days));
+    let columns = schema.fields().iter().map(|field| ident(field.name())).chain([dates
+        .call(vec![
+            cast(ident(start), DataType::Date32),
+            cast(ident(end), DataType::Date32),
+        ])
+        .alias(DATE_COLUMN)]);
+    LogicalPlanBuilder::scan(table_ref, provider_as_source(table), None)?
+        .project(columns)?
+        .unnest_columns_with_options(
+            vec![Column::from_name(DATE_COLUMN)],
+            UnnestOptions::default().with_preserve_nulls(false),
+        )?
+        .build()
+}
+
+/// The list of dates from a start date to an end date, both included, failing if there are
+/// more than `max_days`
+#[derive(Debug)]
+struct DatesBetweenFunction {
+    signature: Signature,
+    max_days: i64,
+}
+
+impl DatesBetweenFunction {
+    fn new(max_days: i64) -> Self {
+        Self {
+            signature: Signature::exact(vec![DataType::Date32, DataType::Date32], Volatility::Immutable),
+            max_days,
+        }
+    }
+}
+
+impl ScalarUDFImpl for DatesBetweenFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "generate_dates_between"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::new_list(DataType::Date32, false))
+    }
+
+    fn invoke(&self, args: &[ColumnarValue]) -> Result<ColumnarValue> {
+        let arrays = ColumnarValue::values_to_arrays(args)?;
+        let (starts, ends) = (as_date32_array(&arrays[0])?, as_date32_array(&arrays[1])?);
+        let mut lengths = Vec::with_capacity(starts.len());
+        let mut dates = vec![];
+        for (start, end) in starts.iter().zip(ends.iter()) {
+            let (Some(start), Some(end)) = (start, end) else {
+                lengths.push(0);
+                continue;
+            };
+            let days = (end as i64 - start as i64 + 1).max(0);
+            if days > self.max_days {
+                return exec_err!(
+                    "generate_dates_between expects at most {} dates per row, got {days} from {} to {}",
+                    self.max_days,
+                    ScalarValue::Date32(Some(start)),
+                    ScalarValue::Date32(Some(end))
+                );
+            }
+            dates.extend(start..start + days as i32);
+            lengths.push(days as usize);
+        }
+        let list = ListArray::try_new(
+            Arc::new(Field::new_list_field(DataType::Date32, false)),
+            OffsetBuffer::from_lengths(lengths),
+            Arc::new(Date32Array::from(dates)),
+            None,
+        )?;
+        Ok(ColumnarValue::Array(Arc::new(list) as ArrayRef))
+    }
+}
@@ -0,0 +1,194 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::any::Any;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use arrow::array::{ArrayRef, Float64Array};
+use datafusion::arrow;
+use datafusion::arrow::datatypes::{DataType, Field};
+use datafusion::common::cast::{as_float64_array, as_list_array};
+use datafusion::common::{exec_err, Result, ScalarValue};
+use datafusion::logical_expr::function::{AccumulatorArgs, StateFieldsArgs};
+use datafusion::logical_expr::{Accumulator, AggregateUDFImpl, Signature, Volatility};
+
+use crate::common::nulls::all_null_or_filtered;
+use crate::compat::single_row_list;
+
+make_udaf_expr_and_func!(
+    GiniFunction,
+    gini,
+    x,
+    "Calculates the Gini coefficient of non-negative values.",
+    gini_udaf
+);
+
+/// The `GiniFunction` calculates the Gini coefficient of non-negative values, from 0 when all
+/// values are equal to `1 - 1/n` when a single value holds the whole total.
+///
+/// - NULL values are ignored, negative values are an error.
+/// - The result is NULL if there are no values or they sum to 0.
+/// - The coefficient depends on the rank of every value, so partial aggregates emit their
+///   values as a list which the final aggregate concatenates and sorts once.
+pub struct GiniFunction {
+    signature: Signature,
+}
+
+impl Debug for GiniFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GiniFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for GiniFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GiniFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::coercible(vec![DataType::Float64], Volatility::Immutable),
+        }
+    }
+}
+
+impl AggregateUDFImpl for GiniFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "gini"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Float64)
+    }
+
+    fn state_fields(&self, _args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(vec![Field::new_list(
+            "values",
+            Field::new("item", DataType::Float64, true),
+            true,
+        )])
+    }
+
+    fn accumulator(&self, _acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        Ok(Box::new(GiniAccumulator::new()))
+    }
+}
+
+/// Accumulator for [`GiniFunction`], which buffers the values.
+#[derive(Debug, Default)]
+pub struct GiniAccumulator {
+    values: Vec<f64>,
+}
+
+impl GiniAccumulator {
+    pub fn new() -> Self {
+        Self { values: vec![] }
+    }
+}
+
+impl Accumulator for GiniAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        if all_null_or_filtered(&values[0], None) {
+            return Ok(());
+        }
+        for value in as_float64_array(&values[0])?.iter().flatten() {
+            if value < 0.0 {
+                return exec_err!("gini expects non-negative values, got {value}");
+            }
+            self.values.push(value);
+        }
+        Ok(())
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        for values in as_list_array(&states[0])?.iter().flatten() {
+            self.values.extend(as_float64_array(&values)?.iter().flatten());
+        }
+        Ok(())
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        let values = Float64Array::from(std::mem::take(&mut self.values));
+        Ok(vec![ScalarValue::List(Arc::new(single_row_list(Arc::new(values))))])
+    }
+
+    /// `G = Σ (2i - n - 1) x_i / (n Σ x)` over the values sorted ascending, with `i` from 1 to `n`
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        self.values.sort_unstable_by(f64::total_cmp);
+        let n = self.values.len() as f64;
+        let total: f64 = self.values.iter().sum();
+        if self.values.is_empty() || total == 0.0 {
+            return Ok(ScalarValue::Float64(None));
+        }
+        let weighted: f64 = self
+            .values
+            .iter()
+            .enumerate()
+            .map(|(i, value)| (2.0 * (i + 1) as f64 - n - 1.0) * value)
+            .sum();
+        Ok(ScalarValue::Float64(Some(weighted / (n * total))))
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self) + self.values.capacity() * std::mem::size_of::<f64>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn evaluate(values: Vec<Option<f64>>) -> Result<ScalarValue> {
+        let mut acc = GiniAccumulator::new();
+        acc.update_batch(&[Arc::new(Float64Array::from(values))])?;
+        acc.evaluate()
+    }
+
+    #[test]
+    fn test_gini() -> Result<()> {
+        assert_eq!(
+            evaluate(vec![Some(5.0), None, Some(5.0)])?,
+            ScalarValue::Float64(Some(0.0))
+        );
+        // A single value out of four holds the whole total
+        assert_eq!(
+            evaluate(vec![Some(0.0), Some(10.0), Some(0.0), Some(0.0)])?,
+            ScalarValue::Float64(Some(0.75))
+        );
+        // Mean absolute difference 4/3 over twice the mean 2
+        let ScalarValue::Float64(Some(gini)) = evaluate(vec![Some(3.0), Some(1.0), Some(2.0)])? else {
+            panic!("expected a non-null gini");
+        };
+        assert!((gini - 2.0 / 9.0).abs() < 1e-12, "got {gini}");
+        assert_eq!(evaluate(vec![Some(0.0), None])?, ScalarValue::Float64(None));
+        assert!(evaluate(vec![Some(-1.0)]).is_err());
+        Ok(())
+    }
+}
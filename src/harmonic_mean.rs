@@ -0,0 +1,269 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use arrow::array::{Array, ArrayRef, Float64Array, UInt64Array};
+use datafusion::arrow;
+use datafusion::arrow::datatypes::{DataType, Field};
+use datafusion::common::cast::as_float64_array;
+use datafusion::common::{downcast_value, exec_err, DataFusionError, Result, ScalarValue};
+use datafusion::logical_expr::function::{AccumulatorArgs, StateFieldsArgs};
+use datafusion::logical_expr::{Accumulator, AggregateUDFImpl, Signature, Volatility};
+use std::any::Any;
+use std::fmt::Debug;
+
+use crate::common::nulls::all_null_or_filtered;
+
+make_udaf_expr_and_func!(
+    HarmonicMeanFunction,
+    harmonic_mean,
+    x,
+    "Calculates the harmonic mean, the number of values divided by the sum of their reciprocals.",
+    harmonic_mean_udaf
+);
+
+/// How `harmonic_mean` treats values equal to zero, whose reciprocal is infinite.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ZeroValuePolicy {
+    /// The result is 0 if any value is zero, which is the limit of the harmonic mean as a value
+    /// approaches zero.
+    #[default]
+    ReturnZero,
+    /// Zero values are skipped like NULLs.
+    IgnoreZeros,
+    /// The result is NULL if any value is zero.
+    ReturnNull,
+    /// Evaluation fails if any value is zero.
+    Error,
+}
+
+pub struct HarmonicMeanFunction {
+    signature: Signature,
+    zero_value_policy: ZeroValuePolicy,
+}
+
+impl Debug for HarmonicMeanFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HarmonicMeanFunction")
+            .field("signature", &self.signature)
+            .field("zero_value_policy", &self.zero_value_policy)
+            .finish()
+    }
+}
+
+impl Default for HarmonicMeanFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HarmonicMeanFunction {
+    pub fn new() -> Self {
+        Self::new_with_zero_value_policy(ZeroValuePolicy::default())
+    }
+
+    pub fn new_with_zero_value_policy(zero_value_policy: ZeroValuePolicy) -> Self {
+        Self {
+            signature: Signature::coercible(vec![DataType::Float64], Volatility::Immutable),
+            zero_value_policy,
+        }
+    }
+}
+
+impl AggregateUDFImpl for HarmonicMeanFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "harmonic_mean"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Float64)
+    }
+
+    fn state_fields(&self, _args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(vec![
+            Field::new("count", DataType::UInt64, true),
+            Field::new("sum_reciprocal", DataType::Float64, true),
+            Field::new("zero_count", DataType::UInt64, true),
+        ])
+    }
+
+    fn accumulator(&self, _acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        Ok(Box::new(HarmonicMeanAccumulator::new(self.zero_value_policy)))
+    }
+}
+
+/// Accumulator for the harmonic mean.
+///
+/// Zero values are counted separately from the sum of reciprocals so that the
+/// [`ZeroValuePolicy`] is applied once, on the merged result.
+#[derive(Debug, Default)]
+pub struct HarmonicMeanAccumulator {
+    count: u64,
+    sum_reciprocal: f64,
+    zero_count: u64,
+    zero_value_policy: ZeroValuePolicy,
+}
+
+impl HarmonicMeanAccumulator {
+    pub fn new(zero_value_policy: ZeroValuePolicy) -> Self {
+        Self {
+            zero_value_policy,
+            ..Default::default()
+        }
+    }
+}
+
+impl Accumulator for HarmonicMeanAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        if all_null_or_filtered(&values[0], None) {
+            return Ok(());
+        }
+        let array = as_float64_array(&values[0])?;
+        for value in array.iter().flatten() {
+            if value == 0.0 {
+                if self.zero_value_policy != ZeroValuePolicy::IgnoreZeros {
+                    self.zero_count += 1;
+                }
+                continue;
+            }
+            self.count += 1;
+            self.sum_reciprocal += value.recip();
+        }
+        Ok(())
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        if all_null_or_filtered(&states[0], None) {
+            return Ok(());
+        }
+        let counts = downcast_value!(states[0], UInt64Array);
+        let sums = downcast_value!(states[1], Float64Array);
+        let zero_counts = downcast_value!(states[2], UInt64Array);
+
+        for i in 0..counts.len() {
+            if counts.is_null(i) {
+                continue;
+            }
+            self.count += counts.value(i);
+            self.sum_reciprocal += sums.value(i);
+            self.zero_count += zero_counts.value(i);
+        }
+        Ok(())
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        if self.zero_count > 0 {
+            return match self.zero_value_policy {
+                ZeroValuePolicy::ReturnZero => Ok(ScalarValue::Float64(Some(0.0))),
+                ZeroValuePolicy::ReturnNull => Ok(ScalarValue::Float64(None)),
+                ZeroValuePolicy::Error => exec_err!("harmonic_mean is undefined for zero values"),
+                ZeroValuePolicy::IgnoreZeros => unreachable!("zero values are not counted when ignored"),
+            };
+        }
+        if self.count == 0 {
+            return Ok(ScalarValue::Float64(None));
+        }
+        Ok(ScalarValue::Float64(Some(self.count as f64 / self.sum_reciprocal)))
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self)
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        Ok(vec![
+            ScalarValue::from(self.count),
+            ScalarValue::from(self.sum_reciprocal),
+            ScalarValue::from(self.zero_count),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    fn harmonic_mean_of(values: Vec<Option<f64>>, policy: ZeroValuePolicy) -> Result<ScalarValue> {
+        let mut acc = HarmonicMeanAccumulator::new(policy);
+        acc.update_batch(&[Arc::new(Float64Array::from(values))])?;
+        acc.evaluate()
+    }
+
+    #[test]
+    fn test_harmonic_mean() -> Result<()> {
+        let values = vec![Some(1.0), Some(4.0), None, Some(4.0)];
+        assert_eq!(
+            harmonic_mean_of(values, ZeroValuePolicy::default())?,
+            ScalarValue::Float64(Some(2.0))
+        );
+        assert_eq!(
+            harmonic_mean_of(vec![None], ZeroValuePolicy::default())?,
+            ScalarValue::Float64(None)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_harmonic_mean_zero_value_policy() -> Result<()> {
+        let values = vec![Some(1.0), Some(0.0), Some(1.0)];
+        assert_eq!(
+            harmonic_mean_of(values.clone(), ZeroValuePolicy::ReturnZero)?,
+            ScalarValue::Float64(Some(0.0))
+        );
+        assert_eq!(
+            harmonic_mean_of(values.clone(), ZeroValuePolicy::IgnoreZeros)?,
+            ScalarValue::Float64(Some(1.0))
+        );
+        assert_eq!(
+            harmonic_mean_of(values.clone(), ZeroValuePolicy::ReturnNull)?,
+            ScalarValue::Float64(None)
+        );
+        assert!(harmonic_mean_of(values, ZeroValuePolicy::Error).is_err());
+        assert_eq!(
+            harmonic_mean_of(vec![Some(0.0)], ZeroValuePolicy::IgnoreZeros)?,
+            ScalarValue::Float64(None)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_harmonic_mean_merge_keeps_zeros() -> Result<()> {
+        let mut merged = HarmonicMeanAccumulator::new(ZeroValuePolicy::ReturnNull);
+        for chunk in [vec![2.0, 2.0], vec![0.0], vec![]] {
+            let mut partial = HarmonicMeanAccumulator::new(ZeroValuePolicy::ReturnNull);
+            partial.update_batch(&[Arc::new(Float64Array::from(chunk))])?;
+            let state = partial
+                .state()?
+                .iter()
+                .map(|s| s.to_array())
+                .collect::<Result<Vec<_>>>()?;
+            merged.merge_batch(&state)?;
+        }
+        assert_eq!(merged.count, 2);
+        assert_eq!(merged.zero_count, 1);
+        assert_eq!(merged.evaluate()?, ScalarValue::Float64(None));
+        Ok(())
+    }
+}
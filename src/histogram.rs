@@ -0,0 +1,735 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::any::Any;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use arrow::array::{Array, ArrayRef, Float64Array, StructArray, UInt64Array};
+use arrow::compute::cast;
+use arrow::datatypes::{DataType, Field, Fields};
+use datafusion::arrow;
+use datafusion::common::cast::{as_binary_array, as_float64_array, as_list_array, as_uint64_array};
+use datafusion::common::{exec_err, plan_err, Result, ScalarValue};
+use datafusion::logical_expr::function::{AccumulatorArgs, StateFieldsArgs};
+use datafusion::logical_expr::{Accumulator, AggregateUDFImpl, Signature, TypeSignature, Volatility};
+
+use crate::common::args::{literal_arg, literal_f64_arg};
+use crate::common::nulls::all_null_or_filtered;
+use crate::common::quantile_buffer::QuantileBuffer;
+use crate::compat::single_row_list;
+use crate::sketches::numeric_histogram::{Bin, NumericHistogram};
+use crate::sketches::tdigest::TDigest;
+
+make_udaf_expr_and_func!(
+    HistogramFunction,
+    histogram,
+    "Counts the values in equal-width buckets, returned as a list of structs with the bounds and the count of every bucket.",
+    histogram_udaf
+);
+
+make_udaf_expr_and_func!(
+    HistogramEquiDepthFunction,
+    histogram_equi_depth,
+    x num_buckets,
+    "Splits the values into buckets of roughly equal counts, returned as a list of structs with the bounds and the count of every bucket.",
+    histogram_equi_depth_udaf
+);
+
+make_udaf_expr_and_func!(
+    HistogramNumericFunction,
+    histogram_numeric,
+    x nb,
+    "Approximates the distribution of the values with nb bins, as Spark's histogram_numeric.",
+    histogram_numeric_udaf
+);
+
+/// Largest number of buckets
+const MAX_BUCKETS: usize = 100_000;
+
+/// The type of the histograms, a list of `{lower, upper, count}` structs
+fn histogram_type() -> DataType {
+    DataType::new_list(DataType::Struct(bucket_fields()), true)
+}
+
+fn bucket_fields() -> Fields {
+    Fields::from(vec![
+        Field::new("lower", DataType::Float64, false),
+        Field::new("upper", DataType::Float64, false),
+        Field::new("count", DataType::UInt64, false),
+    ])
+}
+
+/// The literal `num_buckets` argument of the histograms
+fn num_buckets_arg(acc_args: &AccumulatorArgs, fn_name: &str) -> Result<usize> {
+    match literal_arg(&acc_args.exprs[1], fn_name, "num_buckets")? {
+        ScalarValue::Int64(Some(n)) if (1..=MAX_BUCKETS as i64).contains(&n) => Ok(n as usize),
+        n => plan_err!("{fn_name} expects num_buckets between 1 and {MAX_BUCKETS}, got {n}"),
+    }
+}
+
+/// The histogram of the buckets `(lower, upper, count)`
+fn histogram_value(buckets: impl IntoIterator<Item = (f64, f64, u64)>) -> ScalarValue {
+    let (mut lower, mut upper, mut counts) = (vec![], vec![], vec![]);
+    for (l, u, count) in buckets {
+        lower.push(l);
+        upper.push(u);
+        counts.push(count);
+    }
+    let buckets = StructArray::new(
+        bucket_fields(),
+        vec![
+            Arc::new(Float64Array::from(lower)),
+            Arc::new(Float64Array::from(upper)),
+            Arc::new(UInt64Array::from(counts)),
+        ],
+        None,
+    );
+    ScalarValue::List(Arc::new(single_row_list(Arc::new(buckets))))
+}
+
+/// The `HistogramFunction` counts the values in equal-width buckets,
+/// `histogram(x, num_buckets[, min, max])`.
+///
+/// - The result is a list of `num_buckets` structs `{lower, upper, count}`. Buckets include their
+///   lower bound and exclude their upper bound, except the last one which includes both.
+/// - With the literal bounds `min` and `max`, the buckets split `[min, max]` and the state is a
+///   vector of counts. Values outside of the bounds are not counted.
+/// - Without bounds, the buckets split the range of the values: every value of a group is
+///   buffered, and the minimum and the maximum are found before the values are counted. If all
+///   the values are equal, there is a single bucket.
+/// - NULL, NaN and infinite values are ignored. The result is NULL if no value is counted.
+pub struct HistogramFunction {
+    signature: Signature,
+}
+
+impl Debug for HistogramFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HistogramFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for HistogramFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HistogramFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::one_of(
+                vec![
+                    TypeSignature::Coercible(vec![DataType::Float64, DataType::Int64]),
+                    TypeSignature::Coercible(vec![
+                        DataType::Float64,
+                        DataType::Int64,
+                        DataType::Float64,
+                        DataType::Float64,
+                    ]),
+                ],
+                Volatility::Immutable,
+            ),
+        }
+    }
+}
+
+impl AggregateUDFImpl for HistogramFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "histogram"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(histogram_type())
+    }
+
+    /// Histograms with bounds only use the counts, and the others only the values
+    fn state_fields(&self, _args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(vec![
+            Field::new_list("counts", Field::new("item", DataType::UInt64, true), true),
+            Field::new_list("values", Field::new("item", DataType::Float64, true), true),
+        ])
+    }
+
+    fn accumulator(&self, acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        let num_buckets = num_buckets_arg(&acc_args, self.name())?;
+        let bounds = match acc_args.exprs.get(2..4) {
+            Some([min, max]) => {
+                let min = literal_f64_arg(min, self.name(), "min")?;
+                let max = literal_f64_arg(max, self.name(), "max")?;
+                if !(min.is_finite() && max.is_finite() && min < max) {
+                    return plan_err!("histogram expects finite bounds with min < max, got {min} and {max}");
+                }
+                Some((min, max))
+            }
+            _ => None,
+        };
+        Ok(Box::new(HistogramAccumulator::new(num_buckets, bounds)))
+    }
+}
+
+/// Accumulator for [`HistogramFunction`], counting the values in the buckets of the bounds if
+/// known, and buffering them otherwise.
+#[derive(Debug)]
+pub struct HistogramAccumulator {
+    num_buckets: usize,
+    bounds: Option<(f64, f64)>,
+    /// The counts of the buckets of `bounds`, empty until a value is counted
+    counts: Vec<u64>,
+    /// The values, without bounds
+    values: QuantileBuffer,
+}
+
+impl HistogramAccumulator {
+    pub fn new(num_buckets: usize, bounds: Option<(f64, f64)>) -> Self {
+        Self {
+            num_buckets,
+            bounds,
+            counts: vec![],
+            values: QuantileBuffer::new(),
+        }
+    }
+
+    /// Counts `values` in `num_buckets` buckets of `[min, max]` into `counts`
+    fn count(counts: &mut Vec<u64>, num_buckets: usize, (min, max): (f64, f64), values: impl Iterator<Item = f64>) {
+        let width = (max - min) / num_buckets as f64;
+        for value in values.filter(|value| (min..=max).contains(value)) {
+            if counts.is_empty() {
+                counts.resize(num_buckets, 0);
+            }
+            // Rounding can put values just below max past the last bucket
+            let bucket = (((value - min) / width) as usize).min(num_buckets - 1);
+            counts[bucket] += 1;
+        }
+    }
+
+    fn histogram(num_buckets: usize, (min, max): (f64, f64), counts: &[u64]) -> ScalarValue {
+        let width = (max - min) / num_buckets as f64;
+        let lower = (0..num_buckets).map(|i| min + width * i as f64);
+        // The last bound is exactly max despite rounding
+        let upper = (1..=num_buckets).map(|i| if i == num_buckets { max } else { min + width * i as f64 });
+        histogram_value(
+            lower
+                .zip(upper)
+                .zip(counts.iter().copied())
+                .map(|((l, u), c)| (l, u, c)),
+        )
+    }
+}
+
+impl Accumulator for HistogramAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        if all_null_or_filtered(&values[0], None) {
+            return Ok(());
+        }
+        let finite = as_float64_array(&values[0])?
+            .iter()
+            .flatten()
+            .filter(|value| value.is_finite());
+        match self.bounds {
+            Some(bounds) => Self::count(&mut self.counts, self.num_buckets, bounds, finite),
+            None => {
+                let finite: Float64Array = finite.map(Some).collect();
+                self.values.update_batch(&(Arc::new(finite) as ArrayRef))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        if self.bounds.is_none() {
+            return self.values.merge_batch(&states[1]);
+        }
+        for counts in as_list_array(&states[0])?
+            .iter()
+            .flatten()
+            .filter(|counts| !counts.is_empty())
+        {
+            if counts.len() != self.num_buckets {
+                return exec_err!(
+                    "histogram state has {} buckets, expected {}",
+                    counts.len(),
+                    self.num_buckets
+                );
+            }
+            if self.counts.is_empty() {
+                self.counts.resize(self.num_buckets, 0);
+            }
+            for (count, other) in self.counts.iter_mut().zip(as_uint64_array(&counts)?.values()) {
+                *count += other;
+            }
+        }
+        Ok(())
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        let counts = UInt64Array::from(std::mem::take(&mut self.counts));
+        Ok(vec![
+            ScalarValue::List(Arc::new(single_row_list(Arc::new(counts)))),
+            self.values.state(),
+        ])
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        let null = || ScalarValue::try_from(histogram_type());
+        if let Some(bounds) = self.bounds {
+            if self.counts.is_empty() {
+                return null();
+            }
+            return Ok(Self::histogram(self.num_buckets, bounds, &self.counts));
+        }
+        let values = self.values.sorted();
+        let (Some(&min), Some(&max)) = (values.first(), values.last()) else {
+            return null();
+        };
+        if min == max {
+            return Ok(Self::histogram(1, (min, max), &[values.len() as u64]));
+        }
+        let mut counts = vec![];
+        Self::count(&mut counts, self.num_buckets, (min, max), values.iter().copied());
+        Ok(Self::histogram(self.num_buckets, (min, max), &counts))
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self) + self.counts.capacity() * std::mem::size_of::<u64>() + self.values.size()
+    }
+}
+
+/// The `HistogramEquiDepthFunction` splits the values into buckets holding roughly the same
+/// number of values, `histogram_equi_depth(x, num_buckets)`, as the histograms of optimizer
+/// statistics.
+///
+/// - The result is a list of structs `{lower, upper, count}`, as for [`HistogramFunction`]. The
+///   bounds are the quantiles `i / num_buckets` estimated by a [`TDigest`], the first lower bound
+///   is the minimum and the last upper bound the maximum.
+/// - Counts split the number of values evenly, so they add up to it exactly while the bounds are
+///   estimates. Buckets with the same bounds, e.g. of a value repeated in more than a bucket's
+///   worth of rows, are combined, so there may be fewer than `num_buckets` buckets.
+/// - NULL, NaN and infinite values are ignored. The result is NULL if no value is counted.
+pub struct HistogramEquiDepthFunction {
+    signature: Signature,
+}
+
+impl Debug for HistogramEquiDepthFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HistogramEquiDepthFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for HistogramEquiDepthFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HistogramEquiDepthFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::coercible(vec![DataType::Float64, DataType::Int64], Volatility::Immutable),
+        }
+    }
+}
+
+impl AggregateUDFImpl for HistogramEquiDepthFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "histogram_equi_depth"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(histogram_type())
+    }
+
+    fn state_fields(&self, _args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(vec![Field::new("digest", DataType::Binary, true)])
+    }
+
+    fn accumulator(&self, acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        let num_buckets = num_buckets_arg(&acc_args, self.name())?;
+        Ok(Box::new(HistogramEquiDepthAccumulator::new(num_buckets)))
+    }
+}
+
+/// Accumulator for [`HistogramEquiDepthFunction`]
+#[derive(Debug)]
+pub struct HistogramEquiDepthAccumulator {
+    num_buckets: usize,
+    digest: TDigest,
+}
+
+impl HistogramEquiDepthAccumulator {
+    pub fn new(num_buckets: usize) -> Self {
+        Self {
+            num_buckets,
+            digest: TDigest::default(),
+        }
+    }
+}
+
+impl Accumulator for HistogramEquiDepthAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        if all_null_or_filtered(&values[0], None) {
+            return Ok(());
+        }
+        for value in as_float64_array(&values[0])?.iter().flatten() {
+            if value.is_finite() {
+                self.digest.add(value);
+            }
+        }
+        Ok(())
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        for bytes in as_binary_array(&states[0])?.iter().flatten() {
+            self.digest.merge(&TDigest::from_bytes(bytes)?);
+        }
+        Ok(())
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        Ok(vec![ScalarValue::Binary(Some(self.digest.to_bytes()))])
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        if self.digest.is_empty() {
+            return ScalarValue::try_from(histogram_type());
+        }
+        let n = self.num_buckets;
+        let total = self.digest.count().round() as u64;
+        let mut bounds = Vec::with_capacity(n + 1);
+        for i in 0..=n {
+            let bound = self
+                .digest
+                .quantile(i as f64 / n as f64)
+                .expect("the digest is not empty");
+            // Interpolation can't make the bounds decrease, but guard against rounding
+            bounds.push(bounds.last().map_or(bound, |&last: &f64| last.max(bound)));
+        }
+        // The rank of the end of every bucket, rounded so that the counts add up to the total
+        let end = |i: usize| (total as u128 * i as u128 / n as u128) as u64;
+
+        let mut buckets: Vec<(f64, f64, u64)> = vec![];
+        for i in 0..n {
+            let (lower, upper, count) = (bounds[i], bounds[i + 1], end(i + 1) - end(i));
+            match buckets.last_mut() {
+                Some(last) if last.0 == lower && last.1 == upper => last.2 += count,
+                _ => buckets.push((lower, upper, count)),
+            }
+        }
+        Ok(histogram_value(buckets))
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self) + self.digest.size()
+    }
+}
+
+/// The type of the results of [`HistogramNumericFunction`], a list of `{x, y}` structs
+fn numeric_histogram_type(x_type: &DataType) -> DataType {
+    DataType::new_list(DataType::Struct(numeric_bin_fields(x_type)), true)
+}
+
+fn numeric_bin_fields(x_type: &DataType) -> Fields {
+    Fields::from(vec![
+        Field::new("x", x_type.clone(), true),
+        Field::new("y", DataType::Float64, true),
+    ])
+}
+
+/// The `HistogramNumericFunction` approximates the distribution of the values with a streaming
+/// histogram, `histogram_numeric(x, nb)`, as Spark's function of the same name.
+///
+/// - The result is a list of at most `nb` structs `{x, y}` ordered by `x`, the center of a bin
+///   and the number of values in it. `x` has the type of the input as in Spark 3.3 and later, so
+///   the centers of integers are truncated, and `y` is a `Float64`.
+/// - Bins are merged with the algorithm of [`NumericHistogram`], so results match Spark's except
+///   when the two closest bins tie, where Spark picks one at random.
+/// - `nb` is a literal of at least 2. NULL and NaN values are ignored, and the result is NULL if
+///   there are no other values.
+pub struct HistogramNumericFunction {
+    signature: Signature,
+}
+
+impl Debug for HistogramNumericFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HistogramNumericFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for HistogramNumericFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HistogramNumericFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+        }
+    }
+}
+
+impl AggregateUDFImpl for HistogramNumericFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "histogram_numeric"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        let [x_type, nb_type] = arg_types else {
+            return plan_err!("histogram_numeric expects 2 arguments, got {}", arg_types.len());
+        };
+        let x_type = match x_type {
+            DataType::Dictionary(_, value_type) => value_type.as_ref(),
+            x_type => x_type,
+        };
+        let x_type = match x_type {
+            DataType::Null | DataType::Float16 => DataType::Float64,
+            x_type if x_type.is_numeric() => x_type.clone(),
+            x_type => return plan_err!("histogram_numeric expects a numeric x, got {x_type}"),
+        };
+        if !nb_type.is_integer() {
+            return plan_err!("histogram_numeric expects an integer nb, got {nb_type}");
+        }
+        Ok(vec![x_type, DataType::Int64])
+    }
+
+    fn return_type(&self, arg_types: &[DataType]) -> Result<DataType> {
+        Ok(numeric_histogram_type(&arg_types[0]))
+    }
+
+    fn state_fields(&self, _args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(vec![
+            Field::new_list("x", Field::new("item", DataType::Float64, true), true),
+            Field::new_list("y", Field::new("item", DataType::Float64, true), true),
+        ])
+    }
+
+    fn accumulator(&self, acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        let num_bins = match literal_arg(&acc_args.exprs[1], self.name(), "nb")? {
+            ScalarValue::Int64(Some(n)) if (2..=MAX_BUCKETS as i64).contains(&n) => n as usize,
+            n => return plan_err!("histogram_numeric expects nb between 2 and {MAX_BUCKETS}, got {n}"),
+        };
+        Ok(Box::new(HistogramNumericAccumulator::new(
+            num_bins,
+            acc_args.return_type.clone(),
+        )))
+    }
+}
+
+/// Accumulator for [`HistogramNumericFunction`]
+#[derive(Debug)]
+pub struct HistogramNumericAccumulator {
+    histogram: NumericHistogram,
+    return_type: DataType,
+}
+
+impl HistogramNumericAccumulator {
+    pub fn new(num_bins: usize, return_type: DataType) -> Self {
+        Self {
+            histogram: NumericHistogram::new(num_bins),
+            return_type,
+        }
+    }
+
+    fn x_type(&self) -> Result<DataType> {
+        match &self.return_type {
+            DataType::List(field) => match field.data_type() {
+                DataType::Struct(fields) => Ok(fields[0].data_type().clone()),
+                _ => exec_err!("histogram_numeric returns a list of structs"),
+            },
+            _ => exec_err!("histogram_numeric returns a list of structs"),
+        }
+    }
+}
+
+impl Accumulator for HistogramNumericAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        if all_null_or_filtered(&values[0], None) {
+            return Ok(());
+        }
+        let values = cast(&values[0], &DataType::Float64)?;
+        for value in as_float64_array(&values)?.iter().flatten() {
+            if !value.is_nan() {
+                self.histogram.add(value);
+            }
+        }
+        Ok(())
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        let x_lists = as_list_array(&states[0])?;
+        let y_lists = as_list_array(&states[1])?;
+        for (x, y) in x_lists.iter().zip(y_lists.iter()) {
+            let (Some(x), Some(y)) = (x, y) else {
+                continue;
+            };
+            let (x, y) = (as_float64_array(&x)?, as_float64_array(&y)?);
+            self.histogram
+                .merge(x.values().iter().zip(y.values()).map(|(&x, &y)| Bin { x, y }));
+        }
+        Ok(())
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        let bins = self.histogram.bins();
+        let x = Float64Array::from_iter_values(bins.iter().map(|bin| bin.x));
+        let y = Float64Array::from_iter_values(bins.iter().map(|bin| bin.y));
+        Ok(vec![
+            ScalarValue::List(Arc::new(single_row_list(Arc::new(x)))),
+            ScalarValue::List(Arc::new(single_row_list(Arc::new(y)))),
+        ])
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        if self.histogram.is_empty() {
+            return ScalarValue::try_from(&self.return_type);
+        }
+        let x_type = self.x_type()?;
+        let bins = self.histogram.bins();
+        let x: ArrayRef = Arc::new(Float64Array::from_iter_values(bins.iter().map(|bin| bin.x)));
+        let y = Float64Array::from_iter_values(bins.iter().map(|bin| bin.y));
+        let bins = StructArray::try_new(numeric_bin_fields(&x_type), vec![cast(&x, &x_type)?, Arc::new(y)], None)?;
+        Ok(ScalarValue::List(Arc::new(single_row_list(Arc::new(bins)))))
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self) + self.histogram.size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::array::AsArray;
+    use arrow::datatypes::{Float64Type, UInt64Type};
+
+    use super::*;
+
+    fn buckets(acc: &mut dyn Accumulator) -> Result<Vec<(f64, f64, u64)>> {
+        let ScalarValue::List(list) = acc.evaluate()? else {
+            unreachable!()
+        };
+        let buckets = list.value(0);
+        let buckets = buckets.as_struct();
+        let (lower, upper) = (
+            buckets.column(0).as_primitive::<Float64Type>(),
+            buckets.column(1).as_primitive::<Float64Type>(),
+        );
+        let counts = buckets.column(2).as_primitive::<UInt64Type>();
+        Ok((0..buckets.len())
+            .map(|i| (lower.value(i), upper.value(i), counts.value(i)))
+            .collect())
+    }
+
+    #[test]
+    fn test_histogram_bounds_and_merge() -> Result<()> {
+        let values: ArrayRef = Arc::new(Float64Array::from(vec![
+            Some(0.0),
+            Some(2.5),
+            Some(5.0),
+            Some(9.9),
+            Some(10.0),
+            Some(11.0),
+            None,
+            Some(f64::NAN),
+        ]));
+        let mut fixed = HistogramAccumulator::new(2, Some((0.0, 10.0)));
+        fixed.update_batch(&[Arc::clone(&values)])?;
+        let mut other = HistogramAccumulator::new(2, Some((0.0, 10.0)));
+        other.update_batch(&[Arc::new(Float64Array::from(vec![1.0]))])?;
+        let states = other
+            .state()?
+            .iter()
+            .map(|state| state.to_array())
+            .collect::<Result<Vec<_>>>()?;
+        fixed.merge_batch(&states)?;
+        assert_eq!(buckets(&mut fixed)?, vec![(0.0, 5.0, 3), (5.0, 10.0, 3)]);
+
+        let mut data = HistogramAccumulator::new(4, None);
+        data.update_batch(&[values])?;
+        assert_eq!(
+            buckets(&mut data)?,
+            vec![(0.0, 2.75, 2), (2.75, 5.5, 1), (5.5, 8.25, 0), (8.25, 11.0, 3)]
+        );
+
+        let mut empty = HistogramAccumulator::new(4, Some((0.0, 1.0)));
+        empty.update_batch(&[Arc::new(Float64Array::from(vec![2.0]))])?;
+        assert!(empty.evaluate()?.is_null());
+        Ok(())
+    }
+
+    #[test]
+    fn test_histogram_equi_depth() -> Result<()> {
+        let values: ArrayRef = Arc::new(Float64Array::from_iter_values((0..10).map(f64::from)));
+        let mut first = HistogramEquiDepthAccumulator::new(4);
+        first.update_batch(&[values])?;
+        let mut merged = HistogramEquiDepthAccumulator::new(4);
+        merged.update_batch(&[Arc::new(Float64Array::from(vec![
+            Some(10.0),
+            None,
+            Some(f64::INFINITY),
+        ]))])?;
+        let states = first
+            .state()?
+            .iter()
+            .map(|state| state.to_array())
+            .collect::<Result<Vec<_>>>()?;
+        merged.merge_batch(&states)?;
+        let histogram = buckets(&mut merged)?;
+        assert_eq!(histogram.iter().map(|b| b.2).collect::<Vec<_>>(), vec![2, 3, 3, 3]);
+        assert_eq!((histogram[0].0, histogram[3].1), (0.0, 10.0));
+
+        // A single value is a single bucket
+        let mut same = HistogramEquiDepthAccumulator::new(3);
+        same.update_batch(&[Arc::new(Float64Array::from(vec![5.0; 7]))])?;
+        assert_eq!(buckets(&mut same)?, vec![(5.0, 5.0, 7)]);
+
+        let mut empty = HistogramEquiDepthAccumulator::new(3);
+        empty.update_batch(&[Arc::new(Float64Array::from(vec![None]))])?;
+        assert!(empty.evaluate()?.is_null());
+        Ok(())
+    }
+}
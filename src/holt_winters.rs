@@ -0,0 +1,263 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+use std::any::Any;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use arrow::array::{new_null_array, ArrayRef, Float64Array, StructArray};
+use arrow::datatypes::{DataType, Field, Fields};
+use datafusion::arrow;
+use datafusion::common::{plan_err, Result, ScalarValue};
+use datafusion::logical_expr::function::{AccumulatorArgs, StateFieldsArgs};
+use datafusion::logical_expr::{Accumulator, AggregateUDFImpl, Signature, Volatility};
+
+use crate::common::args::{literal_arg, literal_f64_arg};
+use crate::common::decay::{coerce_ts, seconds_per_unit};
+use crate::common::samples::SampleBuffer;
+use crate::compat::{arg_type, single_row_list};
+
+make_udaf_expr_and_func!(
+    HoltWintersFunction,
+    holt_winters,
+    "Smooths the values in the order of ts with Holt's double exponential smoothing, returning the level, the trend and a forecast.",
+    holt_winters_udaf
+);
+
+fn result_fields() -> Fields {
+    Fields::from(vec![
+        Field::new("level", DataType::Float64, false),
+        Field::new("trend", DataType::Float64, false),
+        Field::new("forecast", DataType::new_list(DataType::Float64, true), false),
+    ])
+}
+
+/// The `HoltWintersFunction` smooths a time series with Holt's linear trend method, also known
+/// as double exponential smoothing, `holt_winters(value, ts, alpha, beta[, horizon])`.
+///
+/// Returns a `Struct<level, trend, forecast>`:
+/// - `level`: the smoothed value at the last sample, `level = alpha * value + (1 - alpha) *
+///   (previous level + previous trend)`, starting from the first value.
+/// - `trend`: the smoothed change per sample, `trend = beta * (level - previous level) +
+///   (1 - beta) * previous trend`, starting from the change between the first two values.
+/// - `forecast`: the `horizon` values following the last sample, `level + k * trend` for `k` in
+///   `1..=horizon`, empty by default.
+///
+/// The samples are smoothed in the order of `ts`, a timestamp, a date or an integer, whatever
+/// the order of the rows, but every sample counts as one step: the series is expected to be
+/// evenly spaced, e.g. grouped by `date_bin` beforehand. `alpha` and `beta` are literal smoothing
+/// factors between 0 and 1, exclusive, as for PromQL's `holt_winters`: larger factors follow the
+/// recent values more closely. Rows with a NULL value or `ts` are ignored, and the result is NULL
+/// with fewer than two samples.
+pub struct HoltWintersFunction {
+    signature: Signature,
+}
+
+impl Debug for HoltWintersFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HoltWintersFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for HoltWintersFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HoltWintersFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+        }
+    }
+}
+
+impl AggregateUDFImpl for HoltWintersFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "holt_winters"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        let (value_type, ts_type, horizon_type) = match arg_types {
+            [value_type, ts_type, _, _] => (value_type, ts_type, None),
+            [value_type, ts_type, _, _, horizon_type] => (value_type, ts_type, Some(horizon_type)),
+            _ => {
+                return plan_err!(
+                    "holt_winters expects a value, a ts, alpha, beta and an optional horizon, got {} arguments",
+                    arg_types.len()
+                )
+            }
+        };
+        if !(value_type.is_numeric() || value_type.is_null()) {
+            return plan_err!("holt_winters expects a numeric value, got {value_type}");
+        }
+        let mut coerced = vec![
+            DataType::Float64,
+            coerce_ts(self.name(), ts_type)?,
+            DataType::Float64,
+            DataType::Float64,
+        ];
+        match horizon_type {
+            Some(horizon_type) if !horizon_type.is_integer() && !horizon_type.is_null() => {
+                return plan_err!("holt_winters expects an integer horizon, got {horizon_type}")
+            }
+            Some(_) => coerced.push(DataType::Int64),
+            None => {}
+        }
+        Ok(coerced)
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Struct(result_fields()))
+    }
+
+    fn state_fields(&self, _args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(SampleBuffer::state_fields())
+    }
+
+    fn accumulator(&self, acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        let time_scale = seconds_per_unit(&arg_type(&acc_args, 1)?).unwrap_or(1.0);
+        let alpha = literal_f64_arg(&acc_args.exprs[2], self.name(), "alpha")?;
+        let beta = literal_f64_arg(&acc_args.exprs[3], self.name(), "beta")?;
+        for (arg_name, factor) in [("alpha", alpha), ("beta", beta)] {
+            if !(factor > 0.0 && factor < 1.0) {
+                return plan_err!("holt_winters expects {arg_name} between 0 and 1, exclusive, got {factor}");
+            }
+        }
+        let horizon = match acc_args.exprs.get(4) {
+            None => 0,
+            Some(expr) => match literal_arg(expr, self.name(), "horizon")? {
+                ScalarValue::Int64(Some(horizon)) if horizon >= 0 => horizon as usize,
+                horizon => return plan_err!("holt_winters expects a non-negative horizon, got {horizon}"),
+            },
+        };
+        Ok(Box::new(HoltWintersAccumulator::new(time_scale, alpha, beta, horizon)))
+    }
+}
+
+/// Accumulator for [`HoltWintersFunction`], buffering the samples to smooth them in order
+#[derive(Debug)]
+pub struct HoltWintersAccumulator {
+    samples: SampleBuffer,
+    time_scale: f64,
+    alpha: f64,
+    beta: f64,
+    horizon: usize,
+}
+
+impl HoltWintersAccumulator {
+    pub fn new(time_scale: f64, alpha: f64, beta: f64, horizon: usize) -> Self {
+        Self {
+            samples: SampleBuffer::new(),
+            time_scale,
+            alpha,
+            beta,
+            horizon,
+        }
+    }
+}
+
+impl Accumulator for HoltWintersAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        self.samples.update_batch(&values[0], &values[1], self.time_scale)
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        self.samples.merge_batch(&states[0], &states[1], "holt_winters")
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        Ok(self.samples.state().to_vec())
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        let samples = self.samples.sorted();
+        let [(_, first), (_, second), ..] = samples else {
+            return Ok(ScalarValue::Struct(Arc::new(StructArray::from(
+                new_null_array(&DataType::Struct(result_fields()), 1).to_data(),
+            ))));
+        };
+        let (mut level, mut trend) = (*first, second - first);
+        for (_, value) in &samples[1..] {
+            let previous = level;
+            level = self.alpha * value + (1.0 - self.alpha) * (level + trend);
+            trend = self.beta * (level - previous) + (1.0 - self.beta) * trend;
+        }
+        let forecast = (1..=self.horizon).map(|k| level + k as f64 * trend);
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(Float64Array::from(vec![level])),
+            Arc::new(Float64Array::from(vec![trend])),
+            Arc::new(single_row_list(Arc::new(Float64Array::from_iter_values(forecast)))),
+        ];
+        Ok(ScalarValue::Struct(Arc::new(StructArray::try_new(
+            result_fields(),
+            columns,
+            None,
+        )?)))
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self) + self.samples.size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::array::{Array, AsArray, Int64Array};
+    use arrow::datatypes::Float64Type;
+
+    use super::*;
+
+    #[test]
+    fn test_holt_winters_follows_a_linear_trend() -> Result<()> {
+        let mut acc = HoltWintersAccumulator::new(1.0, 0.5, 0.5, 2);
+        assert!(acc.evaluate()?.is_null());
+        // out of order, a perfectly linear series is smoothed to itself
+        acc.update_batch(&[
+            Arc::new(Float64Array::from(vec![
+                Some(7.0),
+                Some(1.0),
+                None,
+                Some(3.0),
+                Some(5.0),
+            ])),
+            Arc::new(Int64Array::from(vec![4, 1, 2, 2, 3])),
+        ])?;
+        let ScalarValue::Struct(result) = acc.evaluate()? else {
+            unreachable!()
+        };
+        let column = |index: usize| result.column(index).as_primitive::<Float64Type>().value(0);
+        assert_eq!((column(0), column(1)), (7.0, 2.0));
+        let forecast = result.column(2).as_list::<i32>().value(0);
+        assert_eq!(
+            forecast.as_primitive::<Float64Type>(),
+            &Float64Array::from(vec![9.0, 11.0])
+        );
+        assert!(!result.is_null(0));
+        Ok(())
+    }
+}
@@ -0,0 +1,122 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::any::Any;
+use std::fmt::Debug;
+
+use datafusion::arrow::datatypes::DataType;
+use datafusion::common::{plan_err, Result, ScalarValue};
+use datafusion::logical_expr::{ColumnarValue, ScalarUDFImpl, Signature, Volatility};
+
+use crate::common::interner::{interned_type, StringInterner};
+
+make_udf_expr_and_func!(
+    InternFunction,
+    intern,
+    x,
+    "Returns the strings as a dictionary shared with the other batches of the session.",
+    intern_udf
+);
+
+/// The `InternFunction` returns its string argument as a dictionary of [`interned_type`] whose
+/// values are shared by all batches, see [`crate::common::interner`].
+///
+/// - The strings are unchanged, only their representation is, so `intern(x)` can replace a
+///   low-cardinality string column before a repartition, join or sort.
+/// - Functions created with [`Self::new_with_interner`] share the strings interned so far;
+///   [`Self::new`] interns into a table of its own.
+pub struct InternFunction {
+    signature: Signature,
+    interner: StringInterner,
+}
+
+impl Debug for InternFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("InternFunction")
+            .field("signature", &self.signature)
+            .field("interned", &self.interner.len())
+            .finish()
+    }
+}
+
+impl Default for InternFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InternFunction {
+    pub fn new() -> Self {
+        Self::new_with_interner(StringInterner::default())
+    }
+
+    pub fn new_with_interner(interner: StringInterner) -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+            interner,
+        }
+    }
+
+    pub fn interner(&self) -> &StringInterner {
+        &self.interner
+    }
+}
+
+impl ScalarUDFImpl for InternFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "intern"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        let [arg_type] = arg_types else {
+            return plan_err!("intern expects a single string argument");
+        };
+        let value_type = match arg_type {
+            DataType::Dictionary(_, value_type) => value_type.as_ref(),
+            arg_type => arg_type,
+        };
+        match value_type {
+            DataType::Utf8 | DataType::LargeUtf8 | DataType::Utf8View | DataType::Null => Ok(vec![DataType::Utf8]),
+            _ => plan_err!("intern expects a string argument, got {arg_type}"),
+        }
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(interned_type())
+    }
+
+    fn invoke(&self, args: &[ColumnarValue]) -> Result<ColumnarValue> {
+        match &args[0] {
+            ColumnarValue::Array(array) => Ok(ColumnarValue::Array(self.interner.intern(array)?)),
+            ColumnarValue::Scalar(scalar) => {
+                let value = scalar.cast_to(&DataType::Utf8)?;
+                Ok(ColumnarValue::Scalar(ScalarValue::Dictionary(
+                    Box::new(DataType::Int32),
+                    Box::new(value),
+                )))
+            }
+        }
+    }
+}
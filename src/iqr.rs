@@ -0,0 +1,288 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::any::Any;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use arrow::array::{ArrayRef, Float64Array};
+use datafusion::arrow;
+use datafusion::arrow::datatypes::{DataType, Field};
+use datafusion::common::cast::{as_binary_array, as_float64_array};
+use datafusion::common::{plan_err, Result, ScalarValue};
+use datafusion::logical_expr::function::{AccumulatorArgs, StateFieldsArgs};
+use datafusion::logical_expr::{Accumulator, AggregateUDFImpl, Signature, TypeSignature, Volatility};
+
+use crate::common::args::literal_arg;
+use crate::common::nulls::all_null_or_filtered;
+use crate::common::quantile_buffer::QuantileBuffer;
+use crate::compat::single_row_list;
+use crate::sketches::tdigest::TDigest;
+
+make_udaf_expr_and_func!(
+    IqrFunction,
+    iqr,
+    "Calculates the interquartile range, the difference between the third and first quartiles.",
+    iqr_udaf
+);
+
+/// How [`IqrFunction`] computes the quartiles, selected by its optional second argument.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum IqrMethod {
+    /// `'exact'`: buffer the values and interpolate the quartiles as `percentile_cont` does
+    #[default]
+    Exact,
+    /// `'approx'`: estimate the quartiles with a [`TDigest`] in constant memory
+    Approximate,
+}
+
+impl IqrMethod {
+    fn from_arg(acc_args: &AccumulatorArgs) -> Result<Self> {
+        let Some(expr) = acc_args.exprs.get(1) else {
+            return Ok(Self::default());
+        };
+        match literal_arg(expr, "iqr", "method")? {
+            ScalarValue::Utf8(Some(method)) | ScalarValue::LargeUtf8(Some(method))
+                if method.eq_ignore_ascii_case("exact") =>
+            {
+                Ok(Self::Exact)
+            }
+            ScalarValue::Utf8(Some(method)) | ScalarValue::LargeUtf8(Some(method))
+                if method.eq_ignore_ascii_case("approx") =>
+            {
+                Ok(Self::Approximate)
+            }
+            method => plan_err!("iqr expects a method of 'exact' or 'approx', got {method}"),
+        }
+    }
+}
+
+/// The `IqrFunction` calculates `Q3 - Q1`, the spread of the middle half of the values.
+///
+/// - NULL values are ignored and the result is NULL if there are no values.
+/// - `iqr(x)` and `iqr(x, 'exact')` buffer every value of a group, `iqr(x, 'approx')` uses a
+///   t-digest, see [`IqrMethod`].
+pub struct IqrFunction {
+    signature: Signature,
+}
+
+impl Debug for IqrFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("IqrFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for IqrFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl IqrFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::one_of(
+                vec![
+                    TypeSignature::Coercible(vec![DataType::Float64]),
+                    TypeSignature::Coercible(vec![DataType::Float64, DataType::Utf8]),
+                ],
+                Volatility::Immutable,
+            ),
+        }
+    }
+}
+
+impl AggregateUDFImpl for IqrFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "iqr"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Float64)
+    }
+
+    /// The method is not known when planning the state, so both methods share these fields and
+    /// leave the other method's field empty
+    fn state_fields(&self, _args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(vec![
+            Field::new_list("values", Field::new("item", DataType::Float64, true), true),
+            Field::new("digest", DataType::Binary, true),
+        ])
+    }
+
+    fn accumulator(&self, acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        Ok(match IqrMethod::from_arg(&acc_args)? {
+            IqrMethod::Exact => Box::new(IqrAccumulator::new()),
+            IqrMethod::Approximate => Box::new(ApproxIqrAccumulator::new()),
+        })
+    }
+}
+
+/// Interpolates the `q`-th quantile of the sorted `values` as `percentile_cont` does
+fn interpolated_quantile(values: &[f64], q: f64) -> f64 {
+    let position = q * (values.len() - 1) as f64;
+    let below = position.floor() as usize;
+    let above = position.ceil() as usize;
+    values[below] + (values[above] - values[below]) * (position - below as f64)
+}
+
+/// Accumulator for [`IqrMethod::Exact`], which buffers the values.
+#[derive(Debug, Default)]
+pub struct IqrAccumulator {
+    values: QuantileBuffer,
+}
+
+impl IqrAccumulator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Accumulator for IqrAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        self.values.update_batch(&values[0])
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        self.values.merge_batch(&states[0])
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        Ok(vec![self.values.state(), ScalarValue::Binary(None)])
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        let values = self.values.sorted();
+        if values.is_empty() {
+            return Ok(ScalarValue::Float64(None));
+        }
+        let q1 = interpolated_quantile(values, 0.25);
+        let q3 = interpolated_quantile(values, 0.75);
+        Ok(ScalarValue::Float64(Some(q3 - q1)))
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self) + self.values.size()
+    }
+}
+
+/// Accumulator for [`IqrMethod::Approximate`].
+#[derive(Debug, Default)]
+pub struct ApproxIqrAccumulator {
+    digest: TDigest,
+}
+
+impl ApproxIqrAccumulator {
+    pub fn new() -> Self {
+        Self {
+            digest: TDigest::default(),
+        }
+    }
+}
+
+impl Accumulator for ApproxIqrAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        if all_null_or_filtered(&values[0], None) {
+            return Ok(());
+        }
+        for value in as_float64_array(&values[0])?.iter().flatten() {
+            self.digest.add(value);
+        }
+        Ok(())
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        for bytes in as_binary_array(&states[1])?.iter().flatten() {
+            self.digest.merge(&TDigest::from_bytes(bytes)?);
+        }
+        Ok(())
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        Ok(vec![
+            ScalarValue::List(Arc::new(single_row_list(Arc::new(Float64Array::from(
+                Vec::<f64>::new(),
+            ))))),
+            ScalarValue::Binary(Some(self.digest.to_bytes())),
+        ])
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        let iqr = self
+            .digest
+            .quantile(0.75)
+            .zip(self.digest.quantile(0.25))
+            .map(|(q3, q1)| q3 - q1);
+        Ok(ScalarValue::Float64(iqr))
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self) + self.digest.size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn evaluate(acc: &mut dyn Accumulator, values: Vec<Option<f64>>) -> Result<ScalarValue> {
+        acc.update_batch(&[Arc::new(Float64Array::from(values))])?;
+        acc.evaluate()
+    }
+
+    #[test]
+    fn test_iqr_interpolates_quartiles() -> Result<()> {
+        // Q1 = 1.75 and Q3 = 3.25
+        let values = vec![Some(4.0), None, Some(1.0), Some(3.0), Some(2.0)];
+        assert_eq!(
+            evaluate(&mut IqrAccumulator::new(), values)?,
+            ScalarValue::Float64(Some(1.5))
+        );
+        assert_eq!(
+            evaluate(&mut IqrAccumulator::new(), vec![Some(7.0)])?,
+            ScalarValue::Float64(Some(0.0))
+        );
+        assert_eq!(
+            evaluate(&mut IqrAccumulator::new(), vec![None])?,
+            ScalarValue::Float64(None)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_approx_iqr_matches_exact() -> Result<()> {
+        let values: Vec<Option<f64>> = (0..10_000).map(|i| Some(((i * 7919) % 10_000) as f64)).collect();
+        let ScalarValue::Float64(Some(exact)) = evaluate(&mut IqrAccumulator::new(), values.clone())? else {
+            panic!("expected a non-null iqr");
+        };
+        let ScalarValue::Float64(Some(approx)) = evaluate(&mut ApproxIqrAccumulator::new(), values)? else {
+            panic!("expected a non-null iqr");
+        };
+        assert!((exact - approx).abs() < 20.0, "exact {exact}, approx {approx}");
+        Ok(())
+    }
+}
@@ -0,0 +1,201 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::any::Any;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use arrow::array::{ArrayRef, Float64Array, StructArray, UInt64Array};
+use arrow::datatypes::Fields;
+use datafusion::arrow;
+use datafusion::arrow::datatypes::{DataType, Field};
+use datafusion::common::{Result, ScalarValue};
+use datafusion::logical_expr::function::{AccumulatorArgs, StateFieldsArgs};
+use datafusion::logical_expr::{Accumulator, AggregateUDFImpl, Signature, Volatility};
+
+use crate::common::key_sketches::KeySketches;
+
+make_udaf_expr_and_func!(
+    ApproxJoinCardinalityFunction,
+    approx_join_cardinality,
+    "Estimates the number of distinct join keys and the share of rows held by the most frequent keys.",
+    approx_join_cardinality_udaf
+);
+
+/// Default number of most frequent keys whose share of the rows is reported
+pub const DEFAULT_TOP_K: usize = 10;
+
+fn result_fields() -> Fields {
+    Fields::from(vec![
+        Field::new("rows", DataType::UInt64, false),
+        Field::new("distinct_keys", DataType::UInt64, false),
+        Field::new("top_k_share", DataType::Float64, true),
+        Field::new("max_key_share", DataType::Float64, true),
+    ])
+}
+
+/// The `ApproxJoinCardinalityFunction` summarizes join keys in one pass, for tools estimating
+/// join sizes or detecting skew, e.g. `approx_join_cardinality(customer_id, region)`.
+///
+/// Returns a `Struct<rows, distinct_keys, top_k_share, max_key_share>`:
+/// - `rows`: the number of rows, ignoring rows with a NULL key column as they never join.
+/// - `distinct_keys`: the number of distinct keys, estimated with a HyperLogLog sketch.
+/// - `top_k_share`: the fraction of the rows holding one of the `k` most frequent keys.
+/// - `max_key_share`: the fraction of the rows holding the most frequent key.
+///
+/// The shares are estimated with a Misra-Gries summary and may be underestimated by up to 1%,
+/// they are NULL if there are no rows.
+pub struct ApproxJoinCardinalityFunction {
+    signature: Signature,
+    top_k: usize,
+}
+
+impl Debug for ApproxJoinCardinalityFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ApproxJoinCardinalityFunction")
+            .field("signature", &self.signature)
+            .field("top_k", &self.top_k)
+            .finish()
+    }
+}
+
+impl Default for ApproxJoinCardinalityFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ApproxJoinCardinalityFunction {
+    pub fn new() -> Self {
+        Self::new_with_top_k(DEFAULT_TOP_K)
+    }
+
+    /// Reports the share of the `top_k` most frequent keys
+    pub fn new_with_top_k(top_k: usize) -> Self {
+        Self {
+            signature: Signature::variadic_any(Volatility::Immutable),
+            top_k: top_k.max(1),
+        }
+    }
+}
+
+impl AggregateUDFImpl for ApproxJoinCardinalityFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "approx_join_cardinality"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Struct(result_fields()))
+    }
+
+    fn state_fields(&self, _args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(KeySketches::state_fields())
+    }
+
+    fn accumulator(&self, _acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        Ok(Box::new(ApproxJoinCardinalityAccumulator::new(self.top_k)))
+    }
+}
+
+/// Accumulator for [`ApproxJoinCardinalityFunction`].
+#[derive(Debug)]
+pub struct ApproxJoinCardinalityAccumulator {
+    sketches: KeySketches,
+    top_k: usize,
+}
+
+impl ApproxJoinCardinalityAccumulator {
+    pub fn new(top_k: usize) -> Self {
+        Self {
+            sketches: KeySketches::new(top_k),
+            top_k,
+        }
+    }
+}
+
+impl Accumulator for ApproxJoinCardinalityAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        self.sketches.update(values)
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        self.sketches.merge(states)
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        Ok(self.sketches.state())
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(UInt64Array::from(vec![self.sketches.rows()])),
+            Arc::new(UInt64Array::from(vec![self.sketches.distinct_keys()])),
+            Arc::new(Float64Array::from(vec![self.sketches.top_share(self.top_k)])),
+            Arc::new(Float64Array::from(vec![self.sketches.top_share(1)])),
+        ];
+        Ok(ScalarValue::Struct(Arc::new(StructArray::try_new(
+            result_fields(),
+            columns,
+            None,
+        )?)))
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self) + self.sketches.size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{Int64Array, StringArray};
+
+    #[test]
+    fn test_composite_keys_and_nulls() -> Result<()> {
+        let mut acc = ApproxJoinCardinalityAccumulator::new(1);
+        acc.update_batch(&[
+            Arc::new(Int64Array::from(vec![Some(1), Some(1), Some(1), Some(2), None])),
+            Arc::new(StringArray::from(vec![
+                Some("a"),
+                Some("a"),
+                Some("b"),
+                Some("a"),
+                Some("a"),
+            ])),
+        ])?;
+        let mut other = ApproxJoinCardinalityAccumulator::new(1);
+        other.merge_batch(&acc.state()?.iter().map(|s| s.to_array()).collect::<Result<Vec<_>>>()?)?;
+
+        let ScalarValue::Struct(result) = other.evaluate()? else {
+            panic!("expected a struct");
+        };
+        let column = |i: usize| ScalarValue::try_from_array(result.column(i), 0);
+        assert_eq!(column(0)?, ScalarValue::UInt64(Some(4)));
+        assert_eq!(column(1)?, ScalarValue::UInt64(Some(3)));
+        assert_eq!(column(2)?, ScalarValue::Float64(Some(0.5)));
+        assert_eq!(column(3)?, ScalarValue::Float64(Some(0.5)));
+        Ok(())
+    }
+}
@@ -18,15 +18,19 @@
 // Copired from `datafusion/functions-aggregate/src/kurtosis_pop.rs`
 // Originally authored by goldmedal
 
-use arrow::array::{Array, ArrayRef, Float64Array, UInt64Array};
+use arrow::array::ArrayRef;
 use datafusion::arrow::datatypes::{DataType, Field};
 use datafusion::common::cast::as_float64_array;
-use datafusion::common::{downcast_value, DataFusionError, Result, ScalarValue};
+use datafusion::common::{internal_err, Result, ScalarValue};
 use datafusion::logical_expr::function::{AccumulatorArgs, StateFieldsArgs};
 use datafusion::logical_expr::{Accumulator, AggregateUDFImpl, Signature, Volatility};
 use std::any::Any;
 use std::fmt::Debug;
 
+use crate::common::coercion::NumericCoercion;
+use crate::common::moments::{MomentsSketch, SlidingMomentsSketch};
+use crate::common::nulls::all_null_or_filtered;
+
 make_udaf_expr_and_func!(
     KurtosisPopFunction,
     kurtosis_pop,
@@ -56,7 +60,7 @@ impl Default for KurtosisPopFunction {
 impl KurtosisPopFunction {
     pub fn new() -> Self {
         Self {
-            signature: Signature::coercible(vec![DataType::Float64], Volatility::Immutable),
+            signature: Signature::user_defined(Volatility::Immutable),
         }
     }
 }
@@ -74,115 +78,186 @@ impl AggregateUDFImpl for KurtosisPopFunction {
         &self.signature
     }
 
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        NumericCoercion::Float64.coerce_all(self.name(), &["x"], arg_types)
+    }
+
     fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
         Ok(DataType::Float64)
     }
 
     fn state_fields(&self, _args: StateFieldsArgs) -> Result<Vec<Field>> {
-        Ok(vec![
-            Field::new("count", DataType::UInt64, true),
-            Field::new("sum", DataType::Float64, true),
-            Field::new("sum_sqr", DataType::Float64, true),
-            Field::new("sum_cub", DataType::Float64, true),
-            Field::new("sum_four", DataType::Float64, true),
-        ])
+        Ok(MomentsSketch::state_fields())
     }
 
     fn accumulator(&self, _acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
         Ok(Box::new(KurtosisPopAccumulator::new()))
     }
+
+    fn create_sliding_accumulator(&self, _args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        Ok(Box::new(SlidingKurtosisPopAccumulator::new()))
+    }
 }
 
 /// Accumulator for calculating the excess kurtosis (Fisher’s definition) without bias correction.
-/// This implementation follows the [DuckDB implementation]:
-/// <https://github.com/duckdb/duckdb/blob/main/src/core_functions/aggregate/distributive/kurtosis.cpp>
+///
+/// See [`MomentsSketch`] for how the moments are accumulated.
 #[derive(Debug, Default)]
 pub struct KurtosisPopAccumulator {
-    count: u64,
-    sum: f64,
-    sum_sqr: f64,
-    sum_cub: f64,
-    sum_four: f64,
+    moments: MomentsSketch,
 }
 
 impl KurtosisPopAccumulator {
     pub fn new() -> Self {
         Self {
-            count: 0,
-            sum: 0.0,
-            sum_sqr: 0.0,
-            sum_cub: 0.0,
-            sum_four: 0.0,
+            moments: MomentsSketch::new(),
         }
     }
 }
 
 impl Accumulator for KurtosisPopAccumulator {
     fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        if all_null_or_filtered(&values[0], None) {
+            return Ok(());
+        }
         let array = as_float64_array(&values[0])?;
         for value in array.iter().flatten() {
-            self.count += 1;
-            self.sum += value;
-            self.sum_sqr += value.powi(2);
-            self.sum_cub += value.powi(3);
-            self.sum_four += value.powi(4);
+            self.moments.update(value);
         }
         Ok(())
     }
 
     fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
-        let counts = downcast_value!(states[0], UInt64Array);
-        let sums = downcast_value!(states[1], Float64Array);
-        let sum_sqrs = downcast_value!(states[2], Float64Array);
-        let sum_cubs = downcast_value!(states[3], Float64Array);
-        let sum_fours = downcast_value!(states[4], Float64Array);
-
-        for i in 0..counts.len() {
-            let c = counts.value(i);
-            if c == 0 {
-                continue;
-            }
-            self.count += c;
-            self.sum += sums.value(i);
-            self.sum_sqr += sum_sqrs.value(i);
-            self.sum_cub += sum_cubs.value(i);
-            self.sum_four += sum_fours.value(i);
+        self.moments.merge_state(states)
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        Ok(ScalarValue::Float64(self.moments.kurtosis_pop()))
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self)
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        Ok(self.moments.state())
+    }
+}
+
+/// Accumulator for [`KurtosisPopAccumulator`] over sliding window frames, which supports
+/// retracting values that leave the frame.
+///
+/// See [`SlidingMomentsSketch`] for how precision is preserved while retracting.
+#[derive(Debug, Default)]
+pub struct SlidingKurtosisPopAccumulator {
+    moments: SlidingMomentsSketch,
+}
+
+impl SlidingKurtosisPopAccumulator {
+    pub fn new() -> Self {
+        Self {
+            moments: SlidingMomentsSketch::new(),
         }
+    }
+}
 
+impl Accumulator for SlidingKurtosisPopAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        if all_null_or_filtered(&values[0], None) {
+            return Ok(());
+        }
+        let array = as_float64_array(&values[0])?;
+        for value in array.iter().flatten() {
+            self.moments.update(value);
+        }
         Ok(())
     }
 
-    fn evaluate(&mut self) -> Result<ScalarValue> {
-        if self.count < 1 {
-            return Ok(ScalarValue::Float64(None));
+    fn retract_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        if all_null_or_filtered(&values[0], None) {
+            return Ok(());
+        }
+        let array = as_float64_array(&values[0])?;
+        for value in array.iter().flatten() {
+            self.moments.retract(value);
         }
+        Ok(())
+    }
 
-        let count_64 = 1_f64 / self.count as f64;
-        let m4 = count_64
-            * (self.sum_four - 4.0 * self.sum_cub * self.sum * count_64
-                + 6.0 * self.sum_sqr * self.sum.powi(2) * count_64.powi(2)
-                - 3.0 * self.sum.powi(4) * count_64.powi(3));
+    fn supports_retract_batch(&self) -> bool {
+        true
+    }
 
-        let m2 = (self.sum_sqr - self.sum.powi(2) * count_64) * count_64;
-        if m2 <= 0.0 {
-            return Ok(ScalarValue::Float64(None));
-        }
+    fn merge_batch(&mut self, _states: &[ArrayRef]) -> Result<()> {
+        internal_err!("kurtosis_pop does not merge states over sliding window frames")
+    }
 
-        let target = m4 / (m2.powi(2)) - 3.0;
-        Ok(ScalarValue::Float64(Some(target)))
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        Ok(ScalarValue::Float64(self.moments.moments().kurtosis_pop()))
     }
 
     fn size(&self) -> usize {
-        std::mem::size_of_val(self)
+        std::mem::size_of_val(self) + self.moments.size()
     }
 
     fn state(&mut self) -> Result<Vec<ScalarValue>> {
-        Ok(vec![
-            ScalarValue::from(self.count),
-            ScalarValue::from(self.sum),
-            ScalarValue::from(self.sum_sqr),
-            ScalarValue::from(self.sum_cub),
-            ScalarValue::from(self.sum_four),
-        ])
+        Ok(self.moments.moments().state())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::Float64Array;
+    use std::sync::Arc;
+
+    fn kurtosis_of(values: Vec<f64>) -> Result<ScalarValue> {
+        let mut acc = KurtosisPopAccumulator::new();
+        acc.update_batch(&[Arc::new(Float64Array::from(values))])?;
+        acc.evaluate()
+    }
+
+    fn assert_close(actual: ScalarValue, expected: f64) {
+        let ScalarValue::Float64(Some(actual)) = actual else {
+            panic!("expected a non-null Float64, got {actual:?}");
+        };
+        assert!((actual - expected).abs() < 1e-9, "expected {expected}, got {actual}");
+    }
+
+    #[test]
+    fn test_kurtosis_pop_large_mean_small_variance() -> Result<()> {
+        // Excess kurtosis of [1, 2, 3, 4] is exactly -1.36 and is shift invariant
+        assert_close(kurtosis_of(vec![1.0, 2.0, 3.0, 4.0])?, -1.36);
+        assert_close(kurtosis_of(vec![1e9 + 1.0, 1e9 + 2.0, 1e9 + 3.0, 1e9 + 4.0])?, -1.36);
+        Ok(())
+    }
+
+    #[test]
+    fn test_kurtosis_pop_merge_matches_single_pass() -> Result<()> {
+        let values: Vec<f64> = (0..100).map(|i| 1e8 + ((i * 37) % 11) as f64 * 0.5).collect();
+        let expected = kurtosis_of(values.clone())?;
+        let ScalarValue::Float64(Some(expected)) = expected else {
+            panic!("expected a non-null kurtosis");
+        };
+
+        let mut merged = KurtosisPopAccumulator::new();
+        for chunk in values.chunks(7) {
+            let mut partial = KurtosisPopAccumulator::new();
+            partial.update_batch(&[Arc::new(Float64Array::from(chunk.to_vec()))])?;
+            let state = partial
+                .state()?
+                .iter()
+                .map(|s| s.to_array())
+                .collect::<Result<Vec<_>>>()?;
+            merged.merge_batch(&state)?;
+        }
+        assert_close(merged.evaluate()?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_kurtosis_pop_constant_values() -> Result<()> {
+        assert_eq!(kurtosis_of(vec![1e12; 5])?, ScalarValue::Float64(None));
+        Ok(())
     }
 }
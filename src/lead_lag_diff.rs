@@ -0,0 +1,236 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::any::Any;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use arrow::array::{new_null_array, Array, ArrayRef, Float64Array};
+use arrow::compute::kernels::numeric::sub;
+use arrow::compute::{cast, concat};
+use arrow::datatypes::{DataType, TimeUnit};
+use datafusion::arrow;
+use datafusion::common::cast::as_float64_array;
+use datafusion::common::{plan_err, Result};
+use datafusion::logical_expr::{PartitionEvaluator, Signature, Volatility, WindowUDFImpl};
+
+make_udwf_expr_and_func!(
+    LeadLagDiffFunction,
+    lead_lag_diff,
+    expression,
+    "Computes the difference between the value and the value of the previous row, `expression - lag(expression)`.",
+    lead_lag_diff_udwf
+);
+
+make_udwf_expr_and_func!(
+    LeadLagRatioFunction,
+    lead_lag_ratio,
+    expression,
+    "Computes the ratio of the value to the value of the previous row, `expression / lag(expression)`.",
+    lead_lag_ratio_udwf
+);
+
+/// The `LeadLagDiffFunction` computes `expression - lag(expression)` over the rows of a window
+/// partition in one call, `lead_lag_diff(expression) OVER (ORDER BY ...)`.
+///
+/// - Integers are subtracted as `Int64` and floats as `Float64`, decimals gain a digit of
+///   precision as with `-`. The subtraction fails on overflow.
+/// - Timestamps and dates return durations: `Timestamp(unit)` returns `Duration(unit)`, `Date32`
+///   returns `Duration(Second)` and `Date64` returns `Duration(Millisecond)`.
+/// - The first row of a partition, and rows where either value is NULL, are NULL. Like `lag`,
+///   the window frame is ignored.
+pub struct LeadLagDiffFunction {
+    signature: Signature,
+}
+
+impl Debug for LeadLagDiffFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LeadLagDiffFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for LeadLagDiffFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LeadLagDiffFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+        }
+    }
+}
+
+impl WindowUDFImpl for LeadLagDiffFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "lead_lag_diff"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        let arg_type = single_arg(self.name(), arg_types)?;
+        let arg_type = match arg_type {
+            DataType::Null => DataType::Float64,
+            arg_type if arg_type.is_integer() => DataType::Int64,
+            arg_type if arg_type.is_floating() => DataType::Float64,
+            DataType::Decimal128(_, _)
+            | DataType::Decimal256(_, _)
+            | DataType::Timestamp(_, _)
+            | DataType::Date32
+            | DataType::Date64
+            | DataType::Duration(_) => arg_type.clone(),
+            arg_type => return plan_err!("lead_lag_diff expects a numeric or temporal argument, got {arg_type}"),
+        };
+        Ok(vec![arg_type])
+    }
+
+    fn return_type(&self, arg_types: &[DataType]) -> Result<DataType> {
+        Ok(match &arg_types[0] {
+            DataType::Decimal128(precision, scale) => DataType::Decimal128((*precision + 1).min(38), *scale),
+            DataType::Decimal256(precision, scale) => DataType::Decimal256((*precision + 1).min(76), *scale),
+            DataType::Timestamp(unit, _) => DataType::Duration(*unit),
+            DataType::Date32 => DataType::Duration(TimeUnit::Second),
+            DataType::Date64 => DataType::Duration(TimeUnit::Millisecond),
+            arg_type => arg_type.clone(),
+        })
+    }
+
+    fn partition_evaluator(&self) -> Result<Box<dyn PartitionEvaluator>> {
+        Ok(Box::new(LeadLagDiffEvaluator))
+    }
+}
+
+/// Evaluator for [`LeadLagDiffFunction`]
+#[derive(Debug)]
+pub struct LeadLagDiffEvaluator;
+
+impl PartitionEvaluator for LeadLagDiffEvaluator {
+    fn evaluate_all(&mut self, values: &[ArrayRef], _num_rows: usize) -> Result<ArrayRef> {
+        let values = &values[0];
+        Ok(sub(values, &previous(values)?)?)
+    }
+}
+
+/// The `LeadLagRatioFunction` computes `expression / lag(expression)` over the rows of a window
+/// partition in one call, `lead_lag_ratio(expression) OVER (ORDER BY ...)`.
+///
+/// - The values are divided as `Float64`.
+/// - The first row of a partition, rows where either value is NULL and rows following a zero
+///   are NULL. Like `lag`, the window frame is ignored.
+pub struct LeadLagRatioFunction {
+    signature: Signature,
+}
+
+impl Debug for LeadLagRatioFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LeadLagRatioFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for LeadLagRatioFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LeadLagRatioFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+        }
+    }
+}
+
+impl WindowUDFImpl for LeadLagRatioFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "lead_lag_ratio"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        match single_arg(self.name(), arg_types)? {
+            DataType::Null => Ok(vec![DataType::Float64]),
+            arg_type if arg_type.is_numeric() => Ok(vec![DataType::Float64]),
+            arg_type => plan_err!("lead_lag_ratio expects a numeric argument, got {arg_type}"),
+        }
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Float64)
+    }
+
+    fn partition_evaluator(&self) -> Result<Box<dyn PartitionEvaluator>> {
+        Ok(Box::new(LeadLagRatioEvaluator))
+    }
+}
+
+/// Evaluator for [`LeadLagRatioFunction`]
+#[derive(Debug)]
+pub struct LeadLagRatioEvaluator;
+
+impl PartitionEvaluator for LeadLagRatioEvaluator {
+    fn evaluate_all(&mut self, values: &[ArrayRef], _num_rows: usize) -> Result<ArrayRef> {
+        let values = cast(&values[0], &DataType::Float64)?;
+        let previous = previous(&values)?;
+        let ratios: Float64Array = as_float64_array(&values)?
+            .iter()
+            .zip(as_float64_array(&previous)?.iter())
+            .map(|(value, previous)| match (value, previous) {
+                (Some(value), Some(previous)) if previous != 0.0 => Some(value / previous),
+                _ => None,
+            })
+            .collect();
+        Ok(Arc::new(ratios))
+    }
+}
+
+fn single_arg<'a>(fn_name: &str, arg_types: &'a [DataType]) -> Result<&'a DataType> {
+    match arg_types {
+        [DataType::Dictionary(_, value_type)] => Ok(value_type.as_ref()),
+        [arg_type] => Ok(arg_type),
+        _ => plan_err!("{fn_name} expects 1 argument, got {}", arg_types.len()),
+    }
+}
+
+/// The values shifted down by a row, starting with NULL
+fn previous(values: &ArrayRef) -> Result<ArrayRef> {
+    if values.is_empty() {
+        return Ok(Arc::clone(values));
+    }
+    let first = new_null_array(values.data_type(), 1);
+    Ok(concat(&[first.as_ref(), values.slice(0, values.len() - 1).as_ref()])?)
+}
@@ -15,47 +15,382 @@
 // specific language governing permissions and limitations
 // under the License.
 
-use log::debug;
 use mode::mode_udaf;
+use package::{FunctionPackage, RegistrationOptions};
 use std::sync::Arc;
 
 use datafusion::common::Result;
+use datafusion::execution::context::SessionContext;
 use datafusion::execution::FunctionRegistry;
-use datafusion::logical_expr::AggregateUDF;
+use datafusion::logical_expr::{AggregateUDF, ScalarUDF, WindowUDF};
 
 #[macro_use]
 pub mod macros;
+pub mod any_value;
+pub mod approx_count_distinct_hll;
+pub mod approx_percentile;
+pub mod array_agg_ext;
+pub mod array_intersect_agg;
+pub mod autocorrelation;
+pub mod bitmap;
+pub mod bitwise_agg;
+pub mod bloom_filter;
+pub mod bucket_percentiles;
+pub mod business_days;
+pub mod checksum;
+pub mod circular;
+pub mod collect_set;
 pub mod common;
+pub mod compat;
+pub mod concordance;
+pub mod conditional;
+pub mod config;
+pub mod count_distinct_if;
+pub mod count_min;
+pub mod counter;
+pub mod cumulative_distinct_count;
+pub mod decayed;
+pub mod dispersion;
+pub mod entropy;
+pub mod fingerprint;
+pub mod fiscal;
+pub mod fold_assign;
+pub mod freq_map;
+pub mod gap_fill;
+pub mod generate_dates_between;
+pub mod gini;
+pub mod harmonic_mean;
+pub mod histogram;
+pub mod holt_winters;
+pub mod intern;
+pub mod iqr;
+pub mod join_cardinality;
 pub mod kurtosis_pop;
+pub mod lead_lag_diff;
+pub mod list_flatten_agg;
+pub mod list_index;
+pub mod listagg;
+pub mod log_sum_exp;
+pub mod lttb;
+pub mod map_agg;
+pub mod map_union_agg;
 pub mod max_min_by;
+pub mod minhash;
 pub mod mode;
+pub mod null_safe;
+pub mod package;
+pub mod percentile_by_weight;
+pub mod pivot_agg;
+pub mod product;
+pub mod quantile_exact;
+pub mod range;
+pub mod reservoir_sample;
+pub mod rms;
+pub mod sequence_match;
+pub mod session_id;
+pub mod sketches;
+pub mod skew_detect;
+pub mod string_agg_ext;
+pub mod struct_agg;
+pub mod theta;
+pub mod top_k_window;
+pub mod top_n_by;
+pub mod trimmed_mean;
+pub mod unpivot;
+pub mod value_counts;
+pub mod weighted_avg;
+pub mod welch_t_test;
+pub mod window_funnel;
 pub mod expr_extra_fn {
+    pub use super::any_value::any_value;
+    pub use super::approx_count_distinct_hll::approx_count_distinct_hll;
+    pub use super::approx_count_distinct_hll::approx_count_distinct_hll_sketch;
+    pub use super::approx_percentile::approx_percentile;
+    pub use super::approx_percentile::approx_quantiles;
+    pub use super::approx_percentile::tdigest_agg;
+    pub use super::array_agg_ext::array_agg_ext;
+    pub use super::array_intersect_agg::array_intersect_agg;
+    pub use super::autocorrelation::autocorrelation;
+    pub use super::bitmap::bitmap_agg;
+    pub use super::bitmap::rb_and;
+    pub use super::bitmap::rb_cardinality;
+    pub use super::bitmap::rb_contains;
+    pub use super::bitmap::rb_or;
+    pub use super::bitwise_agg::bit_and_agg;
+    pub use super::bitwise_agg::bit_or_agg;
+    pub use super::bitwise_agg::bit_xor_agg;
+    pub use super::bloom_filter::bloom_contains;
+    pub use super::bloom_filter::bloom_filter_agg;
+    pub use super::bucket_percentiles::bucket_percentiles;
+    pub use super::business_days::business_days_between;
+    pub use super::business_days::holiday;
+    pub use super::checksum::checksum_agg;
+    pub use super::checksum::hash_agg;
+    pub use super::circular::circular_mean;
+    pub use super::circular::circular_stddev;
+    pub use super::collect_set::array_union_agg;
+    pub use super::collect_set::collect_set;
+    pub use super::concordance::concordance;
+    pub use super::concordance::discordance;
+    pub use super::conditional::avg_if;
+    pub use super::conditional::count_if;
+    pub use super::conditional::sum_if;
+    pub use super::count_distinct_if::count_distinct_if;
+    pub use super::count_min::cm_estimate;
+    pub use super::count_min::count_min_agg;
+    pub use super::counter::counter_rate;
+    pub use super::counter::delta_sum;
+    pub use super::counter::increase;
+    pub use super::counter::rate;
+    pub use super::cumulative_distinct_count::cumulative_distinct_count;
+    pub use super::decayed::decayed_avg;
+    pub use super::decayed::decayed_count;
+    pub use super::decayed::decayed_sum;
+    pub use super::dispersion::cv;
+    pub use super::dispersion::stderr;
+    pub use super::entropy::entropy;
+    pub use super::fingerprint::column_fingerprint;
+    pub use super::fingerprint::fingerprint_combine;
+    pub use super::fiscal::fiscal_quarter;
+    pub use super::fiscal::fiscal_year;
+    pub use super::fold_assign::fold_assign;
+    pub use super::freq_map::freq_map;
+    pub use super::gap_fill::interpolate_at;
+    pub use super::gap_fill::locf;
+    pub use super::gini::gini;
+    pub use super::harmonic_mean::harmonic_mean;
+    pub use super::histogram::histogram;
+    pub use super::histogram::histogram_equi_depth;
+    pub use super::histogram::histogram_numeric;
+    pub use super::holt_winters::holt_winters;
+    pub use super::intern::intern;
+    pub use super::iqr::iqr;
+    pub use super::join_cardinality::approx_join_cardinality;
     pub use super::kurtosis_pop::kurtosis_pop;
+    pub use super::lead_lag_diff::lead_lag_diff;
+    pub use super::lead_lag_diff::lead_lag_ratio;
+    pub use super::list_flatten_agg::list_flatten_agg;
+    pub use super::list_index::array_position_all;
+    pub use super::list_index::list_index_of_max;
+    pub use super::list_index::list_index_of_min;
+    pub use super::listagg::group_concat;
+    pub use super::listagg::listagg;
+    pub use super::log_sum_exp::log_sum_exp;
+    pub use super::lttb::lttb;
+    pub use super::map_agg::map_agg;
+    pub use super::map_union_agg::map_union_agg;
+    pub use super::map_union_agg::map_union_list;
+    pub use super::map_union_agg::map_union_sum;
     pub use super::max_min_by::max_by;
     pub use super::max_min_by::min_by;
+    pub use super::minhash::minhash_agg;
+    pub use super::minhash::minhash_jaccard;
+    pub use super::mode::approx_mode_by_time_decay;
     pub use super::mode::mode;
+    pub use super::mode::mode_latest;
+    pub use super::mode::rolling_mode;
+    pub use super::null_safe::cmp_null_last;
+    pub use super::null_safe::equal_null;
+    pub use super::percentile_by_weight::percentile_by_weight_disc;
+    pub use super::percentile_by_weight::percentile_weighted;
+    pub use super::pivot_agg::pivot_agg;
+    pub use super::product::product;
+    pub use super::quantile_exact::quantile_exact;
+    pub use super::range::midrange;
+    pub use super::range::range_agg;
+    pub use super::reservoir_sample::reservoir_sample;
+    pub use super::rms::rms;
+    pub use super::rms::sum_of_squares;
+    pub use super::sequence_match::sequence_count;
+    pub use super::sequence_match::sequence_match;
+    pub use super::session_id::session_id;
+    pub use super::skew_detect::skew_detect;
+    pub use super::string_agg_ext::string_agg_ext;
+    pub use super::struct_agg::struct_agg;
+    pub use super::theta::theta_estimate;
+    pub use super::theta::theta_intersect;
+    pub use super::theta::theta_sketch_agg;
+    pub use super::theta::theta_union;
+    pub use super::top_k_window::top_k_window;
+    pub use super::top_n_by::max_n;
+    pub use super::top_n_by::min_n;
+    pub use super::top_n_by::top_n_by;
+    pub use super::trimmed_mean::approx_trimmed_mean;
+    pub use super::trimmed_mean::approx_winsorized_mean;
+    pub use super::trimmed_mean::trimmed_mean;
+    pub use super::trimmed_mean::winsorized_mean;
+    pub use super::value_counts::approx_top_k;
+    pub use super::value_counts::value_counts;
+    pub use super::weighted_avg::weighted_avg;
+    pub use super::welch_t_test::welch_t_test;
+    pub use super::window_funnel::window_funnel;
 }
 
 pub fn all_extra_aggregate_functions() -> Vec<Arc<AggregateUDF>> {
     vec![
         mode_udaf(),
+        mode::mode_latest_udaf(),
+        mode::approx_mode_by_time_decay_udaf(),
+        mode::rolling_mode_udaf(),
+        map_agg::map_agg_udaf(),
+        map_union_agg::map_union_agg_udaf(),
+        map_union_agg::map_union_sum_udaf(),
+        map_union_agg::map_union_list_udaf(),
         max_min_by::max_by_udaf(),
         max_min_by::min_by_udaf(),
+        top_n_by::top_n_by_udaf(),
+        top_n_by::max_n_udaf(),
+        top_n_by::min_n_udaf(),
         kurtosis_pop::kurtosis_pop_udaf(),
+        harmonic_mean::harmonic_mean_udaf(),
+        product::product_udaf(),
+        concordance::concordance_udaf(),
+        concordance::discordance_udaf(),
+        weighted_avg::weighted_avg_udaf(),
+        trimmed_mean::trimmed_mean_udaf(),
+        trimmed_mean::winsorized_mean_udaf(),
+        iqr::iqr_udaf(),
+        dispersion::cv_udaf(),
+        dispersion::stderr_udaf(),
+        circular::circular_mean_udaf(),
+        circular::circular_stddev_udaf(),
+        entropy::entropy_udaf(),
+        gini::gini_udaf(),
+        join_cardinality::approx_join_cardinality_udaf(),
+        log_sum_exp::log_sum_exp_udaf(),
+        skew_detect::skew_detect_udaf(),
+        rms::rms_udaf(),
+        rms::sum_of_squares_udaf(),
+        fingerprint::column_fingerprint_udaf(),
+        range::range_agg_udaf(),
+        range::midrange_udaf(),
+        conditional::count_if_udaf(),
+        conditional::sum_if_udaf(),
+        conditional::avg_if_udaf(),
+        count_distinct_if::count_distinct_if_udaf(),
+        string_agg_ext::string_agg_ext_udaf(),
+        array_agg_ext::array_agg_ext_udaf(),
+        collect_set::collect_set_udaf(),
+        collect_set::array_union_agg_udaf(),
+        array_intersect_agg::array_intersect_agg_udaf(),
+        value_counts::value_counts_udaf(),
+        value_counts::approx_top_k_udaf(),
+        freq_map::freq_map_udaf(),
+        any_value::any_value_udaf(),
+        checksum::checksum_agg_udaf(),
+        checksum::hash_agg_udaf(),
+        reservoir_sample::reservoir_sample_udaf(),
+        percentile_by_weight::percentile_by_weight_disc_udaf(),
+        percentile_by_weight::percentile_weighted_udaf(),
+        list_flatten_agg::list_flatten_agg_udaf(),
+        listagg::listagg_udaf(),
+        listagg::group_concat_udaf(),
+        approx_count_distinct_hll::approx_count_distinct_hll_udaf(),
+        approx_count_distinct_hll::approx_count_distinct_hll_sketch_udaf(),
+        struct_agg::struct_agg_udaf(),
+        theta::theta_sketch_agg_udaf(),
+        count_min::count_min_agg_udaf(),
+        pivot_agg::pivot_agg_udaf(),
+        bloom_filter::bloom_filter_agg_udaf(),
+        minhash::minhash_agg_udaf(),
+        bitmap::bitmap_agg_udaf(),
+        bitwise_agg::bit_and_agg_udaf(),
+        bitwise_agg::bit_or_agg_udaf(),
+        bitwise_agg::bit_xor_agg_udaf(),
+        approx_percentile::approx_percentile_udaf(),
+        approx_percentile::approx_quantiles_udaf(),
+        approx_percentile::tdigest_agg_udaf(),
+        quantile_exact::quantile_exact_udaf(),
+        histogram::histogram_udaf(),
+        histogram::histogram_equi_depth_udaf(),
+        histogram::histogram_numeric_udaf(),
+        decayed::decayed_sum_udaf(),
+        decayed::decayed_count_udaf(),
+        decayed::decayed_avg_udaf(),
+        holt_winters::holt_winters_udaf(),
+        autocorrelation::autocorrelation_udaf(),
+        lttb::lttb_udaf(),
+        gap_fill::locf_udaf(),
+        gap_fill::interpolate_at_udaf(),
+        window_funnel::window_funnel_udaf(),
+        sequence_match::sequence_match_udaf(),
+        sequence_match::sequence_count_udaf(),
+        welch_t_test::welch_t_test_udaf(),
+        counter::delta_sum_udaf(),
+        counter::counter_rate_udaf(),
+        counter::increase_udaf(),
+        counter::rate_udaf(),
     ]
 }
 
-/// Registers all enabled packages with a [`FunctionRegistry`]
-pub fn register_all_extra_functions(registry: &mut dyn FunctionRegistry) -> Result<()> {
-    let functions: Vec<Arc<AggregateUDF>> = all_extra_aggregate_functions();
+/// Aggregate functions whose results may still change between releases, which are only
+/// registered if enabled in [`RegistrationOptions`]
+pub fn unstable_extra_aggregate_functions() -> Vec<Arc<AggregateUDF>> {
+    vec![
+        bucket_percentiles::bucket_percentiles_udaf(),
+        trimmed_mean::approx_trimmed_mean_udaf(),
+        trimmed_mean::approx_winsorized_mean_udaf(),
+    ]
+}
+
+pub fn all_extra_scalar_functions() -> Vec<Arc<ScalarUDF>> {
+    vec![
+        fold_assign::fold_assign_udf(),
+        fingerprint::fingerprint_combine_udf(),
+        theta::theta_union_udf(),
+        theta::theta_intersect_udf(),
+        theta::theta_estimate_udf(),
+        count_min::cm_estimate_udf(),
+        bloom_filter::bloom_contains_udf(),
+        intern::intern_udf(),
+        minhash::minhash_jaccard_udf(),
+        bitmap::rb_and_udf(),
+        bitmap::rb_or_udf(),
+        bitmap::rb_cardinality_udf(),
+        bitmap::rb_contains_udf(),
+        list_index::array_position_all_udf(),
+        list_index::list_index_of_max_udf(),
+        list_index::list_index_of_min_udf(),
+        business_days::holiday_udf(),
+        business_days::business_days_between_udf(),
+        fiscal::fiscal_year_udf(),
+        fiscal::fiscal_quarter_udf(),
+        null_safe::equal_null_udf(),
+        null_safe::cmp_null_last_udf(),
+    ]
+}
 
-    functions.into_iter().try_for_each(|udf| {
-        let existing_udaf = registry.register_udaf(udf)?;
-        if let Some(existing_udaf) = existing_udaf {
-            debug!("Overwrite existing UDAF: {}", existing_udaf.name());
-        }
-        Ok(()) as Result<()>
-    })?;
+pub fn all_extra_window_functions() -> Vec<Arc<WindowUDF>> {
+    vec![
+        lead_lag_diff::lead_lag_diff_udwf(),
+        lead_lag_diff::lead_lag_ratio_udwf(),
+        cumulative_distinct_count::cumulative_distinct_count_udwf(),
+        top_k_window::top_k_window_udwf(),
+        session_id::session_id_udwf(),
+    ]
+}
+
+/// Registers the table functions, which resolve the tables they read in the catalogs of `ctx`.
+///
+/// `unpivot` reads the options of [`config::ExtraFunctionsConfig`] in the configuration of `ctx`
+/// when it is called.
+pub fn register_extra_table_functions(ctx: &SessionContext) {
+    ctx.register_udtf("unpivot", Arc::new(unpivot::UnpivotFunction::new(ctx)));
+    ctx.register_udtf(
+        "generate_dates_between",
+        Arc::new(generate_dates_between::GenerateDatesBetweenFunction::new(ctx)),
+    );
+}
+
+/// Registers all stable functions with a [`FunctionRegistry`]
+pub fn register_all_extra_functions(registry: &mut dyn FunctionRegistry) -> Result<()> {
+    register_extra_functions_with_options(registry, &RegistrationOptions::default())
+}
 
-    Ok(())
+/// Registers the functions enabled by `options` with a [`FunctionRegistry`]
+pub fn register_extra_functions_with_options(
+    registry: &mut dyn FunctionRegistry,
+    options: &RegistrationOptions,
+) -> Result<()> {
+    FunctionPackage::extra_with_options(options).register_into(registry)
 }
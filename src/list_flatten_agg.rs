@@ -0,0 +1,384 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::any::Any;
+use std::collections::HashSet;
+use std::fmt::Debug;
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use arrow::array::{make_array, Array, ArrayRef, BooleanArray, GenericListArray, MutableArrayData, OffsetSizeTrait};
+use arrow::buffer::OffsetBuffer;
+use arrow::compute::{concat, filter};
+use arrow::datatypes::{DataType, Field, FieldRef};
+use arrow::row::{OwnedRow, RowConverter, SortField};
+use datafusion::arrow;
+use datafusion::common::cast::as_generic_list_array;
+use datafusion::common::{plan_err, Result, ScalarValue};
+use datafusion::logical_expr::function::{AccumulatorArgs, StateFieldsArgs};
+use datafusion::logical_expr::{Accumulator, AggregateUDFImpl, Signature, Volatility};
+
+use crate::common::args::literal_arg;
+use crate::common::output_width::OutputWidth;
+
+make_udaf_expr_and_func!(
+    ListFlattenAggFunction,
+    list_flatten_agg,
+    "Concatenates the lists of a group into a single list.",
+    list_flatten_agg_udaf
+);
+
+/// The `ListFlattenAggFunction` concatenates the non-null lists of a group into one list.
+///
+/// - `list_flatten_agg(list, distinct)` keeps only the first occurrence of every element if
+///   `distinct` is `true`, and `list_flatten_agg(list, distinct, max_length)` keeps at most
+///   the first `max_length` elements.
+/// - Lists are concatenated in the order their rows are aggregated, which isn't deterministic
+///   across partitions.
+/// - Elements are copied in bulk, one range of contiguous lists at a time.
+/// - Returns a `List`, or a `LargeList` if configured with [`OutputWidth::Large`].
+/// - The result is NULL if there are no non-null lists.
+pub struct ListFlattenAggFunction {
+    signature: Signature,
+    output_width: OutputWidth,
+}
+
+impl Debug for ListFlattenAggFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ListFlattenAggFunction")
+            .field("signature", &self.signature)
+            .field("output_width", &self.output_width)
+            .finish()
+    }
+}
+
+impl Default for ListFlattenAggFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ListFlattenAggFunction {
+    pub fn new() -> Self {
+        Self::new_with_output_width(OutputWidth::default())
+    }
+
+    pub fn new_with_output_width(output_width: OutputWidth) -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+            output_width,
+        }
+    }
+}
+
+impl AggregateUDFImpl for ListFlattenAggFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "list_flatten_agg"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        let list_type = match arg_types.first() {
+            Some(DataType::List(field) | DataType::LargeList(field) | DataType::FixedSizeList(field, _)) => {
+                self.output_width.list_type(Arc::clone(field))
+            }
+            Some(DataType::Null) => self.output_width.list_type(item_field(&DataType::Null)),
+            Some(arg_type) => return plan_err!("list_flatten_agg expects a list, got {arg_type}"),
+            None => return plan_err!("list_flatten_agg expects a list, a distinct and a max_length"),
+        };
+        match arg_types.len() {
+            1 => Ok(vec![list_type]),
+            2 => Ok(vec![list_type, DataType::Boolean]),
+            3 => Ok(vec![list_type, DataType::Boolean, DataType::Int64]),
+            _ => plan_err!("list_flatten_agg expects a list, an optional distinct and an optional max_length"),
+        }
+    }
+
+    fn return_type(&self, arg_types: &[DataType]) -> Result<DataType> {
+        Ok(arg_types[0].clone())
+    }
+
+    fn state_fields(&self, args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(vec![Field::new("values", args.input_types[0].clone(), true)])
+    }
+
+    fn accumulator(&self, acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        let distinct = match acc_args.exprs.get(1) {
+            None => false,
+            Some(expr) => match literal_arg(expr, self.name(), "distinct")? {
+                ScalarValue::Boolean(distinct) => distinct.unwrap_or(false),
+                distinct => return plan_err!("list_flatten_agg expects a boolean distinct, got {distinct}"),
+            },
+        };
+        let max_length = match acc_args.exprs.get(2) {
+            None => None,
+            Some(expr) => match literal_arg(expr, self.name(), "max_length")? {
+                ScalarValue::Int64(Some(max_length)) if max_length >= 0 => Some(max_length as usize),
+                max_length => return plan_err!("list_flatten_agg expects a non-negative max_length, got {max_length}"),
+            },
+        };
+        let item_field = match crate::compat::arg_type(&acc_args, 0)? {
+            DataType::List(field) | DataType::LargeList(field) => field,
+            arg_type => return plan_err!("list_flatten_agg expects a list, got {arg_type}"),
+        };
+        Ok(match self.output_width {
+            OutputWidth::Regular => Box::new(ListFlattenAggAccumulator::<i32>::try_new(
+                item_field, distinct, max_length,
+            )?),
+            OutputWidth::Large => Box::new(ListFlattenAggAccumulator::<i64>::try_new(
+                item_field, distinct, max_length,
+            )?),
+        })
+    }
+}
+
+fn item_field(item_type: &DataType) -> FieldRef {
+    Arc::new(Field::new("item", item_type.clone(), true))
+}
+
+/// The elements already emitted by a distinct [`ListFlattenAggAccumulator`], in the row format
+#[derive(Debug)]
+struct Seen {
+    converter: RowConverter,
+    rows: HashSet<OwnedRow>,
+}
+
+impl Seen {
+    /// Keeps the elements of `elements` not seen yet, recording them as seen
+    fn retain_unseen(&mut self, elements: ArrayRef) -> Result<ArrayRef> {
+        let rows = self.converter.convert_columns(&[Arc::clone(&elements)])?;
+        let unseen: BooleanArray = rows.iter().map(|row| Some(self.rows.insert(row.owned()))).collect();
+        if unseen.true_count() == elements.len() {
+            return Ok(elements);
+        }
+        Ok(filter(&elements, &unseen)?)
+    }
+
+    fn size(&self) -> usize {
+        self.converter.size()
+            + self
+                .rows
+                .iter()
+                .map(|row| std::mem::size_of_val(row) + row.as_ref().len())
+                .sum::<usize>()
+    }
+}
+
+/// Accumulator concatenating the lists of a group into a list with offsets `O`.
+#[derive(Debug)]
+pub struct ListFlattenAggAccumulator<O: OffsetSizeTrait> {
+    /// The elements of the lists aggregated so far, one array per batch
+    batches: Vec<ArrayRef>,
+    len: usize,
+    /// Whether a non-null list was aggregated, so that empty lists return an empty list
+    has_lists: bool,
+    item_field: FieldRef,
+    seen: Option<Seen>,
+    max_length: Option<usize>,
+    phantom: PhantomData<O>,
+}
+
+impl<O: OffsetSizeTrait> ListFlattenAggAccumulator<O> {
+    pub fn try_new(item_field: FieldRef, distinct: bool, max_length: Option<usize>) -> Result<Self> {
+        let seen = distinct
+            .then(|| {
+                let converter = RowConverter::new(vec![SortField::new(item_field.data_type().clone())])?;
+                Ok::<_, arrow::error::ArrowError>(Seen {
+                    converter,
+                    rows: HashSet::new(),
+                })
+            })
+            .transpose()?;
+        Ok(Self {
+            batches: vec![],
+            len: 0,
+            has_lists: false,
+            item_field,
+            seen,
+            max_length,
+            phantom: PhantomData,
+        })
+    }
+
+    fn width() -> OutputWidth {
+        OutputWidth::of::<O>()
+    }
+
+    /// Appends the elements of the non-null lists of `lists`
+    fn append(&mut self, lists: &ArrayRef) -> Result<()> {
+        let lists = as_generic_list_array::<O>(lists)?;
+        let offsets = lists.value_offsets();
+        // the element ranges of the non-null lists, merging adjacent ranges
+        let mut ranges: Vec<(usize, usize)> = vec![];
+        for i in 0..lists.len() {
+            if lists.is_null(i) {
+                continue;
+            }
+            self.has_lists = true;
+            let (start, end) = (offsets[i].as_usize(), offsets[i + 1].as_usize());
+            match ranges.last_mut() {
+                Some((_, last_end)) if *last_end == start => *last_end = end,
+                _ if start < end => ranges.push((start, end)),
+                _ => {}
+            }
+        }
+
+        let values = lists.values();
+        let mut elements = match ranges.as_slice() {
+            [] => return Ok(()),
+            [(start, end)] => values.slice(*start, end - start),
+            ranges => {
+                let data = values.to_data();
+                let len = ranges.iter().map(|(start, end)| end - start).sum();
+                let mut copied = MutableArrayData::new(vec![&data], false, len);
+                for (start, end) in ranges {
+                    copied.extend(0, *start, *end);
+                }
+                make_array(copied.freeze())
+            }
+        };
+        if let Some(seen) = &mut self.seen {
+            elements = seen.retain_unseen(elements)?;
+        }
+        if let Some(max_length) = self.max_length {
+            let remaining = max_length.saturating_sub(self.len);
+            if elements.len() > remaining {
+                elements = elements.slice(0, remaining);
+            }
+        }
+        if !elements.is_empty() {
+            self.len += elements.len();
+            Self::width().check_len(self.len, "list_flatten_agg")?;
+            self.batches.push(elements);
+        }
+        Ok(())
+    }
+}
+
+impl<O: OffsetSizeTrait> Accumulator for ListFlattenAggAccumulator<O> {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        self.append(&values[0])
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        self.append(&states[0])
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        Ok(vec![self.evaluate()?])
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        let list_type = Self::width().list_type(Arc::clone(&self.item_field));
+        if !self.has_lists {
+            return ScalarValue::try_from(list_type);
+        }
+        let values = match self.batches.as_slice() {
+            [] => arrow::array::new_empty_array(self.item_field.data_type()),
+            [values] => Arc::clone(values),
+            batches => {
+                let batches: Vec<&dyn Array> = batches.iter().map(|values| values.as_ref()).collect();
+                concat(&batches)?
+            }
+        };
+        let list = GenericListArray::<O>::try_new(
+            Arc::clone(&self.item_field),
+            OffsetBuffer::from_lengths([self.len]),
+            values,
+            None,
+        )?;
+        ScalarValue::try_from_array(&list, 0)
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self)
+            + self.batches.capacity() * std::mem::size_of::<ArrayRef>()
+            + self
+                .batches
+                .iter()
+                .map(|values| values.get_array_memory_size())
+                .sum::<usize>()
+            + self.seen.as_ref().map_or(0, Seen::size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::array::ListArray;
+    use arrow::datatypes::Int64Type;
+
+    use super::*;
+
+    fn lists(lists: Vec<Option<Vec<Option<i64>>>>) -> ArrayRef {
+        Arc::new(ListArray::from_iter_primitive::<Int64Type, _, _>(lists))
+    }
+
+    #[test]
+    fn test_list_flatten_agg() -> Result<()> {
+        let batch = lists(vec![
+            Some(vec![Some(1), Some(2)]),
+            None,
+            Some(vec![]),
+            Some(vec![Some(2), None]),
+            Some(vec![Some(3)]),
+        ]);
+        // skip the first list, so the remaining lists start at a non-zero offset
+        let batch = batch.slice(1, 4);
+        let field = item_field(&DataType::Int64);
+
+        let mut acc = ListFlattenAggAccumulator::<i32>::try_new(Arc::clone(&field), false, None)?;
+        acc.update_batch(&[Arc::clone(&batch)])?;
+        let expected = lists(vec![Some(vec![Some(2), None, Some(3)])]);
+        assert_eq!(acc.evaluate()?, ScalarValue::try_from_array(&expected, 0)?);
+
+        let mut acc = ListFlattenAggAccumulator::<i32>::try_new(Arc::clone(&field), true, Some(2))?;
+        acc.update_batch(&[lists(vec![Some(vec![Some(2), Some(2)])])])?;
+        acc.update_batch(&[batch])?;
+        let expected = lists(vec![Some(vec![Some(2), None])]);
+        assert_eq!(acc.evaluate()?, ScalarValue::try_from_array(&expected, 0)?);
+
+        let mut acc = ListFlattenAggAccumulator::<i32>::try_new(field, false, None)?;
+        acc.update_batch(&[lists(vec![None])])?;
+        assert!(acc.evaluate()?.is_null());
+        acc.update_batch(&[lists(vec![Some(vec![])])])?;
+        let expected = lists(vec![Some(vec![])]);
+        assert_eq!(acc.evaluate()?, ScalarValue::try_from_array(&expected, 0)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_list_flatten_agg_merge() -> Result<()> {
+        let field = item_field(&DataType::Int64);
+        let mut partial = ListFlattenAggAccumulator::<i32>::try_new(Arc::clone(&field), true, None)?;
+        partial.update_batch(&[lists(vec![Some(vec![Some(1), Some(1), Some(4)])])])?;
+        let state = partial.state()?[0].to_array()?;
+
+        let mut merged = ListFlattenAggAccumulator::<i32>::try_new(field, true, None)?;
+        merged.update_batch(&[lists(vec![Some(vec![Some(4), Some(5)])])])?;
+        merged.merge_batch(&[state])?;
+        let expected = lists(vec![Some(vec![Some(4), Some(5), Some(1)])]);
+        assert_eq!(merged.evaluate()?, ScalarValue::try_from_array(&expected, 0)?);
+        Ok(())
+    }
+}
@@ -0,0 +1,414 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::any::Any;
+use std::cmp::Ordering;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use arrow::array::{make_comparator, Array, ArrayRef, BooleanArray, ListArray, UInt32Array, UInt64Array};
+use arrow::buffer::{NullBuffer, OffsetBuffer};
+use arrow::compute::kernels::cmp::eq;
+use arrow::compute::{take, SortOptions};
+use arrow::datatypes::{DataType, Field};
+use datafusion::arrow;
+use datafusion::common::cast::as_list_array;
+use datafusion::common::{plan_err, Result, ScalarValue};
+use datafusion::logical_expr::type_coercion::binary::comparison_coercion;
+use datafusion::logical_expr::{ColumnarValue, ScalarUDFImpl, Signature, Volatility};
+
+make_udf_expr_and_func!(
+    ArrayPositionAllFunction,
+    array_position_all,
+    list value,
+    "Returns the 1-based positions of all the elements of a list equal to a value.",
+    array_position_all_udf
+);
+
+make_udf_expr_and_func!(
+    ListIndexOfMaxFunction,
+    list_index_of_max,
+    list,
+    "Returns the 1-based position of the greatest element of a list.",
+    list_index_of_max_udf
+);
+
+make_udf_expr_and_func!(
+    ListIndexOfMinFunction,
+    list_index_of_min,
+    list,
+    "Returns the 1-based position of the smallest element of a list.",
+    list_index_of_min_udf
+);
+
+/// The element type of the list type `arg_type`, `None` if it isn't a list
+fn element_type(arg_type: &DataType) -> Option<&DataType> {
+    match arg_type {
+        DataType::List(field) | DataType::LargeList(field) | DataType::FixedSizeList(field, _) => {
+            Some(field.data_type())
+        }
+        _ => None,
+    }
+}
+
+fn list_type(element_type: DataType) -> DataType {
+    DataType::new_list(element_type, true)
+}
+
+/// Evaluates `f` on the arguments as arrays, returning a scalar if they are all scalars. List
+/// scalars already hold single-row arrays, so there's no separate scalar path.
+fn invoke_on_arrays(args: &[ColumnarValue], f: impl FnOnce(&[ArrayRef]) -> Result<ArrayRef>) -> Result<ColumnarValue> {
+    let all_scalars = args.iter().all(|arg| matches!(arg, ColumnarValue::Scalar(_)));
+    let result = f(&ColumnarValue::values_to_arrays(args)?)?;
+    if all_scalars {
+        Ok(ColumnarValue::Scalar(ScalarValue::try_from_array(&result, 0)?))
+    } else {
+        Ok(ColumnarValue::Array(result))
+    }
+}
+
+/// Whether every element of `a` equals the element of `b` at the same index, NULL if either is
+fn equal(a: &dyn Array, b: &dyn Array) -> Result<BooleanArray> {
+    if !a.data_type().is_nested() {
+        return Ok(eq(&a, &b)?);
+    }
+    let cmp = make_comparator(a, b, SortOptions::default())?;
+    let values = (0..a.len()).map(|i| cmp(i, i).is_eq()).collect();
+    Ok(BooleanArray::new(values, NullBuffer::union(a.nulls(), b.nulls())))
+}
+
+/// The `ArrayPositionAllFunction` returns the 1-based positions of all the elements of a list
+/// equal to a value, `array_position_all(list, value)`.
+///
+/// - The value and the elements are compared after coercion to a common type, as with `=`.
+/// - NULL elements are never equal to the value, and the result is an empty list if no element
+///   is.
+/// - The result is NULL if the list or the value is NULL.
+pub struct ArrayPositionAllFunction {
+    signature: Signature,
+}
+
+impl Debug for ArrayPositionAllFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ArrayPositionAllFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for ArrayPositionAllFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ArrayPositionAllFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+        }
+    }
+}
+
+impl ScalarUDFImpl for ArrayPositionAllFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "array_position_all"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        let [list_arg, value_type] = arg_types else {
+            return plan_err!("array_position_all expects a list and a value");
+        };
+        let element_type = match list_arg {
+            DataType::Null => value_type,
+            list_arg => match element_type(list_arg) {
+                Some(element_type) => element_type,
+                None => return plan_err!("array_position_all expects a list, got {list_arg}"),
+            },
+        };
+        match comparison_coercion(element_type, value_type) {
+            Some(common) => Ok(vec![list_type(common.clone()), common]),
+            None => plan_err!("array_position_all can't compare {value_type} values with {element_type} elements"),
+        }
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::new_list(DataType::UInt64, false))
+    }
+
+    fn invoke(&self, args: &[ColumnarValue]) -> Result<ColumnarValue> {
+        invoke_on_arrays(args, |arrays| {
+            let list = as_list_array(&arrays[0])?;
+            let (list_values, value) = (list.values(), &arrays[1]);
+            // Compare every element with the value of its row at once
+            let offsets = list.value_offsets();
+            let first = offsets[0] as usize;
+            let elements = list_values.slice(first, offsets[list.len()] as usize - first);
+            let rows = UInt32Array::from_iter_values(
+                (0..list.len()).flat_map(|row| std::iter::repeat(row as u32).take(list.value_length(row) as usize)),
+            );
+            let matches = equal(&elements, &take(value, &rows, None)?)?;
+
+            let mut positions = vec![];
+            let mut lengths = Vec::with_capacity(list.len());
+            for row in 0..list.len() {
+                let start = offsets[row] as usize - first;
+                let end = offsets[row + 1] as usize - first;
+                let before = positions.len();
+                positions.extend(
+                    (start..end)
+                        .filter(|&i| matches.is_valid(i) && matches.value(i))
+                        .map(|i| (i - start + 1) as u64),
+                );
+                lengths.push(positions.len() - before);
+            }
+            let nulls = NullBuffer::union(list.nulls(), value.logical_nulls().as_ref());
+            Ok(Arc::new(ListArray::try_new(
+                Arc::new(Field::new_list_field(DataType::UInt64, false)),
+                OffsetBuffer::from_lengths(lengths),
+                Arc::new(UInt64Array::from(positions)),
+                nulls,
+            )?))
+        })
+    }
+}
+
+/// The 1-based position of the element of every list ordered last by `preferred`, the first one
+/// on ties, NULL for NULL lists and lists of NULLs
+fn index_of_extreme(list_arg: &ArrayRef, preferred: Ordering) -> Result<ArrayRef> {
+    let list = as_list_array(list_arg)?;
+    let values = list.values();
+    let cmp = make_comparator(values.as_ref(), values.as_ref(), SortOptions::default())?;
+    let offsets = list.value_offsets();
+    let indexes = (0..list.len())
+        .map(|row| {
+            if list.is_null(row) {
+                return None;
+            }
+            let (start, end) = (offsets[row] as usize, offsets[row + 1] as usize);
+            let mut best: Option<usize> = None;
+            for i in (start..end).filter(|&i| values.is_valid(i)) {
+                if best.map_or(true, |best| cmp(i, best) == preferred) {
+                    best = Some(i);
+                }
+            }
+            best.map(|best| (best - start + 1) as u64)
+        })
+        .collect::<UInt64Array>();
+    Ok(Arc::new(indexes))
+}
+
+/// Coerces the list argument of [`ListIndexOfMaxFunction`] and [`ListIndexOfMinFunction`] to a
+/// `List`
+fn coerce_list_arg(fn_name: &str, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+    match arg_types {
+        [DataType::Null] => Ok(vec![list_type(DataType::Null)]),
+        [list_arg] => match element_type(list_arg) {
+            Some(element_type) => Ok(vec![list_type(element_type.clone())]),
+            None => plan_err!("{fn_name} expects a list, got {list_arg}"),
+        },
+        _ => plan_err!("{fn_name} expects a single list"),
+    }
+}
+
+/// The `ListIndexOfMaxFunction` returns the 1-based position of the greatest element of a list,
+/// `list_index_of_max(list)`.
+///
+/// - The first position is returned on ties. NULL elements are ignored and NaN is greater than
+///   every other number.
+/// - The result is NULL if the list is NULL, empty, or only has NULL elements.
+pub struct ListIndexOfMaxFunction {
+    signature: Signature,
+}
+
+impl Debug for ListIndexOfMaxFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ListIndexOfMaxFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for ListIndexOfMaxFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ListIndexOfMaxFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+        }
+    }
+}
+
+impl ScalarUDFImpl for ListIndexOfMaxFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "list_index_of_max"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        coerce_list_arg(self.name(), arg_types)
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::UInt64)
+    }
+
+    fn invoke(&self, args: &[ColumnarValue]) -> Result<ColumnarValue> {
+        invoke_on_arrays(args, |arrays| index_of_extreme(&arrays[0], Ordering::Greater))
+    }
+}
+
+/// The `ListIndexOfMinFunction` returns the 1-based position of the smallest element of a list,
+/// `list_index_of_min(list)`.
+///
+/// - The first position is returned on ties. NULL elements are ignored and NaN is greater than
+///   every other number.
+/// - The result is NULL if the list is NULL, empty, or only has NULL elements.
+pub struct ListIndexOfMinFunction {
+    signature: Signature,
+}
+
+impl Debug for ListIndexOfMinFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ListIndexOfMinFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for ListIndexOfMinFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ListIndexOfMinFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+        }
+    }
+}
+
+impl ScalarUDFImpl for ListIndexOfMinFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "list_index_of_min"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        coerce_list_arg(self.name(), arg_types)
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::UInt64)
+    }
+
+    fn invoke(&self, args: &[ColumnarValue]) -> Result<ColumnarValue> {
+        invoke_on_arrays(args, |arrays| index_of_extreme(&arrays[0], Ordering::Less))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::array::{AsArray, Int32Array};
+    use arrow::compute::cast;
+    use arrow::datatypes::{Int32Type, UInt64Type};
+
+    use super::*;
+
+    fn lists(rows: Vec<Option<Vec<Option<i32>>>>) -> ArrayRef {
+        Arc::new(ListArray::from_iter_primitive::<Int32Type, _, _>(rows))
+    }
+
+    #[test]
+    fn test_array_position_all_on_sliced_lists() -> Result<()> {
+        let list = lists(vec![
+            Some(vec![Some(9)]),
+            Some(vec![Some(1), None, Some(2), Some(1)]),
+            None,
+            Some(vec![Some(3)]),
+            Some(vec![]),
+        ])
+        .slice(1, 4);
+        let values: ArrayRef = Arc::new(Int32Array::from(vec![Some(1), Some(1), None, Some(1)]));
+        let ColumnarValue::Array(positions) =
+            ArrayPositionAllFunction::new().invoke(&[ColumnarValue::Array(list), ColumnarValue::Array(values)])?
+        else {
+            unreachable!()
+        };
+        let positions = positions.as_list::<i32>();
+        let rows: Vec<_> = positions
+            .iter()
+            .map(|row| row.map(|row| row.as_primitive::<UInt64Type>().values().to_vec()))
+            .collect();
+        assert_eq!(rows, vec![Some(vec![1, 4]), None, None, Some(vec![])]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_index_of_extremes() -> Result<()> {
+        let list = lists(vec![
+            Some(vec![Some(2), None, Some(5), Some(-1), Some(5)]),
+            Some(vec![None]),
+            None,
+        ]);
+        let max = index_of_extreme(&list, Ordering::Greater)?;
+        assert_eq!(
+            max.as_primitive::<UInt64Type>(),
+            &UInt64Array::from(vec![Some(3), None, None])
+        );
+        let min = index_of_extreme(&list, Ordering::Less)?;
+        assert_eq!(
+            min.as_primitive::<UInt64Type>(),
+            &UInt64Array::from(vec![Some(4), None, None])
+        );
+
+        let floats = cast(&list, &list_type(DataType::Float64))?;
+        assert_eq!(
+            index_of_extreme(&floats, Ordering::Greater)?.as_primitive::<UInt64Type>(),
+            &UInt64Array::from(vec![Some(3), None, None])
+        );
+        Ok(())
+    }
+}
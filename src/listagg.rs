@@ -0,0 +1,441 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::any::Any;
+use std::collections::HashSet;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use arrow::array::{Array, ArrayRef};
+use arrow::compute::can_cast_types;
+use arrow::datatypes::{DataType, Field};
+use datafusion::arrow;
+use datafusion::common::cast::{as_string_array, as_struct_array};
+use datafusion::common::{exec_err, plan_err, Result, ScalarValue};
+use datafusion::logical_expr::function::{AccumulatorArgs, StateFieldsArgs};
+use datafusion::logical_expr::utils::AggregateOrderSensitivity;
+use datafusion::logical_expr::{Accumulator, AggregateUDFImpl, Signature, Volatility};
+
+use crate::common::args::literal_arg;
+use crate::common::output_width::OutputWidth;
+use crate::struct_agg::{ordered_state_fields, struct_fields, StructAggAccumulator};
+
+make_udaf_expr_and_func!(
+    GroupConcatFunction,
+    group_concat,
+    expression separator,
+    "Concatenates the non-null values cast to strings, separated by the separator, like MySQL's `GROUP_CONCAT`.",
+    group_concat_udaf
+);
+
+make_udaf_expr_and_func!(
+    ListAggFunction,
+    listagg,
+    expression delimiter,
+    "Concatenates the non-null values, separated by the delimiter.",
+    listagg_udaf
+);
+
+/// What [`ListAggFunction`] does when the result exceeds its maximum length
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ListAggOverflow {
+    /// Fails the query, as `ON OVERFLOW ERROR`
+    Error,
+    /// Keeps the values that fit followed by the delimiter and `suffix`, as
+    /// `ON OVERFLOW TRUNCATE 'suffix' WITH COUNT` or `WITHOUT COUNT`
+    Truncate { suffix: String, with_count: bool },
+}
+
+/// The `ListAggFunction` concatenates the non-null values of a group, as the SQL standard
+/// `LISTAGG`: `listagg([DISTINCT] x[, delimiter[, max_length[, suffix[, with_count]]]] [ORDER BY ...])`.
+///
+/// - `delimiter` separates the values, none by default. `DISTINCT` skips the values seen
+///   before, and `ORDER BY` orders the values, otherwise they are concatenated in the order
+///   they are aggregated in, which depends on the partitioning of the input.
+/// - `max_length` limits the length of the result in bytes, failing the query by default as
+///   `ON OVERFLOW ERROR`. With `suffix`, the result is truncated as
+///   `ON OVERFLOW TRUNCATE 'suffix'`: the values that fit are followed by the delimiter and
+///   `suffix`, and the number of values left out in parentheses if `with_count` is true, e.g.
+///   `a,b,...(3)`, or only the suffix if no value fits. The `ON OVERFLOW` clause itself isn't
+///   supported by the SQL planner.
+/// - The values are sorted by the accumulator like for `struct_agg`, so `listagg` with
+///   different orderings can run in the same aggregate.
+/// - Returns `Utf8`, or `LargeUtf8` if configured with [`OutputWidth::Large`].
+/// - The result is NULL if there are no non-null values.
+pub struct ListAggFunction {
+    signature: Signature,
+    output_width: OutputWidth,
+}
+
+impl Debug for ListAggFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ListAggFunction")
+            .field("signature", &self.signature)
+            .field("output_width", &self.output_width)
+            .finish()
+    }
+}
+
+impl Default for ListAggFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ListAggFunction {
+    pub fn new() -> Self {
+        Self::new_with_output_width(OutputWidth::default())
+    }
+
+    pub fn new_with_output_width(output_width: OutputWidth) -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+            output_width,
+        }
+    }
+}
+
+fn is_string(data_type: &DataType) -> bool {
+    match data_type {
+        DataType::Utf8 | DataType::LargeUtf8 | DataType::Utf8View | DataType::Null => true,
+        DataType::Dictionary(_, value_type) => is_string(value_type),
+        _ => false,
+    }
+}
+
+impl AggregateUDFImpl for ListAggFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "listagg"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        if arg_types.is_empty() || arg_types.len() > 5 {
+            return plan_err!(
+                "listagg expects 1 to 5 arguments (x[, delimiter[, max_length[, suffix[, with_count]]]]), got {}",
+                arg_types.len()
+            );
+        }
+        let names = ["x", "delimiter", "max_length", "suffix", "with_count"];
+        arg_types
+            .iter()
+            .zip(names)
+            .map(|(arg_type, name)| match name {
+                "max_length" if arg_type.is_integer() || arg_type.is_null() => Ok(DataType::Int64),
+                "max_length" => plan_err!("listagg expects an integer max_length, got {arg_type}"),
+                "with_count" if matches!(arg_type, DataType::Boolean | DataType::Null) => Ok(DataType::Boolean),
+                "with_count" => plan_err!("listagg expects a boolean with_count, got {arg_type}"),
+                _ if is_string(arg_type) => Ok(DataType::Utf8),
+                _ => plan_err!("listagg expects a string {name}, got {arg_type}"),
+            })
+            .collect()
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(self.output_width.string_type())
+    }
+
+    fn state_fields(&self, args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(ordered_state_fields(&[DataType::Utf8], &args))
+    }
+
+    fn accumulator(&self, acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        let string_arg = |index: usize, name: &str| -> Result<Option<String>> {
+            match acc_args.exprs.get(index) {
+                None => Ok(None),
+                Some(expr) => match literal_arg(expr, self.name(), name)? {
+                    ScalarValue::Utf8(value) => Ok(value),
+                    value => plan_err!("listagg expects a string {name}, got {value}"),
+                },
+            }
+        };
+        let delimiter = string_arg(1, "delimiter")?.unwrap_or_default();
+        let max_length = match acc_args.exprs.get(2) {
+            None => None,
+            Some(expr) => match literal_arg(expr, self.name(), "max_length")? {
+                ScalarValue::Int64(None) => None,
+                ScalarValue::Int64(Some(max_length)) if max_length > 0 => Some(max_length as usize),
+                max_length => return plan_err!("listagg expects a positive max_length, got {max_length}"),
+            },
+        };
+        let with_count = match acc_args.exprs.get(4) {
+            None => false,
+            Some(expr) => match literal_arg(expr, self.name(), "with_count")? {
+                ScalarValue::Boolean(with_count) => with_count.unwrap_or_default(),
+                with_count => return plan_err!("listagg expects a boolean with_count, got {with_count}"),
+            },
+        };
+        let overflow = match string_arg(3, "suffix")? {
+            None => ListAggOverflow::Error,
+            Some(suffix) => ListAggOverflow::Truncate { suffix, with_count },
+        };
+        Ok(Box::new(ListAggAccumulator::try_new(
+            &acc_args,
+            delimiter,
+            max_length,
+            overflow,
+            self.output_width,
+        )?))
+    }
+
+    /// The accumulator sorts the values itself, so a sorted input only spares it work
+    fn order_sensitivity(&self) -> AggregateOrderSensitivity {
+        AggregateOrderSensitivity::Beneficial
+    }
+
+    fn with_beneficial_ordering(
+        self: Arc<Self>,
+        _beneficial_ordering: bool,
+    ) -> Result<Option<Arc<dyn AggregateUDFImpl>>> {
+        Ok(Some(self))
+    }
+}
+
+/// The `GroupConcatFunction` concatenates the non-null values of a group as MySQL's
+/// `GROUP_CONCAT`: `group_concat([DISTINCT] x[, separator] [ORDER BY ...])`.
+///
+/// - Values of any type are cast to strings as with `CAST(x AS VARCHAR)`, and NULL values are
+///   skipped. `separator` is `,` by default, and MySQL's `SEPARATOR` clause is passed as an
+///   argument instead.
+/// - `DISTINCT` and `ORDER BY` are supported as for `listagg`. The result isn't truncated to
+///   `group_concat_max_len`, see `listagg` to limit its length.
+/// - Returns `Utf8`, or `LargeUtf8` if configured with [`OutputWidth::Large`].
+/// - The result is NULL if there are no non-null values.
+pub struct GroupConcatFunction {
+    signature: Signature,
+    output_width: OutputWidth,
+}
+
+impl Debug for GroupConcatFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GroupConcatFunction")
+            .field("signature", &self.signature)
+            .field("output_width", &self.output_width)
+            .finish()
+    }
+}
+
+impl Default for GroupConcatFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GroupConcatFunction {
+    pub fn new() -> Self {
+        Self::new_with_output_width(OutputWidth::default())
+    }
+
+    pub fn new_with_output_width(output_width: OutputWidth) -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+            output_width,
+        }
+    }
+}
+
+impl AggregateUDFImpl for GroupConcatFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "group_concat"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        let (value_type, separator_type) = match arg_types {
+            [value_type] => (value_type, None),
+            [value_type, separator_type] => (value_type, Some(separator_type)),
+            _ => {
+                return plan_err!(
+                    "group_concat expects 1 or 2 arguments (x[, separator]), got {}",
+                    arg_types.len()
+                )
+            }
+        };
+        if !can_cast_types(value_type, &DataType::Utf8) {
+            return plan_err!("group_concat can't cast values of type {value_type} to strings");
+        }
+        match separator_type {
+            None => Ok(vec![DataType::Utf8]),
+            Some(separator_type) if is_string(separator_type) => Ok(vec![DataType::Utf8, DataType::Utf8]),
+            Some(separator_type) => plan_err!("group_concat expects a string separator, got {separator_type}"),
+        }
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(self.output_width.string_type())
+    }
+
+    fn state_fields(&self, args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(ordered_state_fields(&[DataType::Utf8], &args))
+    }
+
+    fn accumulator(&self, acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        let separator = match acc_args.exprs.get(1) {
+            None => ",".to_string(),
+            Some(expr) => match literal_arg(expr, self.name(), "separator")? {
+                ScalarValue::Utf8(separator) => separator.unwrap_or_default(),
+                separator => return plan_err!("group_concat expects a string separator, got {separator}"),
+            },
+        };
+        Ok(Box::new(ListAggAccumulator::try_new(
+            &acc_args,
+            separator,
+            None,
+            ListAggOverflow::Error,
+            self.output_width,
+        )?))
+    }
+
+    /// The accumulator sorts the values itself, so a sorted input only spares it work
+    fn order_sensitivity(&self) -> AggregateOrderSensitivity {
+        AggregateOrderSensitivity::Beneficial
+    }
+
+    fn with_beneficial_ordering(
+        self: Arc<Self>,
+        _beneficial_ordering: bool,
+    ) -> Result<Option<Arc<dyn AggregateUDFImpl>>> {
+        Ok(Some(self))
+    }
+}
+
+/// Accumulator for [`ListAggFunction`] and [`GroupConcatFunction`], collecting the values with their ordering values as
+/// [`StructAggAccumulator`] and concatenating them when evaluated
+#[derive(Debug)]
+pub struct ListAggAccumulator {
+    values: StructAggAccumulator,
+    /// The number of arguments, followed by the ordering values in the batches
+    args: usize,
+    distinct: bool,
+    delimiter: String,
+    max_length: Option<usize>,
+    overflow: ListAggOverflow,
+    output_width: OutputWidth,
+}
+
+impl ListAggAccumulator {
+    /// Creates an accumulator of the first argument of `acc_args`, ordered by its `ORDER BY`
+    fn try_new(
+        acc_args: &AccumulatorArgs,
+        delimiter: String,
+        max_length: Option<usize>,
+        overflow: ListAggOverflow,
+        output_width: OutputWidth,
+    ) -> Result<Self> {
+        Ok(Self {
+            values: StructAggAccumulator::try_new_ordered(struct_fields("c", &[DataType::Utf8]), acc_args)?,
+            args: acc_args.exprs.len(),
+            distinct: acc_args.is_distinct,
+            delimiter,
+            max_length,
+            overflow,
+            output_width,
+        })
+    }
+
+    /// Concatenates `values`, truncated or failing if longer than the maximum length
+    fn concat(&self, values: &[&str]) -> Result<String> {
+        let delimiter = self.delimiter.as_str();
+        let full_len =
+            values.iter().map(|value| value.len()).sum::<usize>() + delimiter.len() * values.len().saturating_sub(1);
+        let max_length = match self.max_length {
+            Some(max_length) if full_len > max_length => max_length,
+            _ => return Ok(values.join(delimiter)),
+        };
+        let (suffix, with_count) = match &self.overflow {
+            ListAggOverflow::Error => {
+                return exec_err!("listagg result of {full_len} bytes exceeds the maximum length of {max_length}")
+            }
+            ListAggOverflow::Truncate { suffix, with_count } => (suffix, *with_count),
+        };
+        let indicator = |kept: usize| {
+            let count = match with_count {
+                true => format!("({})", values.len() - kept),
+                false => String::new(),
+            };
+            match kept {
+                0 => format!("{suffix}{count}"),
+                _ => format!("{delimiter}{suffix}{count}"),
+            }
+        };
+        // The most values that fit with the indicator, if any
+        let (mut kept, mut len) = (0, 0);
+        for (index, value) in values.iter().enumerate() {
+            let value_len = len + if index > 0 { delimiter.len() } else { 0 } + value.len();
+            if value_len + indicator(index + 1).len() > max_length {
+                break;
+            }
+            (kept, len) = (index + 1, value_len);
+        }
+        Ok(values[..kept].join(delimiter) + &indicator(kept))
+    }
+}
+
+impl Accumulator for ListAggAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        let mut columns = vec![Arc::clone(&values[0])];
+        columns.extend(values[self.args..].iter().cloned());
+        self.values.update_batch(&columns)
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        self.values.merge_batch(states)
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        self.values.state()
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        let ScalarValue::List(rows) = self.values.evaluate()? else {
+            unreachable!("struct_agg returns a list");
+        };
+        if rows.is_null(0) {
+            return self.output_width.string_scalar(None, "listagg");
+        }
+        let rows = rows.value(0);
+        let strings = as_string_array(as_struct_array(&rows)?.column(0))?;
+        let mut seen = HashSet::new();
+        let values = strings
+            .iter()
+            .flatten()
+            .filter(|value| !self.distinct || seen.insert(*value))
+            .collect::<Vec<_>>();
+        if values.is_empty() {
+            return self.output_width.string_scalar(None, "listagg");
+        }
+        self.output_width.string_scalar(Some(self.concat(&values)?), "listagg")
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self) - std::mem::size_of_val(&self.values) + self.values.size()
+    }
+}
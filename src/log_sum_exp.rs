@@ -0,0 +1,220 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::any::Any;
+use std::fmt::Debug;
+
+use arrow::array::{Array, ArrayRef};
+use datafusion::arrow;
+use datafusion::arrow::datatypes::{DataType, Field};
+use datafusion::common::cast::as_float64_array;
+use datafusion::common::{Result, ScalarValue};
+use datafusion::logical_expr::function::{AccumulatorArgs, StateFieldsArgs};
+use datafusion::logical_expr::{Accumulator, AggregateUDFImpl, Signature, Volatility};
+
+use crate::common::nulls::all_null_or_filtered;
+
+make_udaf_expr_and_func!(
+    LogSumExpFunction,
+    log_sum_exp,
+    x,
+    "Calculates ln(Σ exp(x)) without overflowing for large x.",
+    log_sum_exp_udaf
+);
+
+/// The `LogSumExpFunction` calculates `ln(Σ exp(x))`, e.g. to combine log-probabilities.
+///
+/// The sum is kept as `exp(max) * Σ exp(x - max)`, rescaled when a larger value arrives, so it
+/// neither overflows for large values nor underflows to `-inf` for very negative ones.
+///
+/// - NULL values are ignored and the result is NULL if there are no values.
+/// - `-inf` values contribute nothing, the result is `+inf` if any value is `+inf` and NaN if any
+///   value is NaN.
+pub struct LogSumExpFunction {
+    signature: Signature,
+    aliases: Vec<String>,
+}
+
+impl Debug for LogSumExpFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LogSumExpFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for LogSumExpFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LogSumExpFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::coercible(vec![DataType::Float64], Volatility::Immutable),
+            aliases: vec!["logsumexp".to_string()],
+        }
+    }
+}
+
+impl AggregateUDFImpl for LogSumExpFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "log_sum_exp"
+    }
+
+    fn aliases(&self) -> &[String] {
+        &self.aliases
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Float64)
+    }
+
+    fn state_fields(&self, _args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(vec![
+            Field::new("max", DataType::Float64, true),
+            Field::new("scaled_sum", DataType::Float64, true),
+        ])
+    }
+
+    fn accumulator(&self, _acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        Ok(Box::new(LogSumExpAccumulator::new()))
+    }
+}
+
+/// Accumulator for [`LogSumExpFunction`], tracking the largest value and `Σ exp(x - max)`.
+#[derive(Debug)]
+pub struct LogSumExpAccumulator {
+    max: f64,
+    scaled_sum: f64,
+}
+
+impl Default for LogSumExpAccumulator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LogSumExpAccumulator {
+    pub fn new() -> Self {
+        Self {
+            max: f64::NEG_INFINITY,
+            scaled_sum: 0.0,
+        }
+    }
+
+    /// Adds `exp(max) * scaled_sum`, comparing with `==` first so that equal infinities don't
+    /// produce NaN from `inf - inf`
+    fn add(&mut self, max: f64, scaled_sum: f64) {
+        if max == self.max {
+            self.scaled_sum += scaled_sum;
+        } else if max > self.max {
+            self.scaled_sum = self.scaled_sum * (self.max - max).exp() + scaled_sum;
+            self.max = max;
+        } else {
+            self.scaled_sum += scaled_sum * (max - self.max).exp();
+        }
+    }
+}
+
+impl Accumulator for LogSumExpAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        if all_null_or_filtered(&values[0], None) {
+            return Ok(());
+        }
+        for value in as_float64_array(&values[0])?.iter().flatten() {
+            self.add(value, 1.0);
+        }
+        Ok(())
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        let max = as_float64_array(&states[0])?;
+        let scaled_sum = as_float64_array(&states[1])?;
+        for i in 0..max.len() {
+            if max.is_valid(i) && scaled_sum.is_valid(i) && scaled_sum.value(i) != 0.0 {
+                self.add(max.value(i), scaled_sum.value(i));
+            }
+        }
+        Ok(())
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        Ok(vec![ScalarValue::from(self.max), ScalarValue::from(self.scaled_sum)])
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        if self.scaled_sum == 0.0 {
+            return Ok(ScalarValue::Float64(None));
+        }
+        Ok(ScalarValue::Float64(Some(self.max + self.scaled_sum.ln())))
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::Float64Array;
+    use std::sync::Arc;
+
+    fn evaluate(values: Vec<Option<f64>>) -> Result<Option<f64>> {
+        let mut acc = LogSumExpAccumulator::new();
+        acc.update_batch(&[Arc::new(Float64Array::from(values))])?;
+        let ScalarValue::Float64(result) = acc.evaluate()? else {
+            unreachable!()
+        };
+        Ok(result)
+    }
+
+    #[test]
+    fn test_log_sum_exp_is_stable() -> Result<()> {
+        let result = evaluate(vec![Some(1000.0), None, Some(1000.0)])?.unwrap();
+        assert!((result - (1000.0 + 2f64.ln())).abs() < 1e-9, "got {result}");
+        let result = evaluate(vec![Some(-1000.0), Some(-1000.0 + 3f64.ln())])?.unwrap();
+        assert!((result - (-1000.0 + 4f64.ln())).abs() < 1e-9, "got {result}");
+        assert_eq!(evaluate(vec![None])?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_log_sum_exp_infinities() -> Result<()> {
+        assert_eq!(evaluate(vec![Some(f64::NEG_INFINITY), Some(0.0)])?, Some(0.0));
+        assert_eq!(
+            evaluate(vec![Some(f64::NEG_INFINITY), Some(f64::NEG_INFINITY)])?,
+            Some(f64::NEG_INFINITY)
+        );
+        assert_eq!(
+            evaluate(vec![Some(1.0), Some(f64::INFINITY), Some(f64::INFINITY)])?,
+            Some(f64::INFINITY)
+        );
+        assert!(evaluate(vec![Some(1.0), Some(f64::NAN)])?.unwrap().is_nan());
+        Ok(())
+    }
+}
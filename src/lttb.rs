@@ -0,0 +1,270 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+use std::any::Any;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use arrow::array::{Array, ArrayRef, AsArray, Float64Array, Int64Array, StructArray};
+use arrow::compute::cast;
+use arrow::datatypes::{DataType, Field, Fields, Int64Type};
+use datafusion::arrow;
+use datafusion::common::cast::{as_float64_array, as_list_array};
+use datafusion::common::{exec_err, plan_err, Result, ScalarValue};
+use datafusion::logical_expr::function::{AccumulatorArgs, StateFieldsArgs};
+use datafusion::logical_expr::{Accumulator, AggregateUDFImpl, Signature, Volatility};
+
+use crate::common::args::literal_arg;
+use crate::common::decay::coerce_ts;
+use crate::compat::{arg_type, single_row_list};
+
+make_udaf_expr_and_func!(
+    LttbFunction,
+    lttb,
+    ts value n_points,
+    "Downsamples a series to n_points visually representative points with the Largest-Triangle-Three-Buckets algorithm.",
+    lttb_udaf
+);
+
+fn point_fields(ts_type: &DataType) -> Fields {
+    Fields::from(vec![
+        Field::new("ts", ts_type.clone(), true),
+        Field::new("value", DataType::Float64, true),
+    ])
+}
+
+/// The `LttbFunction` downsamples the series of a group to at most `n_points` points,
+/// `lttb(ts, value, n_points)`, with the Largest-Triangle-Three-Buckets algorithm of Sveinn
+/// Steinarsson's "Downsampling Time Series for Visual Representation" (2013).
+///
+/// - Returns a `List<Struct{ts, value}>` in the order of `ts`, a timestamp, a date or an
+///   integer. The first and last points are always kept, and the other points are split into
+///   `n_points - 2` buckets, keeping the point of each bucket forming the largest triangle with
+///   the point kept before it and the average of the next bucket, which preserves the peaks and
+///   troughs a chart of the series shows.
+/// - `n_points` is a literal of at least 3. Series with no more points are returned whole.
+/// - The points are buffered until the result is emitted, as those of different partitions
+///   interleave in time.
+/// - Rows with a NULL `ts` or value are ignored. The result is NULL if every row is ignored.
+pub struct LttbFunction {
+    signature: Signature,
+}
+
+impl Debug for LttbFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LttbFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for LttbFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LttbFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+        }
+    }
+}
+
+impl AggregateUDFImpl for LttbFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "lttb"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        let [ts_type, value_type, n_points_type] = arg_types else {
+            return plan_err!(
+                "lttb expects a ts, a value and n_points, got {} arguments",
+                arg_types.len()
+            );
+        };
+        if !(value_type.is_numeric() || value_type.is_null()) {
+            return plan_err!("lttb expects a numeric value, got {value_type}");
+        }
+        if !(n_points_type.is_integer() || n_points_type.is_null()) {
+            return plan_err!("lttb expects an integer n_points, got {n_points_type}");
+        }
+        Ok(vec![
+            coerce_ts(self.name(), ts_type)?,
+            DataType::Float64,
+            DataType::Int64,
+        ])
+    }
+
+    fn return_type(&self, arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::new_list(DataType::Struct(point_fields(&arg_types[0])), true))
+    }
+
+    fn state_fields(&self, _args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(vec![
+            Field::new_list("times", Field::new("item", DataType::Int64, true), true),
+            Field::new_list("values", Field::new("item", DataType::Float64, true), true),
+        ])
+    }
+
+    fn accumulator(&self, acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        let n_points = match literal_arg(&acc_args.exprs[2], self.name(), "n_points")? {
+            ScalarValue::Int64(Some(n_points)) if n_points >= 3 => n_points as usize,
+            n_points => return plan_err!("lttb expects n_points of at least 3, got {n_points}"),
+        };
+        Ok(Box::new(LttbAccumulator::new(arg_type(&acc_args, 0)?, n_points)))
+    }
+}
+
+/// The indices of the points of `points`, sorted by time, kept by Largest-Triangle-Three-Buckets
+/// to downsample them to `n_points`, which must be at least 3
+pub fn lttb_indices(points: &[(i64, f64)], n_points: usize) -> Vec<usize> {
+    let len = points.len();
+    if len <= n_points {
+        return (0..len).collect();
+    }
+    let x = |index: usize| points[index].0 as f64;
+    let y = |index: usize| points[index].1;
+    // the points between the first and the last are split into buckets of `every` points
+    let every = (len - 2) as f64 / (n_points - 2) as f64;
+    let bucket = |bucket: usize| (bucket as f64 * every) as usize + 1..((bucket + 1) as f64 * every) as usize + 1;
+    let mut indices = Vec::with_capacity(n_points);
+    let mut kept = 0;
+    indices.push(kept);
+    for index in 0..n_points - 2 {
+        // the average point of the next bucket, or the last point after the last bucket
+        let next = bucket(index + 1);
+        let next = next.start..next.end.min(len);
+        let next = if next.is_empty() { len - 1..len } else { next };
+        let count = next.len() as f64;
+        let average = (
+            next.clone().map(x).sum::<f64>() / count,
+            next.map(y).sum::<f64>() / count,
+        );
+        let area = |candidate: usize| {
+            ((x(kept) - average.0) * (y(candidate) - y(kept)) - (x(kept) - x(candidate)) * (average.1 - y(kept))).abs()
+        };
+        kept = bucket(index)
+            .reduce(|best, candidate| if area(candidate) > area(best) { candidate } else { best })
+            .unwrap_or(kept);
+        indices.push(kept);
+    }
+    indices.push(len - 1);
+    indices
+}
+
+/// Accumulator for [`LttbFunction`], buffering the points with their times as `Int64`
+#[derive(Debug)]
+pub struct LttbAccumulator {
+    ts_type: DataType,
+    n_points: usize,
+    points: Vec<(i64, f64)>,
+}
+
+impl LttbAccumulator {
+    pub fn new(ts_type: DataType, n_points: usize) -> Self {
+        Self {
+            ts_type,
+            n_points,
+            points: vec![],
+        }
+    }
+
+    fn push_all(&mut self, times: &Int64Array, values: &Float64Array) {
+        for (time, value) in times.iter().zip(values.iter()) {
+            if let (Some(time), Some(value)) = (time, value) {
+                self.points.push((time, value));
+            }
+        }
+    }
+}
+
+impl Accumulator for LttbAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        let times = cast(&values[0], &DataType::Int64)?;
+        self.push_all(times.as_primitive::<Int64Type>(), as_float64_array(&values[1])?);
+        Ok(())
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        let (times, values) = (as_list_array(&states[0])?, as_list_array(&states[1])?);
+        for (times, values) in times.iter().zip(values.iter()) {
+            let (Some(times), Some(values)) = (times, values) else {
+                continue;
+            };
+            if times.len() != values.len() {
+                return exec_err!("lttb state has {} times but {} values", times.len(), values.len());
+            }
+            self.push_all(times.as_primitive::<Int64Type>(), as_float64_array(&values)?);
+        }
+        Ok(())
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        let (times, values): (Vec<i64>, Vec<f64>) = std::mem::take(&mut self.points).into_iter().unzip();
+        Ok(vec![
+            ScalarValue::List(Arc::new(single_row_list(Arc::new(Int64Array::from(times))))),
+            ScalarValue::List(Arc::new(single_row_list(Arc::new(Float64Array::from(values))))),
+        ])
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        let fields = point_fields(&self.ts_type);
+        if self.points.is_empty() {
+            return ScalarValue::try_from(DataType::new_list(DataType::Struct(fields), true));
+        }
+        // points of the same time are ordered by value, so the result doesn't depend on the
+        // order they were aggregated in
+        self.points
+            .sort_unstable_by(|a, b| a.0.cmp(&b.0).then(a.1.total_cmp(&b.1)));
+        let indices = lttb_indices(&self.points, self.n_points);
+        let times = Int64Array::from_iter_values(indices.iter().map(|index| self.points[*index].0));
+        let values = Float64Array::from_iter_values(indices.iter().map(|index| self.points[*index].1));
+        let points = StructArray::try_new(fields, vec![cast(&times, &self.ts_type)?, Arc::new(values)], None)?;
+        Ok(ScalarValue::List(Arc::new(single_row_list(Arc::new(points)))))
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self) + self.points.capacity() * std::mem::size_of::<(i64, f64)>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lttb_keeps_the_extremes() {
+        let points: Vec<(i64, f64)> = [0.0, 1.0, 0.0, 0.0, 9.0, 0.0, 0.0, -5.0, 0.0, 0.0]
+            .into_iter()
+            .enumerate()
+            .map(|(x, y)| (x as i64, y))
+            .collect();
+        // the 8 inner points split into buckets [1, 3), [3, 6) and [6, 9)
+        assert_eq!(lttb_indices(&points, 5), vec![0, 2, 4, 7, 9]);
+        assert_eq!(lttb_indices(&points, 10), (0..10).collect::<Vec<_>>());
+        assert_eq!(lttb_indices(&points[..2], 3), vec![0, 1]);
+    }
+}
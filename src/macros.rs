@@ -83,3 +83,74 @@ macro_rules! create_func {
         }
     }
 }
+
+/// Scalar counterpart of [`make_udaf_expr_and_func`], modeled on
+/// `make_udf_function` in `/datafusion/functions/src/macros.rs`.
+macro_rules! make_udf_expr_and_func {
+    ($UDF:ty, $EXPR_FN:ident, $($arg:ident)*, $DOC:expr, $SCALAR_UDF_FN:ident) => {
+        // "fluent expr_fn" style function
+        #[doc = $DOC]
+        pub fn $EXPR_FN(
+            $($arg: datafusion::logical_expr::Expr,)*
+        ) -> datafusion::logical_expr::Expr {
+            datafusion::logical_expr::Expr::ScalarFunction(datafusion::logical_expr::expr::ScalarFunction::new_udf(
+                $SCALAR_UDF_FN(),
+                vec![$($arg),*],
+            ))
+        }
+
+        create_udf_func!($UDF, $SCALAR_UDF_FN);
+    };
+}
+
+macro_rules! create_udf_func {
+    ($UDF:ty, $SCALAR_UDF_FN:ident) => {
+        paste::paste! {
+            /// Singleton instance of [$UDF], ensures the UDF is only created once
+            /// named STATIC_$(UDF). For example `STATIC_FoldAssignFunction`
+            #[allow(non_upper_case_globals)]
+            static [< STATIC_ $UDF >]: std::sync::OnceLock<std::sync::Arc<datafusion::logical_expr::ScalarUDF>> =
+                std::sync::OnceLock::new();
+
+            #[doc = concat!("ScalarFunction that returns a [`ScalarUDF`](datafusion_expr::ScalarUDF) for [`", stringify!($UDF), "`]")]
+            pub fn $SCALAR_UDF_FN() -> std::sync::Arc<datafusion::logical_expr::ScalarUDF> {
+                [< STATIC_ $UDF >]
+                    .get_or_init(|| {
+                        std::sync::Arc::new(datafusion::logical_expr::ScalarUDF::from(<$UDF>::default()))
+                    })
+                    .clone()
+            }
+        }
+    };
+}
+
+/// Window counterpart of [`make_udaf_expr_and_func`], the expression is called without an
+/// `OVER` clause, which is added with `ExprFunctionExt`.
+macro_rules! make_udwf_expr_and_func {
+    ($UDWF:ty, $EXPR_FN:ident, $($arg:ident)*, $DOC:expr, $WINDOW_UDF_FN:ident) => {
+        // "fluent expr_fn" style function
+        #[doc = $DOC]
+        pub fn $EXPR_FN(
+            $($arg: datafusion::logical_expr::Expr,)*
+        ) -> datafusion::logical_expr::Expr {
+            $WINDOW_UDF_FN().call(vec![$($arg),*])
+        }
+
+        paste::paste! {
+            /// Singleton instance of [$UDWF], ensures the UDWF is only created once
+            /// named STATIC_$(UDWF). For example `STATIC_LeadLagDiffFunction`
+            #[allow(non_upper_case_globals)]
+            static [< STATIC_ $UDWF >]: std::sync::OnceLock<std::sync::Arc<datafusion::logical_expr::WindowUDF>> =
+                std::sync::OnceLock::new();
+
+            #[doc = concat!("WindowFunction that returns a [`WindowUDF`](datafusion_expr::WindowUDF) for [`", stringify!($UDWF), "`]")]
+            pub fn $WINDOW_UDF_FN() -> std::sync::Arc<datafusion::logical_expr::WindowUDF> {
+                [< STATIC_ $UDWF >]
+                    .get_or_init(|| {
+                        std::sync::Arc::new(datafusion::logical_expr::WindowUDF::from(<$UDWF>::default()))
+                    })
+                    .clone()
+            }
+        }
+    };
+}
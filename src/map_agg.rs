@@ -0,0 +1,380 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::any::Any;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use arrow::array::{new_empty_array, Array, ArrayRef, MapArray, StructArray, UInt32Array};
+use arrow::buffer::{NullBuffer, OffsetBuffer};
+use arrow::compute::{interleave, take};
+use arrow::datatypes::{DataType, Field, Fields};
+use arrow::row::{RowConverter, SortField};
+use datafusion::arrow;
+use datafusion::common::cast::as_list_array;
+use datafusion::common::{exec_err, plan_err, Result, ScalarValue};
+use datafusion::logical_expr::function::{AccumulatorArgs, StateFieldsArgs};
+use datafusion::logical_expr::{Accumulator, AggregateUDFImpl, Signature, Volatility};
+
+use crate::common::args::literal_arg;
+use crate::common::output_width::OutputWidth;
+use crate::compat::{arg_type, single_row_list};
+
+make_udaf_expr_and_func!(
+    MapAggFunction,
+    map_agg,
+    key value,
+    "Builds a map of the keys to their values, failing on duplicate keys.",
+    map_agg_udaf
+);
+
+/// How [`MapAggFunction`] handles keys found more than once
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicateKeys {
+    /// Keeps the value of the first row aggregated
+    First,
+    /// Keeps the value of the last row aggregated
+    Last,
+    /// Fails the aggregation
+    Error,
+}
+
+impl DuplicateKeys {
+    fn try_from_name(name: &str) -> Result<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "first" => Ok(Self::First),
+            "last" => Ok(Self::Last),
+            "error" => Ok(Self::Error),
+            _ => plan_err!("map_agg expects a policy of 'first', 'last' or 'error', got '{name}'"),
+        }
+    }
+}
+
+/// The entries of a map of `key_type` to nullable `value_type`
+pub(crate) fn map_entries_field(key_type: &DataType, value_type: &DataType) -> Arc<Field> {
+    let entries = Fields::from(vec![
+        Field::new("key", key_type.clone(), false),
+        Field::new("value", value_type.clone(), true),
+    ]);
+    Arc::new(Field::new("entries", DataType::Struct(entries), false))
+}
+
+/// The single-row map of `keys` to `values`, NULL if there are no keys
+pub(crate) fn single_row_map(keys: ArrayRef, values: ArrayRef, fn_name: &str) -> Result<ScalarValue> {
+    check_map_len(keys.len(), fn_name)?;
+    let entries_field = map_entries_field(keys.data_type(), values.data_type());
+    let DataType::Struct(entries_fields) = entries_field.data_type() else {
+        unreachable!("map entries are a struct");
+    };
+    let len = keys.len();
+    let entries = StructArray::try_new(entries_fields.clone(), vec![keys, values], None)?;
+    let nulls = (len == 0).then(|| NullBuffer::new_null(1));
+    let map = MapArray::try_new(entries_field, OffsetBuffer::from_lengths([len]), entries, nulls, false)?;
+    Ok(ScalarValue::Map(Arc::new(map)))
+}
+
+/// Fails with an error naming `fn_name` if a map can't hold `len` entries. Arrow has no `Map`
+/// with 64-bit offsets, so unlike the list aggregates the map aggregates have no
+/// [`OutputWidth::Large`] to switch to.
+pub(crate) fn check_map_len(len: usize, fn_name: &str) -> Result<()> {
+    if len > OutputWidth::Regular.max_offset() {
+        return exec_err!("{fn_name} output of {len} entries exceeds the 32-bit offsets of a map");
+    }
+    Ok(())
+}
+
+/// The `MapAggFunction` builds a `Map` of the keys to their values from the rows of a group,
+/// `map_agg(key, value[, policy])`, like Trino's `map_agg`.
+///
+/// - Rows with a NULL key are ignored, NULL values are kept. Keys can be of any type that can
+///   be ordered, and `Dictionary` keys are unpacked.
+/// - `policy` handles duplicate keys: `'error'`, the default, fails the query, while `'first'`
+///   and `'last'` keep the value of the first or last row aggregated, which depends on the
+///   partitioning of the input.
+/// - The entries are in the order their keys were first aggregated in. The result is NULL if
+///   there are no rows with a key.
+pub struct MapAggFunction {
+    signature: Signature,
+}
+
+impl Debug for MapAggFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MapAggFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for MapAggFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MapAggFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+        }
+    }
+}
+
+impl AggregateUDFImpl for MapAggFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "map_agg"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        let (key_type, value_type, policy_type) = match arg_types {
+            [key_type, value_type] => (key_type, value_type, None),
+            [key_type, value_type, policy_type] => (key_type, value_type, Some(policy_type)),
+            _ => {
+                return plan_err!(
+                    "map_agg expects 2 or 3 arguments (key, value[, policy]), got {}",
+                    arg_types.len()
+                )
+            }
+        };
+        let key_type = match key_type {
+            DataType::Dictionary(_, key_type) => key_type.as_ref().clone(),
+            DataType::Null => DataType::Int64,
+            key_type => key_type.clone(),
+        };
+        if !RowConverter::supports_fields(&[SortField::new(key_type.clone())]) {
+            return plan_err!("map_agg expects a key that can be ordered, got {key_type}");
+        }
+        let value_type = match value_type {
+            DataType::Null => DataType::Int64,
+            value_type => value_type.clone(),
+        };
+        match policy_type {
+            None => Ok(vec![key_type, value_type]),
+            Some(DataType::Utf8 | DataType::LargeUtf8 | DataType::Utf8View) => {
+                Ok(vec![key_type, value_type, DataType::Utf8])
+            }
+            Some(policy_type) => plan_err!("map_agg expects a string policy, got {policy_type}"),
+        }
+    }
+
+    fn return_type(&self, arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Map(map_entries_field(&arg_types[0], &arg_types[1]), false))
+    }
+
+    fn state_fields(&self, args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(vec![
+            Field::new_list("keys", Field::new("item", args.input_types[0].clone(), true), true),
+            Field::new_list("values", Field::new("item", args.input_types[1].clone(), true), true),
+        ])
+    }
+
+    fn accumulator(&self, acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        let policy = match acc_args.exprs.get(2) {
+            None => DuplicateKeys::Error,
+            Some(expr) => match literal_arg(expr, self.name(), "policy")? {
+                ScalarValue::Utf8(Some(policy)) => DuplicateKeys::try_from_name(&policy)?,
+                policy => return plan_err!("map_agg expects a policy of 'first', 'last' or 'error', got {policy}"),
+            },
+        };
+        Ok(Box::new(MapAggAccumulator::try_new(
+            arg_type(&acc_args, 0)?,
+            arg_type(&acc_args, 1)?,
+            policy,
+        )?))
+    }
+}
+
+/// Accumulator for [`MapAggFunction`].
+///
+/// The keys are kept in the row format, and the values of every batch are taken into an array
+/// for the rows whose value is kept, so a batch isn't retained for a few entries.
+#[derive(Debug)]
+pub struct MapAggAccumulator {
+    value_type: DataType,
+    policy: DuplicateKeys,
+    converter: RowConverter,
+    /// The index of every key in `keys`
+    indices: HashMap<Box<[u8]>, usize>,
+    /// The keys in the row format, in the order they were first aggregated in
+    keys: Vec<Box<[u8]>>,
+    /// The value of every key, as the index of its array in `values` and its row in the array
+    entries: Vec<(usize, usize)>,
+    values: Vec<ArrayRef>,
+}
+
+impl MapAggAccumulator {
+    pub fn try_new(key_type: DataType, value_type: DataType, policy: DuplicateKeys) -> Result<Self> {
+        Ok(Self {
+            converter: RowConverter::new(vec![SortField::new(key_type)])?,
+            value_type,
+            policy,
+            indices: HashMap::new(),
+            keys: vec![],
+            entries: vec![],
+            values: vec![],
+        })
+    }
+
+    fn insert(&mut self, keys: &ArrayRef, values: &ArrayRef) -> Result<()> {
+        let rows = self.converter.convert_columns(&[Arc::clone(keys)])?;
+        let array = self.values.len();
+        // The rows of `values` to keep
+        let mut kept = vec![];
+        for (index, row) in rows.iter().enumerate() {
+            if keys.is_null(index) {
+                continue;
+            }
+            match self.indices.get(row.as_ref()) {
+                Some(_) if self.policy == DuplicateKeys::First => {}
+                Some(&entry) if self.policy == DuplicateKeys::Last => {
+                    self.entries[entry] = (array, kept.len());
+                    kept.push(index as u32);
+                }
+                Some(_) => {
+                    let key = ScalarValue::try_from_array(keys, index)?;
+                    return exec_err!("map_agg found the duplicate key {key}");
+                }
+                None => {
+                    self.indices.insert(row.as_ref().into(), self.keys.len());
+                    self.keys.push(row.as_ref().into());
+                    self.entries.push((array, kept.len()));
+                    kept.push(index as u32);
+                }
+            }
+        }
+        if !kept.is_empty() {
+            self.values.push(take(values, &UInt32Array::from(kept), None)?);
+        }
+        Ok(())
+    }
+
+    /// The keys and values of the entries, compacting the values into a single array
+    fn entries(&mut self) -> Result<(ArrayRef, ArrayRef)> {
+        let compact = self.values.len() == 1
+            && self.values[0].len() == self.entries.len()
+            && self
+                .entries
+                .iter()
+                .enumerate()
+                .all(|(index, &entry)| entry == (0, index));
+        if !compact {
+            let values = match self.values.is_empty() {
+                true => new_empty_array(&self.value_type),
+                false => {
+                    let values = self.values.iter().map(|values| values.as_ref()).collect::<Vec<_>>();
+                    interleave(&values, &self.entries)?
+                }
+            };
+            self.values = vec![values];
+            self.entries = (0..self.keys.len()).map(|index| (0, index)).collect();
+        }
+        let parser = self.converter.parser();
+        let mut keys = self
+            .converter
+            .convert_rows(self.keys.iter().map(|row| parser.parse(row)))?;
+        Ok((keys.remove(0), Arc::clone(&self.values[0])))
+    }
+}
+
+impl Accumulator for MapAggAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        self.insert(&values[0], &values[1])
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        let (keys, values) = (as_list_array(&states[0])?, as_list_array(&states[1])?);
+        for (keys, values) in keys.iter().zip(values.iter()) {
+            if let (Some(keys), Some(values)) = (keys, values) {
+                self.insert(&keys, &values)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        let (keys, values) = self.entries()?;
+        check_map_len(keys.len(), "map_agg")?;
+        Ok(vec![
+            ScalarValue::List(Arc::new(single_row_list(keys))),
+            ScalarValue::List(Arc::new(single_row_list(values))),
+        ])
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        let (keys, values) = self.entries()?;
+        single_row_map(keys, values, "map_agg")
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self)
+            + self.converter.size()
+            + self.indices.capacity() * std::mem::size_of::<(Box<[u8]>, usize)>()
+            + self.keys.capacity() * std::mem::size_of::<Box<[u8]>>()
+            + 2 * self.keys.iter().map(|row| row.len()).sum::<usize>()
+            + self.entries.capacity() * std::mem::size_of::<(usize, usize)>()
+            + self
+                .values
+                .iter()
+                .map(|values| values.get_array_memory_size())
+                .sum::<usize>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{AsArray, Int64Array, StringArray};
+    use arrow::datatypes::Int64Type;
+
+    #[test]
+    fn test_map_agg_last_merges_states() -> Result<()> {
+        let batch = |keys: Vec<Option<&str>>, values: Vec<i64>| -> [ArrayRef; 2] {
+            [Arc::new(StringArray::from(keys)), Arc::new(Int64Array::from(values))]
+        };
+        let mut first = MapAggAccumulator::try_new(DataType::Utf8, DataType::Int64, DuplicateKeys::Last)?;
+        first.update_batch(&batch(vec![Some("a"), Some("b"), Some("a"), None], vec![1, 2, 3, 4]))?;
+        first.update_batch(&batch(vec![Some("c"), Some("b")], vec![5, 6]))?;
+        let mut second = MapAggAccumulator::try_new(DataType::Utf8, DataType::Int64, DuplicateKeys::Last)?;
+        second.update_batch(&batch(vec![Some("a"), Some("d")], vec![7, 8]))?;
+
+        let mut merged = MapAggAccumulator::try_new(DataType::Utf8, DataType::Int64, DuplicateKeys::Last)?;
+        for acc in [&mut first, &mut second] {
+            let states = acc.state()?.iter().map(|s| s.to_array()).collect::<Result<Vec<_>>>()?;
+            merged.merge_batch(&states)?;
+        }
+        let ScalarValue::Map(map) = merged.evaluate()? else {
+            panic!("expected a map");
+        };
+        assert_eq!(
+            map.keys().as_string::<i32>(),
+            &StringArray::from(vec!["a", "b", "c", "d"])
+        );
+        assert_eq!(
+            map.values().as_primitive::<Int64Type>(),
+            &Int64Array::from(vec![7, 6, 5, 8])
+        );
+        Ok(())
+    }
+}
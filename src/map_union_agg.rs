@@ -0,0 +1,461 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::any::Any;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use arrow::array::{new_empty_array, Array, ArrayRef, AsArray, ListArray, PrimitiveArray, UInt32Array};
+use arrow::buffer::OffsetBuffer;
+use arrow::compute::{interleave, sum_checked, take};
+use arrow::datatypes::*;
+use arrow::row::{RowConverter, SortField};
+use datafusion::arrow;
+use datafusion::common::cast::as_map_array;
+use datafusion::common::{plan_err, Result, ScalarValue};
+use datafusion::logical_expr::function::{AccumulatorArgs, StateFieldsArgs};
+use datafusion::logical_expr::{Accumulator, AggregateUDFImpl, Signature, Volatility};
+
+use crate::compat::arg_type;
+use crate::map_agg::{map_entries_field, single_row_map};
+
+make_udaf_expr_and_func!(
+    MapUnionAggFunction,
+    map_union_agg,
+    map,
+    "Merges the maps into one, keeping the last value of every key.",
+    map_union_agg_udaf
+);
+
+make_udaf_expr_and_func!(
+    MapUnionSumFunction,
+    map_union_sum,
+    map,
+    "Merges the maps into one, summing the values of every key.",
+    map_union_sum_udaf
+);
+
+make_udaf_expr_and_func!(
+    MapUnionListFunction,
+    map_union_list,
+    map,
+    "Merges the maps into one, collecting the values of every key into a list.",
+    map_union_list_udaf
+);
+
+/// How the values of a key found in several maps are merged, the result of
+/// [`MapUnionAggFunction`], [`MapUnionSumFunction`] or [`MapUnionListFunction`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MapConflict {
+    /// Keeps the value of the last map aggregated
+    Last,
+    /// Sums the non-null values
+    Sum,
+    /// Collects the values into a list, in the order they were aggregated in
+    List,
+}
+
+impl MapConflict {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Last => "map_union_agg",
+            Self::Sum => "map_union_sum",
+            Self::List => "map_union_list",
+        }
+    }
+
+    /// Coerces the map to one of keys that can be ordered, and of the types values are summed
+    /// in for [`Self::Sum`]
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        let [arg_type] = arg_types else {
+            return plan_err!("{} expects 1 argument, got {}", self.name(), arg_types.len());
+        };
+        let DataType::Map(entries_field, _) = arg_type else {
+            return plan_err!("{} expects a map, got {arg_type}", self.name());
+        };
+        let DataType::Struct(entries_fields) = entries_field.data_type() else {
+            return plan_err!(
+                "{} expects map entries to be a struct, got {entries_field}",
+                self.name()
+            );
+        };
+        let (key_type, value_type) = (entries_fields[0].data_type(), entries_fields[1].data_type());
+        if !RowConverter::supports_fields(&[SortField::new(key_type.clone())]) {
+            return plan_err!("{} expects keys that can be ordered, got {key_type}", self.name());
+        }
+        let value_type = match (self, value_type) {
+            (Self::Sum, DataType::Int8 | DataType::Int16 | DataType::Int32 | DataType::Int64) => DataType::Int64,
+            (Self::Sum, DataType::UInt8 | DataType::UInt16 | DataType::UInt32 | DataType::UInt64) => DataType::UInt64,
+            (Self::Sum, DataType::Float16 | DataType::Float32 | DataType::Float64) => DataType::Float64,
+            (Self::Sum, DataType::Decimal128(_, scale)) => DataType::Decimal128(DECIMAL128_MAX_PRECISION, *scale),
+            (Self::Sum, DataType::Null) => DataType::Int64,
+            (Self::Sum, value_type) => return plan_err!("map_union_sum expects numeric values, got {value_type}"),
+            (_, value_type) => value_type.clone(),
+        };
+        Ok(vec![DataType::Map(map_entries_field(key_type, &value_type), false)])
+    }
+
+    /// The type of the values of the merged map
+    fn return_type(&self, arg_types: &[DataType]) -> Result<DataType> {
+        let (key_type, value_type) = map_types(&arg_types[0])?;
+        let value_type = match self {
+            Self::List => DataType::new_list(value_type, true),
+            _ => value_type,
+        };
+        Ok(DataType::Map(map_entries_field(&key_type, &value_type), false))
+    }
+
+    fn state_fields(&self, args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(vec![Field::new("map", self.return_type(args.input_types)?, true)])
+    }
+
+    fn accumulator(&self, acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        let (key_type, value_type) = map_types(&arg_type(&acc_args, 0)?)?;
+        Ok(Box::new(MapUnionAccumulator::try_new(*self, key_type, value_type)?))
+    }
+}
+
+/// The types of the keys and values of `map_type`
+fn map_types(map_type: &DataType) -> Result<(DataType, DataType)> {
+    match map_type {
+        DataType::Map(entries_field, _) => match entries_field.data_type() {
+            DataType::Struct(fields) => Ok((fields[0].data_type().clone(), fields[1].data_type().clone())),
+            _ => plan_err!("Expected map entries to be a struct, got {entries_field}"),
+        },
+        _ => plan_err!("Expected a map, got {map_type}"),
+    }
+}
+
+/// The `MapUnionAggFunction` merges the maps of a group into one, `map_union_agg(map)`, keeping
+/// the value of the last map aggregated for the keys found in several maps.
+///
+/// - The last map depends on the partitioning of the input. See [`MapUnionSumFunction`] and
+///   [`MapUnionListFunction`] to merge the values instead.
+/// - The keys are in the order they were first aggregated in. NULL maps are ignored, and the
+///   result is NULL if there are no entries.
+/// - The policy isn't an argument as the type of the values depends on it, and aggregates can't
+///   return a type depending on their arguments.
+pub struct MapUnionAggFunction {
+    signature: Signature,
+}
+
+/// The `MapUnionSumFunction` merges the maps of a group into one, `map_union_sum(map)`, summing
+/// the values of every key, e.g. to merge per-event counters like Trino's `map_union_sum`.
+///
+/// - Integers are summed as `Int64` or `UInt64`, failing on overflow, floats as `Float64` and
+///   decimals with their maximum precision.
+/// - NULL values are ignored, so a key whose values are all NULL is mapped to NULL.
+/// - The keys are in the order they were first aggregated in. NULL maps are ignored, and the
+///   result is NULL if there are no entries.
+pub struct MapUnionSumFunction {
+    signature: Signature,
+}
+
+/// The `MapUnionListFunction` merges the maps of a group into one, `map_union_list(map)`,
+/// collecting the values of every key into a list.
+///
+/// - The values, including NULLs, are in the order they were aggregated in, which depends on
+///   the partitioning of the input.
+/// - The keys are in the order they were first aggregated in. NULL maps are ignored, and the
+///   result is NULL if there are no entries.
+pub struct MapUnionListFunction {
+    signature: Signature,
+}
+
+macro_rules! map_union_function {
+    ($UDAF:ident, $CONFLICT:expr) => {
+        impl Debug for $UDAF {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.debug_struct(stringify!($UDAF))
+                    .field("signature", &self.signature)
+                    .finish()
+            }
+        }
+
+        impl Default for $UDAF {
+            fn default() -> Self {
+                Self::new()
+            }
+        }
+
+        impl $UDAF {
+            pub fn new() -> Self {
+                Self {
+                    signature: Signature::user_defined(Volatility::Immutable),
+                }
+            }
+        }
+
+        impl AggregateUDFImpl for $UDAF {
+            fn as_any(&self) -> &dyn Any {
+                self
+            }
+
+            fn name(&self) -> &str {
+                $CONFLICT.name()
+            }
+
+            fn signature(&self) -> &Signature {
+                &self.signature
+            }
+
+            fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+                $CONFLICT.coerce_types(arg_types)
+            }
+
+            fn return_type(&self, arg_types: &[DataType]) -> Result<DataType> {
+                $CONFLICT.return_type(arg_types)
+            }
+
+            fn state_fields(&self, args: StateFieldsArgs) -> Result<Vec<Field>> {
+                $CONFLICT.state_fields(args)
+            }
+
+            fn accumulator(&self, acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+                $CONFLICT.accumulator(acc_args)
+            }
+        }
+    };
+}
+
+map_union_function!(MapUnionAggFunction, MapConflict::Last);
+map_union_function!(MapUnionSumFunction, MapConflict::Sum);
+map_union_function!(MapUnionListFunction, MapConflict::List);
+
+/// Accumulator for the `map_union` functions.
+///
+/// The keys are kept in the row format, and the values of every batch are taken into an array
+/// for the entries with a key. The values are only merged when the map is emitted, or once
+/// most of them are outdated or can be summed, to bound the memory used.
+#[derive(Debug)]
+pub struct MapUnionAccumulator {
+    conflict: MapConflict,
+    /// The type of the values of the input maps, and of the elements of the merged lists
+    value_type: DataType,
+    converter: RowConverter,
+    /// The index of every key in `keys`
+    indices: HashMap<Box<[u8]>, usize>,
+    /// The keys in the row format, in the order they were first aggregated in
+    keys: Vec<Box<[u8]>>,
+    /// The values of every key, as the index of their array in `values` and their row in the
+    /// array. Only the last value is kept for [`MapConflict::Last`].
+    positions: Vec<Vec<(usize, usize)>>,
+    values: Vec<ArrayRef>,
+    /// The number of rows in `values`
+    len: usize,
+}
+
+impl MapUnionAccumulator {
+    pub fn try_new(conflict: MapConflict, key_type: DataType, value_type: DataType) -> Result<Self> {
+        Ok(Self {
+            conflict,
+            value_type,
+            converter: RowConverter::new(vec![SortField::new(key_type)])?,
+            indices: HashMap::new(),
+            keys: vec![],
+            positions: vec![],
+            values: vec![],
+            len: 0,
+        })
+    }
+
+    fn insert(&mut self, keys: &ArrayRef, values: &ArrayRef) -> Result<()> {
+        let rows = self.converter.convert_columns(&[Arc::clone(keys)])?;
+        let array = self.values.len();
+        // The rows of `values` to keep
+        let mut kept = vec![];
+        for (index, row) in rows.iter().enumerate() {
+            let entry = match self.indices.get(row.as_ref()) {
+                Some(&entry) => entry,
+                None => {
+                    self.indices.insert(row.as_ref().into(), self.keys.len());
+                    self.keys.push(row.as_ref().into());
+                    self.positions.push(vec![]);
+                    self.keys.len() - 1
+                }
+            };
+            if self.conflict == MapConflict::Last {
+                self.positions[entry].clear();
+            }
+            self.positions[entry].push((array, kept.len()));
+            kept.push(index as u32);
+        }
+        if !kept.is_empty() {
+            self.len += kept.len();
+            self.values.push(take(values, &UInt32Array::from(kept), None)?);
+        }
+        if self.conflict != MapConflict::List && self.len > 2 * self.keys.len() + 1024 {
+            self.merge_values()?;
+        }
+        Ok(())
+    }
+
+    /// Merges the values of every key into a single array, in the order of the keys
+    fn merge_values(&mut self) -> Result<ArrayRef> {
+        let positions = self.positions.iter().flatten().copied().collect::<Vec<_>>();
+        let values = match self.values.is_empty() {
+            true => new_empty_array(&self.value_type),
+            false => {
+                let values = self.values.iter().map(|values| values.as_ref()).collect::<Vec<_>>();
+                interleave(&values, &positions)?
+            }
+        };
+        let offsets = OffsetBuffer::<i32>::from_lengths(self.positions.iter().map(Vec::len));
+        let merged = match self.conflict {
+            MapConflict::Last => Arc::clone(&values),
+            MapConflict::Sum => sum_values(&values, &offsets)?,
+            MapConflict::List => {
+                let field = Arc::new(Field::new("item", self.value_type.clone(), true));
+                Arc::new(ListArray::try_new(field, offsets.clone(), Arc::clone(&values), None)?)
+            }
+        };
+        // Keep the merged values, as the map is emitted once per frame in windows
+        let values = match self.conflict {
+            MapConflict::List => values,
+            _ => Arc::clone(&merged),
+        };
+        let mut start = 0;
+        for positions in self.positions.iter_mut() {
+            let len = match self.conflict {
+                MapConflict::List => positions.len(),
+                _ => 1,
+            };
+            *positions = (start..start + len).map(|row| (0, row)).collect();
+            start += len;
+        }
+        self.len = values.len();
+        self.values = vec![values];
+        Ok(merged)
+    }
+}
+
+/// The sums of the non-null `values` between every pair of `offsets`
+fn sum_values(values: &ArrayRef, offsets: &OffsetBuffer<i32>) -> Result<ArrayRef> {
+    fn sum<T: ArrowNumericType>(values: &ArrayRef, offsets: &OffsetBuffer<i32>) -> Result<ArrayRef> {
+        let values = values.as_primitive::<T>();
+        let sums = offsets
+            .windows(2)
+            .map(|range| sum_checked(&values.slice(range[0] as usize, (range[1] - range[0]) as usize)))
+            .collect::<Result<PrimitiveArray<T>, _>>()?;
+        Ok(Arc::new(sums.with_data_type(values.data_type().clone())))
+    }
+    match values.data_type() {
+        DataType::Int64 => sum::<Int64Type>(values, offsets),
+        DataType::UInt64 => sum::<UInt64Type>(values, offsets),
+        DataType::Float64 => sum::<Float64Type>(values, offsets),
+        DataType::Decimal128(_, _) => sum::<Decimal128Type>(values, offsets),
+        value_type => plan_err!("map_union_sum expects numeric values, got {value_type}"),
+    }
+}
+
+impl Accumulator for MapUnionAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        let maps = as_map_array(&values[0])?;
+        for map in 0..maps.len() {
+            if maps.is_valid(map) {
+                let entries = maps.value(map);
+                self.insert(entries.column(0), entries.column(1))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        let maps = as_map_array(&states[0])?;
+        for map in 0..maps.len() {
+            if maps.is_null(map) {
+                continue;
+            }
+            let entries = maps.value(map);
+            let (keys, values) = (entries.column(0), entries.column(1));
+            if self.conflict != MapConflict::List {
+                self.insert(keys, values)?;
+                continue;
+            }
+            // Every element of the lists is a value of their key
+            let lists = values.as_list::<i32>();
+            let indices = (0..lists.len())
+                .flat_map(|index| std::iter::repeat(index as u32).take(lists.value_length(index) as usize));
+            let keys = take(keys, &UInt32Array::from_iter_values(indices), None)?;
+            let offsets = lists.value_offsets();
+            let (start, end) = (offsets[0] as usize, offsets[lists.len()] as usize);
+            self.insert(&keys, &lists.values().slice(start, end - start))?;
+        }
+        Ok(())
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        Ok(vec![self.evaluate()?])
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        let values = self.merge_values()?;
+        let parser = self.converter.parser();
+        let mut keys = self
+            .converter
+            .convert_rows(self.keys.iter().map(|row| parser.parse(row)))?;
+        single_row_map(keys.remove(0), values, "map_union_agg")
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self)
+            + self.converter.size()
+            + self.indices.capacity() * std::mem::size_of::<(Box<[u8]>, usize)>()
+            + self.keys.capacity() * std::mem::size_of::<Box<[u8]>>()
+            + 2 * self.keys.iter().map(|row| row.len()).sum::<usize>()
+            + self
+                .positions
+                .iter()
+                .map(|positions| {
+                    std::mem::size_of_val(positions) + positions.capacity() * std::mem::size_of::<(usize, usize)>()
+                })
+                .sum::<usize>()
+            + self
+                .values
+                .iter()
+                .map(|values| values.get_array_memory_size())
+                .sum::<usize>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{Int64Array, StringArray};
+
+    #[test]
+    fn test_map_union_sum_merges_values() -> Result<()> {
+        let mut acc = MapUnionAccumulator::try_new(MapConflict::Sum, DataType::Utf8, DataType::Int64)?;
+        let keys: ArrayRef = Arc::new(StringArray::from(vec!["a", "b", "a"]));
+        let values: ArrayRef = Arc::new(Int64Array::from(vec![Some(1), None, Some(2)]));
+        for _ in 0..1000 {
+            acc.insert(&keys, &values)?;
+        }
+        // The sums were merged rather than keeping every value
+        assert!(acc.len < 2 * 1024);
+        let ScalarValue::Map(map) = acc.evaluate()? else {
+            panic!("expected a map");
+        };
+        assert_eq!(map.keys().as_string::<i32>(), &StringArray::from(vec!["a", "b"]));
+        assert_eq!(
+            map.values().as_primitive::<Int64Type>(),
+            &Int64Array::from(vec![Some(3000), None])
+        );
+        Ok(())
+    }
+}
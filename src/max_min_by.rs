@@ -5,7 +5,8 @@ use datafusion::logical_expr::expr::AggregateFunction;
 use datafusion::logical_expr::expr::Sort;
 use datafusion::logical_expr::simplify::SimplifyInfo;
 use datafusion::logical_expr::{expr, function, Accumulator, AggregateUDFImpl};
-use datafusion::prelude::Expr;
+use datafusion::prelude::{lit, when, Expr};
+use datafusion::scalar::ScalarValue;
 use datafusion::{
     common::exec_err,
     logical_expr::{function::AccumulatorArgs, Signature, Volatility},
@@ -24,6 +25,7 @@ make_udaf_expr_and_func!(
 
 pub struct MaxByFunction {
     signature: Signature,
+    null_key_policy: NullKeyPolicy,
 }
 
 impl Debug for MaxByFunction {
@@ -31,6 +33,7 @@ impl Debug for MaxByFunction {
         f.debug_struct("MaxBy")
             .field("name", &self.name())
             .field("signature", &self.signature)
+            .field("null_key_policy", &self.null_key_policy)
             .field("accumulator", &"<FUNC>")
             .finish()
     }
@@ -43,8 +46,13 @@ impl Default for MaxByFunction {
 
 impl MaxByFunction {
     pub fn new() -> Self {
+        Self::new_with_null_key_policy(NullKeyPolicy::default())
+    }
+
+    pub fn new_with_null_key_policy(null_key_policy: NullKeyPolicy) -> Self {
         Self {
             signature: Signature::user_defined(Volatility::Immutable),
+            null_key_policy,
         }
     }
 }
@@ -59,6 +67,60 @@ fn get_min_max_by_result_type(input_types: &[DataType]) -> Result<Vec<DataType>,
     }
 }
 
+/// How `max_by` and `min_by` treat rows whose ordering key (the second argument) is NULL.
+///
+/// Before [`NullKeyPolicy::IgnoreNullKeys`] became the default, both functions returned the
+/// value of a NULL key, as [`NullKeyPolicy::NullsLast`] does for `max_by` and
+/// [`NullKeyPolicy::NullsFirst`] for `min_by`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum NullKeyPolicy {
+    /// Rows with a NULL key are skipped; the result is NULL if every key is NULL.
+    #[default]
+    IgnoreNullKeys,
+    /// A NULL key orders before every other key, so `min_by` returns its value.
+    NullsFirst,
+    /// A NULL key orders after every other key, so `max_by` returns its value.
+    NullsLast,
+}
+
+/// Rewrites `max_by(x, y)` / `min_by(x, y)` into `last_value(x ORDER BY y)`, sorting `y`
+/// ascending for `max_by` and descending for `min_by`.
+///
+/// The policy is applied to the rewritten expression rather than to the accumulator, so the
+/// partial and final aggregation stages order NULL keys the same way.
+fn simplify_to_last_value(
+    mut aggr_func: expr::AggregateFunction,
+    info: &dyn SimplifyInfo,
+    ascending: bool,
+    null_key_policy: NullKeyPolicy,
+) -> Result<Expr, DataFusionError> {
+    let mut order_by = aggr_func.order_by.unwrap_or_default();
+    let (second_arg, mut first_arg) = (aggr_func.args.remove(1), aggr_func.args.remove(0));
+
+    // The last row wins, so NULL keys go first when they should lose
+    let nulls_first = match null_key_policy {
+        NullKeyPolicy::IgnoreNullKeys => {
+            // Rather than filtering, mask the value of NULL keys so the result is NULL when
+            // every key is NULL
+            let null_value = ScalarValue::try_from(info.get_data_type(&first_arg)?)?;
+            first_arg = when(second_arg.clone().is_null(), lit(null_value)).otherwise(first_arg)?;
+            true
+        }
+        NullKeyPolicy::NullsFirst => ascending,
+        NullKeyPolicy::NullsLast => !ascending,
+    };
+    order_by.push(Sort::new(second_arg, ascending, nulls_first));
+
+    Ok(Expr::AggregateFunction(AggregateFunction::new_udf(
+        last_value_udaf(),
+        vec![first_arg],
+        aggr_func.distinct,
+        aggr_func.filter,
+        Some(order_by),
+        aggr_func.null_treatment,
+    )))
+}
+
 impl AggregateUDFImpl for MaxByFunction {
     fn as_any(&self) -> &dyn Any {
         self
@@ -84,20 +146,9 @@ impl AggregateUDFImpl for MaxByFunction {
     }
 
     fn simplify(&self) -> Option<function::AggregateFunctionSimplification> {
-        let simplify = |mut aggr_func: expr::AggregateFunction, _: &dyn SimplifyInfo| {
-            let mut order_by = aggr_func.order_by.unwrap_or_default();
-            let (second_arg, first_arg) = (aggr_func.args.remove(1), aggr_func.args.remove(0));
-
-            order_by.push(Sort::new(second_arg, true, false));
-
-            Ok(Expr::AggregateFunction(AggregateFunction::new_udf(
-                last_value_udaf(),
-                vec![first_arg],
-                aggr_func.distinct,
-                aggr_func.filter,
-                Some(order_by),
-                aggr_func.null_treatment,
-            )))
+        let null_key_policy = self.null_key_policy;
+        let simplify = move |aggr_func: expr::AggregateFunction, info: &dyn SimplifyInfo| {
+            simplify_to_last_value(aggr_func, info, true, null_key_policy)
         };
         Some(Box::new(simplify))
     }
@@ -113,6 +164,7 @@ make_udaf_expr_and_func!(
 
 pub struct MinByFunction {
     signature: Signature,
+    null_key_policy: NullKeyPolicy,
 }
 
 impl Debug for MinByFunction {
@@ -120,6 +172,7 @@ impl Debug for MinByFunction {
         f.debug_struct("MinBy")
             .field("name", &self.name())
             .field("signature", &self.signature)
+            .field("null_key_policy", &self.null_key_policy)
             .field("accumulator", &"<FUNC>")
             .finish()
     }
@@ -133,8 +186,13 @@ impl Default for MinByFunction {
 
 impl MinByFunction {
     pub fn new() -> Self {
+        Self::new_with_null_key_policy(NullKeyPolicy::default())
+    }
+
+    pub fn new_with_null_key_policy(null_key_policy: NullKeyPolicy) -> Self {
         Self {
             signature: Signature::user_defined(Volatility::Immutable),
+            null_key_policy,
         }
     }
 }
@@ -165,20 +223,9 @@ impl AggregateUDFImpl for MinByFunction {
     }
 
     fn simplify(&self) -> Option<function::AggregateFunctionSimplification> {
-        let simplify = |mut aggr_func: expr::AggregateFunction, _: &dyn SimplifyInfo| {
-            let mut order_by = aggr_func.order_by.unwrap_or_default();
-            let (second_arg, first_arg) = (aggr_func.args.remove(1), aggr_func.args.remove(0));
-
-            order_by.push(Sort::new(second_arg, false, false)); // false for ascending sort
-
-            Ok(Expr::AggregateFunction(AggregateFunction::new_udf(
-                last_value_udaf(),
-                vec![first_arg],
-                aggr_func.distinct,
-                aggr_func.filter,
-                Some(order_by),
-                aggr_func.null_treatment,
-            )))
+        let null_key_policy = self.null_key_policy;
+        let simplify = move |aggr_func: expr::AggregateFunction, info: &dyn SimplifyInfo| {
+            simplify_to_last_value(aggr_func, info, false, null_key_policy)
         };
         Some(Box::new(simplify))
     }
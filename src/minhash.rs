@@ -0,0 +1,314 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::any::Any;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use arrow::array::{Array, ArrayRef, Float64Array};
+use datafusion::arrow;
+use datafusion::arrow::datatypes::{DataType, Field};
+use datafusion::common::cast::as_binary_array;
+use datafusion::common::{exec_err, plan_err, Result, ScalarValue};
+use datafusion::logical_expr::function::{AccumulatorArgs, StateFieldsArgs};
+use datafusion::logical_expr::{Accumulator, AggregateUDFImpl, ColumnarValue, ScalarUDFImpl, Signature, Volatility};
+
+use crate::common::args::{literal_arg, scalar_args};
+use crate::fingerprint::{hash_lookup_values, lookup_value_type};
+use crate::sketches::minhash::{MinHashSignature, DEFAULT_NUM_HASHES, NUM_HASHES_RANGE};
+
+make_udaf_expr_and_func!(
+    MinHashAggFunction,
+    minhash_agg,
+    "Returns a serialized MinHash signature of the distinct values, to estimate their similarity with other sets.",
+    minhash_agg_udaf
+);
+
+make_udf_expr_and_func!(
+    MinHashJaccardFunction,
+    minhash_jaccard,
+    a b,
+    "Estimates the Jaccard similarity of the sets of two MinHash signatures.",
+    minhash_jaccard_udf
+);
+
+/// The `MinHashAggFunction` builds a MinHash signature of the distinct non-null values,
+/// serialized in the versioned format of [`crate::sketches::format`], to compare with the
+/// signatures of other groups with [`MinHashJaccardFunction`].
+///
+/// - `minhash_agg(x, k)` keeps the smallest hash of `k` hash functions, between 1 and 2^14, for a
+///   standard error of the Jaccard similarity of at most `0.5 / sqrt(k)`. The default of 128
+///   gives 4.4%.
+/// - Signatures only compare with signatures of the same `k`.
+/// - Values are hashed with Spark's Murmur3 after widening integers to BIGINT, floats to DOUBLE
+///   and strings to VARCHAR, so signatures of columns of different widths compare.
+pub struct MinHashAggFunction {
+    signature: Signature,
+}
+
+impl Debug for MinHashAggFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MinHashAggFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for MinHashAggFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MinHashAggFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+        }
+    }
+}
+
+impl AggregateUDFImpl for MinHashAggFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "minhash_agg"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        match arg_types {
+            [value] => Ok(vec![lookup_value_type(value)]),
+            [value, _] => Ok(vec![lookup_value_type(value), DataType::Int64]),
+            _ => plan_err!("minhash_agg expects a value and optional k"),
+        }
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Binary)
+    }
+
+    fn is_nullable(&self) -> bool {
+        false
+    }
+
+    fn state_fields(&self, _args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(vec![Field::new("signature", DataType::Binary, true)])
+    }
+
+    fn accumulator(&self, acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        let Some(expr) = acc_args.exprs.get(1) else {
+            return Ok(Box::new(MinHashAccumulator::new(DEFAULT_NUM_HASHES)));
+        };
+        let k = literal_arg(expr, self.name(), "k")?;
+        let valid = match k {
+            ScalarValue::Int64(Some(k)) => usize::try_from(k).ok().filter(|k| NUM_HASHES_RANGE.contains(k)),
+            _ => None,
+        };
+        let Some(k) = valid else {
+            return plan_err!(
+                "minhash_agg expects k between {} and {}, got {k}",
+                NUM_HASHES_RANGE.start(),
+                NUM_HASHES_RANGE.end()
+            );
+        };
+        Ok(Box::new(MinHashAccumulator::new(k)))
+    }
+
+    fn default_value(&self, _data_type: &DataType) -> Result<ScalarValue> {
+        Ok(ScalarValue::Binary(Some(MinHashSignature::default().to_bytes())))
+    }
+}
+
+/// Accumulator for [`MinHashAggFunction`], adding the 64-bit hashes of the values to a
+/// [`MinHashSignature`].
+#[derive(Debug)]
+pub struct MinHashAccumulator {
+    signature: MinHashSignature,
+}
+
+impl MinHashAccumulator {
+    pub fn new(num_hashes: usize) -> Self {
+        Self {
+            signature: MinHashSignature::new(num_hashes),
+        }
+    }
+}
+
+impl Accumulator for MinHashAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        let values = &values[0];
+        for (row, hash) in hash_lookup_values(values)?.into_iter().enumerate() {
+            if values.is_valid(row) {
+                self.signature.add_hash(hash);
+            }
+        }
+        Ok(())
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        for bytes in as_binary_array(&states[0])?.iter().flatten() {
+            self.signature.merge(&MinHashSignature::from_bytes(bytes)?)?;
+        }
+        Ok(())
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        Ok(vec![self.evaluate()?])
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        Ok(ScalarValue::Binary(Some(self.signature.to_bytes())))
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self) + self.signature.size()
+    }
+}
+
+/// The `MinHashJaccardFunction` estimates the Jaccard similarity, the size of the intersection
+/// over the size of the union, of the sets of two signatures of [`MinHashAggFunction`].
+///
+/// - The similarity of a set with the empty set is 0, and NULL between two empty sets.
+/// - The result is NULL if either signature is NULL.
+pub struct MinHashJaccardFunction {
+    signature: Signature,
+}
+
+impl Debug for MinHashJaccardFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MinHashJaccardFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for MinHashJaccardFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MinHashJaccardFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+        }
+    }
+}
+
+impl ScalarUDFImpl for MinHashJaccardFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "minhash_jaccard"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        if arg_types.len() != 2 {
+            return plan_err!(
+                "minhash_jaccard expects 2 MinHash signatures, got {} arguments",
+                arg_types.len()
+            );
+        }
+        arg_types
+            .iter()
+            .map(|arg_type| match arg_type {
+                DataType::Binary | DataType::LargeBinary | DataType::BinaryView | DataType::Null => {
+                    Ok(DataType::Binary)
+                }
+                _ => plan_err!("minhash_jaccard expects MinHash signatures, got {arg_type}"),
+            })
+            .collect()
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Float64)
+    }
+
+    fn invoke(&self, args: &[ColumnarValue]) -> Result<ColumnarValue> {
+        let jaccard = |a: Option<&[u8]>, b: Option<&[u8]>| -> Result<Option<f64>> {
+            match (a, b) {
+                (Some(a), Some(b)) => MinHashSignature::from_bytes(a)?.jaccard(&MinHashSignature::from_bytes(b)?),
+                _ => Ok(None),
+            }
+        };
+        if let Some([a, b]) = scalar_args(args) {
+            let (ScalarValue::Binary(a), ScalarValue::Binary(b)) = (a, b) else {
+                return exec_err!("minhash_jaccard expects MinHash signatures, got {a:?} and {b:?}");
+            };
+            let similarity = jaccard(a.as_deref(), b.as_deref())?;
+            return Ok(ColumnarValue::Scalar(ScalarValue::Float64(similarity)));
+        }
+
+        let arrays = ColumnarValue::values_to_arrays(args)?;
+        let (a, b) = (as_binary_array(&arrays[0])?, as_binary_array(&arrays[1])?);
+        let similarities = a
+            .iter()
+            .zip(b.iter())
+            .map(|(a, b)| jaccard(a, b))
+            .collect::<Result<Float64Array>>()?;
+        Ok(ColumnarValue::Array(Arc::new(similarities)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::array::{BinaryArray, Int32Array, Int64Array};
+
+    use super::*;
+
+    fn signature(values: ArrayRef) -> Result<Option<Vec<u8>>> {
+        let mut acc = MinHashAccumulator::new(DEFAULT_NUM_HASHES);
+        let values = arrow::compute::cast(&values, &lookup_value_type(values.data_type()))?;
+        acc.update_batch(&[values])?;
+        match acc.evaluate()? {
+            ScalarValue::Binary(signature) => Ok(signature),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_jaccard_of_columns_of_different_widths() -> Result<()> {
+        let narrow = signature(Arc::new(Int32Array::from(vec![Some(1), Some(2), None, Some(3)])))?;
+        let wide = signature(Arc::new(Int64Array::from(vec![3, 2, 1, 1])))?;
+        let a: ArrayRef = Arc::new(BinaryArray::from_iter(vec![narrow.clone(), narrow]));
+        let b: ArrayRef = Arc::new(BinaryArray::from_iter(vec![wide, None]));
+
+        let ColumnarValue::Array(similarities) =
+            MinHashJaccardFunction::new().invoke(&[ColumnarValue::Array(a), ColumnarValue::Array(b)])?
+        else {
+            unreachable!()
+        };
+        assert_eq!(
+            similarities.as_any().downcast_ref::<Float64Array>().unwrap(),
+            &Float64Array::from(vec![Some(1.0), None])
+        );
+        Ok(())
+    }
+}
@@ -16,43 +16,109 @@
 // under the License.
 
 use arrow::datatypes::{
-    Date32Type, Date64Type, Float16Type, Float32Type, Float64Type, Int16Type, Int32Type, Int64Type, Int8Type,
-    Time32MillisecondType, Time32SecondType, Time64MicrosecondType, Time64NanosecondType, TimestampMicrosecondType,
-    TimestampMillisecondType, TimestampNanosecondType, TimestampSecondType, UInt16Type, UInt32Type, UInt64Type,
-    UInt8Type,
+    ArrowPrimitiveType, Date32Type, Date64Type, Float16Type, Float32Type, Float64Type, Int16Type, Int32Type, Int64Type,
+    Int8Type, Time32MillisecondType, Time32SecondType, Time64MicrosecondType, Time64NanosecondType,
+    TimestampMicrosecondType, TimestampMillisecondType, TimestampNanosecondType, TimestampSecondType, UInt16Type,
+    UInt32Type, UInt64Type, UInt8Type,
 };
 use datafusion::arrow;
+use datafusion::arrow::row::{RowConverter, SortField};
 
 use datafusion::error::Result;
 
 use datafusion::arrow::datatypes::{DataType, Field, TimeUnit};
-use datafusion::common::not_impl_err;
+use datafusion::common::{not_impl_err, plan_err};
 use datafusion::logical_expr::function::{AccumulatorArgs, StateFieldsArgs};
 use datafusion::logical_expr::{Accumulator, AggregateUDFImpl, Signature, Volatility};
-use datafusion::physical_expr::binary_map::OutputType;
 
 use std::any::Any;
 use std::fmt::Debug;
 
+use crate::common::decay::{coerce_ts, half_life_arg, seconds_per_unit};
+use crate::common::emit::CancellationToken;
 use crate::common::mode::{
-    BytesModeAccumulator, BytesViewModeAccumulator, FloatModeAccumulator, PrimitiveModeAccumulator,
+    BytesModeAccumulator, BytesModeDecayAccumulator, BytesModeGroupsAccumulator, BytesModeLatestAccumulator,
+    BytesViewModeAccumulator, DictionaryModeAccumulator, FloatModeAccumulator, PrimitiveModeAccumulator,
+    PrimitiveModeGroupsAccumulator, PrimitiveModeLatestAccumulator, RollingModeAccumulator,
 };
+use crate::compat::{arg_type, GroupsAccumulator, OutputType};
 
 make_udaf_expr_and_func!(ModeFunction, mode, x, "Calculates the most frequent value.", mode_udaf);
 
+make_udaf_expr_and_func!(
+    ModeLatestFunction,
+    mode_latest,
+    x ts,
+    "Calculates the most frequent value, preferring the most recently seen value on ties.",
+    mode_latest_udaf
+);
+
+make_udaf_expr_and_func!(
+    ApproxModeByTimeDecayFunction,
+    approx_mode_by_time_decay,
+    x ts half_life,
+    "Calculates the value with the highest frequency decayed exponentially with the age of each row.",
+    approx_mode_by_time_decay_udaf
+);
+
+make_udaf_expr_and_func!(
+    RollingModeFunction,
+    rolling_mode,
+    x,
+    "Calculates the most frequent value, retracting the values that leave a sliding window frame.",
+    rolling_mode_udaf
+);
+
 /// The `ModeFunction` calculates the mode (most frequent value) from a set of values.
 ///
 /// - Null values are ignored during the calculation.
 /// - If multiple values have the same frequency, the first encountered value with the highest frequency is returned.
 /// - In the case of `Utf8` or `Utf8View`, the first value encountered in the original order with the highest frequency is returned.
+/// - For `Dictionary` inputs the result type is chosen by the [`DictionaryOutput`] policy.
 pub struct ModeFunction {
     signature: Signature,
+    dictionary_output: DictionaryOutput,
+    cancellation: CancellationToken,
+}
+
+/// The result type of `mode` for `Dictionary` inputs.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DictionaryOutput {
+    /// The result has the input's dictionary type, so wide results are not materialized.
+    #[default]
+    PreserveDictionary,
+    /// The result has the dictionary's value type.
+    Unpack,
+    /// The result is `Utf8View` for string dictionaries, and the value type otherwise.
+    StringView,
+}
+
+impl DictionaryOutput {
+    /// The type of the values counted by the accumulator for an input of `data_type`
+    fn value_type(self, data_type: &DataType) -> DataType {
+        let DataType::Dictionary(_, value_type) = data_type else {
+            return data_type.clone();
+        };
+        match (self, value_type.as_ref()) {
+            (DictionaryOutput::StringView, DataType::Utf8 | DataType::LargeUtf8) => DataType::Utf8View,
+            _ => value_type.as_ref().clone(),
+        }
+    }
+
+    fn return_type(self, data_type: &DataType) -> DataType {
+        match (self, data_type) {
+            (DictionaryOutput::PreserveDictionary, DataType::Dictionary(_, _)) => data_type.clone(),
+            _ => self.value_type(data_type),
+        }
+    }
 }
 
 impl Debug for ModeFunction {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("ModeFunction")
             .field("signature", &self.signature)
+            .field("dictionary_output", &self.dictionary_output)
+            .field("cancellation", &self.cancellation)
             .finish()
     }
 }
@@ -65,10 +131,23 @@ impl Default for ModeFunction {
 
 impl ModeFunction {
     pub fn new() -> Self {
+        Self::new_with_dictionary_output(DictionaryOutput::default())
+    }
+
+    pub fn new_with_dictionary_output(dictionary_output: DictionaryOutput) -> Self {
         Self {
             signature: Signature::variadic_any(Volatility::Immutable),
+            dictionary_output,
+            cancellation: CancellationToken::default(),
         }
     }
+
+    /// Fails the emission of the accumulators once `cancellation` is cancelled, see
+    /// [`crate::common::emit`]
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = cancellation;
+        self
+    }
 }
 
 impl AggregateUDFImpl for ModeFunction {
@@ -85,70 +164,495 @@ impl AggregateUDFImpl for ModeFunction {
     }
 
     fn return_type(&self, arg_types: &[DataType]) -> Result<DataType> {
-        Ok(arg_types[0].clone())
+        Ok(self.dictionary_output.return_type(&arg_types[0]))
     }
 
     fn state_fields(&self, args: StateFieldsArgs) -> Result<Vec<Field>> {
-        let value_type = args.input_types[0].clone();
+        let value_type = self.dictionary_output.value_type(&args.input_types[0]);
+
+        Ok(vec![
+            Field::new_list("values", Field::new("item", value_type, true), true),
+            Field::new_list("frequencies", Field::new("item", DataType::Int64, true), true),
+        ])
+    }
+
+    fn accumulator(&self, acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        let data_type = &arg_type(&acc_args, 0)?;
+
+        if let DataType::Dictionary(key_type, _) = data_type {
+            let value_type = self.dictionary_output.value_type(data_type);
+            let key_type = (self.dictionary_output == DictionaryOutput::PreserveDictionary).then(|| *key_type.clone());
+            let inner = create_mode_accumulator(&value_type, &self.cancellation)?;
+            return Ok(Box::new(DictionaryModeAccumulator::new(inner, value_type, key_type)));
+        }
+
+        create_mode_accumulator(data_type, &self.cancellation)
+    }
 
+    fn groups_accumulator_supported(&self, args: AccumulatorArgs) -> bool {
+        arg_type(&args, 0)
+            .is_ok_and(|data_type| create_mode_groups_accumulator(&data_type, &self.cancellation).is_some())
+    }
+
+    fn create_groups_accumulator(&self, args: AccumulatorArgs) -> Result<Box<dyn GroupsAccumulator>> {
+        let data_type = arg_type(&args, 0)?;
+        match create_mode_groups_accumulator(&data_type, &self.cancellation) {
+            Some(accumulator) => Ok(accumulator),
+            None => not_impl_err!("Unsupported data type: {:?} for mode groups accumulator", data_type),
+        }
+    }
+}
+
+/// The [`GroupsAccumulator`] of `mode` for `data_type`, `None` for the types only supported
+/// per group, `Utf8View` and `Dictionary`
+fn create_mode_groups_accumulator(
+    data_type: &DataType,
+    cancellation: &CancellationToken,
+) -> Option<Box<dyn GroupsAccumulator>> {
+    fn primitive<T>(data_type: &DataType, cancellation: &CancellationToken) -> Option<Box<dyn GroupsAccumulator>>
+    where
+        T: ArrowPrimitiveType + Send,
+        T::Native: PartialOrd,
+    {
+        Some(Box::new(
+            PrimitiveModeGroupsAccumulator::<T>::new(data_type).with_cancellation(cancellation.clone()),
+        ))
+    }
+
+    match data_type {
+        DataType::Int8 => primitive::<Int8Type>(data_type, cancellation),
+        DataType::Int16 => primitive::<Int16Type>(data_type, cancellation),
+        DataType::Int32 => primitive::<Int32Type>(data_type, cancellation),
+        DataType::Int64 => primitive::<Int64Type>(data_type, cancellation),
+        DataType::UInt8 => primitive::<UInt8Type>(data_type, cancellation),
+        DataType::UInt16 => primitive::<UInt16Type>(data_type, cancellation),
+        DataType::UInt32 => primitive::<UInt32Type>(data_type, cancellation),
+        DataType::UInt64 => primitive::<UInt64Type>(data_type, cancellation),
+
+        DataType::Date32 => primitive::<Date32Type>(data_type, cancellation),
+        DataType::Date64 => primitive::<Date64Type>(data_type, cancellation),
+        DataType::Time32(TimeUnit::Millisecond) => primitive::<Time32MillisecondType>(data_type, cancellation),
+        DataType::Time32(TimeUnit::Second) => primitive::<Time32SecondType>(data_type, cancellation),
+        DataType::Time64(TimeUnit::Microsecond) => primitive::<Time64MicrosecondType>(data_type, cancellation),
+        DataType::Time64(TimeUnit::Nanosecond) => primitive::<Time64NanosecondType>(data_type, cancellation),
+        DataType::Timestamp(TimeUnit::Microsecond, _) => primitive::<TimestampMicrosecondType>(data_type, cancellation),
+        DataType::Timestamp(TimeUnit::Millisecond, _) => primitive::<TimestampMillisecondType>(data_type, cancellation),
+        DataType::Timestamp(TimeUnit::Nanosecond, _) => primitive::<TimestampNanosecondType>(data_type, cancellation),
+        DataType::Timestamp(TimeUnit::Second, _) => primitive::<TimestampSecondType>(data_type, cancellation),
+
+        DataType::Float16 => primitive::<Float16Type>(data_type, cancellation),
+        DataType::Float32 => primitive::<Float32Type>(data_type, cancellation),
+        DataType::Float64 => primitive::<Float64Type>(data_type, cancellation),
+
+        DataType::Utf8 => Some(Box::new(
+            BytesModeGroupsAccumulator::<i32>::new().with_cancellation(cancellation.clone()),
+        )),
+        DataType::LargeUtf8 => Some(Box::new(
+            BytesModeGroupsAccumulator::<i64>::new().with_cancellation(cancellation.clone()),
+        )),
+        _ => None,
+    }
+}
+
+pub(crate) fn create_mode_accumulator(
+    data_type: &DataType,
+    cancellation: &CancellationToken,
+) -> Result<Box<dyn Accumulator>> {
+    let cancellation = cancellation.clone();
+    let accumulator: Box<dyn Accumulator> = match data_type {
+        DataType::Int8 => {
+            Box::new(PrimitiveModeAccumulator::<Int8Type>::new(data_type).with_cancellation(cancellation))
+        }
+        DataType::Int16 => {
+            Box::new(PrimitiveModeAccumulator::<Int16Type>::new(data_type).with_cancellation(cancellation))
+        }
+        DataType::Int32 => {
+            Box::new(PrimitiveModeAccumulator::<Int32Type>::new(data_type).with_cancellation(cancellation))
+        }
+        DataType::Int64 => {
+            Box::new(PrimitiveModeAccumulator::<Int64Type>::new(data_type).with_cancellation(cancellation))
+        }
+        DataType::UInt8 => {
+            Box::new(PrimitiveModeAccumulator::<UInt8Type>::new(data_type).with_cancellation(cancellation))
+        }
+        DataType::UInt16 => {
+            Box::new(PrimitiveModeAccumulator::<UInt16Type>::new(data_type).with_cancellation(cancellation))
+        }
+        DataType::UInt32 => {
+            Box::new(PrimitiveModeAccumulator::<UInt32Type>::new(data_type).with_cancellation(cancellation))
+        }
+        DataType::UInt64 => {
+            Box::new(PrimitiveModeAccumulator::<UInt64Type>::new(data_type).with_cancellation(cancellation))
+        }
+
+        DataType::Date32 => {
+            Box::new(PrimitiveModeAccumulator::<Date32Type>::new(data_type).with_cancellation(cancellation))
+        }
+        DataType::Date64 => {
+            Box::new(PrimitiveModeAccumulator::<Date64Type>::new(data_type).with_cancellation(cancellation))
+        }
+        DataType::Time32(TimeUnit::Millisecond) => {
+            Box::new(PrimitiveModeAccumulator::<Time32MillisecondType>::new(data_type).with_cancellation(cancellation))
+        }
+        DataType::Time32(TimeUnit::Second) => {
+            Box::new(PrimitiveModeAccumulator::<Time32SecondType>::new(data_type).with_cancellation(cancellation))
+        }
+        DataType::Time64(TimeUnit::Microsecond) => {
+            Box::new(PrimitiveModeAccumulator::<Time64MicrosecondType>::new(data_type).with_cancellation(cancellation))
+        }
+        DataType::Time64(TimeUnit::Nanosecond) => {
+            Box::new(PrimitiveModeAccumulator::<Time64NanosecondType>::new(data_type).with_cancellation(cancellation))
+        }
+        DataType::Timestamp(TimeUnit::Microsecond, _) => Box::new(
+            PrimitiveModeAccumulator::<TimestampMicrosecondType>::new(data_type).with_cancellation(cancellation),
+        ),
+        DataType::Timestamp(TimeUnit::Millisecond, _) => Box::new(
+            PrimitiveModeAccumulator::<TimestampMillisecondType>::new(data_type).with_cancellation(cancellation),
+        ),
+        DataType::Timestamp(TimeUnit::Nanosecond, _) => Box::new(
+            PrimitiveModeAccumulator::<TimestampNanosecondType>::new(data_type).with_cancellation(cancellation),
+        ),
+        DataType::Timestamp(TimeUnit::Second, _) => {
+            Box::new(PrimitiveModeAccumulator::<TimestampSecondType>::new(data_type).with_cancellation(cancellation))
+        }
+
+        DataType::Float16 => {
+            Box::new(FloatModeAccumulator::<Float16Type>::new(data_type).with_cancellation(cancellation))
+        }
+        DataType::Float32 => {
+            Box::new(FloatModeAccumulator::<Float32Type>::new(data_type).with_cancellation(cancellation))
+        }
+        DataType::Float64 => {
+            Box::new(FloatModeAccumulator::<Float64Type>::new(data_type).with_cancellation(cancellation))
+        }
+
+        DataType::Utf8 => Box::new(BytesModeAccumulator::<i32>::new(OutputType::Utf8).with_cancellation(cancellation)),
+        DataType::LargeUtf8 => {
+            Box::new(BytesModeAccumulator::<i64>::new(OutputType::Utf8).with_cancellation(cancellation))
+        }
+        DataType::Utf8View => {
+            Box::new(BytesViewModeAccumulator::new(OutputType::Utf8View).with_cancellation(cancellation))
+        }
+        _ => {
+            return not_impl_err!("Unsupported data type: {:?} for mode function", data_type);
+        }
+    };
+
+    Ok(accumulator)
+}
+
+/// The `ModeLatestFunction` calculates the most frequent value, breaking ties by recency.
+///
+/// - Null values are ignored, rows with a NULL `ts` are counted but don't make their value more recent.
+/// - If multiple values have the same frequency, the one with the greatest `ts` is returned, and
+///   if that ties too the smallest value is returned.
+/// - `ts` can be a timestamp, a date or an integer.
+/// - `Dictionary` inputs return their value type and `Utf8View` inputs return `Utf8`.
+pub struct ModeLatestFunction {
+    signature: Signature,
+}
+
+impl Debug for ModeLatestFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ModeLatestFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for ModeLatestFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ModeLatestFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+        }
+    }
+}
+
+impl AggregateUDFImpl for ModeLatestFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "mode_latest"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        let [value_type, ts_type] = arg_types else {
+            return plan_err!("mode_latest expects 2 arguments, got {}", arg_types.len());
+        };
+        let value_type = match value_type {
+            DataType::Dictionary(_, value_type) => value_type.as_ref(),
+            _ => value_type,
+        };
+        let value_type = match value_type {
+            DataType::Utf8View => DataType::Utf8,
+            _ => value_type.clone(),
+        };
+        let ts_type = match ts_type {
+            DataType::Timestamp(_, _) | DataType::Date32 | DataType::Date64 => ts_type.clone(),
+            DataType::Null => DataType::Int64,
+            _ if ts_type.is_integer() => DataType::Int64,
+            _ => return plan_err!("mode_latest expects a timestamp, date or integer ts, got {ts_type}"),
+        };
+        Ok(vec![value_type, ts_type])
+    }
+
+    fn return_type(&self, arg_types: &[DataType]) -> Result<DataType> {
+        Ok(arg_types[0].clone())
+    }
+
+    fn state_fields(&self, args: StateFieldsArgs) -> Result<Vec<Field>> {
         Ok(vec![
-            Field::new("values", value_type, true),
-            Field::new("frequencies", DataType::UInt64, true),
+            Field::new_list("values", Field::new("item", args.input_types[0].clone(), true), true),
+            Field::new_list("frequencies", Field::new("item", DataType::Int64, true), true),
+            Field::new_list("latest", Field::new("item", DataType::Int64, true), true),
         ])
     }
 
     fn accumulator(&self, acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
-        let data_type = &acc_args.exprs[0].data_type(acc_args.schema)?;
+        let data_type = &arg_type(&acc_args, 0)?;
 
         let accumulator: Box<dyn Accumulator> = match data_type {
-            DataType::Int8 => Box::new(PrimitiveModeAccumulator::<Int8Type>::new(data_type)),
-            DataType::Int16 => Box::new(PrimitiveModeAccumulator::<Int16Type>::new(data_type)),
-            DataType::Int32 => Box::new(PrimitiveModeAccumulator::<Int32Type>::new(data_type)),
-            DataType::Int64 => Box::new(PrimitiveModeAccumulator::<Int64Type>::new(data_type)),
-            DataType::UInt8 => Box::new(PrimitiveModeAccumulator::<UInt8Type>::new(data_type)),
-            DataType::UInt16 => Box::new(PrimitiveModeAccumulator::<UInt16Type>::new(data_type)),
-            DataType::UInt32 => Box::new(PrimitiveModeAccumulator::<UInt32Type>::new(data_type)),
-            DataType::UInt64 => Box::new(PrimitiveModeAccumulator::<UInt64Type>::new(data_type)),
-
-            DataType::Date32 => Box::new(PrimitiveModeAccumulator::<Date32Type>::new(data_type)),
-            DataType::Date64 => Box::new(PrimitiveModeAccumulator::<Date64Type>::new(data_type)),
+            DataType::Int8 => Box::new(PrimitiveModeLatestAccumulator::<Int8Type>::new(data_type)),
+            DataType::Int16 => Box::new(PrimitiveModeLatestAccumulator::<Int16Type>::new(data_type)),
+            DataType::Int32 => Box::new(PrimitiveModeLatestAccumulator::<Int32Type>::new(data_type)),
+            DataType::Int64 => Box::new(PrimitiveModeLatestAccumulator::<Int64Type>::new(data_type)),
+            DataType::UInt8 => Box::new(PrimitiveModeLatestAccumulator::<UInt8Type>::new(data_type)),
+            DataType::UInt16 => Box::new(PrimitiveModeLatestAccumulator::<UInt16Type>::new(data_type)),
+            DataType::UInt32 => Box::new(PrimitiveModeLatestAccumulator::<UInt32Type>::new(data_type)),
+            DataType::UInt64 => Box::new(PrimitiveModeLatestAccumulator::<UInt64Type>::new(data_type)),
+
+            DataType::Date32 => Box::new(PrimitiveModeLatestAccumulator::<Date32Type>::new(data_type)),
+            DataType::Date64 => Box::new(PrimitiveModeLatestAccumulator::<Date64Type>::new(data_type)),
             DataType::Time32(TimeUnit::Millisecond) => {
-                Box::new(PrimitiveModeAccumulator::<Time32MillisecondType>::new(data_type))
+                Box::new(PrimitiveModeLatestAccumulator::<Time32MillisecondType>::new(data_type))
             }
             DataType::Time32(TimeUnit::Second) => {
-                Box::new(PrimitiveModeAccumulator::<Time32SecondType>::new(data_type))
+                Box::new(PrimitiveModeLatestAccumulator::<Time32SecondType>::new(data_type))
             }
             DataType::Time64(TimeUnit::Microsecond) => {
-                Box::new(PrimitiveModeAccumulator::<Time64MicrosecondType>::new(data_type))
+                Box::new(PrimitiveModeLatestAccumulator::<Time64MicrosecondType>::new(data_type))
             }
             DataType::Time64(TimeUnit::Nanosecond) => {
-                Box::new(PrimitiveModeAccumulator::<Time64NanosecondType>::new(data_type))
-            }
-            DataType::Timestamp(TimeUnit::Microsecond, _) => {
-                Box::new(PrimitiveModeAccumulator::<TimestampMicrosecondType>::new(data_type))
-            }
-            DataType::Timestamp(TimeUnit::Millisecond, _) => {
-                Box::new(PrimitiveModeAccumulator::<TimestampMillisecondType>::new(data_type))
-            }
-            DataType::Timestamp(TimeUnit::Nanosecond, _) => {
-                Box::new(PrimitiveModeAccumulator::<TimestampNanosecondType>::new(data_type))
+                Box::new(PrimitiveModeLatestAccumulator::<Time64NanosecondType>::new(data_type))
             }
+            DataType::Timestamp(TimeUnit::Microsecond, _) => Box::new(PrimitiveModeLatestAccumulator::<
+                TimestampMicrosecondType,
+            >::new(data_type)),
+            DataType::Timestamp(TimeUnit::Millisecond, _) => Box::new(PrimitiveModeLatestAccumulator::<
+                TimestampMillisecondType,
+            >::new(data_type)),
+            DataType::Timestamp(TimeUnit::Nanosecond, _) => Box::new(PrimitiveModeLatestAccumulator::<
+                TimestampNanosecondType,
+            >::new(data_type)),
             DataType::Timestamp(TimeUnit::Second, _) => {
-                Box::new(PrimitiveModeAccumulator::<TimestampSecondType>::new(data_type))
+                Box::new(PrimitiveModeLatestAccumulator::<TimestampSecondType>::new(data_type))
+            }
+
+            DataType::Float16 => Box::new(PrimitiveModeLatestAccumulator::<Float16Type>::new(data_type)),
+            DataType::Float32 => Box::new(PrimitiveModeLatestAccumulator::<Float32Type>::new(data_type)),
+            DataType::Float64 => Box::new(PrimitiveModeLatestAccumulator::<Float64Type>::new(data_type)),
+
+            DataType::Utf8 => Box::new(BytesModeLatestAccumulator::<i32>::new()),
+            DataType::LargeUtf8 => Box::new(BytesModeLatestAccumulator::<i64>::new()),
+            _ => {
+                return not_impl_err!("Unsupported data type: {:?} for mode_latest function", data_type);
             }
+        };
+
+        Ok(accumulator)
+    }
+}
+
+/// The `ApproxModeByTimeDecayFunction` calculates the value with the highest exponentially
+/// decayed frequency, e.g. the current favorite category of a user.
+///
+/// - Every occurrence counts `2^(-age / half_life)`, where `age` is the time between its `ts`
+///   and the most recent `ts` of the group, so an occurrence one `half_life` older than another
+///   counts half as much.
+/// - `ts` can be a timestamp, a date or an integer. `half_life` is a literal interval without
+///   months or a number of seconds, or a number in the unit of `ts` for integers.
+/// - Rows with a NULL value or `ts` are ignored. If decayed frequencies tie, the smallest value
+///   is returned.
+/// - Values are strings, `Dictionary` inputs return their value type and `Utf8View` inputs
+///   return `Utf8`. The result is approximate as the frequencies are decayed in floating point.
+pub struct ApproxModeByTimeDecayFunction {
+    signature: Signature,
+}
+
+impl Debug for ApproxModeByTimeDecayFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ApproxModeByTimeDecayFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for ApproxModeByTimeDecayFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ApproxModeByTimeDecayFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+        }
+    }
+}
+
+impl AggregateUDFImpl for ApproxModeByTimeDecayFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "approx_mode_by_time_decay"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        let [value_type, ts_type, half_life_type] = arg_types else {
+            return plan_err!("approx_mode_by_time_decay expects 3 arguments, got {}", arg_types.len());
+        };
+        let value_type = match value_type {
+            DataType::Dictionary(_, value_type) => value_type.as_ref(),
+            _ => value_type,
+        };
+        let value_type = match value_type {
+            DataType::Utf8View | DataType::Null => DataType::Utf8,
+            DataType::Utf8 | DataType::LargeUtf8 => value_type.clone(),
+            _ => return plan_err!("approx_mode_by_time_decay expects a string value, got {value_type}"),
+        };
+        Ok(vec![
+            value_type,
+            coerce_ts(self.name(), ts_type)?,
+            half_life_type.clone(),
+        ])
+    }
+
+    fn return_type(&self, arg_types: &[DataType]) -> Result<DataType> {
+        Ok(arg_types[0].clone())
+    }
 
-            DataType::Float16 => Box::new(FloatModeAccumulator::<Float16Type>::new(data_type)),
-            DataType::Float32 => Box::new(FloatModeAccumulator::<Float32Type>::new(data_type)),
-            DataType::Float64 => Box::new(FloatModeAccumulator::<Float64Type>::new(data_type)),
+    fn state_fields(&self, args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(vec![
+            Field::new_list("values", Field::new("item", args.input_types[0].clone(), true), true),
+            Field::new_list("scores", Field::new("item", DataType::Float64, true), true),
+            Field::new_list("times", Field::new("item", DataType::Float64, true), true),
+        ])
+    }
 
-            DataType::Utf8 => Box::new(BytesModeAccumulator::<i32>::new(OutputType::Utf8)),
-            DataType::LargeUtf8 => Box::new(BytesModeAccumulator::<i64>::new(OutputType::Utf8)),
-            DataType::Utf8View => Box::new(BytesViewModeAccumulator::new(OutputType::Utf8View)),
+    fn accumulator(&self, acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        let data_type = &arg_type(&acc_args, 0)?;
+        let time_scale = seconds_per_unit(&arg_type(&acc_args, 1)?);
+        let half_life = half_life_arg(&acc_args.exprs[2], self.name(), time_scale.is_some())?;
+        let time_scale = time_scale.unwrap_or(1.0);
+
+        let accumulator: Box<dyn Accumulator> = match data_type {
+            DataType::Utf8 => Box::new(BytesModeDecayAccumulator::<i32>::new(time_scale, half_life)),
+            DataType::LargeUtf8 => Box::new(BytesModeDecayAccumulator::<i64>::new(time_scale, half_life)),
             _ => {
-                return not_impl_err!("Unsupported data type: {:?} for mode function", data_type);
+                return not_impl_err!(
+                    "Unsupported data type: {:?} for approx_mode_by_time_decay function",
+                    data_type
+                );
             }
         };
 
         Ok(accumulator)
     }
 }
+
+/// The `RollingModeFunction` calculates the mode over sliding window frames, e.g. the most common
+/// error of the last 5 minutes with
+/// `rolling_mode(error) OVER (ORDER BY ts RANGE BETWEEN INTERVAL '5 minutes' PRECEDING AND CURRENT ROW)`.
+///
+/// - The values leaving the frame are retracted rather than recounting every frame, so `RANGE`
+///   frames over timestamps and `ROWS` frames are both supported. The mode of a frame is kept at
+///   the top of a heap of the frequencies.
+/// - Null values are ignored. If multiple values have the same frequency, the smallest value is
+///   returned, so the result doesn't depend on the order of the rows in the frame.
+/// - Values of any type that can be ordered are supported, `Dictionary` inputs return their
+///   value type. It is also an aggregate, returning the same result as `mode` but for ties.
+pub struct RollingModeFunction {
+    signature: Signature,
+}
+
+impl Debug for RollingModeFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RollingModeFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for RollingModeFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RollingModeFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+        }
+    }
+}
+
+impl AggregateUDFImpl for RollingModeFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "rolling_mode"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        let value_type = match arg_types {
+            [DataType::Dictionary(_, value_type)] => value_type.as_ref().clone(),
+            [value_type] => value_type.clone(),
+            _ => return plan_err!("rolling_mode expects 1 argument, got {}", arg_types.len()),
+        };
+        if !RowConverter::supports_fields(&[SortField::new(value_type.clone())]) {
+            return plan_err!("rolling_mode expects a value that can be ordered, got {value_type}");
+        }
+        Ok(vec![value_type])
+    }
+
+    fn return_type(&self, arg_types: &[DataType]) -> Result<DataType> {
+        Ok(arg_types[0].clone())
+    }
+
+    fn state_fields(&self, args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(vec![
+            Field::new_list("values", Field::new("item", args.input_types[0].clone(), true), true),
+            Field::new_list("frequencies", Field::new("item", DataType::Int64, true), true),
+        ])
+    }
+
+    fn accumulator(&self, acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        Ok(Box::new(RollingModeAccumulator::try_new(&arg_type(&acc_args, 0)?)?))
+    }
+}
@@ -0,0 +1,252 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::any::Any;
+use std::cmp::Ordering;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use arrow::array::{make_comparator, new_empty_array, ArrayRef, BooleanArray, Int32Array};
+use arrow::compute::SortOptions;
+use arrow::datatypes::DataType;
+use datafusion::arrow;
+use datafusion::common::ExprSchema;
+use datafusion::common::{plan_err, Result, ScalarValue};
+use datafusion::logical_expr::type_coercion::binary::comparison_coercion;
+use datafusion::logical_expr::{ColumnarValue, Expr, ScalarUDFImpl, Signature, Volatility};
+
+make_udf_expr_and_func!(
+    EqualNullFunction,
+    equal_null,
+    a b,
+    "Returns whether a and b are equal, treating NULLs as equal to each other and unequal to any value.",
+    equal_null_udf
+);
+
+make_udf_expr_and_func!(
+    CmpNullLastFunction,
+    cmp_null_last,
+    a b,
+    "Compares a and b as -1, 0 or 1, with NULLs after every value.",
+    cmp_null_last_udf
+);
+
+/// Coerces `a` and `b` to a common type that can be compared, as for `=`
+fn coerce_comparable(name: &str, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+    let [a_type, b_type] = arg_types else {
+        return plan_err!("{name} expects 2 arguments, got {}", arg_types.len());
+    };
+    let common_type = match comparison_coercion(a_type, b_type) {
+        Some(DataType::Null) => DataType::Int64,
+        Some(common_type) => common_type,
+        None => return plan_err!("{name} can't compare {a_type} and {b_type}"),
+    };
+    let empty = new_empty_array(&common_type);
+    if make_comparator(&empty, &empty, SortOptions::default()).is_err() {
+        return plan_err!("{name} can't compare values of type {common_type}");
+    }
+    Ok(vec![common_type.clone(), common_type])
+}
+
+/// Compares the rows of `args` with NULLs after every value, returning a scalar if both
+/// arguments are
+fn compare_rows<T>(
+    args: &[ColumnarValue],
+    map: impl Fn(Ordering) -> T,
+    collect: impl FnOnce(Vec<T>) -> ArrayRef,
+) -> Result<ColumnarValue> {
+    let is_scalar = args.iter().all(|arg| matches!(arg, ColumnarValue::Scalar(_)));
+    let arrays = ColumnarValue::values_to_arrays(args)?;
+    let options = SortOptions {
+        descending: false,
+        nulls_first: false,
+    };
+    let cmp = make_comparator(&arrays[0], &arrays[1], options)?;
+    let values = collect((0..arrays[0].len()).map(|index| map(cmp(index, index))).collect());
+    if is_scalar {
+        return Ok(ColumnarValue::Scalar(ScalarValue::try_from_array(&values, 0)?));
+    }
+    Ok(ColumnarValue::Array(values))
+}
+
+/// The `EqualNullFunction` compares values without three-valued logic, `equal_null(a, b)`,
+/// like Snowflake's `EQUAL_NULL` or `a IS NOT DISTINCT FROM b`.
+///
+/// - Two NULLs are equal, and NULL is unequal to every value, so the result is never NULL.
+/// - Values of any type that can be compared are supported, including lists and structs, whose
+///   NULL fields and elements are compared the same way. `a` and `b` are coerced to a common
+///   type as for `=`.
+/// - Floats are compared by their total order, so `NaN` equals `NaN` but `-0.0` doesn't equal
+///   `0.0`.
+pub struct EqualNullFunction {
+    signature: Signature,
+}
+
+impl Debug for EqualNullFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EqualNullFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for EqualNullFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EqualNullFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+        }
+    }
+}
+
+impl ScalarUDFImpl for EqualNullFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "equal_null"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        coerce_comparable(self.name(), arg_types)
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Boolean)
+    }
+
+    fn is_nullable(&self, _args: &[Expr], _schema: &dyn ExprSchema) -> bool {
+        false
+    }
+
+    fn invoke(&self, args: &[ColumnarValue]) -> Result<ColumnarValue> {
+        compare_rows(args, Ordering::is_eq, |values| Arc::new(BooleanArray::from(values)))
+    }
+}
+
+/// The `CmpNullLastFunction` compares values for sorting, `cmp_null_last(a, b)`, returning -1 if
+/// `a` comes before `b`, 0 if they are equal and 1 if it comes after.
+///
+/// - NULLs come after every value and are equal to each other, so the result is never NULL and
+///   is a stable key for comparators, e.g. to sort rows with `ORDER BY ... NULLS LAST`
+///   semantics outside of SQL.
+/// - Values of any type that can be compared are supported, including lists and structs, which
+///   are compared lexicographically with NULL fields and elements last. `a` and `b` are coerced
+///   to a common type as for `<`.
+/// - Floats are compared by their total order, with `NaN` after every other value.
+pub struct CmpNullLastFunction {
+    signature: Signature,
+}
+
+impl Debug for CmpNullLastFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CmpNullLastFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for CmpNullLastFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CmpNullLastFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+        }
+    }
+}
+
+impl ScalarUDFImpl for CmpNullLastFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "cmp_null_last"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        coerce_comparable(self.name(), arg_types)
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Int32)
+    }
+
+    fn is_nullable(&self, _args: &[Expr], _schema: &dyn ExprSchema) -> bool {
+        false
+    }
+
+    fn invoke(&self, args: &[ColumnarValue]) -> Result<ColumnarValue> {
+        compare_rows(
+            args,
+            |ordering| ordering as i32,
+            |values| Arc::new(Int32Array::from(values)),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::Int64Array;
+
+    #[test]
+    fn test_compare_scalar_with_array() -> Result<()> {
+        let args = [
+            ColumnarValue::Array(Arc::new(Int64Array::from(vec![Some(1), None, Some(3)]))),
+            ColumnarValue::Scalar(ScalarValue::Int64(None)),
+        ];
+        let ColumnarValue::Array(equal) = EqualNullFunction::new().invoke(&args)? else {
+            panic!("expected an array");
+        };
+        assert_eq!(equal.as_ref(), &BooleanArray::from(vec![false, true, false]));
+
+        let ColumnarValue::Array(cmp) = CmpNullLastFunction::new().invoke(&args)? else {
+            panic!("expected an array");
+        };
+        assert_eq!(cmp.as_ref(), &Int32Array::from(vec![-1, 0, -1]));
+
+        let args = [
+            ColumnarValue::Scalar(ScalarValue::Int64(Some(2))),
+            ColumnarValue::Scalar(ScalarValue::Int64(Some(1))),
+        ];
+        let ColumnarValue::Scalar(cmp) = CmpNullLastFunction::new().invoke(&args)? else {
+            panic!("expected a scalar");
+        };
+        assert_eq!(cmp, ScalarValue::Int32(Some(1)));
+        Ok(())
+    }
+}
@@ -0,0 +1,529 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+
+use datafusion::common::config::ConfigOptions;
+use datafusion::common::tree_node::Transformed;
+use datafusion::common::{DFSchema, DataFusionError, Result};
+use datafusion::execution::context::SessionContext;
+use datafusion::execution::FunctionRegistry;
+use datafusion::logical_expr::expr::{AggregateFunction, ScalarFunction, WindowFunction};
+use datafusion::logical_expr::expr_rewriter::FunctionRewrite;
+use datafusion::logical_expr::{AggregateUDF, Expr, ScalarUDF, WindowFunctionDefinition, WindowUDF};
+use log::debug;
+
+use crate::business_days::{BusinessDaysBetweenFunction, HolidayFunction};
+use crate::common::calendar::Calendars;
+use crate::common::emit::CancellationToken;
+use crate::common::interner::StringInterner;
+use crate::config::ExtraFunctionsConfig;
+use crate::fiscal::{FiscalQuarterFunction, FiscalYearFunction};
+use crate::freq_map::FreqMapFunction;
+use crate::intern::InternFunction;
+use crate::mode::ModeFunction;
+use crate::reservoir_sample::ReservoirSampleFunction;
+use crate::value_counts::{ApproxTopKFunction, ValueCountsFunction};
+use crate::{
+    all_extra_aggregate_functions, all_extra_scalar_functions, all_extra_window_functions,
+    unstable_extra_aggregate_functions,
+};
+
+/// Which tiers of functions are registered.
+///
+/// Stable functions are always registered. Unstable functions are new functions whose results
+/// may still change between releases, so they are only registered when opted into.
+#[derive(Debug, Default, Clone)]
+pub struct RegistrationOptions {
+    unstable_functions: bool,
+    cancellation: Option<CancellationToken>,
+    interner: Option<StringInterner>,
+    calendars: Calendars,
+    fiscal_year_start_month: Option<u32>,
+    seed: Option<u64>,
+}
+
+impl RegistrationOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Also register the functions of [`crate::unstable_extra_aggregate_functions`]
+    pub fn with_unstable_functions(mut self, enabled: bool) -> Self {
+        self.unstable_functions = enabled;
+        self
+    }
+
+    /// Register the functions with large states, such as `mode` and `value_counts`, so that
+    /// their emission fails soon after `cancellation` is cancelled, see [`crate::common::emit`].
+    ///
+    /// Use a package registered into a copy of the state for every query with its own token,
+    /// see [`FunctionPackage`].
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = Some(cancellation);
+        self
+    }
+
+    /// Register the functions returning low-cardinality strings, such as `intern`, so that they
+    /// intern their strings into `interner`, see [`crate::common::interner`]
+    pub fn with_string_interner(mut self, interner: StringInterner) -> Self {
+        self.interner = Some(interner);
+        self
+    }
+
+    /// Register the business day functions, such as `holiday`, with the custom calendars of
+    /// `calendars` besides the built-in ones
+    pub fn with_calendars(mut self, calendars: Calendars) -> Self {
+        self.calendars = calendars;
+        self
+    }
+
+    /// Register `fiscal_year` and `fiscal_quarter` for fiscal years starting in `start_month`,
+    /// from 1 to 12, when called without a start month
+    pub fn with_fiscal_year_start_month(mut self, start_month: u32) -> Self {
+        self.fiscal_year_start_month = Some(start_month);
+        self
+    }
+
+    /// Register the randomized functions, such as `reservoir_sample`, so that they derive their
+    /// random streams from `seed` when called without a seed, see
+    /// [`ExtraFunctionsConfig::seed`]
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Applies the options of [`ExtraFunctionsConfig`] in `config` as the defaults of the
+    /// functions, e.g. `options.with_config(ctx.state().config_options())`. The options set in
+    /// the session planning a query take precedence, see [`SessionConfigRewrite`].
+    pub fn with_config(mut self, config: &ConfigOptions) -> Self {
+        let Some(extra) = ExtraFunctionsConfig::from_config(config) else {
+            return self;
+        };
+        if extra.intern_strings && self.interner.is_none() {
+            self = self.with_string_interner(StringInterner::default());
+        }
+        if let Some(start_month) = extra.fiscal_year_start_month {
+            self = self.with_fiscal_year_start_month(start_month);
+        }
+        if let Some(seed) = extra.seed {
+            self = self.with_seed(seed);
+        }
+        for (name, holidays) in &extra.calendars {
+            // The names were checked when the options were set
+            if let Ok(calendars) = self.calendars.clone().with_calendar(name, holidays.iter().copied()) {
+                self.calendars = calendars;
+            }
+        }
+        self
+    }
+
+    /// The interner of the functions returning low-cardinality strings, if any
+    pub fn string_interner(&self) -> Option<&StringInterner> {
+        self.interner.as_ref()
+    }
+}
+
+/// The functions with large states, checking `cancellation` while emitting them
+fn cancellable_aggregate_functions(cancellation: &CancellationToken) -> Vec<Arc<AggregateUDF>> {
+    vec![
+        Arc::new(AggregateUDF::from(
+            ModeFunction::new().with_cancellation(cancellation.clone()),
+        )),
+        Arc::new(AggregateUDF::from(
+            ValueCountsFunction::new().with_cancellation(cancellation.clone()),
+        )),
+        Arc::new(AggregateUDF::from(
+            ApproxTopKFunction::new().with_cancellation(cancellation.clone()),
+        )),
+        Arc::new(AggregateUDF::from(
+            FreqMapFunction::new().with_cancellation(cancellation.clone()),
+        )),
+    ]
+}
+
+/// A named set of functions that can be added to and removed from a registry as a unit.
+///
+/// Packages can be enabled on a long-lived [`SessionContext`] without rebuilding it:
+///
+/// - [`Self::register_into_context`] and [`Self::deregister_from_context`] swap the functions
+///   under the context's state lock, so they are safe while queries run. Queries that are
+///   already planned keep the functions they resolved, and later queries see the change.
+/// - To enable a package for a single request only, register it into a copy of the state
+///   instead, e.g. `package.register_into(&mut state)` on `ctx.state()` followed by
+///   `SessionContext::new_with_state(state)`. The copy shares the catalogs with `ctx`.
+#[derive(Clone)]
+pub struct FunctionPackage {
+    name: String,
+    scalar_functions: Vec<Arc<ScalarUDF>>,
+    aggregate_functions: Vec<Arc<AggregateUDF>>,
+    window_functions: Vec<Arc<WindowUDF>>,
+    function_rewrites: Vec<Arc<dyn FunctionRewrite + Send + Sync>>,
+}
+
+impl Debug for FunctionPackage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FunctionPackage")
+            .field("name", &self.name)
+            .field("scalar_functions", &self.scalar_functions)
+            .field("aggregate_functions", &self.aggregate_functions)
+            .field("window_functions", &self.window_functions)
+            .field(
+                "function_rewrites",
+                &self
+                    .function_rewrites
+                    .iter()
+                    .map(|rewrite| rewrite.name())
+                    .collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+impl FunctionPackage {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            scalar_functions: vec![],
+            aggregate_functions: vec![],
+            window_functions: vec![],
+            function_rewrites: vec![],
+        }
+    }
+
+    /// The package with every stable function of this crate
+    pub fn extra() -> Self {
+        Self::extra_with_options(&RegistrationOptions::default())
+    }
+
+    /// The package with the functions of this crate enabled by `options`, and the
+    /// [`SessionConfigRewrite`] applying the session options to them
+    pub fn extra_with_options(options: &RegistrationOptions) -> Self {
+        let package = Self::new("extra")
+            .with_scalar_functions(all_extra_scalar_functions())
+            .with_aggregate_functions(all_extra_aggregate_functions())
+            .with_window_functions(all_extra_window_functions())
+            .with_function_rewrites([Arc::new(SessionConfigRewrite::new(options.clone())) as _]);
+        let package = if options.unstable_functions {
+            package.with_aggregate_functions(unstable_extra_aggregate_functions())
+        } else {
+            package
+        };
+        let package = match &options.interner {
+            Some(interner) => package.with_scalar_functions([Arc::new(ScalarUDF::from(
+                InternFunction::new_with_interner(interner.clone()),
+            ))]),
+            None => package,
+        };
+        let package = if options.calendars.is_empty() {
+            package
+        } else {
+            package.with_scalar_functions([
+                Arc::new(ScalarUDF::from(HolidayFunction::new_with_calendars(
+                    options.calendars.clone(),
+                ))),
+                Arc::new(ScalarUDF::from(BusinessDaysBetweenFunction::new_with_calendars(
+                    options.calendars.clone(),
+                ))),
+            ])
+        };
+        let package = match options.fiscal_year_start_month {
+            Some(start_month) => package.with_scalar_functions([
+                Arc::new(ScalarUDF::from(FiscalYearFunction::new_with_start_month(start_month))),
+                Arc::new(ScalarUDF::from(FiscalQuarterFunction::new_with_start_month(
+                    start_month,
+                ))),
+            ]),
+            None => package,
+        };
+        let package = match options.seed {
+            Some(seed) => package.with_aggregate_functions([Arc::new(AggregateUDF::from(
+                ReservoirSampleFunction::new_with_seed(seed),
+            ))]),
+            None => package,
+        };
+        match &options.cancellation {
+            // Registered last, so they replace the functions of the same names
+            Some(cancellation) => package.with_aggregate_functions(cancellable_aggregate_functions(cancellation)),
+            None => package,
+        }
+    }
+
+    pub fn with_scalar_functions(mut self, functions: impl IntoIterator<Item = Arc<ScalarUDF>>) -> Self {
+        self.scalar_functions.extend(functions);
+        self
+    }
+
+    pub fn with_aggregate_functions(mut self, functions: impl IntoIterator<Item = Arc<AggregateUDF>>) -> Self {
+        self.aggregate_functions.extend(functions);
+        self
+    }
+
+    pub fn with_window_functions(mut self, functions: impl IntoIterator<Item = Arc<WindowUDF>>) -> Self {
+        self.window_functions.extend(functions);
+        self
+    }
+
+    pub fn with_function_rewrites(
+        mut self,
+        rewrites: impl IntoIterator<Item = Arc<dyn FunctionRewrite + Send + Sync>>,
+    ) -> Self {
+        self.function_rewrites.extend(rewrites);
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn scalar_functions(&self) -> &[Arc<ScalarUDF>] {
+        &self.scalar_functions
+    }
+
+    pub fn aggregate_functions(&self) -> &[Arc<AggregateUDF>] {
+        &self.aggregate_functions
+    }
+
+    pub fn window_functions(&self) -> &[Arc<WindowUDF>] {
+        &self.window_functions
+    }
+
+    pub fn function_rewrites(&self) -> &[Arc<dyn FunctionRewrite + Send + Sync>] {
+        &self.function_rewrites
+    }
+
+    /// Registers the functions of this package, replacing any functions with the same names.
+    ///
+    /// The function rewrites are added to those of the registry, and skipped by registries that
+    /// don't support them, in which case the functions keep the options they were created with.
+    pub fn register_into(&self, registry: &mut dyn FunctionRegistry) -> Result<()> {
+        for udf in &self.scalar_functions {
+            if let Some(existing_udf) = registry.register_udf(Arc::clone(udf))? {
+                debug!("Overwrite existing UDF: {}", existing_udf.name());
+            }
+        }
+        for udaf in &self.aggregate_functions {
+            if let Some(existing_udaf) = registry.register_udaf(Arc::clone(udaf))? {
+                debug!("Overwrite existing UDAF: {}", existing_udaf.name());
+            }
+        }
+        for udwf in &self.window_functions {
+            if let Some(existing_udwf) = registry.register_udwf(Arc::clone(udwf))? {
+                debug!("Overwrite existing UDWF: {}", existing_udwf.name());
+            }
+        }
+        for rewrite in &self.function_rewrites {
+            match registry.register_function_rewrite(Arc::clone(rewrite)) {
+                Err(DataFusionError::NotImplemented(_)) => {
+                    debug!("Skip function rewrite unsupported by the registry: {}", rewrite.name())
+                }
+                result => result?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Deregisters the functions of this package.
+    ///
+    /// Functions that have since been replaced by another registration are left in place.
+    /// Registries can't remove function rewrites, which are left in place too; those of this
+    /// crate only rewrite its own functions.
+    pub fn deregister_from(&self, registry: &mut dyn FunctionRegistry) -> Result<()> {
+        for udf in &self.scalar_functions {
+            if registry.udf(udf.name()).is_ok_and(|current| Arc::ptr_eq(&current, udf)) {
+                registry.deregister_udf(udf.name())?;
+            }
+        }
+        for udaf in &self.aggregate_functions {
+            if registry
+                .udaf(udaf.name())
+                .is_ok_and(|current| Arc::ptr_eq(&current, udaf))
+            {
+                registry.deregister_udaf(udaf.name())?;
+            }
+        }
+        for udwf in &self.window_functions {
+            if registry
+                .udwf(udwf.name())
+                .is_ok_and(|current| Arc::ptr_eq(&current, udwf))
+            {
+                registry.deregister_udwf(udwf.name())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Registers the functions of this package into the shared state of `ctx`, see
+    /// [`Self::register_into`]
+    pub fn register_into_context(&self, ctx: &SessionContext) -> Result<()> {
+        self.register_into(&mut *ctx.state_ref().write())
+    }
+
+    /// Deregisters the functions of this package from the shared state of `ctx`, see
+    /// [`Self::deregister_from`]
+    pub fn deregister_from_context(&self, ctx: &SessionContext) -> Result<()> {
+        self.deregister_from(&mut *ctx.state_ref().write())
+    }
+}
+
+/// A [`FunctionRewrite`] applying the options of [`ExtraFunctionsConfig`] of the session
+/// planning a query to the functions of this crate, so that a
+/// `SET datafusion_functions_extra.<option> = ...` applies to the later queries of the session.
+///
+/// - The functions whose options differ from those of the session, such as `reservoir_sample`
+///   for `seed`, are replaced by functions created with the options of the session. They are
+///   found by their type, so functions of other crates with the same names are left alone.
+/// - Options unset in the session fall back to the [`RegistrationOptions`] the functions were
+///   registered with.
+pub struct SessionConfigRewrite {
+    options: RegistrationOptions,
+    /// Interner of the functions returning low-cardinality strings when `intern_strings` is set
+    interner: StringInterner,
+    /// The business day functions of the last custom calendars of a session
+    calendar_functions: Mutex<Option<CalendarFunctions>>,
+}
+
+/// The business day functions for the custom calendars `calendars` of a session
+struct CalendarFunctions {
+    calendars: BTreeMap<String, Vec<i32>>,
+    holiday: Arc<ScalarUDF>,
+    business_days_between: Arc<ScalarUDF>,
+}
+
+impl Debug for SessionConfigRewrite {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SessionConfigRewrite")
+            .field("options", &self.options)
+            .finish()
+    }
+}
+
+impl SessionConfigRewrite {
+    /// Creates the rewrite of the functions registered with `options`
+    pub fn new(options: RegistrationOptions) -> Self {
+        let interner = options.interner.clone().unwrap_or_default();
+        Self {
+            options,
+            interner,
+            calendar_functions: Mutex::default(),
+        }
+    }
+
+    /// The replacement of `udaf` for the options `extra`, if it differs
+    fn aggregate_function(&self, udaf: &AggregateUDF, extra: &ExtraFunctionsConfig) -> Option<Arc<AggregateUDF>> {
+        let function = udaf.inner().as_any().downcast_ref::<ReservoirSampleFunction>()?;
+        let seed = extra.seed.or(self.options.seed);
+        (function.seed() != seed).then(|| {
+            Arc::new(AggregateUDF::from(match seed {
+                Some(seed) => ReservoirSampleFunction::new_with_seed(seed),
+                None => ReservoirSampleFunction::new(),
+            }))
+        })
+    }
+
+    /// The replacement of `udf` for the options `extra`, if it differs
+    fn scalar_function(&self, udf: &Arc<ScalarUDF>, extra: &ExtraFunctionsConfig) -> Result<Option<Arc<ScalarUDF>>> {
+        let function = udf.inner().as_any();
+        let start_month = extra
+            .fiscal_year_start_month
+            .or(self.options.fiscal_year_start_month)
+            .unwrap_or(1);
+        let replacement = if let Some(function) = function.downcast_ref::<FiscalYearFunction>() {
+            (function.start_month() != start_month)
+                .then(|| ScalarUDF::from(FiscalYearFunction::new_with_start_month(start_month)))
+        } else if let Some(function) = function.downcast_ref::<FiscalQuarterFunction>() {
+            (function.start_month() != start_month)
+                .then(|| ScalarUDF::from(FiscalQuarterFunction::new_with_start_month(start_month)))
+        } else if let Some(function) = function.downcast_ref::<InternFunction>() {
+            (extra.intern_strings && !function.interner().shares_table(&self.interner))
+                .then(|| ScalarUDF::from(InternFunction::new_with_interner(self.interner.clone())))
+        } else if function.is::<HolidayFunction>() || function.is::<BusinessDaysBetweenFunction>() {
+            if extra.calendars.is_empty() {
+                return Ok(None);
+            }
+            let replacement = self.calendar_function(udf.name(), &extra.calendars)?;
+            return Ok((!Arc::ptr_eq(udf, &replacement)).then_some(replacement));
+        } else {
+            None
+        };
+        Ok(replacement.map(Arc::new))
+    }
+
+    /// The business day function `name` with the custom calendars `calendars` of the session
+    /// besides those the functions were registered with
+    fn calendar_function(&self, name: &str, calendars: &BTreeMap<String, Vec<i32>>) -> Result<Arc<ScalarUDF>> {
+        let mut cached = self
+            .calendar_functions
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if cached.as_ref().map_or(true, |cached| &cached.calendars != calendars) {
+            let mut with_session = self.options.calendars.clone();
+            for (name, holidays) in calendars {
+                with_session = with_session.with_calendar(name, holidays.iter().copied())?;
+            }
+            *cached = Some(CalendarFunctions {
+                calendars: calendars.clone(),
+                holiday: Arc::new(ScalarUDF::from(HolidayFunction::new_with_calendars(
+                    with_session.clone(),
+                ))),
+                business_days_between: Arc::new(ScalarUDF::from(BusinessDaysBetweenFunction::new_with_calendars(
+                    with_session,
+                ))),
+            });
+        }
+        let cached = cached.as_ref().expect("the calendar functions were just built");
+        Ok(match name == cached.holiday.name() {
+            true => Arc::clone(&cached.holiday),
+            false => Arc::clone(&cached.business_days_between),
+        })
+    }
+}
+
+impl FunctionRewrite for SessionConfigRewrite {
+    fn name(&self) -> &str {
+        "datafusion_functions_extra_session_config"
+    }
+
+    fn rewrite(&self, expr: Expr, _schema: &DFSchema, config: &ConfigOptions) -> Result<Transformed<Expr>> {
+        let Some(extra) = ExtraFunctionsConfig::from_config(config) else {
+            return Ok(Transformed::no(expr));
+        };
+        Ok(match expr {
+            Expr::AggregateFunction(function) => match self.aggregate_function(&function.func, extra) {
+                Some(func) => Transformed::yes(Expr::AggregateFunction(AggregateFunction { func, ..function })),
+                None => Transformed::no(Expr::AggregateFunction(function)),
+            },
+            Expr::WindowFunction(function) => match &function.fun {
+                WindowFunctionDefinition::AggregateUDF(udaf) => match self.aggregate_function(udaf, extra) {
+                    Some(udaf) => Transformed::yes(Expr::WindowFunction(WindowFunction {
+                        fun: WindowFunctionDefinition::AggregateUDF(udaf),
+                        ..function
+                    })),
+                    None => Transformed::no(Expr::WindowFunction(function)),
+                },
+                _ => Transformed::no(Expr::WindowFunction(function)),
+            },
+            Expr::ScalarFunction(function) => match self.scalar_function(&function.func, extra)? {
+                Some(func) => Transformed::yes(Expr::ScalarFunction(ScalarFunction { func, ..function })),
+                None => Transformed::no(Expr::ScalarFunction(function)),
+            },
+            expr => Transformed::no(expr),
+        })
+    }
+}
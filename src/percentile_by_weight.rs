@@ -0,0 +1,519 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::any::Any;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use arrow::array::{ArrayRef, Float64Array};
+use datafusion::arrow;
+use datafusion::arrow::datatypes::{DataType, Field};
+use datafusion::common::cast::{as_binary_array, as_float64_array};
+use datafusion::common::{exec_err, plan_err, Result, ScalarValue};
+use datafusion::logical_expr::function::{AccumulatorArgs, StateFieldsArgs};
+use datafusion::logical_expr::{Accumulator, AggregateUDFImpl, Signature, TypeSignature, Volatility};
+
+use crate::common::args::{literal_arg, literal_f64_arg};
+use crate::common::nulls::all_null_or_filtered;
+use crate::common::quantile_buffer::WeightedQuantileBuffer;
+use crate::compat::single_row_list;
+use crate::sketches::tdigest::TDigest;
+
+make_udaf_expr_and_func!(
+    PercentileByWeightDiscFunction,
+    percentile_by_weight_disc,
+    value weight q,
+    "Returns the smallest value whose cumulative weight reaches the fraction `q` of the total weight.",
+    percentile_by_weight_disc_udaf
+);
+
+make_udaf_expr_and_func!(
+    PercentileWeightedFunction,
+    percentile_weighted,
+    value weight q,
+    "Interpolates the weighted `q`-th percentile of the values.",
+    percentile_weighted_udaf
+);
+
+/// The `PercentileByWeightDiscFunction` computes the exact discrete weighted percentile: the
+/// smallest value whose cumulative weight, in ascending order of value, reaches `q` times the
+/// total weight.
+///
+/// - Unlike an interpolated percentile, the result is always one of the values.
+/// - `q` must be a literal in `[0, 1]`.
+/// - Rows where the value or the weight is NULL and rows of zero weight are skipped. Negative
+///   weights are an error.
+/// - The result is NULL if there are no such rows.
+pub struct PercentileByWeightDiscFunction {
+    signature: Signature,
+}
+
+impl Debug for PercentileByWeightDiscFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PercentileByWeightDiscFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for PercentileByWeightDiscFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PercentileByWeightDiscFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::coercible(
+                vec![DataType::Float64, DataType::Float64, DataType::Float64],
+                Volatility::Immutable,
+            ),
+        }
+    }
+}
+
+impl AggregateUDFImpl for PercentileByWeightDiscFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "percentile_by_weight_disc"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Float64)
+    }
+
+    fn state_fields(&self, _args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(vec![
+            Field::new_list("values", Field::new("item", DataType::Float64, true), true),
+            Field::new_list("weights", Field::new("item", DataType::Float64, true), true),
+        ])
+    }
+
+    fn accumulator(&self, acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        let q = literal_f64_arg(&acc_args.exprs[2], self.name(), "q")?;
+        if !(0.0..=1.0).contains(&q) {
+            return plan_err!("{} expects a q in [0, 1], got {q}", self.name());
+        }
+        Ok(Box::new(PercentileByWeightDiscAccumulator::new(q)))
+    }
+}
+
+/// Accumulator for the discrete weighted percentile, which buffers the `(value, weight)` pairs
+/// and sorts them when evaluated.
+#[derive(Debug)]
+pub struct PercentileByWeightDiscAccumulator {
+    entries: WeightedQuantileBuffer,
+    q: f64,
+}
+
+impl PercentileByWeightDiscAccumulator {
+    pub fn new(q: f64) -> Self {
+        Self {
+            entries: WeightedQuantileBuffer::new(),
+            q,
+        }
+    }
+}
+
+impl Accumulator for PercentileByWeightDiscAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        self.entries
+            .update_batch(&values[0], &values[1], "percentile_by_weight_disc")
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        self.entries
+            .merge_batch(&states[0], &states[1], "percentile_by_weight_disc")
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        Ok(self.entries.state().to_vec())
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        let target = self.q * self.entries.total_weight();
+        let entries = self.entries.sorted();
+        let mut cumulative = 0.0;
+        for &(value, weight) in entries {
+            cumulative += weight;
+            if cumulative >= target {
+                return Ok(ScalarValue::Float64(Some(value)));
+            }
+        }
+        // Rounding can leave the sum of the weights just below the total
+        Ok(ScalarValue::Float64(entries.last().map(|&(value, _)| value)))
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self) + self.entries.size()
+    }
+}
+
+/// How [`PercentileWeightedFunction`] computes the percentile, selected by its optional fourth
+/// argument.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum PercentileWeightedMethod {
+    /// `'exact'`: buffer the values and their weights and interpolate between them
+    #[default]
+    Exact,
+    /// `'approx'`: estimate the percentile with a weighted [`TDigest`] in constant memory
+    Approximate,
+}
+
+impl PercentileWeightedMethod {
+    fn from_arg(acc_args: &AccumulatorArgs) -> Result<Self> {
+        let Some(expr) = acc_args.exprs.get(3) else {
+            return Ok(Self::default());
+        };
+        match literal_arg(expr, "percentile_weighted", "method")? {
+            ScalarValue::Utf8(Some(method)) | ScalarValue::LargeUtf8(Some(method))
+                if method.eq_ignore_ascii_case("exact") =>
+            {
+                Ok(Self::Exact)
+            }
+            ScalarValue::Utf8(Some(method)) | ScalarValue::LargeUtf8(Some(method))
+                if method.eq_ignore_ascii_case("approx") =>
+            {
+                Ok(Self::Approximate)
+            }
+            method => plan_err!("percentile_weighted expects a method of 'exact' or 'approx', got {method}"),
+        }
+    }
+}
+
+/// The `PercentileWeightedFunction` computes the continuous weighted percentile, e.g. of survey
+/// responses weighted by the number of people they represent.
+///
+/// - The values are sorted and the `k`-th one is placed at `S(k-1) / (S - w(k))` of the way from
+///   the smallest to the largest value, where `S(k)` is the cumulative weight of the first `k`
+///   values and `S` the total weight. The percentile interpolates between the values around
+///   `q`, so it matches `percentile_cont` when all the weights are equal.
+/// - `q` must be a literal in `[0, 1]`.
+/// - Rows where the value or the weight is NULL and rows of zero weight are skipped. Negative
+///   weights are an error.
+/// - `percentile_weighted(value, weight, q)` and `percentile_weighted(value, weight, q, 'exact')`
+///   buffer every row of a group, `percentile_weighted(value, weight, q, 'approx')` uses a
+///   t-digest, see [`PercentileWeightedMethod`].
+/// - The result is NULL if there are no rows.
+pub struct PercentileWeightedFunction {
+    signature: Signature,
+}
+
+impl Debug for PercentileWeightedFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PercentileWeightedFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for PercentileWeightedFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PercentileWeightedFunction {
+    pub fn new() -> Self {
+        let weighted = vec![DataType::Float64, DataType::Float64, DataType::Float64];
+        let with_method = [weighted.clone(), vec![DataType::Utf8]].concat();
+        Self {
+            signature: Signature::one_of(
+                vec![
+                    TypeSignature::Coercible(weighted),
+                    TypeSignature::Coercible(with_method),
+                ],
+                Volatility::Immutable,
+            ),
+        }
+    }
+}
+
+impl AggregateUDFImpl for PercentileWeightedFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "percentile_weighted"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Float64)
+    }
+
+    /// The method is not known when planning the state, so both methods share these fields and
+    /// leave the other method's fields empty
+    fn state_fields(&self, _args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(vec![
+            Field::new_list("values", Field::new("item", DataType::Float64, true), true),
+            Field::new_list("weights", Field::new("item", DataType::Float64, true), true),
+            Field::new("digest", DataType::Binary, true),
+        ])
+    }
+
+    fn accumulator(&self, acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        let q = literal_f64_arg(&acc_args.exprs[2], self.name(), "q")?;
+        if !(0.0..=1.0).contains(&q) {
+            return plan_err!("{} expects a q in [0, 1], got {q}", self.name());
+        }
+        Ok(match PercentileWeightedMethod::from_arg(&acc_args)? {
+            PercentileWeightedMethod::Exact => Box::new(PercentileWeightedAccumulator::new(q)),
+            PercentileWeightedMethod::Approximate => Box::new(ApproxPercentileWeightedAccumulator::new(q)),
+        })
+    }
+}
+
+/// Accumulator for [`PercentileWeightedMethod::Exact`], which buffers the `(value, weight)`
+/// pairs and sorts them when evaluated.
+#[derive(Debug)]
+pub struct PercentileWeightedAccumulator {
+    entries: WeightedQuantileBuffer,
+    q: f64,
+}
+
+impl PercentileWeightedAccumulator {
+    pub fn new(q: f64) -> Self {
+        Self {
+            entries: WeightedQuantileBuffer::new(),
+            q,
+        }
+    }
+}
+
+impl Accumulator for PercentileWeightedAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        self.entries.update_batch(&values[0], &values[1], "percentile_weighted")
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        self.entries.merge_batch(&states[0], &states[1], "percentile_weighted")
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        let [values, weights] = self.entries.state();
+        Ok(vec![values, weights, ScalarValue::Binary(None)])
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        let total = self.entries.total_weight();
+        let entries = self.entries.sorted();
+        if let [] | [_] = entries {
+            return Ok(ScalarValue::Float64(entries.first().map(|&(value, _)| value)));
+        }
+        // The position and the value of the previous entry, starting at the first one
+        let mut previous = (0.0, entries[0].0);
+        let mut cumulative = 0.0;
+        for &(value, weight) in entries {
+            let position = cumulative / (total - weight);
+            if position >= self.q {
+                let (previous_position, previous_value) = previous;
+                if position <= previous_position {
+                    return Ok(ScalarValue::Float64(Some(value)));
+                }
+                let fraction = (self.q - previous_position) / (position - previous_position);
+                return Ok(ScalarValue::Float64(Some(
+                    previous_value + (value - previous_value) * fraction,
+                )));
+            }
+            previous = (position, value);
+            cumulative += weight;
+        }
+        // Rounding can leave the position of the last value just below 1
+        Ok(ScalarValue::Float64(entries.last().map(|&(value, _)| value)))
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self) + self.entries.size()
+    }
+}
+
+/// Accumulator for [`PercentileWeightedMethod::Approximate`], adding the values with their
+/// weights to a [`TDigest`].
+#[derive(Debug)]
+pub struct ApproxPercentileWeightedAccumulator {
+    digest: TDigest,
+    q: f64,
+}
+
+impl ApproxPercentileWeightedAccumulator {
+    pub fn new(q: f64) -> Self {
+        Self {
+            digest: TDigest::default(),
+            q,
+        }
+    }
+}
+
+impl Accumulator for ApproxPercentileWeightedAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        if all_null_or_filtered(&values[0], None) || all_null_or_filtered(&values[1], None) {
+            return Ok(());
+        }
+        let (values, weights) = (as_float64_array(&values[0])?, as_float64_array(&values[1])?);
+        for (value, weight) in values.iter().zip(weights.iter()) {
+            let (Some(value), Some(weight)) = (value, weight) else {
+                continue;
+            };
+            if weight < 0.0 || weight.is_nan() {
+                return exec_err!("percentile_weighted expects non-negative weights, got {weight}");
+            }
+            self.digest.add_weighted(value, weight);
+        }
+        Ok(())
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        for bytes in as_binary_array(&states[2])?.iter().flatten() {
+            self.digest.merge(&TDigest::from_bytes(bytes)?);
+        }
+        Ok(())
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        let empty = || {
+            ScalarValue::List(Arc::new(single_row_list(Arc::new(Float64Array::from(
+                Vec::<f64>::new(),
+            )))))
+        };
+        Ok(vec![
+            empty(),
+            empty(),
+            ScalarValue::Binary(Some(self.digest.to_bytes())),
+        ])
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        Ok(ScalarValue::Float64(self.digest.quantile(self.q)))
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self) + self.digest.size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow::array::Float64Array;
+
+    use super::*;
+
+    fn evaluate(q: f64, values: Vec<Option<f64>>, weights: Vec<Option<f64>>) -> Result<ScalarValue> {
+        let mut acc = PercentileByWeightDiscAccumulator::new(q);
+        acc.update_batch(&[
+            Arc::new(Float64Array::from(values)),
+            Arc::new(Float64Array::from(weights)),
+        ])?;
+        acc.evaluate()
+    }
+
+    #[test]
+    fn test_percentile_by_weight_disc() -> Result<()> {
+        let values = vec![Some(3.0), Some(1.0), Some(2.0), None, Some(4.0)];
+        let weights = vec![Some(1.0), Some(2.0), Some(1.0), Some(10.0), Some(0.0)];
+        assert_eq!(evaluate(0.0, values.clone(), weights.clone())?, ScalarValue::from(1.0));
+        assert_eq!(evaluate(0.5, values.clone(), weights.clone())?, ScalarValue::from(1.0));
+        assert_eq!(evaluate(0.51, values.clone(), weights.clone())?, ScalarValue::from(2.0));
+        assert_eq!(evaluate(1.0, values, weights)?, ScalarValue::from(3.0));
+        assert_eq!(
+            evaluate(0.5, vec![Some(1.0)], vec![Some(0.0)])?,
+            ScalarValue::Float64(None)
+        );
+        assert!(evaluate(0.5, vec![Some(1.0)], vec![Some(-1.0)]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge() -> Result<()> {
+        let mut left = PercentileByWeightDiscAccumulator::new(0.75);
+        left.update_batch(&[
+            Arc::new(Float64Array::from(vec![10.0, 20.0])),
+            Arc::new(Float64Array::from(vec![1.0, 1.0])),
+        ])?;
+        let mut right = PercentileByWeightDiscAccumulator::new(0.75);
+        right.update_batch(&[
+            Arc::new(Float64Array::from(vec![30.0, 40.0])),
+            Arc::new(Float64Array::from(vec![1.0, 1.0])),
+        ])?;
+        let states = right
+            .state()?
+            .iter()
+            .map(|state| state.to_array())
+            .collect::<Result<Vec<_>>>()?;
+        left.merge_batch(&states)?;
+        assert_eq!(left.evaluate()?, ScalarValue::from(30.0));
+        Ok(())
+    }
+
+    fn weighted(acc: &mut dyn Accumulator, values: Vec<f64>, weights: Vec<f64>) -> Result<f64> {
+        acc.update_batch(&[
+            Arc::new(Float64Array::from(values)),
+            Arc::new(Float64Array::from(weights)),
+        ])?;
+        match acc.evaluate()? {
+            ScalarValue::Float64(Some(percentile)) => Ok(percentile),
+            percentile => panic!("expected a non-null percentile, got {percentile}"),
+        }
+    }
+
+    #[test]
+    fn test_percentile_weighted_interpolates() -> Result<()> {
+        // Equal weights interpolate as percentile_cont: 1, 2, 3, 4 at 0, 1/3, 2/3, 1
+        let values = vec![4.0, 1.0, 3.0, 2.0];
+        for (q, expected) in [(0.0, 1.0), (0.5, 2.5), (0.75, 3.25), (1.0, 4.0)] {
+            let mut acc = PercentileWeightedAccumulator::new(q);
+            assert_eq!(weighted(&mut acc, values.clone(), vec![2.0; 4])?, expected);
+        }
+        // 1, 2 and 3 at 0, 1 / (4 - 2) and 3 / (4 - 1)
+        let (values, weights) = (vec![1.0, 2.0, 3.0], vec![1.0, 2.0, 1.0]);
+        for (q, expected) in [(0.25, 1.5), (0.5, 2.0), (0.75, 2.5)] {
+            let mut acc = PercentileWeightedAccumulator::new(q);
+            assert_eq!(weighted(&mut acc, values.clone(), weights.clone())?, expected);
+        }
+        let mut acc = PercentileWeightedAccumulator::new(0.5);
+        assert_eq!(weighted(&mut acc, vec![7.0], vec![3.0])?, 7.0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_approx_percentile_weighted_matches_exact() -> Result<()> {
+        let values: Vec<f64> = (0..10_000).map(|i| ((i * 7919) % 10_000) as f64).collect();
+        let weights: Vec<f64> = values.iter().map(|value| 1.0 + (*value as i64 % 3) as f64).collect();
+        let exact = weighted(
+            &mut PercentileWeightedAccumulator::new(0.9),
+            values.clone(),
+            weights.clone(),
+        )?;
+        let approx = weighted(&mut ApproxPercentileWeightedAccumulator::new(0.9), values, weights)?;
+        assert!((exact - approx).abs() < 20.0, "exact {exact}, approx {approx}");
+        Ok(())
+    }
+}
@@ -0,0 +1,384 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::any::Any;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use arrow::array::{Array, ArrayRef, StructArray, UInt32Array};
+use datafusion::arrow;
+use datafusion::arrow::compute::{sum, sum_checked, take};
+use datafusion::arrow::datatypes::{DataType, Field, Fields};
+use datafusion::common::cast::{as_float64_array, as_int64_array, as_string_array, as_struct_array, as_uint64_array};
+use datafusion::common::{exec_err, plan_err, Result, ScalarValue};
+use datafusion::functions_aggregate::min_max::MaxAccumulator;
+use datafusion::logical_expr::function::{AccumulatorArgs, StateFieldsArgs};
+use datafusion::logical_expr::{Accumulator, AggregateUDFImpl, Signature, Volatility};
+
+use crate::compat::arg_type;
+
+make_udaf_expr_and_func!(
+    PivotAggFunction,
+    pivot_agg,
+    key value keys,
+    "Returns a struct with a field per expected key, holding the value of the rows with that key.",
+    pivot_agg_udaf
+);
+
+/// How `pivot_agg` reduces the values of the rows with the same key.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum PivotPolicy {
+    /// The first non-null value, in the order the rows are aggregated
+    #[default]
+    First,
+    /// The sum of the values, as BIGINT, BIGINT UNSIGNED or DOUBLE
+    Sum,
+    /// The largest value
+    Max,
+}
+
+impl PivotPolicy {
+    /// The type values are aggregated as, which is also the type of the fields of the result
+    fn value_type(self, value_type: &DataType) -> Result<DataType> {
+        if self != PivotPolicy::Sum {
+            return Ok(value_type.clone());
+        }
+        match value_type {
+            DataType::Null | DataType::Int8 | DataType::Int16 | DataType::Int32 | DataType::Int64 => {
+                Ok(DataType::Int64)
+            }
+            DataType::UInt8 | DataType::UInt16 | DataType::UInt32 | DataType::UInt64 => Ok(DataType::UInt64),
+            DataType::Float16 | DataType::Float32 | DataType::Float64 | DataType::Decimal128(_, _) => {
+                Ok(DataType::Float64)
+            }
+            _ => plan_err!("pivot_agg can only sum numeric values, got {value_type}"),
+        }
+    }
+}
+
+/// The struct type of the result, with a nullable field of `value_type` per key
+fn pivot_type(keys: &Fields, value_type: &DataType) -> DataType {
+    DataType::Struct(
+        keys.iter()
+            .map(|key| Field::new(key.name(), value_type.clone(), true))
+            .collect(),
+    )
+}
+
+/// The `PivotAggFunction` pivots rows of keys and values into a struct with one field per
+/// expected key, `pivot_agg(key, value, keys)`.
+///
+/// - `keys` is a struct whose field names are the expected keys, typically a literal such as
+///   `{'us': NULL, 'eu': NULL}` or `named_struct('us', NULL, 'eu', NULL)`. Its values are
+///   ignored: DataFusion 42 only passes the types of the arguments when planning the result
+///   type, so the keys must be part of the type of the argument.
+/// - Keys are compared as strings. Rows with NULL keys, keys that aren't expected or NULL
+///   values are skipped, and the fields of keys without rows are NULL.
+/// - The values of the rows with the same key are reduced with a [`PivotPolicy`], by default
+///   their first value.
+pub struct PivotAggFunction {
+    signature: Signature,
+    policy: PivotPolicy,
+}
+
+impl Debug for PivotAggFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PivotAggFunction")
+            .field("signature", &self.signature)
+            .field("policy", &self.policy)
+            .finish()
+    }
+}
+
+impl Default for PivotAggFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PivotAggFunction {
+    pub fn new() -> Self {
+        Self::new_with_policy(PivotPolicy::default())
+    }
+
+    pub fn new_with_policy(policy: PivotPolicy) -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+            policy,
+        }
+    }
+}
+
+impl AggregateUDFImpl for PivotAggFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "pivot_agg"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        let [_, value_type, keys_type] = arg_types else {
+            return plan_err!("pivot_agg expects a key, a value and the expected keys");
+        };
+        match keys_type {
+            DataType::Struct(keys) if !keys.is_empty() => Ok(vec![
+                DataType::Utf8,
+                self.policy.value_type(value_type)?,
+                keys_type.clone(),
+            ]),
+            _ => plan_err!(
+                "pivot_agg expects the keys as a struct literal such as {{'a': NULL, 'b': NULL}}, got {keys_type}"
+            ),
+        }
+    }
+
+    fn return_type(&self, arg_types: &[DataType]) -> Result<DataType> {
+        let DataType::Struct(keys) = &arg_types[2] else {
+            return plan_err!("pivot_agg expects the keys as a struct, got {}", arg_types[2]);
+        };
+        Ok(pivot_type(keys, &arg_types[1]))
+    }
+
+    fn is_nullable(&self) -> bool {
+        false
+    }
+
+    fn state_fields(&self, args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(vec![Field::new("pivot", args.return_type.clone(), true)])
+    }
+
+    fn accumulator(&self, acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        let DataType::Struct(keys) = arg_type(&acc_args, 2)? else {
+            return exec_err!("pivot_agg expects the keys as a struct");
+        };
+        let value_type = arg_type(&acc_args, 1)?;
+        Ok(Box::new(PivotAccumulator::try_new(&keys, &value_type, self.policy)?))
+    }
+}
+
+/// The reduced values of one key
+#[derive(Debug)]
+enum PivotCell {
+    First(Option<ScalarValue>),
+    Sum(ScalarValue),
+    Max(MaxAccumulator),
+}
+
+impl PivotCell {
+    fn try_new(policy: PivotPolicy, value_type: &DataType) -> Result<Self> {
+        Ok(match policy {
+            PivotPolicy::First => PivotCell::First(None),
+            PivotPolicy::Sum => PivotCell::Sum(ScalarValue::try_from(value_type)?),
+            PivotPolicy::Max => PivotCell::Max(MaxAccumulator::try_new(value_type)?),
+        })
+    }
+
+    /// Reduces the values of the key, or partial results of other accumulators, into the cell
+    fn update(&mut self, values: &ArrayRef) -> Result<()> {
+        match self {
+            PivotCell::First(first @ None) => {
+                if let Some(row) = (0..values.len()).find(|row| values.is_valid(*row)) {
+                    *first = Some(ScalarValue::try_from_array(values, row)?);
+                }
+            }
+            PivotCell::First(Some(_)) => {}
+            PivotCell::Sum(total) => {
+                let batch_total = match values.data_type() {
+                    DataType::Int64 => ScalarValue::Int64(sum_checked(as_int64_array(values)?)?),
+                    DataType::UInt64 => ScalarValue::UInt64(sum_checked(as_uint64_array(values)?)?),
+                    DataType::Float64 => ScalarValue::Float64(sum(as_float64_array(values)?)),
+                    value_type => return exec_err!("pivot_agg can only sum numeric values, got {value_type}"),
+                };
+                *total = match (total.is_null(), batch_total.is_null()) {
+                    (_, true) => return Ok(()),
+                    (true, false) => batch_total,
+                    (false, false) => total.add_checked(batch_total)?,
+                };
+            }
+            PivotCell::Max(max) => max.update_batch(std::slice::from_ref(values))?,
+        }
+        Ok(())
+    }
+
+    fn evaluate(&mut self, value_type: &DataType) -> Result<ScalarValue> {
+        match self {
+            PivotCell::First(first) => first.clone().map_or_else(|| ScalarValue::try_from(value_type), Ok),
+            PivotCell::Sum(total) => Ok(total.clone()),
+            PivotCell::Max(max) => max.evaluate(),
+        }
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self)
+            + match self {
+                PivotCell::First(first) => first.as_ref().map_or(0, ScalarValue::size),
+                PivotCell::Sum(total) => total.size(),
+                PivotCell::Max(max) => max.size(),
+            }
+    }
+}
+
+/// Accumulator for [`PivotAggFunction`], reducing the values of every expected key into a
+/// [`PivotCell`].
+#[derive(Debug)]
+pub struct PivotAccumulator {
+    keys: Fields,
+    value_type: DataType,
+    /// Index of every expected key in `keys` and `cells`
+    key_indices: HashMap<String, usize>,
+    cells: Vec<PivotCell>,
+}
+
+impl PivotAccumulator {
+    pub fn try_new(keys: &Fields, value_type: &DataType, policy: PivotPolicy) -> Result<Self> {
+        Ok(Self {
+            keys: keys.clone(),
+            value_type: value_type.clone(),
+            key_indices: keys
+                .iter()
+                .enumerate()
+                .map(|(index, key)| (key.name().clone(), index))
+                .collect(),
+            cells: keys
+                .iter()
+                .map(|_| PivotCell::try_new(policy, value_type))
+                .collect::<Result<_>>()?,
+        })
+    }
+}
+
+impl Accumulator for PivotAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        let (keys, values) = (as_string_array(&values[0])?, &values[1]);
+        let mut rows = vec![vec![]; self.cells.len()];
+        for (row, key) in keys.iter().enumerate() {
+            if let Some(&index) = key.and_then(|key| self.key_indices.get(key)) {
+                rows[index].push(row as u32);
+            }
+        }
+        for (cell, rows) in self.cells.iter_mut().zip(rows) {
+            if !rows.is_empty() {
+                cell.update(&take(values, &UInt32Array::from(rows), None)?)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        let pivots = as_struct_array(&states[0])?;
+        for (cell, values) in self.cells.iter_mut().zip(pivots.columns()) {
+            cell.update(values)?;
+        }
+        Ok(())
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        Ok(vec![self.evaluate()?])
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        let DataType::Struct(fields) = pivot_type(&self.keys, &self.value_type) else {
+            unreachable!()
+        };
+        let columns = self
+            .cells
+            .iter_mut()
+            .map(|cell| cell.evaluate(&self.value_type)?.to_array())
+            .collect::<Result<Vec<_>>>()?;
+        Ok(ScalarValue::Struct(Arc::new(StructArray::try_new(
+            fields, columns, None,
+        )?)))
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self)
+            + self.key_indices.capacity() * std::mem::size_of::<(String, usize)>()
+            + self.keys.iter().map(|key| key.name().capacity()).sum::<usize>()
+            + self.cells.iter().map(PivotCell::size).sum::<usize>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::array::{Int64Array, StringArray};
+
+    use super::*;
+
+    /// A batch of `(key, value)` rows
+    type Batch<'a> = (Vec<Option<&'a str>>, Vec<Option<i64>>);
+
+    fn pivot(policy: PivotPolicy, batches: &[Batch]) -> Result<ScalarValue> {
+        let keys = Fields::from(vec![
+            Field::new("a", DataType::Null, true),
+            Field::new("b", DataType::Null, true),
+            Field::new("c", DataType::Null, true),
+        ]);
+        let mut acc = PivotAccumulator::try_new(&keys, &DataType::Int64, policy)?;
+        for (batch_keys, batch_values) in batches {
+            let mut partial = PivotAccumulator::try_new(&keys, &DataType::Int64, policy)?;
+            partial.update_batch(&[
+                Arc::new(StringArray::from(batch_keys.clone())),
+                Arc::new(Int64Array::from(batch_values.clone())),
+            ])?;
+            acc.merge_batch(&[partial.state()?[0].to_array()?])?;
+        }
+        acc.evaluate()
+    }
+
+    fn fields(pivot: ScalarValue) -> Vec<ScalarValue> {
+        let ScalarValue::Struct(pivot) = pivot else {
+            unreachable!()
+        };
+        pivot
+            .columns()
+            .iter()
+            .map(|column| ScalarValue::try_from_array(column, 0).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_policies_reduce_values_of_each_key() -> Result<()> {
+        let batches = [
+            (
+                vec![Some("a"), Some("b"), Some("a"), None],
+                vec![None, Some(2), Some(5), Some(9)],
+            ),
+            (vec![Some("a"), Some("x"), Some("b")], vec![Some(3), Some(7), Some(4)]),
+        ];
+        let int = |value: Option<i64>| ScalarValue::Int64(value);
+        assert_eq!(
+            fields(pivot(PivotPolicy::First, &batches)?),
+            vec![int(Some(5)), int(Some(2)), int(None)]
+        );
+        assert_eq!(
+            fields(pivot(PivotPolicy::Sum, &batches)?),
+            vec![int(Some(8)), int(Some(6)), int(None)]
+        );
+        assert_eq!(
+            fields(pivot(PivotPolicy::Max, &batches)?),
+            vec![int(Some(5)), int(Some(4)), int(None)]
+        );
+        Ok(())
+    }
+}
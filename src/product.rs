@@ -0,0 +1,467 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use arrow::array::{Array, ArrayRef, Float64Array, Int64Array, UInt64Array};
+use datafusion::arrow;
+use datafusion::arrow::datatypes::{DataType, Field};
+use datafusion::common::cast::{as_float64_array, as_int64_array};
+use datafusion::common::{downcast_value, exec_err, DataFusionError, Result, ScalarValue};
+use datafusion::logical_expr::function::{AccumulatorArgs, StateFieldsArgs};
+use datafusion::logical_expr::{Accumulator, AggregateUDFImpl, Signature, Volatility};
+use std::any::Any;
+use std::fmt::Debug;
+
+use crate::common::coercion::NumericCoercion;
+use crate::common::nulls::all_null_or_filtered;
+use crate::compat::arg_type;
+
+make_udaf_expr_and_func!(
+    ProductFunction,
+    product,
+    x,
+    "Calculates the product of all non-null values.",
+    product_udaf
+);
+
+/// How `product` handles integer products that overflow `Int64`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Evaluation fails on overflow.
+    #[default]
+    Error,
+    /// The product saturates at `i64::MIN` or `i64::MAX`.
+    Saturate,
+    /// The result is `Float64`; the product is exact until it overflows `Int64` and
+    /// approximated with floating point afterwards.
+    PromoteToFloat64,
+}
+
+/// How `product` multiplies values.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ProductMethod {
+    /// Values are multiplied directly.
+    #[default]
+    Direct,
+    /// The logarithms of the absolute values are summed and the product is recovered with
+    /// `exp`, so very long groups do not overflow or underflow intermediate results. The
+    /// result is always `Float64` and approximate.
+    LogDomain,
+}
+
+/// The `ProductFunction` multiplies all non-null values of a group.
+///
+/// - Inputs are coerced with [`NumericCoercion::Int64OrFloat64`]: integers are multiplied as
+///   `Int64` and handled according to the [`OverflowPolicy`], floating point and decimal inputs
+///   are multiplied as `Float64`.
+/// - The result is NULL if there are no non-null values.
+pub struct ProductFunction {
+    signature: Signature,
+    overflow_policy: OverflowPolicy,
+    method: ProductMethod,
+}
+
+impl Debug for ProductFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ProductFunction")
+            .field("signature", &self.signature)
+            .field("overflow_policy", &self.overflow_policy)
+            .field("method", &self.method)
+            .finish()
+    }
+}
+
+impl Default for ProductFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ProductFunction {
+    pub fn new() -> Self {
+        Self::new_with_options(OverflowPolicy::default(), ProductMethod::default())
+    }
+
+    pub fn new_with_options(overflow_policy: OverflowPolicy, method: ProductMethod) -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+            overflow_policy,
+            method,
+        }
+    }
+
+    fn is_integer_product(&self, input_type: &DataType) -> bool {
+        self.method == ProductMethod::Direct && input_type == &DataType::Int64
+    }
+}
+
+impl AggregateUDFImpl for ProductFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "product"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        NumericCoercion::Int64OrFloat64.coerce_all(self.name(), &["x"], arg_types)
+    }
+
+    fn return_type(&self, arg_types: &[DataType]) -> Result<DataType> {
+        if self.is_integer_product(&arg_types[0]) && self.overflow_policy != OverflowPolicy::PromoteToFloat64 {
+            Ok(DataType::Int64)
+        } else {
+            Ok(DataType::Float64)
+        }
+    }
+
+    fn state_fields(&self, args: StateFieldsArgs) -> Result<Vec<Field>> {
+        if self.method == ProductMethod::LogDomain {
+            return Ok(vec![
+                Field::new("count", DataType::UInt64, true),
+                Field::new("log_sum", DataType::Float64, true),
+                Field::new("negative_count", DataType::UInt64, true),
+                Field::new("zero_count", DataType::UInt64, true),
+            ]);
+        }
+        if self.is_integer_product(&args.input_types[0]) {
+            return Ok(vec![
+                Field::new("product", DataType::Int64, true),
+                Field::new("promoted_product", DataType::Float64, true),
+            ]);
+        }
+        Ok(vec![Field::new("product", DataType::Float64, true)])
+    }
+
+    fn accumulator(&self, acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        let data_type = arg_type(&acc_args, 0)?;
+        if self.method == ProductMethod::LogDomain {
+            return Ok(Box::new(LogProductAccumulator::new(data_type)));
+        }
+        if self.is_integer_product(&data_type) {
+            return Ok(Box::new(IntProductAccumulator::new(self.overflow_policy)));
+        }
+        Ok(Box::new(FloatProductAccumulator::new()))
+    }
+}
+
+/// Accumulator for the product of `Int64` values.
+///
+/// With [`OverflowPolicy::PromoteToFloat64`], `promoted` holds the product from the first
+/// overflow on and `product` is unused.
+#[derive(Debug, Default)]
+pub struct IntProductAccumulator {
+    product: Option<i64>,
+    promoted: Option<f64>,
+    overflow_policy: OverflowPolicy,
+}
+
+impl IntProductAccumulator {
+    pub fn new(overflow_policy: OverflowPolicy) -> Self {
+        Self {
+            overflow_policy,
+            ..Default::default()
+        }
+    }
+
+    fn multiply_promoted(&mut self, value: f64) {
+        self.product = None;
+        self.promoted = Some(self.promoted.unwrap_or(1.0) * value);
+    }
+
+    fn multiply(&mut self, value: i64) -> Result<()> {
+        if self.promoted.is_some() {
+            self.multiply_promoted(value as f64);
+            return Ok(());
+        }
+        let Some(product) = self.product else {
+            self.product = Some(value);
+            return Ok(());
+        };
+        match (product.checked_mul(value), self.overflow_policy) {
+            (Some(product), _) => self.product = Some(product),
+            (None, OverflowPolicy::Error) => {
+                return exec_err!("product overflowed Int64 multiplying {} by {}", product, value);
+            }
+            (None, OverflowPolicy::Saturate) => self.product = Some(product.saturating_mul(value)),
+            (None, OverflowPolicy::PromoteToFloat64) => {
+                self.promoted = Some(product as f64);
+                self.multiply_promoted(value as f64);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Accumulator for IntProductAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        if all_null_or_filtered(&values[0], None) {
+            return Ok(());
+        }
+        let array = as_int64_array(&values[0])?;
+        array.iter().flatten().try_for_each(|value| self.multiply(value))
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        let products = downcast_value!(states[0], Int64Array);
+        let promoted = downcast_value!(states[1], Float64Array);
+        for i in 0..products.len() {
+            if promoted.is_valid(i) {
+                if let Some(product) = self.product {
+                    self.multiply_promoted(product as f64);
+                }
+                self.multiply_promoted(promoted.value(i));
+            } else if products.is_valid(i) {
+                self.multiply(products.value(i))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        if self.overflow_policy == OverflowPolicy::PromoteToFloat64 {
+            return Ok(ScalarValue::Float64(self.promoted.or(self.product.map(|p| p as f64))));
+        }
+        Ok(ScalarValue::Int64(self.product))
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self)
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        Ok(vec![
+            ScalarValue::Int64(self.product),
+            ScalarValue::Float64(self.promoted),
+        ])
+    }
+}
+
+/// Accumulator for the product of `Float64` values.
+#[derive(Debug, Default)]
+pub struct FloatProductAccumulator {
+    product: Option<f64>,
+}
+
+impl FloatProductAccumulator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn multiply(&mut self, value: f64) {
+        self.product = Some(self.product.unwrap_or(1.0) * value);
+    }
+}
+
+impl Accumulator for FloatProductAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        if all_null_or_filtered(&values[0], None) {
+            return Ok(());
+        }
+        let array = as_float64_array(&values[0])?;
+        array.iter().flatten().for_each(|value| self.multiply(value));
+        Ok(())
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        let products = downcast_value!(states[0], Float64Array);
+        products.iter().flatten().for_each(|value| self.multiply(value));
+        Ok(())
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        Ok(ScalarValue::Float64(self.product))
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self)
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        Ok(vec![ScalarValue::Float64(self.product)])
+    }
+}
+
+/// Accumulator for [`ProductMethod::LogDomain`], which sums `ln(|x|)` and tracks the sign and
+/// zeros separately.
+#[derive(Debug)]
+pub struct LogProductAccumulator {
+    count: u64,
+    log_sum: f64,
+    negative_count: u64,
+    zero_count: u64,
+    input_type: DataType,
+}
+
+impl LogProductAccumulator {
+    pub fn new(input_type: DataType) -> Self {
+        Self {
+            count: 0,
+            log_sum: 0.0,
+            negative_count: 0,
+            zero_count: 0,
+            input_type,
+        }
+    }
+
+    fn multiply(&mut self, value: f64) {
+        self.count += 1;
+        if value == 0.0 {
+            self.zero_count += 1;
+            return;
+        }
+        if value.is_sign_negative() {
+            self.negative_count += 1;
+        }
+        self.log_sum += value.abs().ln();
+    }
+}
+
+impl Accumulator for LogProductAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        if all_null_or_filtered(&values[0], None) {
+            return Ok(());
+        }
+        if self.input_type == DataType::Int64 {
+            let array = as_int64_array(&values[0])?;
+            array.iter().flatten().for_each(|value| self.multiply(value as f64));
+        } else {
+            let array = as_float64_array(&values[0])?;
+            array.iter().flatten().for_each(|value| self.multiply(value));
+        }
+        Ok(())
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        if all_null_or_filtered(&states[0], None) {
+            return Ok(());
+        }
+        let counts = downcast_value!(states[0], UInt64Array);
+        let log_sums = downcast_value!(states[1], Float64Array);
+        let negative_counts = downcast_value!(states[2], UInt64Array);
+        let zero_counts = downcast_value!(states[3], UInt64Array);
+
+        for i in 0..counts.len() {
+            if counts.is_null(i) {
+                continue;
+            }
+            self.count += counts.value(i);
+            self.log_sum += log_sums.value(i);
+            self.negative_count += negative_counts.value(i);
+            self.zero_count += zero_counts.value(i);
+        }
+        Ok(())
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        if self.count == 0 {
+            return Ok(ScalarValue::Float64(None));
+        }
+        if self.zero_count > 0 {
+            return Ok(ScalarValue::Float64(Some(0.0)));
+        }
+        let sign = if self.negative_count % 2 == 0 { 1.0 } else { -1.0 };
+        Ok(ScalarValue::Float64(Some(sign * self.log_sum.exp())))
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self)
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        Ok(vec![
+            ScalarValue::from(self.count),
+            ScalarValue::from(self.log_sum),
+            ScalarValue::from(self.negative_count),
+            ScalarValue::from(self.zero_count),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    fn int_product_of(chunks: Vec<Vec<Option<i64>>>, policy: OverflowPolicy) -> Result<ScalarValue> {
+        let mut merged = IntProductAccumulator::new(policy);
+        for chunk in chunks {
+            let mut partial = IntProductAccumulator::new(policy);
+            partial.update_batch(&[Arc::new(Int64Array::from(chunk))])?;
+            let state = partial
+                .state()?
+                .iter()
+                .map(|s| s.to_array())
+                .collect::<Result<Vec<_>>>()?;
+            merged.merge_batch(&state)?;
+        }
+        merged.evaluate()
+    }
+
+    #[test]
+    fn test_int_product() -> Result<()> {
+        let chunks = vec![vec![Some(2), None, Some(-3)], vec![], vec![None], vec![Some(7)]];
+        assert_eq!(
+            int_product_of(chunks, OverflowPolicy::Error)?,
+            ScalarValue::Int64(Some(-42))
+        );
+        assert_eq!(
+            int_product_of(vec![vec![None]], OverflowPolicy::Error)?,
+            ScalarValue::Int64(None)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_int_product_overflow_policy() -> Result<()> {
+        let chunks = || vec![vec![Some(1 << 40)], vec![Some(-(1 << 30))], vec![Some(2)]];
+        assert!(int_product_of(chunks(), OverflowPolicy::Error).is_err());
+        assert_eq!(
+            int_product_of(chunks(), OverflowPolicy::Saturate)?,
+            ScalarValue::Int64(Some(i64::MIN))
+        );
+        assert_eq!(
+            int_product_of(chunks(), OverflowPolicy::PromoteToFloat64)?,
+            ScalarValue::Float64(Some(-(2.0f64.powi(71))))
+        );
+        assert_eq!(
+            int_product_of(vec![vec![Some(3)]], OverflowPolicy::PromoteToFloat64)?,
+            ScalarValue::Float64(Some(3.0))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_log_product() -> Result<()> {
+        let mut acc = LogProductAccumulator::new(DataType::Float64);
+        acc.update_batch(&[Arc::new(Float64Array::from(vec![1e300; 1000]))])?;
+        acc.update_batch(&[Arc::new(Float64Array::from(vec![1e-300; 999]))])?;
+        acc.update_batch(&[Arc::new(Float64Array::from(vec![-2.0]))])?;
+        let ScalarValue::Float64(Some(product)) = acc.evaluate()? else {
+            panic!("expected a non-null product");
+        };
+        assert!((product + 2e300).abs() <= 1e-6 * 2e300, "got {product}");
+
+        acc.update_batch(&[Arc::new(Float64Array::from(vec![0.0]))])?;
+        assert_eq!(acc.evaluate()?, ScalarValue::Float64(Some(0.0)));
+        Ok(())
+    }
+}
@@ -0,0 +1,257 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::any::Any;
+use std::fmt::Debug;
+
+use arrow::array::ArrayRef;
+use datafusion::arrow;
+use datafusion::arrow::datatypes::{DataType, Field};
+use datafusion::common::{plan_err, Result, ScalarValue};
+use datafusion::logical_expr::function::{AccumulatorArgs, StateFieldsArgs};
+use datafusion::logical_expr::{Accumulator, AggregateUDFImpl, Signature, TypeSignature, Volatility};
+
+use crate::common::args::{literal_arg, literal_f64_arg};
+use crate::common::quantile_buffer::QuantileBuffer;
+
+make_udaf_expr_and_func!(
+    QuantileExactFunction,
+    quantile_exact,
+    x q,
+    "Computes the exact `q`-th quantile of the values with the default linear interpolation.",
+    quantile_exact_udaf
+);
+
+/// How [`QuantileExactFunction`] picks or interpolates the quantile between the sorted values,
+/// selected by its optional third argument, named as NumPy's `quantile` methods.
+///
+/// With `n` sorted values `x[0]`, ..., `x[n - 1]`, the methods but `Exclusive` place the
+/// quantile at the index `q * (n - 1)`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum QuantileMethod {
+    /// `'linear'` or `'inclusive'`: interpolate between the values around the index, as
+    /// `percentile_cont`, NumPy's default, Excel's `PERCENTILE.INC` and ClickHouse's
+    /// `quantileExactInclusive` do
+    #[default]
+    Linear,
+    /// `'lower'`: the value below the index
+    Lower,
+    /// `'higher'`: the value above the index
+    Higher,
+    /// `'nearest'`: the value nearest to the index, the even one on ties as in NumPy
+    Nearest,
+    /// `'midpoint'`: the mean of the values around the index
+    Midpoint,
+    /// `'exclusive'`: interpolate at the index `q * (n + 1) - 1`, as Excel's `PERCENTILE.EXC`,
+    /// NumPy's `'weibull'` and ClickHouse's `quantileExactExclusive` do. Quantiles below the
+    /// first and above the last value are clamped to them, where Excel fails instead.
+    Exclusive,
+}
+
+impl QuantileMethod {
+    fn from_arg(acc_args: &AccumulatorArgs) -> Result<Self> {
+        let Some(expr) = acc_args.exprs.get(2) else {
+            return Ok(Self::default());
+        };
+        let method = match literal_arg(expr, "quantile_exact", "method")? {
+            ScalarValue::Utf8(Some(method)) | ScalarValue::LargeUtf8(Some(method)) => method.to_ascii_lowercase(),
+            method => return plan_err!("quantile_exact expects a method name, got {method}"),
+        };
+        match method.as_str() {
+            "linear" | "inclusive" => Ok(Self::Linear),
+            "lower" => Ok(Self::Lower),
+            "higher" => Ok(Self::Higher),
+            "nearest" => Ok(Self::Nearest),
+            "midpoint" => Ok(Self::Midpoint),
+            "exclusive" => Ok(Self::Exclusive),
+            _ => plan_err!(
+                "quantile_exact expects a method of 'linear', 'inclusive', 'lower', 'higher', 'nearest', \
+                'midpoint' or 'exclusive', got {method}"
+            ),
+        }
+    }
+
+    /// The `q`-th quantile of the non-empty sorted `values`
+    pub fn quantile(&self, values: &[f64], q: f64) -> f64 {
+        let last = values.len() - 1;
+        let index = match self {
+            Self::Exclusive => (q * (values.len() + 1) as f64 - 1.0).clamp(0.0, last as f64),
+            _ => q * last as f64,
+        };
+        let (below, above) = (index.floor() as usize, index.ceil() as usize);
+        let fraction = index - below as f64;
+        match self {
+            Self::Linear | Self::Exclusive => values[below] + (values[above] - values[below]) * fraction,
+            Self::Lower => values[below],
+            Self::Higher => values[above],
+            Self::Nearest if fraction == 0.5 => values[if below % 2 == 0 { below } else { above }],
+            Self::Nearest => values[index.round() as usize],
+            Self::Midpoint => (values[below] + values[above]) / 2.0,
+        }
+    }
+}
+
+/// The `QuantileExactFunction` computes the exact quantile of the values,
+/// `quantile_exact(x, q[, method])`, buffering every value of a group.
+///
+/// - `q` must be a literal in `[0, 1]`, and the methods of [`QuantileMethod`] match the
+///   results of other systems exactly.
+/// - NULL values are ignored and the result is NULL if there are no values.
+pub struct QuantileExactFunction {
+    signature: Signature,
+}
+
+impl Debug for QuantileExactFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("QuantileExactFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for QuantileExactFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl QuantileExactFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::one_of(
+                vec![
+                    TypeSignature::Coercible(vec![DataType::Float64, DataType::Float64]),
+                    TypeSignature::Coercible(vec![DataType::Float64, DataType::Float64, DataType::Utf8]),
+                ],
+                Volatility::Immutable,
+            ),
+        }
+    }
+}
+
+impl AggregateUDFImpl for QuantileExactFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "quantile_exact"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Float64)
+    }
+
+    fn state_fields(&self, _args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(vec![Field::new_list(
+            "values",
+            Field::new("item", DataType::Float64, true),
+            true,
+        )])
+    }
+
+    fn accumulator(&self, acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        let q = literal_f64_arg(&acc_args.exprs[1], self.name(), "q")?;
+        if !(0.0..=1.0).contains(&q) {
+            return plan_err!("{} expects a q in [0, 1], got {q}", self.name());
+        }
+        Ok(Box::new(QuantileExactAccumulator::new(
+            q,
+            QuantileMethod::from_arg(&acc_args)?,
+        )))
+    }
+}
+
+/// Accumulator for [`QuantileExactFunction`], which buffers the values and sorts them when
+/// evaluated.
+#[derive(Debug)]
+pub struct QuantileExactAccumulator {
+    values: QuantileBuffer,
+    q: f64,
+    method: QuantileMethod,
+}
+
+impl QuantileExactAccumulator {
+    pub fn new(q: f64, method: QuantileMethod) -> Self {
+        Self {
+            values: QuantileBuffer::new(),
+            q,
+            method,
+        }
+    }
+}
+
+impl Accumulator for QuantileExactAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        self.values.update_batch(&values[0])
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        self.values.merge_batch(&states[0])
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        Ok(vec![self.values.state()])
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        let values = self.values.sorted();
+        if values.is_empty() {
+            return Ok(ScalarValue::Float64(None));
+        }
+        Ok(ScalarValue::Float64(Some(self.method.quantile(values, self.q))))
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self) + self.values.size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quantile_methods_match_numpy() {
+        // np.quantile([1, 2, 3, 4, 10], q, method=...)
+        let values = [1.0, 2.0, 3.0, 4.0, 10.0];
+        let cases = [
+            (QuantileMethod::Linear, 0.3, 2.2),
+            (QuantileMethod::Lower, 0.3, 2.0),
+            (QuantileMethod::Higher, 0.3, 3.0),
+            (QuantileMethod::Nearest, 0.3, 2.0),
+            (QuantileMethod::Midpoint, 0.3, 2.5),
+            (QuantileMethod::Exclusive, 0.3, 1.8),
+            (QuantileMethod::Linear, 0.9, 7.6),
+            (QuantileMethod::Exclusive, 0.9, 10.0),
+            (QuantileMethod::Exclusive, 0.1, 1.0),
+        ];
+        for (method, q, expected) in cases {
+            let quantile = method.quantile(&values, q);
+            assert!((quantile - expected).abs() < 1e-9, "{method:?} {q}: {quantile}");
+        }
+        // Ties at the index 1.5 and 2.5 go to the even index
+        assert_eq!(QuantileMethod::Nearest.quantile(&values[..4], 0.5), 3.0);
+        assert_eq!(QuantileMethod::Nearest.quantile(&values, 0.375), 3.0);
+        assert_eq!(QuantileMethod::Nearest.quantile(&values, 0.625), 3.0);
+        assert_eq!(QuantileMethod::Exclusive.quantile(&[5.0], 0.5), 5.0);
+    }
+}
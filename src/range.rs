@@ -0,0 +1,267 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::any::Any;
+use std::fmt::Debug;
+
+use arrow::array::ArrayRef;
+use datafusion::arrow;
+use datafusion::arrow::datatypes::{DataType, Field};
+use datafusion::common::cast::as_float64_array;
+use datafusion::common::{Result, ScalarValue};
+use datafusion::logical_expr::function::{AccumulatorArgs, StateFieldsArgs};
+use datafusion::logical_expr::{Accumulator, AggregateUDFImpl, Signature, Volatility};
+
+use crate::common::nulls::all_null_or_filtered;
+
+make_udaf_expr_and_func!(
+    RangeAggFunction,
+    range_agg,
+    x,
+    "Calculates the range of the values, max(x) - min(x).",
+    range_agg_udaf
+);
+
+make_udaf_expr_and_func!(
+    MidrangeFunction,
+    midrange,
+    x,
+    "Calculates the midrange of the values, (max(x) + min(x)) / 2.",
+    midrange_udaf
+);
+
+/// Which statistic [`ExtremesAccumulator`] evaluates to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExtremesStat {
+    Range,
+    Midrange,
+}
+
+impl ExtremesStat {
+    fn evaluate(self, min: f64, max: f64) -> f64 {
+        match self {
+            ExtremesStat::Range => max - min,
+            // halving first keeps the sum of two large values from overflowing
+            ExtremesStat::Midrange => min / 2.0 + max / 2.0,
+        }
+    }
+}
+
+fn state_fields() -> Vec<Field> {
+    vec![
+        Field::new("min", DataType::Float64, true),
+        Field::new("max", DataType::Float64, true),
+    ]
+}
+
+/// The `RangeAggFunction` calculates `max(x) - min(x)` in a single pass.
+///
+/// - NULL values are ignored and the result is NULL if there are no values.
+pub struct RangeAggFunction {
+    signature: Signature,
+}
+
+impl Debug for RangeAggFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RangeAggFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for RangeAggFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RangeAggFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::coercible(vec![DataType::Float64], Volatility::Immutable),
+        }
+    }
+}
+
+impl AggregateUDFImpl for RangeAggFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "range_agg"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Float64)
+    }
+
+    fn state_fields(&self, _args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(state_fields())
+    }
+
+    fn accumulator(&self, _acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        Ok(Box::new(ExtremesAccumulator::new(ExtremesStat::Range)))
+    }
+}
+
+/// The `MidrangeFunction` calculates `(max(x) + min(x)) / 2` in a single pass.
+///
+/// - NULL values are ignored and the result is NULL if there are no values.
+pub struct MidrangeFunction {
+    signature: Signature,
+}
+
+impl Debug for MidrangeFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MidrangeFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for MidrangeFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MidrangeFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::coercible(vec![DataType::Float64], Volatility::Immutable),
+        }
+    }
+}
+
+impl AggregateUDFImpl for MidrangeFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "midrange"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Float64)
+    }
+
+    fn state_fields(&self, _args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(state_fields())
+    }
+
+    fn accumulator(&self, _acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        Ok(Box::new(ExtremesAccumulator::new(ExtremesStat::Midrange)))
+    }
+}
+
+/// Accumulator tracking the minimum and maximum value, shared by [`RangeAggFunction`] and
+/// [`MidrangeFunction`].
+#[derive(Debug)]
+pub struct ExtremesAccumulator {
+    extremes: Option<(f64, f64)>,
+    stat: ExtremesStat,
+}
+
+impl ExtremesAccumulator {
+    pub fn new(stat: ExtremesStat) -> Self {
+        Self { extremes: None, stat }
+    }
+
+    fn update(&mut self, min: f64, max: f64) {
+        self.extremes = Some(match self.extremes {
+            Some((current_min, current_max)) => (current_min.min(min), current_max.max(max)),
+            None => (min, max),
+        });
+    }
+}
+
+impl Accumulator for ExtremesAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        if all_null_or_filtered(&values[0], None) {
+            return Ok(());
+        }
+        for value in as_float64_array(&values[0])?.iter().flatten() {
+            self.update(value, value);
+        }
+        Ok(())
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        let mins = as_float64_array(&states[0])?;
+        let maxs = as_float64_array(&states[1])?;
+        for (min, max) in mins.iter().zip(maxs.iter()) {
+            if let (Some(min), Some(max)) = (min, max) {
+                self.update(min, max);
+            }
+        }
+        Ok(())
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        let (min, max) = self.extremes.unzip();
+        Ok(vec![ScalarValue::Float64(min), ScalarValue::Float64(max)])
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        Ok(ScalarValue::Float64(
+            self.extremes.map(|(min, max)| self.stat.evaluate(min, max)),
+        ))
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow::array::Float64Array;
+
+    use super::*;
+
+    #[test]
+    fn test_extremes_merge() -> Result<()> {
+        let mut first = ExtremesAccumulator::new(ExtremesStat::Midrange);
+        first.update_batch(&[Arc::new(Float64Array::from(vec![Some(4.0), None, Some(-2.0)]))])?;
+        let mut empty = ExtremesAccumulator::new(ExtremesStat::Midrange);
+
+        let mut merged = ExtremesAccumulator::new(ExtremesStat::Midrange);
+        for mut acc in [first, empty] {
+            let state = acc.state()?;
+            let state: Vec<ArrayRef> = state.iter().map(|s| s.to_array()).collect::<Result<_>>()?;
+            merged.merge_batch(&state)?;
+        }
+        merged.update_batch(&[Arc::new(Float64Array::from(vec![10.0]))])?;
+        assert_eq!(merged.evaluate()?, ScalarValue::Float64(Some(4.0)));
+
+        empty = ExtremesAccumulator::new(ExtremesStat::Range);
+        assert_eq!(empty.evaluate()?, ScalarValue::Float64(None));
+        Ok(())
+    }
+}
@@ -0,0 +1,389 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::any::Any;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use ahash::RandomState;
+use arrow::array::{Array, ArrayRef, Float64Array};
+use arrow::datatypes::{DataType, Field};
+use datafusion::arrow;
+use datafusion::common::cast::{as_float64_array, as_list_array};
+use datafusion::common::hash_utils::create_hashes;
+use datafusion::common::{exec_err, plan_err, Result, ScalarValue};
+use datafusion::logical_expr::function::{AccumulatorArgs, StateFieldsArgs};
+use datafusion::logical_expr::{Accumulator, AggregateUDFImpl, Signature, Volatility};
+
+use crate::common::args::literal_arg;
+use crate::compat::single_row_list;
+
+make_udaf_expr_and_func!(
+    ReservoirSampleFunction,
+    reservoir_sample,
+    "Returns a uniform random sample of up to `n` non-null values as a list.",
+    reservoir_sample_udaf
+);
+
+/// The `ReservoirSampleFunction` returns a uniform random sample without replacement of up to
+/// `n` of the non-null values of a group, as a list in random order.
+///
+/// - `reservoir_sample(x, n)` and `reservoir_sample(x, n, seed)` take constant `n` and `seed`.
+///   Every value has the same chance to be sampled, whatever the partitioning of the input.
+/// - Every value is given a random key and the sample is made of the `n` values with the
+///   largest keys, so partial samples merge into a sample of their union.
+/// - Without a `seed`, the seed of [`Self::new_with_seed`] is used, if any, and otherwise the
+///   sample changes between runs.
+/// - With a seed, the key of every value is a hash of the seed and the value, so the sample only
+///   depends on the values of the group, whatever the order and partitioning of the input.
+///   Equal values get equal keys, so they are kept or dropped together.
+/// - The result is NULL if there are no non-null values.
+pub struct ReservoirSampleFunction {
+    signature: Signature,
+    /// Seed of the samples without a `seed` argument, see
+    /// [`crate::config::ExtraFunctionsConfig::seed`]
+    seed: Option<u64>,
+}
+
+impl Debug for ReservoirSampleFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ReservoirSampleFunction")
+            .field("signature", &self.signature)
+            .field("seed", &self.seed)
+            .finish()
+    }
+}
+
+impl Default for ReservoirSampleFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ReservoirSampleFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Volatile),
+            seed: None,
+        }
+    }
+
+    /// The function sampling with `seed` when called without a `seed` argument
+    pub fn new_with_seed(seed: u64) -> Self {
+        Self {
+            seed: Some(seed),
+            ..Self::new()
+        }
+    }
+
+    /// The seed of the samples without a `seed` argument, if any
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+}
+
+impl AggregateUDFImpl for ReservoirSampleFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "reservoir_sample"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        let (value, integers) = match arg_types {
+            [value, integers @ ..] if (1..=2).contains(&integers.len()) => (value, integers),
+            _ => {
+                return plan_err!(
+                    "reservoir_sample expects 2 or 3 arguments (x, n[, seed]), got {}",
+                    arg_types.len()
+                )
+            }
+        };
+        if let Some(arg) = integers.iter().find(|arg| !arg.is_integer() && !arg.is_null()) {
+            return plan_err!("reservoir_sample expects integer n and seed, got {arg}");
+        }
+        let mut coerced = vec![value.clone()];
+        coerced.extend(integers.iter().map(|_| DataType::Int64));
+        Ok(coerced)
+    }
+
+    fn return_type(&self, arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::new_list(arg_types[0].clone(), true))
+    }
+
+    fn state_fields(&self, args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(vec![
+            Field::new_list("sample", Field::new("item", args.input_types[0].clone(), true), true),
+            Field::new_list("keys", Field::new("item", DataType::Float64, true), true),
+        ])
+    }
+
+    fn accumulator(&self, acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        let n = match literal_arg(&acc_args.exprs[1], "reservoir_sample", "n")? {
+            ScalarValue::Int64(Some(n)) if n > 0 => n as usize,
+            n => return plan_err!("reservoir_sample expects a positive n, got {n}"),
+        };
+        let seed = match acc_args.exprs.get(2) {
+            None => self.seed,
+            Some(expr) => match literal_arg(expr, "reservoir_sample", "seed")? {
+                ScalarValue::Int64(Some(seed)) => Some(seed as u64),
+                seed => return plan_err!("reservoir_sample expects a non-null seed, got {seed}"),
+            },
+        };
+        let value_type = crate::compat::arg_type(&acc_args, 0)?;
+        Ok(Box::new(ReservoirSampleAccumulator::new(value_type, n, seed)))
+    }
+}
+
+/// Seeds hashing the values keyed by a seed, which must be the same for every partial aggregate
+/// of a query
+const VALUE_HASH_SEEDS: (u64, u64, u64, u64) = (0x2F1B_07C3, 0x8E4D_A51F, 0x61C8_8647, 0x3C6E_F372);
+
+/// SplitMix64, a small generator whose output only depends on its seed
+#[derive(Debug)]
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    /// A uniform random number in `[0, 1)`
+    fn next_f64(&mut self) -> f64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^= z >> 31;
+        (z >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// How [`ReservoirSampleAccumulator`] draws the keys of the values
+#[derive(Debug)]
+enum Keys {
+    /// Keys drawn from a random stream, which depend on the order the values are read in
+    Random(SplitMix64),
+    /// Keys hashed from the seed and the values, with the buffer of the hashes of a batch
+    Seeded { seed: u64, hashes: Vec<u64> },
+}
+
+impl Keys {
+    /// The keys of `values`, those of the NULL values are meaningless
+    fn draw(&mut self, values: &ArrayRef) -> Result<Vec<f64>> {
+        match self {
+            Keys::Random(rng) => Ok((0..values.len())
+                .map(|index| match values.is_valid(index) {
+                    true => rng.next_f64(),
+                    false => f64::NEG_INFINITY,
+                })
+                .collect()),
+            Keys::Seeded { seed, hashes } => {
+                hashes.clear();
+                hashes.resize(values.len(), 0);
+                let (k0, k1, k2, k3) = VALUE_HASH_SEEDS;
+                create_hashes(
+                    std::slice::from_ref(values),
+                    &RandomState::with_seeds(k0, k1, k2, k3),
+                    hashes,
+                )?;
+                Ok(hashes.iter().map(|hash| SplitMix64(*seed ^ hash).next_f64()).collect())
+            }
+        }
+    }
+}
+
+/// Accumulator for [`ReservoirSampleFunction`], keeping the values with the `n` largest keys.
+#[derive(Debug)]
+pub struct ReservoirSampleAccumulator {
+    value_type: DataType,
+    n: usize,
+    keys: Keys,
+    /// `(key, value)` of the candidates, of which the `n` with the largest keys are the sample
+    candidates: Vec<(f64, ScalarValue)>,
+    /// The smallest key of the sample once `n` values have been seen, below which values are
+    /// skipped
+    threshold: f64,
+}
+
+impl ReservoirSampleAccumulator {
+    pub fn new(value_type: DataType, n: usize, seed: Option<u64>) -> Self {
+        let keys = match seed {
+            Some(seed) => Keys::Seeded { seed, hashes: vec![] },
+            None => Keys::Random(SplitMix64(RandomState::new().hash_one(0u64))),
+        };
+        Self {
+            value_type,
+            n,
+            keys,
+            candidates: vec![],
+            threshold: f64::NEG_INFINITY,
+        }
+    }
+
+    /// Offers the value at `index` of `values` with `key`, converting it only if it can be
+    /// part of the sample
+    fn offer(&mut self, key: f64, values: &ArrayRef, index: usize) -> Result<()> {
+        if key <= self.threshold || values.is_null(index) {
+            return Ok(());
+        }
+        self.candidates.push((key, ScalarValue::try_from_array(values, index)?));
+        if self.candidates.len() >= 2 * self.n {
+            self.truncate();
+        }
+        Ok(())
+    }
+
+    /// Keeps the `n` candidates with the largest keys, sorted by descending key
+    fn truncate(&mut self) {
+        self.candidates.sort_unstable_by(|a, b| b.0.total_cmp(&a.0));
+        self.candidates.truncate(self.n);
+        if self.candidates.len() == self.n {
+            self.threshold = self.candidates[self.n - 1].0;
+        }
+    }
+}
+
+impl Accumulator for ReservoirSampleAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        let values = &values[0];
+        for (index, key) in self.keys.draw(values)?.into_iter().enumerate() {
+            self.offer(key, values, index)?;
+        }
+        Ok(())
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        let (samples, keys) = (as_list_array(&states[0])?, as_list_array(&states[1])?);
+        for (sample, keys) in samples.iter().zip(keys.iter()) {
+            let (Some(sample), Some(keys)) = (sample, keys) else {
+                continue;
+            };
+            if sample.len() != keys.len() {
+                return exec_err!(
+                    "reservoir_sample state has {} values but {} keys",
+                    sample.len(),
+                    keys.len()
+                );
+            }
+            for (index, key) in as_float64_array(&keys)?.iter().enumerate() {
+                self.offer(key.unwrap_or(f64::NEG_INFINITY), &sample, index)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        self.truncate();
+        let keys = Float64Array::from_iter_values(self.candidates.iter().map(|(key, _)| *key));
+        Ok(vec![
+            self.evaluate()?,
+            ScalarValue::List(Arc::new(single_row_list(Arc::new(keys)))),
+        ])
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        self.truncate();
+        if self.candidates.is_empty() {
+            return ScalarValue::try_from(DataType::new_list(self.value_type.clone(), true));
+        }
+        let values: Vec<ScalarValue> = self.candidates.iter().map(|(_, value)| value.clone()).collect();
+        Ok(ScalarValue::List(ScalarValue::new_list_nullable(
+            &values,
+            &self.value_type,
+        )))
+    }
+
+    fn size(&self) -> usize {
+        let hashes = match &self.keys {
+            Keys::Seeded { hashes, .. } => hashes.capacity() * std::mem::size_of::<u64>(),
+            Keys::Random(_) => 0,
+        };
+        std::mem::size_of_val(self)
+            + hashes
+            + self.candidates.capacity() * std::mem::size_of::<(f64, ScalarValue)>()
+            + self
+                .candidates
+                .iter()
+                .map(|(_, value)| value.size() - std::mem::size_of_val(value))
+                .sum::<usize>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::Int64Array;
+
+    fn sample(acc: &mut ReservoirSampleAccumulator) -> Result<Vec<i64>> {
+        let ScalarValue::List(list) = acc.evaluate()? else {
+            panic!("expected a list");
+        };
+        let values = list.value(0);
+        Ok(values
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .unwrap()
+            .iter()
+            .flatten()
+            .collect())
+    }
+
+    #[test]
+    fn test_reservoir_sample_is_reproducible_with_a_seed() -> Result<()> {
+        let values: ArrayRef = Arc::new(Int64Array::from_iter((0..1000).map(|i| (i % 10 != 0).then_some(i))));
+        let mut first = ReservoirSampleAccumulator::new(DataType::Int64, 5, Some(7));
+        first.update_batch(&[Arc::clone(&values)])?;
+        let mut second = ReservoirSampleAccumulator::new(DataType::Int64, 5, Some(7));
+        second.update_batch(&[values])?;
+
+        let first = sample(&mut first)?;
+        assert_eq!(first.len(), 5);
+        assert!(first.iter().all(|value| value % 10 != 0));
+        assert_eq!(first, sample(&mut second)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_reservoir_sample_merge_is_uniform() -> Result<()> {
+        // Values of the small partition must be sampled as often as those of the large one
+        let (mut small_hits, trials) = (0, 2000);
+        for trial in 0..trials {
+            // every accumulator is created with the same seed, as by a query
+            let mut merged = ReservoirSampleAccumulator::new(DataType::Int64, 10, Some(trial));
+            for range in [0..10, 10..100] {
+                let mut partial = ReservoirSampleAccumulator::new(DataType::Int64, 10, Some(trial));
+                partial.update_batch(&[Arc::new(Int64Array::from_iter_values(range))])?;
+                let state: Vec<ArrayRef> = partial
+                    .state()?
+                    .iter()
+                    .map(|value| value.to_array())
+                    .collect::<Result<_>>()?;
+                merged.merge_batch(&state)?;
+            }
+            let sample = sample(&mut merged)?;
+            assert_eq!(sample.len(), 10);
+            small_hits += sample.iter().filter(|value| **value < 10).count();
+        }
+        // 10% of the values are in the small partition, so 1 per sample is expected
+        let small_rate = small_hits as f64 / trials as f64;
+        assert!((small_rate - 1.0).abs() < 0.1, "{small_rate}");
+        Ok(())
+    }
+}
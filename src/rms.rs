@@ -0,0 +1,387 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::any::Any;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use arrow::array::{Array, ArrayRef, BooleanArray, Float64Array, UInt64Array};
+use datafusion::arrow;
+use datafusion::arrow::datatypes::{DataType, Field, UInt64Type};
+use datafusion::common::cast::{as_float64_array, as_primitive_array};
+use datafusion::common::{Result, ScalarValue};
+use datafusion::logical_expr::function::{AccumulatorArgs, StateFieldsArgs};
+use datafusion::logical_expr::{Accumulator, AggregateUDFImpl, Signature, Volatility};
+
+use crate::common::nulls::all_null_or_filtered;
+use crate::compat::{EmitTo, GroupsAccumulator};
+
+make_udaf_expr_and_func!(
+    RmsFunction,
+    rms,
+    x,
+    "Calculates the root mean square, sqrt(avg(x * x)).",
+    rms_udaf
+);
+
+make_udaf_expr_and_func!(
+    SumOfSquaresFunction,
+    sum_of_squares,
+    x,
+    "Calculates the sum of the squares of the values.",
+    sum_of_squares_udaf
+);
+
+/// Which statistic the accumulators of this module evaluate to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SquaresStat {
+    SumOfSquares,
+    Rms,
+}
+
+impl SquaresStat {
+    fn evaluate(self, sum_squares: f64, count: u64) -> Option<f64> {
+        if count == 0 {
+            return None;
+        }
+        match self {
+            SquaresStat::SumOfSquares => Some(sum_squares),
+            SquaresStat::Rms => Some((sum_squares / count as f64).sqrt()),
+        }
+    }
+}
+
+fn state_fields() -> Vec<Field> {
+    vec![
+        Field::new("sum_squares", DataType::Float64, true),
+        Field::new("count", DataType::UInt64, true),
+    ]
+}
+
+/// The `RmsFunction` calculates the root mean square `sqrt(Σ x² / n)`, the magnitude of a
+/// signal.
+///
+/// - NULL values are ignored and the result is NULL if there are no values.
+pub struct RmsFunction {
+    signature: Signature,
+}
+
+impl Debug for RmsFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RmsFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for RmsFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RmsFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::coercible(vec![DataType::Float64], Volatility::Immutable),
+        }
+    }
+}
+
+impl AggregateUDFImpl for RmsFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "rms"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Float64)
+    }
+
+    fn state_fields(&self, _args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(state_fields())
+    }
+
+    fn accumulator(&self, _acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        Ok(Box::new(SquaresAccumulator::new(SquaresStat::Rms)))
+    }
+
+    fn groups_accumulator_supported(&self, _args: AccumulatorArgs) -> bool {
+        true
+    }
+
+    fn create_groups_accumulator(&self, _args: AccumulatorArgs) -> Result<Box<dyn GroupsAccumulator>> {
+        Ok(Box::new(SquaresGroupsAccumulator::new(SquaresStat::Rms)))
+    }
+}
+
+/// The `SumOfSquaresFunction` calculates `Σ x²`, the energy of a signal.
+///
+/// - NULL values are ignored and the result is NULL if there are no values, as for `sum`.
+pub struct SumOfSquaresFunction {
+    signature: Signature,
+}
+
+impl Debug for SumOfSquaresFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SumOfSquaresFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for SumOfSquaresFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SumOfSquaresFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::coercible(vec![DataType::Float64], Volatility::Immutable),
+        }
+    }
+}
+
+impl AggregateUDFImpl for SumOfSquaresFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "sum_of_squares"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Float64)
+    }
+
+    fn state_fields(&self, _args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(state_fields())
+    }
+
+    fn accumulator(&self, _acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        Ok(Box::new(SquaresAccumulator::new(SquaresStat::SumOfSquares)))
+    }
+
+    fn groups_accumulator_supported(&self, _args: AccumulatorArgs) -> bool {
+        true
+    }
+
+    fn create_groups_accumulator(&self, _args: AccumulatorArgs) -> Result<Box<dyn GroupsAccumulator>> {
+        Ok(Box::new(SquaresGroupsAccumulator::new(SquaresStat::SumOfSquares)))
+    }
+}
+
+/// Accumulator summing the squares of the values, shared by [`RmsFunction`] and
+/// [`SumOfSquaresFunction`].
+#[derive(Debug)]
+pub struct SquaresAccumulator {
+    sum_squares: f64,
+    count: u64,
+    stat: SquaresStat,
+}
+
+impl SquaresAccumulator {
+    pub fn new(stat: SquaresStat) -> Self {
+        Self {
+            sum_squares: 0.0,
+            count: 0,
+            stat,
+        }
+    }
+}
+
+impl Accumulator for SquaresAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        if all_null_or_filtered(&values[0], None) {
+            return Ok(());
+        }
+        for value in as_float64_array(&values[0])?.iter().flatten() {
+            self.sum_squares += value * value;
+            self.count += 1;
+        }
+        Ok(())
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        let sum_squares = as_float64_array(&states[0])?;
+        let count = as_primitive_array::<UInt64Type>(&states[1])?;
+        for i in 0..count.len() {
+            if count.is_valid(i) {
+                self.sum_squares += sum_squares.value(i);
+                self.count += count.value(i);
+            }
+        }
+        Ok(())
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        Ok(vec![ScalarValue::from(self.sum_squares), ScalarValue::from(self.count)])
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        Ok(ScalarValue::Float64(self.stat.evaluate(self.sum_squares, self.count)))
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self)
+    }
+}
+
+/// [`GroupsAccumulator`] for [`SquaresAccumulator`], which keeps the sums of every group in
+/// contiguous vectors instead of allocating an [`Accumulator`] per group.
+#[derive(Debug)]
+pub struct SquaresGroupsAccumulator {
+    sum_squares: Vec<f64>,
+    counts: Vec<u64>,
+    stat: SquaresStat,
+}
+
+impl SquaresGroupsAccumulator {
+    pub fn new(stat: SquaresStat) -> Self {
+        Self {
+            sum_squares: vec![],
+            counts: vec![],
+            stat,
+        }
+    }
+}
+
+/// Whether row `i` is selected by `opt_filter`
+fn selected(opt_filter: Option<&BooleanArray>, i: usize) -> bool {
+    opt_filter.map_or(true, |filter| filter.is_valid(i) && filter.value(i))
+}
+
+impl GroupsAccumulator for SquaresGroupsAccumulator {
+    fn update_batch(
+        &mut self,
+        values: &[ArrayRef],
+        group_indices: &[usize],
+        opt_filter: Option<&BooleanArray>,
+        total_num_groups: usize,
+    ) -> Result<()> {
+        self.sum_squares.resize(total_num_groups, 0.0);
+        self.counts.resize(total_num_groups, 0);
+        if all_null_or_filtered(&values[0], opt_filter) {
+            return Ok(());
+        }
+        let values = as_float64_array(&values[0])?;
+        for (i, &group_index) in group_indices.iter().enumerate() {
+            if selected(opt_filter, i) && values.is_valid(i) {
+                let value = values.value(i);
+                self.sum_squares[group_index] += value * value;
+                self.counts[group_index] += 1;
+            }
+        }
+        Ok(())
+    }
+
+    fn merge_batch(
+        &mut self,
+        values: &[ArrayRef],
+        group_indices: &[usize],
+        opt_filter: Option<&BooleanArray>,
+        total_num_groups: usize,
+    ) -> Result<()> {
+        self.sum_squares.resize(total_num_groups, 0.0);
+        self.counts.resize(total_num_groups, 0);
+        let sum_squares = as_float64_array(&values[0])?;
+        let counts = as_primitive_array::<UInt64Type>(&values[1])?;
+        for (i, &group_index) in group_indices.iter().enumerate() {
+            if selected(opt_filter, i) && counts.is_valid(i) {
+                self.sum_squares[group_index] += sum_squares.value(i);
+                self.counts[group_index] += counts.value(i);
+            }
+        }
+        Ok(())
+    }
+
+    fn evaluate(&mut self, emit_to: EmitTo) -> Result<ArrayRef> {
+        let sum_squares = emit_to.take_needed(&mut self.sum_squares);
+        let counts = emit_to.take_needed(&mut self.counts);
+        let results: Float64Array = sum_squares
+            .into_iter()
+            .zip(counts)
+            .map(|(sum_squares, count)| self.stat.evaluate(sum_squares, count))
+            .collect();
+        Ok(Arc::new(results))
+    }
+
+    fn state(&mut self, emit_to: EmitTo) -> Result<Vec<ArrayRef>> {
+        let sum_squares = emit_to.take_needed(&mut self.sum_squares);
+        let counts = emit_to.take_needed(&mut self.counts);
+        Ok(vec![
+            Arc::new(Float64Array::from(sum_squares)),
+            Arc::new(UInt64Array::from(counts)),
+        ])
+    }
+
+    fn size(&self) -> usize {
+        self.sum_squares.capacity() * std::mem::size_of::<f64>() + self.counts.capacity() * std::mem::size_of::<u64>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_squares_groups_accumulator() -> Result<()> {
+        let values: ArrayRef = Arc::new(Float64Array::from(vec![
+            Some(3.0),
+            Some(-4.0),
+            None,
+            Some(5.0),
+            Some(7.0),
+        ]));
+        let filter = BooleanArray::from(vec![true, true, true, true, false]);
+
+        let mut acc = SquaresGroupsAccumulator::new(SquaresStat::Rms);
+        acc.update_batch(&[values], &[0, 0, 1, 2, 2], Some(&filter), 3)?;
+
+        // merging the state into a new accumulator keeps the sums of each group
+        let state = acc.state(EmitTo::All)?;
+        let mut merged = SquaresGroupsAccumulator::new(SquaresStat::Rms);
+        merged.merge_batch(&state, &[0, 1, 2], None, 3)?;
+
+        let expected: ArrayRef = Arc::new(Float64Array::from(vec![Some(12.5f64.sqrt()), None, Some(5.0)]));
+        assert_eq!(&merged.evaluate(EmitTo::All)?, &expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_sum_of_squares() -> Result<()> {
+        let mut acc = SquaresAccumulator::new(SquaresStat::SumOfSquares);
+        assert_eq!(acc.evaluate()?, ScalarValue::Float64(None));
+        acc.update_batch(&[Arc::new(Float64Array::from(vec![Some(1.0), None, Some(-2.0)]))])?;
+        assert_eq!(acc.evaluate()?, ScalarValue::Float64(Some(5.0)));
+        Ok(())
+    }
+}
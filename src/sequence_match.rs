@@ -0,0 +1,480 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+use std::any::Any;
+use std::collections::HashSet;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use arrow::array::{Array, ArrayRef, AsArray, Int64Array, UInt32Array};
+use arrow::compute::cast;
+use arrow::datatypes::{DataType, Field, Int64Type, UInt32Type};
+use datafusion::arrow;
+use datafusion::common::cast::{as_boolean_array, as_list_array};
+use datafusion::common::{exec_err, plan_err, Result, ScalarValue};
+use datafusion::logical_expr::function::{AccumulatorArgs, StateFieldsArgs};
+use datafusion::logical_expr::{Accumulator, AggregateUDFImpl, Signature, Volatility};
+
+use crate::common::args::literal_arg;
+use crate::common::decay::{coerce_ts, seconds_per_unit};
+use crate::compat::{arg_type, single_row_list};
+
+make_udaf_expr_and_func!(
+    SequenceMatchFunction,
+    sequence_match,
+    "Returns whether the events, in the order of ts, match a pattern of conditions like '(?1).*(?2)'.",
+    sequence_match_udaf
+);
+
+make_udaf_expr_and_func!(
+    SequenceCountFunction,
+    sequence_count,
+    "Counts the non-overlapping matches of a pattern of conditions like '(?1).*(?2)' in the events, in the order of ts.",
+    sequence_count_udaf
+);
+
+/// The most conditions of a pattern, as the conditions an event meets are a bitmask
+const MAX_CONDITIONS: usize = 32;
+
+/// A comparison of a time constraint `(?t<op><seconds>)`
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Comparison {
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Equal,
+}
+
+impl Comparison {
+    fn holds(&self, elapsed: f64, bound: f64) -> bool {
+        match self {
+            Self::Less => elapsed < bound,
+            Self::LessOrEqual => elapsed <= bound,
+            Self::Greater => elapsed > bound,
+            Self::GreaterOrEqual => elapsed >= bound,
+            Self::Equal => elapsed == bound,
+        }
+    }
+}
+
+/// A token of a pattern
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    /// `(?N)`, an event meeting the condition of index `N - 1`, within the time constraints that
+    /// precede it of the event meeting the previous condition
+    Condition(usize, Vec<(Comparison, f64)>),
+    /// `.`, any event
+    AnyEvent,
+    /// `.*`, any number of events
+    AnyEvents,
+}
+
+/// A pattern of [`SequenceMatchFunction`] and [`SequenceCountFunction`], following the syntax
+/// of ClickHouse's `sequenceMatch`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pattern {
+    tokens: Vec<Token>,
+    /// Whether there are time constraints, so the states must track the time of their last
+    /// condition
+    timed: bool,
+}
+
+impl Pattern {
+    /// Parses `pattern`, whose conditions refer to the `conditions` conditions of `fn_name`
+    pub fn try_new(pattern: &str, conditions: usize, fn_name: &str) -> Result<Self> {
+        let mut tokens = vec![];
+        let mut constraints = vec![];
+        let mut rest = pattern;
+        while !rest.is_empty() {
+            let position = pattern.len() - rest.len();
+            if let Some(after) = rest.strip_prefix(".*") {
+                tokens.push(Token::AnyEvents);
+                rest = after;
+            } else if let Some(after) = rest.strip_prefix('.') {
+                tokens.push(Token::AnyEvent);
+                rest = after;
+            } else if let Some((group, after)) = rest.strip_prefix("(?").and_then(|group| group.split_once(')')) {
+                if let Some(constraint) = group.strip_prefix('t') {
+                    let (comparison, bound) = [
+                        ("<=", Comparison::LessOrEqual),
+                        (">=", Comparison::GreaterOrEqual),
+                        ("==", Comparison::Equal),
+                        ("<", Comparison::Less),
+                        (">", Comparison::Greater),
+                    ]
+                    .into_iter()
+                    .find_map(|(op, comparison)| Some((comparison, constraint.strip_prefix(op)?)))
+                    .unwrap_or((Comparison::Equal, ""));
+                    let (Ok(bound), true) = (
+                        bound.parse::<f64>(),
+                        tokens.iter().any(|t| matches!(t, Token::Condition(..))),
+                    ) else {
+                        return plan_err!(
+                            "{fn_name} expects a time constraint like (?t<=60) after a condition, \
+                             got '(?{group})' at {position} in '{pattern}'"
+                        );
+                    };
+                    constraints.push((comparison, bound));
+                } else {
+                    match group.parse::<usize>() {
+                        Ok(condition) if (1..=conditions).contains(&condition) => {
+                            tokens.push(Token::Condition(condition - 1, std::mem::take(&mut constraints)))
+                        }
+                        _ => {
+                            return plan_err!(
+                                "{fn_name} expects a condition between (?1) and (?{conditions}), \
+                                 got '(?{group})' at {position} in '{pattern}'"
+                            )
+                        }
+                    }
+                }
+                rest = after;
+            } else {
+                return plan_err!("{fn_name} could not parse the pattern '{pattern}' at {position}");
+            }
+        }
+        if !constraints.is_empty() {
+            return plan_err!("{fn_name} expects a condition after the time constraints of '{pattern}'");
+        }
+        if !tokens.iter().any(|token| matches!(token, Token::Condition(..))) {
+            return plan_err!("{fn_name} expects a pattern with a condition, got '{pattern}'");
+        }
+        let timed = tokens
+            .iter()
+            .any(|token| matches!(token, Token::Condition(_, constraints) if !constraints.is_empty()));
+        Ok(Self { tokens, timed })
+    }
+
+    /// Adds the state at `token` to `states`, with the states it reaches without an event
+    fn add_state(&self, states: &mut HashSet<(usize, Option<i64>)>, token: usize, last: Option<i64>) {
+        if states.insert((token, last)) && self.tokens.get(token) == Some(&Token::AnyEvents) {
+            self.add_state(states, token + 1, last);
+        }
+    }
+
+    /// Counts the non-overlapping matches of the pattern in `events`, sorted by time, each
+    /// ending as early as possible, stopping at the first if `first_only`
+    ///
+    /// The events are their times, in seconds per `time_scale`, and the bitmask of the
+    /// conditions they meet. The pattern is matched by simulating its automaton, whose states
+    /// are a token and the time of the last condition if the pattern is timed.
+    pub fn count(&self, events: &[(i64, u32)], time_scale: f64, first_only: bool) -> usize {
+        let mut matches = 0;
+        let mut states = HashSet::new();
+        for &(time, conditions) in events {
+            // a match may start at any event
+            self.add_state(&mut states, 0, None);
+            let mut next = HashSet::new();
+            for (token, last) in states.drain() {
+                match self.tokens.get(token) {
+                    Some(Token::AnyEvents) => self.add_state(&mut next, token, last),
+                    Some(Token::AnyEvent) => self.add_state(&mut next, token + 1, last),
+                    Some(Token::Condition(condition, constraints)) => {
+                        let met = conditions & (1 << condition) != 0
+                            && constraints.iter().all(|(comparison, bound)| {
+                                last.is_some_and(|last| comparison.holds((time - last) as f64 * time_scale, *bound))
+                            });
+                        if met {
+                            self.add_state(&mut next, token + 1, self.timed.then_some(time));
+                        }
+                    }
+                    None => {}
+                }
+            }
+            if next.iter().any(|(token, _)| *token == self.tokens.len()) {
+                matches += 1;
+                if first_only {
+                    break;
+                }
+                // the next match starts after this one
+                next.clear();
+            }
+            states = next;
+        }
+        matches
+    }
+}
+
+/// What the sequence aggregates return, [`SequenceMatchFunction`] or [`SequenceCountFunction`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SequenceOutput {
+    /// Whether the pattern matches
+    Match,
+    /// The number of non-overlapping matches
+    Count,
+}
+
+impl SequenceOutput {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Match => "sequence_match",
+            Self::Count => "sequence_count",
+        }
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        let [pattern_type, ts_type, conditions @ ..] = arg_types else {
+            return plan_err!(
+                "{} expects a pattern, a ts and conditions, got {} arguments",
+                self.name(),
+                arg_types.len()
+            );
+        };
+        if conditions.is_empty() || conditions.len() > MAX_CONDITIONS {
+            return plan_err!(
+                "{} expects between 1 and {MAX_CONDITIONS} conditions, got {}",
+                self.name(),
+                conditions.len()
+            );
+        }
+        if !matches!(
+            pattern_type,
+            DataType::Utf8 | DataType::LargeUtf8 | DataType::Utf8View | DataType::Null
+        ) {
+            return plan_err!("{} expects a string pattern, got {pattern_type}", self.name());
+        }
+        if let Some(condition_type) = conditions
+            .iter()
+            .find(|t| !matches!(t, DataType::Boolean | DataType::Null))
+        {
+            return plan_err!("{} expects boolean conditions, got {condition_type}", self.name());
+        }
+        let mut coerced = vec![DataType::Utf8, coerce_ts(self.name(), ts_type)?];
+        coerced.extend(std::iter::repeat(DataType::Boolean).take(conditions.len()));
+        Ok(coerced)
+    }
+
+    fn return_type(&self) -> DataType {
+        match self {
+            Self::Match => DataType::Boolean,
+            Self::Count => DataType::Int64,
+        }
+    }
+
+    fn accumulator(&self, acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        let pattern = match literal_arg(&acc_args.exprs[0], self.name(), "pattern")? {
+            ScalarValue::Utf8(Some(pattern)) => Pattern::try_new(&pattern, acc_args.exprs.len() - 2, self.name())?,
+            _ => return plan_err!("{} expects a non-null pattern", self.name()),
+        };
+        let time_scale = seconds_per_unit(&arg_type(&acc_args, 1)?).unwrap_or(1.0);
+        Ok(Box::new(SequenceAccumulator::new(*self, pattern, time_scale)))
+    }
+}
+
+/// The `SequenceMatchFunction` returns whether a sequence of events matches a pattern,
+/// `sequence_match(pattern, ts, cond1, cond2, ...)`, like ClickHouse's `sequenceMatch`.
+///
+/// - The events are the rows meeting one of the up to 32 conditions, in the order of `ts`, a
+///   timestamp, a date or an integer, then of the conditions they meet. The rows meeting none
+///   are skipped.
+/// - `pattern` is a literal of `(?N)`, an event meeting `condN`, `.`, any event, `.*`, any
+///   number of events, and `(?t<op><seconds>)`, a constraint on the time since the event of
+///   the previous condition, for the event of the next condition, with `op` among `<`, `<=`,
+///   `>`, `>=` and `==`. Times are in seconds for timestamps and dates, and in the unit of `ts`
+///   for integers. The pattern may match any of the events, like a regular expression search:
+///   `(?1).*(?t<=3600)(?2)` matches an event meeting `cond1` followed by one meeting `cond2`
+///   within an hour.
+/// - The events are buffered until the result is emitted, as those of different partitions
+///   interleave in time.
+/// - Rows with a NULL `ts` are ignored, and NULL conditions aren't met.
+pub struct SequenceMatchFunction {
+    signature: Signature,
+}
+
+/// The `SequenceCountFunction` counts the matches of a pattern in a sequence of events,
+/// `sequence_count(pattern, ts, cond1, cond2, ...)`, like ClickHouse's `sequenceCount`.
+///
+/// - The events and the pattern are those of [`SequenceMatchFunction`]. The matches don't
+///   overlap: each ends at the earliest event completing one, and the next starts after it.
+pub struct SequenceCountFunction {
+    signature: Signature,
+}
+
+macro_rules! sequence_function {
+    ($UDAF:ident, $OUTPUT:expr) => {
+        impl Debug for $UDAF {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.debug_struct(stringify!($UDAF))
+                    .field("signature", &self.signature)
+                    .finish()
+            }
+        }
+
+        impl Default for $UDAF {
+            fn default() -> Self {
+                Self::new()
+            }
+        }
+
+        impl $UDAF {
+            pub fn new() -> Self {
+                Self {
+                    signature: Signature::user_defined(Volatility::Immutable),
+                }
+            }
+        }
+
+        impl AggregateUDFImpl for $UDAF {
+            fn as_any(&self) -> &dyn Any {
+                self
+            }
+
+            fn name(&self) -> &str {
+                $OUTPUT.name()
+            }
+
+            fn signature(&self) -> &Signature {
+                &self.signature
+            }
+
+            fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+                $OUTPUT.coerce_types(arg_types)
+            }
+
+            fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+                Ok($OUTPUT.return_type())
+            }
+
+            fn state_fields(&self, _args: StateFieldsArgs) -> Result<Vec<Field>> {
+                Ok(vec![
+                    Field::new_list("times", Field::new("item", DataType::Int64, true), true),
+                    Field::new_list("conditions", Field::new("item", DataType::UInt32, true), true),
+                ])
+            }
+
+            fn accumulator(&self, acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+                $OUTPUT.accumulator(acc_args)
+            }
+        }
+    };
+}
+
+sequence_function!(SequenceMatchFunction, SequenceOutput::Match);
+sequence_function!(SequenceCountFunction, SequenceOutput::Count);
+
+/// Accumulator for the sequence aggregates, buffering the times of the events as `Int64` with
+/// the bitmask of the conditions they meet
+#[derive(Debug)]
+pub struct SequenceAccumulator {
+    output: SequenceOutput,
+    pattern: Pattern,
+    time_scale: f64,
+    events: Vec<(i64, u32)>,
+}
+
+impl SequenceAccumulator {
+    pub fn new(output: SequenceOutput, pattern: Pattern, time_scale: f64) -> Self {
+        Self {
+            output,
+            pattern,
+            time_scale,
+            events: vec![],
+        }
+    }
+}
+
+impl Accumulator for SequenceAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        let times = cast(&values[1], &DataType::Int64)?;
+        let conditions = values[2..]
+            .iter()
+            .map(|condition| as_boolean_array(condition))
+            .collect::<Result<Vec<_>>>()?;
+        for (row, time) in times.as_primitive::<Int64Type>().iter().enumerate() {
+            let met = conditions
+                .iter()
+                .enumerate()
+                .filter(|(_, condition)| condition.is_valid(row) && condition.value(row))
+                .fold(0, |met, (index, _)| met | 1 << index);
+            if let (Some(time), true) = (time, met != 0) {
+                self.events.push((time, met));
+            }
+        }
+        Ok(())
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        let (times, conditions) = (as_list_array(&states[0])?, as_list_array(&states[1])?);
+        for (times, conditions) in times.iter().zip(conditions.iter()) {
+            let (Some(times), Some(conditions)) = (times, conditions) else {
+                continue;
+            };
+            if times.len() != conditions.len() {
+                return exec_err!(
+                    "{} state has {} times but {} conditions",
+                    self.output.name(),
+                    times.len(),
+                    conditions.len()
+                );
+            }
+            let times = times.as_primitive::<Int64Type>().values().iter();
+            let conditions = conditions.as_primitive::<UInt32Type>().values().iter();
+            self.events.extend(times.copied().zip(conditions.copied()));
+        }
+        Ok(())
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        let (times, conditions): (Vec<i64>, Vec<u32>) = std::mem::take(&mut self.events).into_iter().unzip();
+        Ok(vec![
+            ScalarValue::List(Arc::new(single_row_list(Arc::new(Int64Array::from(times))))),
+            ScalarValue::List(Arc::new(single_row_list(Arc::new(UInt32Array::from(conditions))))),
+        ])
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        self.events.sort_unstable();
+        let count = |first_only| self.pattern.count(&self.events, self.time_scale, first_only);
+        Ok(match self.output {
+            SequenceOutput::Match => ScalarValue::Boolean(Some(count(true) > 0)),
+            SequenceOutput::Count => ScalarValue::Int64(Some(count(false) as i64)),
+        })
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self) + self.events.capacity() * std::mem::size_of::<(i64, u32)>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pattern_matches() -> Result<()> {
+        let count = |pattern: &str, events: &[(i64, u32)]| -> Result<usize> {
+            Ok(Pattern::try_new(pattern, 3, "sequence_count")?.count(events, 1.0, false))
+        };
+        // 1, 3, 2, 1, 2 with both 1 and 2 at 40
+        let events = [(0, 0b001), (10, 0b100), (20, 0b010), (30, 0b001), (40, 0b011)];
+        assert_eq!(count("(?1)(?2)", &events)?, 1);
+        assert_eq!(count("(?1).*(?2)", &events)?, 2);
+        assert_eq!(count("(?1).(?2)", &events)?, 1);
+        assert_eq!(count("(?3)(?1)", &events)?, 0);
+        assert_eq!(count("(?1).*(?t>15)(?2)", &events)?, 1);
+        assert_eq!(count("(?1).*(?t<=10)(?2)", &events)?, 1);
+        assert_eq!(count("(?2)", &events)?, 2);
+
+        let error = |pattern: &str| Pattern::try_new(pattern, 3, "sequence_match").unwrap_err().to_string();
+        assert!(error("(?4)").contains("expects a condition between (?1) and (?3)"));
+        assert!(error("(?t<5)(?1)").contains("expects a time constraint like (?t<=60) after a condition"));
+        assert!(error("(?1)(?t<5)").contains("expects a condition after the time constraints"));
+        assert!(error("(?1)x").contains("could not parse the pattern '(?1)x' at 4"));
+        assert!(error(".*").contains("expects a pattern with a condition"));
+        Ok(())
+    }
+}
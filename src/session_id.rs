@@ -0,0 +1,172 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+use std::any::Any;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use arrow::array::{Array, ArrayRef, AsArray, Int64Array};
+use arrow::compute::cast;
+use arrow::datatypes::{DataType, Int64Type};
+use datafusion::arrow;
+use datafusion::common::{exec_err, plan_err, Result, ScalarValue};
+use datafusion::logical_expr::{PartitionEvaluator, Signature, Volatility, WindowUDFImpl};
+
+use crate::common::decay::{coerce_ts, duration, seconds_per_unit};
+
+make_udwf_expr_and_func!(
+    SessionIdFunction,
+    session_id,
+    ts gap,
+    "Numbers the sessions of a window partition, starting a new one when ts is more than gap after the previous row.",
+    session_id_udwf
+);
+
+/// The `SessionIdFunction` sessionizes the rows of a window partition,
+/// `session_id(ts, gap) OVER (PARTITION BY user ORDER BY ts)`.
+///
+/// - Returns the number of the session of the row, from 1, starting a new session whenever `ts`,
+///   a timestamp, a date or an integer, is more than `gap` after the `ts` of the previous row.
+///   The partition is expected to be ordered by `ts`.
+/// - `gap` is an interval without months or a number of seconds, or a number in the unit of
+///   `ts` for integers, the same for every row of a partition.
+/// - Rows with a NULL `ts` are NULL and don't start a session. Like `row_number`, the window
+///   frame is ignored.
+pub struct SessionIdFunction {
+    signature: Signature,
+}
+
+impl Debug for SessionIdFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SessionIdFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for SessionIdFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SessionIdFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+        }
+    }
+}
+
+impl WindowUDFImpl for SessionIdFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "session_id"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        let [ts_type, gap_type] = arg_types else {
+            return plan_err!("session_id expects 2 arguments (ts, gap), got {}", arg_types.len());
+        };
+        if !(gap_type.is_numeric() || matches!(gap_type, DataType::Interval(_))) {
+            return plan_err!("session_id expects an interval or numeric gap, got {gap_type}");
+        }
+        Ok(vec![coerce_ts(self.name(), ts_type)?, gap_type.clone()])
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Int64)
+    }
+
+    fn partition_evaluator(&self) -> Result<Box<dyn PartitionEvaluator>> {
+        Ok(Box::new(SessionIdEvaluator))
+    }
+}
+
+/// Evaluator for [`SessionIdFunction`]
+#[derive(Debug)]
+pub struct SessionIdEvaluator;
+
+impl PartitionEvaluator for SessionIdEvaluator {
+    fn evaluate_all(&mut self, values: &[ArrayRef], num_rows: usize) -> Result<ArrayRef> {
+        if num_rows == 0 {
+            return Ok(Arc::new(Int64Array::from(Vec::<i64>::new())));
+        }
+        let time_scale = seconds_per_unit(values[0].data_type());
+        let gap = ScalarValue::try_from_array(&values[1], 0)?;
+        for row in 1..num_rows {
+            if ScalarValue::try_from_array(&values[1], row)? != gap {
+                return exec_err!("session_id expects a constant gap");
+            }
+        }
+        let gap = duration(gap, "session_id", "gap", time_scale.is_some())?;
+        let times = cast(&values[0], &DataType::Int64)?;
+        let (mut session, mut previous) = (0, None);
+        let sessions: Int64Array = times
+            .as_primitive::<Int64Type>()
+            .iter()
+            .map(|time| {
+                let time = time?;
+                if previous.map_or(true, |previous| {
+                    (time - previous) as f64 * time_scale.unwrap_or(1.0) > gap
+                }) {
+                    session += 1;
+                }
+                previous = Some(time);
+                Some(session)
+            })
+            .collect();
+        Ok(Arc::new(sessions))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::array::{IntervalMonthDayNanoArray, TimestampMillisecondArray};
+    use arrow::datatypes::IntervalMonthDayNano;
+
+    use super::*;
+
+    #[test]
+    fn test_sessions_split_on_gap() -> Result<()> {
+        let minute = IntervalMonthDayNano::new(0, 0, 60_000_000_000);
+        let evaluate = |times: Vec<Option<i64>>, gap: IntervalMonthDayNano| {
+            let len = times.len();
+            SessionIdEvaluator.evaluate_all(
+                &[
+                    Arc::new(TimestampMillisecondArray::from(times)),
+                    Arc::new(IntervalMonthDayNanoArray::from(vec![gap; len])),
+                ],
+                len,
+            )
+        };
+        // a gap of exactly a minute stays in the session
+        let sessions = evaluate(vec![Some(0), Some(60_000), None, Some(120_001), Some(130_000)], minute)?;
+        assert_eq!(
+            sessions.as_primitive::<Int64Type>(),
+            &Int64Array::from(vec![Some(1), Some(1), None, Some(2), Some(2)])
+        );
+        assert!(evaluate(vec![Some(0)], IntervalMonthDayNano::new(1, 0, 0)).is_err());
+        Ok(())
+    }
+}
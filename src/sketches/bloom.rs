@@ -0,0 +1,196 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Bloom filter for approximate membership.
+//!
+//! See Bloom, B. H. (1970). "Space/Time Trade-offs in Hash Coding with Allowable Errors". A
+//! filter sets `num_hashes` bits of a bit array per value, and a value is reported as
+//! contained if all of its bits are set: values added are always found, and other values are
+//! found with a false positive probability that grows as bits are set.
+
+use datafusion::common::exec_err;
+use datafusion::error::Result;
+
+use crate::sketches::format::{self, SketchKind};
+
+/// Default number of distinct values the filter is sized for
+pub const DEFAULT_EXPECTED_ITEMS: u64 = 10_000;
+
+/// Default false positive probability at the expected number of distinct values
+pub const DEFAULT_FPP: f64 = 0.01;
+
+/// Largest number of bits of a filter, 512 MiB
+pub const MAX_BITS: u64 = 1 << 32;
+
+/// Largest number of bits set per value
+const MAX_HASHES: usize = 30;
+
+/// A Bloom filter over 64-bit hashes.
+///
+/// Filters only find values hashed the same way as their inputs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BloomFilter {
+    num_bits: u64,
+    num_hashes: usize,
+    words: Vec<u64>,
+}
+
+impl Default for BloomFilter {
+    fn default() -> Self {
+        Self::with_fpp(DEFAULT_EXPECTED_ITEMS, DEFAULT_FPP)
+    }
+}
+
+impl BloomFilter {
+    /// Creates an empty filter of `num_bits` bits, setting `num_hashes` bits per value
+    pub fn new(num_bits: u64, num_hashes: usize) -> Self {
+        assert!(
+            (1..=MAX_BITS).contains(&num_bits) && (1..=MAX_HASHES).contains(&num_hashes),
+            "Invalid Bloom filter of {num_bits} bits and {num_hashes} hashes"
+        );
+        Self {
+            num_bits,
+            num_hashes,
+            words: vec![0; num_bits.div_ceil(64) as usize],
+        }
+    }
+
+    /// Creates an empty filter whose false positive probability is `fpp`, in (0, 1), once
+    /// `expected_items` distinct values are added. The size is capped at [`MAX_BITS`].
+    pub fn with_fpp(expected_items: u64, fpp: f64) -> Self {
+        assert!(
+            fpp > 0.0 && fpp < 1.0,
+            "Invalid Bloom filter false positive probability {fpp}"
+        );
+        let n = expected_items.max(1) as f64;
+        let ln2 = std::f64::consts::LN_2;
+        let num_bits = (-n * fpp.ln() / (ln2 * ln2)).ceil().clamp(64.0, MAX_BITS as f64) as u64;
+        let num_hashes = (num_bits as f64 / n * ln2).round().clamp(1.0, MAX_HASHES as f64) as usize;
+        Self::new(num_bits, num_hashes)
+    }
+
+    pub fn num_bits(&self) -> u64 {
+        self.num_bits
+    }
+
+    pub fn num_hashes(&self) -> usize {
+        self.num_hashes
+    }
+
+    /// Adds the value hashed to `hash`
+    pub fn add_hash(&mut self, hash: u64) {
+        for bit in bits(self.num_bits, self.num_hashes, hash) {
+            self.words[(bit / 64) as usize] |= 1 << (bit % 64);
+        }
+    }
+
+    /// Whether the value hashed to `hash` may have been added, always true if it was
+    pub fn contains_hash(&self, hash: u64) -> bool {
+        bits(self.num_bits, self.num_hashes, hash).all(|bit| self.words[(bit / 64) as usize] & (1 << (bit % 64)) != 0)
+    }
+
+    /// Merges `other` into this filter, which must have the same number of bits and hashes
+    pub fn merge(&mut self, other: &BloomFilter) -> Result<()> {
+        if (self.num_bits, self.num_hashes) != (other.num_bits, other.num_hashes) {
+            return exec_err!(
+                "Can't merge a Bloom filter of {} bits and {} hashes into one of {} bits and {} hashes",
+                other.num_bits,
+                other.num_hashes,
+                self.num_bits,
+                self.num_hashes
+            );
+        }
+        for (word, other) in self.words.iter_mut().zip(&other.words) {
+            *word |= other;
+        }
+        Ok(())
+    }
+
+    pub fn size(&self) -> usize {
+        std::mem::size_of_val(self) + self.words.capacity() * std::mem::size_of::<u64>()
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        format::encode(SketchKind::Bloom, |writer| {
+            writer.put_u64(self.num_bits);
+            writer.put_len(self.num_hashes);
+            self.words.iter().for_each(|word| writer.put_u64(*word));
+        })
+    }
+
+    /// Deserializes a filter written by [`Self::to_bytes`]
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        format::decode(bytes, SketchKind::Bloom, |_version, reader| {
+            let num_bits = reader.get_u64()?;
+            let num_hashes = reader.get_len()?;
+            if !(1..=MAX_BITS).contains(&num_bits) || !(1..=MAX_HASHES).contains(&num_hashes) {
+                return exec_err!("Invalid Bloom filter of {num_bits} bits and {num_hashes} hashes");
+            }
+            let num_words = num_bits.div_ceil(64) as usize;
+            if reader.remaining() != num_words * std::mem::size_of::<u64>() {
+                return exec_err!("Invalid Bloom filter state: expected {num_words} words");
+            }
+            let words = (0..num_words).map(|_| reader.get_u64()).collect::<Result<Vec<_>>>()?;
+            Ok(Self {
+                num_bits,
+                num_hashes,
+                words,
+            })
+        })
+    }
+}
+
+/// The bits of `hash` in a filter, derived from the two halves of the hash as in Kirsch and
+/// Mitzenmacher's "Less Hashing, Same Performance"
+fn bits(num_bits: u64, num_hashes: usize, hash: u64) -> impl Iterator<Item = u64> {
+    let (h1, h2) = (hash & 0xFFFF_FFFF, hash >> 32);
+    (0..num_hashes as u64).map(move |i| h1.wrapping_add(i.wrapping_mul(h2)) % num_bits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::hash::{BuildHasher, Hasher};
+
+    fn hash(value: u64) -> u64 {
+        let mut hasher = ahash::RandomState::with_seeds(1, 2, 3, 4).build_hasher();
+        hasher.write_u64(value);
+        hasher.finish()
+    }
+
+    #[test]
+    fn test_bloom_filter_false_positive_rate() -> Result<()> {
+        let (mut a, mut b) = (BloomFilter::with_fpp(1000, 0.01), BloomFilter::with_fpp(1000, 0.01));
+        (0..500).for_each(|value| a.add_hash(hash(value)));
+        (500..1000).for_each(|value| b.add_hash(hash(value)));
+        a.merge(&b)?;
+
+        assert!((0..1000).all(|value| a.contains_hash(hash(value))));
+        let false_positives = (1000..11_000).filter(|value| a.contains_hash(hash(*value))).count();
+        assert!(false_positives < 200, "{false_positives} false positives");
+        assert_eq!(BloomFilter::from_bytes(&a.to_bytes())?, a);
+        Ok(())
+    }
+
+    #[test]
+    fn test_bloom_filter_rejects_mismatches() {
+        let mut a = BloomFilter::with_fpp(1000, 0.01);
+        assert!(a.merge(&BloomFilter::with_fpp(1000, 0.001)).is_err());
+        let bytes = a.to_bytes();
+        assert!(BloomFilter::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+    }
+}
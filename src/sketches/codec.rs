@@ -0,0 +1,285 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Portable binary encoding for sketch states.
+//!
+//! Every value is written in little-endian byte order with a fixed width, whatever the
+//! endianness and pointer width of the target, so a state serialized on one node can be
+//! decoded on any other. Lengths are encoded as `u64` rather than `usize` and are checked
+//! against both the remaining input and `usize::MAX` when decoding, which keeps 32-bit
+//! targets from truncating them.
+
+use datafusion::common::exec_err;
+use datafusion::error::Result;
+
+/// Encodes a sketch state, see the [module documentation](self) for the format
+#[derive(Debug, Default)]
+pub struct StateWriter {
+    buf: Vec<u8>,
+}
+
+impl StateWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            buf: Vec::with_capacity(capacity),
+        }
+    }
+
+    pub fn put_u8(&mut self, value: u8) {
+        self.buf.push(value);
+    }
+
+    pub fn put_u16(&mut self, value: u16) {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn put_u32(&mut self, value: u32) {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn put_u64(&mut self, value: u64) {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn put_i64(&mut self, value: i64) {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn put_f64(&mut self, value: f64) {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    /// Writes a length (or count), always as a `u64`
+    pub fn put_len(&mut self, len: usize) {
+        self.put_u64(len as u64);
+    }
+
+    /// Writes `bytes` prefixed with their length
+    pub fn put_bytes(&mut self, bytes: &[u8]) {
+        self.put_len(bytes.len());
+        self.buf.extend_from_slice(bytes);
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        self.buf
+    }
+}
+
+/// Decodes a sketch state written by [`StateWriter`]
+#[derive(Debug)]
+pub struct StateReader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> StateReader<'a> {
+    pub fn new(buf: &'a [u8]) -> Self {
+        Self { buf, pos: 0 }
+    }
+
+    /// Number of bytes left to read
+    pub fn remaining(&self) -> usize {
+        self.buf.len() - self.pos
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if len > self.remaining() {
+            return exec_err!(
+                "Truncated sketch state: expected {} more bytes at offset {}, found {}",
+                len,
+                self.pos,
+                self.remaining()
+            );
+        }
+        let bytes = &self.buf[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn take_array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.take(N)?);
+        Ok(array)
+    }
+
+    pub fn get_u8(&mut self) -> Result<u8> {
+        Ok(self.take_array::<1>()?[0])
+    }
+
+    pub fn get_u16(&mut self) -> Result<u16> {
+        Ok(u16::from_le_bytes(self.take_array()?))
+    }
+
+    pub fn get_u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.take_array()?))
+    }
+
+    pub fn get_u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.take_array()?))
+    }
+
+    pub fn get_i64(&mut self) -> Result<i64> {
+        Ok(i64::from_le_bytes(self.take_array()?))
+    }
+
+    pub fn get_f64(&mut self) -> Result<f64> {
+        Ok(f64::from_le_bytes(self.take_array()?))
+    }
+
+    /// Reads a length written by [`StateWriter::put_len`]
+    pub fn get_len(&mut self) -> Result<usize> {
+        let len = self.get_u64()?;
+        match usize::try_from(len) {
+            Ok(len) => Ok(len),
+            Err(_) => exec_err!("Sketch state length {} does not fit in usize on this target", len),
+        }
+    }
+
+    /// Reads a count of items that each take at least `item_size` bytes, rejecting counts that
+    /// cannot fit in the remaining input before anything is allocated for them
+    pub fn get_count(&mut self, item_size: usize) -> Result<usize> {
+        let count = self.get_len()?;
+        if count.saturating_mul(item_size) > self.remaining() {
+            return exec_err!(
+                "Truncated sketch state: {} items of {} bytes do not fit in the remaining {} bytes",
+                count,
+                item_size,
+                self.remaining()
+            );
+        }
+        Ok(count)
+    }
+
+    /// Reads bytes written by [`StateWriter::put_bytes`]
+    pub fn get_bytes(&mut self) -> Result<&'a [u8]> {
+        let len = self.get_len()?;
+        self.take(len)
+    }
+
+    /// Checks the whole input was consumed
+    pub fn finish(self) -> Result<()> {
+        if self.remaining() != 0 {
+            return exec_err!("Invalid sketch state: {} unexpected trailing bytes", self.remaining());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layout_is_little_endian() {
+        let mut writer = StateWriter::new();
+        writer.put_u8(0x01);
+        writer.put_u16(0x0203);
+        writer.put_u32(0x0405_0607);
+        writer.put_u64(0x0809_0a0b_0c0d_0e0f);
+        writer.put_i64(-2);
+        writer.put_f64(1.0);
+        writer.put_bytes(b"ab");
+
+        #[rustfmt::skip]
+        let expected: Vec<u8> = vec![
+            0x01,
+            0x03, 0x02,
+            0x07, 0x06, 0x05, 0x04,
+            0x0f, 0x0e, 0x0d, 0x0c, 0x0b, 0x0a, 0x09, 0x08,
+            0xfe, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xf0, 0x3f,
+            0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, b'a', b'b',
+        ];
+        assert_eq!(writer.finish(), expected);
+    }
+
+    #[test]
+    fn test_round_trip() -> Result<()> {
+        let u64s = [0, 1, u32::MAX as u64, u32::MAX as u64 + 1, u64::MAX];
+        let i64s = [i64::MIN, -1, 0, 1, i64::MAX];
+        let f64s = [
+            0.0,
+            -0.0,
+            1.5,
+            f64::MIN_POSITIVE,
+            f64::MAX,
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+            f64::NAN,
+        ];
+        let bytes: [&[u8]; 3] = [b"", b"x", &[0xff; 300]];
+
+        let mut writer = StateWriter::new();
+        u64s.iter().for_each(|v| writer.put_u64(*v));
+        i64s.iter().for_each(|v| writer.put_i64(*v));
+        f64s.iter().for_each(|v| writer.put_f64(*v));
+        bytes.iter().for_each(|v| writer.put_bytes(v));
+        for v in [0, 1, u16::MAX as u32, u32::MAX] {
+            writer.put_u32(v);
+            writer.put_u16(v as u16);
+            writer.put_u8(v as u8);
+        }
+        let encoded = writer.finish();
+
+        let mut reader = StateReader::new(&encoded);
+        for v in u64s {
+            assert_eq!(reader.get_u64()?, v);
+        }
+        for v in i64s {
+            assert_eq!(reader.get_i64()?, v);
+        }
+        for v in f64s {
+            // compare bits so NaN and -0.0 round trip exactly
+            assert_eq!(reader.get_f64()?.to_bits(), v.to_bits());
+        }
+        for v in bytes {
+            assert_eq!(reader.get_bytes()?, v);
+        }
+        for v in [0, 1, u16::MAX as u32, u32::MAX] {
+            assert_eq!(reader.get_u32()?, v);
+            assert_eq!(reader.get_u16()?, v as u16);
+            assert_eq!(reader.get_u8()?, v as u8);
+        }
+        reader.finish()
+    }
+
+    #[test]
+    fn test_truncated_and_trailing_input() {
+        let mut writer = StateWriter::new();
+        writer.put_u64(7);
+        let encoded = writer.finish();
+
+        for len in 0..encoded.len() {
+            assert!(StateReader::new(&encoded[..len]).get_u64().is_err());
+        }
+        assert!(StateReader::new(&encoded).finish().is_err());
+    }
+
+    #[test]
+    fn test_lengths_are_bounded_by_input() {
+        let mut writer = StateWriter::new();
+        writer.put_u64(u64::MAX);
+        let encoded = writer.finish();
+
+        assert!(StateReader::new(&encoded).get_bytes().is_err());
+        assert!(StateReader::new(&encoded).get_count(8).is_err());
+    }
+}
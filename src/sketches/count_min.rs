@@ -0,0 +1,213 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Count-Min sketch for approximate frequencies.
+//!
+//! See Cormode, G. and Muthukrishnan, S. (2005). "An Improved Data Stream Summary: The Count-Min
+//! Sketch and its Applications". A sketch has `depth` rows of `width` counters, and a value adds
+//! its weight to one counter per row, chosen by hashing. The smallest of its counters
+//! overestimates the frequency of a value by at most `e / width` of the total weight with
+//! probability `1 - e^-depth`.
+
+use datafusion::common::exec_err;
+use datafusion::error::Result;
+
+use crate::sketches::format::{self, SketchKind};
+
+/// Default number of counters per row, overestimating by at most 0.13% of the total
+pub const DEFAULT_WIDTH: usize = 2048;
+
+/// Default number of rows, failing the error bound with probability e^-5, 0.7%
+pub const DEFAULT_DEPTH: usize = 5;
+
+/// Smallest and largest number of counters per row
+pub const WIDTH_RANGE: std::ops::RangeInclusive<usize> = 1..=(1 << 20);
+
+/// Smallest and largest number of rows
+pub const DEPTH_RANGE: std::ops::RangeInclusive<usize> = 1..=16;
+
+/// A Count-Min sketch over 64-bit hashes.
+///
+/// Sketches only estimate the frequencies of values hashed the same way as their inputs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CountMinSketch {
+    width: usize,
+    depth: usize,
+    /// Total weight added
+    total: u64,
+    /// `depth` rows of `width` counters
+    counters: Vec<u64>,
+}
+
+impl Default for CountMinSketch {
+    fn default() -> Self {
+        Self::new(DEFAULT_WIDTH, DEFAULT_DEPTH)
+    }
+}
+
+impl CountMinSketch {
+    /// Creates an empty sketch, `width` and `depth` must be in [`WIDTH_RANGE`] and
+    /// [`DEPTH_RANGE`]
+    pub fn new(width: usize, depth: usize) -> Self {
+        assert!(
+            WIDTH_RANGE.contains(&width) && DEPTH_RANGE.contains(&depth),
+            "Invalid Count-Min sketch of width {width} and depth {depth}"
+        );
+        Self {
+            width,
+            depth,
+            total: 0,
+            counters: vec![0; width * depth],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Total weight added
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// Adds `weight` occurrences of the value hashed to `hash`
+    pub fn add_hash(&mut self, hash: u64, weight: u64) {
+        self.total = self.total.saturating_add(weight);
+        for index in indexes(self.width, self.depth, hash) {
+            self.counters[index] = self.counters[index].saturating_add(weight);
+        }
+    }
+
+    /// Estimates the number of occurrences of the value hashed to `hash`, never less than the
+    /// actual number
+    pub fn estimate(&self, hash: u64) -> u64 {
+        indexes(self.width, self.depth, hash)
+            .map(|index| self.counters[index])
+            .min()
+            .unwrap_or(0)
+    }
+
+    /// Merges `other` into this sketch, which must have the same dimensions
+    pub fn merge(&mut self, other: &CountMinSketch) -> Result<()> {
+        if (self.width, self.depth) != (other.width, other.depth) {
+            return exec_err!(
+                "Can't merge a Count-Min sketch of width {} and depth {} into one of width {} and depth {}",
+                other.width,
+                other.depth,
+                self.width,
+                self.depth
+            );
+        }
+        self.total = self.total.saturating_add(other.total);
+        for (counter, other) in self.counters.iter_mut().zip(&other.counters) {
+            *counter = counter.saturating_add(*other);
+        }
+        Ok(())
+    }
+
+    pub fn size(&self) -> usize {
+        std::mem::size_of_val(self) + self.counters.capacity() * std::mem::size_of::<u64>()
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        format::encode(SketchKind::CountMin, |writer| {
+            writer.put_len(self.width);
+            writer.put_len(self.depth);
+            writer.put_u64(self.total);
+            self.counters.iter().for_each(|counter| writer.put_u64(*counter));
+        })
+    }
+
+    /// Deserializes a sketch written by [`Self::to_bytes`]
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        format::decode(bytes, SketchKind::CountMin, |_version, reader| {
+            let width = reader.get_len()?;
+            let depth = reader.get_len()?;
+            if !WIDTH_RANGE.contains(&width) || !DEPTH_RANGE.contains(&depth) {
+                return exec_err!("Invalid Count-Min sketch of width {width} and depth {depth}");
+            }
+            let total = reader.get_u64()?;
+            if reader.remaining() != width * depth * std::mem::size_of::<u64>() {
+                return exec_err!("Invalid Count-Min sketch state: expected {} counters", width * depth);
+            }
+            let counters = (0..width * depth)
+                .map(|_| reader.get_u64())
+                .collect::<Result<Vec<_>>>()?;
+            Ok(Self {
+                width,
+                depth,
+                total,
+                counters,
+            })
+        })
+    }
+}
+
+/// The index of the counter of `hash` in every row of a sketch, derived from the two halves of
+/// the hash as in Kirsch and Mitzenmacher's "Less Hashing, Same Performance"
+fn indexes(width: usize, depth: usize, hash: u64) -> impl Iterator<Item = usize> {
+    let (h1, h2) = (hash & 0xFFFF_FFFF, hash >> 32);
+    (0..depth).map(move |row| {
+        let column = h1.wrapping_add((row as u64).wrapping_mul(h2)) % width as u64;
+        row * width + column as usize
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::hash::{BuildHasher, Hasher};
+
+    fn hash(value: u64) -> u64 {
+        let mut hasher = ahash::RandomState::with_seeds(1, 2, 3, 4).build_hasher();
+        hasher.write_u64(value);
+        hasher.finish()
+    }
+
+    #[test]
+    fn test_count_min_sketch_overestimates_within_bound() -> Result<()> {
+        let mut a = CountMinSketch::new(256, 4);
+        let mut b = CountMinSketch::new(256, 4);
+        for value in 0..1000u64 {
+            a.add_hash(hash(value), 1);
+        }
+        b.add_hash(hash(7), 500);
+        a.merge(&b)?;
+        assert_eq!(a.total(), 1500);
+
+        let estimate = a.estimate(hash(7));
+        // e / 256 of the total weight
+        assert!((501..=501 + 16).contains(&estimate), "estimated {estimate}");
+        for value in 0..1000u64 {
+            assert!(a.estimate(hash(value)) >= 1);
+        }
+        assert_eq!(CountMinSketch::from_bytes(&a.to_bytes())?, a);
+        Ok(())
+    }
+
+    #[test]
+    fn test_count_min_sketch_rejects_mismatches() {
+        let mut a = CountMinSketch::new(256, 4);
+        assert!(a.merge(&CountMinSketch::new(256, 5)).is_err());
+        let bytes = a.to_bytes();
+        assert!(CountMinSketch::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+    }
+}
@@ -0,0 +1,231 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Versioned envelope for serialized sketch states.
+//!
+//! Serialized states may be stored in tables and read back by a later release of this crate,
+//! so each one starts with a header:
+//!
+//! | offset | size | content                                   |
+//! |--------|------|-------------------------------------------|
+//! | 0      | 2    | magic bytes [`MAGIC`]                      |
+//! | 2      | 1    | [`SketchKind`] of the payload              |
+//! | 3      | 1    | payload format version, starting at 1      |
+//! | 4      | ..   | payload, encoded with [`super::codec`]     |
+//!
+//! When a payload format changes, bump the version returned by [`SketchKind::current_version`]
+//! and keep decoding the older versions in the sketch's decoder. Versions newer than the current
+//! one were written by a later release and are rejected with an error rather than misread.
+
+use datafusion::common::exec_err;
+use datafusion::error::Result;
+
+use crate::sketches::codec::{StateReader, StateWriter};
+
+/// Magic bytes at the start of every serialized sketch
+pub const MAGIC: [u8; 2] = *b"DX";
+
+/// Size in bytes of the header written by [`encode`]
+pub const HEADER_SIZE: usize = 4;
+
+/// Identifies the sketch a serialized state belongs to, so decoding one sketch's state as
+/// another fails instead of silently producing garbage. Values are part of the format and
+/// must never be reused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum SketchKind {
+    Moments = 1,
+    HyperLogLog = 2,
+    TDigest = 3,
+    /// A [`super::space_saving`] summary of hashes
+    SpaceSaving = 4,
+    Bitmap = 5,
+    Bloom = 6,
+    Theta = 7,
+    CountMin = 8,
+    MinHash = 9,
+}
+
+impl SketchKind {
+    /// The payload version written by this release
+    pub fn current_version(self) -> u8 {
+        match self {
+            SketchKind::HyperLogLog => 2,
+            SketchKind::Moments
+            | SketchKind::SpaceSaving
+            | SketchKind::TDigest
+            | SketchKind::Bitmap
+            | SketchKind::Bloom
+            | SketchKind::Theta
+            | SketchKind::CountMin
+            | SketchKind::MinHash => 1,
+        }
+    }
+
+    fn try_from_u8(value: u8) -> Option<Self> {
+        [
+            SketchKind::Moments,
+            SketchKind::HyperLogLog,
+            SketchKind::TDigest,
+            SketchKind::SpaceSaving,
+            SketchKind::Bitmap,
+            SketchKind::Bloom,
+            SketchKind::Theta,
+            SketchKind::CountMin,
+            SketchKind::MinHash,
+        ]
+        .into_iter()
+        .find(|kind| *kind as u8 == value)
+    }
+}
+
+/// Serializes a sketch of `kind` with the current version header, `write_payload` writes the
+/// sketch itself
+pub fn encode(kind: SketchKind, write_payload: impl FnOnce(&mut StateWriter)) -> Vec<u8> {
+    let mut writer = StateWriter::new();
+    writer.put_u8(MAGIC[0]);
+    writer.put_u8(MAGIC[1]);
+    writer.put_u8(kind as u8);
+    writer.put_u8(kind.current_version());
+    write_payload(&mut writer);
+    writer.finish()
+}
+
+/// Deserializes a sketch of `kind` written by [`encode`].
+///
+/// The header is validated before `read_payload` is called with the payload version, which is
+/// between 1 and [`SketchKind::current_version`], so it can upgrade states written by older
+/// releases. The payload must be consumed entirely.
+pub fn decode<T>(
+    bytes: &[u8],
+    kind: SketchKind,
+    read_payload: impl FnOnce(u8, &mut StateReader) -> Result<T>,
+) -> Result<T> {
+    let mut reader = StateReader::new(bytes);
+    let version = read_header(&mut reader, kind)?;
+    let sketch = read_payload(version, &mut reader)?;
+    reader.finish()?;
+    Ok(sketch)
+}
+
+fn read_header(reader: &mut StateReader, kind: SketchKind) -> Result<u8> {
+    if reader.remaining() < HEADER_SIZE {
+        return exec_err!("Invalid {:?} sketch state: missing header", kind);
+    }
+    let magic = [reader.get_u8()?, reader.get_u8()?];
+    if magic != MAGIC {
+        return exec_err!("Invalid {:?} sketch state: unexpected magic bytes {:?}", kind, magic);
+    }
+    let found = reader.get_u8()?;
+    if found != kind as u8 {
+        return match SketchKind::try_from_u8(found) {
+            Some(found) => exec_err!("Expected a {:?} sketch state, found a {:?} sketch state", kind, found),
+            None => exec_err!(
+                "Expected a {:?} sketch state, found unknown sketch kind {}",
+                kind,
+                found
+            ),
+        };
+    }
+    let version = reader.get_u8()?;
+    if version == 0 {
+        return exec_err!("Invalid {:?} sketch state: version 0", kind);
+    }
+    if version > kind.current_version() {
+        return exec_err!(
+            "{:?} sketch state has version {}, but this release only reads up to version {}; \
+             it was likely written by a newer release",
+            kind,
+            version,
+            kind.current_version()
+        );
+    }
+    Ok(version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode_u64(kind: SketchKind, value: u64) -> Vec<u8> {
+        encode(kind, |writer| writer.put_u64(value))
+    }
+
+    fn decode_u64(bytes: &[u8], kind: SketchKind) -> Result<u64> {
+        decode(bytes, kind, |_, reader| reader.get_u64())
+    }
+
+    #[test]
+    fn test_header_layout() {
+        let bytes = encode_u64(SketchKind::TDigest, 7);
+        assert_eq!(bytes[..HEADER_SIZE], [b'D', b'X', 3, 1]);
+        assert_eq!(bytes[HEADER_SIZE..], 7u64.to_le_bytes());
+    }
+
+    #[test]
+    fn test_round_trip_passes_version() -> Result<()> {
+        let bytes = encode_u64(SketchKind::Bloom, 42);
+        let decoded = decode(&bytes, SketchKind::Bloom, |version, reader| {
+            assert_eq!(version, SketchKind::Bloom.current_version());
+            reader.get_u64()
+        })?;
+        assert_eq!(decoded, 42);
+        Ok(())
+    }
+
+    #[test]
+    fn test_rejects_invalid_headers() {
+        let bytes = encode_u64(SketchKind::HyperLogLog, 1);
+
+        let err = decode_u64(&bytes, SketchKind::SpaceSaving).unwrap_err().to_string();
+        assert!(
+            err.contains("Expected a SpaceSaving sketch state, found a HyperLogLog"),
+            "{err}"
+        );
+
+        let mut unknown = bytes.clone();
+        unknown[2] = 200;
+        assert!(decode_u64(&unknown, SketchKind::HyperLogLog).is_err());
+
+        let mut bad_magic = bytes.clone();
+        bad_magic[0] = 0;
+        assert!(decode_u64(&bad_magic, SketchKind::HyperLogLog).is_err());
+
+        let mut version_zero = bytes.clone();
+        version_zero[3] = 0;
+        assert!(decode_u64(&version_zero, SketchKind::HyperLogLog).is_err());
+
+        for len in 0..HEADER_SIZE {
+            assert!(decode_u64(&bytes[..len], SketchKind::HyperLogLog).is_err());
+        }
+    }
+
+    #[test]
+    fn test_rejects_newer_versions() {
+        let mut bytes = encode_u64(SketchKind::Bitmap, 1);
+        bytes[3] = SketchKind::Bitmap.current_version() + 1;
+        let err = decode_u64(&bytes, SketchKind::Bitmap).unwrap_err().to_string();
+        assert!(err.contains("written by a newer release"), "{err}");
+    }
+
+    #[test]
+    fn test_rejects_trailing_payload() {
+        let mut bytes = encode_u64(SketchKind::Moments, 1);
+        bytes.push(0);
+        assert!(decode_u64(&bytes, SketchKind::Moments).is_err());
+    }
+}
@@ -0,0 +1,378 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! HyperLogLog for approximate distinct counts.
+//!
+//! See Flajolet, P. et al. (2007). "HyperLogLog: the analysis of a near-optimal cardinality
+//! estimation algorithm", with linear counting for small cardinalities. Hashes are 64-bit, so no
+//! large range correction is needed.
+//!
+//! As in HyperLogLog++ (Heule, S. et al. (2013). "HyperLogLog in Practice"), a sketch starts
+//! with a sparse list of the registers of a 2^25-register sketch, which estimates small
+//! cardinalities with linear counting in much less memory than the dense registers. The list is
+//! converted to the dense registers once it would take more memory than them.
+
+use datafusion::common::exec_err;
+use datafusion::error::Result;
+
+use crate::sketches::format::{self, SketchKind};
+
+/// Default precision, giving 16384 registers and a standard error of about 0.8%
+pub const DEFAULT_PRECISION: u8 = 14;
+
+/// Smallest and largest precision of a sketch
+pub const PRECISION_RANGE: std::ops::RangeInclusive<u8> = 4..=18;
+
+/// Precision of the sparse representation
+const SPARSE_PRECISION: u8 = 25;
+
+/// Bits of a sparse entry holding the rank, below the 25-bit register index
+const SPARSE_RANK_BITS: u32 = 6;
+
+/// The index of the register of `hash` at `precision` and the rank of its remaining bits
+fn index_and_rank(hash: u64, precision: u8) -> (usize, u8) {
+    let index = (hash >> (64 - precision)) as usize;
+    // The remaining bits, with a sentinel bit bounding the rank
+    let rest = (hash << precision) | (1 << (precision - 1));
+    (index, rest.leading_zeros() as u8 + 1)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Registers {
+    /// The non-zero registers at [`SPARSE_PRECISION`], encoded as `index << 6 | rank` and
+    /// sorted by index
+    Sparse(Vec<u32>),
+    /// The `2^precision` registers
+    Dense(Vec<u8>),
+}
+
+/// A HyperLogLog sketch over 64-bit hashes, with `2^precision` registers.
+///
+/// Sketches only estimate the union of their inputs when merged if the values were hashed the
+/// same way.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HyperLogLog {
+    precision: u8,
+    registers: Registers,
+}
+
+impl Default for HyperLogLog {
+    fn default() -> Self {
+        Self::new(DEFAULT_PRECISION)
+    }
+}
+
+impl HyperLogLog {
+    /// Creates an empty sketch, `precision` must be in [`PRECISION_RANGE`]
+    pub fn new(precision: u8) -> Self {
+        assert!(
+            PRECISION_RANGE.contains(&precision),
+            "Invalid HyperLogLog precision {precision}"
+        );
+        Self {
+            precision,
+            registers: Registers::Sparse(vec![]),
+        }
+    }
+
+    pub fn precision(&self) -> u8 {
+        self.precision
+    }
+
+    /// The largest number of sparse entries, which take as much memory as the dense registers
+    fn max_sparse_len(&self) -> usize {
+        (1 << self.precision) / std::mem::size_of::<u32>()
+    }
+
+    /// Adds the value hashed to `hash`
+    pub fn add_hash(&mut self, hash: u64) {
+        match &mut self.registers {
+            Registers::Sparse(entries) => {
+                let (index, rank) = index_and_rank(hash, SPARSE_PRECISION);
+                let entry = (index as u32) << SPARSE_RANK_BITS | rank as u32;
+                match entries.binary_search_by_key(&(index as u32), |entry| entry >> SPARSE_RANK_BITS) {
+                    Ok(i) => entries[i] = entries[i].max(entry),
+                    Err(i) => entries.insert(i, entry),
+                }
+                if entries.len() > self.max_sparse_len() {
+                    self.densify();
+                }
+            }
+            Registers::Dense(registers) => {
+                let (index, rank) = index_and_rank(hash, self.precision);
+                registers[index] = registers[index].max(rank);
+            }
+        }
+    }
+
+    /// The dense register index and rank of the sparse `entry`
+    fn dense_register(&self, entry: u32) -> (usize, u8) {
+        let shift = SPARSE_PRECISION - self.precision;
+        let sparse_index = entry >> SPARSE_RANK_BITS;
+        let sparse_rank = (entry & ((1 << SPARSE_RANK_BITS) - 1)) as u8;
+        let index = (sparse_index >> shift) as usize;
+        // The bits of the sparse index below the dense index come first in the dense rank
+        let low = sparse_index & ((1 << shift) - 1);
+        let rank = if low == 0 {
+            shift + sparse_rank
+        } else {
+            (low.leading_zeros() - (32 - shift as u32)) as u8 + 1
+        };
+        (index, rank)
+    }
+
+    /// Converts the sparse entries into the dense registers
+    fn densify(&mut self) {
+        let Registers::Sparse(entries) = &self.registers else {
+            return;
+        };
+        let mut registers = vec![0; 1 << self.precision];
+        for &entry in entries {
+            let (index, rank) = self.dense_register(entry);
+            registers[index] = registers[index].max(rank);
+        }
+        self.registers = Registers::Dense(registers);
+    }
+
+    /// Merges `other` into this sketch
+    pub fn merge(&mut self, other: &HyperLogLog) -> Result<()> {
+        if other.precision != self.precision {
+            return exec_err!(
+                "Cannot merge HyperLogLog sketches of precisions {} and {}",
+                self.precision,
+                other.precision
+            );
+        }
+        if let (Registers::Sparse(entries), Registers::Sparse(other_entries)) = (&self.registers, &other.registers) {
+            let merged = merge_sparse(entries, other_entries);
+            let outgrown = merged.len() > self.max_sparse_len();
+            self.registers = Registers::Sparse(merged);
+            if outgrown {
+                self.densify();
+            }
+            return Ok(());
+        }
+        self.densify();
+        let Registers::Dense(registers) = &mut self.registers else {
+            unreachable!("the registers were just densified");
+        };
+        match &other.registers {
+            Registers::Sparse(entries) => {
+                for &entry in entries {
+                    let (index, rank) = other.dense_register(entry);
+                    registers[index] = registers[index].max(rank);
+                }
+            }
+            Registers::Dense(other) => {
+                for (register, other) in registers.iter_mut().zip(other) {
+                    *register = (*register).max(*other);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Estimates the number of distinct hashes added
+    pub fn count(&self) -> u64 {
+        let registers = match &self.registers {
+            Registers::Sparse(entries) => {
+                // Linear counting over the 2^25 sparse registers
+                let m = (1u64 << SPARSE_PRECISION) as f64;
+                let zeros = m - entries.len() as f64;
+                return (m * (m / zeros).ln()).round() as u64;
+            }
+            Registers::Dense(registers) => registers,
+        };
+        let m = registers.len() as f64;
+        let alpha = 0.7213 / (1.0 + 1.079 / m);
+        let sum: f64 = registers.iter().map(|&r| 2f64.powi(-(r as i32))).sum();
+        let estimate = alpha * m * m / sum;
+        let zeros = registers.iter().filter(|&&r| r == 0).count();
+        if estimate <= 2.5 * m && zeros > 0 {
+            (m * (m / zeros as f64).ln()).round() as u64
+        } else {
+            estimate.round() as u64
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        std::mem::size_of_val(self)
+            + match &self.registers {
+                Registers::Sparse(entries) => entries.capacity() * std::mem::size_of::<u32>(),
+                Registers::Dense(registers) => registers.capacity(),
+            }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        format::encode(SketchKind::HyperLogLog, |writer| {
+            writer.put_u8(self.precision);
+            match &self.registers {
+                Registers::Sparse(entries) => {
+                    writer.put_u8(0);
+                    writer.put_len(entries.len());
+                    entries.iter().for_each(|entry| writer.put_u32(*entry));
+                }
+                Registers::Dense(registers) => {
+                    writer.put_u8(1);
+                    writer.put_bytes(registers);
+                }
+            }
+        })
+    }
+
+    /// Deserializes a sketch written by [`Self::to_bytes`].
+    ///
+    /// Version 1 states, written before the sparse representation, only hold dense registers.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        format::decode(bytes, SketchKind::HyperLogLog, |version, reader| {
+            let precision = reader.get_u8()?;
+            if !PRECISION_RANGE.contains(&precision) {
+                return exec_err!("Invalid HyperLogLog precision {precision}");
+            }
+            let sparse = version >= 2 && reader.get_u8()? == 0;
+            if sparse {
+                let len = reader.get_count(std::mem::size_of::<u32>())?;
+                let entries = (0..len).map(|_| reader.get_u32()).collect::<Result<Vec<_>>>()?;
+                let sorted = entries
+                    .windows(2)
+                    .all(|pair| pair[0] >> SPARSE_RANK_BITS < pair[1] >> SPARSE_RANK_BITS);
+                if !sorted
+                    || entries
+                        .iter()
+                        .any(|entry| entry >> SPARSE_RANK_BITS >= 1 << SPARSE_PRECISION)
+                {
+                    return exec_err!("Invalid HyperLogLog state: unsorted or out of range sparse registers");
+                }
+                return Ok(Self {
+                    precision,
+                    registers: Registers::Sparse(entries),
+                });
+            }
+            let registers = reader.get_bytes()?;
+            if registers.len() != 1 << precision {
+                return exec_err!(
+                    "Invalid HyperLogLog state: {} registers for precision {precision}",
+                    registers.len()
+                );
+            }
+            Ok(Self {
+                precision,
+                registers: Registers::Dense(registers.to_vec()),
+            })
+        })
+    }
+}
+
+/// Merges two lists of sparse entries sorted by index, keeping the largest rank of every index
+fn merge_sparse(left: &[u32], right: &[u32]) -> Vec<u32> {
+    let mut merged = Vec::with_capacity(left.len() + right.len());
+    let (mut i, mut j) = (0, 0);
+    while i < left.len() && j < right.len() {
+        let (l, r) = (left[i], right[j]);
+        match (l >> SPARSE_RANK_BITS).cmp(&(r >> SPARSE_RANK_BITS)) {
+            std::cmp::Ordering::Less => {
+                merged.push(l);
+                i += 1;
+            }
+            std::cmp::Ordering::Greater => {
+                merged.push(r);
+                j += 1;
+            }
+            std::cmp::Ordering::Equal => {
+                merged.push(l.max(r));
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    merged.extend_from_slice(&left[i..]);
+    merged.extend_from_slice(&right[j..]);
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::hash::{BuildHasher, Hasher};
+
+    fn hash(value: u64) -> u64 {
+        let mut hasher = ahash::RandomState::with_seeds(1, 2, 3, 4).build_hasher();
+        hasher.write_u64(value);
+        hasher.finish()
+    }
+
+    #[test]
+    fn test_hyperloglog_estimates() -> Result<()> {
+        let mut small = HyperLogLog::default();
+        (0..100).chain(0..100).for_each(|v| small.add_hash(hash(v)));
+        assert_eq!(small.count(), 100);
+
+        let mut partials = [HyperLogLog::default(), HyperLogLog::default()];
+        for v in 0..200_000 {
+            partials[(v % 2) as usize].add_hash(hash(v));
+        }
+        let mut merged = HyperLogLog::from_bytes(&partials[0].to_bytes())?;
+        merged.merge(&partials[1])?;
+        let count = merged.count() as f64;
+        assert!((count - 200_000.0).abs() < 200_000.0 * 0.03, "got {count}");
+
+        assert!(merged.merge(&HyperLogLog::new(10)).is_err());
+        assert_eq!(HyperLogLog::new(4).count(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_sparse_matches_dense() -> Result<()> {
+        let mut sparse = HyperLogLog::new(10);
+        let mut dense = HyperLogLog::new(10);
+        dense.densify();
+        for v in 0..200 {
+            sparse.add_hash(hash(v));
+            dense.add_hash(hash(v));
+        }
+        assert!(matches!(sparse.registers, Registers::Sparse(_)));
+        assert_eq!(HyperLogLog::from_bytes(&sparse.to_bytes())?, sparse);
+        sparse.densify();
+        assert_eq!(sparse, dense);
+
+        // merging sparse sketches converts them once they outgrow the dense registers
+        let mut merged = HyperLogLog::new(10);
+        for offset in [0, 200] {
+            let mut partial = HyperLogLog::new(10);
+            (offset..offset + 200).for_each(|v| partial.add_hash(hash(v)));
+            merged.merge(&partial)?;
+        }
+        assert!(matches!(merged.registers, Registers::Dense(_)));
+        let count = merged.count() as f64;
+        assert!((count - 400.0).abs() < 400.0 * 0.1, "got {count}");
+        Ok(())
+    }
+
+    #[test]
+    fn test_decodes_version_1() -> Result<()> {
+        let mut registers = vec![0; 16];
+        registers[3] = 2;
+        let mut bytes = format::encode(SketchKind::HyperLogLog, |writer| {
+            writer.put_u8(4);
+            writer.put_bytes(&registers);
+        });
+        bytes[3] = 1;
+        let sketch = HyperLogLog::from_bytes(&bytes)?;
+        assert_eq!(sketch.registers, Registers::Dense(registers));
+        Ok(())
+    }
+}
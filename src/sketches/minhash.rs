@@ -0,0 +1,212 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! MinHash signatures for estimating the Jaccard similarity of sets.
+//!
+//! See Broder, A. (1997). "On the resemblance and containment of documents". A signature keeps,
+//! for each of `k` hash functions, the smallest hash of the values of a set. Two sets have the
+//! same minimum for a hash function with probability equal to their Jaccard similarity, so the
+//! fraction of equal minima estimates it with a standard error of at most `0.5 / sqrt(k)`.
+//!
+//! The `k` hash functions are derived from a single 64-bit hash of each value by remixing it
+//! with a different constant per function.
+
+use datafusion::common::exec_err;
+use datafusion::error::Result;
+
+use crate::sketches::format::{self, SketchKind};
+
+/// Default number of hash functions, for a standard error of at most 4.4%
+pub const DEFAULT_NUM_HASHES: usize = 128;
+
+/// Smallest and largest number of hash functions
+pub const NUM_HASHES_RANGE: std::ops::RangeInclusive<usize> = 1..=(1 << 14);
+
+/// A MinHash signature over 64-bit hashes.
+///
+/// Signatures only compare with signatures of values hashed the same way.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MinHashSignature {
+    /// The smallest hash of every hash function, `u64::MAX` while the set is empty
+    mins: Vec<u64>,
+    /// Whether any value was added
+    empty: bool,
+}
+
+impl Default for MinHashSignature {
+    fn default() -> Self {
+        Self::new(DEFAULT_NUM_HASHES)
+    }
+}
+
+impl MinHashSignature {
+    /// Creates the signature of the empty set, `num_hashes` must be in [`NUM_HASHES_RANGE`]
+    pub fn new(num_hashes: usize) -> Self {
+        assert!(
+            NUM_HASHES_RANGE.contains(&num_hashes),
+            "Invalid MinHash signature of {num_hashes} hashes"
+        );
+        Self {
+            mins: vec![u64::MAX; num_hashes],
+            empty: true,
+        }
+    }
+
+    pub fn num_hashes(&self) -> usize {
+        self.mins.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.empty
+    }
+
+    /// Adds the value hashed to `hash`
+    pub fn add_hash(&mut self, hash: u64) {
+        self.empty = false;
+        for (index, min) in self.mins.iter_mut().enumerate() {
+            *min = (*min).min(permute(hash, index));
+        }
+    }
+
+    /// Merges `other` into this signature, making it the signature of the union of the sets.
+    /// Both must have the same number of hashes.
+    pub fn merge(&mut self, other: &MinHashSignature) -> Result<()> {
+        self.check_compatible(other)?;
+        self.empty &= other.empty;
+        for (min, other) in self.mins.iter_mut().zip(&other.mins) {
+            *min = (*min).min(*other);
+        }
+        Ok(())
+    }
+
+    /// Estimates the Jaccard similarity of the sets of this signature and `other`, `None` if both
+    /// are empty. Both must have the same number of hashes.
+    pub fn jaccard(&self, other: &MinHashSignature) -> Result<Option<f64>> {
+        self.check_compatible(other)?;
+        if self.empty && other.empty {
+            return Ok(None);
+        }
+        if self.empty || other.empty {
+            return Ok(Some(0.0));
+        }
+        let equal = self.mins.iter().zip(&other.mins).filter(|(a, b)| a == b).count();
+        Ok(Some(equal as f64 / self.mins.len() as f64))
+    }
+
+    fn check_compatible(&self, other: &MinHashSignature) -> Result<()> {
+        if self.mins.len() != other.mins.len() {
+            return exec_err!(
+                "Can't combine MinHash signatures of {} and {} hashes",
+                self.mins.len(),
+                other.mins.len()
+            );
+        }
+        Ok(())
+    }
+
+    pub fn size(&self) -> usize {
+        std::mem::size_of_val(self) + self.mins.capacity() * std::mem::size_of::<u64>()
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        format::encode(SketchKind::MinHash, |writer| {
+            writer.put_len(self.mins.len());
+            writer.put_u8(u8::from(self.empty));
+            self.mins.iter().for_each(|min| writer.put_u64(*min));
+        })
+    }
+
+    /// Deserializes a signature written by [`Self::to_bytes`]
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        format::decode(bytes, SketchKind::MinHash, |_version, reader| {
+            let num_hashes = reader.get_len()?;
+            if !NUM_HASHES_RANGE.contains(&num_hashes) {
+                return exec_err!("Invalid MinHash signature of {num_hashes} hashes");
+            }
+            let empty = match reader.get_u8()? {
+                0 => false,
+                1 => true,
+                flag => return exec_err!("Invalid MinHash signature state: empty flag {flag}"),
+            };
+            if reader.remaining() != num_hashes * std::mem::size_of::<u64>() {
+                return exec_err!("Invalid MinHash signature state: expected {num_hashes} hashes");
+            }
+            let mins = (0..num_hashes).map(|_| reader.get_u64()).collect::<Result<Vec<_>>>()?;
+            Ok(Self { mins, empty })
+        })
+    }
+}
+
+/// The hash of the `index`-th hash function for a value hashed to `hash`, remixing it with the
+/// SplitMix64 finalizer so that every function orders the values differently
+fn permute(hash: u64, index: usize) -> u64 {
+    let mut z = hash ^ (index as u64 + 1).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::hash::{BuildHasher, Hasher};
+
+    fn hash(value: u64) -> u64 {
+        let mut hasher = ahash::RandomState::with_seeds(1, 2, 3, 4).build_hasher();
+        hasher.write_u64(value);
+        hasher.finish()
+    }
+
+    fn signature(values: std::ops::Range<u64>) -> MinHashSignature {
+        let mut signature = MinHashSignature::new(512);
+        values.for_each(|value| signature.add_hash(hash(value)));
+        signature
+    }
+
+    #[test]
+    fn test_jaccard_estimate_within_bound() -> Result<()> {
+        // |A ∩ B| / |A ∪ B| = 500 / 1500
+        let (a, b) = (signature(0..1000), signature(500..1500));
+        let estimate = a.jaccard(&b)?.unwrap();
+        // 3 standard errors of 0.5 / sqrt(512)
+        assert!((estimate - 1.0 / 3.0).abs() < 0.07, "estimated {estimate}");
+        assert_eq!(a.jaccard(&a)?, Some(1.0));
+
+        let mut union = signature(0..500);
+        union.merge(&signature(500..1000))?;
+        assert_eq!(union, a);
+        Ok(())
+    }
+
+    #[test]
+    fn test_empty_signatures() -> Result<()> {
+        let empty = MinHashSignature::new(512);
+        assert_eq!(empty.jaccard(&empty)?, None);
+        assert_eq!(empty.jaccard(&signature(0..10))?, Some(0.0));
+        assert_eq!(MinHashSignature::from_bytes(&empty.to_bytes())?, empty);
+        Ok(())
+    }
+
+    #[test]
+    fn test_rejects_mismatches() {
+        let a = signature(0..10);
+        assert!(a.jaccard(&MinHashSignature::new(256)).is_err());
+        let bytes = a.to_bytes();
+        assert_eq!(MinHashSignature::from_bytes(&bytes).unwrap(), a);
+        assert!(MinHashSignature::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+    }
+}
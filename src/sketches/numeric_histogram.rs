@@ -0,0 +1,133 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+//! Streaming histogram of Hive and Spark's `histogram_numeric`.
+//!
+//! See Ben-Haim, Y. and Tom-Tov, E. (2010). "A Streaming Parallel Decision Tree Algorithm",
+//! and `NumericHistogram` in Hive.
+
+/// A bin of a [`NumericHistogram`]: `y` values whose mean is `x`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bin {
+    pub x: f64,
+    pub y: f64,
+}
+
+/// Approximates a distribution with at most `num_bins` bins, ordered by their center `x`.
+///
+/// A value equal to the center of a bin is counted in the bin; any other value becomes a bin of
+/// its own, and the two bins with the closest centers are then merged if there are too many.
+/// Unlike Hive, which picks one at random, ties between the closest bins merge the first.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NumericHistogram {
+    num_bins: usize,
+    bins: Vec<Bin>,
+}
+
+impl NumericHistogram {
+    pub fn new(num_bins: usize) -> Self {
+        Self {
+            num_bins: num_bins.max(1),
+            bins: vec![],
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bins.is_empty()
+    }
+
+    /// The bins, ordered by `x`
+    pub fn bins(&self) -> &[Bin] {
+        &self.bins
+    }
+
+    pub fn add(&mut self, value: f64) {
+        let index = self.bins.partition_point(|bin| bin.x < value);
+        match self.bins.get_mut(index) {
+            Some(bin) if bin.x == value => bin.y += 1.0,
+            _ => {
+                self.bins.insert(index, Bin { x: value, y: 1.0 });
+                self.trim();
+            }
+        }
+    }
+
+    /// Merges the bins of another histogram, which need not be ordered, as Hive merges
+    /// serialized histograms
+    pub fn merge(&mut self, bins: impl IntoIterator<Item = Bin>) {
+        self.bins.extend(bins);
+        self.bins.sort_by(|a, b| a.x.total_cmp(&b.x));
+        self.trim();
+    }
+
+    /// Merges the closest bins until there are at most `num_bins`
+    fn trim(&mut self) {
+        while self.bins.len() > self.num_bins {
+            let mut closest = 0;
+            let mut smallest_gap = f64::INFINITY;
+            for (i, pair) in self.bins.windows(2).enumerate() {
+                let gap = pair[1].x - pair[0].x;
+                if gap < smallest_gap {
+                    smallest_gap = gap;
+                    closest = i;
+                }
+            }
+            let (left, right) = (self.bins[closest], self.bins.remove(closest + 1));
+            let y = left.y + right.y;
+            self.bins[closest] = Bin {
+                x: (left.x * left.y + right.x * right.y) / y,
+                y,
+            };
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        std::mem::size_of_val(self) + self.bins.capacity() * std::mem::size_of::<Bin>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bins(histogram: &NumericHistogram) -> Vec<(f64, f64)> {
+        histogram.bins().iter().map(|bin| (bin.x, bin.y)).collect()
+    }
+
+    #[test]
+    fn test_merges_the_closest_bins() {
+        let mut histogram = NumericHistogram::new(3);
+        for value in [1.0, 10.0, 2.0, 10.0, 20.0, 4.0] {
+            histogram.add(value);
+        }
+        // 1 and 2 merge when 20 is added, then 1.5 (twice) and 4 when 4 is added
+        assert_eq!(bins(&histogram), vec![(7.0 / 3.0, 3.0), (10.0, 2.0), (20.0, 1.0)]);
+
+        let mut other = NumericHistogram::new(3);
+        other.add(30.0);
+        histogram.merge(other.bins().iter().copied());
+        assert_eq!(bins(&histogram), vec![(5.4, 5.0), (20.0, 1.0), (30.0, 1.0)]);
+    }
+
+    #[test]
+    fn test_equal_gaps_merge_the_first_bins() {
+        let mut histogram = NumericHistogram::new(2);
+        for value in [3.0, 2.0, 1.0] {
+            histogram.add(value);
+        }
+        assert_eq!(bins(&histogram), vec![(1.5, 2.0), (3.0, 1.0)]);
+    }
+}
@@ -0,0 +1,392 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+//! Roaring bitmap of 32-bit integers.
+//!
+//! See Chambi, S. et al. (2016). "Better bitmap performance with Roaring bitmaps". Values are
+//! split by their high 16 bits into containers holding the low 16 bits, either as a sorted
+//! array while there are at most [`ARRAY_MAX_LEN`] of them, or as a bitmap of 2^16 bits. Each
+//! container takes at most 8 KiB, so sparse and dense sets are both compact.
+
+use datafusion::common::exec_err;
+use datafusion::error::Result;
+
+use crate::sketches::format::{self, SketchKind};
+
+/// Largest number of values kept in an array container, beyond which a bitmap is smaller
+pub const ARRAY_MAX_LEN: usize = 4096;
+
+const BITMAP_WORDS: usize = 1 << 10;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Container {
+    /// Sorted low bits, between 1 and [`ARRAY_MAX_LEN`] of them
+    Array(Vec<u16>),
+    /// Bit `v % 64` of word `v / 64` is set for every low bits `v`, with the number of bits set,
+    /// more than [`ARRAY_MAX_LEN`]
+    Bitmap(Box<[u64; BITMAP_WORDS]>, usize),
+}
+
+impl Container {
+    /// Returns the container of the sorted and deduplicated `values`, `None` if empty
+    fn from_sorted(values: Vec<u16>) -> Option<Self> {
+        match values.len() {
+            0 => None,
+            len if len <= ARRAY_MAX_LEN => Some(Container::Array(values)),
+            len => {
+                let mut words = Box::new([0; BITMAP_WORDS]);
+                values.iter().for_each(|v| words[*v as usize / 64] |= 1 << (v % 64));
+                Some(Container::Bitmap(words, len))
+            }
+        }
+    }
+
+    /// Returns the container of the bits set in `words`, `None` if empty
+    fn from_words(words: Box<[u64; BITMAP_WORDS]>) -> Option<Self> {
+        let len = words.iter().map(|word| word.count_ones() as usize).sum::<usize>();
+        match len {
+            0 => None,
+            len if len <= ARRAY_MAX_LEN => Some(Container::Array(bits(&words).collect())),
+            len => Some(Container::Bitmap(words, len)),
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Container::Array(values) => values.len(),
+            Container::Bitmap(_, len) => *len,
+        }
+    }
+
+    fn contains(&self, value: u16) -> bool {
+        match self {
+            Container::Array(values) => values.binary_search(&value).is_ok(),
+            Container::Bitmap(words, _) => words[value as usize / 64] & (1 << (value % 64)) != 0,
+        }
+    }
+
+    fn insert(&mut self, value: u16) {
+        match self {
+            Container::Array(values) => {
+                if let Err(index) = values.binary_search(&value) {
+                    values.insert(index, value);
+                    if values.len() > ARRAY_MAX_LEN {
+                        *self = Container::from_sorted(std::mem::take(values)).unwrap();
+                    }
+                }
+            }
+            Container::Bitmap(words, len) => {
+                let word = &mut words[value as usize / 64];
+                if *word & (1 << (value % 64)) == 0 {
+                    *word |= 1 << (value % 64);
+                    *len += 1;
+                }
+            }
+        }
+    }
+
+    fn words(&self) -> Box<[u64; BITMAP_WORDS]> {
+        match self {
+            Container::Array(values) => {
+                let mut words = Box::new([0; BITMAP_WORDS]);
+                values.iter().for_each(|v| words[*v as usize / 64] |= 1 << (v % 64));
+                words
+            }
+            Container::Bitmap(words, _) => words.clone(),
+        }
+    }
+
+    fn union(&self, other: &Container) -> Container {
+        match (self, other) {
+            (Container::Array(a), Container::Array(b)) => {
+                let mut values = Vec::with_capacity(a.len() + b.len());
+                let (mut i, mut j) = (0, 0);
+                while i < a.len() && j < b.len() {
+                    match a[i].cmp(&b[j]) {
+                        std::cmp::Ordering::Less => {
+                            values.push(a[i]);
+                            i += 1;
+                        }
+                        std::cmp::Ordering::Greater => {
+                            values.push(b[j]);
+                            j += 1;
+                        }
+                        std::cmp::Ordering::Equal => {
+                            values.push(a[i]);
+                            i += 1;
+                            j += 1;
+                        }
+                    }
+                }
+                values.extend_from_slice(&a[i..]);
+                values.extend_from_slice(&b[j..]);
+                Container::from_sorted(values).unwrap()
+            }
+            (Container::Bitmap(words, _), other) | (other, Container::Bitmap(words, _)) => {
+                let mut union = other.words();
+                union.iter_mut().zip(words.iter()).for_each(|(a, b)| *a |= b);
+                Container::from_words(union).unwrap()
+            }
+        }
+    }
+
+    fn intersect(&self, other: &Container) -> Option<Container> {
+        match (self, other) {
+            (Container::Array(values), other) | (other, Container::Array(values)) => {
+                Container::from_sorted(values.iter().copied().filter(|v| other.contains(*v)).collect())
+            }
+            (Container::Bitmap(a, _), Container::Bitmap(b, _)) => {
+                let mut intersection = a.clone();
+                intersection.iter_mut().zip(b.iter()).for_each(|(a, b)| *a &= b);
+                Container::from_words(intersection)
+            }
+        }
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            Container::Array(values) => values.capacity() * std::mem::size_of::<u16>(),
+            Container::Bitmap(words, _) => std::mem::size_of_val(words.as_ref()),
+        }
+    }
+}
+
+/// Iterates the bits set in `words` in increasing order
+fn bits(words: &[u64; BITMAP_WORDS]) -> impl Iterator<Item = u16> + '_ {
+    words.iter().enumerate().flat_map(|(index, word)| {
+        let mut word = *word;
+        std::iter::from_fn(move || {
+            (word != 0).then(|| {
+                let bit = word.trailing_zeros();
+                word &= word - 1;
+                (index * 64) as u16 + bit as u16
+            })
+        })
+    })
+}
+
+/// A set of `u32` values stored as a Roaring bitmap.
+///
+/// Equal sets always have the same containers, so their serialized forms are equal too.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RoaringBitmap {
+    /// Non-empty containers sorted by the high 16 bits of their values
+    containers: Vec<(u16, Container)>,
+}
+
+impl RoaringBitmap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn split(value: u32) -> (u16, u16) {
+        ((value >> 16) as u16, value as u16)
+    }
+
+    pub fn insert(&mut self, value: u32) {
+        let (high, low) = Self::split(value);
+        match self.containers.binary_search_by_key(&high, |(key, _)| *key) {
+            Ok(index) => self.containers[index].1.insert(low),
+            Err(index) => self.containers.insert(index, (high, Container::Array(vec![low]))),
+        }
+    }
+
+    pub fn contains(&self, value: u32) -> bool {
+        let (high, low) = Self::split(value);
+        match self.containers.binary_search_by_key(&high, |(key, _)| *key) {
+            Ok(index) => self.containers[index].1.contains(low),
+            Err(_) => false,
+        }
+    }
+
+    /// Number of values in the set
+    pub fn len(&self) -> u64 {
+        self.containers
+            .iter()
+            .map(|(_, container)| container.len() as u64)
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.containers.is_empty()
+    }
+
+    /// Returns the values of either set
+    pub fn union(&self, other: &RoaringBitmap) -> RoaringBitmap {
+        let (a, b) = (&self.containers, &other.containers);
+        let mut containers = Vec::with_capacity(a.len().max(b.len()));
+        let (mut i, mut j) = (0, 0);
+        while i < a.len() && j < b.len() {
+            match a[i].0.cmp(&b[j].0) {
+                std::cmp::Ordering::Less => {
+                    containers.push(a[i].clone());
+                    i += 1;
+                }
+                std::cmp::Ordering::Greater => {
+                    containers.push(b[j].clone());
+                    j += 1;
+                }
+                std::cmp::Ordering::Equal => {
+                    containers.push((a[i].0, a[i].1.union(&b[j].1)));
+                    i += 1;
+                    j += 1;
+                }
+            }
+        }
+        containers.extend_from_slice(&a[i..]);
+        containers.extend_from_slice(&b[j..]);
+        RoaringBitmap { containers }
+    }
+
+    /// Returns the values of both sets
+    pub fn intersect(&self, other: &RoaringBitmap) -> RoaringBitmap {
+        let containers = self
+            .containers
+            .iter()
+            .filter_map(|(key, container)| {
+                let index = other.containers.binary_search_by_key(key, |(key, _)| *key).ok()?;
+                Some((*key, container.intersect(&other.containers[index].1)?))
+            })
+            .collect();
+        RoaringBitmap { containers }
+    }
+
+    /// Adds the values of `other`
+    pub fn merge(&mut self, other: &RoaringBitmap) {
+        *self = self.union(other);
+    }
+
+    pub fn size(&self) -> usize {
+        std::mem::size_of_val(self)
+            + self.containers.capacity() * std::mem::size_of::<(u16, Container)>()
+            + self
+                .containers
+                .iter()
+                .map(|(_, container)| container.size())
+                .sum::<usize>()
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        format::encode(SketchKind::Bitmap, |writer| {
+            writer.put_len(self.containers.len());
+            for (key, container) in &self.containers {
+                writer.put_u16(*key);
+                match container {
+                    Container::Array(values) => {
+                        writer.put_u8(0);
+                        writer.put_len(values.len());
+                        values.iter().for_each(|v| writer.put_u16(*v));
+                    }
+                    Container::Bitmap(words, _) => {
+                        writer.put_u8(1);
+                        words.iter().for_each(|word| writer.put_u64(*word));
+                    }
+                }
+            }
+        })
+    }
+
+    /// Deserializes a bitmap written by [`Self::to_bytes`]
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        format::decode(bytes, SketchKind::Bitmap, |_version, reader| {
+            // a key, a container type and at least one value each
+            let len = reader.get_count(5)?;
+            let mut containers = Vec::with_capacity(len);
+            for _ in 0..len {
+                let key = reader.get_u16()?;
+                if containers.last().is_some_and(|(last, _)| *last >= key) {
+                    return exec_err!("Invalid bitmap state: unsorted containers");
+                }
+                let container = match reader.get_u8()? {
+                    0 => {
+                        let len = reader.get_count(std::mem::size_of::<u16>())?;
+                        let values = (0..len).map(|_| reader.get_u16()).collect::<Result<Vec<_>>>()?;
+                        if len == 0 || len > ARRAY_MAX_LEN || !values.windows(2).all(|pair| pair[0] < pair[1]) {
+                            return exec_err!("Invalid bitmap state: invalid array container");
+                        }
+                        Container::Array(values)
+                    }
+                    1 => {
+                        let mut words = Box::new([0; BITMAP_WORDS]);
+                        for word in words.iter_mut() {
+                            *word = reader.get_u64()?;
+                        }
+                        match Container::from_words(words) {
+                            Some(container @ Container::Bitmap(..)) => container,
+                            _ => return exec_err!("Invalid bitmap state: sparse bitmap container"),
+                        }
+                    }
+                    kind => return exec_err!("Invalid bitmap state: unknown container type {kind}"),
+                };
+                containers.push((key, container));
+            }
+            Ok(Self { containers })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bitmap(values: impl IntoIterator<Item = u32>) -> RoaringBitmap {
+        let mut bitmap = RoaringBitmap::new();
+        values.into_iter().for_each(|v| bitmap.insert(v));
+        bitmap
+    }
+
+    #[test]
+    fn test_roaring_bitmap_set_operations() -> Result<()> {
+        // dense values switch their container to a bitmap, sparse values keep an array
+        let a = bitmap((0..10_000).chain([70_000, u32::MAX]));
+        let b = bitmap((5_000..20_000).step_by(2).chain([70_000, 1 << 20]));
+        assert_eq!(a.len(), 10_002);
+        assert!(a.contains(9_999) && a.contains(u32::MAX) && !a.contains(10_000));
+
+        let union = a.union(&b);
+        assert_eq!(
+            union,
+            bitmap(
+                (0..10_000)
+                    .chain((10_000..20_000).step_by(2))
+                    .chain([70_000, 1 << 20, u32::MAX])
+            )
+        );
+        let intersection = a.intersect(&b);
+        assert_eq!(intersection, bitmap((5_000..10_000).step_by(2).chain([70_000])));
+        assert!(a.intersect(&bitmap([10_001])).is_empty());
+
+        for bitmap in [a, b, union, intersection, RoaringBitmap::new()] {
+            assert_eq!(RoaringBitmap::from_bytes(&bitmap.to_bytes())?, bitmap);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_roaring_bitmap_rejects_invalid_states() {
+        let bytes = bitmap([1, 2, 3]).to_bytes();
+        assert!(RoaringBitmap::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        let unsorted = format::encode(SketchKind::Bitmap, |writer| {
+            writer.put_len(1);
+            writer.put_u16(0);
+            writer.put_u8(0);
+            writer.put_len(2);
+            writer.put_u16(2);
+            writer.put_u16(1);
+        });
+        assert!(RoaringBitmap::from_bytes(&unsorted).is_err());
+    }
+}
@@ -0,0 +1,342 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Space-Saving summary for the most frequent items of a stream.
+//!
+//! See Metwally, A. et al. (2005). "Efficient Computation of Frequent and Top-k Elements in Data
+//! Streams", and Agarwal, P. et al. (2012). "Mergeable Summaries" for merging two summaries.
+
+use std::collections::{BTreeSet, HashMap};
+use std::hash::Hash;
+
+use datafusion::common::exec_err;
+use datafusion::error::Result;
+
+use crate::sketches::format::{self, SketchKind};
+
+/// The estimated count of an item of a [`SpaceSaving`] summary.
+///
+/// `count` overestimates the occurrences of `item` by at most `error`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Counter<K> {
+    pub item: K,
+    pub count: u64,
+    pub error: u64,
+}
+
+/// Keeps counters for at most `capacity` items out of a stream of `total` items.
+///
+/// Counts are upper bounds that overcount by at most `total / capacity`, so every item
+/// occurring more often than that is kept. Counters are kept in the order their items were
+/// first counted.
+#[derive(Debug, Clone)]
+pub struct SpaceSaving<K> {
+    capacity: usize,
+    counters: Vec<Counter<K>>,
+    index: HashMap<K, usize>,
+    /// `(count, slot)` of every counter, to find the smallest in `O(log capacity)`
+    by_count: BTreeSet<(u64, usize)>,
+    /// When the item of every counter was first counted, to return the counters in that order
+    first_counted: Vec<u64>,
+    next_seq: u64,
+}
+
+impl<K: Hash + Eq + Clone> SpaceSaving<K> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            counters: vec![],
+            index: HashMap::new(),
+            by_count: BTreeSet::new(),
+            first_counted: vec![],
+            next_seq: 0,
+        }
+    }
+
+    /// Rebuilds a summary from counters returned by [`Self::counters`]
+    pub fn from_counters(capacity: usize, counters: impl IntoIterator<Item = Counter<K>>) -> Result<Self> {
+        let mut summary = Self::new(capacity);
+        for counter in counters {
+            if summary.counters.len() == summary.capacity {
+                return exec_err!("Invalid SpaceSaving state: more counters than capacity {capacity}");
+            }
+            if summary.index.contains_key(&counter.item) {
+                return exec_err!("Invalid SpaceSaving state: duplicate counter");
+            }
+            summary.push(counter);
+        }
+        Ok(summary)
+    }
+
+    pub fn len(&self) -> usize {
+        self.counters.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.counters.is_empty()
+    }
+
+    fn push(&mut self, counter: Counter<K>) {
+        let slot = self.counters.len();
+        self.index.insert(counter.item.clone(), slot);
+        self.by_count.insert((counter.count, slot));
+        self.first_counted.push(self.next_seq);
+        self.next_seq += 1;
+        self.counters.push(counter);
+    }
+
+    /// The smallest count, which bounds the occurrences of the items without a counter, or 0
+    /// while there are fewer counters than the capacity
+    pub fn min_count(&self) -> u64 {
+        if self.counters.len() < self.capacity {
+            return 0;
+        }
+        self.by_count.first().map_or(0, |(count, _)| *count)
+    }
+
+    /// Adds `weight` occurrences of `item`
+    pub fn add(&mut self, item: K, weight: u64) {
+        if let Some(&slot) = self.index.get(&item) {
+            let counter = &mut self.counters[slot];
+            self.by_count.remove(&(counter.count, slot));
+            counter.count += weight;
+            self.by_count.insert((counter.count, slot));
+        } else if self.counters.len() < self.capacity {
+            self.push(Counter {
+                item,
+                count: weight,
+                error: 0,
+            });
+        } else {
+            // Replace the item with the smallest count, which may have occurred that many times
+            let (min, slot) = self.by_count.pop_first().expect("the summary is full");
+            self.index.remove(&self.counters[slot].item);
+            self.index.insert(item.clone(), slot);
+            self.counters[slot] = Counter {
+                item,
+                count: min + weight,
+                error: min,
+            };
+            self.by_count.insert((min + weight, slot));
+            self.first_counted[slot] = self.next_seq;
+            self.next_seq += 1;
+        }
+    }
+
+    /// Merges `other` into this summary.
+    ///
+    /// An item missing from one of the summaries may have occurred as often as its smallest
+    /// count, which is added to the count and the error of the item. The `capacity` largest
+    /// counts are kept.
+    pub fn merge(&mut self, other: SpaceSaving<K>) {
+        let (min, other_min) = (self.min_count(), other.min_count());
+        let mut merged: Vec<Counter<K>> = self.counters().cloned().collect();
+        let mut positions: HashMap<K, usize> = merged
+            .iter()
+            .enumerate()
+            .map(|(i, counter)| (counter.item.clone(), i))
+            .collect();
+        let mut in_other = vec![false; merged.len()];
+        for counter in other.counters() {
+            match positions.get(&counter.item) {
+                Some(&i) => {
+                    merged[i].count += counter.count;
+                    merged[i].error += counter.error;
+                    in_other[i] = true;
+                }
+                None => {
+                    positions.insert(counter.item.clone(), merged.len());
+                    merged.push(Counter {
+                        item: counter.item.clone(),
+                        count: counter.count + min,
+                        error: counter.error + min,
+                    });
+                }
+            }
+        }
+        for (counter, _) in merged.iter_mut().zip(&in_other).filter(|(_, in_other)| !**in_other) {
+            counter.count += other_min;
+            counter.error += other_min;
+        }
+
+        let kept = largest(&merged, self.capacity);
+        *self = Self::new(self.capacity);
+        for (i, counter) in merged.into_iter().enumerate() {
+            if kept[i] {
+                self.push(counter);
+            }
+        }
+    }
+
+    /// The counters in the order their items were first counted
+    pub fn counters(&self) -> impl Iterator<Item = &Counter<K>> {
+        let mut slots: Vec<usize> = (0..self.counters.len()).collect();
+        slots.sort_unstable_by_key(|&slot| self.first_counted[slot]);
+        slots.into_iter().map(|slot| &self.counters[slot])
+    }
+
+    /// The memory used by the summary, besides the heap allocations of the items
+    pub fn size(&self) -> usize {
+        std::mem::size_of_val(self)
+            + self.counters.capacity() * std::mem::size_of::<Counter<K>>()
+            + self.index.capacity() * std::mem::size_of::<(K, usize)>()
+            + self.by_count.len() * std::mem::size_of::<(u64, usize)>()
+            + self.first_counted.capacity() * std::mem::size_of::<u64>()
+    }
+}
+
+impl SpaceSaving<u64> {
+    /// Serializes a summary of hashes, with its counters in the order they were first counted
+    pub fn to_bytes(&self) -> Vec<u8> {
+        format::encode(SketchKind::SpaceSaving, |writer| {
+            writer.put_len(self.capacity);
+            writer.put_len(self.counters.len());
+            for counter in self.counters() {
+                writer.put_u64(counter.item);
+                writer.put_u64(counter.count);
+                writer.put_u64(counter.error);
+            }
+        })
+    }
+
+    /// Deserializes a summary written by [`Self::to_bytes`]
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        format::decode(bytes, SketchKind::SpaceSaving, |_version, reader| {
+            let capacity = reader.get_len()?;
+            let len = reader.get_count(24)?;
+            let counters = (0..len)
+                .map(|_| {
+                    Ok(Counter {
+                        item: reader.get_u64()?,
+                        count: reader.get_u64()?,
+                        error: reader.get_u64()?,
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?;
+            Self::from_counters(capacity, counters)
+        })
+    }
+}
+
+/// Flags the `n` counters with the largest counts, ties broken by position
+fn largest<K>(counters: &[Counter<K>], n: usize) -> Vec<bool> {
+    let mut positions: Vec<usize> = (0..counters.len()).collect();
+    positions.sort_by(|&a, &b| counters[b].count.cmp(&counters[a].count).then(a.cmp(&b)));
+    let mut kept = vec![false; counters.len()];
+    for i in positions.into_iter().take(n) {
+        kept[i] = true;
+    }
+    kept
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Checks that every counter bounds the occurrences of its item and that every item
+    /// occurring more than `total / capacity` times has a counter
+    fn check_bounds(summary: &SpaceSaving<u64>, occurrences: &HashMap<u64, u64>, total: u64) {
+        for counter in summary.counters() {
+            let actual = occurrences.get(&counter.item).copied().unwrap_or(0);
+            assert!(counter.count - counter.error <= actual, "{counter:?} {actual}");
+            assert!(actual <= counter.count, "{counter:?} {actual}");
+        }
+        for (item, count) in occurrences {
+            if *count > total / summary.capacity as u64 {
+                assert!(summary.index.contains_key(item), "{item} occurring {count} times");
+            }
+        }
+    }
+
+    fn stream(len: u64, seed: u64) -> Vec<u64> {
+        // a skewed stream: item i occurs about twice as often as item i + 1
+        (0..len)
+            .map(|i| {
+                let x = (i + seed).wrapping_mul(0x9E37_79B9_7F4A_7C15) >> 40;
+                u64::from(x.trailing_ones()) + (x % 3) * 100
+            })
+            .collect()
+    }
+
+    fn occurrences(items: &[u64]) -> HashMap<u64, u64> {
+        let mut occurrences = HashMap::new();
+        for item in items {
+            *occurrences.entry(*item).or_insert(0) += 1;
+        }
+        occurrences
+    }
+
+    #[test]
+    fn test_add() {
+        let items = stream(10_000, 0);
+        let mut summary = SpaceSaving::new(8);
+        for item in &items {
+            summary.add(*item, 1);
+        }
+        assert_eq!(summary.len(), 8);
+        check_bounds(&summary, &occurrences(&items), items.len() as u64);
+
+        let mut counters: Vec<&Counter<u64>> = summary.counters().collect();
+        counters.sort_by_key(|counter| std::cmp::Reverse(counter.count));
+        assert_eq!(counters[0].item, 0);
+        assert_eq!(counters[1].item, 100);
+    }
+
+    #[test]
+    fn test_merge() -> Result<()> {
+        let (left_items, right_items) = (stream(5_000, 0), stream(7_000, 5_000));
+        let mut left = SpaceSaving::new(8);
+        let mut right = SpaceSaving::new(8);
+        left_items.iter().for_each(|item| left.add(*item, 1));
+        right_items.iter().for_each(|item| right.add(*item, 1));
+
+        let right = SpaceSaving::from_counters(8, right.counters().cloned())?;
+        left.merge(right);
+        assert_eq!(left.len(), 8);
+        let items = [left_items, right_items].concat();
+        check_bounds(&left, &occurrences(&items), items.len() as u64);
+        Ok(())
+    }
+
+    #[test]
+    fn test_bytes_round_trip() -> Result<()> {
+        let items = stream(10_000, 0);
+        let mut summary = SpaceSaving::new(8);
+        items.iter().for_each(|item| summary.add(*item, 1));
+
+        let decoded = SpaceSaving::from_bytes(&summary.to_bytes())?;
+        assert!(decoded.counters().eq(summary.counters()));
+        assert_eq!(decoded.min_count(), summary.min_count());
+        assert!(SpaceSaving::from_bytes(&summary.to_bytes()[..5]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_keeps_first_counted_order() {
+        let mut summary = SpaceSaving::new(3);
+        for item in ["b", "c", "a", "c", "d"] {
+            summary.add(item, 1);
+        }
+        // "d" replaced "b", the first item with the smallest count
+        let counters: Vec<(&str, u64, u64)> = summary
+            .counters()
+            .map(|counter| (counter.item, counter.count, counter.error))
+            .collect();
+        assert_eq!(counters, vec![("c", 2, 0), ("a", 1, 0), ("d", 2, 1)]);
+        assert!(SpaceSaving::from_counters(1, summary.counters().cloned()).is_err());
+    }
+}
@@ -0,0 +1,325 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Merging t-digest for approximate quantiles.
+//!
+//! See Dunning, T. and Ertl, O. (2019). "Computing Extremely Accurate Quantiles Using
+//! t-Digests". <https://arxiv.org/abs/1902.04023>
+
+use std::f64::consts::PI;
+
+use datafusion::common::exec_err;
+use datafusion::error::Result;
+
+use crate::sketches::format::{self, SketchKind};
+
+/// Default compression, bounding a digest to roughly 100 centroids
+pub const DEFAULT_COMPRESSION: f64 = 100.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Centroid {
+    mean: f64,
+    weight: f64,
+}
+
+/// A t-digest: a mergeable summary of a distribution that estimates quantiles with a relative
+/// error that is smallest near the tails.
+///
+/// Values are buffered and merged into the centroids with the `k1` (arcsine) scale function
+/// once the buffer fills up, or before the digest is queried, merged or serialized.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TDigest {
+    compression: f64,
+    centroids: Vec<Centroid>,
+    buffer: Vec<Centroid>,
+    count: f64,
+    min: f64,
+    max: f64,
+}
+
+impl Default for TDigest {
+    fn default() -> Self {
+        Self::new(DEFAULT_COMPRESSION)
+    }
+}
+
+impl TDigest {
+    pub fn new(compression: f64) -> Self {
+        Self {
+            compression,
+            centroids: vec![],
+            buffer: vec![],
+            count: 0.0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+        }
+    }
+
+    /// Total weight of the values added
+    pub fn count(&self) -> f64 {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0.0
+    }
+
+    /// Adds `value` with weight 1, NaN values are ignored
+    pub fn add(&mut self, value: f64) {
+        self.add_weighted(value, 1.0);
+    }
+
+    /// Adds `value` with `weight`, NaN values and non-positive weights are ignored
+    pub fn add_weighted(&mut self, value: f64, weight: f64) {
+        if value.is_nan() || weight.is_nan() || weight <= 0.0 {
+            return;
+        }
+        self.buffer.push(Centroid { mean: value, weight });
+        self.count += weight;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        if self.buffer.len() >= self.buffer_capacity() {
+            self.compress();
+        }
+    }
+
+    /// Merges `other` into this digest
+    pub fn merge(&mut self, other: &TDigest) {
+        if other.is_empty() {
+            return;
+        }
+        self.buffer.extend_from_slice(&other.centroids);
+        self.buffer.extend_from_slice(&other.buffer);
+        self.count += other.count;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+        self.compress();
+    }
+
+    fn buffer_capacity(&self) -> usize {
+        (self.compression as usize).max(1) * 5
+    }
+
+    /// `k1` scale function, mapping a quantile to its index in `0..=compression / 2`
+    fn k(&self, q: f64) -> f64 {
+        self.compression / (2.0 * PI) * (2.0 * q - 1.0).asin()
+    }
+
+    fn q(&self, k: f64) -> f64 {
+        let k = k.min(self.compression / 4.0);
+        ((2.0 * PI * k / self.compression).sin() + 1.0) / 2.0
+    }
+
+    /// Merges the buffer into the centroids
+    fn compress(&mut self) {
+        if self.buffer.is_empty() {
+            return;
+        }
+        let mut all = std::mem::take(&mut self.centroids);
+        all.append(&mut self.buffer);
+        all.sort_unstable_by(|a, b| a.mean.total_cmp(&b.mean));
+
+        let total = self.count;
+        let mut centroids = Vec::with_capacity(all.len().min(self.compression as usize * 2));
+        let mut weight_before = 0.0;
+        let mut weight_limit = total * self.q(self.k(0.0) + 1.0);
+        let mut current = all[0];
+        for next in &all[1..] {
+            if weight_before + current.weight + next.weight <= weight_limit {
+                let weight = current.weight + next.weight;
+                current.mean += (next.mean - current.mean) * next.weight / weight;
+                current.weight = weight;
+            } else {
+                weight_before += current.weight;
+                weight_limit = total * self.q(self.k(weight_before / total) + 1.0);
+                centroids.push(current);
+                current = *next;
+            }
+        }
+        centroids.push(current);
+        self.centroids = centroids;
+    }
+
+    /// Estimates the `q`-th quantile (`0 <= q <= 1`), `None` if the digest is empty
+    pub fn quantile(&mut self, q: f64) -> Option<f64> {
+        self.compress();
+        if self.centroids.is_empty() {
+            return None;
+        }
+        if q <= 0.0 {
+            return Some(self.min);
+        }
+        if q >= 1.0 {
+            return Some(self.max);
+        }
+        if self.centroids.len() == 1 {
+            return Some(self.centroids[0].mean);
+        }
+
+        // Interpolate between the centers of the centroids, with the extremes anchored at min/max
+        let target = q * self.count;
+        let first = self.centroids[0];
+        if target < first.weight / 2.0 {
+            return Some(self.min + (first.mean - self.min) * target / (first.weight / 2.0));
+        }
+        let mut weight_before = 0.0;
+        for pair in self.centroids.windows(2) {
+            let (left, right) = (pair[0], pair[1]);
+            let left_center = weight_before + left.weight / 2.0;
+            let right_center = weight_before + left.weight + right.weight / 2.0;
+            if target <= right_center {
+                let fraction = (target - left_center) / (right_center - left_center);
+                return Some(left.mean + (right.mean - left.mean) * fraction);
+            }
+            weight_before += left.weight;
+        }
+        let last = self.centroids[self.centroids.len() - 1];
+        let last_center = self.count - last.weight / 2.0;
+        let fraction = ((target - last_center) / (last.weight / 2.0)).min(1.0);
+        Some(last.mean + (self.max - last.mean) * fraction)
+    }
+
+    /// Estimates the mean of the values between the `lower` and `upper` quantiles, splitting the
+    /// weight of the centroids that straddle a bound. `None` if the digest is empty or the range
+    /// holds no weight.
+    pub fn trimmed_mean(&mut self, lower: f64, upper: f64) -> Option<f64> {
+        self.compress();
+        let (lower, upper) = (lower * self.count, upper * self.count);
+        let (mut sum, mut weight, mut weight_before) = (0.0, 0.0, 0.0);
+        for centroid in &self.centroids {
+            let weight_after = weight_before + centroid.weight;
+            let overlap = weight_after.min(upper) - weight_before.max(lower);
+            if overlap > 0.0 {
+                sum += centroid.mean * overlap;
+                weight += overlap;
+            }
+            weight_before = weight_after;
+        }
+        (weight > 0.0).then(|| sum / weight)
+    }
+
+    /// Estimates the mean of the values after clamping those below the `lower` quantile and above
+    /// the `upper` quantile to the first and last values kept. `None` if the digest is empty or
+    /// the range holds no weight.
+    pub fn winsorized_mean(&mut self, lower: f64, upper: f64) -> Option<f64> {
+        let middle = self.trimmed_mean(lower, upper)?;
+        // The first and last values kept are at the centers of the ranks next to the bounds,
+        // which makes the estimate exact while every centroid holds a single value
+        let low = self.quantile(lower + 0.5 / self.count)?;
+        let high = self.quantile(upper - 0.5 / self.count)?;
+        Some(lower * low + (upper - lower) * middle + (1.0 - upper) * high)
+    }
+
+    /// Size in bytes of the heap allocations of the digest
+    pub fn size(&self) -> usize {
+        (self.centroids.capacity() + self.buffer.capacity()) * std::mem::size_of::<Centroid>()
+    }
+
+    /// Serializes the digest in the versioned format of [`crate::sketches::format`]
+    pub fn to_bytes(&mut self) -> Vec<u8> {
+        self.compress();
+        format::encode(SketchKind::TDigest, |writer| {
+            writer.put_f64(self.compression);
+            writer.put_f64(self.count);
+            writer.put_f64(self.min);
+            writer.put_f64(self.max);
+            writer.put_len(self.centroids.len());
+            for centroid in &self.centroids {
+                writer.put_f64(centroid.mean);
+                writer.put_f64(centroid.weight);
+            }
+        })
+    }
+
+    /// Deserializes a digest written by [`Self::to_bytes`]
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        format::decode(bytes, SketchKind::TDigest, |_version, reader| {
+            let mut digest = Self::new(reader.get_f64()?);
+            if digest.compression.is_nan() || digest.compression < 1.0 {
+                return exec_err!("Invalid t-digest compression {}", digest.compression);
+            }
+            digest.count = reader.get_f64()?;
+            digest.min = reader.get_f64()?;
+            digest.max = reader.get_f64()?;
+            let len = reader.get_count(16)?;
+            digest.centroids = (0..len)
+                .map(|_| {
+                    Ok(Centroid {
+                        mean: reader.get_f64()?,
+                        weight: reader.get_f64()?,
+                    })
+                })
+                .collect::<Result<_>>()?;
+            Ok(digest)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn digest_of(values: impl Iterator<Item = f64>) -> TDigest {
+        let mut digest = TDigest::default();
+        values.for_each(|v| digest.add(v));
+        digest
+    }
+
+    #[test]
+    fn test_tdigest_small_inputs_are_exact() {
+        let mut digest = digest_of([3.0, 1.0, 2.0].into_iter());
+        assert_eq!(digest.quantile(0.0), Some(1.0));
+        assert_eq!(digest.quantile(0.5), Some(2.0));
+        assert_eq!(digest.quantile(1.0), Some(3.0));
+        assert_eq!(TDigest::default().quantile(0.5), None);
+    }
+
+    #[test]
+    fn test_tdigest_uniform_quantiles() {
+        let mut digest = digest_of((0..100_000).map(|i| ((i * 7919) % 100_000) as f64));
+        for q in [0.01, 0.25, 0.5, 0.95, 0.99] {
+            let actual = digest.quantile(q).unwrap();
+            let expected = q * 100_000.0;
+            assert!(
+                (actual - expected).abs() < 100.0,
+                "q={q} expected {expected}, got {actual}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_tdigest_merge_and_round_trip() -> Result<()> {
+        let mut merged = TDigest::default();
+        for chunk in 0..10 {
+            let mut partial = digest_of((0..1000).map(|i| (chunk * 1000 + i) as f64));
+            merged.merge(&TDigest::from_bytes(&partial.to_bytes())?);
+        }
+        assert_eq!(merged.count(), 10_000.0);
+        let median = merged.quantile(0.5).unwrap();
+        assert!((median - 5000.0).abs() < 50.0, "got {median}");
+        let trimmed = merged.trimmed_mean(0.1, 0.5).unwrap();
+        assert!((trimmed - 3000.0).abs() < 50.0, "got {trimmed}");
+        // 0.1 * 1000 + 0.4 * 3000 + 0.5 * 5000
+        let winsorized = merged.winsorized_mean(0.1, 0.5).unwrap();
+        assert!((winsorized - 3800.0).abs() < 50.0, "got {winsorized}");
+
+        let bytes = merged.to_bytes();
+        assert_eq!(TDigest::from_bytes(&bytes)?, merged);
+        assert!(TDigest::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,273 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Theta sketch for approximate distinct counts supporting set operations.
+//!
+//! See Dasgupta, A. et al. (2016). "A Framework for Estimating Stream Expression Cardinalities".
+//! A sketch keeps the hashes below a threshold `theta`, at most `nominal_entries` of them, so
+//! the number of distinct hashes is estimated as their count divided by `theta`, as a fraction
+//! of the hash space. Unlike HyperLogLog, the sketches of two sets can be intersected.
+
+use datafusion::common::exec_err;
+use datafusion::error::Result;
+
+use crate::sketches::format::{self, SketchKind};
+
+/// Default number of hashes kept, giving a relative standard error of about
+/// `1 / sqrt(4096)`, 1.6%
+pub const DEFAULT_NOMINAL_ENTRIES: usize = 4096;
+
+/// Smallest and largest number of hashes kept
+pub const NOMINAL_ENTRIES_RANGE: std::ops::RangeInclusive<usize> = 16..=(1 << 26);
+
+/// A theta sketch over 64-bit hashes.
+///
+/// Sketches only estimate set operations of their inputs if the values were hashed the same
+/// way.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThetaSketch {
+    nominal_entries: usize,
+    /// Hashes are kept if below `theta`, `u64::MAX` standing for the whole hash space
+    theta: u64,
+    /// Hashes below `theta`, sorted and deduplicated up to `compacted`
+    hashes: Vec<u64>,
+    compacted: usize,
+}
+
+impl Default for ThetaSketch {
+    fn default() -> Self {
+        Self::new(DEFAULT_NOMINAL_ENTRIES)
+    }
+}
+
+impl ThetaSketch {
+    /// Creates an empty sketch, `nominal_entries` must be in [`NOMINAL_ENTRIES_RANGE`]
+    pub fn new(nominal_entries: usize) -> Self {
+        assert!(
+            NOMINAL_ENTRIES_RANGE.contains(&nominal_entries),
+            "Invalid theta sketch nominal entries {nominal_entries}"
+        );
+        Self {
+            nominal_entries,
+            theta: u64::MAX,
+            hashes: vec![],
+            compacted: 0,
+        }
+    }
+
+    pub fn nominal_entries(&self) -> usize {
+        self.nominal_entries
+    }
+
+    /// Adds the value hashed to `hash`
+    pub fn add_hash(&mut self, hash: u64) {
+        if hash < self.theta {
+            self.hashes.push(hash);
+            // compacting once the buffer doubles amortizes the sort
+            if self.hashes.len() >= 2 * self.nominal_entries.max(self.compacted) {
+                self.compact();
+            }
+        }
+    }
+
+    /// Sorts and deduplicates the hashes, keeping the `nominal_entries` smallest and lowering
+    /// `theta` to the smallest hash dropped
+    fn compact(&mut self) {
+        if self.compacted == self.hashes.len() {
+            return;
+        }
+        self.hashes.sort_unstable();
+        self.hashes.dedup();
+        if self.hashes.len() > self.nominal_entries {
+            self.theta = self.hashes[self.nominal_entries];
+            self.hashes.truncate(self.nominal_entries);
+        }
+        self.compacted = self.hashes.len();
+    }
+
+    /// The sorted hashes of a compacted sketch
+    fn sorted_hashes(&self) -> &[u64] {
+        debug_assert_eq!(self.compacted, self.hashes.len());
+        &self.hashes
+    }
+
+    fn from_parts(nominal_entries: usize, theta: u64, hashes: Vec<u64>) -> Self {
+        let mut sketch = Self {
+            nominal_entries,
+            theta,
+            compacted: 0,
+            hashes,
+        };
+        sketch.compact();
+        sketch
+    }
+
+    /// Merges `other` into this sketch, which then estimates the union of their inputs
+    pub fn merge(&mut self, other: &ThetaSketch) {
+        self.theta = self.theta.min(other.theta);
+        let theta = self.theta;
+        self.hashes.retain(|hash| *hash < theta);
+        self.hashes.extend(other.hashes.iter().filter(|hash| **hash < theta));
+        self.nominal_entries = self.nominal_entries.min(other.nominal_entries);
+        self.compacted = 0;
+        self.compact();
+    }
+
+    /// A sketch estimating the union of the inputs of the two sketches
+    pub fn union(&self, other: &ThetaSketch) -> ThetaSketch {
+        let mut union = self.clone();
+        union.merge(other);
+        union
+    }
+
+    /// A sketch estimating the intersection of the inputs of the two sketches
+    pub fn intersect(&self, other: &ThetaSketch) -> ThetaSketch {
+        let (mut left, mut right) = (self.clone(), other.clone());
+        left.compact();
+        right.compact();
+        let theta = left.theta.min(right.theta);
+        let (left, right) = (left.sorted_hashes(), right.sorted_hashes());
+        let mut hashes = vec![];
+        let (mut i, mut j) = (0, 0);
+        while i < left.len() && j < right.len() && left[i] < theta && right[j] < theta {
+            match left[i].cmp(&right[j]) {
+                std::cmp::Ordering::Less => i += 1,
+                std::cmp::Ordering::Greater => j += 1,
+                std::cmp::Ordering::Equal => {
+                    hashes.push(left[i]);
+                    i += 1;
+                    j += 1;
+                }
+            }
+        }
+        let nominal_entries = self.nominal_entries.min(other.nominal_entries);
+        Self::from_parts(nominal_entries, theta, hashes)
+    }
+
+    /// Estimates the number of distinct hashes added
+    pub fn estimate(&self) -> f64 {
+        if self.compacted != self.hashes.len() {
+            let mut compacted = self.clone();
+            compacted.compact();
+            return compacted.estimate();
+        }
+        let retained = self.hashes.len() as f64;
+        if self.theta == u64::MAX {
+            return retained;
+        }
+        retained / (self.theta as f64 / u64::MAX as f64)
+    }
+
+    pub fn size(&self) -> usize {
+        std::mem::size_of_val(self) + self.hashes.capacity() * std::mem::size_of::<u64>()
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut sketch = self.clone();
+        sketch.compact();
+        format::encode(SketchKind::Theta, |writer| {
+            writer.put_len(sketch.nominal_entries);
+            writer.put_u64(sketch.theta);
+            writer.put_len(sketch.hashes.len());
+            sketch.hashes.iter().for_each(|hash| writer.put_u64(*hash));
+        })
+    }
+
+    /// Deserializes a sketch written by [`Self::to_bytes`]
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        format::decode(bytes, SketchKind::Theta, |_version, reader| {
+            let nominal_entries = reader.get_len()?;
+            if !NOMINAL_ENTRIES_RANGE.contains(&nominal_entries) {
+                return exec_err!("Invalid theta sketch nominal entries {nominal_entries}");
+            }
+            let theta = reader.get_u64()?;
+            let len = reader.get_count(std::mem::size_of::<u64>())?;
+            if len > nominal_entries {
+                return exec_err!("Invalid theta sketch state: {len} hashes for {nominal_entries} nominal entries");
+            }
+            let hashes = (0..len).map(|_| reader.get_u64()).collect::<Result<Vec<_>>>()?;
+            let valid = hashes.windows(2).all(|pair| pair[0] < pair[1]) && hashes.iter().all(|hash| *hash < theta);
+            if !valid {
+                return exec_err!("Invalid theta sketch state: unsorted hashes or hashes above theta");
+            }
+            Ok(Self {
+                nominal_entries,
+                theta,
+                compacted: hashes.len(),
+                hashes,
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::hash::{BuildHasher, Hasher};
+
+    fn hash(value: u64) -> u64 {
+        let mut hasher = ahash::RandomState::with_seeds(1, 2, 3, 4).build_hasher();
+        hasher.write_u64(value);
+        hasher.finish()
+    }
+
+    fn sketch(values: std::ops::Range<u64>) -> ThetaSketch {
+        let mut sketch = ThetaSketch::new(1024);
+        values.for_each(|v| sketch.add_hash(hash(v)));
+        sketch
+    }
+
+    fn assert_close(estimate: f64, expected: f64, tolerance: f64) {
+        assert!(
+            (estimate - expected).abs() <= expected * tolerance,
+            "estimated {estimate}, expected {expected}"
+        );
+    }
+
+    #[test]
+    fn test_theta_sketch_estimates() -> Result<()> {
+        let small = sketch(0..100);
+        assert_eq!(small.estimate(), 100.0);
+        assert_eq!(ThetaSketch::default().estimate(), 0.0);
+
+        let (a, b) = (sketch(0..60_000), sketch(40_000..100_000));
+        // 1 / sqrt(1024) is a standard error of about 3%
+        assert_close(a.estimate(), 60_000.0, 0.1);
+        assert_close(a.union(&b).estimate(), 100_000.0, 0.1);
+        assert_close(a.intersect(&b).estimate(), 20_000.0, 0.25);
+        assert_eq!(a.intersect(&sketch(200_000..200_100)).estimate(), 0.0);
+
+        let mut merged = ThetaSketch::from_bytes(&a.to_bytes())?;
+        merged.merge(&b);
+        assert_eq!(merged.estimate(), a.union(&b).estimate());
+        Ok(())
+    }
+
+    #[test]
+    fn test_theta_sketch_rejects_invalid_states() {
+        let bytes = sketch(0..10).to_bytes();
+        assert!(ThetaSketch::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        let unsorted = format::encode(SketchKind::Theta, |writer| {
+            writer.put_len(16);
+            writer.put_u64(u64::MAX);
+            writer.put_len(2);
+            writer.put_u64(2);
+            writer.put_u64(1);
+        });
+        assert!(ThetaSketch::from_bytes(&unsorted).is_err());
+    }
+}
@@ -0,0 +1,162 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::any::Any;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use arrow::array::{ArrayRef, Float64Array, StructArray, UInt64Array};
+use arrow::datatypes::Fields;
+use datafusion::arrow;
+use datafusion::arrow::datatypes::{DataType, Field};
+use datafusion::common::{Result, ScalarValue};
+use datafusion::logical_expr::function::{AccumulatorArgs, StateFieldsArgs};
+use datafusion::logical_expr::{Accumulator, AggregateUDFImpl, Signature, Volatility};
+
+use crate::common::key_sketches::KeySketches;
+
+make_udaf_expr_and_func!(
+    SkewDetectFunction,
+    skew_detect,
+    "Reports the share of rows held by the most frequent keys and the estimated distinct count of keys.",
+    skew_detect_udaf
+);
+
+fn result_fields() -> Fields {
+    Fields::from(vec![
+        Field::new("rows", DataType::UInt64, false),
+        Field::new("distinct_keys", DataType::UInt64, false),
+        Field::new("top1_share", DataType::Float64, true),
+        Field::new("top10_share", DataType::Float64, true),
+    ])
+}
+
+/// The `SkewDetectFunction` reports how concentrated the rows of each group are on a few keys,
+/// e.g. `SELECT skew_detect(customer_id) FROM orders` before joining `orders` on `customer_id`.
+///
+/// Returns a `Struct<rows, distinct_keys, top1_share, top10_share>`:
+/// - `rows`: the number of rows, ignoring rows with a NULL key column.
+/// - `distinct_keys`: the number of distinct keys, estimated with a HyperLogLog sketch.
+/// - `top1_share` and `top10_share`: the fraction of the rows holding the most frequent key and
+///   one of the 10 most frequent keys, NULL if there are no rows.
+///
+/// The shares are estimated with a Misra-Gries summary and may be underestimated by up to 1%.
+/// Compared to a uniform distribution, where `top1_share` is about `1 / distinct_keys`, a large
+/// `top1_share` means the rows of that key will all land in one join partition.
+pub struct SkewDetectFunction {
+    signature: Signature,
+}
+
+impl Debug for SkewDetectFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SkewDetectFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for SkewDetectFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SkewDetectFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::variadic_any(Volatility::Immutable),
+        }
+    }
+}
+
+impl AggregateUDFImpl for SkewDetectFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "skew_detect"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Struct(result_fields()))
+    }
+
+    fn state_fields(&self, _args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(KeySketches::state_fields())
+    }
+
+    fn accumulator(&self, _acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        Ok(Box::new(SkewDetectAccumulator::new()))
+    }
+}
+
+/// Accumulator for [`SkewDetectFunction`].
+#[derive(Debug)]
+pub struct SkewDetectAccumulator {
+    sketches: KeySketches,
+}
+
+impl Default for SkewDetectAccumulator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SkewDetectAccumulator {
+    pub fn new() -> Self {
+        Self {
+            sketches: KeySketches::new(10),
+        }
+    }
+}
+
+impl Accumulator for SkewDetectAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        self.sketches.update(values)
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        self.sketches.merge(states)
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        Ok(self.sketches.state())
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(UInt64Array::from(vec![self.sketches.rows()])),
+            Arc::new(UInt64Array::from(vec![self.sketches.distinct_keys()])),
+            Arc::new(Float64Array::from(vec![self.sketches.top_share(1)])),
+            Arc::new(Float64Array::from(vec![self.sketches.top_share(10)])),
+        ];
+        Ok(ScalarValue::Struct(Arc::new(StructArray::try_new(
+            result_fields(),
+            columns,
+            None,
+        )?)))
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self) + self.sketches.size()
+    }
+}
@@ -0,0 +1,312 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::any::Any;
+use std::fmt::Debug;
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use arrow::array::{Array, ArrayRef, BooleanArray, GenericStringArray, OffsetSizeTrait};
+use datafusion::arrow;
+use datafusion::arrow::datatypes::{DataType, Field};
+use datafusion::common::cast::as_generic_string_array;
+use datafusion::common::{plan_err, Result, ScalarValue};
+use datafusion::logical_expr::function::{AccumulatorArgs, StateFieldsArgs};
+use datafusion::logical_expr::{Accumulator, AggregateUDFImpl, Signature, Volatility};
+
+use crate::common::args::literal_arg;
+use crate::common::output_width::OutputWidth;
+use crate::compat::{EmitTo, GroupsAccumulator};
+
+make_udaf_expr_and_func!(
+    StringAggExtFunction,
+    string_agg_ext,
+    x delimiter,
+    "Concatenates the values, separated by the delimiter.",
+    string_agg_ext_udaf
+);
+
+/// The `StringAggExtFunction` concatenates the non-null values of a group, separated by a
+/// constant delimiter, like `string_agg`.
+///
+/// - Returns `Utf8`, or `LargeUtf8` if configured with [`OutputWidth::Large`]; `string_agg`
+///   always returns `LargeUtf8`.
+/// - Groups are concatenated in the order their rows are aggregated, which isn't
+///   deterministic across partitions.
+/// - The result is NULL if there are no values.
+pub struct StringAggExtFunction {
+    signature: Signature,
+    output_width: OutputWidth,
+}
+
+impl Debug for StringAggExtFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StringAggExtFunction")
+            .field("signature", &self.signature)
+            .field("output_width", &self.output_width)
+            .finish()
+    }
+}
+
+impl Default for StringAggExtFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StringAggExtFunction {
+    pub fn new() -> Self {
+        Self::new_with_output_width(OutputWidth::default())
+    }
+
+    pub fn new_with_output_width(output_width: OutputWidth) -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+            output_width,
+        }
+    }
+}
+
+impl AggregateUDFImpl for StringAggExtFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "string_agg_ext"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        let [value_type, delimiter_type] = arg_types else {
+            return plan_err!("string_agg_ext expects a value and a delimiter");
+        };
+        for arg_type in [value_type, delimiter_type] {
+            let arg_type = match arg_type {
+                DataType::Dictionary(_, value_type) => value_type,
+                arg_type => arg_type,
+            };
+            if !matches!(
+                arg_type,
+                DataType::Utf8 | DataType::LargeUtf8 | DataType::Utf8View | DataType::Null
+            ) {
+                return plan_err!("string_agg_ext expects string arguments, got {arg_type}");
+            }
+        }
+        // the values are read as strings of the output width, so they're copied as they are
+        Ok(vec![self.output_width.string_type(), DataType::Utf8])
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(self.output_width.string_type())
+    }
+
+    fn state_fields(&self, _args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(vec![Field::new("concatenated", self.output_width.string_type(), true)])
+    }
+
+    fn accumulator(&self, acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        let delimiter = delimiter_arg(&acc_args)?;
+        Ok(match self.output_width {
+            OutputWidth::Regular => Box::new(StringAggExtAccumulator::<i32>::new(delimiter)),
+            OutputWidth::Large => Box::new(StringAggExtAccumulator::<i64>::new(delimiter)),
+        })
+    }
+
+    fn groups_accumulator_supported(&self, _args: AccumulatorArgs) -> bool {
+        true
+    }
+
+    fn create_groups_accumulator(&self, args: AccumulatorArgs) -> Result<Box<dyn GroupsAccumulator>> {
+        let delimiter = delimiter_arg(&args)?;
+        Ok(match self.output_width {
+            OutputWidth::Regular => Box::new(StringAggExtGroupsAccumulator::<i32>::new(delimiter)),
+            OutputWidth::Large => Box::new(StringAggExtGroupsAccumulator::<i64>::new(delimiter)),
+        })
+    }
+}
+
+fn delimiter_arg(acc_args: &AccumulatorArgs) -> Result<String> {
+    match literal_arg(&acc_args.exprs[1], "string_agg_ext", "delimiter")? {
+        ScalarValue::Utf8(delimiter) => Ok(delimiter.unwrap_or_default()),
+        delimiter => plan_err!("string_agg_ext expects a string delimiter, got {delimiter}"),
+    }
+}
+
+/// Appends `value` to `concatenated`, separated by `delimiter` if it already holds a value
+fn append(concatenated: &mut Option<String>, value: &str, delimiter: &str) {
+    match concatenated {
+        Some(concatenated) => {
+            concatenated.push_str(delimiter);
+            concatenated.push_str(value);
+        }
+        None => *concatenated = Some(value.to_string()),
+    }
+}
+
+/// Builds the output array of `OutputWidth` `O` from the concatenated strings, failing if
+/// they don't fit its offsets.
+fn build_array<O: OffsetSizeTrait>(concatenated: Vec<Option<String>>) -> Result<ArrayRef> {
+    let width = OutputWidth::of::<O>();
+    let len = concatenated.iter().flatten().map(String::len).sum();
+    width.check_len(len, "string_agg_ext")?;
+    Ok(Arc::new(GenericStringArray::<O>::from(concatenated)))
+}
+
+/// Accumulator concatenating the values of a group into a string with offsets `O`.
+#[derive(Debug)]
+pub struct StringAggExtAccumulator<O: OffsetSizeTrait> {
+    concatenated: Option<String>,
+    delimiter: String,
+    phantom: PhantomData<O>,
+}
+
+impl<O: OffsetSizeTrait> StringAggExtAccumulator<O> {
+    pub fn new(delimiter: String) -> Self {
+        Self {
+            concatenated: None,
+            delimiter,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<O: OffsetSizeTrait> Accumulator for StringAggExtAccumulator<O> {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        for value in as_generic_string_array::<O>(&values[0])?.iter().flatten() {
+            append(&mut self.concatenated, value, &self.delimiter);
+        }
+        Ok(())
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        self.update_batch(states)
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        Ok(vec![self.evaluate()?])
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        let array = build_array::<O>(vec![self.concatenated.clone()])?;
+        ScalarValue::try_from_array(&array, 0)
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self) + self.concatenated.as_ref().map_or(0, String::capacity) + self.delimiter.capacity()
+    }
+}
+
+/// [`GroupsAccumulator`] for [`StringAggExtAccumulator`], which checks the total length of
+/// the emitted groups against the offsets `O` before building the output.
+#[derive(Debug)]
+pub struct StringAggExtGroupsAccumulator<O: OffsetSizeTrait> {
+    concatenated: Vec<Option<String>>,
+    delimiter: String,
+    phantom: PhantomData<O>,
+}
+
+impl<O: OffsetSizeTrait> StringAggExtGroupsAccumulator<O> {
+    pub fn new(delimiter: String) -> Self {
+        Self {
+            concatenated: vec![],
+            delimiter,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<O: OffsetSizeTrait> GroupsAccumulator for StringAggExtGroupsAccumulator<O> {
+    fn update_batch(
+        &mut self,
+        values: &[ArrayRef],
+        group_indices: &[usize],
+        opt_filter: Option<&BooleanArray>,
+        total_num_groups: usize,
+    ) -> Result<()> {
+        self.concatenated.resize(total_num_groups, None);
+        let values = as_generic_string_array::<O>(&values[0])?;
+        for (i, &group_index) in group_indices.iter().enumerate() {
+            let selected = opt_filter.map_or(true, |filter| filter.is_valid(i) && filter.value(i));
+            if selected && values.is_valid(i) {
+                append(&mut self.concatenated[group_index], values.value(i), &self.delimiter);
+            }
+        }
+        Ok(())
+    }
+
+    fn merge_batch(
+        &mut self,
+        values: &[ArrayRef],
+        group_indices: &[usize],
+        opt_filter: Option<&BooleanArray>,
+        total_num_groups: usize,
+    ) -> Result<()> {
+        self.update_batch(values, group_indices, opt_filter, total_num_groups)
+    }
+
+    fn evaluate(&mut self, emit_to: EmitTo) -> Result<ArrayRef> {
+        build_array::<O>(emit_to.take_needed(&mut self.concatenated))
+    }
+
+    fn state(&mut self, emit_to: EmitTo) -> Result<Vec<ArrayRef>> {
+        Ok(vec![self.evaluate(emit_to)?])
+    }
+
+    fn size(&self) -> usize {
+        self.concatenated.capacity() * std::mem::size_of::<Option<String>>()
+            + self.concatenated.iter().flatten().map(String::capacity).sum::<usize>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::array::{LargeStringArray, StringArray};
+
+    use super::*;
+
+    #[test]
+    fn test_string_agg_ext_groups_accumulator() -> Result<()> {
+        let values: ArrayRef = Arc::new(LargeStringArray::from(vec![Some("a"), None, Some("b"), Some("c")]));
+        let filter = BooleanArray::from(vec![true, true, true, false]);
+
+        let mut acc = StringAggExtGroupsAccumulator::<i64>::new(", ".to_string());
+        acc.update_batch(&[values], &[0, 0, 0, 1], Some(&filter), 2)?;
+
+        // merging two partial states appends the second to the first
+        let state = acc.state(EmitTo::All)?;
+        let mut merged = StringAggExtGroupsAccumulator::<i64>::new(", ".to_string());
+        merged.merge_batch(&state, &[0, 1], None, 2)?;
+        merged.merge_batch(&state, &[0, 1], None, 2)?;
+
+        let expected: ArrayRef = Arc::new(LargeStringArray::from(vec![Some("a, b, a, b"), None]));
+        assert_eq!(&merged.evaluate(EmitTo::All)?, &expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_string_agg_ext_accumulator() -> Result<()> {
+        let mut acc = StringAggExtAccumulator::<i32>::new("-".to_string());
+        assert_eq!(acc.evaluate()?, ScalarValue::Utf8(None));
+        acc.update_batch(&[Arc::new(StringArray::from(vec!["x", "y"]))])?;
+        assert_eq!(acc.evaluate()?, ScalarValue::Utf8(Some("x-y".to_string())));
+        Ok(())
+    }
+}
@@ -0,0 +1,398 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::any::Any;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use arrow::array::{Array, ArrayRef, StructArray};
+use arrow::compute::{concat, lexsort_to_indices, take, SortColumn, SortOptions};
+use arrow::datatypes::{DataType, Field, Fields};
+use datafusion::arrow;
+use datafusion::common::cast::{as_list_array, as_struct_array};
+use datafusion::common::{Result, ScalarValue};
+use datafusion::logical_expr::function::{AccumulatorArgs, StateFieldsArgs};
+use datafusion::logical_expr::utils::AggregateOrderSensitivity;
+use datafusion::logical_expr::{Accumulator, AggregateUDFImpl, Signature, Volatility};
+
+use crate::common::output_width::OutputWidth;
+
+make_udaf_expr_and_func!(
+    StructAggFunction,
+    struct_agg,
+    "Collects the rows of the arguments into a list of structs.",
+    struct_agg_udaf
+);
+
+/// The `StructAggFunction` packs the rows of its arguments into structs and collects them into
+/// a list, like DuckDB's `list(struct_pack(...))`.
+///
+/// - The fields of the structs are named `c0`, `c1`, ... as those of `struct(...)`.
+/// - `struct_agg(x, y ORDER BY z)` returns the structs in the order of `z`, otherwise in the
+///   order the rows are aggregated in, which depends on the partitioning of the input.
+/// - Rows are copied one batch at a time when the list is emitted.
+/// - Returns a `List`, or a `LargeList` if configured with [`OutputWidth::Large`].
+/// - The result is NULL if there are no rows.
+pub struct StructAggFunction {
+    signature: Signature,
+    output_width: OutputWidth,
+}
+
+impl Debug for StructAggFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StructAggFunction")
+            .field("signature", &self.signature)
+            .field("output_width", &self.output_width)
+            .finish()
+    }
+}
+
+impl Default for StructAggFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StructAggFunction {
+    pub fn new() -> Self {
+        Self::new_with_output_width(OutputWidth::default())
+    }
+
+    pub fn new_with_output_width(output_width: OutputWidth) -> Self {
+        Self {
+            signature: Signature::variadic_any(Volatility::Immutable),
+            output_width,
+        }
+    }
+}
+
+impl AggregateUDFImpl for StructAggFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "struct_agg"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, arg_types: &[DataType]) -> Result<DataType> {
+        let item = Field::new("item", DataType::Struct(struct_fields("c", arg_types)), true);
+        Ok(self.output_width.list_type(Arc::new(item)))
+    }
+
+    fn state_fields(&self, args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(ordered_state_fields(args.input_types, &args))
+    }
+
+    fn accumulator(&self, acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        let arg_types = (0..acc_args.exprs.len())
+            .map(|i| crate::compat::arg_type(&acc_args, i))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Box::new(
+            StructAggAccumulator::try_new_ordered(struct_fields("c", &arg_types), &acc_args)?
+                .with_output_width(self.output_width),
+        ))
+    }
+
+    /// The accumulator sorts the rows itself, so several `struct_agg` with different orderings
+    /// can run in the same aggregate and a sorted input only spares it work
+    fn order_sensitivity(&self) -> AggregateOrderSensitivity {
+        AggregateOrderSensitivity::Beneficial
+    }
+
+    fn with_beneficial_ordering(
+        self: Arc<Self>,
+        _beneficial_ordering: bool,
+    ) -> Result<Option<Arc<dyn AggregateUDFImpl>>> {
+        Ok(Some(self))
+    }
+}
+
+/// Nullable fields `{prefix}0`, `{prefix}1`, ... of `types`
+pub(crate) fn struct_fields(prefix: &str, types: &[DataType]) -> Fields {
+    types
+        .iter()
+        .enumerate()
+        .map(|(i, data_type)| Field::new(format!("{prefix}{i}"), data_type.clone(), true))
+        .collect()
+}
+
+/// The state of [`StructAggAccumulator`] for rows of `types`: the rows, and their ordering values
+/// with an `ORDER BY`
+pub(crate) fn ordered_state_fields(types: &[DataType], args: &StateFieldsArgs) -> Vec<Field> {
+    let mut fields = vec![Field::new("rows", list_type(struct_fields("c", types)), true)];
+    if !args.ordering_fields.is_empty() {
+        let ordering_types: Vec<DataType> = args
+            .ordering_fields
+            .iter()
+            .map(|field| field.data_type().clone())
+            .collect();
+        fields.push(Field::new(
+            "orderings",
+            list_type(struct_fields("o", &ordering_types)),
+            true,
+        ));
+    }
+    fields
+}
+
+fn list_type(fields: Fields) -> DataType {
+    DataType::new_list(DataType::Struct(fields), true)
+}
+
+/// Accumulator packing the rows of a group into structs, which are kept one array per batch
+/// along with the values they are ordered by.
+///
+/// The state is always made of `List`s, only the result has the configured [`OutputWidth`].
+#[derive(Debug)]
+pub struct StructAggAccumulator {
+    fields: Fields,
+    ordering_fields: Fields,
+    options: Vec<SortOptions>,
+    rows: Vec<ArrayRef>,
+    orderings: Vec<ArrayRef>,
+    output_width: OutputWidth,
+}
+
+impl StructAggAccumulator {
+    /// Creates an accumulator of structs of `fields` ordered by the values of `ordering_fields`
+    /// with `options`, in the order they are aggregated if there are none
+    pub fn new(fields: Fields, ordering_fields: Fields, options: Vec<SortOptions>) -> Self {
+        Self {
+            fields,
+            ordering_fields,
+            options,
+            rows: vec![],
+            orderings: vec![],
+            output_width: OutputWidth::default(),
+        }
+    }
+
+    /// Evaluates to a list of `output_width`
+    pub fn with_output_width(mut self, output_width: OutputWidth) -> Self {
+        self.output_width = output_width;
+        self
+    }
+
+    /// Creates an accumulator of structs of `fields` ordered by the `ORDER BY` of `acc_args`
+    pub fn try_new_ordered(fields: Fields, acc_args: &AccumulatorArgs) -> Result<Self> {
+        let ordering_types = acc_args
+            .ordering_req
+            .iter()
+            .map(|sort_expr| sort_expr.expr.data_type(acc_args.schema))
+            .collect::<Result<Vec<_>>>()?;
+        let options = acc_args
+            .ordering_req
+            .iter()
+            .map(|sort_expr| sort_expr.options)
+            .collect();
+        Ok(Self::new(fields, struct_fields("o", &ordering_types), options))
+    }
+
+    fn push(&mut self, rows: ArrayRef, orderings: Option<ArrayRef>) {
+        if rows.is_empty() {
+            return;
+        }
+        self.rows.push(rows);
+        self.orderings.extend(orderings);
+    }
+
+    /// All the structs and their ordering values, sorted
+    fn sorted(&self) -> Result<Option<(ArrayRef, Option<ArrayRef>)>> {
+        let (rows, orderings) = match (concat_all(&self.rows)?, concat_all(&self.orderings)?) {
+            (None, _) => return Ok(None),
+            (Some(rows), orderings) => (rows, orderings),
+        };
+        let Some(orderings) = orderings.filter(|_| !self.options.is_empty()) else {
+            return Ok(Some((rows, None)));
+        };
+        let columns: Vec<SortColumn> = as_struct_array(&orderings)?
+            .columns()
+            .iter()
+            .zip(&self.options)
+            .map(|(values, options)| SortColumn {
+                values: Arc::clone(values),
+                options: Some(*options),
+            })
+            .collect();
+        let indices = lexsort_to_indices(&columns, None)?;
+        Ok(Some((
+            take(&rows, &indices, None)?,
+            Some(take(&orderings, &indices, None)?),
+        )))
+    }
+
+    fn list(fields: &Fields, values: Option<ArrayRef>) -> Result<ScalarValue> {
+        match values {
+            Some(values) => OutputWidth::Regular.list_scalar(values, "struct_agg"),
+            None => OutputWidth::Regular.null_list(&DataType::Struct(fields.clone())),
+        }
+    }
+}
+
+/// Concatenates `arrays`, `None` if there are none
+fn concat_all(arrays: &[ArrayRef]) -> Result<Option<ArrayRef>> {
+    Ok(match arrays {
+        [] => None,
+        [array] => Some(Arc::clone(array)),
+        arrays => {
+            let arrays: Vec<&dyn Array> = arrays.iter().map(|array| array.as_ref()).collect();
+            Some(concat(&arrays)?)
+        }
+    })
+}
+
+impl Accumulator for StructAggAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        let (columns, ordering_columns) = values.split_at(self.fields.len());
+        let rows = StructArray::try_new(self.fields.clone(), columns.to_vec(), None)?;
+        let orderings = (!self.options.is_empty())
+            .then(|| StructArray::try_new(self.ordering_fields.clone(), ordering_columns.to_vec(), None))
+            .transpose()?;
+        self.push(
+            Arc::new(rows),
+            orderings.map(|orderings| Arc::new(orderings) as ArrayRef),
+        );
+        Ok(())
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        let rows_lists = as_list_array(&states[0])?;
+        let orderings_lists = states.get(1).map(|state| as_list_array(state)).transpose()?;
+        for i in 0..rows_lists.len() {
+            if rows_lists.is_null(i) {
+                continue;
+            }
+            let orderings = orderings_lists.map(|lists| lists.value(i));
+            self.push(rows_lists.value(i), orderings);
+        }
+        Ok(())
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        let (rows, orderings) = match self.sorted()? {
+            Some((rows, orderings)) => (Some(rows), orderings),
+            None => (None, None),
+        };
+        let mut state = vec![Self::list(&self.fields, rows)?];
+        if !self.options.is_empty() {
+            state.push(Self::list(&self.ordering_fields, orderings)?);
+        }
+        Ok(state)
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        match self.sorted()? {
+            Some((rows, _)) => self.output_width.list_scalar(rows, "struct_agg"),
+            None => self.output_width.null_list(&DataType::Struct(self.fields.clone())),
+        }
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self)
+            + (self.rows.capacity() + self.orderings.capacity()) * std::mem::size_of::<ArrayRef>()
+            + self
+                .rows
+                .iter()
+                .chain(&self.orderings)
+                .map(|array| array.get_array_memory_size())
+                .sum::<usize>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::array::{Int64Array, NullArray, StringArray};
+
+    use super::*;
+
+    fn batch(xs: Vec<i64>, ys: Vec<Option<&str>>, keys: Vec<i64>) -> Vec<ArrayRef> {
+        vec![
+            Arc::new(Int64Array::from(xs)),
+            Arc::new(StringArray::from(ys)),
+            Arc::new(Int64Array::from(keys)),
+        ]
+    }
+
+    fn new_accumulator() -> StructAggAccumulator {
+        StructAggAccumulator::new(
+            struct_fields("c", &[DataType::Int64, DataType::Utf8]),
+            struct_fields("o", &[DataType::Int64]),
+            vec![SortOptions::default().desc()],
+        )
+    }
+
+    /// Aggregates `len` rows of a NULL column, which takes no memory however long it is
+    fn evaluate_nulls(len: usize, output_width: OutputWidth) -> Result<ScalarValue> {
+        let mut accumulator = StructAggAccumulator::new(struct_fields("c", &[DataType::Null]), Fields::empty(), vec![])
+            .with_output_width(output_width);
+        accumulator.update_batch(&[Arc::new(NullArray::new(len))])?;
+        accumulator.evaluate()
+    }
+
+    #[test]
+    fn test_struct_agg_output_width_at_offset_limit() -> Result<()> {
+        let limit = i32::MAX as usize;
+
+        let ScalarValue::List(list) = evaluate_nulls(limit, OutputWidth::Regular)? else {
+            unreachable!()
+        };
+        assert_eq!(list.value_length(0) as usize, limit);
+
+        let err = evaluate_nulls(limit + 1, OutputWidth::Regular).unwrap_err();
+        assert!(err.to_string().contains("OutputWidth::Large"), "{err}");
+
+        let ScalarValue::LargeList(list) = evaluate_nulls(limit + 1, OutputWidth::Large)? else {
+            unreachable!()
+        };
+        assert_eq!(list.value_length(0) as usize, limit + 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_struct_agg_orders_merged_rows() -> Result<()> {
+        let mut partial = new_accumulator();
+        partial.update_batch(&batch(vec![1, 2], vec![Some("a"), None], vec![10, 30]))?;
+        let states = partial
+            .state()?
+            .iter()
+            .map(|state| state.to_array())
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut merged = new_accumulator();
+        merged.update_batch(&batch(vec![3], vec![Some("c")], vec![20]))?;
+        merged.merge_batch(&states)?;
+
+        let ScalarValue::List(list) = merged.evaluate()? else {
+            unreachable!()
+        };
+        let rows = list.value(0);
+        let rows = as_struct_array(&rows)?;
+        assert_eq!(rows.column(0).as_ref(), &Int64Array::from(vec![2, 3, 1]));
+        assert_eq!(
+            rows.column(1).as_ref(),
+            &StringArray::from(vec![None, Some("c"), Some("a")])
+        );
+
+        assert!(new_accumulator().evaluate()?.is_null());
+        Ok(())
+    }
+}
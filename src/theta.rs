@@ -0,0 +1,478 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::any::Any;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use arrow::array::{Array, ArrayRef, BinaryArray, Float64Array};
+use datafusion::arrow;
+use datafusion::arrow::datatypes::{DataType, Field};
+use datafusion::common::cast::as_binary_array;
+use datafusion::common::{exec_err, plan_err, Result, ScalarValue};
+use datafusion::logical_expr::function::{AccumulatorArgs, StateFieldsArgs};
+use datafusion::logical_expr::{Accumulator, AggregateUDFImpl, ColumnarValue, ScalarUDFImpl, Signature, Volatility};
+
+use crate::common::args::{literal_arg, scalar_args};
+use crate::fingerprint::{fingerprint_value_type, hash_rows};
+use crate::sketches::theta::{ThetaSketch, DEFAULT_NOMINAL_ENTRIES, NOMINAL_ENTRIES_RANGE};
+
+make_udaf_expr_and_func!(
+    ThetaSketchAggFunction,
+    theta_sketch_agg,
+    "Returns a serialized theta sketch of the distinct values, for set operations with other sketches.",
+    theta_sketch_agg_udaf
+);
+
+make_udf_expr_and_func!(
+    ThetaUnionFunction,
+    theta_union,
+    a b,
+    "Returns a theta sketch of the union of the values of two theta sketches.",
+    theta_union_udf
+);
+
+make_udf_expr_and_func!(
+    ThetaIntersectFunction,
+    theta_intersect,
+    a b,
+    "Returns a theta sketch of the intersection of the values of two theta sketches.",
+    theta_intersect_udf
+);
+
+make_udf_expr_and_func!(
+    ThetaEstimateFunction,
+    theta_estimate,
+    sketch,
+    "Estimates the number of distinct values of a theta sketch.",
+    theta_estimate_udf
+);
+
+/// The `ThetaSketchAggFunction` builds a theta sketch of the distinct non-null values, serialized
+/// in the versioned format of [`crate::sketches::format`].
+///
+/// - `theta_sketch_agg(x, nominal_entries)` keeps up to `nominal_entries` hashes, between 16
+///   and 2^26, for a relative standard error of about `1 / sqrt(nominal_entries)`. The default
+///   of 4096 gives about 1.6%.
+/// - Sketches of different tables combine with [`ThetaUnionFunction`] and
+///   [`ThetaIntersectFunction`], and [`ThetaEstimateFunction`] estimates their distinct values.
+/// - Values are hashed with Spark's Murmur3, so sketches written by different releases combine.
+pub struct ThetaSketchAggFunction {
+    signature: Signature,
+}
+
+impl Debug for ThetaSketchAggFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ThetaSketchAggFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for ThetaSketchAggFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ThetaSketchAggFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+        }
+    }
+}
+
+impl AggregateUDFImpl for ThetaSketchAggFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "theta_sketch_agg"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        match arg_types {
+            [value] => Ok(vec![fingerprint_value_type(value)]),
+            [value, _] => Ok(vec![fingerprint_value_type(value), DataType::Int64]),
+            _ => plan_err!("theta_sketch_agg expects a value and optional nominal_entries"),
+        }
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Binary)
+    }
+
+    fn is_nullable(&self) -> bool {
+        false
+    }
+
+    fn state_fields(&self, _args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(vec![Field::new("sketch", DataType::Binary, true)])
+    }
+
+    fn accumulator(&self, acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        let Some(expr) = acc_args.exprs.get(1) else {
+            return Ok(Box::new(ThetaSketchAccumulator::new(DEFAULT_NOMINAL_ENTRIES)));
+        };
+        let nominal_entries = literal_arg(expr, self.name(), "nominal_entries")?;
+        let valid = match nominal_entries {
+            ScalarValue::Int64(Some(nominal_entries)) => usize::try_from(nominal_entries)
+                .ok()
+                .filter(|nominal_entries| NOMINAL_ENTRIES_RANGE.contains(nominal_entries)),
+            _ => None,
+        };
+        let Some(nominal_entries) = valid else {
+            return plan_err!(
+                "theta_sketch_agg expects nominal_entries between {} and {}, got {nominal_entries}",
+                NOMINAL_ENTRIES_RANGE.start(),
+                NOMINAL_ENTRIES_RANGE.end()
+            );
+        };
+        Ok(Box::new(ThetaSketchAccumulator::new(nominal_entries)))
+    }
+
+    fn default_value(&self, _data_type: &DataType) -> Result<ScalarValue> {
+        Ok(ScalarValue::Binary(Some(ThetaSketch::default().to_bytes())))
+    }
+}
+
+/// Accumulator for [`ThetaSketchAggFunction`], adding the 64-bit hashes of the values to a
+/// [`ThetaSketch`].
+#[derive(Debug)]
+pub struct ThetaSketchAccumulator {
+    sketch: ThetaSketch,
+}
+
+impl ThetaSketchAccumulator {
+    pub fn new(nominal_entries: usize) -> Self {
+        Self {
+            sketch: ThetaSketch::new(nominal_entries),
+        }
+    }
+}
+
+impl Accumulator for ThetaSketchAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        let values = &values[0];
+        for (row, hash) in hash_rows(std::slice::from_ref(values), 2)?.into_iter().enumerate() {
+            if values.is_valid(row) {
+                self.sketch.add_hash(hash as u64);
+            }
+        }
+        Ok(())
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        for bytes in as_binary_array(&states[0])?.iter().flatten() {
+            self.sketch.merge(&ThetaSketch::from_bytes(bytes)?);
+        }
+        Ok(())
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        Ok(vec![self.evaluate()?])
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        Ok(ScalarValue::Binary(Some(self.sketch.to_bytes())))
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self) + self.sketch.size()
+    }
+}
+
+/// Coerces the sketch arguments of the scalar functions to Binary
+fn coerce_sketch_types(fn_name: &str, arity: usize, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+    if arg_types.len() != arity {
+        return plan_err!(
+            "{fn_name} expects {arity} theta sketches, got {} arguments",
+            arg_types.len()
+        );
+    }
+    arg_types
+        .iter()
+        .map(|arg_type| match arg_type {
+            DataType::Binary | DataType::LargeBinary | DataType::BinaryView | DataType::Null => Ok(DataType::Binary),
+            _ => plan_err!("{fn_name} expects theta sketches, got {arg_type}"),
+        })
+        .collect()
+}
+
+/// Invokes the set operation `op` on the sketches of `args`, NULL if either sketch is NULL
+fn invoke_set_operation(
+    args: &[ColumnarValue],
+    fn_name: &str,
+    op: impl Fn(&ThetaSketch, &ThetaSketch) -> ThetaSketch,
+) -> Result<ColumnarValue> {
+    let combine = |a: Option<&[u8]>, b: Option<&[u8]>| -> Result<Option<Vec<u8>>> {
+        match (a, b) {
+            (Some(a), Some(b)) => Ok(Some(
+                op(&ThetaSketch::from_bytes(a)?, &ThetaSketch::from_bytes(b)?).to_bytes(),
+            )),
+            _ => Ok(None),
+        }
+    };
+    if let Some([a, b]) = scalar_args(args) {
+        let (ScalarValue::Binary(a), ScalarValue::Binary(b)) = (a, b) else {
+            return exec_err!("{fn_name} expects theta sketches, got {a:?} and {b:?}");
+        };
+        let combined = combine(a.as_deref(), b.as_deref())?;
+        return Ok(ColumnarValue::Scalar(ScalarValue::Binary(combined)));
+    }
+
+    let arrays = ColumnarValue::values_to_arrays(args)?;
+    let (a, b) = (as_binary_array(&arrays[0])?, as_binary_array(&arrays[1])?);
+    let combined = a
+        .iter()
+        .zip(b.iter())
+        .map(|(a, b)| combine(a, b))
+        .collect::<Result<BinaryArray>>()?;
+    Ok(ColumnarValue::Array(Arc::new(combined)))
+}
+
+/// The `ThetaUnionFunction` combines two sketches of [`ThetaSketchAggFunction`] into a sketch
+/// of the union of their values.
+///
+/// - The result keeps the smaller nominal entries of the two sketches.
+/// - The result is NULL if either sketch is NULL.
+pub struct ThetaUnionFunction {
+    signature: Signature,
+}
+
+impl Debug for ThetaUnionFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ThetaUnionFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for ThetaUnionFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ThetaUnionFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+        }
+    }
+}
+
+impl ScalarUDFImpl for ThetaUnionFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "theta_union"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        coerce_sketch_types(self.name(), 2, arg_types)
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Binary)
+    }
+
+    fn invoke(&self, args: &[ColumnarValue]) -> Result<ColumnarValue> {
+        invoke_set_operation(args, self.name(), ThetaSketch::union)
+    }
+}
+
+/// The `ThetaIntersectFunction` combines two sketches of [`ThetaSketchAggFunction`] into a
+/// sketch of the values they have in common.
+///
+/// - The error of the estimate grows as the intersection gets smaller than the union: it's
+///   about `sqrt(union / intersection)` times that of the sketches.
+/// - The result is NULL if either sketch is NULL.
+pub struct ThetaIntersectFunction {
+    signature: Signature,
+}
+
+impl Debug for ThetaIntersectFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ThetaIntersectFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for ThetaIntersectFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ThetaIntersectFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+        }
+    }
+}
+
+impl ScalarUDFImpl for ThetaIntersectFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "theta_intersect"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        coerce_sketch_types(self.name(), 2, arg_types)
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Binary)
+    }
+
+    fn invoke(&self, args: &[ColumnarValue]) -> Result<ColumnarValue> {
+        invoke_set_operation(args, self.name(), ThetaSketch::intersect)
+    }
+}
+
+/// The `ThetaEstimateFunction` estimates the number of distinct values of a sketch of
+/// [`ThetaSketchAggFunction`], [`ThetaUnionFunction`] or [`ThetaIntersectFunction`].
+///
+/// - The estimate is exact while the sketch holds fewer hashes than its nominal entries.
+/// - The result is NULL if the sketch is NULL.
+pub struct ThetaEstimateFunction {
+    signature: Signature,
+}
+
+impl Debug for ThetaEstimateFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ThetaEstimateFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for ThetaEstimateFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ThetaEstimateFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+        }
+    }
+}
+
+impl ScalarUDFImpl for ThetaEstimateFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "theta_estimate"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        coerce_sketch_types(self.name(), 1, arg_types)
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Float64)
+    }
+
+    fn invoke(&self, args: &[ColumnarValue]) -> Result<ColumnarValue> {
+        let estimate = |sketch: &[u8]| Ok(ThetaSketch::from_bytes(sketch)?.estimate());
+        if let Some([sketch]) = scalar_args(args) {
+            let ScalarValue::Binary(sketch) = sketch else {
+                return exec_err!("theta_estimate expects a theta sketch, got {sketch:?}");
+            };
+            let estimate = sketch.as_deref().map(estimate).transpose()?;
+            return Ok(ColumnarValue::Scalar(ScalarValue::Float64(estimate)));
+        }
+
+        let arrays = ColumnarValue::values_to_arrays(args)?;
+        let estimates = as_binary_array(&arrays[0])?
+            .iter()
+            .map(|sketch| sketch.map(estimate).transpose())
+            .collect::<Result<Float64Array>>()?;
+        Ok(ColumnarValue::Array(Arc::new(estimates)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::array::Int64Array;
+
+    use super::*;
+
+    fn sketch(values: std::ops::Range<i64>) -> Result<Option<Vec<u8>>> {
+        let mut acc = ThetaSketchAccumulator::new(DEFAULT_NOMINAL_ENTRIES);
+        acc.update_batch(&[Arc::new(Int64Array::from_iter(values.map(Some).chain([None]))) as ArrayRef])?;
+        match acc.evaluate()? {
+            ScalarValue::Binary(sketch) => Ok(sketch),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_set_operations_on_arrays() -> Result<()> {
+        let a: ArrayRef = Arc::new(BinaryArray::from_iter(vec![sketch(0..100)?, None]));
+        let b: ArrayRef = Arc::new(BinaryArray::from_iter(vec![sketch(50..120)?, sketch(0..1)?]));
+        let args = [ColumnarValue::Array(a), ColumnarValue::Array(b)];
+
+        let estimate = |sketches: ColumnarValue| -> Result<Vec<Option<f64>>> {
+            let ColumnarValue::Array(estimates) = ThetaEstimateFunction::new().invoke(&[sketches])? else {
+                unreachable!()
+            };
+            Ok(estimates
+                .as_any()
+                .downcast_ref::<Float64Array>()
+                .unwrap()
+                .iter()
+                .collect())
+        };
+        let union = ThetaUnionFunction::new().invoke(&args)?;
+        assert_eq!(estimate(union)?, vec![Some(120.0), None]);
+        let intersection = ThetaIntersectFunction::new().invoke(&args)?;
+        assert_eq!(estimate(intersection)?, vec![Some(50.0), None]);
+        Ok(())
+    }
+}
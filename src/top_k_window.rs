@@ -0,0 +1,172 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::any::Any;
+use std::fmt::Debug;
+use std::ops::Range;
+use std::sync::Arc;
+
+use arrow::array::{Array, ArrayRef};
+use arrow::datatypes::DataType;
+use arrow::row::{RowConverter, SortField};
+use datafusion::arrow;
+use datafusion::common::cast::as_int64_array;
+use datafusion::common::{exec_err, plan_err, Result, ScalarValue};
+use datafusion::logical_expr::{PartitionEvaluator, Signature, Volatility, WindowUDFImpl};
+
+use crate::common::mode::RollingFrequencies;
+use crate::compat::single_row_list;
+
+make_udwf_expr_and_func!(
+    TopKWindowFunction,
+    top_k_window,
+    expression k,
+    "Returns the `k` most frequent non-null values of the window frame as a list, the most frequent first.",
+    top_k_window_udwf
+);
+
+/// The `TopKWindowFunction` returns the `k` most frequent values of every window frame, e.g. a
+/// leaderboard of the last hour with
+/// `top_k_window(page, 3) OVER (ORDER BY ts RANGE BETWEEN INTERVAL '1 hour' PRECEDING AND CURRENT ROW)`.
+///
+/// - The values are counted as for `rolling_mode`: the rows leaving the frame are retracted
+///   rather than recounting every frame, and the most frequent values are kept in a heap.
+/// - The values are listed from the most frequent, the smallest first on ties. Null values are
+///   ignored, so a frame without non-null values returns an empty list.
+/// - `k` must be a positive integer, the same for every row of a partition.
+pub struct TopKWindowFunction {
+    signature: Signature,
+}
+
+impl Debug for TopKWindowFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TopKWindowFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for TopKWindowFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TopKWindowFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+        }
+    }
+}
+
+impl WindowUDFImpl for TopKWindowFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "top_k_window"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        let [value_type, k_type] = arg_types else {
+            return plan_err!(
+                "top_k_window expects 2 arguments (expression, k), got {}",
+                arg_types.len()
+            );
+        };
+        let value_type = match value_type {
+            DataType::Dictionary(_, value_type) => value_type.as_ref().clone(),
+            DataType::Null => DataType::Int64,
+            value_type => value_type.clone(),
+        };
+        if !RowConverter::supports_fields(&[SortField::new(value_type.clone())]) {
+            return plan_err!("top_k_window expects a value that can be ordered, got {value_type}");
+        }
+        if !k_type.is_integer() {
+            return plan_err!("top_k_window expects an integer k, got {k_type}");
+        }
+        Ok(vec![value_type, DataType::Int64])
+    }
+
+    fn return_type(&self, arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::new_list(arg_types[0].clone(), true))
+    }
+
+    fn nullable(&self) -> bool {
+        false
+    }
+
+    fn partition_evaluator(&self) -> Result<Box<dyn PartitionEvaluator>> {
+        Ok(Box::<TopKWindowEvaluator>::default())
+    }
+}
+
+/// Evaluator for [`TopKWindowFunction`], adding the rows entering every frame and retracting
+/// the rows leaving it since the previous one.
+#[derive(Debug, Default)]
+pub struct TopKWindowEvaluator {
+    /// Created from the arguments of the first frame evaluated
+    state: Option<(RollingFrequencies, usize)>,
+    /// The rows counted in the frequencies
+    frame: Range<usize>,
+}
+
+impl PartitionEvaluator for TopKWindowEvaluator {
+    fn uses_window_frame(&self) -> bool {
+        true
+    }
+
+    fn evaluate(&mut self, values: &[ArrayRef], range: &Range<usize>) -> Result<ScalarValue> {
+        let (values, k) = (&values[0], as_int64_array(&values[1])?);
+        let (frequencies, k) = match &mut self.state {
+            Some((frequencies, k)) => (frequencies, *k),
+            state => {
+                let k = match k.iter().next().flatten() {
+                    Some(first) if first > 0 && k.null_count() == 0 && k.values().iter().all(|&k| k == first) => {
+                        first as usize
+                    }
+                    _ => return exec_err!("top_k_window expects a positive constant k"),
+                };
+                let (frequencies, k) = state.insert((RollingFrequencies::try_new(values.data_type())?, k));
+                (frequencies, *k)
+            }
+        };
+
+        // Frames usually slide forward, otherwise they are counted again
+        if range.start < self.frame.start || range.end < self.frame.end || range.start >= self.frame.end {
+            frequencies.clear();
+            self.frame = range.start..range.start;
+        }
+        if range.end > self.frame.end {
+            frequencies.add(&values.slice(self.frame.end, range.end - self.frame.end), |_| 1)?;
+        }
+        if range.start > self.frame.start {
+            frequencies.add(&values.slice(self.frame.start, range.start - self.frame.start), |_| -1)?;
+        }
+        self.frame = range.clone();
+
+        let top = frequencies.top_k(k);
+        let values = frequencies.values(top.iter().map(|(row, _)| row))?;
+        Ok(ScalarValue::List(Arc::new(single_row_list(values))))
+    }
+}
@@ -0,0 +1,528 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::any::Any;
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use arrow::array::{Array, ArrayRef};
+use arrow::compute::SortOptions;
+use arrow::datatypes::{DataType, Field};
+use arrow::row::{OwnedRow, RowConverter, SortField};
+use datafusion::arrow;
+use datafusion::common::cast::as_list_array;
+use datafusion::common::{exec_err, plan_err, Result, ScalarValue};
+use datafusion::logical_expr::function::{AccumulatorArgs, StateFieldsArgs};
+use datafusion::logical_expr::{Accumulator, AggregateUDFImpl, Signature, Volatility};
+use datafusion::physical_expr::PhysicalExpr;
+
+use crate::common::args::literal_arg;
+use crate::compat::{arg_type, single_row_list};
+
+make_udaf_expr_and_func!(
+    TopNByFunction,
+    top_n_by,
+    value key n,
+    "Returns the values of the rows with the `n` largest keys as a list, ordered by descending key.",
+    top_n_by_udaf
+);
+
+make_udaf_expr_and_func!(
+    MaxNFunction,
+    max_n,
+    x n,
+    "Returns the `n` largest non-null values as a list, in descending order.",
+    max_n_udaf
+);
+
+make_udaf_expr_and_func!(
+    MinNFunction,
+    min_n,
+    x n,
+    "Returns the `n` smallest non-null values as a list, in ascending order.",
+    min_n_udaf
+);
+
+/// The `TopNByFunction` returns the values of the rows with the `n` largest keys of a group,
+/// `top_n_by(value, key, n)`, e.g. the 5 best selling products of every store with
+/// `top_n_by(product, sales, 5) ... GROUP BY store`.
+///
+/// - The result is a list of up to `n` values ordered by descending key. Keys of any type that
+///   can be ordered are compared as by `ORDER BY key DESC`.
+/// - Rows with a NULL key are skipped, NULL values are kept. The result is NULL if there are no
+///   rows with a key.
+/// - Every group keeps at most `n` rows. Of rows with equal keys, the ones read first are kept,
+///   so which ones are returned depends on the partitioning of the input.
+pub struct TopNByFunction {
+    signature: Signature,
+}
+
+impl Debug for TopNByFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TopNByFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for TopNByFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TopNByFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+        }
+    }
+}
+
+impl AggregateUDFImpl for TopNByFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "top_n_by"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        let [value_type, key_type, n_type] = arg_types else {
+            return plan_err!("top_n_by expects 3 arguments (value, key, n), got {}", arg_types.len());
+        };
+        Ok(vec![
+            value_type.clone(),
+            orderable_type(self.name(), "key", key_type)?,
+            n_type_arg(self.name(), n_type)?,
+        ])
+    }
+
+    fn return_type(&self, arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::new_list(arg_types[0].clone(), true))
+    }
+
+    fn state_fields(&self, args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(vec![
+            Field::new_list("values", Field::new("item", args.input_types[0].clone(), true), true),
+            Field::new_list("keys", Field::new("item", args.input_types[1].clone(), true), true),
+        ])
+    }
+
+    fn accumulator(&self, acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        Ok(Box::new(TopNByAccumulator::try_new(
+            arg_type(&acc_args, 0)?,
+            arg_type(&acc_args, 1)?,
+            n_arg(&acc_args.exprs[2], self.name())?,
+        )?))
+    }
+}
+
+/// The `MaxNFunction` returns the `n` largest values of a group, `max_n(x, n)`.
+///
+/// - The result is a list of up to `n` values in descending order, compared as by
+///   `ORDER BY x DESC`. Equal values are all kept, e.g. `max_n(x, 2)` of `3, 3, 1` is `[3, 3]`.
+/// - NULL values are skipped, and the result is NULL if there are no other values.
+/// - Every group keeps at most `n` values, see [`TopNByAccumulator`].
+pub struct MaxNFunction {
+    signature: Signature,
+}
+
+impl Debug for MaxNFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MaxNFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for MaxNFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MaxNFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+        }
+    }
+}
+
+impl AggregateUDFImpl for MaxNFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "max_n"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        coerce_n_types(self.name(), arg_types)
+    }
+
+    fn return_type(&self, arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::new_list(arg_types[0].clone(), true))
+    }
+
+    fn state_fields(&self, args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(vec![Field::new_list(
+            "values",
+            Field::new("item", args.input_types[0].clone(), true),
+            true,
+        )])
+    }
+
+    fn accumulator(&self, acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        Ok(Box::new(TopNByAccumulator::try_new_values(
+            arg_type(&acc_args, 0)?,
+            n_arg(&acc_args.exprs[1], self.name())?,
+            false,
+        )?))
+    }
+}
+
+/// The `MinNFunction` returns the `n` smallest values of a group, `min_n(x, n)`, as a list in
+/// ascending order. It is otherwise the same as [`MaxNFunction`].
+pub struct MinNFunction {
+    signature: Signature,
+}
+
+impl Debug for MinNFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MinNFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for MinNFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MinNFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+        }
+    }
+}
+
+impl AggregateUDFImpl for MinNFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "min_n"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        coerce_n_types(self.name(), arg_types)
+    }
+
+    fn return_type(&self, arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::new_list(arg_types[0].clone(), true))
+    }
+
+    fn state_fields(&self, args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(vec![Field::new_list(
+            "values",
+            Field::new("item", args.input_types[0].clone(), true),
+            true,
+        )])
+    }
+
+    fn accumulator(&self, acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        Ok(Box::new(TopNByAccumulator::try_new_values(
+            arg_type(&acc_args, 0)?,
+            n_arg(&acc_args.exprs[1], self.name())?,
+            true,
+        )?))
+    }
+}
+
+/// The arguments of `max_n(x, n)` and `min_n(x, n)`
+fn coerce_n_types(fn_name: &str, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+    let [value_type, n_type] = arg_types else {
+        return plan_err!("{fn_name} expects 2 arguments (x, n), got {}", arg_types.len());
+    };
+    Ok(vec![
+        orderable_type(fn_name, "x", value_type)?,
+        n_type_arg(fn_name, n_type)?,
+    ])
+}
+
+/// The type of an argument ordered in the row format, unwrapping dictionaries
+fn orderable_type(fn_name: &str, arg_name: &str, arg_type: &DataType) -> Result<DataType> {
+    let arg_type = match arg_type {
+        DataType::Dictionary(_, value_type) => value_type.as_ref().clone(),
+        arg_type => arg_type.clone(),
+    };
+    if !RowConverter::supports_fields(&[SortField::new(arg_type.clone())]) {
+        return plan_err!("{fn_name} expects {arg_name} that can be ordered, got {arg_type}");
+    }
+    Ok(arg_type)
+}
+
+fn n_type_arg(fn_name: &str, n_type: &DataType) -> Result<DataType> {
+    if !n_type.is_integer() {
+        return plan_err!("{fn_name} expects an integer n, got {n_type}");
+    }
+    Ok(DataType::Int64)
+}
+
+fn n_arg(expr: &Arc<dyn PhysicalExpr>, fn_name: &str) -> Result<usize> {
+    match literal_arg(expr, fn_name, "n")? {
+        ScalarValue::Int64(Some(n)) if n > 0 => Ok(n as usize),
+        n => plan_err!("{fn_name} expects a positive n, got {n}"),
+    }
+}
+
+/// A row kept by [`TopNByAccumulator`], ordered by key and then by descending arrival so that
+/// the smallest entry is the one to evict first
+#[derive(Debug)]
+struct Entry {
+    key: OwnedRow,
+    seq: u64,
+    value: ScalarValue,
+}
+
+impl Ord for Entry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key.cmp(&other.key).then(other.seq.cmp(&self.seq))
+    }
+}
+
+impl PartialOrd for Entry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Entry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Entry {}
+
+/// Accumulator for [`TopNByFunction`], keeping the `n` rows with the largest keys in a heap.
+///
+/// The keys are compared in the row format. For [`MaxNFunction`] and [`MinNFunction`], the
+/// values are their own keys, in descending order for `min_n` so that the smallest are kept.
+#[derive(Debug)]
+pub struct TopNByAccumulator {
+    value_type: DataType,
+    converter: RowConverter,
+    n: usize,
+    /// The kept rows, the smallest first
+    heap: BinaryHeap<Reverse<Entry>>,
+    seq: u64,
+    /// Whether the keys are a separate argument, rather than the values
+    by_key: bool,
+}
+
+impl TopNByAccumulator {
+    pub fn try_new(value_type: DataType, key_type: DataType, n: usize) -> Result<Self> {
+        Ok(Self {
+            value_type,
+            converter: RowConverter::new(vec![SortField::new(key_type)])?,
+            n,
+            heap: BinaryHeap::with_capacity(n),
+            seq: 0,
+            by_key: true,
+        })
+    }
+
+    /// Keeps the `n` largest values, or the `n` smallest ones if `smallest`
+    pub fn try_new_values(value_type: DataType, n: usize, smallest: bool) -> Result<Self> {
+        let options = SortOptions {
+            descending: smallest,
+            nulls_first: false,
+        };
+        Ok(Self {
+            converter: RowConverter::new(vec![SortField::new_with_options(value_type.clone(), options)])?,
+            value_type,
+            n,
+            heap: BinaryHeap::with_capacity(n),
+            seq: 0,
+            by_key: false,
+        })
+    }
+
+    /// Offers the rows of `values` and `keys`, converting the values only if they are kept
+    fn offer(&mut self, values: &ArrayRef, keys: &ArrayRef) -> Result<()> {
+        if values.len() != keys.len() {
+            return exec_err!("top_n_by state has {} values but {} keys", values.len(), keys.len());
+        }
+        let rows = self.converter.convert_columns(&[Arc::clone(keys)])?;
+        for (index, key) in rows.iter().enumerate() {
+            if keys.is_null(index) {
+                continue;
+            }
+            if self.heap.len() == self.n {
+                // Equal keys lose to the rows read before them
+                match self.heap.peek() {
+                    Some(Reverse(smallest)) if key > smallest.key.row() => {
+                        self.heap.pop();
+                    }
+                    _ => continue,
+                }
+            }
+            self.heap.push(Reverse(Entry {
+                key: key.owned(),
+                seq: self.seq,
+                value: ScalarValue::try_from_array(values, index)?,
+            }));
+            self.seq += 1;
+        }
+        Ok(())
+    }
+
+    /// The kept rows, the largest first
+    fn sorted(&self) -> Vec<&Entry> {
+        let mut entries: Vec<&Entry> = self.heap.iter().map(|Reverse(entry)| entry).collect();
+        entries.sort_unstable_by(|a, b| b.cmp(a));
+        entries
+    }
+
+    fn values(&self, entries: &[&Entry]) -> Result<ScalarValue> {
+        if entries.is_empty() {
+            return ScalarValue::try_from(DataType::new_list(self.value_type.clone(), true));
+        }
+        let values: Vec<ScalarValue> = entries.iter().map(|entry| entry.value.clone()).collect();
+        Ok(ScalarValue::List(ScalarValue::new_list_nullable(
+            &values,
+            &self.value_type,
+        )))
+    }
+}
+
+impl Accumulator for TopNByAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        let keys = if self.by_key { &values[1] } else { &values[0] };
+        self.offer(&values[0], keys)
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        let keys = if self.by_key { &states[1] } else { &states[0] };
+        let (values, keys) = (as_list_array(&states[0])?, as_list_array(keys)?);
+        for (values, keys) in values.iter().zip(keys.iter()) {
+            if let (Some(values), Some(keys)) = (values, keys) {
+                self.offer(&values, &keys)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        let entries = self.sorted();
+        if !self.by_key {
+            return Ok(vec![self.values(&entries)?]);
+        }
+        let keys = self
+            .converter
+            .convert_rows(entries.iter().map(|entry| entry.key.row()))?;
+        Ok(vec![
+            self.values(&entries)?,
+            ScalarValue::List(Arc::new(single_row_list(Arc::clone(&keys[0])))),
+        ])
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        self.values(&self.sorted())
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self)
+            + self.converter.size()
+            + self.heap.capacity() * std::mem::size_of::<Reverse<Entry>>()
+            + self
+                .heap
+                .iter()
+                .map(|Reverse(entry)| {
+                    entry.key.as_ref().len() + entry.value.size() - std::mem::size_of_val(&entry.value)
+                })
+                .sum::<usize>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{Int64Array, StringArray};
+
+    fn top(acc: &mut TopNByAccumulator) -> Result<Vec<Option<String>>> {
+        let ScalarValue::List(list) = acc.evaluate()? else {
+            panic!("expected a list");
+        };
+        let values = list.value(0);
+        Ok(values
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap()
+            .iter()
+            .map(|value| value.map(str::to_string))
+            .collect())
+    }
+
+    #[test]
+    fn test_top_n_by_merge_keeps_the_largest_keys() -> Result<()> {
+        let batches = [
+            (vec![Some("a"), Some("b"), None], vec![Some(1), Some(5), Some(3)]),
+            (vec![Some("c"), Some("d"), Some("e")], vec![Some(5), None, Some(4)]),
+        ];
+        let mut merged = TopNByAccumulator::try_new(DataType::Utf8, DataType::Int64, 3)?;
+        for (values, keys) in batches {
+            let mut partial = TopNByAccumulator::try_new(DataType::Utf8, DataType::Int64, 3)?;
+            partial.update_batch(&[Arc::new(StringArray::from(values)), Arc::new(Int64Array::from(keys))])?;
+            let state: Vec<ArrayRef> = partial
+                .state()?
+                .iter()
+                .map(|value| value.to_array())
+                .collect::<Result<_>>()?;
+            merged.merge_batch(&state)?;
+        }
+        // "c" ties with "b" but is merged after it
+        assert_eq!(
+            top(&mut merged)?,
+            vec![Some("b".into()), Some("c".into()), Some("e".into())]
+        );
+        Ok(())
+    }
+}
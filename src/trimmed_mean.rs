@@ -0,0 +1,483 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::any::Any;
+use std::fmt::Debug;
+
+use arrow::array::ArrayRef;
+use datafusion::arrow;
+use datafusion::arrow::datatypes::{DataType, Field};
+use datafusion::common::cast::{as_binary_array, as_float64_array};
+use datafusion::common::{plan_err, Result, ScalarValue};
+use datafusion::logical_expr::function::{AccumulatorArgs, StateFieldsArgs};
+use datafusion::logical_expr::{Accumulator, AggregateUDFImpl, Signature, Volatility};
+
+use crate::common::args::literal_f64_arg;
+use crate::common::nulls::all_null_or_filtered;
+use crate::common::quantile_buffer::QuantileBuffer;
+use crate::sketches::tdigest::TDigest;
+
+make_udaf_expr_and_func!(
+    TrimmedMeanFunction,
+    trimmed_mean,
+    x fraction,
+    "Calculates the mean after discarding the lowest and highest `fraction` of the values.",
+    trimmed_mean_udaf
+);
+
+make_udaf_expr_and_func!(
+    ApproxTrimmedMeanFunction,
+    approx_trimmed_mean,
+    x fraction,
+    "Approximates the mean after discarding the lowest and highest `fraction` of the values.",
+    approx_trimmed_mean_udaf
+);
+
+make_udaf_expr_and_func!(
+    WinsorizedMeanFunction,
+    winsorized_mean,
+    x fraction,
+    "Calculates the mean after clamping the lowest and highest `fraction` of the values to the remaining extremes.",
+    winsorized_mean_udaf
+);
+
+make_udaf_expr_and_func!(
+    ApproxWinsorizedMeanFunction,
+    approx_winsorized_mean,
+    x fraction,
+    "Approximates the mean after clamping the lowest and highest `fraction` of the values to the quantile bounds.",
+    approx_winsorized_mean_udaf
+);
+
+/// What the buffered and t-digest accumulators do with the values outside the `fraction` tails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TailPolicy {
+    /// Discard the tails, as `trimmed_mean` does
+    Trim,
+    /// Clamp the tails to the nearest kept value, as `winsorized_mean` does
+    Winsorize,
+}
+
+/// Reads the literal `fraction` argument, which must be in `[0, 0.5)`
+fn trim_fraction(acc_args: &AccumulatorArgs, fn_name: &str) -> Result<f64> {
+    let fraction = literal_f64_arg(&acc_args.exprs[1], fn_name, "fraction")?;
+    if !(0.0..0.5).contains(&fraction) {
+        return plan_err!("{fn_name} expects a fraction in [0, 0.5), got {fraction}");
+    }
+    Ok(fraction)
+}
+
+/// The `TrimmedMeanFunction` calculates the mean of the values after discarding the
+/// `floor(n * fraction)` lowest and highest values.
+///
+/// - NULL values are ignored.
+/// - `fraction` must be a literal in `[0, 0.5)`.
+/// - Every value of a group is buffered, see [`ApproxTrimmedMeanFunction`] for large groups.
+pub struct TrimmedMeanFunction {
+    signature: Signature,
+}
+
+impl Debug for TrimmedMeanFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TrimmedMeanFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for TrimmedMeanFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TrimmedMeanFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::coercible(vec![DataType::Float64, DataType::Float64], Volatility::Immutable),
+        }
+    }
+}
+
+impl AggregateUDFImpl for TrimmedMeanFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "trimmed_mean"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Float64)
+    }
+
+    fn state_fields(&self, _args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(vec![Field::new_list(
+            "values",
+            Field::new("item", DataType::Float64, true),
+            true,
+        )])
+    }
+
+    fn accumulator(&self, acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        let fraction = trim_fraction(&acc_args, self.name())?;
+        Ok(Box::new(TrimmedMeanAccumulator::new(fraction, TailPolicy::Trim)))
+    }
+}
+
+/// Accumulator for [`TrimmedMeanFunction`] and [`WinsorizedMeanFunction`], which buffers the values.
+#[derive(Debug)]
+pub struct TrimmedMeanAccumulator {
+    values: QuantileBuffer,
+    fraction: f64,
+    tails: TailPolicy,
+}
+
+impl TrimmedMeanAccumulator {
+    pub fn new(fraction: f64, tails: TailPolicy) -> Self {
+        Self {
+            values: QuantileBuffer::new(),
+            fraction,
+            tails,
+        }
+    }
+}
+
+impl Accumulator for TrimmedMeanAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        self.values.update_batch(&values[0])
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        self.values.merge_batch(&states[0])
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        Ok(vec![self.values.state()])
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        let values = self.values.sorted();
+        let n = values.len();
+        let tail = (n as f64 * self.fraction) as usize;
+        let kept = &values[tail..n - tail];
+        let (Some(first), Some(last)) = (kept.first(), kept.last()) else {
+            return Ok(ScalarValue::Float64(None));
+        };
+        let mean = match self.tails {
+            TailPolicy::Trim => kept.iter().sum::<f64>() / kept.len() as f64,
+            TailPolicy::Winsorize => (kept.iter().sum::<f64>() + tail as f64 * (first + last)) / n as f64,
+        };
+        Ok(ScalarValue::Float64(Some(mean)))
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self) + self.values.size()
+    }
+}
+
+/// The `ApproxTrimmedMeanFunction` approximates [`TrimmedMeanFunction`] in constant memory.
+///
+/// The values are summarized in a [`TDigest`] and the mean is taken over the centroids between
+/// the `fraction` and `1 - fraction` quantiles, so it is accurate for large groups but may
+/// differ from the exact result for small ones.
+pub struct ApproxTrimmedMeanFunction {
+    signature: Signature,
+}
+
+impl Debug for ApproxTrimmedMeanFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ApproxTrimmedMeanFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for ApproxTrimmedMeanFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ApproxTrimmedMeanFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::coercible(vec![DataType::Float64, DataType::Float64], Volatility::Immutable),
+        }
+    }
+}
+
+impl AggregateUDFImpl for ApproxTrimmedMeanFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "approx_trimmed_mean"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Float64)
+    }
+
+    fn state_fields(&self, _args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(vec![Field::new("digest", DataType::Binary, true)])
+    }
+
+    fn accumulator(&self, acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        let fraction = trim_fraction(&acc_args, self.name())?;
+        Ok(Box::new(ApproxTrimmedMeanAccumulator::new(fraction, TailPolicy::Trim)))
+    }
+}
+
+/// Accumulator for [`ApproxTrimmedMeanFunction`] and [`ApproxWinsorizedMeanFunction`].
+#[derive(Debug)]
+pub struct ApproxTrimmedMeanAccumulator {
+    digest: TDigest,
+    fraction: f64,
+    tails: TailPolicy,
+}
+
+impl ApproxTrimmedMeanAccumulator {
+    pub fn new(fraction: f64, tails: TailPolicy) -> Self {
+        Self {
+            digest: TDigest::default(),
+            fraction,
+            tails,
+        }
+    }
+}
+
+impl Accumulator for ApproxTrimmedMeanAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        if all_null_or_filtered(&values[0], None) {
+            return Ok(());
+        }
+        for value in as_float64_array(&values[0])?.iter().flatten() {
+            self.digest.add(value);
+        }
+        Ok(())
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        for bytes in as_binary_array(&states[0])?.iter().flatten() {
+            self.digest.merge(&TDigest::from_bytes(bytes)?);
+        }
+        Ok(())
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        Ok(vec![ScalarValue::Binary(Some(self.digest.to_bytes()))])
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        let (lower, upper) = (self.fraction, 1.0 - self.fraction);
+        Ok(ScalarValue::Float64(match self.tails {
+            TailPolicy::Trim => self.digest.trimmed_mean(lower, upper),
+            TailPolicy::Winsorize => self.digest.winsorized_mean(lower, upper),
+        }))
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self) + self.digest.size()
+    }
+}
+
+/// The `WinsorizedMeanFunction` calculates the mean of the values after replacing the
+/// `floor(n * fraction)` lowest and highest values with the lowest and highest remaining values.
+///
+/// See [`TrimmedMeanFunction`] for how NULLs and `fraction` are handled.
+pub struct WinsorizedMeanFunction {
+    signature: Signature,
+}
+
+impl Debug for WinsorizedMeanFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WinsorizedMeanFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for WinsorizedMeanFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WinsorizedMeanFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::coercible(vec![DataType::Float64, DataType::Float64], Volatility::Immutable),
+        }
+    }
+}
+
+impl AggregateUDFImpl for WinsorizedMeanFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "winsorized_mean"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Float64)
+    }
+
+    fn state_fields(&self, args: StateFieldsArgs) -> Result<Vec<Field>> {
+        TrimmedMeanFunction::new().state_fields(args)
+    }
+
+    fn accumulator(&self, acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        let fraction = trim_fraction(&acc_args, self.name())?;
+        Ok(Box::new(TrimmedMeanAccumulator::new(fraction, TailPolicy::Winsorize)))
+    }
+}
+
+/// The `ApproxWinsorizedMeanFunction` approximates [`WinsorizedMeanFunction`] in constant memory,
+/// clamping the tails to the `fraction` and `1 - fraction` quantiles of a [`TDigest`].
+pub struct ApproxWinsorizedMeanFunction {
+    signature: Signature,
+}
+
+impl Debug for ApproxWinsorizedMeanFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ApproxWinsorizedMeanFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for ApproxWinsorizedMeanFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ApproxWinsorizedMeanFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::coercible(vec![DataType::Float64, DataType::Float64], Volatility::Immutable),
+        }
+    }
+}
+
+impl AggregateUDFImpl for ApproxWinsorizedMeanFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "approx_winsorized_mean"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Float64)
+    }
+
+    fn state_fields(&self, args: StateFieldsArgs) -> Result<Vec<Field>> {
+        ApproxTrimmedMeanFunction::new().state_fields(args)
+    }
+
+    fn accumulator(&self, acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        let fraction = trim_fraction(&acc_args, self.name())?;
+        Ok(Box::new(ApproxTrimmedMeanAccumulator::new(
+            fraction,
+            TailPolicy::Winsorize,
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow::array::Float64Array;
+
+    use super::*;
+
+    #[test]
+    fn test_trimmed_mean() -> Result<()> {
+        let values: ArrayRef = Arc::new(Float64Array::from(vec![
+            Some(-100.0),
+            Some(1.0),
+            None,
+            Some(2.0),
+            Some(3.0),
+            Some(1000.0),
+        ]));
+
+        // 5 values, floor(5 * 0.2) = 1 trimmed from each end
+        let mut acc = TrimmedMeanAccumulator::new(0.2, TailPolicy::Trim);
+        acc.update_batch(&[Arc::clone(&values)])?;
+        assert_eq!(acc.evaluate()?, ScalarValue::Float64(Some(2.0)));
+
+        // floor(5 * 0.1) = 0 trimmed
+        let mut acc = TrimmedMeanAccumulator::new(0.1, TailPolicy::Trim);
+        acc.update_batch(&[Arc::clone(&values)])?;
+        assert_eq!(acc.evaluate()?, ScalarValue::Float64(Some(181.2)));
+
+        // -100 and 1000 are clamped to 1 and 3
+        let mut acc = TrimmedMeanAccumulator::new(0.2, TailPolicy::Winsorize);
+        acc.update_batch(&[values])?;
+        assert_eq!(acc.evaluate()?, ScalarValue::Float64(Some(2.0)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_approx_trimmed_mean_matches_exact() -> Result<()> {
+        let values: ArrayRef = Arc::new(Float64Array::from_iter_values(
+            (0..10_000).map(|i| ((i * 7919) % 10_000) as f64),
+        ));
+        for tails in [TailPolicy::Trim, TailPolicy::Winsorize] {
+            let mut exact = TrimmedMeanAccumulator::new(0.25, tails);
+            exact.update_batch(&[Arc::clone(&values)])?;
+            let mut approx = ApproxTrimmedMeanAccumulator::new(0.25, tails);
+            approx.update_batch(&[Arc::clone(&values)])?;
+
+            let (ScalarValue::Float64(Some(exact)), ScalarValue::Float64(Some(approx))) =
+                (exact.evaluate()?, approx.evaluate()?)
+            else {
+                panic!("expected non-null means");
+            };
+            assert!(
+                (exact - approx).abs() < 1.0,
+                "{tails:?}: exact {exact}, approx {approx}"
+            );
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,202 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::collections::HashSet;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use datafusion::arrow::datatypes::{DataType, SchemaRef};
+use datafusion::catalog::TableProvider;
+use datafusion::common::{plan_err, Column, Result, TableReference, UnnestOptions};
+use datafusion::datasource::function::TableFunctionImpl;
+use datafusion::datasource::{provider_as_source, ViewTable};
+use datafusion::execution::context::SessionContext;
+use datafusion::functions_nested::expr_fn::make_array;
+use datafusion::logical_expr::type_coercion::binary::type_union_resolution;
+use datafusion::logical_expr::{cast, ident, lit, Expr, LogicalPlanBuilder, ScalarUDF};
+
+use crate::common::interner::StringInterner;
+use crate::common::tables::{column_arg, table_arg, SessionTables};
+use crate::config::ExtraFunctionsConfig;
+use crate::intern::InternFunction;
+
+/// Name of the output column holding the names of the value columns
+const KEY_COLUMN: &str = "key";
+/// Name of the output column holding the values of the value columns
+const VALUE_COLUMN: &str = "value";
+
+/// The `UnpivotFunction` melts a wide table into a long one, the table function
+/// `unpivot(table, value_col, ...)`.
+///
+/// - Every input row is turned into one row per value column, made of the other columns of the
+///   table, the name of the value column as `key` and its value as `value`.
+/// - The value columns are cast to a type they all unify to, as in `UNION`, and strings only
+///   unify with strings. NULL values are skipped, as with SQL's `UNPIVOT`.
+/// - The table and the value columns are identifiers or strings, e.g.
+///   `SELECT * FROM unpivot(metrics, cpu, 'memory')`.
+///
+/// Tables are resolved in the catalogs of the [`SessionContext`] the function was created for,
+/// see [`crate::register_extra_table_functions`] and [`SessionTables`].
+///
+/// With [`Self::with_string_interner`], or while the session of `ctx` sets
+/// [`ExtraFunctionsConfig::intern_strings`], the `key` column is a dictionary shared by all
+/// batches.
+pub struct UnpivotFunction {
+    tables: SessionTables,
+    interner: Option<StringInterner>,
+    /// Whether the session currently sets `intern_strings`, read when the function is called
+    session_interns_strings: Box<dyn Fn() -> bool + Send + Sync>,
+    /// Interner of the `key` columns while the session sets `intern_strings`
+    session_interner: StringInterner,
+}
+
+impl Debug for UnpivotFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("UnpivotFunction")
+            .field("tables", &self.tables)
+            .field("interner", &self.interner)
+            .finish()
+    }
+}
+
+impl UnpivotFunction {
+    /// Creates the function for the catalogs and default schema of `ctx`
+    pub fn new(ctx: &SessionContext) -> Self {
+        // A weak reference, as the session holds the function
+        let state = ctx.state_weak_ref();
+        Self {
+            tables: SessionTables::new(ctx),
+            interner: None,
+            session_interns_strings: Box::new(move || {
+                state.upgrade().is_some_and(|state| {
+                    ExtraFunctionsConfig::from_config(state.read().config_options())
+                        .is_some_and(|config| config.intern_strings)
+                })
+            }),
+            session_interner: StringInterner::default(),
+        }
+    }
+
+    /// Interns the names of the value columns in the `key` column into `interner`
+    pub fn with_string_interner(mut self, interner: StringInterner) -> Self {
+        self.interner = Some(interner);
+        self
+    }
+}
+
+impl TableFunctionImpl for UnpivotFunction {
+    fn call(&self, args: &[Expr]) -> Result<Arc<dyn TableProvider>> {
+        let [table, value_args @ ..] = args else {
+            return plan_err!("unpivot expects a table and at least one value column");
+        };
+        if value_args.is_empty() {
+            return plan_err!("unpivot expects a table and at least one value column");
+        }
+        let table_ref = table_arg("unpivot", table)?;
+        let value_columns = value_args
+            .iter()
+            .map(|arg| column_arg("unpivot", arg))
+            .collect::<Result<Vec<_>>>()?;
+
+        let table = self.tables.table("unpivot", &table_ref)?;
+        let interner = match &self.interner {
+            Some(interner) => Some(interner),
+            None => (self.session_interns_strings)().then_some(&self.session_interner),
+        };
+        let plan = unpivot_plan(table_ref, table, &value_columns, interner)?;
+        Ok(Arc::new(ViewTable::try_new(plan, None)?))
+    }
+}
+
+/// The plan unpivoting `value_columns` of `table`: the names and values of the value columns of
+/// every row are collected into two lists, which are unnested together. The keys are interned
+/// into `interner` if set.
+fn unpivot_plan(
+    table_ref: TableReference,
+    table: Arc<dyn TableProvider>,
+    value_columns: &[&str],
+    interner: Option<&StringInterner>,
+) -> Result<datafusion::logical_expr::LogicalPlan> {
+    let schema: SchemaRef = table.schema();
+    let mut seen = HashSet::new();
+    let mut value_types = vec![];
+    for name in value_columns {
+        let Ok(field) = schema.field_with_name(name) else {
+            return plan_err!("unpivot can't find value column {name} in table {table_ref}");
+        };
+        if !seen.insert(*name) {
+            return plan_err!("unpivot expects distinct value columns, got {name} twice");
+        }
+        value_types.push(field.data_type().clone());
+    }
+    // Strings and other types "unify" to the other type, failing on every non-numeric string
+    let is_string =
+        |data_type: &DataType| matches!(data_type, DataType::Utf8 | DataType::LargeUtf8 | DataType::Utf8View);
+    let unified = if value_types.iter().all(is_string) || !value_types.iter().any(is_string) {
+        type_union_resolution(&value_types)
+    } else {
+        None
+    };
+    let Some(value_type) = unified else {
+        return plan_err!(
+            "unpivot can't unify the types of the value columns: {}",
+            value_types
+                .iter()
+                .map(DataType::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    };
+
+    let id_columns = schema
+        .fields()
+        .iter()
+        .map(|field| field.name().as_str())
+        .filter(|name| !seen.contains(name))
+        .collect::<Vec<_>>();
+    if let Some(name) = id_columns.iter().find(|name| [KEY_COLUMN, VALUE_COLUMN].contains(name)) {
+        return plan_err!("unpivot can't add its {name} column, table {table_ref} already has a column named {name}");
+    }
+
+    let keys = value_columns.iter().map(|name| lit(*name)).collect();
+    let values = value_columns
+        .iter()
+        .map(|name| cast(ident(*name), value_type.clone()))
+        .collect();
+    let lists = id_columns.iter().map(|name| ident(*name)).chain([
+        make_array(keys).alias(KEY_COLUMN),
+        make_array(values).alias(VALUE_COLUMN),
+    ]);
+    let plan = LogicalPlanBuilder::scan(table_ref, provider_as_source(table), None)?
+        .project(lists)?
+        .unnest_columns_with_options(
+            vec![Column::from_name(KEY_COLUMN), Column::from_name(VALUE_COLUMN)],
+            UnnestOptions::default(),
+        )?
+        .filter(ident(VALUE_COLUMN).is_not_null())?;
+    let plan = match interner {
+        Some(interner) => {
+            let intern = ScalarUDF::from(InternFunction::new_with_interner(interner.clone()));
+            plan.project(id_columns.iter().map(|name| ident(*name)).chain([
+                intern.call(vec![ident(KEY_COLUMN)]).alias(KEY_COLUMN),
+                ident(VALUE_COLUMN),
+            ]))?
+        }
+        None => plan,
+    };
+    plan.build()
+}
@@ -0,0 +1,631 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::any::Any;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use arrow::array::{Array, ArrayRef, Int64Array, StructArray};
+use arrow::datatypes::{DataType, Field, Fields};
+use datafusion::arrow;
+use datafusion::common::cast::{as_int64_array, as_list_array};
+use datafusion::common::{plan_err, Result, ScalarValue};
+use datafusion::logical_expr::function::{AccumulatorArgs, StateFieldsArgs};
+use datafusion::logical_expr::{Accumulator, AggregateUDFImpl, Signature, Volatility};
+
+use crate::common::args::literal_arg;
+use crate::common::emit::CancellationToken;
+use crate::compat::single_row_list;
+use crate::sketches::space_saving::{Counter, SpaceSaving};
+
+make_udaf_expr_and_func!(
+    ValueCountsFunction,
+    value_counts,
+    "Counts the occurrences of every value, optionally ordered by `sort_by`.",
+    value_counts_udaf
+);
+
+make_udaf_expr_and_func!(
+    ApproxTopKFunction,
+    approx_top_k,
+    "Estimates the k most frequent values and their counts, optionally ordered by `sort_by`.",
+    approx_top_k_udaf
+);
+
+/// The order of the `(value, count)` entries returned by [`ValueCountsFunction`] and
+/// [`ApproxTopKFunction`], selected by their optional last argument.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SortBy {
+    /// `'count'`: by descending count, ties by ascending value
+    #[default]
+    CountDesc,
+    /// `'value'`: by ascending value
+    ValueAsc,
+    /// `'insertion'`: by the order the values were first aggregated in, which depends on the
+    /// partitioning of the input
+    Insertion,
+}
+
+impl SortBy {
+    fn from_arg(acc_args: &AccumulatorArgs, index: usize, fn_name: &str) -> Result<Self> {
+        let Some(expr) = acc_args.exprs.get(index) else {
+            return Ok(Self::default());
+        };
+        match literal_arg(expr, fn_name, "sort_by")? {
+            ScalarValue::Utf8(Some(sort_by)) if sort_by.eq_ignore_ascii_case("count") => Ok(Self::CountDesc),
+            ScalarValue::Utf8(Some(sort_by)) if sort_by.eq_ignore_ascii_case("value") => Ok(Self::ValueAsc),
+            ScalarValue::Utf8(Some(sort_by)) if sort_by.eq_ignore_ascii_case("insertion") => Ok(Self::Insertion),
+            sort_by => plan_err!("{fn_name} expects a sort_by of 'count', 'value' or 'insertion', got {sort_by}"),
+        }
+    }
+
+    /// Sorts `entries`, whose value and count are returned by `entry`
+    fn sort<T>(self, entries: &mut [T], entry: impl Fn(&T) -> (&ScalarValue, i64)) {
+        let by_value = |a: &ScalarValue, b: &ScalarValue| a.partial_cmp(b).unwrap_or(Ordering::Equal);
+        match self {
+            SortBy::CountDesc => entries.sort_by(|a, b| {
+                let ((a, a_count), (b, b_count)) = (entry(a), entry(b));
+                b_count.cmp(&a_count).then_with(|| by_value(a, b))
+            }),
+            SortBy::ValueAsc => entries.sort_by(|a, b| by_value(entry(a).0, entry(b).0)),
+            SortBy::Insertion => {}
+        }
+    }
+}
+
+/// Coerces the value argument, which may be a dictionary, to its value type
+fn value_type(arg_type: &DataType) -> DataType {
+    match arg_type {
+        DataType::Dictionary(_, value_type) => value_type.as_ref().clone(),
+        DataType::Null => DataType::Int64,
+        arg_type => arg_type.clone(),
+    }
+}
+
+/// The type of the `(value, count)` entries
+fn entry_fields(value_type: &DataType) -> Fields {
+    Fields::from(vec![
+        Field::new("value", value_type.clone(), true),
+        Field::new("count", DataType::Int64, false),
+    ])
+}
+
+/// The type of the `(value, count, error)` entries of [`ApproxTopKFunction`]
+fn top_k_entry_fields(value_type: &DataType) -> Fields {
+    Fields::from(vec![
+        Field::new("value", value_type.clone(), true),
+        Field::new("count", DataType::Int64, false),
+        Field::new("error", DataType::Int64, false),
+    ])
+}
+
+fn list_of(fields: Fields) -> DataType {
+    DataType::new_list(DataType::Struct(fields), true)
+}
+
+fn state_fields(value_type: &DataType) -> Vec<Field> {
+    vec![
+        Field::new_list("values", Field::new("item", value_type.clone(), true), true),
+        Field::new_list("counts", Field::new("item", DataType::Int64, true), true),
+    ]
+}
+
+/// Collects `values` into an array, checking `cancellation` every chunk
+fn values_array(
+    value_type: &DataType,
+    cancellation: &CancellationToken,
+    values: impl IntoIterator<Item = ScalarValue>,
+) -> Result<ArrayRef> {
+    let values: Vec<ScalarValue> = cancellation.chunked(values).collect::<Result<_>>()?;
+    if values.is_empty() {
+        return Ok(arrow::array::new_empty_array(value_type));
+    }
+    ScalarValue::iter_to_array(values)
+}
+
+/// The `ValueCountsFunction` counts the occurrences of every non-null value, returning a list
+/// of `{value, count}` structs.
+///
+/// - `value_counts(x)` orders the entries by descending count and ties by ascending value;
+///   `value_counts(x, sort_by)` selects another [`SortBy`].
+/// - The result is an empty list if there are no values.
+pub struct ValueCountsFunction {
+    signature: Signature,
+    cancellation: CancellationToken,
+}
+
+impl Debug for ValueCountsFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ValueCountsFunction")
+            .field("signature", &self.signature)
+            .field("cancellation", &self.cancellation)
+            .finish()
+    }
+}
+
+impl Default for ValueCountsFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ValueCountsFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+            cancellation: CancellationToken::default(),
+        }
+    }
+
+    /// Fails the emission of the accumulators once `cancellation` is cancelled, see
+    /// [`crate::common::emit`]
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = cancellation;
+        self
+    }
+}
+
+impl AggregateUDFImpl for ValueCountsFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "value_counts"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        match arg_types {
+            [value] => Ok(vec![value_type(value)]),
+            [value, _] => Ok(vec![value_type(value), DataType::Utf8]),
+            _ => plan_err!("value_counts expects a value and an optional sort_by"),
+        }
+    }
+
+    fn return_type(&self, arg_types: &[DataType]) -> Result<DataType> {
+        Ok(list_of(entry_fields(&arg_types[0])))
+    }
+
+    fn state_fields(&self, args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(state_fields(&args.input_types[0]))
+    }
+
+    fn accumulator(&self, acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        let sort_by = SortBy::from_arg(&acc_args, 1, "value_counts")?;
+        let value_type = crate::compat::arg_type(&acc_args, 0)?;
+        Ok(Box::new(
+            ValueCountsAccumulator::new(value_type, sort_by).with_cancellation(self.cancellation.clone()),
+        ))
+    }
+}
+
+/// The `ApproxTopKFunction` estimates the `k` most frequent non-null values with the
+/// Space-Saving algorithm, returning a list of up to `k` `{value, count, error}` structs.
+///
+/// - Up to `max(10 * k, 100)` counters are kept per group; `count` is an upper bound that
+///   overcounts by at most `error`, itself at most the number of values divided by that
+///   capacity.
+/// - `approx_top_k(x, k)` orders the entries by descending count and ties by ascending value;
+///   `approx_top_k(x, k, sort_by)` selects another [`SortBy`], applied to the top `k`.
+pub struct ApproxTopKFunction {
+    signature: Signature,
+    cancellation: CancellationToken,
+}
+
+impl Debug for ApproxTopKFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ApproxTopKFunction")
+            .field("signature", &self.signature)
+            .field("cancellation", &self.cancellation)
+            .finish()
+    }
+}
+
+impl Default for ApproxTopKFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ApproxTopKFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+            cancellation: CancellationToken::default(),
+        }
+    }
+
+    /// Fails the emission of the accumulators once `cancellation` is cancelled, see
+    /// [`crate::common::emit`]
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = cancellation;
+        self
+    }
+}
+
+impl AggregateUDFImpl for ApproxTopKFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "approx_top_k"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        match arg_types {
+            [value, _] => Ok(vec![value_type(value), DataType::Int64]),
+            [value, _, _] => Ok(vec![value_type(value), DataType::Int64, DataType::Utf8]),
+            _ => plan_err!("approx_top_k expects a value, k and an optional sort_by"),
+        }
+    }
+
+    fn return_type(&self, arg_types: &[DataType]) -> Result<DataType> {
+        Ok(list_of(top_k_entry_fields(&arg_types[0])))
+    }
+
+    fn state_fields(&self, args: StateFieldsArgs) -> Result<Vec<Field>> {
+        let mut fields = state_fields(&args.input_types[0]);
+        fields.push(Field::new_list(
+            "errors",
+            Field::new("item", DataType::Int64, true),
+            true,
+        ));
+        Ok(fields)
+    }
+
+    fn accumulator(&self, acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        let k = match literal_arg(&acc_args.exprs[1], "approx_top_k", "k")? {
+            ScalarValue::Int64(Some(k)) if k > 0 => k as usize,
+            k => return plan_err!("approx_top_k expects a positive k, got {k}"),
+        };
+        let sort_by = SortBy::from_arg(&acc_args, 2, "approx_top_k")?;
+        let value_type = crate::compat::arg_type(&acc_args, 0)?;
+        Ok(Box::new(
+            ApproxTopKAccumulator::new(value_type, sort_by, k).with_cancellation(self.cancellation.clone()),
+        ))
+    }
+}
+
+/// Accumulator of [`ValueCountsFunction`], counting the values of a group in the order they
+/// are first seen.
+#[derive(Debug)]
+pub struct ValueCountsAccumulator {
+    entries: Vec<(ScalarValue, i64)>,
+    index: HashMap<ScalarValue, usize>,
+    value_type: DataType,
+    sort_by: SortBy,
+    cancellation: CancellationToken,
+}
+
+impl ValueCountsAccumulator {
+    pub fn new(value_type: DataType, sort_by: SortBy) -> Self {
+        Self {
+            entries: vec![],
+            index: HashMap::new(),
+            value_type,
+            sort_by,
+            cancellation: CancellationToken::default(),
+        }
+    }
+
+    /// Fails the emission of the state and result once `cancellation` is cancelled
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = cancellation;
+        self
+    }
+
+    fn add(&mut self, value: ScalarValue, count: i64) {
+        match self.index.get(&value) {
+            Some(&i) => self.entries[i].1 += count,
+            None => {
+                self.index.insert(value.clone(), self.entries.len());
+                self.entries.push((value, count));
+            }
+        }
+    }
+
+    fn add_all(&mut self, values: &ArrayRef, counts: Option<&Int64Array>) -> Result<()> {
+        for i in 0..values.len() {
+            if values.is_valid(i) {
+                let count = counts.map_or(1, |counts| counts.value(i));
+                self.add(ScalarValue::try_from_array(values, i)?, count);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Accumulator for ValueCountsAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        self.add_all(&values[0], None)
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        let values_lists = as_list_array(&states[0])?;
+        let counts_lists = as_list_array(&states[1])?;
+        for (values, counts) in values_lists.iter().zip(counts_lists.iter()) {
+            let (Some(values), Some(counts)) = (values, counts) else {
+                continue;
+            };
+            self.add_all(&values, Some(as_int64_array(&counts)?))?;
+        }
+        Ok(())
+    }
+
+    /// The entries in the order they were first seen, so that merging keeps the insertion
+    /// order of every partition
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        let values = values_array(
+            &self.value_type,
+            &self.cancellation,
+            self.entries.iter().map(|(value, _)| value.clone()),
+        )?;
+        let counts = Int64Array::from_iter_values(self.entries.iter().map(|(_, count)| *count));
+        Ok(vec![
+            ScalarValue::List(Arc::new(single_row_list(values))),
+            ScalarValue::List(Arc::new(single_row_list(Arc::new(counts)))),
+        ])
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        let mut entries = self.entries.clone();
+        self.sort_by.sort(&mut entries, |(value, count)| (value, *count));
+
+        let counts = Int64Array::from_iter_values(entries.iter().map(|(_, count)| *count));
+        let values = values_array(
+            &self.value_type,
+            &self.cancellation,
+            entries.into_iter().map(|(value, _)| value),
+        )?;
+        let entries = StructArray::try_new(entry_fields(&self.value_type), vec![values, Arc::new(counts)], None)?;
+        Ok(ScalarValue::List(Arc::new(single_row_list(Arc::new(entries)))))
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self)
+            + self
+                .entries
+                .iter()
+                .map(|(value, _)| 2 * value.size() + std::mem::size_of::<(i64, usize)>())
+                .sum::<usize>()
+    }
+}
+
+/// Accumulator of [`ApproxTopKFunction`], keeping a [`SpaceSaving`] summary of the values of a
+/// group.
+#[derive(Debug)]
+pub struct ApproxTopKAccumulator {
+    summary: SpaceSaving<ScalarValue>,
+    value_type: DataType,
+    sort_by: SortBy,
+    k: usize,
+    cancellation: CancellationToken,
+}
+
+impl ApproxTopKAccumulator {
+    pub fn new(value_type: DataType, sort_by: SortBy, k: usize) -> Self {
+        Self {
+            summary: SpaceSaving::new(Self::capacity(k)),
+            value_type,
+            sort_by,
+            k,
+            cancellation: CancellationToken::default(),
+        }
+    }
+
+    /// Fails the emission of the state and result once `cancellation` is cancelled
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = cancellation;
+        self
+    }
+
+    /// The number of counters kept to estimate the top `k`
+    fn capacity(k: usize) -> usize {
+        k.saturating_mul(10).max(100)
+    }
+
+    /// The value and count of a counter numbered by the order it was first counted in
+    fn entry<'a>((_, counter): &'a (usize, &Counter<ScalarValue>)) -> (&'a ScalarValue, i64) {
+        (&counter.item, counter.count as i64)
+    }
+
+    fn counts_array<'a>(counts: impl IntoIterator<Item = &'a u64>) -> ArrayRef {
+        Arc::new(Int64Array::from_iter_values(
+            counts.into_iter().map(|count| *count as i64),
+        ))
+    }
+}
+
+impl Accumulator for ApproxTopKAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        let values = &values[0];
+        for i in 0..values.len() {
+            if values.is_valid(i) {
+                self.summary.add(ScalarValue::try_from_array(values, i)?, 1);
+            }
+        }
+        Ok(())
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        let values_lists = as_list_array(&states[0])?;
+        let counts_lists = as_list_array(&states[1])?;
+        let errors_lists = as_list_array(&states[2])?;
+        for ((values, counts), errors) in values_lists.iter().zip(counts_lists.iter()).zip(errors_lists.iter()) {
+            let (Some(values), Some(counts), Some(errors)) = (values, counts, errors) else {
+                continue;
+            };
+            let (counts, errors) = (as_int64_array(&counts)?, as_int64_array(&errors)?);
+            let counters = (0..values.len())
+                .map(|i| {
+                    Ok(Counter {
+                        item: ScalarValue::try_from_array(&values, i)?,
+                        count: counts.value(i) as u64,
+                        error: errors.value(i) as u64,
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?;
+            let capacity = Self::capacity(self.k);
+            self.summary.merge(SpaceSaving::from_counters(capacity, counters)?);
+        }
+        Ok(())
+    }
+
+    /// The counters in the order their values were first counted, so that merging keeps the
+    /// insertion order of every partition
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        let values = values_array(
+            &self.value_type,
+            &self.cancellation,
+            self.summary.counters().map(|counter| counter.item.clone()),
+        )?;
+        let counts = Self::counts_array(self.summary.counters().map(|counter| &counter.count));
+        let errors = Self::counts_array(self.summary.counters().map(|counter| &counter.error));
+        Ok(vec![
+            ScalarValue::List(Arc::new(single_row_list(values))),
+            ScalarValue::List(Arc::new(single_row_list(counts))),
+            ScalarValue::List(Arc::new(single_row_list(errors))),
+        ])
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        let mut top: Vec<(usize, &Counter<ScalarValue>)> = self.summary.counters().enumerate().collect();
+        // the top k are always the most frequent, whichever order they are returned in
+        SortBy::CountDesc.sort(&mut top, Self::entry);
+        top.truncate(self.k);
+        if self.sort_by == SortBy::Insertion {
+            top.sort_by_key(|(first_counted, _)| *first_counted);
+        }
+        self.sort_by.sort(&mut top, Self::entry);
+        let top: Vec<&Counter<ScalarValue>> = top.into_iter().map(|(_, counter)| counter).collect();
+
+        let counts = Self::counts_array(top.iter().map(|counter| &counter.count));
+        let errors = Self::counts_array(top.iter().map(|counter| &counter.error));
+        let values = values_array(
+            &self.value_type,
+            &self.cancellation,
+            top.into_iter().map(|counter| counter.item.clone()),
+        )?;
+        let entries = StructArray::try_new(top_k_entry_fields(&self.value_type), vec![values, counts, errors], None)?;
+        Ok(ScalarValue::List(Arc::new(single_row_list(Arc::new(entries)))))
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self)
+            + self.summary.size()
+            + self
+                .summary
+                .counters()
+                .map(|counter| 2 * counter.item.size())
+                .sum::<usize>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::array::StringArray;
+
+    use super::*;
+
+    fn entries(acc: &mut dyn Accumulator) -> Vec<(ScalarValue, i64)> {
+        let ScalarValue::List(list) = acc.evaluate().unwrap() else {
+            unreachable!()
+        };
+        let entries = list.value(0);
+        let entries = entries.as_any().downcast_ref::<StructArray>().unwrap();
+        let counts = as_int64_array(entries.column(1)).unwrap();
+        (0..entries.len())
+            .map(|i| {
+                (
+                    ScalarValue::try_from_array(entries.column(0), i).unwrap(),
+                    counts.value(i),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_sort_by() -> Result<()> {
+        let values: ArrayRef = Arc::new(StringArray::from(vec![
+            Some("b"),
+            Some("c"),
+            None,
+            Some("a"),
+            Some("c"),
+        ]));
+        let utf8 = |value: &str| ScalarValue::Utf8(Some(value.to_string()));
+
+        let mut acc = ValueCountsAccumulator::new(DataType::Utf8, SortBy::CountDesc);
+        acc.update_batch(&[Arc::clone(&values)])?;
+        assert_eq!(entries(&mut acc), vec![(utf8("c"), 2), (utf8("a"), 1), (utf8("b"), 1)]);
+
+        let mut acc = ValueCountsAccumulator::new(DataType::Utf8, SortBy::ValueAsc);
+        acc.update_batch(&[Arc::clone(&values)])?;
+        assert_eq!(entries(&mut acc), vec![(utf8("a"), 1), (utf8("b"), 1), (utf8("c"), 2)]);
+
+        let mut acc = ApproxTopKAccumulator::new(DataType::Utf8, SortBy::Insertion, 2);
+        acc.update_batch(&[values])?;
+        assert_eq!(entries(&mut acc), vec![(utf8("c"), 2), (utf8("a"), 1)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_approx_top_k_bounds_rare_values() -> Result<()> {
+        let mut acc = ApproxTopKAccumulator::new(DataType::Int64, SortBy::CountDesc, 1);
+        // one frequent value among many values seen once
+        let values: Vec<i64> = (0..1000).map(|i| if i % 2 == 0 { -1 } else { i }).collect();
+        acc.update_batch(&[Arc::new(Int64Array::from(values))])?;
+        assert_eq!(acc.summary.len(), 100);
+
+        let top = entries(&mut acc);
+        assert_eq!(top.len(), 1);
+        assert_eq!(top[0].0, ScalarValue::Int64(Some(-1)));
+        assert!(top[0].1 >= 500 && top[0].1 <= 510, "{top:?}");
+        Ok(())
+    }
+
+    #[test]
+    fn test_approx_top_k_merge() -> Result<()> {
+        let batch = |values: Vec<i64>| -> Vec<ArrayRef> { vec![Arc::new(Int64Array::from(values))] };
+        let mut left = ApproxTopKAccumulator::new(DataType::Int64, SortBy::CountDesc, 2);
+        left.update_batch(&batch(vec![1, 1, 2, 3]))?;
+        let mut right = ApproxTopKAccumulator::new(DataType::Int64, SortBy::CountDesc, 2);
+        right.update_batch(&batch(vec![2, 2, 2, 1]))?;
+        let states = right
+            .state()?
+            .iter()
+            .map(|state| state.to_array())
+            .collect::<Result<Vec<_>>>()?;
+        left.merge_batch(&states)?;
+        assert_eq!(
+            entries(&mut left),
+            vec![(ScalarValue::Int64(Some(2)), 4), (ScalarValue::Int64(Some(1)), 3)]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,319 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::any::Any;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use arrow::array::{Array, ArrayRef, BooleanArray, Float64Array};
+use datafusion::arrow;
+use datafusion::arrow::datatypes::{DataType, Field};
+use datafusion::common::cast::as_float64_array;
+use datafusion::common::{Result, ScalarValue};
+use datafusion::logical_expr::function::{AccumulatorArgs, StateFieldsArgs};
+use datafusion::logical_expr::{Accumulator, AggregateUDFImpl, Signature, Volatility};
+
+use crate::common::coercion::NumericCoercion;
+use crate::common::nulls::all_null_or_filtered;
+use crate::compat::{EmitTo, GroupsAccumulator};
+
+make_udaf_expr_and_func!(
+    WeightedAvgFunction,
+    weighted_avg,
+    value weight,
+    "Calculates the mean of the values weighted by the weights.",
+    weighted_avg_udaf
+);
+
+/// The `WeightedAvgFunction` calculates `sum(value * weight) / sum(weight)`.
+///
+/// - Rows where the value or the weight is NULL are skipped, as Spark does for its
+///   two-argument aggregates such as `covar_pop`.
+/// - The result is NULL if there are no such rows or the weights sum to zero.
+pub struct WeightedAvgFunction {
+    signature: Signature,
+}
+
+impl Debug for WeightedAvgFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WeightedAvgFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for WeightedAvgFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WeightedAvgFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+        }
+    }
+}
+
+impl AggregateUDFImpl for WeightedAvgFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "weighted_avg"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        NumericCoercion::Float64.coerce_all(self.name(), &["value", "weight"], arg_types)
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Float64)
+    }
+
+    fn state_fields(&self, _args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(vec![
+            Field::new("sum_wx", DataType::Float64, true),
+            Field::new("sum_w", DataType::Float64, true),
+        ])
+    }
+
+    fn accumulator(&self, _acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        Ok(Box::new(WeightedAvgAccumulator::new()))
+    }
+
+    fn groups_accumulator_supported(&self, _args: AccumulatorArgs) -> bool {
+        true
+    }
+
+    fn create_groups_accumulator(&self, _args: AccumulatorArgs) -> Result<Box<dyn GroupsAccumulator>> {
+        Ok(Box::new(WeightedAvgGroupsAccumulator::new()))
+    }
+}
+
+fn weighted_mean(sum_wx: f64, sum_w: f64) -> Option<f64> {
+    (sum_w != 0.0).then(|| sum_wx / sum_w)
+}
+
+/// Calls `f` with the row index, value and weight of every row where both are non-null and
+/// selected by `opt_filter`.
+fn for_each_weighted(
+    values: &Float64Array,
+    weights: &Float64Array,
+    opt_filter: Option<&BooleanArray>,
+    mut f: impl FnMut(usize, f64, f64),
+) {
+    for i in 0..values.len() {
+        let selected = opt_filter.map_or(true, |filter| filter.is_valid(i) && filter.value(i));
+        if selected && values.is_valid(i) && weights.is_valid(i) {
+            f(i, values.value(i), weights.value(i));
+        }
+    }
+}
+
+/// Accumulator for the weighted mean.
+#[derive(Debug, Default)]
+pub struct WeightedAvgAccumulator {
+    sum_wx: f64,
+    sum_w: f64,
+}
+
+impl WeightedAvgAccumulator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Accumulator for WeightedAvgAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        if all_null_or_filtered(&values[0], None) || all_null_or_filtered(&values[1], None) {
+            return Ok(());
+        }
+        let (xs, ws) = (as_float64_array(&values[0])?, as_float64_array(&values[1])?);
+        for_each_weighted(xs, ws, None, |_, x, w| {
+            self.sum_wx += x * w;
+            self.sum_w += w;
+        });
+        Ok(())
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        let (sum_wxs, sum_ws) = (as_float64_array(&states[0])?, as_float64_array(&states[1])?);
+        for_each_weighted(sum_wxs, sum_ws, None, |_, sum_wx, sum_w| {
+            self.sum_wx += sum_wx;
+            self.sum_w += sum_w;
+        });
+        Ok(())
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        Ok(ScalarValue::Float64(weighted_mean(self.sum_wx, self.sum_w)))
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self)
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        Ok(vec![ScalarValue::from(self.sum_wx), ScalarValue::from(self.sum_w)])
+    }
+}
+
+/// [`GroupsAccumulator`] for the weighted mean, which keeps the sums of every group in
+/// contiguous vectors instead of allocating an [`Accumulator`] per group.
+#[derive(Debug, Default)]
+pub struct WeightedAvgGroupsAccumulator {
+    sum_wx: Vec<f64>,
+    sum_w: Vec<f64>,
+}
+
+impl WeightedAvgGroupsAccumulator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn add(
+        &mut self,
+        xs: &Float64Array,
+        ws: &Float64Array,
+        group_indices: &[usize],
+        opt_filter: Option<&BooleanArray>,
+        total_num_groups: usize,
+        weigh: impl Fn(f64, f64) -> f64,
+    ) {
+        self.sum_wx.resize(total_num_groups, 0.0);
+        self.sum_w.resize(total_num_groups, 0.0);
+        if all_null_or_filtered(xs, opt_filter) || all_null_or_filtered(ws, opt_filter) {
+            return;
+        }
+        for_each_weighted(xs, ws, opt_filter, |i, x, w| {
+            let group_index = group_indices[i];
+            self.sum_wx[group_index] += weigh(x, w);
+            self.sum_w[group_index] += w;
+        });
+    }
+}
+
+impl GroupsAccumulator for WeightedAvgGroupsAccumulator {
+    fn update_batch(
+        &mut self,
+        values: &[ArrayRef],
+        group_indices: &[usize],
+        opt_filter: Option<&BooleanArray>,
+        total_num_groups: usize,
+    ) -> Result<()> {
+        let (xs, ws) = (as_float64_array(&values[0])?, as_float64_array(&values[1])?);
+        self.add(xs, ws, group_indices, opt_filter, total_num_groups, |x, w| x * w);
+        Ok(())
+    }
+
+    fn merge_batch(
+        &mut self,
+        values: &[ArrayRef],
+        group_indices: &[usize],
+        opt_filter: Option<&BooleanArray>,
+        total_num_groups: usize,
+    ) -> Result<()> {
+        let (sum_wxs, sum_ws) = (as_float64_array(&values[0])?, as_float64_array(&values[1])?);
+        self.add(
+            sum_wxs,
+            sum_ws,
+            group_indices,
+            opt_filter,
+            total_num_groups,
+            |sum_wx, _| sum_wx,
+        );
+        Ok(())
+    }
+
+    fn evaluate(&mut self, emit_to: EmitTo) -> Result<ArrayRef> {
+        let sum_wx = emit_to.take_needed(&mut self.sum_wx);
+        let sum_w = emit_to.take_needed(&mut self.sum_w);
+        let averages: Float64Array = sum_wx
+            .into_iter()
+            .zip(sum_w)
+            .map(|(sum_wx, sum_w)| weighted_mean(sum_wx, sum_w))
+            .collect();
+        Ok(Arc::new(averages))
+    }
+
+    fn state(&mut self, emit_to: EmitTo) -> Result<Vec<ArrayRef>> {
+        let sum_wx = emit_to.take_needed(&mut self.sum_wx);
+        let sum_w = emit_to.take_needed(&mut self.sum_w);
+        Ok(vec![
+            Arc::new(Float64Array::from(sum_wx)),
+            Arc::new(Float64Array::from(sum_w)),
+        ])
+    }
+
+    fn size(&self) -> usize {
+        (self.sum_wx.capacity() + self.sum_w.capacity()) * std::mem::size_of::<f64>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_weighted_avg_groups_accumulator() -> Result<()> {
+        let values: ArrayRef = Arc::new(Float64Array::from(vec![
+            Some(1.0),
+            Some(3.0),
+            None,
+            Some(5.0),
+            Some(7.0),
+        ]));
+        let weights: ArrayRef = Arc::new(Float64Array::from(vec![
+            Some(1.0),
+            Some(3.0),
+            Some(9.0),
+            None,
+            Some(1.0),
+        ]));
+        let filter = BooleanArray::from(vec![true, true, true, true, false]);
+
+        let mut acc = WeightedAvgGroupsAccumulator::new();
+        acc.update_batch(&[values, weights], &[0, 0, 1, 1, 2], Some(&filter), 3)?;
+
+        // merging the state into a new accumulator keeps the sums of each group
+        let state = acc.state(EmitTo::All)?;
+        let mut merged = WeightedAvgGroupsAccumulator::new();
+        merged.merge_batch(&state, &[0, 1, 2], None, 3)?;
+
+        let expected: ArrayRef = Arc::new(Float64Array::from(vec![Some(2.5), None, None]));
+        assert_eq!(&merged.evaluate(EmitTo::All)?, &expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_weighted_avg_zero_weights() -> Result<()> {
+        let mut acc = WeightedAvgAccumulator::new();
+        acc.update_batch(&[
+            Arc::new(Float64Array::from(vec![1.0, 2.0])),
+            Arc::new(Float64Array::from(vec![1.0, -1.0])),
+        ])?;
+        assert_eq!(acc.evaluate()?, ScalarValue::Float64(None));
+        Ok(())
+    }
+}
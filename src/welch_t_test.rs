@@ -0,0 +1,244 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+use std::any::Any;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use arrow::array::{new_null_array, Array, ArrayRef, Float64Array, StructArray};
+use arrow::datatypes::{DataType, Field, Fields};
+use datafusion::arrow;
+use datafusion::common::cast::{as_boolean_array, as_float64_array};
+use datafusion::common::{plan_err, Result, ScalarValue};
+use datafusion::logical_expr::function::{AccumulatorArgs, StateFieldsArgs};
+use datafusion::logical_expr::{Accumulator, AggregateUDFImpl, Signature, Volatility};
+
+use crate::common::moments::MomentsSketch;
+use crate::common::student_t::two_sided_p_value;
+
+make_udaf_expr_and_func!(
+    WelchTTestFunction,
+    welch_t_test,
+    value group_flag,
+    "Compares the means of the values with and without group_flag with Welch's t-test.",
+    welch_t_test_udaf
+);
+
+fn result_fields() -> Fields {
+    Fields::from(vec![
+        Field::new("t_stat", DataType::Float64, false),
+        Field::new("p_value", DataType::Float64, false),
+        Field::new("dof", DataType::Float64, false),
+    ])
+}
+
+/// The `WelchTTestFunction` tests whether two groups of values have the same mean,
+/// `welch_t_test(value, group_flag)`, with Welch's unequal variances t-test, e.g. the metric of
+/// the treatment and the control of an A/B test.
+///
+/// - The values where `group_flag` is true are compared to those where it is false. Returns a
+///   `Struct{t_stat, p_value, dof}`: the t statistic, positive if the mean of the true group is
+///   the larger, the two-sided p-value, and the Welch–Satterthwaite degrees of freedom.
+/// - The groups are accumulated as for `variance`, see [`MomentsSketch`], so the test runs in
+///   constant memory.
+/// - Rows with a NULL value or flag are ignored. The result is NULL unless both groups have at
+///   least 2 values and one of them varies.
+pub struct WelchTTestFunction {
+    signature: Signature,
+}
+
+impl Debug for WelchTTestFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WelchTTestFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for WelchTTestFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WelchTTestFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+        }
+    }
+}
+
+impl AggregateUDFImpl for WelchTTestFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "welch_t_test"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        let [value_type, flag_type] = arg_types else {
+            return plan_err!(
+                "welch_t_test expects 2 arguments (value, group_flag), got {}",
+                arg_types.len()
+            );
+        };
+        if !value_type.is_numeric() && !value_type.is_null() {
+            return plan_err!("welch_t_test expects a numeric value, got {value_type}");
+        }
+        if !matches!(flag_type, DataType::Boolean | DataType::Null) {
+            return plan_err!("welch_t_test expects a boolean group_flag, got {flag_type}");
+        }
+        Ok(vec![DataType::Float64, DataType::Boolean])
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Struct(result_fields()))
+    }
+
+    fn state_fields(&self, _args: StateFieldsArgs) -> Result<Vec<Field>> {
+        // the moments of the false group, then of the true group
+        Ok(["false", "true"]
+            .into_iter()
+            .flat_map(|group| {
+                MomentsSketch::state_fields()
+                    .into_iter()
+                    .map(move |field| field.clone().with_name(format!("{group}_{}", field.name())))
+            })
+            .collect())
+    }
+
+    fn accumulator(&self, _acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        Ok(Box::<WelchTTestAccumulator>::default())
+    }
+}
+
+/// Accumulator for [`WelchTTestFunction`], with the moments of the values of either flag
+#[derive(Debug, Default)]
+pub struct WelchTTestAccumulator {
+    groups: [MomentsSketch; 2],
+}
+
+impl WelchTTestAccumulator {
+    /// The t statistic, p-value and degrees of freedom of the test
+    fn test(&self) -> Option<[f64; 3]> {
+        let [control, treatment] = &self.groups;
+        // the squared standard errors of the means
+        let control_error = control.variance_sample()? / control.count() as f64;
+        let treatment_error = treatment.variance_sample()? / treatment.count() as f64;
+        let error = control_error + treatment_error;
+        if error <= 0.0 {
+            return None;
+        }
+        let t = (treatment.mean()? - control.mean()?) / error.sqrt();
+        let dof = error * error
+            / (control_error * control_error / (control.count() - 1) as f64
+                + treatment_error * treatment_error / (treatment.count() - 1) as f64);
+        Some([t, two_sided_p_value(t, dof), dof])
+    }
+}
+
+impl Accumulator for WelchTTestAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        let (values, flags) = (as_float64_array(&values[0])?, as_boolean_array(&values[1])?);
+        for (value, flag) in values.iter().zip(flags.iter()) {
+            if let (Some(value), Some(flag)) = (value, flag) {
+                self.groups[flag as usize].update(value);
+            }
+        }
+        Ok(())
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        let (control, treatment) = states.split_at(states.len() / 2);
+        self.groups[0].merge_state(control)?;
+        self.groups[1].merge_state(treatment)
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        Ok(self.groups.iter().flat_map(MomentsSketch::state).collect())
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        let Some(test) = self.test() else {
+            return Ok(ScalarValue::Struct(Arc::new(StructArray::from(
+                new_null_array(&DataType::Struct(result_fields()), 1).to_data(),
+            ))));
+        };
+        let columns = test
+            .into_iter()
+            .map(|value| Arc::new(Float64Array::from(vec![value])) as ArrayRef)
+            .collect();
+        Ok(ScalarValue::Struct(Arc::new(StructArray::try_new(
+            result_fields(),
+            columns,
+            None,
+        )?)))
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::array::BooleanArray;
+
+    use super::*;
+
+    #[test]
+    fn test_welch_t_test() -> Result<()> {
+        let mut acc = WelchTTestAccumulator::default();
+        acc.update_batch(&[
+            Arc::new(Float64Array::from(vec![
+                Some(1.0),
+                Some(2.0),
+                Some(3.0),
+                Some(4.0),
+                Some(4.0),
+                Some(6.0),
+                Some(8.0),
+                None,
+            ])),
+            Arc::new(BooleanArray::from(vec![
+                Some(false),
+                Some(false),
+                Some(false),
+                None,
+                Some(true),
+                Some(true),
+                Some(true),
+                Some(true),
+            ])),
+        ])?;
+        // means 2 and 6, variances 1 and 4: t = 4 / sqrt(5 / 3), dof = (5 / 3)² / (1 / 18 + 16 / 18)
+        let [t, p, dof] = acc.test().unwrap();
+        assert!((t - 4.0 / (5.0f64 / 3.0).sqrt()).abs() < 1e-12);
+        assert!((dof - 50.0 / 17.0).abs() < 1e-12);
+        assert!((p - 0.054_786_766_041_045).abs() < 1e-10);
+
+        acc.groups[1] = MomentsSketch::new();
+        assert_eq!(acc.test(), None);
+        Ok(())
+    }
+}
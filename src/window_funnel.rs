@@ -0,0 +1,327 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+use std::any::Any;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use arrow::array::{Array, ArrayRef, AsArray, Int64Array, UInt8Array};
+use arrow::compute::cast;
+use arrow::datatypes::{DataType, Field, Int64Type, UInt8Type};
+use datafusion::arrow;
+use datafusion::common::cast::{as_boolean_array, as_list_array};
+use datafusion::common::{exec_err, plan_err, Result, ScalarValue};
+use datafusion::logical_expr::function::{AccumulatorArgs, StateFieldsArgs};
+use datafusion::logical_expr::{Accumulator, AggregateUDFImpl, Signature, Volatility};
+
+use crate::common::args::literal_arg;
+use crate::common::decay::{coerce_ts, duration_arg, seconds_per_unit};
+use crate::compat::{arg_type, single_row_list};
+
+make_udaf_expr_and_func!(
+    WindowFunnelFunction,
+    window_funnel,
+    "Returns the deepest step of a funnel of conditions reached in order within a window of ts.",
+    window_funnel_udaf
+);
+
+fn is_mode_type(data_type: &DataType) -> bool {
+    matches!(data_type, DataType::Utf8 | DataType::LargeUtf8 | DataType::Utf8View)
+}
+
+/// The modes of [`WindowFunnelFunction`], named as in ClickHouse's `windowFunnel`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FunnelMode {
+    /// A condition met again for a chain interrupts the funnel
+    pub strict_deduplication: bool,
+    /// A row meeting no condition or a step out of order interrupts the funnel
+    pub strict_order: bool,
+    /// Every step must be strictly after the previous one
+    pub strict_increase: bool,
+}
+
+impl FunnelMode {
+    fn set(&mut self, mode: &str) -> Result<()> {
+        match mode {
+            "strict_deduplication" => self.strict_deduplication = true,
+            "strict_order" => self.strict_order = true,
+            "strict_increase" => self.strict_increase = true,
+            _ => {
+                return plan_err!(
+                    "window_funnel expects a mode of 'strict_deduplication', 'strict_order' or 'strict_increase', \
+                     got '{mode}'"
+                )
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The `WindowFunnelFunction` computes how far a group went through a funnel of steps,
+/// `window_funnel(window[, mode, ...], ts, cond1, cond2, ...)`, like ClickHouse's `windowFunnel`.
+///
+/// - A chain starts at every row meeting `cond1`, and reaches step `n` at a row meeting `condn`
+///   after the chain reached step `n - 1`, within `window` of its start. Returns the deepest step
+///   reached by a chain as an `Int64`, 0 if no row meets `cond1`.
+/// - The rows are ordered by `ts`, a timestamp, a date or an integer. `window` is a literal
+///   interval without months or a number of seconds, or a number in the unit of `ts` for
+///   integers.
+/// - The literal `mode`s are `'strict_deduplication'`, `'strict_order'` and
+///   `'strict_increase'`, see [`FunnelMode`].
+/// - The rows meeting a condition are buffered until the result is emitted, as those of
+///   different partitions interleave in time.
+/// - Rows with a NULL `ts` are ignored, and NULL conditions aren't met.
+pub struct WindowFunnelFunction {
+    signature: Signature,
+}
+
+impl Debug for WindowFunnelFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WindowFunnelFunction")
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl Default for WindowFunnelFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WindowFunnelFunction {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::user_defined(Volatility::Immutable),
+        }
+    }
+}
+
+impl AggregateUDFImpl for WindowFunnelFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "window_funnel"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        let modes = arg_types.iter().skip(1).take_while(|t| is_mode_type(t)).count();
+        let (Some(window_type), Some((ts_type, conditions))) = (
+            arg_types.first(),
+            arg_types.get(modes + 1..).and_then(|rest| rest.split_first()),
+        ) else {
+            return plan_err!(
+                "window_funnel expects a window, a ts and conditions, got {} arguments",
+                arg_types.len()
+            );
+        };
+        if conditions.is_empty() || conditions.len() > u8::MAX as usize {
+            return plan_err!(
+                "window_funnel expects between 1 and {} conditions, got {}",
+                u8::MAX,
+                conditions.len()
+            );
+        }
+        if let Some(condition_type) = conditions
+            .iter()
+            .find(|t| !matches!(t, DataType::Boolean | DataType::Null))
+        {
+            return plan_err!("window_funnel expects boolean conditions, got {condition_type}");
+        }
+        let mut coerced = vec![window_type.clone()];
+        coerced.extend(std::iter::repeat(DataType::Utf8).take(modes));
+        coerced.push(coerce_ts(self.name(), ts_type)?);
+        coerced.extend(std::iter::repeat(DataType::Boolean).take(conditions.len()));
+        Ok(coerced)
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Int64)
+    }
+
+    fn state_fields(&self, _args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(vec![
+            Field::new_list("times", Field::new("item", DataType::Int64, true), true),
+            Field::new_list("steps", Field::new("item", DataType::UInt8, true), true),
+        ])
+    }
+
+    fn accumulator(&self, acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        let mut mode = FunnelMode::default();
+        let mut ts_index = 1;
+        while is_mode_type(&arg_type(&acc_args, ts_index)?) {
+            match literal_arg(&acc_args.exprs[ts_index], self.name(), "mode")? {
+                ScalarValue::Utf8(Some(name)) => mode.set(&name)?,
+                _ => return plan_err!("window_funnel expects a non-null mode"),
+            }
+            ts_index += 1;
+        }
+        let time_scale = seconds_per_unit(&arg_type(&acc_args, ts_index)?);
+        let window = duration_arg(&acc_args.exprs[0], self.name(), "window", time_scale.is_some())?;
+        Ok(Box::new(WindowFunnelAccumulator {
+            mode,
+            ts_index,
+            steps: acc_args.exprs.len() - ts_index - 1,
+            window: window / time_scale.unwrap_or(1.0),
+            events: vec![],
+        }))
+    }
+}
+
+/// The deepest step reached by the `events`, sorted by time then step, of a funnel of `steps`
+/// steps within `window`, following `getEventLevel` in ClickHouse's
+/// `AggregateFunctionWindowFunnel.h`
+fn funnel_level(events: &[(i64, u8)], steps: usize, window: f64, mode: FunnelMode) -> usize {
+    // the start and the latest time of the chain that reached each step
+    let mut reached: Vec<Option<(i64, i64)>> = vec![None; steps];
+    for &(time, step) in events {
+        let step = step as usize;
+        // steps are reached in order, so the interrupted funnel reached the steps before `step`
+        match step {
+            0 if reached[0].is_some() => break,
+            0 => {}
+            1 => reached[0] = Some((time, time)),
+            _ if mode.strict_deduplication && reached[step - 1].is_some() => break,
+            _ if mode.strict_order && reached[0].is_some() && reached[step - 2].is_none() => break,
+            _ => {
+                let Some((start, previous)) = reached[step - 2] else {
+                    continue;
+                };
+                if (time - start) as f64 <= window && (!mode.strict_increase || previous < time) {
+                    reached[step - 1] = Some((start, time));
+                    if step == steps {
+                        return steps;
+                    }
+                }
+            }
+        }
+    }
+    reached.iter().take_while(|step| step.is_some()).count()
+}
+
+/// Accumulator for [`WindowFunnelFunction`], buffering the times and steps of the rows, with a
+/// step of 0 for the rows meeting no condition in [`FunnelMode::strict_order`]
+#[derive(Debug)]
+pub struct WindowFunnelAccumulator {
+    mode: FunnelMode,
+    ts_index: usize,
+    steps: usize,
+    /// The window in the unit of the times
+    window: f64,
+    events: Vec<(i64, u8)>,
+}
+
+impl Accumulator for WindowFunnelAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        let times = cast(&values[self.ts_index], &DataType::Int64)?;
+        let times = times.as_primitive::<Int64Type>();
+        let conditions = values[self.ts_index + 1..]
+            .iter()
+            .map(|condition| as_boolean_array(condition))
+            .collect::<Result<Vec<_>>>()?;
+        for (row, time) in times.iter().enumerate() {
+            let Some(time) = time else {
+                continue;
+            };
+            let len = self.events.len();
+            for (step, condition) in conditions.iter().enumerate() {
+                if condition.is_valid(row) && condition.value(row) {
+                    self.events.push((time, step as u8 + 1));
+                }
+            }
+            if self.mode.strict_order && self.events.len() == len {
+                self.events.push((time, 0));
+            }
+        }
+        Ok(())
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        let (times, steps) = (as_list_array(&states[0])?, as_list_array(&states[1])?);
+        for (times, steps) in times.iter().zip(steps.iter()) {
+            let (Some(times), Some(steps)) = (times, steps) else {
+                continue;
+            };
+            if times.len() != steps.len() {
+                return exec_err!(
+                    "window_funnel state has {} times but {} steps",
+                    times.len(),
+                    steps.len()
+                );
+            }
+            let times = times.as_primitive::<Int64Type>().values().iter();
+            let steps = steps.as_primitive::<UInt8Type>().values().iter();
+            self.events.extend(times.copied().zip(steps.copied()));
+        }
+        Ok(())
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        let (times, steps): (Vec<i64>, Vec<u8>) = std::mem::take(&mut self.events).into_iter().unzip();
+        Ok(vec![
+            ScalarValue::List(Arc::new(single_row_list(Arc::new(Int64Array::from(times))))),
+            ScalarValue::List(Arc::new(single_row_list(Arc::new(UInt8Array::from(steps))))),
+        ])
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        self.events.sort_unstable();
+        let level = funnel_level(&self.events, self.steps, self.window, self.mode);
+        Ok(ScalarValue::Int64(Some(level as i64)))
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self) + self.events.capacity() * std::mem::size_of::<(i64, u8)>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_funnel_modes() {
+        let level = |events: &[(i64, u8)], mode: FunnelMode| funnel_level(events, 3, 10.0, mode);
+        let strict = |strict_deduplication, strict_order, strict_increase| FunnelMode {
+            strict_deduplication,
+            strict_order,
+            strict_increase,
+        };
+        // the chain started at 0 times out before step 3, the one restarted at 5 completes
+        let events = [(0, 1), (2, 2), (5, 1), (12, 2), (14, 3)];
+        assert_eq!(level(&events, FunnelMode::default()), 3);
+        assert_eq!(level(&events[..4], FunnelMode::default()), 2);
+        assert_eq!(level(&[(0, 2), (1, 3)], FunnelMode::default()), 0);
+
+        let repeated = [(0, 1), (1, 2), (2, 2), (3, 3)];
+        assert_eq!(level(&repeated, FunnelMode::default()), 3);
+        assert_eq!(level(&repeated, strict(true, false, false)), 2);
+
+        let interrupted = [(0, 1), (1, 0), (2, 2)];
+        assert_eq!(level(&interrupted, strict(false, true, false)), 1);
+        assert_eq!(level(&[(0, 1), (1, 3), (2, 2)], strict(false, true, false)), 1);
+
+        let simultaneous = [(0, 1), (0, 2), (1, 3)];
+        assert_eq!(level(&simultaneous, FunnelMode::default()), 3);
+        assert_eq!(level(&simultaneous, strict(false, false, true)), 1);
+    }
+}
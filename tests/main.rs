@@ -15,8 +15,31 @@
 // specific language governing permissions and limitations
 // under the License.
 
+use std::sync::Arc;
+
+use arrow::array::{
+    ArrayRef, BooleanArray, Float64Array, Int32Builder, Int64Array, ListArray, MapBuilder, RecordBatch, StringArray,
+    StringBuilder, TimestampSecondArray,
+};
+use arrow::datatypes::Int32Type;
+use arrow::util::pretty::pretty_format_batches;
+use datafusion::logical_expr::AggregateUDF;
+use datafusion::prelude::{SessionConfig, SessionContext};
+use datafusion_functions_extra::array_agg_ext::ArrayAggExtFunction;
+use datafusion_functions_extra::common::emit::CancellationToken;
+use datafusion_functions_extra::common::output_width::OutputWidth;
+use datafusion_functions_extra::config::ExtraFunctionsConfig;
+use datafusion_functions_extra::harmonic_mean::{harmonic_mean_udaf, HarmonicMeanFunction, ZeroValuePolicy};
+use datafusion_functions_extra::max_min_by::{MaxByFunction, MinByFunction, NullKeyPolicy};
+use datafusion_functions_extra::mode::{DictionaryOutput, ModeFunction};
+use datafusion_functions_extra::package::{FunctionPackage, RegistrationOptions};
+use datafusion_functions_extra::product::{OverflowPolicy, ProductFunction, ProductMethod};
+use datafusion_functions_extra::string_agg_ext::StringAggExtFunction;
+use datafusion_functions_extra::{register_all_extra_functions, register_extra_functions_with_options};
+
 use crate::utils::TestExecution;
 
+mod plan_splits;
 mod utils;
 
 static TEST_TABLE: &str = r#"
@@ -116,6 +139,90 @@ async fn test_mode_time64() {
     "###);
 }
 
+#[tokio::test]
+async fn test_mode_dictionary() {
+    let sql = "SELECT mode(dict_col) AS mode, arrow_typeof(mode(dict_col)) AS mode_type \
+               FROM (SELECT arrow_cast(utf8_col, 'Dictionary(Int32, Utf8)') AS dict_col FROM test_table)";
+
+    let mut execution = TestExecution::new().await.unwrap().with_setup(TEST_TABLE).await;
+    let actual = execution.run_and_format(sql).await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +-------+-------------------------+
+        - "| mode  | mode_type               |"
+        - +-------+-------------------------+
+        - "| apple | Dictionary(Int32, Utf8) |"
+        - +-------+-------------------------+
+    "###);
+
+    let mut execution = TestExecution::new()
+        .await
+        .unwrap()
+        .with_setup(TEST_TABLE)
+        .await
+        .with_udaf(AggregateUDF::from(ModeFunction::new_with_dictionary_output(
+            DictionaryOutput::Unpack,
+        )));
+    let actual = execution.run_and_format(sql).await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +-------+-----------+
+        - "| mode  | mode_type |"
+        - +-------+-----------+
+        - "| apple | Utf8      |"
+        - +-------+-----------+
+    "###);
+
+    let mut execution = TestExecution::new()
+        .await
+        .unwrap()
+        .with_setup(TEST_TABLE)
+        .await
+        .with_udaf(AggregateUDF::from(ModeFunction::new_with_dictionary_output(
+            DictionaryOutput::StringView,
+        )));
+    let actual = execution.run_and_format(sql).await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +-------+-----------+
+        - "| mode  | mode_type |"
+        - +-------+-----------+
+        - "| apple | Utf8View  |"
+        - +-------+-----------+
+    "###);
+}
+
+#[tokio::test]
+async fn test_mode_merge() {
+    let partition = |values: Vec<i64>, strings: Vec<&str>| {
+        RecordBatch::try_from_iter(vec![
+            ("value", Arc::new(Int64Array::from(values)) as ArrayRef),
+            ("string", Arc::new(StringArray::from(strings)) as ArrayRef),
+        ])
+        .unwrap()
+    };
+
+    let mut execution = TestExecution::new().await.unwrap().with_partitioned_table(
+        "tab",
+        vec![
+            partition(vec![0, 1], vec!["a", "b"]),
+            partition(vec![2, 1], vec!["b", "c"]),
+            partition(vec![3, 1], vec!["b", "a"]),
+        ],
+    );
+    let actual = execution
+        .run_and_format("SELECT mode(value), mode(string) FROM tab")
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +-----------------+------------------+
+        - "| mode(tab.value) | mode(tab.string) |"
+        - +-----------------+------------------+
+        - "| 1               | b                |"
+        - +-----------------+------------------+
+    "###);
+}
+
 #[tokio::test]
 async fn test_max_by_and_min_by() {
     let mut execution = TestExecution::new().await.unwrap();
@@ -172,7 +279,7 @@ async fn test_max_by_and_min_by() {
     - +---------------------------------------------+
     "###);
 
-    // Test max_by with null values
+    // Test max_by with null values, NULL keys are ignored by default
     let actual = execution
         .run_and_format("SELECT max_by(x, y) FROM VALUES (1, 10), (2, null), (3, 15), (null, 8) as tab(x, y);")
         .await;
@@ -181,11 +288,11 @@ async fn test_max_by_and_min_by() {
     - +---------------------+
     - "| max_by(tab.x,tab.y) |"
     - +---------------------+
-    - "| 2                   |"
+    - "| 3                   |"
     - +---------------------+
     "###);
 
-    // Test min_by with null values
+    // Test min_by with null values, NULL keys are ignored by default
     let actual = execution
         .run_and_format("SELECT min_by(x, y) FROM VALUES (1, 10), (2, null), (3, 15), (null, 8) as tab(x, y);")
         .await;
@@ -194,7 +301,7 @@ async fn test_max_by_and_min_by() {
     - +---------------------+
     - "| min_by(tab.x,tab.y) |"
     - +---------------------+
-    - "| 2                   |"
+    - "|                     |"
     - +---------------------+
     "###);
 
@@ -251,6 +358,96 @@ async fn test_max_by_and_min_by() {
     "###);
 }
 
+/// Splits `(x, y)` rows across partitions so that the NULL key and the extreme keys land in
+/// different partial aggregates.
+fn null_key_partitions() -> Vec<RecordBatch> {
+    let partition = |x: Vec<Option<i64>>, y: Vec<Option<i64>>| {
+        RecordBatch::try_from_iter(vec![
+            ("x", Arc::new(Int64Array::from(x)) as ArrayRef),
+            ("y", Arc::new(Int64Array::from(y)) as ArrayRef),
+        ])
+        .unwrap()
+    };
+    vec![
+        partition(vec![Some(1), Some(2)], vec![Some(10), None]),
+        partition(vec![Some(3)], vec![Some(15)]),
+        partition(vec![Some(4), Some(5)], vec![Some(8), Some(20)]),
+    ]
+}
+
+#[tokio::test]
+async fn test_max_by_and_min_by_null_key_policy() {
+    let sql = "SELECT max_by(x, y), min_by(x, y) FROM tab";
+
+    let mut execution = TestExecution::new()
+        .await
+        .unwrap()
+        .with_partitioned_table("tab", null_key_partitions());
+    let actual = execution.run_and_format(sql).await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +---------------------+---------------------+
+        - "| max_by(tab.x,tab.y) | min_by(tab.x,tab.y) |"
+        - +---------------------+---------------------+
+        - "| 5                   | 4                   |"
+        - +---------------------+---------------------+
+    "###);
+
+    let mut execution = TestExecution::new()
+        .await
+        .unwrap()
+        .with_udaf(AggregateUDF::from(MaxByFunction::new_with_null_key_policy(
+            NullKeyPolicy::NullsLast,
+        )))
+        .with_udaf(AggregateUDF::from(MinByFunction::new_with_null_key_policy(
+            NullKeyPolicy::NullsLast,
+        )))
+        .with_partitioned_table("tab", null_key_partitions());
+    let actual = execution.run_and_format(sql).await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +---------------------+---------------------+
+        - "| max_by(tab.x,tab.y) | min_by(tab.x,tab.y) |"
+        - +---------------------+---------------------+
+        - "| 2                   | 4                   |"
+        - +---------------------+---------------------+
+    "###);
+
+    let mut execution = TestExecution::new()
+        .await
+        .unwrap()
+        .with_udaf(AggregateUDF::from(MaxByFunction::new_with_null_key_policy(
+            NullKeyPolicy::NullsFirst,
+        )))
+        .with_udaf(AggregateUDF::from(MinByFunction::new_with_null_key_policy(
+            NullKeyPolicy::NullsFirst,
+        )))
+        .with_partitioned_table("tab", vec![null_key_partitions().remove(0)]);
+    let actual = execution.run_and_format(sql).await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +---------------------+---------------------+
+        - "| max_by(tab.x,tab.y) | min_by(tab.x,tab.y) |"
+        - +---------------------+---------------------+
+        - "| 1                   | 2                   |"
+        - +---------------------+---------------------+
+    "###);
+
+    // Only NULL keys
+    let mut execution = TestExecution::new().await.unwrap();
+    let actual = execution
+        .run_and_format("SELECT max_by(x, y), min_by(x, y) FROM VALUES (1, CAST(NULL AS BIGINT)) as tab(x, y)")
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +---------------------+---------------------+
+        - "| max_by(tab.x,tab.y) | min_by(tab.x,tab.y) |"
+        - +---------------------+---------------------+
+        - "|                     |                     |"
+        - +---------------------+---------------------+
+    "###);
+}
+
 #[tokio::test]
 async fn test_kurtosis_pop() {
     let mut execution = TestExecution::new().await.unwrap().with_setup(TEST_TABLE).await;
@@ -264,7 +461,7 @@ async fn test_kurtosis_pop() {
         - +------------------------------------+
         - "| kurtosis_pop(test_table.int64_col) |"
         - +------------------------------------+
-        - "| -0.9599999999999755                |"
+        - "| -0.96                              |"
         - +------------------------------------+
     "###);
 
@@ -277,7 +474,7 @@ async fn test_kurtosis_pop() {
     - +--------------------------------------+
     - "| kurtosis_pop(test_table.float64_col) |"
     - +--------------------------------------+
-    - "| -0.9599999999999755                  |"
+    - "| -0.96                                |"
     - +--------------------------------------+
 "###);
 
@@ -310,3 +507,3936 @@ async fn test_kurtosis_pop() {
 - +--------------------+
 "###);
 }
+
+#[tokio::test]
+async fn test_fold_assign() {
+    let mut execution = TestExecution::new().await.unwrap().with_setup(TEST_TABLE).await;
+
+    let actual = execution
+        .run_and_format("SELECT utf8_col, int64_col, fold_assign(utf8_col, 3, 7) AS utf8_fold, fold_assign(int64_col, 3, 7) AS int64_fold FROM test_table")
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +----------+-----------+-----------+------------+
+        - "| utf8_col | int64_col | utf8_fold | int64_fold |"
+        - +----------+-----------+-----------+------------+
+        - "| apple    | 1         | 1         | 2          |"
+        - "| banana   | 2         | 0         | 1          |"
+        - "| apple    | 2         | 1         | 1          |"
+        - "| orange   | 3         | 1         | 0          |"
+        - "| banana   | 3         | 0         | 0          |"
+        - "| apple    | 3         | 1         | 0          |"
+        - "|          |           | 0         | 0          |"
+        - +----------+-----------+-----------+------------+
+    "###);
+
+    // Matches Spark's `pmod(hash(0L, 1L), 10)`
+    let actual = execution.run_and_format("SELECT fold_assign(1, 10, 0)").await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +------------------------------------------+
+        - "| fold_assign(Int64(1),Int64(10),Int64(0)) |"
+        - +------------------------------------------+
+        - "| 1                                        |"
+        - +------------------------------------------+
+    "###);
+
+    // Literals are folded without building arrays and must match the rows above
+    let actual = execution
+        .run_and_format("SELECT fold_assign('apple', 3, 7) AS utf8_fold, fold_assign(2, 3, 7) AS int64_fold")
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +-----------+------------+
+        - "| utf8_fold | int64_fold |"
+        - +-----------+------------+
+        - "| 1         | 1          |"
+        - +-----------+------------+
+    "###);
+
+    let actual = execution.run_and_format("SELECT fold_assign('a', NULL, 0)").await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +--------------------------------------+
+        - "| fold_assign(Utf8(\"a\"),NULL,Int64(0)) |"
+        - +--------------------------------------+
+        - "|                                      |"
+        - +--------------------------------------+
+    "###);
+}
+
+#[tokio::test]
+async fn test_kurtosis_pop_sliding_window() {
+    let mut execution = TestExecution::new().await.unwrap();
+
+    let actual = execution
+        .run_and_format(
+            "SELECT i, kurtosis_pop(x) OVER (ORDER BY i ROWS BETWEEN 3 PRECEDING AND CURRENT ROW) AS k \
+             FROM VALUES (1, 1e9 + 1), (2, 1e9 + 2), (3, 1e9 + 2), (4, 1e9 + 8), (5, 1e9 + 3), \
+             (6, 5), (7, 5), (8, 5), (9, 5), (10, 6) as tab(i, x)",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +----+---------------------+
+        - "| i  | k                   |"
+        - +----+---------------------+
+        - "| 1  |                     |"
+        - "| 2  | -2.0                |"
+        - "| 3  | -1.5                |"
+        - "| 4  | -0.717430100556292  |"
+        - "| 5  | -0.7450260186064197 |"
+        - "| 6  | -0.6666666666666665 |"
+        - "| 7  | -1.9999999999999996 |"
+        - "| 8  | -0.6666666666666661 |"
+        - "| 9  |                     |"
+        - "| 10 | -0.6666666666666661 |"
+        - +----+---------------------+
+    "###);
+}
+
+#[tokio::test]
+async fn test_harmonic_mean() {
+    let mut execution = TestExecution::new().await.unwrap().with_setup(TEST_TABLE).await;
+
+    let actual = execution
+        .run_and_format("SELECT harmonic_mean(int64_col), harmonic_mean(float64_col) FROM test_table")
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +-------------------------------------+---------------------------------------+
+        - "| harmonic_mean(test_table.int64_col) | harmonic_mean(test_table.float64_col) |"
+        - +-------------------------------------+---------------------------------------+
+        - "| 1.9999999999999998                  | 1.9999999999999998                    |"
+        - +-------------------------------------+---------------------------------------+
+    "###);
+
+    let actual = execution
+        .run_and_format(
+            "SELECT harmonic_mean(f), harmonic_mean(d) FROM VALUES \
+             (CAST(1.5 AS FLOAT), CAST(1.5 AS DECIMAL(10, 2))), \
+             (CAST(3 AS FLOAT), CAST(3 AS DECIMAL(10, 2))), \
+             (NULL, NULL) as tab(f, d)",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +----------------------+----------------------+
+        - "| harmonic_mean(tab.f) | harmonic_mean(tab.d) |"
+        - +----------------------+----------------------+
+        - "| 2.0                  | 2.0                  |"
+        - +----------------------+----------------------+
+    "###);
+
+    let actual = execution
+        .run_and_format("SELECT harmonic_mean(x) FROM VALUES (1.0), (0.0) as tab(x)")
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +----------------------+
+        - "| harmonic_mean(tab.x) |"
+        - +----------------------+
+        - "| 0.0                  |"
+        - +----------------------+
+    "###);
+
+    let actual = execution.run_and_format("SELECT harmonic_mean(null)").await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +---------------------+
+        - "| harmonic_mean(NULL) |"
+        - +---------------------+
+        - "|                     |"
+        - +---------------------+
+    "###);
+}
+
+#[tokio::test]
+async fn test_harmonic_mean_zero_value_policy() {
+    let sql = "SELECT harmonic_mean(x) FROM tab";
+    let partition =
+        |x: Vec<i64>| RecordBatch::try_from_iter(vec![("x", Arc::new(Int64Array::from(x)) as ArrayRef)]).unwrap();
+    let partitions = || vec![partition(vec![2, 2]), partition(vec![0]), partition(vec![4, 4])];
+
+    let mut execution = TestExecution::new()
+        .await
+        .unwrap()
+        .with_udaf(AggregateUDF::from(HarmonicMeanFunction::new_with_zero_value_policy(
+            ZeroValuePolicy::IgnoreZeros,
+        )))
+        .with_partitioned_table("tab", partitions());
+    let actual = execution.run_and_format(sql).await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +----------------------+
+        - "| harmonic_mean(tab.x) |"
+        - +----------------------+
+        - "| 2.6666666666666665   |"
+        - +----------------------+
+    "###);
+
+    let mut execution = TestExecution::new()
+        .await
+        .unwrap()
+        .with_udaf(AggregateUDF::from(HarmonicMeanFunction::new_with_zero_value_policy(
+            ZeroValuePolicy::ReturnNull,
+        )))
+        .with_partitioned_table("tab", partitions());
+    let actual = execution.run_and_format(sql).await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +----------------------+
+        - "| harmonic_mean(tab.x) |"
+        - +----------------------+
+        - "|                      |"
+        - +----------------------+
+    "###);
+}
+
+#[tokio::test]
+async fn test_product() {
+    let mut execution = TestExecution::new().await.unwrap().with_setup(TEST_TABLE).await;
+
+    let actual = execution
+        .run_and_format("SELECT product(int64_col), product(float64_col) FROM test_table")
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +-------------------------------+---------------------------------+
+        - "| product(test_table.int64_col) | product(test_table.float64_col) |"
+        - +-------------------------------+---------------------------------+
+        - "| 108                           | 108.0                           |"
+        - +-------------------------------+---------------------------------+
+    "###);
+
+    let actual = execution
+        .run_and_format("SELECT product(x) FROM VALUES (CAST(1.5 AS DECIMAL(10, 2))), (-4) as tab(x)")
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +----------------+
+        - "| product(tab.x) |"
+        - +----------------+
+        - "| -6.0           |"
+        - +----------------+
+    "###);
+
+    let actual = execution.run_and_format("SELECT product(null)").await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +---------------+
+        - "| product(NULL) |"
+        - +---------------+
+        - "|               |"
+        - +---------------+
+    "###);
+
+    let actual = execution
+        .run("SELECT product(x) FROM VALUES (9223372036854775807), (2) as tab(x)")
+        .await;
+    assert!(actual.unwrap_err().to_string().contains("product overflowed Int64"));
+}
+
+#[tokio::test]
+async fn test_product_options() {
+    let sql = "SELECT product(x), arrow_typeof(product(x)) FROM tab";
+    let partition =
+        |x: Vec<i64>| RecordBatch::try_from_iter(vec![("x", Arc::new(Int64Array::from(x)) as ArrayRef)]).unwrap();
+    let partitions = || {
+        vec![
+            partition(vec![1 << 40, 3]),
+            partition(vec![1 << 30]),
+            partition(vec![-1]),
+        ]
+    };
+
+    let mut execution = TestExecution::new()
+        .await
+        .unwrap()
+        .with_udaf(AggregateUDF::from(ProductFunction::new_with_options(
+            OverflowPolicy::Saturate,
+            ProductMethod::Direct,
+        )))
+        .with_partitioned_table("tab", partitions());
+    let actual = execution.run_and_format(sql).await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +----------------------+------------------------------+
+        - "| product(tab.x)       | arrow_typeof(product(tab.x)) |"
+        - +----------------------+------------------------------+
+        - "| -9223372036854775807 | Int64                        |"
+        - +----------------------+------------------------------+
+    "###);
+
+    let mut execution = TestExecution::new()
+        .await
+        .unwrap()
+        .with_udaf(AggregateUDF::from(ProductFunction::new_with_options(
+            OverflowPolicy::PromoteToFloat64,
+            ProductMethod::Direct,
+        )))
+        .with_partitioned_table("tab", partitions());
+    let actual = execution.run_and_format(sql).await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +-----------------------+------------------------------+
+        - "| product(tab.x)        | arrow_typeof(product(tab.x)) |"
+        - +-----------------------+------------------------------+
+        - "| -3.541774862152234e21 | Float64                      |"
+        - +-----------------------+------------------------------+
+    "###);
+
+    let mut execution = TestExecution::new()
+        .await
+        .unwrap()
+        .with_udaf(AggregateUDF::from(ProductFunction::new_with_options(
+            OverflowPolicy::default(),
+            ProductMethod::LogDomain,
+        )))
+        .with_partitioned_table("tab", partitions());
+    let actual = execution.run_and_format(sql).await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +-----------------------+------------------------------+
+        - "| product(tab.x)        | arrow_typeof(product(tab.x)) |"
+        - +-----------------------+------------------------------+
+        - "| -3.541774862152235e21 | Float64                      |"
+        - +-----------------------+------------------------------+
+    "###);
+}
+
+#[tokio::test]
+async fn test_concordance() {
+    let mut execution = TestExecution::new().await.unwrap().with_setup(TEST_TABLE).await;
+
+    let actual = execution
+        .run_and_format(
+            "SELECT concordance(int64_col, float64_col), discordance(int64_col, float64_col) FROM test_table",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +----------------------------------------------------------+----------------------------------------------------------+
+        - "| concordance(test_table.int64_col,test_table.float64_col) | discordance(test_table.int64_col,test_table.float64_col) |"
+        - +----------------------------------------------------------+----------------------------------------------------------+
+        - "| 0.7333333333333333                                       | 0.0                                                      |"
+        - +----------------------------------------------------------+----------------------------------------------------------+
+    "###);
+
+    let actual = execution
+        .run_and_format(
+            "SELECT concordance(x, y), discordance(x, y) \
+             FROM VALUES (1, 1.0), (2, 3.0), (3, 2.0), (4, 4.0), (NULL, 5.0) as tab(x, y)",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +--------------------------+--------------------------+
+        - "| concordance(tab.x,tab.y) | discordance(tab.x,tab.y) |"
+        - +--------------------------+--------------------------+
+        - "| 0.8333333333333334       | 0.16666666666666666      |"
+        - +--------------------------+--------------------------+
+    "###);
+
+    let actual = execution.run_and_format("SELECT concordance(1, 2)").await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +--------------------------------+
+        - "| concordance(Int64(1),Int64(2)) |"
+        - +--------------------------------+
+        - "|                                |"
+        - +--------------------------------+
+    "###);
+}
+
+#[tokio::test]
+async fn test_weighted_avg() {
+    let mut execution = TestExecution::new().await.unwrap().with_setup(TEST_TABLE).await;
+
+    let actual = execution
+        .run_and_format("SELECT weighted_avg(float64_col, int64_col) FROM test_table")
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +-----------------------------------------------------------+
+        - "| weighted_avg(test_table.float64_col,test_table.int64_col) |"
+        - +-----------------------------------------------------------+
+        - "| 2.5714285714285716                                        |"
+        - +-----------------------------------------------------------+
+    "###);
+
+    let actual = execution
+        .run_and_format(
+            "SELECT utf8_col, weighted_avg(float64_col, int64_col) FROM test_table GROUP BY utf8_col ORDER BY utf8_col",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +----------+-----------------------------------------------------------+
+        - "| utf8_col | weighted_avg(test_table.float64_col,test_table.int64_col) |"
+        - +----------+-----------------------------------------------------------+
+        - "| apple    | 2.3333333333333335                                        |"
+        - "| banana   | 2.6                                                       |"
+        - "| orange   | 3.0                                                       |"
+        - "|          |                                                           |"
+        - +----------+-----------------------------------------------------------+
+    "###);
+
+    let actual = execution
+        .run_and_format(
+            "SELECT weighted_avg(x, w) FROM VALUES (1.0, 1.0), (5.0, NULL), (NULL, 4.0), (3.0, 3.0) as tab(x, w)",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +---------------------------+
+        - "| weighted_avg(tab.x,tab.w) |"
+        - +---------------------------+
+        - "| 2.5                       |"
+        - +---------------------------+
+    "###);
+}
+
+#[tokio::test]
+async fn test_bucket_percentiles() {
+    let mut execution = TestExecution::new()
+        .await
+        .unwrap()
+        .with_unstable_functions()
+        .with_setup(TEST_TABLE)
+        .await;
+
+    let actual = execution
+        .run_and_format("SELECT bucket_percentiles(float64_col, utf8_col) FROM test_table")
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +-------------------------------------------------------------------------------------------------------------------------+
+        - "| bucket_percentiles(test_table.float64_col,test_table.utf8_col)                                                          |"
+        - +-------------------------------------------------------------------------------------------------------------------------+
+        - "| {apple: {p50: 2.0, p95: 3.0, p99: 3.0}, banana: {p50: 2.5, p95: 3.0, p99: 3.0}, orange: {p50: 3.0, p95: 3.0, p99: 3.0}} |"
+        - +-------------------------------------------------------------------------------------------------------------------------+
+    "###);
+
+    let actual = execution
+        .run_and_format(
+            "SELECT int64_col, bucket_percentiles(float64_col, utf8_col) FROM test_table \
+             WHERE int64_col > 1 GROUP BY int64_col ORDER BY int64_col",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +-----------+-------------------------------------------------------------------------------------------------------------------------+
+        - "| int64_col | bucket_percentiles(test_table.float64_col,test_table.utf8_col)                                                          |"
+        - +-----------+-------------------------------------------------------------------------------------------------------------------------+
+        - "| 2         | {apple: {p50: 2.0, p95: 2.0, p99: 2.0}, banana: {p50: 2.0, p95: 2.0, p99: 2.0}}                                         |"
+        - "| 3         | {apple: {p50: 3.0, p95: 3.0, p99: 3.0}, banana: {p50: 3.0, p95: 3.0, p99: 3.0}, orange: {p50: 3.0, p95: 3.0, p99: 3.0}} |"
+        - +-----------+-------------------------------------------------------------------------------------------------------------------------+
+    "###);
+
+    let actual = execution
+        .run_and_format("SELECT bucket_percentiles(float64_col, utf8_col) FROM test_table WHERE int64_col > 10")
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +----------------------------------------------------------------+
+        - "| bucket_percentiles(test_table.float64_col,test_table.utf8_col) |"
+        - +----------------------------------------------------------------+
+        - "|                                                                |"
+        - +----------------------------------------------------------------+
+    "###);
+}
+
+#[tokio::test]
+async fn test_bucket_percentiles_merge() {
+    let partition = |range: std::ops::Range<i64>| {
+        let keys: Vec<i64> = range.clone().map(|i| i % 2).collect();
+        let values: Vec<i64> = range.collect();
+        RecordBatch::try_from_iter(vec![
+            ("value", Arc::new(Int64Array::from(values)) as ArrayRef),
+            ("bucket", Arc::new(Int64Array::from(keys)) as ArrayRef),
+        ])
+        .unwrap()
+    };
+
+    let mut execution = TestExecution::new()
+        .await
+        .unwrap()
+        .with_unstable_functions()
+        .with_partitioned_table("tab", vec![partition(0..100), partition(100..150), partition(150..200)]);
+    let actual = execution
+        .run_and_format("SELECT bucket_percentiles(value, bucket) FROM tab")
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +-----------------------------------------------------------------------------------+
+        - "| bucket_percentiles(tab.value,tab.bucket)                                          |"
+        - +-----------------------------------------------------------------------------------+
+        - "| {0: {p50: 99.0, p95: 189.0, p99: 197.0}, 1: {p50: 100.0, p95: 190.0, p99: 198.0}} |"
+        - +-----------------------------------------------------------------------------------+
+    "###);
+}
+
+#[tokio::test]
+async fn test_trimmed_mean() {
+    let mut execution = TestExecution::new().await.unwrap().with_unstable_functions();
+
+    let actual = execution
+        .run_and_format(
+            "SELECT trimmed_mean(x, 0.2), approx_trimmed_mean(x, 0.2) FROM VALUES (-100.0), (1.0), (NULL), (2.0), (3.0), (1000.0) as tab(x)",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +----------------------------------+-----------------------------------------+
+        - "| trimmed_mean(tab.x,Float64(0.2)) | approx_trimmed_mean(tab.x,Float64(0.2)) |"
+        - +----------------------------------+-----------------------------------------+
+        - "| 2.0                              | 2.0                                     |"
+        - +----------------------------------+-----------------------------------------+
+    "###);
+
+    let actual = execution
+        .run_and_format("SELECT trimmed_mean(x, 0.0) FROM VALUES (1.0), (2.0), (6.0) as tab(x)")
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +--------------------------------+
+        - "| trimmed_mean(tab.x,Float64(0)) |"
+        - +--------------------------------+
+        - "| 3.0                            |"
+        - +--------------------------------+
+    "###);
+
+    let err = execution
+        .run("SELECT trimmed_mean(x, 0.5) FROM VALUES (1.0) as tab(x)")
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("expects a fraction in [0, 0.5)"), "{err}");
+}
+
+#[tokio::test]
+async fn test_mode_latest() {
+    let mut execution = TestExecution::new().await.unwrap();
+
+    let actual = execution
+        .run_and_format(
+            "SELECT user_id, mode_latest(device, CAST(ts AS TIMESTAMP)) AS device FROM VALUES \
+                (1, 'pixel', '2024-01-01'), (1, 'iphone', '2024-03-01'), (1, 'pixel', '2024-02-01'), (1, 'iphone', '2024-01-15'), \
+                (2, 'galaxy', '2024-01-01'), (2, 'galaxy', NULL), (2, 'pixel', '2024-05-01'), (2, NULL, '2024-06-01') \
+            as tab(user_id, device, ts) GROUP BY user_id ORDER BY user_id",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +---------+--------+
+        - "| user_id | device |"
+        - +---------+--------+
+        - "| 1       | iphone |"
+        - "| 2       | galaxy |"
+        - +---------+--------+
+    "###);
+
+    let actual = execution
+        .run_and_format("SELECT mode_latest(x, ts) FROM VALUES (1.5, 2), (2.5, 1), (2.5, 3), (1.5, 3) as tab(x, ts)")
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +---------------------------+
+        - "| mode_latest(tab.x,tab.ts) |"
+        - +---------------------------+
+        - "| 1.5                       |"
+        - +---------------------------+
+    "###);
+}
+
+#[tokio::test]
+async fn test_mode_latest_merge() {
+    let partition = |values: Vec<&str>, ts: Vec<i64>| {
+        RecordBatch::try_from_iter(vec![
+            ("value", Arc::new(StringArray::from(values)) as ArrayRef),
+            ("ts", Arc::new(Int64Array::from(ts)) as ArrayRef),
+        ])
+        .unwrap()
+    };
+
+    let mut execution = TestExecution::new().await.unwrap().with_partitioned_table(
+        "tab",
+        vec![
+            partition(vec!["a", "b"], vec![1, 2]),
+            partition(vec!["b", "c"], vec![3, 4]),
+            partition(vec!["a", "c"], vec![9, 5]),
+        ],
+    );
+    let actual = execution.run_and_format("SELECT mode_latest(value, ts) FROM tab").await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +-------------------------------+
+        - "| mode_latest(tab.value,tab.ts) |"
+        - +-------------------------------+
+        - "| a                             |"
+        - +-------------------------------+
+    "###);
+}
+
+#[tokio::test]
+async fn test_winsorized_mean() {
+    let mut execution = TestExecution::new().await.unwrap().with_unstable_functions();
+
+    let actual = execution
+        .run_and_format(
+            "SELECT winsorized_mean(x, 0.2), approx_winsorized_mean(x, 0.2), trimmed_mean(x, 0.2) FROM VALUES (-100.0), (1.0), (NULL), (2.0), (6.0), (1000.0) as tab(x)",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +-------------------------------------+--------------------------------------------+----------------------------------+
+        - "| winsorized_mean(tab.x,Float64(0.2)) | approx_winsorized_mean(tab.x,Float64(0.2)) | trimmed_mean(tab.x,Float64(0.2)) |"
+        - +-------------------------------------+--------------------------------------------+----------------------------------+
+        - "| 3.2                                 | 3.2000000000000886                         | 3.0                              |"
+        - +-------------------------------------+--------------------------------------------+----------------------------------+
+    "###);
+
+    let err = execution
+        .run("SELECT approx_winsorized_mean(x, -0.1) FROM VALUES (1.0) as tab(x)")
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("expects a fraction in [0, 0.5)"), "{err}");
+}
+
+#[tokio::test]
+async fn test_function_package_hot_swap() {
+    let ctx = SessionContext::new();
+    let package = FunctionPackage::new("experimental").with_aggregate_functions(vec![harmonic_mean_udaf()]);
+    let query = "SELECT harmonic_mean(x) FROM VALUES (1.0), (2.0), (4.0) as tab(x)";
+
+    assert!(ctx.sql(query).await.is_err());
+
+    package.register_into_context(&ctx).unwrap();
+    let planned = ctx.sql(query).await.unwrap();
+    package.deregister_from_context(&ctx).unwrap();
+
+    // Plans built before the package was removed keep running
+    let actual = pretty_format_batches(&planned.collect().await.unwrap())
+        .unwrap()
+        .to_string();
+    insta::assert_snapshot!(actual, @r###"
+        +----------------------+
+        | harmonic_mean(tab.x) |
+        +----------------------+
+        | 1.7142857142857142   |
+        +----------------------+
+    "###);
+    assert!(ctx.sql(query).await.is_err());
+
+    // Enabling the package on a copy of the state doesn't affect the shared context
+    let mut state = ctx.state();
+    package.register_into(&mut state).unwrap();
+    assert!(SessionContext::new_with_state(state).sql(query).await.is_ok());
+    assert!(ctx.sql(query).await.is_err());
+}
+
+#[tokio::test]
+async fn test_iqr() {
+    let mut execution = TestExecution::new().await.unwrap().with_setup(TEST_TABLE).await;
+
+    let actual = execution
+        .run_and_format(
+            "SELECT utf8_col, iqr(float64_col), iqr(int64_col, 'approx') FROM test_table GROUP BY utf8_col ORDER BY utf8_col",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +----------+-----------------------------+------------------------------------------+
+        - "| utf8_col | iqr(test_table.float64_col) | iqr(test_table.int64_col,Utf8(\"approx\")) |"
+        - +----------+-----------------------------+------------------------------------------+
+        - "| apple    | 1.0                         | 1.5                                      |"
+        - "| banana   | 0.5                         | 1.0                                      |"
+        - "| orange   | 0.0                         | 0.0                                      |"
+        - "|          |                             |                                          |"
+        - +----------+-----------------------------+------------------------------------------+
+    "###);
+
+    let err = execution
+        .run("SELECT iqr(float64_col, 'median') FROM test_table")
+        .await
+        .unwrap_err();
+    assert!(
+        err.to_string().contains("iqr expects a method of 'exact' or 'approx'"),
+        "{err}"
+    );
+}
+
+#[tokio::test]
+async fn test_unstable_functions_opt_in() {
+    let query = "SELECT approx_trimmed_mean(x, 0.1) FROM VALUES (1.0), (2.0) as tab(x)";
+
+    let mut ctx = SessionContext::new();
+    register_all_extra_functions(&mut ctx).unwrap();
+    assert!(ctx
+        .sql("SELECT trimmed_mean(x, 0.1) FROM VALUES (1.0) as tab(x)")
+        .await
+        .is_ok());
+    assert!(ctx.sql(query).await.is_err());
+
+    let mut ctx = SessionContext::new();
+    register_extra_functions_with_options(&mut ctx, &RegistrationOptions::new().with_unstable_functions(true)).unwrap();
+    assert!(ctx.sql(query).await.is_ok());
+}
+
+#[tokio::test]
+async fn test_cv_and_stderr() {
+    let mut execution = TestExecution::new().await.unwrap().with_setup(TEST_TABLE).await;
+
+    let actual = execution
+        .run_and_format(
+            "SELECT utf8_col, cv(float64_col), stderr_mean(int64_col) FROM test_table GROUP BY utf8_col ORDER BY utf8_col",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +----------+----------------------------+------------------------------+
+        - "| utf8_col | cv(test_table.float64_col) | stderr(test_table.int64_col) |"
+        - +----------+----------------------------+------------------------------+
+        - "| apple    | 0.5                        | 0.5773502691896257           |"
+        - "| banana   | 0.282842712474619          | 0.5                          |"
+        - "| orange   |                            |                              |"
+        - "|          |                            |                              |"
+        - +----------+----------------------------+------------------------------+
+    "###);
+
+    let actual = execution
+        .run_and_format(
+            "SELECT x, stderr(x) OVER (ORDER BY x ROWS BETWEEN 1 PRECEDING AND CURRENT ROW) FROM VALUES (1.0), (3.0), (7.0) as tab(x)",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +-----+----------------------------------------------------------------------------------------+
+        - "| x   | stderr(tab.x) ORDER BY [tab.x ASC NULLS LAST] ROWS BETWEEN 1 PRECEDING AND CURRENT ROW |"
+        - +-----+----------------------------------------------------------------------------------------+
+        - "| 1.0 |                                                                                        |"
+        - "| 3.0 | 1.0                                                                                    |"
+        - "| 7.0 | 2.0                                                                                    |"
+        - +-----+----------------------------------------------------------------------------------------+
+    "###);
+}
+
+#[tokio::test]
+async fn test_circular_mean_and_stddev() {
+    let mut execution = TestExecution::new().await.unwrap();
+
+    let actual = execution
+        .run_and_format(
+            "SELECT station, round(degrees(circular_mean(radians(direction))), 6) AS mean, round(circular_stddev(radians(direction)), 6) AS stddev \
+            FROM VALUES ('a', 350.0), ('a', 20.0), ('a', NULL), ('b', 90.0), ('b', 180.0), ('c', NULL) as tab(station, direction) \
+            GROUP BY station ORDER BY station",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +---------+-------+----------+
+        - "| station | mean  | stddev   |"
+        - +---------+-------+----------+
+        - "| a       | 5.0   | 0.263318 |"
+        - "| b       | 135.0 | 0.832555 |"
+        - "| c       |       |          |"
+        - +---------+-------+----------+
+    "###);
+}
+
+#[tokio::test]
+async fn test_entropy() {
+    let mut execution = TestExecution::new().await.unwrap();
+
+    let actual = execution
+        .run_and_format(
+            "SELECT g, entropy(x, 2) AS bits, round(entropy(x), 6) AS nats \
+            FROM VALUES ('a', 'h'), ('a', 't'), ('a', NULL), ('b', 'h'), ('b', 'h'), ('c', NULL) as tab(g, x) \
+            GROUP BY g ORDER BY g",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +---+------+----------+
+        - "| g | bits | nats     |"
+        - +---+------+----------+
+        - "| a | 1.0  | 0.693147 |"
+        - "| b | 0.0  | 0.0      |"
+        - "| c |      |          |"
+        - +---+------+----------+
+    "###);
+
+    let err = execution
+        .run("SELECT entropy(x, 1) FROM VALUES (1) as tab(x)")
+        .await
+        .unwrap_err();
+    insta::assert_snapshot!(err, @r###"
+        Error during planning: entropy expects a positive base other than 1, got 1
+    "###);
+}
+
+#[tokio::test]
+async fn test_entropy_merge() {
+    let partition = |values: Vec<i64>| {
+        RecordBatch::try_from_iter(vec![("value", Arc::new(Int64Array::from(values)) as ArrayRef)]).unwrap()
+    };
+
+    let mut execution = TestExecution::new().await.unwrap().with_partitioned_table(
+        "tab",
+        vec![
+            partition(vec![0, 1]),
+            partition(vec![2, 3]),
+            partition(vec![3, 2, 1, 0]),
+        ],
+    );
+    let actual = execution.run_and_format("SELECT entropy(value, 2) FROM tab").await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +-----------------------------+
+        - "| entropy(tab.value,Int64(2)) |"
+        - +-----------------------------+
+        - "| 2.0                         |"
+        - +-----------------------------+
+    "###);
+}
+
+#[tokio::test]
+async fn test_gini() {
+    let partition = |values: Vec<Option<f64>>| {
+        RecordBatch::try_from_iter_with_nullable(vec![(
+            "income",
+            Arc::new(Float64Array::from(values)) as ArrayRef,
+            true,
+        )])
+        .unwrap()
+    };
+
+    let mut execution = TestExecution::new().await.unwrap().with_partitioned_table(
+        "tab",
+        vec![
+            partition(vec![Some(1.0), Some(2.0)]),
+            partition(vec![None, Some(3.0)]),
+            partition(vec![Some(10.0)]),
+        ],
+    );
+    let actual = execution
+        .run_and_format("SELECT round(gini(income), 6) AS gini FROM tab")
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +--------+
+        - "| gini   |"
+        - +--------+
+        - "| 0.4375 |"
+        - +--------+
+    "###);
+
+    let err = execution
+        .run("SELECT gini(x) FROM VALUES (1.0), (-2.0) as tab(x)")
+        .await
+        .unwrap_err();
+    insta::assert_snapshot!(err, @r###"
+        Execution error: gini expects non-negative values, got -2
+    "###);
+}
+
+#[tokio::test]
+async fn test_approx_join_cardinality() {
+    let mut execution = TestExecution::new().await.unwrap();
+
+    let actual = execution
+        .run_and_format(
+            "SELECT approx_join_cardinality(k) AS single, approx_join_cardinality(k, region) AS composite \
+            FROM VALUES (1, 'eu'), (1, 'eu'), (1, 'us'), (2, 'eu'), (3, NULL), (NULL, 'eu') as tab(k, region)",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +-------------------------------------------------------------------+-------------------------------------------------------------------+
+        - "| single                                                            | composite                                                         |"
+        - +-------------------------------------------------------------------+-------------------------------------------------------------------+
+        - "| {rows: 5, distinct_keys: 3, top_k_share: 1.0, max_key_share: 0.6} | {rows: 4, distinct_keys: 3, top_k_share: 1.0, max_key_share: 0.5} |"
+        - +-------------------------------------------------------------------+-------------------------------------------------------------------+
+    "###);
+}
+
+#[tokio::test]
+async fn test_log_sum_exp() {
+    let mut execution = TestExecution::new().await.unwrap();
+
+    let actual = execution
+        .run_and_format(
+            "SELECT g, round(log_sum_exp(x), 6) AS lse, round(logsumexp(x) - max(x), 6) AS offset \
+            FROM VALUES ('a', 1000.0), ('a', 1000.0), ('a', NULL), ('b', ln(0.25)), ('b', ln(0.75)), ('c', NULL) as tab(g, x) \
+            GROUP BY g ORDER BY g",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +---+-------------+----------+
+        - "| g | lse         | offset   |"
+        - +---+-------------+----------+
+        - "| a | 1000.693147 | 0.693147 |"
+        - "| b | 0.0         | 0.287682 |"
+        - "| c |             |          |"
+        - +---+-------------+----------+
+    "###);
+}
+
+#[tokio::test]
+async fn test_skew_detect() {
+    let mut execution = TestExecution::new().await.unwrap();
+
+    let actual = execution
+        .run_and_format(
+            "WITH series AS (SELECT unnest(range(1, 101)) AS value) \
+            SELECT g, skew_detect(k) AS skew \
+            FROM (SELECT 'uniform' AS g, value % 20 AS k FROM series \
+            UNION ALL SELECT 'skewed' AS g, CASE WHEN value <= 70 THEN 0 ELSE value END AS k FROM series \
+            UNION ALL SELECT 'empty' AS g, NULL AS k) \
+            GROUP BY g ORDER BY g",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +---------+--------------------------------------------------------------------+
+        - "| g       | skew                                                               |"
+        - +---------+--------------------------------------------------------------------+
+        - "| empty   | {rows: 0, distinct_keys: 0, top1_share: , top10_share: }           |"
+        - "| skewed  | {rows: 100, distinct_keys: 31, top1_share: 0.7, top10_share: 0.79} |"
+        - "| uniform | {rows: 100, distinct_keys: 20, top1_share: 0.05, top10_share: 0.5} |"
+        - +---------+--------------------------------------------------------------------+
+    "###);
+}
+
+#[tokio::test]
+async fn test_rms_and_sum_of_squares() {
+    let mut execution = TestExecution::new().await.unwrap();
+
+    let actual = execution
+        .run_and_format(
+            "SELECT g, rms(x) AS rms, sum_of_squares(x) AS sum_of_squares \
+            FROM VALUES ('a', 3), ('a', -4), ('a', NULL), ('b', 1.5), ('c', NULL) as tab(g, x) \
+            GROUP BY g ORDER BY g",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +---+--------------------+----------------+
+        - "| g | rms                | sum_of_squares |"
+        - +---+--------------------+----------------+
+        - "| a | 3.5355339059327378 | 25.0           |"
+        - "| b | 1.5                | 2.25           |"
+        - "| c |                    |                |"
+        - +---+--------------------+----------------+
+    "###);
+}
+
+#[tokio::test]
+async fn test_column_fingerprint() {
+    let mut execution = TestExecution::new().await.unwrap();
+
+    let actual = execution
+        .run_and_format(
+            "WITH snapshot_a AS (SELECT * FROM VALUES ('a', 1), ('b', 2), ('c', NULL) as t(part, x)), \
+            snapshot_b AS (SELECT * FROM VALUES ('c', NULL), ('b', 2), ('a', 1) as t(part, x)), \
+            snapshot_c AS (SELECT * FROM VALUES ('a', 1), ('b', 3), ('c', NULL) as t(part, x)) \
+            SELECT \
+            (SELECT column_fingerprint(x) FROM snapshot_a) = (SELECT column_fingerprint(x) FROM snapshot_b) AS same_rows, \
+            (SELECT column_fingerprint(x) FROM snapshot_a) = (SELECT column_fingerprint(x) FROM snapshot_c) AS changed_row, \
+            (SELECT fingerprint_combine(a.f, b.f) FROM \
+                (SELECT column_fingerprint(x) AS f FROM snapshot_a WHERE part = 'a') a, \
+                (SELECT column_fingerprint(x) AS f FROM snapshot_a WHERE part <> 'a') b) \
+            = (SELECT column_fingerprint(x) FROM snapshot_a) AS combined",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +-----------+-------------+----------+
+        - "| same_rows | changed_row | combined |"
+        - +-----------+-------------+----------+
+        - "| true      | false       | true     |"
+        - +-----------+-------------+----------+
+    "###);
+}
+
+#[tokio::test]
+async fn test_range_agg_and_midrange() {
+    let mut execution = TestExecution::new().await.unwrap();
+
+    let actual = execution
+        .run_and_format(
+            "SELECT g, range_agg(x), midrange(x) FROM VALUES (1, 3), (1, -5), (1, NULL), (2, 7), (3, NULL) as tab(g, x) \
+            GROUP BY g ORDER BY g",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +---+------------------+-----------------+
+        - "| g | range_agg(tab.x) | midrange(tab.x) |"
+        - +---+------------------+-----------------+
+        - "| 1 | 8.0              | -1.0            |"
+        - "| 2 | 0.0              | 7.0             |"
+        - "| 3 |                  |                 |"
+        - +---+------------------+-----------------+
+    "###);
+}
+
+#[tokio::test]
+async fn test_conditional_aggregates() {
+    // the default dialect doesn't parse `FILTER (WHERE ...)`
+    let mut execution = TestExecution::new()
+        .await
+        .unwrap()
+        .with_setup("SET datafusion.sql_parser.dialect = 'PostgreSQL'")
+        .await;
+
+    let actual = execution
+        .run_and_format(
+            "SELECT g, count_if(x, x > 1), sum_if(x, x > 1), avg_if(x, x > 1), \
+            sum_if(x, x > 1) FILTER (WHERE x < 4) AS filtered \
+            FROM (VALUES (1, 1), (1, 2), (1, 4), (1, NULL), (2, 1), (2, NULL)) AS tab(g, x) \
+            GROUP BY g ORDER BY g",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +---+----------------------------------+--------------------------------+--------------------------------+----------+
+        - "| g | count_if(tab.x,tab.x > Int64(1)) | sum_if(tab.x,tab.x > Int64(1)) | avg_if(tab.x,tab.x > Int64(1)) | filtered |"
+        - +---+----------------------------------+--------------------------------+--------------------------------+----------+
+        - "| 1 | 2                                | 6                              | 3.0                            | 2        |"
+        - "| 2 | 0                                |                                |                                |          |"
+        - +---+----------------------------------+--------------------------------+--------------------------------+----------+
+    "###);
+
+    // the conditional aggregates are evaluated as filtered built-in aggregates
+    let actual = execution
+        .run_and_format("EXPLAIN SELECT sum_if(x, x > 1) FROM (VALUES (1), (2)) AS tab(x)")
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +---------------+----------------------------------------------------------------------------------------------------------------+
+        - "| plan_type     | plan                                                                                                           |"
+        - +---------------+----------------------------------------------------------------------------------------------------------------+
+        - "| logical_plan  | Aggregate: groupBy=[[]], aggr=[[sum(tab.x) FILTER (WHERE tab.x > Int64(1)) AS sum_if(tab.x,tab.x > Int64(1))]] |"
+        - "|               |   SubqueryAlias: tab                                                                                           |"
+        - "|               |     Projection: column1 AS x                                                                                   |"
+        - "|               |       Values: (Int64(1)), (Int64(2))                                                                           |"
+        - "| physical_plan | AggregateExec: mode=Single, gby=[], aggr=[sum_if(tab.x,tab.x > Int64(1))]                                      |"
+        - "|               |   ProjectionExec: expr=[column1@0 as x]                                                                        |"
+        - "|               |     ValuesExec                                                                                                 |"
+        - "|               |                                                                                                                |"
+        - +---------------+----------------------------------------------------------------------------------------------------------------+
+    "###);
+
+    // window functions use the accumulators directly
+    let actual = execution
+        .run_and_format(
+            "SELECT x, count_if(x, x % 2 = 0) OVER (ORDER BY x) AS evens, \
+            avg_if(x, x % 2 = 0) OVER (ORDER BY x) AS even_avg \
+            FROM (VALUES (1), (2), (3), (4)) AS tab(x)",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +---+-------+----------+
+        - "| x | evens | even_avg |"
+        - +---+-------+----------+
+        - "| 1 | 0     |          |"
+        - "| 2 | 1     | 2.0      |"
+        - "| 3 | 1     | 2.0      |"
+        - "| 4 | 2     | 3.0      |"
+        - +---+-------+----------+
+    "###);
+
+    let error = execution
+        .run("SELECT sum_if(x, x) FROM (VALUES (1)) AS tab(x)")
+        .await
+        .unwrap_err();
+    insta::assert_snapshot!(error, @r###"
+        Error during planning: Execution error: User-defined coercion failed with Plan("sum_if expects a boolean condition, got Int64") No function matches the given name and argument types 'sum_if(Int64, Int64)'. You might need to add explicit type casts.
+        	Candidate functions:
+        	sum_if(UserDefined)
+    "###);
+}
+
+#[tokio::test]
+async fn test_string_agg_ext_and_array_agg_ext() {
+    let mut execution = TestExecution::new().await.unwrap();
+
+    let actual = execution
+        .run_and_format(
+            "SELECT g, string_agg_ext(x, ', ') AS s, arrow_typeof(string_agg_ext(x, ', ')) AS s_type, \
+            array_agg_ext(x) AS a, arrow_typeof(array_agg_ext(x)) AS a_type \
+            FROM VALUES (1, 'a'), (1, NULL), (1, 'b'), (2, NULL) as tab(g, x) GROUP BY g ORDER BY g",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +---+------+--------+----------+-----------------------------------------------------------------------------------------------------------------+
+        - "| g | s    | s_type | a        | a_type                                                                                                          |"
+        - +---+------+--------+----------+-----------------------------------------------------------------------------------------------------------------+
+        - "| 1 | a, b | Utf8   | [a, , b] | List(Field { name: \"item\", data_type: Utf8, nullable: true, dict_id: 0, dict_is_ordered: false, metadata: {} }) |"
+        - "| 2 |      | Utf8   | []       | List(Field { name: \"item\", data_type: Utf8, nullable: true, dict_id: 0, dict_is_ordered: false, metadata: {} }) |"
+        - +---+------+--------+----------+-----------------------------------------------------------------------------------------------------------------+
+    "###);
+
+    let mut execution = execution
+        .with_udaf(AggregateUDF::from(StringAggExtFunction::new_with_output_width(
+            OutputWidth::Large,
+        )))
+        .with_udaf(AggregateUDF::from(ArrayAggExtFunction::new_with_output_width(
+            OutputWidth::Large,
+        )));
+
+    let actual = execution
+        .run_and_format(
+            "SELECT string_agg_ext(x, '') AS s, arrow_typeof(string_agg_ext(x, '')) AS s_type, \
+            array_agg_ext(x) AS a, arrow_typeof(array_agg_ext(x)) AS a_type \
+            FROM VALUES ('a'), ('b') as tab(x)",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +----+-----------+--------+----------------------------------------------------------------------------------------------------------------------+
+        - "| s  | s_type    | a      | a_type                                                                                                               |"
+        - +----+-----------+--------+----------------------------------------------------------------------------------------------------------------------+
+        - "| ab | LargeUtf8 | [a, b] | LargeList(Field { name: \"item\", data_type: Utf8, nullable: true, dict_id: 0, dict_is_ordered: false, metadata: {} }) |"
+        - +----+-----------+--------+----------------------------------------------------------------------------------------------------------------------+
+    "###);
+}
+
+#[tokio::test]
+async fn test_count_distinct_if() {
+    let mut execution = TestExecution::new().await.unwrap();
+
+    let actual = execution
+        .run_and_format(
+            "SELECT g, count_distinct_if(x, x <> 'b') AS strings, \
+            count_distinct_if(arrow_cast(x, 'Utf8View'), x <> 'b') AS views, \
+            count_distinct_if(length(x), x <> 'b') AS lengths \
+            FROM VALUES (1, 'a'), (1, 'a'), (1, 'b'), (1, 'cc'), (1, NULL), (2, 'b'), (2, NULL) as tab(g, x) \
+            GROUP BY g ORDER BY g",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +---+---------+-------+---------+
+        - "| g | strings | views | lengths |"
+        - +---+---------+-------+---------+
+        - "| 1 | 2       | 2     | 2       |"
+        - "| 2 | 0       | 0     | 0       |"
+        - +---+---------+-------+---------+
+    "###);
+}
+
+#[tokio::test]
+async fn test_value_counts_and_approx_top_k() {
+    let mut execution = TestExecution::new().await.unwrap();
+
+    let actual = execution
+        .run_and_format(
+            "SELECT value_counts(x) AS by_count, value_counts(x, 'value') AS by_value, \
+            value_counts(x, 'insertion') AS by_insertion, approx_top_k(x, 2) AS top_2 \
+            FROM VALUES ('b'), ('c'), (NULL), ('a'), ('c'), ('a'), ('d') as tab(x)",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +------------------------------------------------------------------------------------------+------------------------------------------------------------------------------------------+------------------------------------------------------------------------------------------+------------------------------------------------------------------+
+        - "| by_count                                                                                 | by_value                                                                                 | by_insertion                                                                             | top_2                                                            |"
+        - +------------------------------------------------------------------------------------------+------------------------------------------------------------------------------------------+------------------------------------------------------------------------------------------+------------------------------------------------------------------+
+        - "| [{value: a, count: 2}, {value: c, count: 2}, {value: b, count: 1}, {value: d, count: 1}] | [{value: a, count: 2}, {value: b, count: 1}, {value: c, count: 2}, {value: d, count: 1}] | [{value: b, count: 1}, {value: c, count: 2}, {value: a, count: 2}, {value: d, count: 1}] | [{value: a, count: 2, error: 0}, {value: c, count: 2, error: 0}] |"
+        - +------------------------------------------------------------------------------------------+------------------------------------------------------------------------------------------+------------------------------------------------------------------------------------------+------------------------------------------------------------------+
+    "###);
+
+    let error = execution
+        .run("SELECT value_counts(x, 'random') FROM VALUES (1) as tab(x)")
+        .await
+        .unwrap_err();
+    insta::assert_snapshot!(error, @r###"
+        Error during planning: value_counts expects a sort_by of 'count', 'value' or 'insertion', got random
+    "###);
+}
+
+#[tokio::test]
+async fn test_any_value() {
+    let mut execution = TestExecution::new().await.unwrap();
+
+    let actual = execution
+        .run_and_format(
+            "SELECT g, any_value(x) IS NOT NULL AS has_value, any_value(x, true ORDER BY y) AS first, \
+            any_value(x, true ORDER BY y DESC) AS last \
+            FROM VALUES (1, NULL, 1), (1, 'b', 2), (1, 'c', 3), (1, NULL, 4), (2, NULL, 1) as tab(g, x, y) \
+            GROUP BY g ORDER BY g",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +---+-----------+-------+------+
+        - "| g | has_value | first | last |"
+        - +---+-----------+-------+------+
+        - "| 1 | true      | b     | c    |"
+        - "| 2 | false     |       |      |"
+        - +---+-----------+-------+------+
+    "###);
+
+    let error = execution
+        .run("SELECT any_value(x, true) FROM VALUES (1) as tab(x)")
+        .await
+        .unwrap_err();
+    insta::assert_snapshot!(error, @r###"
+        Optimizer rule 'simplify_expressions' failed
+        caused by
+        Error during planning: any_value(x, true) requires an ORDER BY to pick the first value of
+    "###);
+}
+
+#[tokio::test]
+async fn test_checksum_agg_and_hash_agg() {
+    let mut execution = TestExecution::new().await.unwrap();
+
+    let actual = execution
+        .run_and_format(
+            "WITH snapshot_a AS (SELECT * FROM VALUES ('a', 1), ('b', NULL), (NULL, 2) as t(s, x)), \
+            snapshot_b AS (SELECT * FROM VALUES (NULL, 2), ('a', 1), ('b', NULL) as t(s, x)), \
+            snapshot_c AS (SELECT * FROM VALUES ('a', 1), (NULL, NULL), ('b', 2) as t(s, x)) \
+            SELECT \
+            (SELECT checksum_agg(s, x) FROM snapshot_a) = (SELECT checksum_agg(s, x) FROM snapshot_b) AS same_rows, \
+            (SELECT checksum_agg(s, x) FROM snapshot_a) = (SELECT checksum_agg(s, x) FROM snapshot_c) AS moved_null, \
+            (SELECT hash_agg(s, x) FROM snapshot_a) = (SELECT hash_agg(s, x) FROM snapshot_b) AS same_hash, \
+            (SELECT hash_agg(x) FROM snapshot_a) = (SELECT column_fingerprint(x) FROM snapshot_a) AS fingerprint, \
+            (SELECT checksum_agg(x) FROM snapshot_a WHERE x > 5) AS no_rows",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +-----------+------------+-----------+-------------+---------+
+        - "| same_rows | moved_null | same_hash | fingerprint | no_rows |"
+        - +-----------+------------+-----------+-------------+---------+
+        - "| true      | false      | true      | true        | 0       |"
+        - +-----------+------------+-----------+-------------+---------+
+    "###);
+}
+
+#[tokio::test]
+async fn test_reservoir_sample() {
+    let mut execution = TestExecution::new().await.unwrap();
+
+    let actual = execution
+        .run_and_format(
+            "SELECT g, array_length(reservoir_sample(x, 3, 42)) AS seeded, array_sort(reservoir_sample(x, 10)) AS everything \
+            FROM VALUES (1, 1), (1, 2), (1, NULL), (1, 3), (1, 4), (1, 5), (2, 6), (3, NULL) as tab(g, x) \
+            GROUP BY g ORDER BY g",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +---+--------+-----------------+
+        - "| g | seeded | everything      |"
+        - +---+--------+-----------------+
+        - "| 1 | 3      | [1, 2, 3, 4, 5] |"
+        - "| 2 | 1      | [6]             |"
+        - "| 3 |        |                 |"
+        - +---+--------+-----------------+
+    "###);
+
+    let error = execution
+        .run("SELECT reservoir_sample(x, 0) FROM VALUES (1) as tab(x)")
+        .await
+        .unwrap_err();
+    insta::assert_snapshot!(error, @r###"
+        Error during planning: reservoir_sample expects a positive n, got 0
+    "###);
+}
+
+#[tokio::test]
+async fn test_reservoir_sample_session_seed() {
+    let sql = "SELECT reservoir_sample(value, 5) AS sample FROM (SELECT unnest(range(1000)) AS value)";
+    let mut samples = vec![];
+    for _ in 0..2 {
+        let config = SessionConfig::new().with_option_extension(ExtraFunctionsConfig {
+            seed: Some(42),
+            ..Default::default()
+        });
+        let mut execution = TestExecution::new_with_config(config, None).await.unwrap();
+        samples.push(execution.run_and_format(sql).await);
+    }
+    assert_eq!(samples[0], samples[1]);
+}
+
+#[tokio::test]
+async fn test_percentile_by_weight_disc() {
+    let mut execution = TestExecution::new().await.unwrap();
+
+    let actual = execution
+        .run_and_format(
+            "SELECT g, percentile_by_weight_disc(x, w, 0.5) AS median, percentile_by_weight_disc(x, w, 0.9) AS p90 \
+            FROM VALUES (1, 10, 5), (1, 20, 1), (1, 30, 4), (1, NULL, 100), (1, 40, 0), (2, 7, 1), (3, 1, NULL) as tab(g, x, w) \
+            GROUP BY g ORDER BY g",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +---+--------+------+
+        - "| g | median | p90  |"
+        - +---+--------+------+
+        - "| 1 | 10.0   | 30.0 |"
+        - "| 2 | 7.0    | 7.0  |"
+        - "| 3 |        |      |"
+        - +---+--------+------+
+    "###);
+
+    let error = execution
+        .run("SELECT percentile_by_weight_disc(x, 1, 1.5) FROM VALUES (1) as tab(x)")
+        .await
+        .unwrap_err();
+    insta::assert_snapshot!(error, @r###"
+        Error during planning: percentile_by_weight_disc expects a q in [0, 1], got 1.5
+    "###);
+
+    let error = execution
+        .run("SELECT percentile_by_weight_disc(x, -1, 0.5) FROM VALUES (1) as tab(x)")
+        .await
+        .unwrap_err();
+    insta::assert_snapshot!(error, @r###"
+        Execution error: percentile_by_weight_disc expects non-negative weights, got -1
+    "###);
+}
+
+#[tokio::test]
+async fn test_list_flatten_agg() {
+    let mut execution = TestExecution::new().await.unwrap();
+
+    let actual = execution
+        .run_and_format(
+            "SELECT g, list_flatten_agg(x) AS flattened, list_flatten_agg(x, true) AS distinct_elements, \
+            list_flatten_agg(x, true, 2) AS capped \
+            FROM VALUES (1, [1, 2]), (1, NULL), (1, []), (1, [2, 3]), (2, []), (3, NULL) as tab(g, x) \
+            GROUP BY g ORDER BY g",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +---+--------------+-------------------+--------+
+        - "| g | flattened    | distinct_elements | capped |"
+        - +---+--------------+-------------------+--------+
+        - "| 1 | [1, 2, 2, 3] | [1, 2, 3]         | [1, 2] |"
+        - "| 2 | []           | []                | []     |"
+        - "| 3 |              |                   |        |"
+        - +---+--------------+-------------------+--------+
+    "###);
+
+    let error = execution
+        .run("SELECT list_flatten_agg(x, false, -1) FROM VALUES ([1]) as tab(x)")
+        .await
+        .unwrap_err();
+    insta::assert_snapshot!(error, @r###"
+        Error during planning: list_flatten_agg expects a non-negative max_length, got -1
+    "###);
+}
+
+#[tokio::test]
+async fn test_approx_count_distinct_hll() {
+    let mut execution = TestExecution::new().await.unwrap();
+
+    let actual = execution
+        .run_and_format(
+            "SELECT g, approx_count_distinct_hll(x) AS default_precision, approx_count_distinct_hll(x, 4) AS low_precision, \
+            arrow_typeof(approx_count_distinct_hll_sketch(x, 18)) AS sketch_type \
+            FROM VALUES (1, 'a'), (1, 'b'), (1, 'a'), (1, NULL), (2, 'c'), (3, NULL) as tab(g, x) \
+            GROUP BY g ORDER BY g",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +---+-------------------+---------------+-------------+
+        - "| g | default_precision | low_precision | sketch_type |"
+        - +---+-------------------+---------------+-------------+
+        - "| 1 | 2                 | 2             | Binary      |"
+        - "| 2 | 1                 | 1             | Binary      |"
+        - "| 3 | 0                 | 0             | Binary      |"
+        - +---+-------------------+---------------+-------------+
+    "###);
+
+    let actual = execution
+        .run_and_format("SELECT approx_count_distinct_hll(x) AS distinct_values FROM VALUES (1) as tab(x) WHERE x > 1")
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +-----------------+
+        - "| distinct_values |"
+        - +-----------------+
+        - "| 0               |"
+        - +-----------------+
+    "###);
+
+    let error = execution
+        .run("SELECT approx_count_distinct_hll(x, 19) FROM VALUES (1) as tab(x)")
+        .await
+        .unwrap_err();
+    insta::assert_snapshot!(error, @r###"
+        Error during planning: approx_count_distinct_hll expects a precision between 4 and 18, got 19
+    "###);
+}
+
+#[tokio::test]
+async fn test_struct_agg() {
+    let mut execution = TestExecution::new().await.unwrap();
+
+    let actual = execution
+        .run_and_format(
+            "SELECT g, struct_agg(x, y ORDER BY x DESC) AS by_x_desc, struct_agg(y ORDER BY x) AS by_x \
+            FROM VALUES (1, 1, 'a'), (1, 3, NULL), (1, 2, 'c'), (2, NULL, 'd') as tab(g, x, y) \
+            GROUP BY g ORDER BY g",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +---+-------------------------------------------------+----------------------------+
+        - "| g | by_x_desc                                       | by_x                       |"
+        - +---+-------------------------------------------------+----------------------------+
+        - "| 1 | [{c0: 3, c1: }, {c0: 2, c1: c}, {c0: 1, c1: a}] | [{c0: a}, {c0: c}, {c0: }] |"
+        - "| 2 | [{c0: , c1: d}]                                 | [{c0: d}]                  |"
+        - +---+-------------------------------------------------+----------------------------+
+    "###);
+
+    let actual = execution
+        .run_and_format("SELECT struct_agg(x) AS structs FROM VALUES (1) as tab(x) WHERE x > 1")
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +---------+
+        - "| structs |"
+        - +---------+
+        - "|         |"
+        - +---------+
+    "###);
+}
+
+#[tokio::test]
+async fn test_theta_sketch() {
+    let mut execution = TestExecution::new().await.unwrap();
+
+    let actual = execution
+        .run_and_format(
+            "WITH a AS (SELECT theta_sketch_agg(x) AS sketch FROM (SELECT unnest(range(1, 101)) AS x)), \
+            b AS (SELECT theta_sketch_agg(x, 16) AS sketch FROM (SELECT unnest(range(51, 121)) AS x)) \
+            SELECT theta_estimate(a.sketch) AS a, theta_estimate(theta_union(a.sketch, b.sketch)) AS a_union_b, \
+            theta_estimate(theta_intersect(a.sketch, b.sketch)) > 0 AS a_intersect_b_positive, \
+            theta_estimate(theta_union(a.sketch, NULL)) AS a_union_null FROM a, b",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +-------+------------------+------------------------+--------------+
+        - "| a     | a_union_b        | a_intersect_b_positive | a_union_null |"
+        - +-------+------------------+------------------------+--------------+
+        - "| 100.0 | 99.8540877950308 | true                   |              |"
+        - +-------+------------------+------------------------+--------------+
+    "###);
+
+    let actual = execution
+        .run_and_format(
+            "SELECT theta_estimate(theta_sketch_agg(x)) AS distinct_values FROM VALUES (1) as tab(x) WHERE x > 1",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +-----------------+
+        - "| distinct_values |"
+        - +-----------------+
+        - "| 0.0             |"
+        - +-----------------+
+    "###);
+
+    let error = execution
+        .run("SELECT theta_sketch_agg(x, 8) FROM VALUES (1) as tab(x)")
+        .await
+        .unwrap_err();
+    insta::assert_snapshot!(error, @r###"
+        Error during planning: theta_sketch_agg expects nominal_entries between 16 and 67108864, got 8
+    "###);
+}
+
+#[tokio::test]
+async fn test_unpivot() {
+    let mut execution = TestExecution::new()
+        .await
+        .unwrap()
+        .with_setup(
+            "CREATE TABLE metrics (host VARCHAR, cpu INT, memory DOUBLE, disk BIGINT) AS VALUES \
+            ('a', 1, 0.5, 10), ('b', NULL, 1.5, 20);",
+        )
+        .await;
+
+    let actual = execution
+        .run_and_format("SELECT * FROM unpivot(metrics, cpu, 'memory', disk) ORDER BY host, key")
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +------+--------+-------+
+        - "| host | key    | value |"
+        - +------+--------+-------+
+        - "| a    | cpu    | 1.0   |"
+        - "| a    | disk   | 10.0  |"
+        - "| a    | memory | 0.5   |"
+        - "| b    | disk   | 20.0  |"
+        - "| b    | memory | 1.5   |"
+        - +------+--------+-------+
+    "###);
+
+    let actual = execution
+        .run_and_format("SELECT key, arrow_typeof(value) AS value_type, count(*) AS rows FROM unpivot(metrics, cpu, disk) GROUP BY key, value_type ORDER BY key")
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +------+------------+------+
+        - "| key  | value_type | rows |"
+        - +------+------------+------+
+        - "| cpu  | Int64      | 1    |"
+        - "| disk | Int64      | 2    |"
+        - +------+------------+------+
+    "###);
+
+    let error = execution
+        .run("SELECT * FROM unpivot(metrics, host, cpu)")
+        .await
+        .unwrap_err();
+    insta::assert_snapshot!(error, @r###"
+        Error during planning: unpivot can't unify the types of the value columns: Utf8, Int32
+    "###);
+
+    let error = execution
+        .run("SELECT * FROM unpivot(metrics, cpu, gpu)")
+        .await
+        .unwrap_err();
+    insta::assert_snapshot!(error, @r###"
+        Error during planning: unpivot can't find value column gpu in table metrics
+    "###);
+}
+
+#[tokio::test]
+async fn test_count_min_sketch() {
+    let mut execution = TestExecution::new().await.unwrap();
+
+    let actual = execution
+        .run_and_format(
+            "WITH sketches AS (SELECT count_min_agg(CAST(x AS INT)) AS sketch, count_min_agg(x, 4, 2) AS small \
+            FROM VALUES (1), (2), (2), (3), (3), (3), (NULL) as tab(x)) \
+            SELECT v, cm_estimate(sketch, v) AS estimate, cm_estimate(small, v) >= cm_estimate(sketch, v) AS small_overestimates \
+            FROM sketches, VALUES (1), (2), (3), (4), (NULL) as lookups(v) ORDER BY v",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +---+----------+---------------------+
+        - "| v | estimate | small_overestimates |"
+        - +---+----------+---------------------+
+        - "| 1 | 1        | true                |"
+        - "| 2 | 2        | true                |"
+        - "| 3 | 3        | true                |"
+        - "| 4 | 0        | true                |"
+        - "|   |          |                     |"
+        - +---+----------+---------------------+
+    "###);
+
+    let error = execution
+        .run("SELECT count_min_agg(x, 8, 0) FROM VALUES (1) as tab(x)")
+        .await
+        .unwrap_err();
+    insta::assert_snapshot!(error, @r###"
+        Error during planning: count_min_agg expects a depth between 1 and 16, got 0
+    "###);
+}
+
+#[tokio::test]
+async fn test_pivot_agg() {
+    let mut execution = TestExecution::new().await.unwrap();
+
+    let actual = execution
+        .run_and_format(
+            "SELECT g, pivot_agg(region, sales, {'us': NULL, 'eu': NULL, 'apac': NULL}) AS by_region, \
+            pivot_agg(code, sales, named_struct('1', NULL)) AS by_code \
+            FROM VALUES (1, 'us', 10, 1), (1, 'eu', NULL, 2), (1, 'eu', 20, 1), (1, 'mars', 5, 1), (2, NULL, 30, 1) \
+            as tab(g, region, sales, code) GROUP BY g ORDER BY g",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +---+--------------------------+---------+
+        - "| g | by_region                | by_code |"
+        - +---+--------------------------+---------+
+        - "| 1 | {us: 10, eu: 20, apac: } | {1: 10} |"
+        - "| 2 | {us: , eu: , apac: }     | {1: 30} |"
+        - +---+--------------------------+---------+
+    "###);
+
+    let actual = execution
+        .run_and_format(
+            "SELECT arrow_typeof(pivot_agg(region, sales, {'us': NULL})['us']) AS us_type \
+            FROM VALUES ('us', 1.5) as tab(region, sales)",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +---------+
+        - "| us_type |"
+        - +---------+
+        - "| Float64 |"
+        - +---------+
+    "###);
+
+    let error = execution
+        .run("SELECT pivot_agg(region, sales, ['us', 'eu']) FROM VALUES ('us', 1) as tab(region, sales)")
+        .await
+        .unwrap_err();
+    insta::assert_snapshot!(error, @r###"
+        Error during planning: Execution error: User-defined coercion failed with Plan("pivot_agg expects the keys as a struct literal such as {'a': NULL, 'b': NULL}, got List(Field { name: \"item\", data_type: Utf8, nullable: true, dict_id: 0, dict_is_ordered: false, metadata: {} })") No function matches the given name and argument types 'pivot_agg(Utf8, Int64, List(Field { name: "item", data_type: Utf8, nullable: true, dict_id: 0, dict_is_ordered: false, metadata: {} }))'. You might need to add explicit type casts.
+        	Candidate functions:
+        	pivot_agg(UserDefined)
+    "###);
+}
+
+#[tokio::test]
+async fn test_bloom_filter() {
+    let mut execution = TestExecution::new().await.unwrap();
+
+    let actual = execution
+        .run_and_format(
+            "WITH filters AS (SELECT bloom_filter_agg(CAST(x AS INT)) AS filter \
+            FROM VALUES ('1'), ('2'), ('3'), (NULL) as tab(x)) \
+            SELECT v, bloom_contains(filter, v) AS contained, bloom_contains(NULL, v) AS null_filter \
+            FROM filters, VALUES (1), (3), (1000), (NULL) as lookups(v) ORDER BY v",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +------+-----------+-------------+
+        - "| v    | contained | null_filter |"
+        - +------+-----------+-------------+
+        - "| 1    | true      |             |"
+        - "| 3    | true      |             |"
+        - "| 1000 | false     |             |"
+        - "|      |           |             |"
+        - +------+-----------+-------------+
+    "###);
+
+    let actual = execution
+        .run_and_format(
+            "SELECT sum(CAST(bloom_contains(filter, v) AS INT)) AS false_positives \
+            FROM (SELECT bloom_filter_agg(x, 100, 0.01) AS filter FROM (SELECT unnest(range(0, 100)) AS x)), \
+            (SELECT unnest(range(100, 10100)) AS v)",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +-----------------+
+        - "| false_positives |"
+        - +-----------------+
+        - "| 123             |"
+        - +-----------------+
+    "###);
+
+    let error = execution
+        .run("SELECT bloom_filter_agg(x, 100, 1.5) FROM VALUES (1) as tab(x)")
+        .await
+        .unwrap_err();
+    insta::assert_snapshot!(error, @r###"
+        Error during planning: bloom_filter_agg expects an fpp between 0 and 1 exclusive, got 1.5
+    "###);
+}
+
+#[tokio::test]
+async fn test_intern() {
+    let mut execution = TestExecution::new()
+        .await
+        .unwrap()
+        .with_setup("CREATE TABLE t (region VARCHAR) AS VALUES ('us'), ('eu'), (NULL), ('us');")
+        .await;
+
+    let actual = execution
+        .run_and_format(
+            "SELECT intern(region) AS region, arrow_typeof(intern(region)) AS region_type, count(*) AS rows \
+            FROM t GROUP BY 1, 2 ORDER BY 1",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +--------+-------------------------+------+
+        - "| region | region_type             | rows |"
+        - +--------+-------------------------+------+
+        - "| eu     | Dictionary(Int32, Utf8) | 1    |"
+        - "| us     | Dictionary(Int32, Utf8) | 2    |"
+        - "|        | Dictionary(Int32, Utf8) | 1    |"
+        - +--------+-------------------------+------+
+    "###);
+
+    let actual = execution
+        .run_and_format("SELECT intern('us') = 'us' AS same, intern(NULL) IS NULL AS is_null")
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +------+---------+
+        - "| same | is_null |"
+        - +------+---------+
+        - "| true | true    |"
+        - +------+---------+
+    "###);
+
+    let error = execution.run("SELECT intern(1)").await.unwrap_err();
+    insta::assert_snapshot!(error, @r###"
+        Error during planning: Execution error: User-defined coercion failed with Plan("intern expects a string argument, got Int64") No function matches the given name and argument types 'intern(Int64)'. You might need to add explicit type casts.
+        	Candidate functions:
+        	intern(UserDefined)
+    "###);
+
+    let config = SessionConfig::new().with_option_extension(ExtraFunctionsConfig {
+        intern_strings: true,
+        ..Default::default()
+    });
+    let mut execution = TestExecution::new_with_config(config, None)
+        .await
+        .unwrap()
+        .with_setup(
+            "CREATE TABLE metrics (host VARCHAR, cpu INT, disk BIGINT) AS VALUES ('a', 1, 10), ('b', NULL, 20);",
+        )
+        .await;
+
+    let actual = execution
+        .run_and_format("SELECT host, key, arrow_typeof(key) AS key_type, value FROM unpivot(metrics, cpu, disk) ORDER BY host, key")
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +------+------+-------------------------+-------+
+        - "| host | key  | key_type                | value |"
+        - +------+------+-------------------------+-------+
+        - "| a    | cpu  | Dictionary(Int32, Utf8) | 1     |"
+        - "| a    | disk | Dictionary(Int32, Utf8) | 10    |"
+        - "| b    | disk | Dictionary(Int32, Utf8) | 20    |"
+        - +------+------+-------------------------+-------+
+    "###);
+}
+
+#[tokio::test]
+async fn test_minhash() {
+    let mut execution = TestExecution::new().await.unwrap();
+
+    let actual = execution
+        .run_and_format(
+            "WITH signatures AS (SELECT g, minhash_agg(x, 256) AS signature \
+            FROM (SELECT x % 2 AS g, x FROM (SELECT unnest(range(0, 1000)) AS x) \
+            UNION ALL SELECT 2 AS g, x FROM (SELECT unnest(range(0, 500)) AS x)) GROUP BY g) \
+            SELECT a.g AS a, b.g AS b, round(minhash_jaccard(a.signature, b.signature), 2) AS similarity \
+            FROM signatures a JOIN signatures b ON a.g < b.g ORDER BY a, b",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +---+---+------------+
+        - "| a | b | similarity |"
+        - +---+---+------------+
+        - "| 0 | 1 | 0.0        |"
+        - "| 0 | 2 | 0.36       |"
+        - "| 1 | 2 | 0.27       |"
+        - +---+---+------------+
+    "###);
+
+    let actual = execution
+        .run_and_format(
+            "WITH narrow AS (SELECT minhash_agg(CAST(x AS INT)) AS narrow FROM VALUES (1), (2), (NULL) as tab(x)), \
+            wide AS (SELECT minhash_agg(CAST(x AS BIGINT)) AS wide FROM VALUES (2), (1) as tab(x)), \
+            empty AS (SELECT minhash_agg(x) AS empty FROM VALUES (1) as tab(x) WHERE x > 1) \
+            SELECT minhash_jaccard(narrow, wide) AS same, minhash_jaccard(narrow, empty) AS disjoint, \
+            minhash_jaccard(empty, empty) AS both_empty, minhash_jaccard(narrow, NULL) AS null_signature \
+            FROM narrow, wide, empty",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +------+----------+------------+----------------+
+        - "| same | disjoint | both_empty | null_signature |"
+        - +------+----------+------------+----------------+
+        - "| 1.0  | 0.0      |            |                |"
+        - +------+----------+------------+----------------+
+    "###);
+
+    let error = execution
+        .run("SELECT minhash_jaccard(minhash_agg(x, 64), minhash_agg(x)) FROM VALUES (1) as tab(x)")
+        .await
+        .unwrap_err();
+    insta::assert_snapshot!(error, @r###"
+        Execution error: Can't combine MinHash signatures of 64 and 128 hashes
+    "###);
+
+    let error = execution
+        .run("SELECT minhash_agg(x, 0) FROM VALUES (1) as tab(x)")
+        .await
+        .unwrap_err();
+    insta::assert_snapshot!(error, @r###"
+        Error during planning: minhash_agg expects k between 1 and 16384, got 0
+    "###);
+}
+
+#[tokio::test]
+async fn test_mode_emits_sorted_groups_early() {
+    let groups: Vec<i64> = (0..100).map(|x| x / 10).collect();
+    let strings: Vec<String> = (0..100).map(|x| (x % 3).to_string()).collect();
+    let numbers: Vec<i64> = (0..100).map(|x| x % 4).collect();
+    let batch = RecordBatch::try_from_iter(vec![
+        ("g", Arc::new(Int64Array::from(groups)) as ArrayRef),
+        ("s", Arc::new(StringArray::from(strings)) as ArrayRef),
+        ("n", Arc::new(Int64Array::from(numbers)) as ArrayRef),
+    ])
+    .unwrap();
+    let batches = (0..100)
+        .step_by(8)
+        .map(|offset| batch.slice(offset, 8.min(100 - offset)))
+        .collect();
+    let config = SessionConfig::new().with_batch_size(8).with_target_partitions(1);
+    let mut execution = TestExecution::new_with_config(config, None)
+        .await
+        .unwrap()
+        .with_sorted_table("t", batches, "g");
+    let query = "SELECT g, mode(s) AS s, mode(n) AS n FROM t GROUP BY g";
+
+    let actual = execution.run_and_format(&format!("EXPLAIN {query}")).await;
+    assert!(
+        actual.iter().any(|line| line.contains("ordering_mode=Sorted")),
+        "{actual:#?}"
+    );
+
+    let actual = execution.run_and_format(&format!("{query} ORDER BY g")).await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +---+---+---+
+        - "| g | s | n |"
+        - +---+---+---+
+        - "| 0 | 0 | 0 |"
+        - "| 1 | 1 | 2 |"
+        - "| 2 | 2 | 0 |"
+        - "| 3 | 0 | 2 |"
+        - "| 4 | 1 | 0 |"
+        - "| 5 | 2 | 2 |"
+        - "| 6 | 0 | 0 |"
+        - "| 7 | 1 | 2 |"
+        - "| 8 | 2 | 0 |"
+        - "| 9 | 0 | 2 |"
+        - +---+---+---+
+    "###);
+}
+
+#[tokio::test]
+async fn test_approx_percentile() {
+    let mut execution = TestExecution::new()
+        .await
+        .unwrap()
+        .with_setup(
+            "CREATE TABLE daily AS SELECT x % 2 AS day, tdigest_agg(CAST(x AS INT)) AS digest \
+            FROM (SELECT unnest(range(0, 10000)) AS x) GROUP BY x % 2;",
+        )
+        .await;
+
+    let actual = execution
+        .run_and_format(
+            "SELECT round(approx_percentile(x, 0.5), 1) AS p50, round(approx_percentile(x, 0.99, 1000), 1) AS p99, \
+            (SELECT approx_percentile(x, 0.5) FROM VALUES (1.0) as tab(x) WHERE x < 0) AS empty \
+            FROM (SELECT unnest(range(0, 10000)) AS x)",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +--------+--------+-------+
+        - "| p50    | p99    | empty |"
+        - +--------+--------+-------+
+        - "| 4999.5 | 9899.5 |       |"
+        - +--------+--------+-------+
+    "###);
+
+    let actual = execution
+        .run_and_format(
+            "SELECT round(approx_percentile(digest, 0.5), 1) AS p50, round(approx_percentile(merged, 0.9), 1) AS p90 \
+            FROM daily, (SELECT tdigest_agg(digest) AS merged FROM daily) WHERE day = 0",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +--------+--------+
+        - "| p50    | p90    |"
+        - +--------+--------+
+        - "| 4999.0 | 8999.5 |"
+        - +--------+--------+
+    "###);
+
+    let error = execution
+        .run("SELECT approx_percentile(x, 1.5) FROM VALUES (1) as tab(x)")
+        .await
+        .unwrap_err();
+    insta::assert_snapshot!(error, @r###"
+        Error during planning: approx_percentile expects a q between 0 and 1, got 1.5
+    "###);
+
+    let error = execution
+        .run("SELECT approx_percentile(x, 0.5) FROM VALUES ('a') as tab(x)")
+        .await
+        .unwrap_err();
+    insta::assert_snapshot!(error, @r###"
+        Error during planning: Execution error: User-defined coercion failed with Plan("approx_percentile expects numbers or t-digests, got Utf8") No function matches the given name and argument types 'approx_percentile(Utf8, Float64)'. You might need to add explicit type casts.
+        	Candidate functions:
+        	approx_percentile(UserDefined)
+    "###);
+}
+
+#[tokio::test]
+async fn test_list_index() {
+    let mut execution = TestExecution::new().await.unwrap();
+
+    let actual = execution
+        .run_and_format(
+            "SELECT id, array_position_all(l, v) AS positions, list_index_of_max(l) AS max, list_index_of_min(l) AS min \
+            FROM VALUES (1, [3, 1, NULL, 3, 2], 3), (2, [1, 2], 5), (3, [], 1), (4, [NULL, NULL], 1), \
+            (5, NULL, 1), (6, [2, 2], NULL) as tab(id, l, v) ORDER BY id",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +----+-----------+-----+-----+
+        - "| id | positions | max | min |"
+        - +----+-----------+-----+-----+
+        - "| 1  | [1, 4]    | 1   | 2   |"
+        - "| 2  | []        | 2   | 1   |"
+        - "| 3  | []        |     |     |"
+        - "| 4  | []        |     |     |"
+        - "| 5  |           |     |     |"
+        - "| 6  |           | 1   | 1   |"
+        - +----+-----------+-----+-----+
+    "###);
+
+    let actual = execution
+        .run_and_format(
+            "SELECT array_position_all([1.5, 2.0, 1.5], 1.5) AS floats, array_position_all(['a', 'b', 'a'], 'a') AS strings, \
+            array_position_all([[1], [2], [1]], [1]) AS lists, list_index_of_max([1.0, 'NaN', 2.0]) AS nan_max, \
+            list_index_of_min(['b', 'a', 'a']) AS string_min",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +--------+---------+--------+---------+------------+
+        - "| floats | strings | lists  | nan_max | string_min |"
+        - +--------+---------+--------+---------+------------+
+        - "| [1, 3] | [1, 3]  | [1, 3] | 2       | 2          |"
+        - +--------+---------+--------+---------+------------+
+    "###);
+
+    let error = execution.run("SELECT list_index_of_max(1)").await.unwrap_err();
+    insta::assert_snapshot!(error, @r###"
+        Error during planning: Execution error: User-defined coercion failed with Plan("list_index_of_max expects a list, got Int64") No function matches the given name and argument types 'list_index_of_max(Int64)'. You might need to add explicit type casts.
+        	Candidate functions:
+        	list_index_of_max(UserDefined)
+    "###);
+}
+
+#[tokio::test]
+async fn test_approx_quantiles() {
+    let mut execution = TestExecution::new().await.unwrap();
+
+    let actual = execution
+        .run_and_format(
+            "SELECT x % 2 AS g, approx_quantiles(x, [0.9, 0, 0.5, 1]) AS quantiles \
+            FROM (SELECT unnest(range(0, 1001)) AS x) GROUP BY g ORDER BY g",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +---+-----------------------------------------+
+        - "| g | quantiles                               |"
+        - +---+-----------------------------------------+
+        - "| 0 | [900.8000000000001, 0.0, 500.0, 1000.0] |"
+        - "| 1 | [900.0, 1.0, 500.0, 999.0]              |"
+        - +---+-----------------------------------------+
+    "###);
+
+    let actual = execution
+        .run_and_format(
+            "WITH digests AS (SELECT tdigest_agg(x) AS digest FROM (SELECT unnest(range(0, 101)) AS x) GROUP BY x % 3), \
+            empty AS (SELECT approx_quantiles(x, [0.5]) AS empty FROM VALUES (1.0) as tab(x) WHERE x > 1) \
+            SELECT approx_quantiles(digest, [0.25, 0.75], 200) AS merged, (SELECT empty FROM empty) AS empty FROM digests",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +----------------+-------+
+        - "| merged         | empty |"
+        - +----------------+-------+
+        - "| [24.75, 75.25] |       |"
+        - +----------------+-------+
+    "###);
+
+    let error = execution
+        .run("SELECT approx_quantiles(x, [0.5, 1.5]) FROM VALUES (1) as tab(x)")
+        .await
+        .unwrap_err();
+    insta::assert_snapshot!(error, @r###"
+        Error during planning: approx_quantiles expects a q between 0 and 1, got 1.5
+    "###);
+
+    let error = execution
+        .run("SELECT approx_quantiles(x, []) FROM VALUES (1) as tab(x)")
+        .await
+        .unwrap_err();
+    insta::assert_snapshot!(error, @r###"
+        Error during planning: approx_quantiles expects a non-empty list of q
+    "###);
+}
+
+#[tokio::test]
+async fn test_generate_dates_between() {
+    let mut execution = TestExecution::new()
+        .await
+        .unwrap()
+        .with_setup(
+            "CREATE TABLE bookings (id INT, check_in DATE, check_out TIMESTAMP) AS VALUES \
+            (1, DATE '2024-02-27', TIMESTAMP '2024-03-01 11:00:00'), (2, DATE '2024-03-05', TIMESTAMP '2024-03-05 10:00:00'), \
+            (3, DATE '2024-03-05', TIMESTAMP '2024-03-04 10:00:00'), (4, NULL, TIMESTAMP '2024-03-04 10:00:00');",
+        )
+        .await;
+
+    let actual = execution
+        .run_and_format("SELECT * FROM generate_dates_between(bookings, check_in, 'check_out') ORDER BY id, date")
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +----+------------+---------------------+------------+
+        - "| id | check_in   | check_out           | date       |"
+        - +----+------------+---------------------+------------+
+        - "| 1  | 2024-02-27 | 2024-03-01T11:00:00 | 2024-02-27 |"
+        - "| 1  | 2024-02-27 | 2024-03-01T11:00:00 | 2024-02-28 |"
+        - "| 1  | 2024-02-27 | 2024-03-01T11:00:00 | 2024-02-29 |"
+        - "| 1  | 2024-02-27 | 2024-03-01T11:00:00 | 2024-03-01 |"
+        - "| 2  | 2024-03-05 | 2024-03-05T10:00:00 | 2024-03-05 |"
+        - +----+------------+---------------------+------------+
+    "###);
+
+    let error = execution
+        .run("SELECT * FROM generate_dates_between(bookings, check_in, check_out, 3)")
+        .await
+        .unwrap_err();
+    insta::assert_snapshot!(error, @r###"
+        Execution error: generate_dates_between expects at most 3 dates per row, got 4 from 2024-02-27 to 2024-03-01
+    "###);
+
+    let error = execution
+        .run("SELECT * FROM generate_dates_between(bookings, id, check_out)")
+        .await
+        .unwrap_err();
+    insta::assert_snapshot!(error, @r###"
+        Error during planning: generate_dates_between expects a date or timestamp column, got id of type Int32
+    "###);
+}
+
+#[tokio::test]
+async fn test_percentile_weighted() {
+    let mut execution = TestExecution::new().await.unwrap();
+
+    let actual = execution
+        .run_and_format(
+            "SELECT g, percentile_weighted(v, w, 0.5) AS exact, round(percentile_weighted(v, w, 0.5, 'approx'), 1) AS approx \
+            FROM VALUES ('a', 1, 1), ('a', 2, 2), ('a', 3, 1), ('a', NULL, 5), ('a', 9, 0), ('b', 1, 1), ('b', 2, 1), \
+            ('c', 1, NULL) as tab(g, v, w) GROUP BY g ORDER BY g",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +---+-------+--------+
+        - "| g | exact | approx |"
+        - +---+-------+--------+
+        - "| a | 2.0   | 2.0    |"
+        - "| b | 1.5   | 1.5    |"
+        - "| c |       |        |"
+        - +---+-------+--------+
+    "###);
+
+    let error = execution
+        .run("SELECT percentile_weighted(v, w, 0.5) FROM VALUES (1, -1) as tab(v, w)")
+        .await
+        .unwrap_err();
+    insta::assert_snapshot!(error, @r###"
+        Execution error: percentile_weighted expects non-negative weights, got -1
+    "###);
+
+    let error = execution
+        .run("SELECT percentile_weighted(v, w, 0.5, 'median') FROM VALUES (1, 1) as tab(v, w)")
+        .await
+        .unwrap_err();
+    insta::assert_snapshot!(error, @r###"
+        Error during planning: percentile_weighted expects a method of 'exact' or 'approx', got median
+    "###);
+}
+
+#[tokio::test]
+async fn test_business_days() {
+    let mut execution = TestExecution::new().await.unwrap();
+
+    let actual = execution
+        .run_and_format(
+            "SELECT calendar, holiday(DATE '2024-12-26', calendar) AS boxing_day, \
+            business_days_between(DATE '2024-12-01', DATE '2025-01-01', calendar) AS december, \
+            business_days_between(DATE '2025-01-01', DATE '2024-12-01', calendar) AS backwards \
+            FROM VALUES ('us'), ('EU'), ('weekends'), (NULL) as tab(calendar) ORDER BY calendar",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +----------+------------+----------+-----------+
+        - "| calendar | boxing_day | december | backwards |"
+        - +----------+------------+----------+-----------+
+        - "| EU       | true       | 20       | -20       |"
+        - "| us       | false      | 21       | -21       |"
+        - "| weekends | false      | 22       | -22       |"
+        - "|          |            |          |           |"
+        - +----------+------------+----------+-----------+
+    "###);
+
+    let error = execution
+        .run("SELECT holiday(DATE '2024-12-26', 'acme')")
+        .await
+        .unwrap_err();
+    insta::assert_snapshot!(error, @r###"
+        Execution error: holiday can't find calendar 'acme', expected one of us, eu, weekends
+    "###);
+
+    let error = execution
+        .run("SELECT business_days_between(DATE '2024-12-01', DATE '2300-01-01', 'us')")
+        .await
+        .unwrap_err();
+    insta::assert_snapshot!(error, @r###"
+        Execution error: business_days_between supports dates from 1900-01-01 to 2199-12-31, got 2300-01-01
+    "###);
+
+    let mut config = SessionConfig::new().with_option_extension(ExtraFunctionsConfig::default());
+    config
+        .options_mut()
+        .set("datafusion_functions_extra.calendars.acme", "2024-12-24, 2024-12-31")
+        .unwrap();
+    let mut execution = TestExecution::new_with_config(config, None).await.unwrap();
+
+    let actual = execution
+        .run_and_format(
+            "SELECT holiday(d, 'acme') AS holiday, business_days_between(d, d + INTERVAL '8 days', 'Acme') AS business_days \
+            FROM VALUES (DATE '2024-12-24'), (DATE '2024-12-25') as tab(d) ORDER BY d",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +---------+---------------+
+        - "| holiday | business_days |"
+        - +---------+---------------+
+        - "| true    | 4             |"
+        - "| false   | 5             |"
+        - +---------+---------------+
+    "###);
+}
+
+#[tokio::test]
+async fn test_quantile_exact() {
+    let mut execution = TestExecution::new().await.unwrap();
+
+    let actual = execution
+        .run_and_format(
+            "SELECT quantile_exact(x, 0.3) AS linear, quantile_exact(x, 0.3, 'lower') AS lower, \
+            quantile_exact(x, 0.3, 'higher') AS higher, quantile_exact(x, 0.3, 'nearest') AS nearest, \
+            quantile_exact(x, 0.3, 'midpoint') AS midpoint, quantile_exact(x, 0.3, 'exclusive') AS exclusive \
+            FROM VALUES (1), (2), (NULL), (3), (4), (10) as tab(x)",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +--------+-------+--------+---------+----------+--------------------+
+        - "| linear | lower | higher | nearest | midpoint | exclusive          |"
+        - +--------+-------+--------+---------+----------+--------------------+
+        - "| 2.2    | 2.0   | 3.0    | 2.0     | 2.5      | 1.7999999999999998 |"
+        - +--------+-------+--------+---------+----------+--------------------+
+    "###);
+
+    let actual = execution
+        .run_and_format(
+            "SELECT quantile_exact(x, 0.5) AS median, quantile_exact(x, 0.9, 'EXCLUSIVE') AS clamped \
+            FROM VALUES (1), (2), (3), (4) as tab(x)",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +--------+---------+
+        - "| median | clamped |"
+        - +--------+---------+
+        - "| 2.5    | 4.0     |"
+        - +--------+---------+
+    "###);
+
+    let error = execution
+        .run("SELECT quantile_exact(x, 0.5, 'median') FROM VALUES (1) as tab(x)")
+        .await
+        .unwrap_err();
+    insta::assert_snapshot!(error, @r###"
+        Error during planning: quantile_exact expects a method of 'linear', 'inclusive', 'lower', 'higher', 'nearest', 'midpoint' or 'exclusive', got median
+    "###);
+}
+
+#[tokio::test]
+async fn test_fiscal_year() {
+    let mut execution = TestExecution::new().await.unwrap();
+
+    let actual = execution
+        .run_and_format(
+            "SELECT d, fiscal_year(d) AS calendar_year, fiscal_year(d, 10) AS us_federal, fiscal_quarter(d, 10) AS us_quarter, \
+            fiscal_year(CAST(d AS TIMESTAMP), m) AS by_row, fiscal_quarter(d, m) AS quarter_by_row \
+            FROM VALUES (DATE '2024-09-30', 4), (DATE '2024-10-01', 4), (DATE '2025-03-31', 4), (DATE '2025-04-01', NULL), \
+            (NULL, 4) as tab(d, m) ORDER BY d",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +------------+---------------+------------+------------+--------+----------------+
+        - "| d          | calendar_year | us_federal | us_quarter | by_row | quarter_by_row |"
+        - +------------+---------------+------------+------------+--------+----------------+
+        - "| 2024-09-30 | 2024          | 2024       | 4          | 2025   | 2              |"
+        - "| 2024-10-01 | 2024          | 2025       | 1          | 2025   | 3              |"
+        - "| 2025-03-31 | 2025          | 2025       | 2          | 2025   | 4              |"
+        - "| 2025-04-01 | 2025          | 2025       | 3          |        |                |"
+        - "|            |               |            |            |        |                |"
+        - +------------+---------------+------------+------------+--------+----------------+
+    "###);
+
+    let error = execution
+        .run("SELECT fiscal_year(DATE '2024-01-01', 13)")
+        .await
+        .unwrap_err();
+    insta::assert_snapshot!(error, @r###"
+        Execution error: fiscal_year expects a start month from 1 to 12, got 13
+    "###);
+
+    let mut config = SessionConfig::new().with_option_extension(ExtraFunctionsConfig::default());
+    config
+        .options_mut()
+        .set("datafusion_functions_extra.fiscal_year_start_month", "7")
+        .unwrap();
+    let mut execution = TestExecution::new_with_config(config, None).await.unwrap();
+
+    let actual = execution
+        .run_and_format(
+            "SELECT fiscal_year(DATE '2024-07-01') AS fiscal_year, fiscal_quarter(DATE '2024-07-01') AS fiscal_quarter, \
+            fiscal_year(DATE '2024-07-01', 1) AS calendar_year",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +-------------+----------------+---------------+
+        - "| fiscal_year | fiscal_quarter | calendar_year |"
+        - +-------------+----------------+---------------+
+        - "| 2025        | 1              | 2024          |"
+        - +-------------+----------------+---------------+
+    "###);
+}
+
+#[tokio::test]
+async fn test_session_options_set_after_registration() {
+    let config = SessionConfig::new().with_option_extension(ExtraFunctionsConfig::default());
+    let mut execution = TestExecution::new_with_config(config, None)
+        .await
+        .unwrap()
+        .with_setup("CREATE TABLE metrics (host VARCHAR, cpu INT) AS VALUES ('a', 1);")
+        .await;
+    let sql =
+        "SELECT fiscal_year(DATE '2024-11-01') AS fiscal_year, fiscal_quarter(DATE '2024-11-01') AS fiscal_quarter, \
+        arrow_typeof(key) AS key_type FROM unpivot(metrics, cpu)";
+
+    let actual = execution.run_and_format(sql).await;
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +-------------+----------------+----------+
+        - "| fiscal_year | fiscal_quarter | key_type |"
+        - +-------------+----------------+----------+
+        - "| 2024        | 4              | Utf8     |"
+        - +-------------+----------------+----------+
+    "###);
+    let holiday = "SELECT holiday(DATE '2024-12-24', 'acme') AS holiday";
+    let error = execution.run(holiday).await.unwrap_err();
+    insta::assert_snapshot!(error, @r###"
+        Execution error: holiday can't find calendar 'acme', expected one of us, eu, weekends
+    "###);
+
+    for set in [
+        "SET datafusion_functions_extra.fiscal_year_start_month = 10",
+        "SET datafusion_functions_extra.calendars.acme = '2024-12-24'",
+        "SET datafusion_functions_extra.intern_strings = true",
+    ] {
+        execution.run(set).await.unwrap();
+    }
+    let actual = execution.run_and_format(sql).await;
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +-------------+----------------+-------------------------+
+        - "| fiscal_year | fiscal_quarter | key_type                |"
+        - +-------------+----------------+-------------------------+
+        - "| 2025        | 1              | Dictionary(Int32, Utf8) |"
+        - +-------------+----------------+-------------------------+
+    "###);
+    let actual = execution.run_and_format(holiday).await;
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +---------+
+        - "| holiday |"
+        - +---------+
+        - "| true    |"
+        - +---------+
+    "###);
+
+    let sample = "SELECT reservoir_sample(value, 5) AS sample FROM (SELECT unnest(range(1000)) AS value)";
+    let seeded = "SELECT reservoir_sample(value, 5, 42) AS sample FROM (SELECT unnest(range(1000)) AS value)";
+    execution.run("SET datafusion_functions_extra.seed = 42").await.unwrap();
+    assert_eq!(
+        execution.run_and_format(sample).await,
+        execution.run_and_format(seeded).await
+    );
+}
+
+#[tokio::test]
+async fn test_histogram() {
+    let mut execution = TestExecution::new().await.unwrap();
+
+    let actual = execution
+        .run_and_format(
+            "SELECT x % 2 AS g, histogram(x, 4, 0, 100) AS fixed FROM (SELECT unnest(range(0, 120, 7)) AS x) \
+            GROUP BY g ORDER BY g",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +---+----------------------------------------------------------------------------------------------------------------------------------------------------------+
+        - "| g | fixed                                                                                                                                                    |"
+        - +---+----------------------------------------------------------------------------------------------------------------------------------------------------------+
+        - "| 0 | [{lower: 0.0, upper: 25.0, count: 2}, {lower: 25.0, upper: 50.0, count: 2}, {lower: 50.0, upper: 75.0, count: 2}, {lower: 75.0, upper: 100.0, count: 2}] |"
+        - "| 1 | [{lower: 0.0, upper: 25.0, count: 2}, {lower: 25.0, upper: 50.0, count: 2}, {lower: 50.0, upper: 75.0, count: 1}, {lower: 75.0, upper: 100.0, count: 2}] |"
+        - +---+----------------------------------------------------------------------------------------------------------------------------------------------------------+
+    "###);
+
+    let actual = execution
+        .run_and_format(
+            "WITH data AS (SELECT histogram(x, 3) AS data FROM VALUES (1.0), (2.0), (4.0), (NULL), (7.0) as tab(x)), \
+            same AS (SELECT histogram(x, 3) AS same FROM VALUES (5), (5) as tab(x)), \
+            empty AS (SELECT histogram(x, 3, 0, 1) AS empty FROM VALUES (5) as tab(x)) \
+            SELECT data, same, empty FROM data, same, empty",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +--------------------------------------------------------------------------------------------------------------+--------------------------------------+-------+
+        - "| data                                                                                                         | same                                 | empty |"
+        - +--------------------------------------------------------------------------------------------------------------+--------------------------------------+-------+
+        - "| [{lower: 1.0, upper: 3.0, count: 2}, {lower: 3.0, upper: 5.0, count: 1}, {lower: 5.0, upper: 7.0, count: 1}] | [{lower: 5.0, upper: 5.0, count: 2}] |       |"
+        - +--------------------------------------------------------------------------------------------------------------+--------------------------------------+-------+
+    "###);
+
+    let error = execution
+        .run("SELECT histogram(x, 2, 1, 1) FROM VALUES (1) as tab(x)")
+        .await
+        .unwrap_err();
+    insta::assert_snapshot!(error, @r###"
+        Error during planning: histogram expects finite bounds with min < max, got 1 and 1
+    "###);
+}
+
+#[tokio::test]
+async fn test_approx_mode_by_time_decay() {
+    let mut execution = TestExecution::new().await.unwrap();
+
+    let actual = execution
+        .run_and_format(
+            "SELECT user_id, approx_mode_by_time_decay(category, CAST(ts AS TIMESTAMP), INTERVAL '7 days') AS favorite \
+            FROM VALUES \
+                (1, 'books', '2024-01-01'), (1, 'books', '2024-01-02'), (1, 'books', '2024-01-03'), \
+                (1, 'games', '2024-02-01'), (1, 'games', '2024-02-02'), \
+                (2, 'music', '2024-01-01'), (2, 'music', '2024-01-02'), (2, 'games', '2024-01-03'), \
+                (2, NULL, '2024-05-01'), (2, 'games', NULL) \
+            as tab(user_id, category, ts) GROUP BY user_id ORDER BY user_id",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +---------+----------+
+        - "| user_id | favorite |"
+        - +---------+----------+
+        - "| 1       | games    |"
+        - "| 2       | music    |"
+        - +---------+----------+
+    "###);
+
+    let actual = execution
+        .run_and_format(
+            "SELECT approx_mode_by_time_decay(x, ts, 10) AS recent, approx_mode_by_time_decay(x, ts, 1000) AS frequent \
+            FROM VALUES ('a', 0), ('a', 1), ('a', 2), ('b', 30) as tab(x, ts)",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +--------+----------+
+        - "| recent | frequent |"
+        - +--------+----------+
+        - "| b      | a        |"
+        - +--------+----------+
+    "###);
+
+    let err = execution
+        .run("SELECT approx_mode_by_time_decay(x, ts, 0) FROM VALUES ('a', 0) as tab(x, ts)")
+        .await
+        .unwrap_err();
+    insta::assert_snapshot!(err, @r###"
+        Error during planning: approx_mode_by_time_decay expects a positive half_life, got 0
+    "###);
+}
+
+#[tokio::test]
+async fn test_histogram_equi_depth() {
+    let mut execution = TestExecution::new().await.unwrap();
+
+    let actual = execution
+        .run_and_format(
+            "SELECT x % 2 AS g, histogram_equi_depth(x, 4) AS buckets FROM (SELECT unnest(range(0, 20)) AS x) \
+            GROUP BY g ORDER BY g",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +---+-------------------------------------------------------------------------------------------------------------------------------------------------------+
+        - "| g | buckets                                                                                                                                               |"
+        - +---+-------------------------------------------------------------------------------------------------------------------------------------------------------+
+        - "| 0 | [{lower: 0.0, upper: 4.0, count: 2}, {lower: 4.0, upper: 9.0, count: 3}, {lower: 9.0, upper: 14.0, count: 2}, {lower: 14.0, upper: 18.0, count: 3}]   |"
+        - "| 1 | [{lower: 1.0, upper: 5.0, count: 2}, {lower: 5.0, upper: 10.0, count: 3}, {lower: 10.0, upper: 15.0, count: 2}, {lower: 15.0, upper: 19.0, count: 3}] |"
+        - +---+-------------------------------------------------------------------------------------------------------------------------------------------------------+
+    "###);
+
+    let actual = execution
+        .run_and_format(
+            "SELECT histogram_equi_depth(x, 3) AS skewed FROM VALUES (1), (1), (1), (1), (1), (1), (2), (3), (NULL) as tab(x)",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +--------------------------------------------------------------------------+
+        - "| skewed                                                                   |"
+        - +--------------------------------------------------------------------------+
+        - "| [{lower: 1.0, upper: 1.0, count: 5}, {lower: 1.0, upper: 3.0, count: 3}] |"
+        - +--------------------------------------------------------------------------+
+    "###);
+}
+
+#[tokio::test]
+async fn test_decayed_sum() {
+    let mut execution = TestExecution::new().await.unwrap();
+
+    let actual = execution
+        .run_and_format(
+            "SELECT user_id, \
+                decayed_sum(amount, CAST(ts AS TIMESTAMP), INTERVAL '1 day') AS amount, \
+                decayed_count(CAST(ts AS DATE), INTERVAL '1 day') AS visits \
+            FROM VALUES \
+                (1, 40.0, '2024-01-01'), (1, 10.0, '2024-01-03'), (1, NULL, '2024-01-02'), \
+                (2, 5.0, '2024-01-01 12:00:00'), (2, 7.0, NULL) \
+            as tab(user_id, amount, ts) GROUP BY user_id ORDER BY user_id",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +---------+--------+--------+
+        - "| user_id | amount | visits |"
+        - +---------+--------+--------+
+        - "| 1       | 20.0   | 1.75   |"
+        - "| 2       | 5.0    | 1.0    |"
+        - +---------+--------+--------+
+    "###);
+
+    let actual = execution
+        .run_and_format(
+            "SELECT decayed_sum(x, ts, 2) AS sum, decayed_count(ts, 2) AS count \
+            FROM VALUES (1, 0), (2, 4), (NULL, 6) as tab(x, ts) WHERE ts > 10",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +-----+-------+
+        - "| sum | count |"
+        - +-----+-------+
+        - "|     | 0.0   |"
+        - +-----+-------+
+    "###);
+}
+
+#[tokio::test]
+async fn test_decayed_avg() {
+    let mut execution = TestExecution::new().await.unwrap();
+
+    let actual = execution
+        .run_and_format(
+            "SELECT user_id, decayed_avg(amount, CAST(ts AS TIMESTAMP), INTERVAL '1 day') AS avg_amount \
+            FROM VALUES \
+                (1, 40.0, '2024-01-01'), (1, 10.0, '2024-01-03'), (1, NULL, '2024-01-02'), \
+                (2, 5.0, '2024-01-01 12:00:00'), (2, 7.0, NULL) \
+            as tab(user_id, amount, ts) GROUP BY user_id ORDER BY user_id",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +---------+------------+
+        - "| user_id | avg_amount |"
+        - +---------+------------+
+        - "| 1       | 16.0       |"
+        - "| 2       | 5.0        |"
+        - +---------+------------+
+    "###);
+
+    let actual = execution
+        .run_and_format(
+            "SELECT decayed_avg(x, ts, 2) AS avg FROM VALUES (1, 0), (2, 4), (NULL, 6) as tab(x, ts) WHERE ts > 10",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +-----+
+        - "| avg |"
+        - +-----+
+        - "|     |"
+        - +-----+
+    "###);
+}
+
+#[tokio::test]
+async fn test_freq_map() {
+    let mut execution = TestExecution::new().await.unwrap();
+
+    let actual = execution
+        .run_and_format(
+            "SELECT g, freq_map(x) AS counts FROM VALUES (1, 'a'), (1, 'b'), (1, 'a'), (1, NULL), (2, NULL) \
+            as tab(g, x) GROUP BY g ORDER BY g",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +---+--------------+
+        - "| g | counts       |"
+        - +---+--------------+
+        - "| 1 | {a: 2, b: 1} |"
+        - "| 2 |              |"
+        - +---+--------------+
+    "###);
+
+    let actual = execution
+        .run_and_format("SELECT freq_map(x) AS counts FROM VALUES (2), (1), (2) as tab(x)")
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +--------------+
+        - "| counts       |"
+        - +--------------+
+        - "| {2: 2, 1: 1} |"
+        - +--------------+
+    "###);
+
+    let actual = execution
+        .run_and_format("SELECT map_extract(freq_map(x), 'b') AS b FROM VALUES ('a'), ('b'), ('b') as tab(x)")
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +-----+
+        - "| b   |"
+        - +-----+
+        - "| [2] |"
+        - +-----+
+    "###);
+}
+
+#[tokio::test]
+async fn test_numeric_coercion() {
+    let mut execution = TestExecution::new().await.unwrap();
+
+    let actual = execution
+        .run_and_format(
+            "SELECT arrow_typeof(product(i)) AS product_int, arrow_typeof(product(u)) AS product_uint, \
+                arrow_typeof(product(d)) AS product_decimal, product(d) AS product, \
+                arrow_typeof(weighted_avg(d, u)) AS weighted_avg, arrow_typeof(kurtosis_pop(i)) AS kurtosis_pop, \
+                arrow_typeof(cv(d)) AS cv \
+            FROM (SELECT CAST(x AS TINYINT) AS i, CAST(x AS INT UNSIGNED) AS u, CAST(x AS DECIMAL(10, 2)) AS d \
+                FROM VALUES (1.5), (2.0), (3.0) as tab(x))",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +-------------+--------------+-----------------+---------+--------------+--------------+---------+
+        - "| product_int | product_uint | product_decimal | product | weighted_avg | kurtosis_pop | cv      |"
+        - +-------------+--------------+-----------------+---------+--------------+--------------+---------+
+        - "| Int64       | Int64        | Float64         | 9.0     | Float64      | Float64      | Float64 |"
+        - +-------------+--------------+-----------------+---------+--------------+--------------+---------+
+    "###);
+
+    let err = execution
+        .run("SELECT weighted_avg(x, w) FROM VALUES (1, 'a') as tab(x, w)")
+        .await
+        .unwrap_err();
+    insta::assert_snapshot!(err, @r###"
+        Error during planning: Execution error: User-defined coercion failed with Plan("weighted_avg expects a numeric weight, got Utf8") No function matches the given name and argument types 'weighted_avg(Int64, Utf8)'. You might need to add explicit type casts.
+        	Candidate functions:
+        	weighted_avg(UserDefined)
+    "###);
+}
+
+#[tokio::test]
+async fn test_histogram_numeric() {
+    let mut execution = TestExecution::new().await.unwrap();
+
+    let actual = execution
+        .run_and_format("SELECT histogram_numeric(x, 3) FROM VALUES (1.0), (2.0), (10.0), (11.0), (30.0), (NULL) t(x)")
+        .await;
+    insta::assert_snapshot!(actual.join("\n"), @r###"
+        +----------------------------------------------------------+
+        | histogram_numeric(t.x,Int64(3))                          |
+        +----------------------------------------------------------+
+        | [{x: 1.5, y: 2.0}, {x: 10.5, y: 2.0}, {x: 30.0, y: 1.0}] |
+        +----------------------------------------------------------+
+    "###);
+
+    let actual = execution
+        .run_and_format("SELECT histogram_numeric(x, 2) FROM VALUES (1), (2), (3), (10) t(x)")
+        .await;
+    insta::assert_snapshot!(actual.join("\n"), @r###"
+        +-----------------------------------+
+        | histogram_numeric(t.x,Int64(2))   |
+        +-----------------------------------+
+        | [{x: 2, y: 3.0}, {x: 10, y: 1.0}] |
+        +-----------------------------------+
+    "###);
+
+    let actual = execution
+        .run_and_format("SELECT histogram_numeric(x, 2) FROM VALUES (CAST(NULL AS DOUBLE)) t(x)")
+        .await;
+    insta::assert_snapshot!(actual.join("\n"), @r###"
+        +---------------------------------+
+        | histogram_numeric(t.x,Int64(2)) |
+        +---------------------------------+
+        |                                 |
+        +---------------------------------+
+    "###);
+
+    let err = execution
+        .run("SELECT histogram_numeric(x, 1) FROM VALUES (1.0) t(x)")
+        .await
+        .unwrap_err();
+    insta::assert_snapshot!(err, @r###"
+        Error during planning: histogram_numeric expects nb between 2 and 100000, got 1
+    "###);
+}
+
+#[tokio::test]
+async fn test_lead_lag_diff() {
+    let mut execution = TestExecution::new().await.unwrap();
+
+    let actual = execution
+        .run_and_format(
+            "SELECT g, x, lead_lag_diff(x) OVER (PARTITION BY g ORDER BY t) AS diff, lead_lag_ratio(x) OVER (PARTITION BY g ORDER BY t) AS ratio \
+             FROM VALUES ('a', 1, 10), ('a', 2, 15), ('a', 3, NULL), ('a', 4, 0), ('a', 5, 3), ('b', 1, 7) t(g, t, x) ORDER BY g, t",
+        )
+        .await;
+    insta::assert_snapshot!(actual.join("\n"), @r###"
+        +---+----+------+-------+
+        | g | x  | diff | ratio |
+        +---+----+------+-------+
+        | a | 10 |      |       |
+        | a | 15 | 5    | 1.5   |
+        | a |    |      |       |
+        | a | 0  |      |       |
+        | a | 3  | 3    |       |
+        | b | 7  |      |       |
+        +---+----+------+-------+
+    "###);
+
+    let actual = execution
+        .run_and_format(
+            "SELECT lead_lag_diff(ts) OVER (ORDER BY ts) AS ts_diff, lead_lag_diff(d) OVER (ORDER BY ts) AS date_diff \
+             FROM (SELECT ts, CAST(ts AS DATE) AS d FROM VALUES (TIMESTAMP '2024-01-01 00:00:00'), (TIMESTAMP '2024-01-02 06:00:00'), \
+             (TIMESTAMP '2024-01-05 00:00:30') t(ts))",
+        )
+        .await;
+    insta::assert_snapshot!(actual.join("\n"), @r###"
+        +-----------+-----------+
+        | ts_diff   | date_diff |
+        +-----------+-----------+
+        |           |           |
+        | PT108000S | PT86400S  |
+        | PT237630S | PT259200S |
+        +-----------+-----------+
+    "###);
+
+    let actual = execution
+        .run_and_format(
+            "SELECT arrow_typeof(d), d FROM (SELECT lead_lag_diff(x) OVER (ORDER BY x) AS d \
+             FROM VALUES (CAST(1.5 AS DECIMAL(3, 1))), (CAST(99.9 AS DECIMAL(3, 1))) t(x))",
+        )
+        .await;
+    insta::assert_snapshot!(actual.join("\n"), @r###"
+        +------------------+------+
+        | arrow_typeof(d)  | d    |
+        +------------------+------+
+        | Decimal128(4, 1) |      |
+        | Decimal128(4, 1) | 98.4 |
+        +------------------+------+
+    "###);
+
+    let err = execution
+        .run("SELECT lead_lag_diff(x) OVER () FROM VALUES ('a') t(x)")
+        .await
+        .unwrap_err();
+    insta::assert_snapshot!(err, @r###"
+        Error during planning: Execution error: User-defined coercion failed with Plan("lead_lag_diff expects a numeric or temporal argument, got Utf8") No function matches the given name and argument types 'lead_lag_diff(Utf8)'. You might need to add explicit type casts.
+        	Candidate functions:
+        	lead_lag_diff(UserDefined)
+    "###);
+}
+
+#[tokio::test]
+async fn test_top_n_by() {
+    let mut execution = TestExecution::new().await.unwrap();
+
+    let actual = execution
+        .run_and_format(
+            "SELECT store, top_n_by(product, sales, 2) FROM VALUES \
+             ('s1', 'apple', 10), ('s1', 'pear', 30), ('s1', 'plum', 20), ('s1', 'fig', NULL), \
+             ('s2', 'kiwi', 5), ('s3', 'lime', NULL) t(store, product, sales) GROUP BY store ORDER BY store",
+        )
+        .await;
+    insta::assert_snapshot!(actual.join("\n"), @r###"
+        +-------+--------------------------------------+
+        | store | top_n_by(t.product,t.sales,Int64(2)) |
+        +-------+--------------------------------------+
+        | s1    | [pear, plum]                         |
+        | s2    | [kiwi]                               |
+        | s3    |                                      |
+        +-------+--------------------------------------+
+    "###);
+
+    let actual = execution
+        .run_and_format(
+            "SELECT top_n_by(x, d, 3) FROM VALUES (1, DATE '2024-03-01'), (2, DATE '2024-01-01'), (NULL, DATE '2024-05-01'), \
+             (4, DATE '2024-02-01') t(x, d)",
+        )
+        .await;
+    insta::assert_snapshot!(actual.join("\n"), @r###"
+        +----------------------------+
+        | top_n_by(t.x,t.d,Int64(3)) |
+        +----------------------------+
+        | [, 1, 4]                   |
+        +----------------------------+
+    "###);
+
+    let err = execution
+        .run("SELECT top_n_by(x, x, 0) FROM VALUES (1) t(x)")
+        .await
+        .unwrap_err();
+    insta::assert_snapshot!(err, @r###"
+        Error during planning: top_n_by expects a positive n, got 0
+    "###);
+}
+
+#[tokio::test]
+async fn test_cumulative_distinct_count() {
+    let mut execution = TestExecution::new().await.unwrap();
+
+    let actual = execution
+        .run_and_format(
+            "SELECT g, t, x, cumulative_distinct_count(x) OVER (PARTITION BY g ORDER BY t) AS running, \
+             cumulative_distinct_count(x) OVER (PARTITION BY g ORDER BY t, x ROWS BETWEEN UNBOUNDED PRECEDING AND CURRENT ROW) AS by_row \
+             FROM VALUES ('a', 1, 'u1'), ('a', 2, 'u2'), ('a', 2, 'u1'), ('a', 3, NULL), ('a', 4, 'u3'), ('b', 1, NULL), ('b', 2, 'u1') \
+             t(g, t, x) ORDER BY g, t, x",
+        )
+        .await;
+    insta::assert_snapshot!(actual.join("\n"), @r###"
+        +---+---+----+---------+--------+
+        | g | t | x  | running | by_row |
+        +---+---+----+---------+--------+
+        | a | 1 | u1 | 1       | 1      |
+        | a | 2 | u1 | 2       | 1      |
+        | a | 2 | u2 | 2       | 2      |
+        | a | 3 |    | 2       | 2      |
+        | a | 4 | u3 | 3       | 3      |
+        | b | 1 |    | 0       | 0      |
+        | b | 2 | u1 | 1       | 1      |
+        +---+---+----+---------+--------+
+    "###);
+
+    let actual = execution
+        .run_and_format(
+            "SELECT t, cumulative_distinct_count(x) OVER (ORDER BY t) FROM VALUES (1, 5), (2, 5), (3, 7), (4, 5) t(t, x) ORDER BY t",
+        )
+        .await;
+    insta::assert_snapshot!(actual.join("\n"), @r###"
+        +---+----------------------------------------------------------------------------------------------------------------+
+        | t | cumulative_distinct_count(t.x) ORDER BY [t.t ASC NULLS LAST] RANGE BETWEEN UNBOUNDED PRECEDING AND CURRENT ROW |
+        +---+----------------------------------------------------------------------------------------------------------------+
+        | 1 | 1                                                                                                              |
+        | 2 | 1                                                                                                              |
+        | 3 | 2                                                                                                              |
+        | 4 | 2                                                                                                              |
+        +---+----------------------------------------------------------------------------------------------------------------+
+    "###);
+
+    let err = execution
+        .run("SELECT cumulative_distinct_count(x) OVER (ORDER BY x ROWS BETWEEN 1 PRECEDING AND CURRENT ROW) FROM VALUES (1), (2), (3) t(x)")
+        .await
+        .unwrap_err();
+    insta::assert_snapshot!(err, @r###"
+        Execution error: cumulative_distinct_count expects frames starting at UNBOUNDED PRECEDING
+    "###);
+}
+
+#[tokio::test]
+async fn test_max_n_min_n() {
+    let mut execution = TestExecution::new().await.unwrap();
+
+    let actual = execution
+        .run_and_format(
+            "SELECT g, max_n(x, 2), min_n(x, 3) FROM VALUES ('a', 3), ('a', 1), ('a', NULL), ('a', 3), ('a', 7), ('b', 2), ('c', NULL) \
+             t(g, x) GROUP BY g ORDER BY g",
+        )
+        .await;
+    insta::assert_snapshot!(actual.join("\n"), @r###"
+        +---+---------------------+---------------------+
+        | g | max_n(t.x,Int64(2)) | min_n(t.x,Int64(3)) |
+        +---+---------------------+---------------------+
+        | a | [7, 3]              | [1, 3, 3]           |
+        | b | [2]                 | [2]                 |
+        | c |                     |                     |
+        +---+---------------------+---------------------+
+    "###);
+
+    let actual = execution
+        .run_and_format("SELECT max_n(s, 2), min_n(s, 2) FROM VALUES ('pear'), ('apple'), ('fig') t(s)")
+        .await;
+    insta::assert_snapshot!(actual.join("\n"), @r###"
+        +---------------------+---------------------+
+        | max_n(t.s,Int64(2)) | min_n(t.s,Int64(2)) |
+        +---------------------+---------------------+
+        | [pear, fig]         | [apple, fig]        |
+        +---------------------+---------------------+
+    "###);
+
+    let partition = |values: Vec<Option<f64>>| {
+        RecordBatch::try_from_iter(vec![("x", Arc::new(Float64Array::from(values)) as ArrayRef)]).unwrap()
+    };
+    let mut execution = TestExecution::new().await.unwrap().with_partitioned_table(
+        "tab",
+        vec![
+            partition(vec![Some(1.5), Some(9.0), None]),
+            partition(vec![Some(-2.0), Some(4.0)]),
+            partition(vec![Some(f64::NAN), Some(0.0)]),
+        ],
+    );
+    let actual = execution
+        .run_and_format("SELECT max_n(x, 3), min_n(x, 3) FROM tab")
+        .await;
+    insta::assert_snapshot!(actual.join("\n"), @r###"
+        +-----------------------+-----------------------+
+        | max_n(tab.x,Int64(3)) | min_n(tab.x,Int64(3)) |
+        +-----------------------+-----------------------+
+        | [NaN, 9.0, 4.0]       | [-2.0, 0.0, 1.5]      |
+        +-----------------------+-----------------------+
+    "###);
+}
+
+#[tokio::test]
+async fn test_rolling_mode() {
+    let mut execution = TestExecution::new().await.unwrap();
+
+    let actual = execution
+        .run_and_format(
+            "SELECT ts, error, rolling_mode(error) OVER (ORDER BY ts RANGE BETWEEN INTERVAL '5 minutes' PRECEDING AND CURRENT ROW) AS last_5m \
+             FROM VALUES (TIMESTAMP '2024-01-01 10:00:00', 'timeout'), (TIMESTAMP '2024-01-01 10:01:00', 'refused'), \
+             (TIMESTAMP '2024-01-01 10:02:00', 'timeout'), (TIMESTAMP '2024-01-01 10:06:30', 'refused'), \
+             (TIMESTAMP '2024-01-01 10:07:00', NULL), (TIMESTAMP '2024-01-01 10:20:00', 'reset') t(ts, error) ORDER BY ts",
+        )
+        .await;
+    insta::assert_snapshot!(actual.join("\n"), @r###"
+        +---------------------+---------+---------+
+        | ts                  | error   | last_5m |
+        +---------------------+---------+---------+
+        | 2024-01-01T10:00:00 | timeout | timeout |
+        | 2024-01-01T10:01:00 | refused | refused |
+        | 2024-01-01T10:02:00 | timeout | timeout |
+        | 2024-01-01T10:06:30 | refused | refused |
+        | 2024-01-01T10:07:00 |         | refused |
+        | 2024-01-01T10:20:00 | reset   | reset   |
+        +---------------------+---------+---------+
+    "###);
+
+    let actual = execution
+        .run_and_format(
+            "SELECT x, rolling_mode(x) OVER (ORDER BY t ROWS BETWEEN 2 PRECEDING AND CURRENT ROW) \
+             FROM VALUES (1, 3), (2, 3), (3, 1), (4, 1), (5, 2) t(t, x) ORDER BY t",
+        )
+        .await;
+    insta::assert_snapshot!(actual.join("\n"), @r###"
+        +---+------------------------------------------------------------------------------------------+
+        | x | rolling_mode(t.x) ORDER BY [t.t ASC NULLS LAST] ROWS BETWEEN 2 PRECEDING AND CURRENT ROW |
+        +---+------------------------------------------------------------------------------------------+
+        | 3 | 3                                                                                        |
+        | 3 | 3                                                                                        |
+        | 1 | 3                                                                                        |
+        | 1 | 1                                                                                        |
+        | 2 | 1                                                                                        |
+        +---+------------------------------------------------------------------------------------------+
+    "###);
+
+    let actual = execution
+        .run_and_format("SELECT rolling_mode(x) FROM VALUES (2), (1), (2), (NULL) t(x)")
+        .await;
+    insta::assert_snapshot!(actual.join("\n"), @r###"
+        +-------------------+
+        | rolling_mode(t.x) |
+        +-------------------+
+        | 2                 |
+        +-------------------+
+    "###);
+}
+
+#[tokio::test]
+async fn test_collect_set() {
+    let mut execution = TestExecution::new().await.unwrap();
+
+    let actual = execution
+        .run_and_format(
+            "SELECT g, collect_set(s), collect_set(s ORDER BY s), collect_set(s ORDER BY s DESC), collect_set(s, 2 ORDER BY s) \
+             FROM VALUES ('a', 'pear'), ('a', 'fig'), ('a', 'pear'), ('a', NULL), ('a', 'apple'), ('b', NULL) t(g, s) \
+             GROUP BY g ORDER BY g",
+        )
+        .await;
+    insta::assert_snapshot!(actual.join("\n"), @r###"
+        +---+--------------------+------------------------------------------------+--------------------------------------------------+---------------------------------------------------------+
+        | g | collect_set(t.s)   | collect_set(t.s) ORDER BY [t.s ASC NULLS LAST] | collect_set(t.s) ORDER BY [t.s DESC NULLS FIRST] | collect_set(t.s,Int64(2)) ORDER BY [t.s ASC NULLS LAST] |
+        +---+--------------------+------------------------------------------------+--------------------------------------------------+---------------------------------------------------------+
+        | a | [pear, fig, apple] | [apple, fig, pear]                             | [pear, fig, apple]                               | [apple, fig]                                            |
+        | b |                    |                                                |                                                  |                                                         |
+        +---+--------------------+------------------------------------------------+--------------------------------------------------+---------------------------------------------------------+
+    "###);
+
+    let partition = |values: Vec<Option<i64>>| {
+        RecordBatch::try_from_iter(vec![("x", Arc::new(Int64Array::from(values)) as ArrayRef)]).unwrap()
+    };
+    let mut execution = TestExecution::new().await.unwrap().with_partitioned_table(
+        "tab",
+        vec![
+            partition(vec![Some(5), Some(3), Some(5), None]),
+            partition(vec![Some(8), Some(1), Some(3)]),
+            partition(vec![Some(2), Some(9)]),
+        ],
+    );
+    let actual = execution
+        .run_and_format(
+            "SELECT collect_set(x ORDER BY x), collect_set(x, 3 ORDER BY x DESC), collect_set(x, NULL, 'desc') FROM tab",
+        )
+        .await;
+    insta::assert_snapshot!(actual.join("\n"), @r###"
+        +----------------------------------------------------+---------------------------------------------------------------+--------------------------------------+
+        | collect_set(tab.x) ORDER BY [tab.x ASC NULLS LAST] | collect_set(tab.x,Int64(3)) ORDER BY [tab.x DESC NULLS FIRST] | collect_set(tab.x,NULL,Utf8("desc")) |
+        +----------------------------------------------------+---------------------------------------------------------------+--------------------------------------+
+        | [1, 2, 3, 5, 8, 9]                                 | [9, 8, 5]                                                     | [9, 8, 5, 3, 2, 1]                   |
+        +----------------------------------------------------+---------------------------------------------------------------+--------------------------------------+
+    "###);
+
+    let err = execution
+        .run("SELECT collect_set(x ORDER BY x + 1) FROM tab")
+        .await
+        .unwrap_err();
+    insta::assert_snapshot!(err, @r###"
+        Optimizer rule 'simplify_expressions' failed
+        caused by
+        Error during planning: collect_set can only be ordered by its argument
+    "###);
+}
+
+#[tokio::test]
+async fn test_array_union_agg() {
+    let mut execution = TestExecution::new().await.unwrap();
+
+    let actual = execution
+        .run_and_format(
+            "SELECT g, array_union_agg(tags) \
+             FROM VALUES (1, ['rust', 'sql']), (1, NULL), (1, ['sql', NULL, 'arrow']), (1, arrow_cast([], 'List(Utf8)')), (2, NULL), (3, arrow_cast([], 'List(Utf8)')) t(g, tags) \
+             GROUP BY g ORDER BY g",
+        )
+        .await;
+    insta::assert_snapshot!(actual.join("\n"), @r###"
+        +---+-------------------------+
+        | g | array_union_agg(t.tags) |
+        +---+-------------------------+
+        | 1 | [rust, sql, arrow]      |
+        | 2 |                         |
+        | 3 |                         |
+        +---+-------------------------+
+    "###);
+
+    let partition = |values: Vec<Option<Vec<Option<i32>>>>| {
+        let lists = ListArray::from_iter_primitive::<Int32Type, _, _>(values);
+        RecordBatch::try_from_iter(vec![("x", Arc::new(lists) as ArrayRef)]).unwrap()
+    };
+    let mut execution = TestExecution::new().await.unwrap().with_partitioned_table(
+        "tab",
+        vec![
+            partition(vec![Some(vec![Some(3), Some(1)]), None, Some(vec![Some(3)])]),
+            partition(vec![Some(vec![Some(2), None, Some(1)])]),
+            partition(vec![Some(vec![Some(5)]), Some(vec![Some(2)])]),
+        ],
+    );
+    let actual = execution
+        .run_and_format("SELECT array_sort(array_union_agg(x)) FROM tab")
+        .await;
+    insta::assert_snapshot!(actual.join("\n"), @r###"
+        +------------------------------------+
+        | array_sort(array_union_agg(tab.x)) |
+        +------------------------------------+
+        | [1, 2, 3, 5]                       |
+        +------------------------------------+
+    "###);
+
+    let err = execution.run("SELECT array_union_agg(1)").await.unwrap_err();
+    insta::assert_snapshot!(err, @r###"
+        Error during planning: Execution error: User-defined coercion failed with Plan("array_union_agg expects a list, got Int64") No function matches the given name and argument types 'array_union_agg(Int64)'. You might need to add explicit type casts.
+        	Candidate functions:
+        	array_union_agg(UserDefined)
+    "###);
+}
+
+#[tokio::test]
+async fn test_array_intersect_agg() {
+    let mut execution = TestExecution::new().await.unwrap();
+
+    let actual = execution
+        .run_and_format(
+            "SELECT g, array_intersect_agg(tags) \
+             FROM VALUES (1, ['rust', 'sql', 'arrow']), (1, NULL), (1, ['arrow', 'sql', NULL, 'sql']), \
+                (2, ['rust']), (2, ['sql']), (3, NULL) t(g, tags) \
+             GROUP BY g ORDER BY g",
+        )
+        .await;
+    insta::assert_snapshot!(actual.join("\n"), @r###"
+        +---+-----------------------------+
+        | g | array_intersect_agg(t.tags) |
+        +---+-----------------------------+
+        | 1 | [sql, arrow]                |
+        | 2 | []                          |
+        | 3 |                             |
+        +---+-----------------------------+
+    "###);
+
+    let partition = |values: Vec<Option<Vec<Option<i32>>>>| {
+        let lists = ListArray::from_iter_primitive::<Int32Type, _, _>(values);
+        RecordBatch::try_from_iter(vec![("x", Arc::new(lists) as ArrayRef)]).unwrap()
+    };
+    let mut execution = TestExecution::new().await.unwrap().with_partitioned_table(
+        "tab",
+        vec![
+            partition(vec![Some(vec![Some(3), Some(1), Some(2)]), None]),
+            partition(vec![Some(vec![Some(2), Some(3), Some(5)])]),
+            partition(vec![
+                Some(vec![Some(4), Some(3), Some(2)]),
+                Some(vec![Some(2), Some(3)]),
+            ]),
+        ],
+    );
+    let actual = execution
+        .run_and_format("SELECT array_sort(array_intersect_agg(x)) FROM tab")
+        .await;
+    insta::assert_snapshot!(actual.join("\n"), @r###"
+        +----------------------------------------+
+        | array_sort(array_intersect_agg(tab.x)) |
+        +----------------------------------------+
+        | [2, 3]                                 |
+        +----------------------------------------+
+    "###);
+}
+
+#[tokio::test]
+async fn test_top_k_window() {
+    let mut execution = TestExecution::new().await.unwrap();
+
+    let actual = execution
+        .run_and_format(
+            "SELECT t, page, \
+                top_k_window(page, 2) OVER (ORDER BY t ROWS BETWEEN 3 PRECEDING AND CURRENT ROW) AS by_row, \
+                top_k_window(page, 2) OVER (ORDER BY t RANGE BETWEEN INTERVAL '5 minutes' PRECEDING AND CURRENT ROW) AS by_time \
+             FROM VALUES \
+                (TIMESTAMP '2024-01-01 00:00:00', 'home'), \
+                (TIMESTAMP '2024-01-01 00:01:00', 'docs'), \
+                (TIMESTAMP '2024-01-01 00:02:00', 'docs'), \
+                (TIMESTAMP '2024-01-01 00:03:00', NULL), \
+                (TIMESTAMP '2024-01-01 00:04:00', 'blog'), \
+                (TIMESTAMP '2024-01-01 00:08:00', 'home'), \
+                (TIMESTAMP '2024-01-01 00:09:00', 'blog'), \
+                (TIMESTAMP '2024-01-01 00:20:00', 'home') t(t, page) \
+             ORDER BY t",
+        )
+        .await;
+    insta::assert_snapshot!(actual.join("\n"), @r###"
+        +---------------------+------+--------------+--------------+
+        | t                   | page | by_row       | by_time      |
+        +---------------------+------+--------------+--------------+
+        | 2024-01-01T00:00:00 | home | [home]       | [home]       |
+        | 2024-01-01T00:01:00 | docs | [docs, home] | [docs, home] |
+        | 2024-01-01T00:02:00 | docs | [docs, home] | [docs, home] |
+        | 2024-01-01T00:03:00 |      | [docs, home] | [docs, home] |
+        | 2024-01-01T00:04:00 | blog | [docs, blog] | [docs, blog] |
+        | 2024-01-01T00:08:00 | home | [blog, docs] | [blog, home] |
+        | 2024-01-01T00:09:00 | blog | [blog, home] | [blog, home] |
+        | 2024-01-01T00:20:00 | home | [blog, home] | [home]       |
+        +---------------------+------+--------------+--------------+
+    "###);
+
+    let actual = execution
+        .run_and_format(
+            "SELECT g, x, top_k_window(x, 1) OVER (PARTITION BY g ORDER BY x ROWS BETWEEN 1 PRECEDING AND 1 FOLLOWING) AS top \
+             FROM VALUES (1, 3), (1, 1), (1, 1), (2, NULL), (2, 5) t(g, x) ORDER BY g, x",
+        )
+        .await;
+    insta::assert_snapshot!(actual.join("\n"), @r###"
+        +---+---+-----+
+        | g | x | top |
+        +---+---+-----+
+        | 1 | 1 | [1] |
+        | 1 | 1 | [1] |
+        | 1 | 3 | [1] |
+        | 2 | 5 | [5] |
+        | 2 |   | [5] |
+        +---+---+-----+
+    "###);
+
+    let err = execution
+        .run("SELECT top_k_window(x, 0) OVER (ORDER BY x) FROM VALUES (1), (2) t(x)")
+        .await
+        .unwrap_err();
+    insta::assert_snapshot!(err, @r###"
+        Execution error: top_k_window expects a positive constant k
+    "###);
+}
+
+#[tokio::test]
+async fn test_null_safe_comparison() {
+    let mut execution = TestExecution::new().await.unwrap();
+
+    let actual = execution
+        .run_and_format(
+            "SELECT a, b, equal_null(a, b), cmp_null_last(a, b) \
+             FROM VALUES (1, 1), (1, 2), (2, 1), (1, NULL), (NULL, 1), (NULL, NULL), (1, 1.5) t(a, b)",
+        )
+        .await;
+    insta::assert_snapshot!(actual.join("\n"), @r###"
+        +---+-----+---------------------+------------------------+
+        | a | b   | equal_null(t.a,t.b) | cmp_null_last(t.a,t.b) |
+        +---+-----+---------------------+------------------------+
+        | 1 | 1.0 | true                | 0                      |
+        | 1 | 2.0 | false               | -1                     |
+        | 2 | 1.0 | false               | 1                      |
+        | 1 |     | false               | -1                     |
+        |   | 1.0 | false               | 1                      |
+        |   |     | true                | 0                      |
+        | 1 | 1.5 | false               | -1                     |
+        +---+-----+---------------------+------------------------+
+    "###);
+
+    let actual = execution
+        .run_and_format(
+            "SELECT a, b, equal_null(a, b), cmp_null_last(a, b) \
+             FROM VALUES ([1, NULL], [1, NULL]), ([1, NULL], [1, 2]), ([1], [1, 2]), ([2], NULL) t(a, b)",
+        )
+        .await;
+    insta::assert_snapshot!(actual.join("\n"), @r###"
+        +-------+--------+---------------------+------------------------+
+        | a     | b      | equal_null(t.a,t.b) | cmp_null_last(t.a,t.b) |
+        +-------+--------+---------------------+------------------------+
+        | [1, ] | [1, ]  | true                | 0                      |
+        | [1, ] | [1, 2] | false               | 1                      |
+        | [1]   | [1, 2] | false               | -1                     |
+        | [2]   |        | false               | -1                     |
+        +-------+--------+---------------------+------------------------+
+    "###);
+
+    let actual = execution
+        .run_and_format(
+            "SELECT equal_null(named_struct('x', 1, 'y', CAST(NULL AS VARCHAR)), named_struct('x', 1, 'y', CAST(NULL AS VARCHAR))) AS eq, \
+                cmp_null_last(named_struct('x', 1, 'y', 'b'), named_struct('x', 1, 'y', CAST(NULL AS VARCHAR))) AS cmp, \
+                equal_null('NaN'::double, 'NaN'::double) AS nan, \
+                equal_null(NULL, NULL) AS nulls",
+        )
+        .await;
+    insta::assert_snapshot!(actual.join("\n"), @r###"
+        +------+-----+------+-------+
+        | eq   | cmp | nan  | nulls |
+        +------+-----+------+-------+
+        | true | -1  | true | true  |
+        +------+-----+------+-------+
+    "###);
+
+    let err = execution.run("SELECT equal_null(1, [1])").await.unwrap_err();
+    insta::assert_snapshot!(err, @r###"
+        Error during planning: Execution error: User-defined coercion failed with Plan("equal_null can't compare Int64 and List(Field { name: \"item\", data_type: Int64, nullable: true, dict_id: 0, dict_is_ordered: false, metadata: {} })") No function matches the given name and argument types 'equal_null(Int64, List(Field { name: "item", data_type: Int64, nullable: true, dict_id: 0, dict_is_ordered: false, metadata: {} }))'. You might need to add explicit type casts.
+        	Candidate functions:
+        	equal_null(UserDefined)
+    "###);
+}
+
+#[tokio::test]
+async fn test_map_agg() {
+    let mut execution = TestExecution::new().await.unwrap();
+
+    let actual = execution
+        .run_and_format(
+            "SELECT g, map_agg(k, v), map_agg(k, v, 'first') AS first, map_agg(k, v, 'last') AS last \
+             FROM VALUES (1, 'a', 1), (1, 'b', NULL), (1, NULL, 3), (1, 'a', 4), (2, NULL, 5) t(g, k, v) \
+             WHERE g = 2 OR k IS NULL OR v IS NULL OR v = 1 \
+             GROUP BY g ORDER BY g",
+        )
+        .await;
+    insta::assert_snapshot!(actual.join("\n"), @r###"
+        +---+------------------+-------------+-------------+
+        | g | map_agg(t.k,t.v) | first       | last        |
+        +---+------------------+-------------+-------------+
+        | 1 | {a: 1, b: }      | {a: 1, b: } | {a: 1, b: } |
+        | 2 |                  |             |             |
+        +---+------------------+-------------+-------------+
+    "###);
+
+    let actual = execution
+        .run_and_format(
+            "SELECT map_agg(k, v, 'first') AS first, map_agg(k, v, 'last') AS last, map_agg(k, v, 'last')['a'] AS a \
+             FROM VALUES ('a', 1), ('b', NULL), ('a', 4), ('c', 2), ('b', 5) t(k, v)",
+        )
+        .await;
+    insta::assert_snapshot!(actual.join("\n"), @r###"
+        +-------------------+--------------------+---+
+        | first             | last               | a |
+        +-------------------+--------------------+---+
+        | {a: 1, b: , c: 2} | {a: 4, b: 5, c: 2} | 4 |
+        +-------------------+--------------------+---+
+    "###);
+
+    let partition = |keys: Vec<i64>, values: Vec<&str>| {
+        RecordBatch::try_from_iter(vec![
+            ("k", Arc::new(Int64Array::from(keys)) as ArrayRef),
+            ("v", Arc::new(StringArray::from(values)) as ArrayRef),
+        ])
+        .unwrap()
+    };
+    let mut execution = TestExecution::new().await.unwrap().with_partitioned_table(
+        "tab",
+        vec![
+            partition(vec![3, 1], vec!["c", "a"]),
+            partition(vec![2], vec!["b"]),
+            partition(vec![4, 5], vec!["d", "e"]),
+        ],
+    );
+    let actual = execution
+        .run_and_format(
+            "SELECT array_sort(map_keys(map_agg(k, v))) AS keys, map_extract(map_agg(k, v), 4) AS v FROM tab",
+        )
+        .await;
+    insta::assert_snapshot!(actual.join("\n"), @r###"
+        +-----------------+-----+
+        | keys            | v   |
+        +-----------------+-----+
+        | [1, 2, 3, 4, 5] | [d] |
+        +-----------------+-----+
+    "###);
+
+    let err = execution.run("SELECT map_agg(k % 2, v) FROM tab").await.unwrap_err();
+    insta::assert_snapshot!(err, @r###"
+        Execution error: map_agg found the duplicate key 1
+    "###);
+}
+
+#[tokio::test]
+async fn test_map_union_agg() {
+    let mut execution = TestExecution::new().await.unwrap();
+
+    let actual = execution
+        .run_and_format(
+            "SELECT g, map_union_agg(m), map_union_sum(m), map_union_list(m), array_length(map_extract(map_union_list(m), 'd')[1]) AS d \
+             FROM VALUES (1, MAP {'a': 1, 'b': 2}), (1, NULL), (1, MAP {'b': 3, 'c': NULL}), (1, MAP {'c': 4}), \
+                (2, MAP {'d': NULL}), (3, NULL) t(g, m) \
+             GROUP BY g ORDER BY g",
+        )
+        .await;
+    insta::assert_snapshot!(actual.join("\n"), @r###"
+        +---+--------------------+--------------------+-------------------------------+---+
+        | g | map_union_agg(t.m) | map_union_sum(t.m) | map_union_list(t.m)           | d |
+        +---+--------------------+--------------------+-------------------------------+---+
+        | 1 | {a: 1, b: 3, c: 4} | {a: 1, b: 5, c: 4} | {a: [1], b: [2, 3], c: [, 4]} |   |
+        | 2 | {d: }              | {d: }              | {d: []}                       | 1 |
+        | 3 |                    |                    |                               |   |
+        +---+--------------------+--------------------+-------------------------------+---+
+    "###);
+
+    let partition = |rows: Vec<(&str, i32)>| {
+        let mut builder = MapBuilder::new(None, StringBuilder::new(), Int32Builder::new());
+        for (key, value) in rows {
+            builder.keys().append_value(key);
+            builder.values().append_value(value);
+            builder.append(true).unwrap();
+        }
+        RecordBatch::try_from_iter(vec![("m", Arc::new(builder.finish()) as ArrayRef)]).unwrap()
+    };
+    let mut execution = TestExecution::new().await.unwrap().with_partitioned_table(
+        "tab",
+        vec![
+            partition(vec![("a", 1), ("b", 2)]),
+            partition(vec![("a", 3)]),
+            partition(vec![("b", 4), ("a", 5)]),
+        ],
+    );
+    let actual = execution
+        .run_and_format(
+            "SELECT map_extract(map_union_sum(m), 'a') AS a, map_extract(map_union_sum(m), 'b') AS b, \
+                array_sort(map_extract(map_union_list(m), 'a')[1]) AS a_values \
+             FROM tab",
+        )
+        .await;
+    insta::assert_snapshot!(actual.join("\n"), @r###"
+        +-----+-----+-----------+
+        | a   | b   | a_values  |
+        +-----+-----+-----------+
+        | [9] | [6] | [1, 3, 5] |
+        +-----+-----+-----------+
+    "###);
+
+    let err = execution.run("SELECT map_union_sum(MAP {'a': 'x'})").await.unwrap_err();
+    insta::assert_snapshot!(err, @r###"
+        Error during planning: Execution error: User-defined coercion failed with Plan("map_union_sum expects numeric values, got Utf8") No function matches the given name and argument types 'map_union_sum(Map(Field { name: "entries", data_type: Struct([Field { name: "key", data_type: Utf8, nullable: false, dict_id: 0, dict_is_ordered: false, metadata: {} }, Field { name: "value", data_type: Utf8, nullable: true, dict_id: 0, dict_is_ordered: false, metadata: {} }]), nullable: false, dict_id: 0, dict_is_ordered: false, metadata: {} }, false))'. You might need to add explicit type casts.
+        	Candidate functions:
+        	map_union_sum(UserDefined)
+    "###);
+}
+
+#[tokio::test]
+async fn test_listagg() {
+    let mut execution = TestExecution::new().await.unwrap();
+
+    let actual = execution
+        .run_and_format(
+            "SELECT g, listagg(s ORDER BY s), listagg(s, ', ' ORDER BY i DESC), listagg(DISTINCT s, '|' ORDER BY s) \
+             FROM VALUES (1, 1, 'b'), (1, 2, 'a'), (1, 3, NULL), (1, 4, 'b'), (1, 5, 'c'), (2, 1, NULL) t(g, i, s) \
+             GROUP BY g ORDER BY g",
+        )
+        .await;
+    insta::assert_snapshot!(actual.join("\n"), @r###"
+        +---+--------------------------------------------+---------------------------------------------------------+---------------------------------------------------------------+
+        | g | listagg(t.s) ORDER BY [t.s ASC NULLS LAST] | listagg(t.s,Utf8(", ")) ORDER BY [t.i DESC NULLS FIRST] | listagg(DISTINCT t.s,Utf8("|")) ORDER BY [t.s ASC NULLS LAST] |
+        +---+--------------------------------------------+---------------------------------------------------------+---------------------------------------------------------------+
+        | 1 | abbc                                       | c, b, a, b                                              | a|b|c                                                         |
+        | 2 |                                            |                                                         |                                                               |
+        +---+--------------------------------------------+---------------------------------------------------------+---------------------------------------------------------------+
+    "###);
+
+    let actual = execution
+        .run_and_format(
+            "SELECT listagg(s, ',', 12, '...' ORDER BY s) AS truncated, \
+                listagg(s, ',', 12, '...', true ORDER BY s) AS with_count, \
+                listagg(s, ',', 2, '...' ORDER BY s) AS none_fit, \
+                listagg(s, ',', 100, '...' ORDER BY s) AS fits \
+             FROM VALUES ('alpha'), ('beta'), ('gamma'), ('delta') t(s)",
+        )
+        .await;
+    insta::assert_snapshot!(actual.join("\n"), @r###"
+        +-----------+--------------+----------+------------------------+
+        | truncated | with_count   | none_fit | fits                   |
+        +-----------+--------------+----------+------------------------+
+        | alpha,... | alpha,...(3) | ...      | alpha,beta,delta,gamma |
+        +-----------+--------------+----------+------------------------+
+    "###);
+
+    let partition = |values: Vec<(i64, &str)>| {
+        let (keys, values): (Vec<_>, Vec<_>) = values.into_iter().unzip();
+        RecordBatch::try_from_iter(vec![
+            ("k", Arc::new(Int64Array::from(keys)) as ArrayRef),
+            ("s", Arc::new(StringArray::from(values)) as ArrayRef),
+        ])
+        .unwrap()
+    };
+    let mut execution = TestExecution::new().await.unwrap().with_partitioned_table(
+        "tab",
+        vec![
+            partition(vec![(3, "c"), (1, "a")]),
+            partition(vec![(5, "e"), (2, "b")]),
+            partition(vec![(4, "d"), (1, "a")]),
+        ],
+    );
+    let actual = execution
+        .run_and_format("SELECT listagg(s, '-' ORDER BY k), listagg(DISTINCT s, '-' ORDER BY k DESC) FROM tab")
+        .await;
+    insta::assert_snapshot!(actual.join("\n"), @r###"
+        +----------------------------------------------------------+---------------------------------------------------------------------+
+        | listagg(tab.s,Utf8("-")) ORDER BY [tab.k ASC NULLS LAST] | listagg(DISTINCT tab.s,Utf8("-")) ORDER BY [tab.k DESC NULLS FIRST] |
+        +----------------------------------------------------------+---------------------------------------------------------------------+
+        | a-a-b-c-d-e                                              | e-d-c-b-a                                                           |
+        +----------------------------------------------------------+---------------------------------------------------------------------+
+    "###);
+
+    let err = execution
+        .run("SELECT listagg(s, ',', 5 ORDER BY k) FROM tab")
+        .await
+        .unwrap_err();
+    insta::assert_snapshot!(err, @r###"
+        Execution error: listagg result of 11 bytes exceeds the maximum length of 5
+    "###);
+}
+
+#[tokio::test]
+async fn test_group_concat() {
+    let mut execution = TestExecution::new().await.unwrap();
+
+    let actual = execution
+        .run_and_format(
+            "SELECT g, group_concat(x ORDER BY x), group_concat(DISTINCT x, '; ' ORDER BY x DESC), group_concat(d ORDER BY d) \
+             FROM VALUES (1, 3, DATE '2024-01-03'), (1, 1, NULL), (1, NULL, DATE '2024-01-02'), (1, 3, DATE '2024-01-01'), \
+                (2, NULL, NULL) t(g, x, d) \
+             GROUP BY g ORDER BY g",
+        )
+        .await;
+    insta::assert_snapshot!(actual.join("\n"), @r###"
+        +---+-------------------------------------------------+-----------------------------------------------------------------------+-------------------------------------------------+
+        | g | group_concat(t.x) ORDER BY [t.x ASC NULLS LAST] | group_concat(DISTINCT t.x,Utf8("; ")) ORDER BY [t.x DESC NULLS FIRST] | group_concat(t.d) ORDER BY [t.d ASC NULLS LAST] |
+        +---+-------------------------------------------------+-----------------------------------------------------------------------+-------------------------------------------------+
+        | 1 | 1,3,3                                           | 3; 1                                                                  | 2024-01-01,2024-01-02,2024-01-03                |
+        | 2 |                                                 |                                                                       |                                                 |
+        +---+-------------------------------------------------+-----------------------------------------------------------------------+-------------------------------------------------+
+    "###);
+
+    let actual = execution
+        .run_and_format(
+            "SELECT group_concat(b ORDER BY b), group_concat(f, '') FROM VALUES (true, 1.5), (false, NULL) t(b, f)",
+        )
+        .await;
+    insta::assert_snapshot!(actual.join("\n"), @r###"
+        +-------------------------------------------------+----------------------------+
+        | group_concat(t.b) ORDER BY [t.b ASC NULLS LAST] | group_concat(t.f,Utf8("")) |
+        +-------------------------------------------------+----------------------------+
+        | false,true                                      | 1.5                        |
+        +-------------------------------------------------+----------------------------+
+    "###);
+}
+
+#[tokio::test]
+async fn test_bitmap_agg() {
+    let mut execution = TestExecution::new().await.unwrap();
+
+    let actual = execution
+        .run_and_format(
+            "WITH segments AS (SELECT segment, bitmap_agg(user_id) AS users FROM VALUES \
+            ('a', 1), ('a', 2), ('a', 3), ('a', 3), ('a', NULL), ('b', 3), ('b', 4), ('b', 4294967295) AS tab(segment, user_id) \
+            GROUP BY segment), a AS (SELECT users FROM segments WHERE segment = 'a'), \
+            b AS (SELECT users FROM segments WHERE segment = 'b') \
+            SELECT rb_cardinality(a.users) AS a, rb_cardinality(rb_or(a.users, b.users)) AS a_or_b, \
+            rb_cardinality(rb_and(a.users, b.users)) AS a_and_b, rb_contains(b.users, 4294967295) AS b_contains_max, \
+            rb_contains(a.users, 4) AS a_contains_4, rb_cardinality(rb_or(a.users, NULL)) AS a_or_null FROM a, b",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +---+--------+---------+----------------+--------------+-----------+
+        - "| a | a_or_b | a_and_b | b_contains_max | a_contains_4 | a_or_null |"
+        - +---+--------+---------+----------------+--------------+-----------+
+        - "| 3 | 5      | 1       | true           | false        |           |"
+        - +---+--------+---------+----------------+--------------+-----------+
+    "###);
+
+    let actual = execution
+        .run_and_format("SELECT rb_cardinality(bitmap_agg(x)) AS users FROM VALUES (1) as tab(x) WHERE x > 1")
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +-------+
+        - "| users |"
+        - +-------+
+        - "| 0     |"
+        - +-------+
+    "###);
+
+    let error = execution
+        .run("SELECT bitmap_agg(x) FROM VALUES (-1) as tab(x)")
+        .await
+        .unwrap_err();
+    insta::assert_snapshot!(error, @r###"
+        Execution error: bitmap_agg expects values between 0 and 4294967295, got -1
+    "###);
+}
+
+#[tokio::test]
+async fn test_bitwise_agg() {
+    let mut execution = TestExecution::new().await.unwrap();
+
+    let actual = execution
+        .run_and_format(
+            "SELECT g, encode(bit_and_agg(mask), 'hex') AS all_flags, encode(bit_or_agg(mask), 'hex') AS any_flags, \
+            encode(bit_xor_agg(mask), 'hex') AS odd_flags, arrow_typeof(bit_or_agg(arrow_cast(mask, 'FixedSizeBinary(2)'))) AS fixed \
+            FROM VALUES (1, X'0cff'), (1, NULL), (1, X'0a0f'), (2, X'0001') AS tab(g, mask) GROUP BY g ORDER BY g",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +---+-----------+-----------+-----------+--------------------+
+        - "| g | all_flags | any_flags | odd_flags | fixed              |"
+        - +---+-----------+-----------+-----------+--------------------+
+        - "| 1 | 080f      | 0eff      | 06f0      | FixedSizeBinary(2) |"
+        - "| 2 | 0001      | 0001      | 0001      | FixedSizeBinary(2) |"
+        - +---+-----------+-----------+-----------+--------------------+
+    "###);
+
+    let error = execution
+        .run("SELECT bit_and_agg(mask) FROM VALUES (X'01'), (X'0102') AS tab(mask)")
+        .await
+        .unwrap_err();
+    insta::assert_snapshot!(error, @r###"
+        Execution error: bit_and_agg expects values of the same length, got 1 and 2 bytes
+    "###);
+}
+
+#[tokio::test]
+async fn test_counter_aggregates() {
+    let mut execution = TestExecution::new().await.unwrap().with_partitioned_table(
+        "tab",
+        vec![
+            RecordBatch::try_from_iter(vec![
+                ("host", Arc::new(StringArray::from(vec!["a", "a", "b"])) as ArrayRef),
+                ("ts", Arc::new(Int64Array::from(vec![60, 0, 0])) as ArrayRef),
+                (
+                    "requests",
+                    Arc::new(Int64Array::from(vec![Some(30), Some(100), None])) as ArrayRef,
+                ),
+            ])
+            .unwrap(),
+            RecordBatch::try_from_iter(vec![
+                ("host", Arc::new(StringArray::from(vec!["a", "b", "b"])) as ArrayRef),
+                ("ts", Arc::new(Int64Array::from(vec![30, 10, 10])) as ArrayRef),
+                (
+                    "requests",
+                    Arc::new(Int64Array::from(vec![Some(150), Some(5), Some(7)])) as ArrayRef,
+                ),
+            ])
+            .unwrap(),
+        ],
+    );
+
+    let actual = execution
+        .run_and_format(
+            "SELECT host, delta_sum(requests, to_timestamp(ts)) AS increase, \
+            counter_rate(requests, to_timestamp(ts)) AS rate FROM tab GROUP BY host ORDER BY host",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +------+----------+--------------------+
+        - "| host | increase | rate               |"
+        - +------+----------+--------------------+
+        - "| a    | 80.0     | 1.3333333333333333 |"
+        - "| b    | 2.0      |                    |"
+        - +------+----------+--------------------+
+    "###);
+}
+
+#[tokio::test]
+async fn test_promql_increase_and_rate() {
+    let mut execution = TestExecution::new().await.unwrap();
+
+    let actual = execution
+        .run_and_format(
+            "SELECT date_bin(INTERVAL '1 minute', ts) AS window, \
+            increase(requests, ts, INTERVAL '1 minute') AS increase, rate(requests, ts, 60) AS rate \
+            FROM (SELECT to_timestamp(t) AS ts, requests FROM VALUES (5, 10), (15, 20), (25, 30), (35, 5), \
+            (45, 15), (55, 25), (65, 40), (80, 50) AS tab(t, requests)) GROUP BY window ORDER BY window",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +---------------------+-------------------+--------------------+
+        - "| window              | increase          | rate               |"
+        - +---------------------+-------------------+--------------------+
+        - "| 1970-01-01T00:00:00 | 54.00000000000001 | 0.9                |"
+        - "| 1970-01-01T00:01:00 | 18.33333333333333 | 0.3055555555555555 |"
+        - +---------------------+-------------------+--------------------+
+    "###);
+
+    let error = execution
+        .run("SELECT increase(x, x, INTERVAL '1 month') FROM VALUES (1) AS tab(x)")
+        .await
+        .unwrap_err();
+    insta::assert_snapshot!(error, @r###"
+        Error during planning: increase expects a window in days, hours, minutes or seconds for timestamps and a number for integers, got IntervalMonthDayNano { months: 1, days: 0, nanoseconds: 0 }
+    "###);
+}
+
+#[tokio::test]
+async fn test_holt_winters() {
+    let mut execution = TestExecution::new().await.unwrap();
+
+    let actual = execution
+        .run_and_format(
+            "SELECT g, holt_winters(x, ts, 0.5, 0.5) AS smoothed, holt_winters(x, ts, 0.8, 0.2, 3) AS forecast \
+            FROM VALUES (1, 20.0, 3), (1, 10.0, 1), (1, 16.0, 2), (1, 21.0, 4), (1, NULL, 5), (2, 1.0, 1) \
+            AS tab(g, x, ts) GROUP BY g ORDER BY g",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +---+--------------------------------------------+----------------------------------------------------------------------------------+
+        - "| g | smoothed                                   | forecast                                                                         |"
+        - +---+--------------------------------------------+----------------------------------------------------------------------------------+
+        - "| 1 | {level: 23.75, trend: 4.125, forecast: []} | {level: 22.016, trend: 4.8672, forecast: [26.8832, 31.7504, 36.617599999999996]} |"
+        - "| 2 |                                            |                                                                                  |"
+        - +---+--------------------------------------------+----------------------------------------------------------------------------------+
+    "###);
+
+    let error = execution
+        .run("SELECT holt_winters(x, x, 1.5, 0.5) FROM VALUES (1) AS tab(x)")
+        .await
+        .unwrap_err();
+    insta::assert_snapshot!(error, @r###"
+        Error during planning: holt_winters expects alpha between 0 and 1, exclusive, got 1.5
+    "###);
+}
+
+#[tokio::test]
+async fn test_autocorrelation() {
+    let partition = |days: Vec<i64>, sales: Vec<Option<f64>>| {
+        RecordBatch::try_from_iter(vec![
+            ("day", Arc::new(Int64Array::from(days)) as ArrayRef),
+            ("sales", Arc::new(Float64Array::from(sales)) as ArrayRef),
+        ])
+        .unwrap()
+    };
+    let mut execution = TestExecution::new().await.unwrap().with_partitioned_table(
+        "tab",
+        vec![
+            partition(vec![1, 4, 5, 8], vec![Some(1.0), Some(-1.0), Some(1.0), None]),
+            partition(vec![3, 2, 6, 7], vec![Some(1.0), Some(-1.0), Some(-1.0), None]),
+        ],
+    );
+
+    let actual = execution
+        .run_and_format(
+            "SELECT autocorrelation(sales, 1 ORDER BY day) AS lag1, autocorrelation(sales, 2 ORDER BY day DESC) AS lag2, \
+            autocorrelation(sales, 6 ORDER BY day) AS lag6 FROM tab",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +---------------------+--------------------+------+
+        - "| lag1                | lag2               | lag6 |"
+        - +---------------------+--------------------+------+
+        - "| -0.8333333333333334 | 0.6666666666666666 |      |"
+        - +---------------------+--------------------+------+
+    "###);
+
+    let error = execution
+        .run("SELECT autocorrelation(sales, 1) FROM tab")
+        .await
+        .unwrap_err();
+    insta::assert_snapshot!(error, @r###"
+        Error during planning: autocorrelation requires an ORDER BY to order the series by
+    "###);
+}
+
+#[tokio::test]
+async fn test_lttb() {
+    let partition = |seconds: Vec<i64>, values: Vec<Option<f64>>| {
+        RecordBatch::try_from_iter(vec![
+            ("ts", Arc::new(TimestampSecondArray::from(seconds)) as ArrayRef),
+            ("value", Arc::new(Float64Array::from(values)) as ArrayRef),
+        ])
+        .unwrap()
+    };
+    let mut execution = TestExecution::new().await.unwrap().with_partitioned_table(
+        "tab",
+        vec![
+            partition(
+                vec![0, 4, 5, 8, 9],
+                vec![Some(0.0), Some(9.0), Some(0.0), Some(0.0), None],
+            ),
+            partition(
+                vec![3, 2, 6, 7, 1, 10],
+                vec![Some(0.0), Some(0.0), Some(0.0), Some(-5.0), Some(1.0), None],
+            ),
+        ],
+    );
+
+    let actual = execution
+        .run_and_format("SELECT lttb(ts, value, 5) AS five, array_length(lttb(ts, value, 20)) AS all_points FROM tab")
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------+------------+
+        - "| five                                                                                                                                                                                                 | all_points |"
+        - +------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------+------------+
+        - "| [{ts: 1970-01-01T00:00:00, value: 0.0}, {ts: 1970-01-01T00:00:02, value: 0.0}, {ts: 1970-01-01T00:00:04, value: 9.0}, {ts: 1970-01-01T00:00:07, value: -5.0}, {ts: 1970-01-01T00:00:08, value: 0.0}] | 9          |"
+        - +------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------+------------+
+    "###);
+
+    let error = execution.run("SELECT lttb(ts, value, 2) FROM tab").await.unwrap_err();
+    insta::assert_snapshot!(error, @r###"
+        Error during planning: lttb expects n_points of at least 3, got 2
+    "###);
+}
+
+#[tokio::test]
+async fn test_gap_fill() {
+    let partition = |seconds: Vec<i64>, values: Vec<Option<f64>>| {
+        RecordBatch::try_from_iter(vec![
+            ("ts", Arc::new(TimestampSecondArray::from(seconds)) as ArrayRef),
+            ("value", Arc::new(Float64Array::from(values)) as ArrayRef),
+        ])
+        .unwrap()
+    };
+    let mut execution = TestExecution::new().await.unwrap().with_partitioned_table(
+        "tab",
+        vec![
+            partition(vec![0, 30, 40], vec![Some(10.0), Some(7.0), None]),
+            partition(vec![20, 10], vec![None, Some(15.0)]),
+        ],
+    );
+
+    let actual = execution
+        .run_and_format(
+            "SELECT locf(value, ts) AS last, locf(value, ts, TIMESTAMP '1970-01-01T00:00:25') AS locf_25, \
+            interpolate_at(value, ts, TIMESTAMP '1970-01-01T00:00:25') AS at_25, \
+            interpolate_at(value, ts, TIMESTAMP '1970-01-01T00:00:10') AS at_10, \
+            interpolate_at(value, ts, TIMESTAMP '1970-01-01T00:00:35') AS at_35 FROM tab",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +------+---------+-------+-------+-------+
+        - "| last | locf_25 | at_25 | at_10 | at_35 |"
+        - +------+---------+-------+-------+-------+
+        - "| 7.0  | 15.0    | 9.0   | 15.0  |       |"
+        - +------+---------+-------+-------+-------+
+    "###);
+
+    let error = execution
+        .run("SELECT interpolate_at(value, ts, ts) FROM tab")
+        .await
+        .unwrap_err();
+    insta::assert_snapshot!(error, @r###"
+        This feature is not implemented: interpolate_at expects a literal target_ts, got: ts@0
+    "###);
+}
+
+#[tokio::test]
+async fn test_window_funnel() {
+    let partition = |users: Vec<&str>, seconds: Vec<i64>, events: Vec<&str>| {
+        RecordBatch::try_from_iter(vec![
+            ("user_id", Arc::new(StringArray::from(users)) as ArrayRef),
+            ("ts", Arc::new(TimestampSecondArray::from(seconds)) as ArrayRef),
+            ("event", Arc::new(StringArray::from(events)) as ArrayRef),
+        ])
+        .unwrap()
+    };
+    let mut execution = TestExecution::new().await.unwrap().with_partitioned_table(
+        "tab",
+        vec![
+            partition(
+                vec!["a", "a", "b", "c", "c"],
+                vec![0, 600, 0, 0, 60],
+                vec!["view", "cart", "view", "view", "cart"],
+            ),
+            partition(
+                vec!["a", "a", "b", "c"],
+                vec![300, 900, 7200, 120],
+                vec!["cart", "buy", "cart", "cart"],
+            ),
+        ],
+    );
+
+    let actual = execution
+        .run_and_format(
+            "SELECT user_id, \
+            window_funnel(INTERVAL '1 hour', ts, event = 'view', event = 'cart', event = 'buy') AS level, \
+            window_funnel(INTERVAL '1 hour', 'strict_deduplication', ts, event = 'view', event = 'cart', event = 'buy') \
+            AS dedup FROM tab GROUP BY user_id ORDER BY user_id",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +---------+-------+-------+
+        - "| user_id | level | dedup |"
+        - +---------+-------+-------+
+        - "| a       | 3     | 2     |"
+        - "| b       | 1     | 1     |"
+        - "| c       | 2     | 2     |"
+        - +---------+-------+-------+
+    "###);
+
+    let error = execution
+        .run("SELECT window_funnel(60, 'strict', ts, event = 'view') FROM tab")
+        .await
+        .unwrap_err();
+    insta::assert_snapshot!(error, @r###"
+        Error during planning: window_funnel expects a mode of 'strict_deduplication', 'strict_order' or 'strict_increase', got 'strict'
+    "###);
+}
+
+#[tokio::test]
+async fn test_sequence_match() {
+    let partition = |users: Vec<&str>, seconds: Vec<i64>, events: Vec<&str>| {
+        RecordBatch::try_from_iter(vec![
+            ("user_id", Arc::new(StringArray::from(users)) as ArrayRef),
+            ("ts", Arc::new(TimestampSecondArray::from(seconds)) as ArrayRef),
+            ("event", Arc::new(StringArray::from(events)) as ArrayRef),
+        ])
+        .unwrap()
+    };
+    let mut execution = TestExecution::new().await.unwrap().with_partitioned_table(
+        "tab",
+        vec![
+            partition(
+                vec!["a", "a", "b", "b", "a"],
+                vec![0, 4000, 0, 60, 5000],
+                vec!["view", "view", "buy", "view", "buy"],
+            ),
+            partition(
+                vec!["a", "a", "b", "b"],
+                vec![100, 200, 30, 120],
+                vec!["search", "buy", "refund", "view"],
+            ),
+        ],
+    );
+
+    let actual = execution
+        .run_and_format(
+            "SELECT user_id, \
+            sequence_match('(?1).*(?2)', ts, event = 'view', event = 'buy') AS bought, \
+            sequence_match('(?1)(?2)', ts, event = 'view', event = 'buy') AS directly, \
+            sequence_count('(?1).*(?t<=3600)(?2)', ts, event = 'view', event = 'buy') AS within_hour \
+            FROM tab GROUP BY user_id ORDER BY user_id",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +---------+--------+----------+-------------+
+        - "| user_id | bought | directly | within_hour |"
+        - +---------+--------+----------+-------------+
+        - "| a       | true   | true     | 2           |"
+        - "| b       | false  | false    | 0           |"
+        - +---------+--------+----------+-------------+
+    "###);
+
+    let error = execution
+        .run("SELECT sequence_count('(?1)(?3)', ts, event = 'view', event = 'buy') FROM tab")
+        .await
+        .unwrap_err();
+    insta::assert_snapshot!(error, @r###"
+        Error during planning: sequence_count expects a condition between (?1) and (?2), got '(?3)' at 4 in '(?1)(?3)'
+    "###);
+}
+
+#[tokio::test]
+async fn test_session_id() {
+    let mut execution = TestExecution::new().await.unwrap();
+
+    let actual = execution
+        .run_and_format(
+            "SELECT user_id, ts, session_id(ts, INTERVAL '30 minutes') OVER (PARTITION BY user_id ORDER BY ts) AS session, \
+            session_id(epoch, 1800) OVER (PARTITION BY user_id ORDER BY ts) AS epoch_session \
+            FROM (SELECT user_id, CAST(ts AS TIMESTAMP) AS ts, CAST(ts AS BIGINT) AS epoch \
+            FROM VALUES ('a', 0), ('a', 1200), ('a', 3000), ('a', 4801), ('b', 600), ('b', NULL), ('b', 60000) t(user_id, ts)) \
+            ORDER BY user_id, ts",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +---------+---------------------+---------+---------------+
+        - "| user_id | ts                  | session | epoch_session |"
+        - +---------+---------------------+---------+---------------+
+        - "| a       | 1970-01-01T00:00:00 | 1       | 1             |"
+        - "| a       | 1970-01-01T00:20:00 | 1       | 1             |"
+        - "| a       | 1970-01-01T00:50:00 | 1       | 1             |"
+        - "| a       | 1970-01-01T01:20:01 | 2       | 2             |"
+        - "| b       | 1970-01-01T00:10:00 | 1       | 1             |"
+        - "| b       | 1970-01-01T16:40:00 | 2       | 2             |"
+        - "| b       |                     |         |               |"
+        - +---------+---------------------+---------+---------------+
+    "###);
+
+    let error = execution
+        .run("SELECT session_id(ts, 'a') OVER (ORDER BY ts) FROM VALUES (1) t(ts)")
+        .await
+        .unwrap_err();
+    insta::assert_snapshot!(error, @r###"
+        Error during planning: Execution error: User-defined coercion failed with Plan("session_id expects an interval or numeric gap, got Utf8") No function matches the given name and argument types 'session_id(Int64, Utf8)'. You might need to add explicit type casts.
+        	Candidate functions:
+        	session_id(UserDefined)
+    "###);
+}
+
+#[tokio::test]
+async fn test_welch_t_test() {
+    let partition = |values: Vec<Option<f64>>, flags: Vec<Option<bool>>| {
+        RecordBatch::try_from_iter(vec![
+            ("value", Arc::new(Float64Array::from(values)) as ArrayRef),
+            ("treated", Arc::new(BooleanArray::from(flags)) as ArrayRef),
+        ])
+        .unwrap()
+    };
+    let mut execution = TestExecution::new().await.unwrap().with_partitioned_table(
+        "tab",
+        vec![
+            partition(
+                vec![Some(1.0), Some(4.0), Some(6.0), None],
+                vec![Some(false), Some(true), None, Some(true)],
+            ),
+            partition(
+                vec![Some(2.0), Some(3.0), Some(8.0), None],
+                vec![Some(false), Some(false), Some(true), None],
+            ),
+        ],
+    );
+
+    let actual = execution
+        .run_and_format(
+            "SELECT welch_t_test(value, treated) AS test, welch_t_test(value, treated AND value < 5) AS small \
+            FROM tab",
+        )
+        .await;
+
+    insta::assert_yaml_snapshot!(actual, @r###"
+        - +-----------------------------------------------------------------------------------+-------+
+        - "| test                                                                              | small |"
+        - +-----------------------------------------------------------------------------------+-------+
+        - "| {t_stat: 1.9215378456610457, p_value: 0.276520821060805, dof: 1.1695501730103803} |       |"
+        - +-----------------------------------------------------------------------------------+-------+
+    "###);
+}
+
+#[tokio::test]
+async fn test_cancelled_emission() {
+    let cancellation = CancellationToken::new();
+    let mut ctx = SessionContext::new();
+    register_extra_functions_with_options(
+        &mut ctx,
+        &RegistrationOptions::new().with_cancellation(cancellation.clone()),
+    )
+    .unwrap();
+    let query = "SELECT mode(x), value_counts(x) FROM VALUES (1), (2), (2) as tab(x)";
+
+    let results = ctx.sql(query).await.unwrap().collect().await.unwrap();
+    insta::assert_snapshot!(pretty_format_batches(&results).unwrap(), @r###"
+        +-------------+----------------------------------------------+
+        | mode(tab.x) | value_counts(tab.x)                          |
+        +-------------+----------------------------------------------+
+        | 2           | [{value: 2, count: 2}, {value: 1, count: 1}] |
+        +-------------+----------------------------------------------+
+    "###);
+
+    cancellation.cancel();
+    let error = ctx.sql(query).await.unwrap().collect().await.unwrap_err();
+    insta::assert_snapshot!(error, @r###"
+        Execution error: Aggregate emission was cancelled
+    "###);
+}
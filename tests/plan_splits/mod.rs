@@ -0,0 +1,212 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Runs aggregations through plans that split them into partial and final stages, to
+//! exercise the state, merge and emit paths of the accumulators end-to-end. Each query is
+//! compared with the result of a plan that aggregates a single partition in one stage.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use arrow::array::{ArrayRef, Float64Array, Int64Array, RecordBatch, StringArray};
+use datafusion::common::cast::{as_int64_array, as_list_array};
+use datafusion::execution::disk_manager::DiskManagerConfig;
+use datafusion::execution::runtime_env::RuntimeConfig;
+use datafusion::prelude::SessionConfig;
+
+use crate::utils::TestExecution;
+
+const ROWS: i64 = 20_000;
+const GROUPS: i64 = 500;
+const PARTITIONS: i64 = 4;
+
+/// Queries whose results don't depend on the order the rows are merged in: every group has
+/// a single most frequent value, the `max_by` and `min_by` keys are unique and moments are
+/// rounded
+const QUERIES: &[&str] = &[
+    "SELECT g, mode(x), mode(s), max_by(i, y), min_by(i, y) FROM tab GROUP BY g ORDER BY g",
+    "SELECT g, round(kurtosis_pop(f), 6), round(cv(f), 6), round(stderr(f), 6) FROM tab GROUP BY g ORDER BY g",
+    "SELECT mode(x), mode(s), max_by(i, y), min_by(i, y), round(kurtosis_pop(f), 6), round(cv(f), 6) FROM tab",
+];
+
+/// Builds `PARTITIONS` batches of `ROWS` rows in total, in `GROUPS` groups `g`
+fn batches() -> Vec<RecordBatch> {
+    (0..PARTITIONS)
+        .map(|partition| {
+            let i: Vec<i64> = (0..ROWS).filter(|i| i % PARTITIONS == partition).collect();
+            let g = i.iter().map(|i| i % GROUPS);
+            // a third of the rows of each group share the value `g % 10`, the others are unique
+            let x: Vec<Option<i64>> = i
+                .iter()
+                .map(|i| match i % 7 {
+                    6 => None,
+                    _ if i % 3 == 0 => Some(i % GROUPS % 10),
+                    _ => Some(i + 10),
+                })
+                .collect();
+            let s: Vec<Option<String>> = x.iter().map(|x| x.map(|x| format!("value-{x}"))).collect();
+            // 20011 is a prime larger than `ROWS`, so every row has a distinct `y`
+            let y = i.iter().map(|i| i * 7919 % 20_011);
+            let f = i.iter().map(|i| ((i * 31) % 97) as f64 + 1.0);
+            RecordBatch::try_from_iter(vec![
+                ("g", Arc::new(Int64Array::from_iter_values(g)) as ArrayRef),
+                ("i", Arc::new(Int64Array::from(i.clone())) as ArrayRef),
+                ("x", Arc::new(Int64Array::from(x)) as ArrayRef),
+                ("s", Arc::new(StringArray::from(s)) as ArrayRef),
+                ("y", Arc::new(Int64Array::from_iter_values(y)) as ArrayRef),
+                ("f", Arc::new(Float64Array::from_iter_values(f)) as ArrayRef),
+            ])
+            .unwrap()
+        })
+        .collect()
+}
+
+/// Runs `QUERIES` over a single partition aggregated in one stage
+async fn expected_results() -> Vec<Vec<String>> {
+    let config = SessionConfig::new().with_target_partitions(1);
+    let batch = arrow::compute::concat_batches(&batches()[0].schema(), &batches()).unwrap();
+    let mut execution = TestExecution::new_with_config(config, None)
+        .await
+        .unwrap()
+        .with_partitioned_table("tab", vec![batch]);
+    let mut results = vec![];
+    for query in QUERIES {
+        results.push(execution.run_and_format(query).await);
+    }
+    results
+}
+
+/// Runs `QUERIES` over `PARTITIONS` partitions and compares them with [`expected_results`]
+async fn assert_same_results(config: SessionConfig, memory_limit: Option<usize>) {
+    let mut execution = TestExecution::new_with_config(config, memory_limit)
+        .await
+        .unwrap()
+        .with_partitioned_table("tab", batches());
+    for (query, expected) in QUERIES.iter().zip(expected_results().await) {
+        assert_eq!(execution.run_and_format(query).await, expected, "{query}");
+    }
+}
+
+fn split_config() -> SessionConfig {
+    SessionConfig::new()
+        .with_target_partitions(PARTITIONS as usize)
+        .with_repartition_aggregations(true)
+}
+
+#[tokio::test]
+async fn test_plan_is_split() {
+    let mut execution = TestExecution::new_with_config(split_config(), None)
+        .await
+        .unwrap()
+        .with_partitioned_table("tab", batches());
+
+    let plan = execution
+        .run_and_format(&format!("EXPLAIN {}", QUERIES[0]))
+        .await
+        .join("\n");
+    assert!(plan.contains("AggregateExec: mode=Partial"), "{plan}");
+    assert!(plan.contains("AggregateExec: mode=FinalPartitioned"), "{plan}");
+}
+
+#[tokio::test]
+async fn test_partitioned() {
+    assert_same_results(split_config(), None).await;
+}
+
+#[tokio::test]
+async fn test_small_batches() {
+    // every partition emits many small batches of partial state to be merged
+    assert_same_results(split_config().with_batch_size(7), None).await;
+}
+
+#[tokio::test]
+async fn test_repartitioned_input() {
+    // round-robin repartitioning interleaves the rows of every partition
+    let config = split_config()
+        .with_batch_size(16)
+        .with_round_robin_repartition(true)
+        .with_target_partitions(7);
+    assert_same_results(config, None).await;
+}
+
+#[tokio::test]
+async fn test_memory_limited() {
+    // a single final stream over 1 MB of memory spills the sorted groups of the `mode` query to
+    // disk and merges them back, emitting the groups batch by batch; repartitioning isn't used
+    // as its buffers can't spill
+    const MEMORY_LIMIT: usize = 1024 * 1024;
+    let mut config = SessionConfig::new().with_target_partitions(1).with_batch_size(8);
+    // the default reservation for merging the spilled groups exceeds the whole pool
+    config.options_mut().execution.sort_spill_reservation_bytes = 16 * 1024;
+    assert_same_results(config.clone(), Some(MEMORY_LIMIT)).await;
+
+    // DataFusion doesn't report the spills of aggregates in their metrics, so check that the
+    // same query fails once it can't spill
+    let runtime_config = RuntimeConfig::new()
+        .with_memory_limit(MEMORY_LIMIT, 1.0)
+        .with_disk_manager(DiskManagerConfig::Disabled);
+    let mut execution = TestExecution::new_with_runtime(config, runtime_config)
+        .await
+        .unwrap()
+        .with_partitioned_table("tab", batches());
+    let err = execution.run(QUERIES[0]).await.unwrap_err().to_string();
+    assert!(err.contains("HashAggSpill"), "{err}");
+}
+
+#[tokio::test]
+async fn test_reservoir_sample_is_reproducible() {
+    // the partitions are aggregated by concurrent tasks, in whichever order they are scheduled
+    let mut execution = TestExecution::new_with_config(split_config(), None)
+        .await
+        .unwrap()
+        .with_partitioned_table("tab", batches());
+    let query = "SELECT g, reservoir_sample(i, 4, 7) FROM tab GROUP BY g ORDER BY g";
+    let first = execution.run_and_format(query).await;
+    for _ in 0..3 {
+        assert_eq!(execution.run_and_format(query).await, first);
+    }
+}
+
+#[tokio::test]
+async fn test_reservoir_sample_with_one_seed() {
+    // every group and partition samples with the same seed, so they must still draw different
+    // keys for the rows at the same positions: the `i / GROUPS`-th row of every group
+    let mut execution = TestExecution::new_with_config(split_config(), None)
+        .await
+        .unwrap()
+        .with_partitioned_table("tab", batches());
+    let results = execution
+        .run("SELECT g, reservoir_sample(i, 4, 7) FROM tab GROUP BY g")
+        .await
+        .unwrap();
+
+    let mut positions = HashSet::new();
+    for batch in &results {
+        let groups = as_int64_array(batch.column(0)).unwrap();
+        let samples = as_list_array(batch.column(1)).unwrap();
+        for (g, sample) in groups.values().iter().zip(samples.iter()) {
+            let sample = sample.unwrap();
+            let mut sample: Vec<i64> = as_int64_array(&sample).unwrap().values().to_vec();
+            assert_eq!(sample.len(), 4);
+            assert!(sample.iter().all(|i| i % GROUPS == *g), "{g}: {sample:?}");
+            sample.iter_mut().for_each(|i| *i /= GROUPS);
+            sample.sort_unstable();
+            positions.insert(sample);
+        }
+    }
+    assert!(positions.len() > GROUPS as usize * 9 / 10, "{}", positions.len());
+}
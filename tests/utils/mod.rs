@@ -15,13 +15,21 @@
 // specific language governing permissions and limitations
 // under the License.
 
+use std::sync::Arc;
+
 use arrow::record_batch::RecordBatch;
 use arrow::util::pretty::pretty_format_batches;
+use datafusion::datasource::MemTable;
 use datafusion::error::Result;
 use datafusion::execution::context::SessionContext;
-use datafusion::prelude::SessionConfig;
+use datafusion::execution::runtime_env::{RuntimeConfig, RuntimeEnv};
+use datafusion::logical_expr::AggregateUDF;
+use datafusion::prelude::{col, SessionConfig};
 use datafusion::sql::parser::DFParser;
-use datafusion_functions_extra::register_all_extra_functions;
+use datafusion_functions_extra::package::RegistrationOptions;
+use datafusion_functions_extra::{
+    register_extra_functions_with_options, register_extra_table_functions, unstable_extra_aggregate_functions,
+};
 use log::debug;
 
 pub struct TestExecution {
@@ -30,9 +38,26 @@ pub struct TestExecution {
 
 impl TestExecution {
     pub async fn new() -> Result<Self> {
-        let config = SessionConfig::new();
-        let mut ctx = SessionContext::new_with_config(config);
-        register_all_extra_functions(&mut ctx)?;
+        Self::new_with_config(SessionConfig::new(), None).await
+    }
+
+    /// Creates an execution using `config`, whose memory pool is limited to `memory_limit`
+    /// bytes if set
+    pub async fn new_with_config(config: SessionConfig, memory_limit: Option<usize>) -> Result<Self> {
+        let mut runtime_config = RuntimeConfig::new();
+        if let Some(memory_limit) = memory_limit {
+            runtime_config = runtime_config.with_memory_limit(memory_limit, 1.0);
+        }
+        Self::new_with_runtime(config, runtime_config).await
+    }
+
+    /// Creates an execution using `config` and the runtime built from `runtime_config`
+    pub async fn new_with_runtime(config: SessionConfig, runtime_config: RuntimeConfig) -> Result<Self> {
+        let runtime = Arc::new(RuntimeEnv::new(runtime_config)?);
+        let mut ctx = SessionContext::new_with_config_rt(config, runtime);
+        let options = RegistrationOptions::new().with_config(ctx.state().config_options());
+        register_extra_functions_with_options(&mut ctx, &options)?;
+        register_extra_table_functions(&ctx);
         Ok(Self { ctx })
     }
 
@@ -53,6 +78,44 @@ impl TestExecution {
         self
     }
 
+    /// Registers `udaf`, replacing any function with the same name
+    pub fn with_udaf(self, udaf: AggregateUDF) -> Self {
+        self.ctx.register_udaf(udaf);
+        self
+    }
+
+    /// Also registers the functions of [`unstable_extra_aggregate_functions`], which aren't
+    /// registered by default
+    pub fn with_unstable_functions(self) -> Self {
+        for udaf in unstable_extra_aggregate_functions() {
+            self.ctx.register_udaf(udaf.as_ref().clone());
+        }
+        self
+    }
+
+    /// Registers a table named `name` with one partition per batch
+    pub fn with_partitioned_table(self, name: &str, batches: Vec<RecordBatch>) -> Self {
+        let schema = batches[0].schema();
+        let partitions = batches.into_iter().map(|batch| vec![batch]).collect();
+        let table = MemTable::try_new(schema, partitions).expect("Error creating partitioned table");
+        self.ctx
+            .register_table(name, Arc::new(table))
+            .expect("Error registering partitioned table");
+        self
+    }
+
+    /// Registers a table named `name` whose batches are declared sorted by `sort_column`
+    pub fn with_sorted_table(self, name: &str, batches: Vec<RecordBatch>, sort_column: &str) -> Self {
+        let schema = batches[0].schema();
+        let table = MemTable::try_new(schema, vec![batches])
+            .expect("Error creating sorted table")
+            .with_sort_order(vec![vec![col(sort_column).sort(true, false)]]);
+        self.ctx
+            .register_table(name, Arc::new(table))
+            .expect("Error registering sorted table");
+        self
+    }
+
     pub async fn run(&mut self, sql: &str) -> Result<Vec<RecordBatch>> {
         debug!("Running query: {sql}");
         self.ctx.sql(sql).await?.collect().await